@@ -0,0 +1,22 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    #[cfg(feature = "grpc")]
+    compile_control_proto();
+}
+
+/// Generate the gRPC server and client for proto/control.proto
+///
+/// protox parses the proto in Rust, so building with `--features grpc` doesn't need protoc.
+#[cfg(feature = "grpc")]
+fn compile_control_proto() {
+    println!("cargo:rerun-if-changed=proto/control.proto");
+
+    let descriptors = protox::compile(["control.proto"], ["proto"])
+        .unwrap_or_else(|e| panic!("Failed to parse proto/control.proto: {}", e));
+    tonic_build::configure()
+        .build_server(true)
+        .build_client(true)
+        .compile_fds(descriptors)
+        .unwrap_or_else(|e| panic!("Failed to generate gRPC code: {}", e));
+}
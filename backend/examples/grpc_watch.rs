@@ -0,0 +1,104 @@
+//! Minimal client for the gRPC control service
+//!
+//! Start the server with `cargo run --features grpc -- --api --grpc-addr 127.0.0.1:50051`, then:
+//!
+//! ```text
+//! cargo run --features grpc --example grpc_watch -- http://127.0.0.1:50051 [start|dry-run]
+//! ```
+//!
+//! Without a command it prints the current status and follows progress events. With `start` or
+//! `dry-run` it starts a backup over the bidirectional Control stream and follows that run.
+
+pub mod proto {
+    tonic::include_proto!("rustyhashbackup.control.v1");
+}
+
+use proto::backup_control_client::BackupControlClient;
+use proto::{control_command, control_event, ControlCommand, DryRunMode, StartBackupRequest};
+use tokio_stream::StreamExt;
+
+#[rocket::main]
+async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let addr = args
+        .first()
+        .cloned()
+        .unwrap_or_else(|| "http://127.0.0.1:50051".to_string());
+    let command = args.get(1).map(String::as_str);
+
+    let mut client = BackupControlClient::connect(addr).await?;
+
+    let dry_run_mode = match command {
+        None => {
+            let status = client.get_status(proto::GetStatusRequest {}).await?;
+            println!("{:?}", status.into_inner());
+
+            let mut events = client
+                .watch_progress(proto::WatchProgressRequest {})
+                .await?
+                .into_inner();
+            while let Some(event) = events.message().await? {
+                print_progress(&event);
+            }
+            return Ok(());
+        }
+        Some("start") => DryRunMode::None,
+        Some("dry-run") => DryRunMode::Quick,
+        Some(other) => return Err(format!("Unknown command: {}", other).into()),
+    };
+
+    let start = ControlCommand {
+        command: Some(control_command::Command::Start(StartBackupRequest {
+            dry_run_mode: dry_run_mode.into(),
+            quiet: true,
+            source: None,
+            path: None,
+            idempotency_key: None,
+            note: None,
+        })),
+    };
+    // Keep the request stream open so the server keeps sending progress for the run
+    let commands = tokio_stream::once(start).chain(tokio_stream::pending());
+    let mut events = client.control(commands).await?.into_inner();
+
+    while let Some(event) = events.message().await? {
+        match event.event {
+            Some(control_event::Event::Result(result)) => {
+                println!("{}", result.message);
+                if !result.success {
+                    break;
+                }
+            }
+            Some(control_event::Event::Progress(progress)) => {
+                print_progress(&progress);
+                if matches!(
+                    progress.status(),
+                    proto::BackupStatus::Completed | proto::BackupStatus::Failed
+                ) {
+                    break;
+                }
+            }
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+fn print_progress(event: &proto::ProgressEvent) {
+    match (&event.progress, &event.message) {
+        (_, Some(message)) => println!("[{:?}] {}", event.status(), message),
+        (Some(progress), None) => println!(
+            "[{:?}] {} {}/{} ({:.1}%){}",
+            event.status(),
+            progress.phase_description,
+            progress.files_processed,
+            progress.total_files,
+            progress.percentage,
+            progress
+                .eta_seconds
+                .map(|eta| format!(", {}s left", eta))
+                .unwrap_or_default()
+        ),
+        (None, None) => println!("[{:?}]", event.status()),
+    }
+}
@@ -0,0 +1,118 @@
+use crate::models::error::Result;
+use crate::models::user::{Role, User};
+use crate::service::auth::{
+    session_user, sign_in, sign_in_required, sign_out, SESSION_LIFETIME_SECS,
+};
+use rocket::http::{Cookie, CookieJar, SameSite, Status};
+use rocket::request::{self, FromRequest, Request};
+
+/// Cookie holding the session token of a signed-in user
+pub const SESSION_COOKIE: &str = "rhb_session";
+
+/// Anyone allowed to read status, history, logs and the catalog
+///
+/// Holds the signed-in user, or `None` while no users are set up and nobody has to sign in.
+pub struct Viewer(pub Option<User>);
+
+/// Anyone allowed to change the configuration and start, stop or decide things
+pub struct Admin(pub Option<User>);
+
+impl Admin {
+    /// Name for the audit log: the signed-in user, or `fallback` while no users are set up
+    pub fn actor(&self, fallback: Option<String>) -> String {
+        match &self.0 {
+            Some(user) => user.name.clone(),
+            None => fallback.unwrap_or_else(|| "api".to_string()),
+        }
+    }
+}
+
+/// Whether `user` may change things; everyone may while no users are set up
+pub fn can_change(user: Option<&User>) -> bool {
+    user.is_none_or(|user| user.role == Role::Admin)
+}
+
+/// Check a user's password and keep their new session in a cookie
+pub fn start_session(cookies: &CookieJar<'_>, name: &str, password: &str) -> Result<Option<User>> {
+    let Some((user, token)) = sign_in(name, password)? else {
+        log::warn!("Refused a sign-in as '{}'", name);
+        return Ok(None);
+    };
+    log::info!("{} signed in", user.name);
+    cookies.add(
+        Cookie::build((SESSION_COOKIE, token))
+            .http_only(true)
+            .same_site(SameSite::Strict)
+            .max_age(rocket::time::Duration::seconds(SESSION_LIFETIME_SECS)),
+    );
+    Ok(Some(user))
+}
+
+/// End the request's session, if it has one, and drop its cookie
+pub fn end_session(cookies: &CookieJar<'_>) {
+    if let Some(cookie) = cookies.get(SESSION_COOKIE) {
+        if let Err(e) = sign_out(cookie.value()) {
+            log::warn!("Failed to end a session: {}", e);
+        }
+    }
+    cookies.remove(SESSION_COOKIE);
+}
+
+/// The user signed in with the request's session cookie; 401 without one once users exist
+fn signed_in(request: &Request<'_>) -> request::Outcome<Option<User>, ()> {
+    if !sign_in_required() {
+        return request::Outcome::Success(None);
+    }
+    let user = request
+        .cookies()
+        .get(SESSION_COOKIE)
+        .map(|cookie| session_user(cookie.value()));
+    match user {
+        Some(Ok(Some(user))) => request::Outcome::Success(Some(user)),
+        Some(Err(e)) => {
+            log::warn!("Failed to look up a session: {}", e);
+            request::Outcome::Error((Status::InternalServerError, ()))
+        }
+        _ => request::Outcome::Error((Status::Unauthorized, ())),
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Viewer {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        signed_in(request).map(Viewer)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        match signed_in(request) {
+            request::Outcome::Success(Some(user)) if user.role != Role::Admin => {
+                request::Outcome::Error((Status::Forbidden, ()))
+            }
+            outcome => outcome.map(Admin),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_actor_is_the_signed_in_user() {
+        let alice = Admin(Some(User {
+            name: "alice".to_string(),
+            role: Role::Admin,
+            created_at: 0,
+        }));
+        assert_eq!(alice.actor(Some("mallory".to_string())), "alice");
+        assert_eq!(Admin(None).actor(Some("bob".to_string())), "bob");
+        assert_eq!(Admin(None).actor(None), "api");
+    }
+}
@@ -1,14 +1,42 @@
-use crate::api_state::AppState;
+use crate::api_auth::{can_change, end_session, start_session, Admin, Viewer};
+use crate::api_state::{AppState, LaunchOutcome, TriggerOutcome};
 use crate::models::api::*;
+use crate::models::audit_event::AuditEvent;
+use crate::models::catalog_file::CatalogFile;
 use crate::models::config::Config;
+use crate::models::coverage::CoverageReport;
 use crate::models::dry_run_mode::DryRunMode;
+use crate::models::error::BackupError;
+use crate::models::job::{Job, JobDefinition};
+use crate::models::manifest::{HashManifest, ManifestComparison};
+use crate::models::orphan::{OrphanAction, OrphanCleanup, OrphanReport};
+use crate::models::post_process_result::{PostProcessResult, ProcessorStatus};
+use crate::models::prune_plan::{PlanStatus, PrunePlan};
+use crate::models::run_diff::RunDiff;
+use crate::models::run_report::FileStatus;
+use crate::models::run_selection::RunSelection;
+use crate::models::unclaimed_file::UnclaimedFile;
+use crate::repo::catalog;
 use crate::repo::sqlite;
-use rocket::http::Status;
+use crate::service::audit::record_audit_event;
+use crate::service::auth::sign_in_required;
+use crate::service::coverage::build_coverage_report;
+use crate::service::manifest::build_manifest;
+use crate::service::mass_change::describe_mass_change;
+use crate::service::orphans::find_orphans;
+use crate::service::regression;
+use crate::service::run_diff::diff_runs;
+use crate::service::workspace::workspace_size;
+use crate::utils::db_logger::subscribe_logs;
+use rocket::http::{CookieJar, Header, Status};
 use rocket::serde::json::Json;
 use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::error::RecvError;
 use rocket::tokio::time::{interval, Duration};
 use rocket::{
-    response::stream::{Event, EventStream},
+    http::ContentType,
+    request::{self, FromRequest, Request},
+    response::stream::{Event, EventStream, TextStream},
     State,
 };
 use rocket_dyn_templates::{context, Template};
@@ -16,28 +44,30 @@ use serde_json::json;
 
 /// GET /api/config - Get current configuration (JSON)
 #[get("/config", rank = 2)]
-pub fn get_config(state: &State<AppState>) -> Result<Json<ConfigResponse>, Status> {
+pub fn get_config(_admin: Admin, state: &State<AppState>) -> Result<Json<ConfigResponse>, Status> {
     let config_file_path = state.get_config_file_path();
-    match state.get_config() {
+    match state.get_base_config() {
         Some(config) => Ok(Json(ConfigResponse {
             success: true,
             message: "Configuration retrieved successfully".to_string(),
             config: Some(config),
             config_file_path,
+            issues: Vec::new(),
         })),
         None => Ok(Json(ConfigResponse {
             success: false,
             message: "No configuration set".to_string(),
             config: None,
             config_file_path,
+            issues: Vec::new(),
         })),
     }
 }
 
 /// GET /api/config/form - Get configuration form fields pre-populated (HTML)
 #[get("/config/form")]
-pub fn get_config_form(state: &State<AppState>) -> Template {
-    let config = state.get_config();
+pub fn get_config_form(_admin: Admin, state: &State<AppState>) -> Template {
+    let config = state.get_base_config();
 
     Template::render(
         "partials/config_form_fields",
@@ -50,6 +80,7 @@ pub fn get_config_form(state: &State<AppState>) -> Template {
 /// POST /api/config - Set configuration (JSON)
 #[post("/config", format = "json", data = "<config>")]
 pub fn set_config(
+    _admin: Admin,
     config: Json<Config>,
     state: &State<AppState>,
 ) -> Result<Json<ConfigResponse>, Status> {
@@ -60,32 +91,30 @@ pub fn set_config(
             message: format!("Invalid configuration: {}", e),
             config: None,
             config_file_path: state.get_config_file_path(),
+            issues: validation_errors(e),
         }));
     }
 
     // Reinitialize database if path changed
-    reinitialize_database(&config.0.database_file);
+    reinitialize_database(&config.0);
 
     state.set_config(config.0.clone());
 
     // Log configuration change
-    let _ = sqlite::insert_log_entry(
-        "INFO",
-        "Configuration updated via API",
-        Some("api_routes::set_config"),
-    );
+    log::info!("Configuration updated via API");
 
     Ok(Json(ConfigResponse {
         success: true,
         message: "Configuration set successfully".to_string(),
         config: Some(config.0),
         config_file_path: state.get_config_file_path(),
+        issues: Vec::new(),
     }))
 }
 
 /// POST /api/config/form - Set configuration (JSON, returns HTML)
 #[post("/config/form", format = "json", data = "<config>")]
-pub fn set_config_form(config: Json<Config>, state: &State<AppState>) -> Template {
+pub fn set_config_form(_admin: Admin, config: Json<Config>, state: &State<AppState>) -> Template {
     // Validate configuration
     if let Err(e) = crate::models::config_validator::validate_config(&config.0) {
         return Template::render(
@@ -99,16 +128,12 @@ pub fn set_config_form(config: Json<Config>, state: &State<AppState>) -> Templat
     }
 
     // Reinitialize database if path changed
-    reinitialize_database(&config.0.database_file);
+    reinitialize_database(&config.0);
 
     state.set_config(config.0.clone());
 
     // Log configuration change
-    let _ = sqlite::insert_log_entry(
-        "INFO",
-        "Configuration updated via form",
-        Some("api_routes::set_config_form"),
-    );
+    log::info!("Configuration updated via form");
 
     Template::render(
         "partials/config_form_response",
@@ -122,6 +147,7 @@ pub fn set_config_form(config: Json<Config>, state: &State<AppState>) -> Templat
 /// POST /api/config/save - Save configuration to file
 #[post("/config/save", format = "json", data = "<request>")]
 pub fn save_config_to_file(
+    _admin: Admin,
     request: Json<serde_json::Value>,
     state: &State<AppState>,
 ) -> Result<Json<serde_json::Value>, Status> {
@@ -166,7 +192,8 @@ pub fn save_config_to_file(
     if let Err(e) = crate::models::config_validator::validate_config(&config) {
         return Ok(Json(json!({
             "success": false,
-            "message": format!("Config validation failed: {}", e)
+            "message": format!("Config validation failed: {}", e),
+            "issues": validation_errors(e)
         })));
     }
 
@@ -209,15 +236,11 @@ pub fn save_config_to_file(
 
     // Reinitialize database with new config
     if let Some(config) = state.get_config() {
-        reinitialize_database(&config.database_file);
+        reinitialize_database(&config);
     }
 
     // Log the save
-    let _ = sqlite::insert_log_entry(
-        "INFO",
-        &format!("Configuration saved to file: {}", file_path),
-        Some("api_routes::save_config_to_file"),
-    );
+    log::info!("Configuration saved to file: {}", file_path);
 
     Ok(Json(json!({
         "success": true,
@@ -228,6 +251,7 @@ pub fn save_config_to_file(
 /// POST /api/config/load - Load configuration from file
 #[post("/config/load", format = "json", data = "<request>")]
 pub fn load_config_from_file(
+    _admin: Admin,
     request: Json<serde_json::Value>,
     state: &State<AppState>,
 ) -> Result<Json<serde_json::Value>, Status> {
@@ -251,20 +275,16 @@ pub fn load_config_from_file(
         Ok(()) => {
             // Reinitialize database with new config
             if let Some(config) = state.get_config() {
-                reinitialize_database(&config.database_file);
+                reinitialize_database(&config);
             }
 
             // Log the load
-            let _ = sqlite::insert_log_entry(
-                "INFO",
-                &format!("Configuration loaded from file: {}", file_path),
-                Some("api_routes::load_config_from_file"),
-            );
+            log::info!("Configuration loaded from file: {}", file_path);
 
             Ok(Json(json!({
                 "success": true,
                 "message": format!("Configuration loaded from {}", file_path),
-                "config": state.get_config(),
+                "config": state.get_base_config(),
                 "config_file_path": state.get_config_file_path()
             })))
         }
@@ -275,14 +295,59 @@ pub fn load_config_from_file(
     }
 }
 
+/// POST /api/config/reload - Re-read the current config file and apply it
+#[post("/config/reload")]
+pub fn reload_config(_admin: Admin, state: &State<AppState>) -> Json<serde_json::Value> {
+    let Some(file_path) = state.get_config_file_path() else {
+        return Json(json!({
+            "success": false,
+            "message": "No config file path set"
+        }));
+    };
+
+    if state.get_status() == BackupStatus::Running {
+        return Json(json!({
+            "success": false,
+            "message": "Cannot reload configuration while a backup is running"
+        }));
+    }
+
+    let current = state.get_base_config();
+    let result = match &current {
+        Some(config) => crate::service::reload::reload_config(&file_path, config),
+        None => crate::models::config::setup_config(file_path.clone()).inspect(|config| {
+            reinitialize_database(config);
+        }),
+    };
+
+    match result {
+        Ok(config) => {
+            state.set_config(config);
+
+            log::info!("Configuration reloaded from file: {}", file_path);
+
+            Json(json!({
+                "success": true,
+                "message": format!("Configuration reloaded from {}", file_path),
+                "config": state.get_base_config(),
+                "config_file_path": file_path
+            }))
+        }
+        Err(e) => Json(json!({
+            "success": false,
+            "message": format!("Failed to reload configuration: {}", e)
+        })),
+    }
+}
+
 /// Helper function to reinitialize database when config changes
-fn reinitialize_database(db_path: &str) {
+fn reinitialize_database(config: &Config) {
     use std::path::Path;
 
-    let db_file = if db_path.is_empty() {
+    let db_file = if config.database_file.is_empty() {
         ":memory:".to_string()
     } else {
-        db_path.to_string()
+        config.database_file.clone()
     };
 
     log::info!("Reinitializing database: {}", db_file);
@@ -312,6 +377,12 @@ fn reinitialize_database(db_path: &str) {
     // Setup database schema
     if let Err(e) = sqlite::setup_database() {
         log::error!("Failed to setup database schema: {}", e);
+    } else if let Err(e) =
+        sqlite::shard_catalog(config.catalog_sharding, &config.catalog_shard_roots())
+    {
+        log::error!("Failed to set up catalog shards: {}", e);
+    } else if let Err(e) = catalog::open_catalog(config.catalog_database_url.as_deref()) {
+        log::error!("Failed to open the catalog: {}", e);
     } else {
         log::info!("Database initialized successfully");
     }
@@ -319,53 +390,38 @@ fn reinitialize_database(db_path: &str) {
 
 /// GET /api/status - Get current backup status
 #[get("/status")]
-pub fn get_status(state: &State<AppState>) -> Json<StatusResponse> {
-    let status = state.get_status();
-    let progress = state.get_progress();
-    let current_run = state.get_current_run();
+pub fn get_status(_viewer: Viewer, state: &State<AppState>) -> Json<StatusResponse> {
+    Json(state.status_response())
+}
 
-    Json(StatusResponse {
-        status,
-        progress,
-        started_at: current_run.as_ref().map(|r| r.started_at.to_rfc3339()),
-        completed_at: current_run
-            .as_ref()
-            .and_then(|r| r.completed_at.map(|dt| dt.to_rfc3339())),
-        error: current_run.as_ref().and_then(|r| r.error.clone()),
-        dry_run_mode: current_run
-            .as_ref()
-            .map(|r| format!("{:?}", r.dry_run_mode)),
-    })
+/// Value of the optional `Idempotency-Key` request header
+pub struct IdempotencyKey(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IdempotencyKey {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let key = request
+            .headers()
+            .get_one("Idempotency-Key")
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(str::to_string);
+        request::Outcome::Success(IdempotencyKey(key))
+    }
 }
 
 /// POST /api/start - Start a backup
+///
+/// The idempotency key comes from the `Idempotency-Key` header or the `idempotency_key` field.
 #[post("/start", format = "json", data = "<request>")]
 pub fn start_backup(
+    _admin: Admin,
     request: Json<StartBackupRequest>,
+    idempotency_key: IdempotencyKey,
     state: &State<AppState>,
 ) -> Result<Json<StartBackupResponse>, Status> {
-    // Check if already running
-    let current_status = state.get_status();
-    if current_status == BackupStatus::Running {
-        return Ok(Json(StartBackupResponse {
-            success: false,
-            message: "A backup is already running".to_string(),
-            backup_id: None,
-        }));
-    }
-
-    // Check if configuration is set
-    let config = match state.get_config() {
-        Some(config) => config,
-        None => {
-            return Ok(Json(StartBackupResponse {
-                success: false,
-                message: "No configuration set. Please set configuration first.".to_string(),
-                backup_id: None,
-            }));
-        }
-    };
-
     // Determine dry run mode
     let dry_run_mode = if request.dry_run_full {
         DryRunMode::Full
@@ -375,87 +431,334 @@ pub fn start_backup(
         DryRunMode::None
     };
 
-    // Start the backup run
-    let backup_id = state.start_backup_run(dry_run_mode);
-    let backup_id_response = backup_id.clone();
-
-    // Clone necessary data for the async task
-    let state_inner = state.inner().clone();
-    let config_clone = config.clone();
-    let quiet = request.quiet;
-
-    // Spawn backup task
-    rocket::tokio::spawn(async move {
-        log::info!("Backup task started with ID: {}", backup_id);
-
-        let state_for_blocking = state_inner.clone();
-        let result = tokio::task::spawn_blocking(move || {
-            crate::run_backup(
-                &config_clone,
-                dry_run_mode,
-                quiet,
-                Some(&state_for_blocking),
-            )
-        })
-        .await;
+    let selection = RunSelection {
+        source: request.source.clone(),
+        path: request.path.clone(),
+        ..Default::default()
+    };
+    let idempotency_key = idempotency_key
+        .0
+        .or_else(|| request.idempotency_key.clone());
+
+    match state.launch_backup(
+        dry_run_mode,
+        request.quiet,
+        selection,
+        request.note.as_deref(),
+        idempotency_key.as_deref(),
+    ) {
+        Ok(LaunchOutcome::Started(backup_id)) => Ok(Json(StartBackupResponse {
+            success: true,
+            message: format!("Backup started with mode: {:?}", dry_run_mode),
+            backup_id: Some(backup_id),
+            status: Some(BackupStatus::Running),
+        })),
+        Ok(LaunchOutcome::Existing { backup_id, status }) => Ok(Json(StartBackupResponse {
+            success: true,
+            message: "A backup was already started with this idempotency key".to_string(),
+            backup_id: Some(backup_id),
+            status: Some(status),
+        })),
+        Err(message) => Ok(Json(StartBackupResponse {
+            success: false,
+            message,
+            backup_id: None,
+            status: None,
+        })),
+    }
+}
 
-        match result {
-            Ok(Ok(())) => {
-                state_inner.complete_backup_run(None);
-                state_inner.notify_message("Backup completed successfully".to_string());
-            }
-            Ok(Err(e)) => {
-                let error_msg = format!("Backup failed: {}", e);
-                state_inner.complete_backup_run(Some(error_msg.clone()));
-                state_inner.notify_message(error_msg);
-            }
-            Err(e) => {
-                let error_msg = format!("Backup task panicked: {}", e);
-                state_inner.complete_backup_run(Some(error_msg.clone()));
-                state_inner.notify_message(error_msg);
-            }
+/// Why a trigger didn't start a backup
+#[derive(Responder)]
+pub enum TriggerRejection {
+    /// No trigger has the token; no body
+    #[response(status = 404)]
+    UnknownToken(()),
+    /// Fired again within its interval, with how long to wait in `Retry-After` and the body
+    #[response(status = 429)]
+    TooSoon(Json<TriggerTooSoonResponse>, Header<'static>),
+}
+
+/// POST /api/trigger/<token> - Start the backup a configured trigger stands for
+///
+/// Unknown tokens get 404 and triggers fired again within their interval 429.
+#[post("/trigger/<token>")]
+pub fn trigger_backup(
+    token: &str,
+    idempotency_key: IdempotencyKey,
+    state: &State<AppState>,
+) -> Result<Json<StartBackupResponse>, TriggerRejection> {
+    match state.fire_trigger(token, idempotency_key.0.as_deref()) {
+        TriggerOutcome::UnknownToken => Err(TriggerRejection::UnknownToken(())),
+        TriggerOutcome::TooSoon { retry_after_secs } => Err(TriggerRejection::TooSoon(
+            Json(TriggerTooSoonResponse {
+                message: format!(
+                    "The trigger may fire again in {} second(s)",
+                    retry_after_secs
+                ),
+                retry_after_secs,
+            }),
+            Header::new("Retry-After", retry_after_secs.to_string()),
+        )),
+        TriggerOutcome::Launched(LaunchOutcome::Started(backup_id)) => {
+            Ok(Json(StartBackupResponse {
+                success: true,
+                message: "Backup triggered".to_string(),
+                backup_id: Some(backup_id),
+                status: Some(BackupStatus::Running),
+            }))
         }
-    });
+        TriggerOutcome::Launched(LaunchOutcome::Existing { backup_id, status }) => {
+            Ok(Json(StartBackupResponse {
+                success: true,
+                message: "A backup was already started with this idempotency key".to_string(),
+                backup_id: Some(backup_id),
+                status: Some(status),
+            }))
+        }
+        TriggerOutcome::Refused(message) => Ok(Json(StartBackupResponse {
+            success: false,
+            message,
+            backup_id: None,
+            status: None,
+        })),
+    }
+}
 
-    Ok(Json(StartBackupResponse {
-        success: true,
-        message: format!("Backup started with mode: {:?}", dry_run_mode),
-        backup_id: Some(backup_id_response),
+/// POST /api/login - Sign in and keep the session in a cookie
+#[post("/login", format = "json", data = "<credentials>")]
+pub fn login(
+    credentials: Json<LoginRequest>,
+    cookies: &CookieJar<'_>,
+) -> Result<Json<SessionResponse>, Status> {
+    let user = start_session(cookies, &credentials.name, &credentials.password)
+        .map_err(|_| Status::InternalServerError)?;
+    Ok(Json(SessionResponse {
+        success: user.is_some(),
+        message: match &user {
+            Some(user) => format!("Signed in as {}", user.name),
+            None => "Wrong user name or password".to_string(),
+        },
+        user,
+        sign_in_required: true,
     }))
 }
 
+/// POST /api/logout - End the session
+#[post("/logout")]
+pub fn logout(cookies: &CookieJar<'_>) -> Json<SessionResponse> {
+    end_session(cookies);
+    Json(SessionResponse {
+        success: true,
+        message: "Signed out".to_string(),
+        user: None,
+        sign_in_required: sign_in_required(),
+    })
+}
+
+/// GET /api/me - The signed-in user
+#[get("/me")]
+pub fn get_me(viewer: Viewer) -> Json<SessionResponse> {
+    Json(SessionResponse {
+        success: true,
+        message: match &viewer.0 {
+            Some(user) => format!("Signed in as {}", user.name),
+            None => "No users are set up, so nobody has to sign in".to_string(),
+        },
+        sign_in_required: viewer.0.is_some(),
+        user: viewer.0,
+    })
+}
+
 /// POST /api/stop - Stop the current backup
 #[post("/stop")]
-pub fn stop_backup(state: &State<AppState>) -> Json<StopBackupResponse> {
-    let current_status = state.get_status();
-
-    if current_status != BackupStatus::Running {
-        return Json(StopBackupResponse {
+pub fn stop_backup(_admin: Admin, state: &State<AppState>) -> Json<StopBackupResponse> {
+    match state.stop_backup() {
+        Ok(()) => Json(StopBackupResponse {
+            success: true,
+            message: "Stop signal sent. Backup will stop after current operation.".to_string(),
+        }),
+        Err(message) => Json(StopBackupResponse {
             success: false,
-            message: "No backup is currently running".to_string(),
-        });
+            message,
+        }),
     }
+}
+
+/// POST /api/pause - Hold the running backup between files
+#[post("/pause")]
+pub fn pause_backup(_admin: Admin, state: &State<AppState>) -> Json<PauseBackupResponse> {
+    Json(match state.pause_backup() {
+        Ok(()) => PauseBackupResponse {
+            success: true,
+            message: "Backup paused".to_string(),
+        },
+        Err(message) => PauseBackupResponse {
+            success: false,
+            message,
+        },
+    })
+}
 
-    state.request_stop();
+/// POST /api/resume - Continue a paused backup
+#[post("/resume")]
+pub fn resume_backup(_admin: Admin, state: &State<AppState>) -> Json<PauseBackupResponse> {
+    Json(match state.resume_backup() {
+        Ok(()) => PauseBackupResponse {
+            success: true,
+            message: "Backup resumed".to_string(),
+        },
+        Err(message) => PauseBackupResponse {
+            success: false,
+            message,
+        },
+    })
+}
 
-    Json(StopBackupResponse {
-        success: true,
-        message: "Stop signal sent. Backup will stop after current operation.".to_string(),
+/// POST /api/confirm-mass-change - Let a run held back by `mass_change_guard` copy its files
+#[post("/confirm-mass-change?<by>")]
+pub fn confirm_mass_change(
+    admin: Admin,
+    by: Option<String>,
+    state: &State<AppState>,
+) -> Json<PauseBackupResponse> {
+    Json(match state.confirm_mass_change() {
+        Ok(change) => {
+            let description = describe_mass_change(&change);
+            if let Err(e) =
+                record_audit_event("mass_change_confirmed", &admin.actor(by), description)
+            {
+                log::warn!("Failed to record mass change confirmation: {}", e);
+            }
+            PauseBackupResponse {
+                success: true,
+                message: "Mass change confirmed, backup resumed".to_string(),
+            }
+        }
+        Err(message) => PauseBackupResponse {
+            success: false,
+            message,
+        },
     })
 }
 
 /// GET /api/history - Get backup history
 #[get("/history")]
-pub fn get_history(state: &State<AppState>) -> Json<BackupHistoryResponse> {
+pub fn get_history(_viewer: Viewer, state: &State<AppState>) -> Json<BackupHistoryResponse> {
     let entries = state.get_history();
     let total = entries.len();
 
     Json(BackupHistoryResponse { entries, total })
 }
 
+/// Page size used when streaming run files
+const RUN_FILES_STREAM_PAGE: usize = 1000;
+
+/// Largest page the paginated run files endpoint returns
+const MAX_RUN_FILES_LIMIT: usize = 1000;
+
+/// GET /api/history/<id>/files - Paginated per-file outcomes of a run
+#[get("/history/<id>/files?<status>&<limit>&<offset>")]
+pub fn get_run_files(
+    _viewer: Viewer,
+    id: &str,
+    status: Option<&str>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> Result<Json<RunFilesResponse>, Status> {
+    let status = parse_file_status(status)?;
+    let limit = limit.unwrap_or(100).min(MAX_RUN_FILES_LIMIT);
+    let offset = offset.unwrap_or(0);
+
+    let total = sqlite::count_run_files(id, status).map_err(|_| Status::InternalServerError)?;
+    let entries = sqlite::select_run_files(id, status, limit, offset)
+        .map_err(|_| Status::InternalServerError)?;
+
+    Ok(Json(RunFilesResponse {
+        entries,
+        total,
+        limit,
+        offset,
+    }))
+}
+
+/// GET /api/history/<id>/files.ndjson - Stream every per-file outcome of a run as NDJSON
+#[get("/history/<id>/files.ndjson?<status>")]
+pub fn stream_run_files(
+    _viewer: Viewer,
+    id: String,
+    status: Option<&str>,
+) -> Result<(ContentType, TextStream![String]), Status> {
+    let status = parse_file_status(status)?;
+    let content_type = ContentType::new("application", "x-ndjson");
+
+    Ok((
+        content_type,
+        TextStream! {
+            let mut after_id = 0;
+            loop {
+                let page = match sqlite::select_run_files_after(
+                    &id,
+                    status,
+                    after_id,
+                    RUN_FILES_STREAM_PAGE,
+                ) {
+                    Ok(page) => page,
+                    Err(e) => {
+                        log::error!("Failed to stream files for run {}: {}", id, e);
+                        break;
+                    }
+                };
+
+                for (row_id, outcome) in &page {
+                    after_id = *row_id;
+                    if let Ok(line) = serde_json::to_string(outcome) {
+                        yield line + "\n";
+                    }
+                }
+
+                if page.len() < RUN_FILES_STREAM_PAGE {
+                    break;
+                }
+            }
+        },
+    ))
+}
+
+/// Most paths the run comparison endpoint lists per group
+const MAX_RUN_DIFF_LIMIT: usize = 10_000;
+
+/// GET /api/runs/<id>/diff/<other_id> - Files new, changed and deleted between two runs
+#[get("/runs/<id>/diff/<other_id>?<limit>")]
+pub fn get_run_diff(
+    _viewer: Viewer,
+    id: &str,
+    other_id: &str,
+    limit: Option<usize>,
+) -> Result<Json<RunDiff>, Status> {
+    if id == other_id {
+        return Err(Status::BadRequest);
+    }
+    match diff_runs(id, other_id, limit.unwrap_or(100).min(MAX_RUN_DIFF_LIMIT)) {
+        Ok(diff) => Ok(Json(diff)),
+        // Either run is unknown or has no file actions kept
+        Err(BackupError::RunDiff(_)) => Err(Status::NotFound),
+        Err(_) => Err(Status::InternalServerError),
+    }
+}
+
+fn parse_file_status(status: Option<&str>) -> Result<Option<FileStatus>, Status> {
+    match status {
+        Some(status) => FileStatus::parse(status)
+            .map(Some)
+            .ok_or(Status::BadRequest),
+        None => Ok(None),
+    }
+}
+
 /// GET /api/events - Server-Sent Events for real-time progress updates
 #[get("/events")]
-pub fn progress_events(state: &State<AppState>) -> EventStream![] {
+pub fn progress_events(_viewer: Viewer, state: &State<AppState>) -> EventStream![] {
     let mut receiver = state.subscribe_progress();
     let state_clone = state.inner().clone();
 
@@ -484,40 +787,87 @@ pub fn progress_events(state: &State<AppState>) -> EventStream![] {
     }
 }
 
-/// GET /api/validate - Validate current configuration
+/// GET /api/validate - Validate current configuration, reporting issues per field
 #[get("/validate")]
-pub fn validate_config_endpoint(state: &State<AppState>) -> Result<Json<ConfigResponse>, Status> {
+pub fn validate_config_endpoint(
+    _admin: Admin,
+    state: &State<AppState>,
+) -> Result<Json<ConfigResponse>, Status> {
     let config_file_path = state.get_config_file_path();
-    match state.get_config() {
-        Some(config) => match crate::models::config_validator::validate_config(&config) {
-            Ok(_) => Ok(Json(ConfigResponse {
-                success: true,
-                message: "Configuration is valid".to_string(),
-                config: Some(config),
-                config_file_path,
-            })),
-            Err(e) => Ok(Json(ConfigResponse {
-                success: false,
-                message: format!("Configuration validation failed: {}", e),
-                config: Some(config),
-                config_file_path,
-            })),
-        },
+    match state.get_base_config() {
+        Some(config) => Ok(Json(validation_response(config, config_file_path))),
         None => Ok(Json(ConfigResponse {
             success: false,
             message: "No configuration set".to_string(),
             config: None,
             config_file_path,
+            issues: Vec::new(),
         })),
     }
 }
 
+/// POST /api/validate - Validate a submitted configuration without applying it
+#[post("/validate", format = "json", data = "<config>")]
+pub fn validate_submitted_config(
+    _admin: Admin,
+    config: Json<Config>,
+    state: &State<AppState>,
+) -> Result<Json<ConfigResponse>, Status> {
+    Ok(Json(validation_response(
+        config.0,
+        state.get_config_file_path(),
+    )))
+}
+
+/// Build a validation result listing every error and warning for a configuration
+fn validation_response(config: Config, config_file_path: Option<String>) -> ConfigResponse {
+    let issues = crate::models::config_validator::check_config(&config);
+    let error_count = issues.iter().filter(|issue| issue.is_error()).count();
+
+    ConfigResponse {
+        success: error_count == 0,
+        message: if error_count == 0 {
+            "Configuration is valid".to_string()
+        } else {
+            format!("Configuration has {} error(s)", error_count)
+        },
+        config: Some(config),
+        config_file_path,
+        issues,
+    }
+}
+
+/// Per-field issues carried by a validation error, if any
+fn validation_errors(
+    error: crate::models::error::BackupError,
+) -> Vec<crate::models::validation_issue::ValidationIssue> {
+    match error {
+        crate::models::error::BackupError::ConfigValidation(issues) => issues,
+        _ => Vec::new(),
+    }
+}
+
 /// GET /api/health - Health check endpoint
 #[get("/health")]
 pub fn health_check() -> &'static str {
     "OK"
 }
 
+/// The OpenAPI document describing these routes, kept by hand next to `Cargo.toml`
+const OPENAPI_SPEC: &str = include_str!("../openapi.json");
+
+/// GET /api/openapi.json - OpenAPI 3 document of the API
+#[get("/openapi.json")]
+pub fn openapi_spec() -> (ContentType, &'static str) {
+    (ContentType::JSON, OPENAPI_SPEC)
+}
+
+/// GET /api/docs - Swagger UI for the OpenAPI document
+#[get("/docs")]
+pub fn api_docs() -> Template {
+    Template::render("api_docs", context! { title: "API Reference" })
+}
+
 /// Helper function to format timestamp as "X time ago"
 fn format_time_ago(timestamp: &str) -> String {
     use chrono::{DateTime, Utc};
@@ -542,7 +892,7 @@ fn format_time_ago(timestamp: &str) -> String {
 
 /// GET /api/dashboard/metrics - Get dashboard metrics
 #[get("/dashboard/metrics")]
-pub fn get_dashboard_metrics(state: &State<AppState>) -> Template {
+pub fn get_dashboard_metrics(_viewer: Viewer, state: &State<AppState>) -> Template {
     let status = state.get_status();
     let history = state.get_history();
     let config = state.get_config();
@@ -554,7 +904,7 @@ pub fn get_dashboard_metrics(state: &State<AppState>) -> Template {
             "status": format!("{:?}", entry.status),
             "color": match entry.status {
                 BackupStatus::Completed => "green",
-                BackupStatus::Failed => "red",
+                BackupStatus::Failed | BackupStatus::Interrupted => "red",
                 BackupStatus::Running => "blue",
                 _ => "gray",
             }
@@ -564,10 +914,15 @@ pub fn get_dashboard_metrics(state: &State<AppState>) -> Template {
     // Current backup status
     let current_status = json!({
         "value": format!("{:?}", status),
-        "subtitle": if status == BackupStatus::Running { "In progress" } else { "Idle" },
+        "subtitle": match status {
+            BackupStatus::Running => "In progress",
+            BackupStatus::Paused => "Paused",
+            _ => "Idle",
+        },
         "color": match status {
             BackupStatus::Running => "blue",
-            BackupStatus::Failed => "red",
+            BackupStatus::Paused => "yellow",
+            BackupStatus::Failed | BackupStatus::Interrupted => "red",
             BackupStatus::Completed => "green",
             _ => "gray",
         }
@@ -581,8 +936,8 @@ pub fn get_dashboard_metrics(state: &State<AppState>) -> Template {
     });
 
     // Query database for total files backed up
-    let total_source_files = sqlite::get_total_source_files().unwrap_or(0);
-    let total_source_size = sqlite::get_total_source_size().unwrap_or(0);
+    let total_source_files = catalog::get_total_source_files().unwrap_or(0);
+    let total_source_size = catalog::get_total_source_size().unwrap_or(0);
     let total_files = json!({
         "value": total_source_files.to_string(),
         "subtitle": sqlite::format_bytes(total_source_size)
@@ -605,6 +960,18 @@ pub fn get_dashboard_metrics(state: &State<AppState>) -> Template {
         "status": if dest_count > 0 { "Active" } else { "None configured" }
     });
 
+    // Shown only while the latest run was far slower or larger than the runs before it
+    let run_anomalies = sqlite::select_runs(1)
+        .ok()
+        .and_then(|runs| runs.into_iter().next())
+        .filter(|run| !run.anomalies.is_empty())
+        .map(|run| {
+            json!({
+                "count": run.anomalies.len().to_string(),
+                "detail": run.anomalies.iter().map(regression::describe).collect::<Vec<_>>().join("; "),
+            })
+        });
+
     Template::render(
         "partials/dashboard_metrics",
         context! {
@@ -614,30 +981,54 @@ pub fn get_dashboard_metrics(state: &State<AppState>) -> Template {
             total_files,
             source_directories,
             backup_destinations,
+            run_anomalies,
         },
     )
 }
 
 /// GET /api/progress - Get current backup progress
 #[get("/progress")]
-pub fn get_progress(state: &State<AppState>) -> Json<Option<BackupProgress>> {
+pub fn get_progress(_viewer: Viewer, state: &State<AppState>) -> Json<Option<BackupProgress>> {
     Json(state.get_progress())
 }
 
-/// GET /api/logs - Get all logs with optional filters
-#[get("/logs?<level>&<since>&<search>&<limit>&<offset>")]
+/// Largest list of files in progress returned
+const MAX_FILE_PROGRESS_LIMIT: usize = 100;
+
+/// GET /api/progress/files - The files being copied right now, with their own progress
+#[get("/progress/files?<limit>")]
+pub fn get_file_progress(
+    _viewer: Viewer,
+    limit: Option<usize>,
+    state: &State<AppState>,
+) -> Json<FileProgressResponse> {
+    let limit = limit.unwrap_or(20).min(MAX_FILE_PROGRESS_LIMIT);
+    Json(state.files_in_progress(limit))
+}
+
+/// GET /api/logs - Get all logs with optional filters, `run` limiting them to one backup run
+#[get("/logs?<level>&<since>&<search>&<run>&<limit>&<offset>")]
 pub fn get_logs(
+    _viewer: Viewer,
     level: Option<String>,
     since: Option<i64>,
     search: Option<String>,
+    run: Option<String>,
     limit: Option<usize>,
     offset: Option<usize>,
 ) -> Template {
     use chrono::DateTime;
 
     // Query database for logs with filters
-    let logs = sqlite::query_logs(level.as_deref(), since, search.as_deref(), limit, offset)
-        .unwrap_or_else(|_| vec![]);
+    let logs = sqlite::query_logs(
+        level.as_deref(),
+        since,
+        search.as_deref(),
+        run.as_deref(),
+        limit,
+        offset,
+    )
+    .unwrap_or_else(|_| vec![]);
 
     // Format logs for display
     let formatted_logs: Vec<serde_json::Value> = logs
@@ -669,13 +1060,50 @@ pub fn get_logs(
     )
 }
 
+/// GET /api/logs/stream - Server-Sent Events carrying log entries as they are written
+#[get("/logs/stream?<level>&<search>&<run>")]
+pub fn stream_logs(
+    _viewer: Viewer,
+    level: Option<String>,
+    search: Option<String>,
+    run: Option<String>,
+) -> EventStream![] {
+    let mut receiver = subscribe_logs();
+
+    EventStream! {
+        let mut interval = interval(Duration::from_secs(15));
+
+        loop {
+            select! {
+                log = receiver.recv() => {
+                    match log {
+                        Ok(log) => {
+                            if log.matches(level.as_deref(), search.as_deref(), run.as_deref()) {
+                                yield Event::json(&log).event("log");
+                            }
+                        }
+                        // Entries missed while the client fell behind are in the table
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => break,
+                    }
+                }
+                _ = interval.tick() => {
+                    // Send heartbeat to keep connection alive
+                    yield Event::data("heartbeat");
+                }
+            }
+        }
+    }
+}
+
 /// GET /api/logs/recent - Get recent logs (last 50)
 #[get("/logs/recent")]
-pub fn get_recent_logs() -> Template {
+pub fn get_recent_logs(_viewer: Viewer) -> Template {
     use chrono::DateTime;
 
     // Query database for recent logs (last 50)
-    let logs = sqlite::query_logs(None, None, None, Some(50), None).unwrap_or_else(|_| vec![]);
+    let logs =
+        sqlite::query_logs(None, None, None, None, Some(50), None).unwrap_or_else(|_| vec![]);
 
     // Format logs for display
     let formatted_logs: Vec<serde_json::Value> = logs
@@ -707,9 +1135,40 @@ pub fn get_recent_logs() -> Template {
     )
 }
 
+/// GET /api/logs/export.ndjson - Download the logs matching the filters as JSON lines, oldest first
+#[get("/logs/export.ndjson?<level>&<since>&<search>&<run>")]
+pub fn export_logs(
+    _viewer: Viewer,
+    level: Option<&str>,
+    since: Option<i64>,
+    search: Option<&str>,
+    run: Option<&str>,
+) -> Result<(ContentType, String), Status> {
+    let logs = sqlite::query_logs(level, since, search, run, None, None)
+        .map_err(|_| Status::InternalServerError)?;
+
+    let mut lines = String::new();
+    for log in logs.iter().rev() {
+        let context = log
+            .context
+            .as_deref()
+            .and_then(|context| serde_json::from_str::<serde_json::Value>(context).ok());
+        let line = json!({
+            "timestamp": log.timestamp,
+            "level": log.level,
+            "message": log.message,
+            "context": context,
+            "source": log.source,
+        });
+        lines.push_str(&line.to_string());
+        lines.push('\n');
+    }
+    Ok((ContentType::new("application", "x-ndjson"), lines))
+}
+
 /// POST /api/logs/clear - Clear log history
 #[post("/logs/clear")]
-pub fn clear_logs() -> Json<serde_json::Value> {
+pub fn clear_logs(_admin: Admin) -> Json<serde_json::Value> {
     match sqlite::delete_all_logs() {
         Ok(count) => Json(json!({
             "success": true,
@@ -722,27 +1181,43 @@ pub fn clear_logs() -> Json<serde_json::Value> {
     }
 }
 
+/// POST /api/logs/prune - Prune log history to the configured retention
+#[post("/logs/prune")]
+pub fn prune_logs(_admin: Admin, state: &State<AppState>) -> Json<serde_json::Value> {
+    match state.prune_logs() {
+        Ok(count) => Json(json!({
+            "success": true,
+            "message": format!("Pruned {} log entries", count),
+            "deleted": count
+        })),
+        Err(e) => Json(json!({
+            "success": false,
+            "message": format!("Failed to prune logs: {}", e)
+        })),
+    }
+}
+
 /// GET /api/logs/stats - Get log statistics by level
 #[get("/logs/stats")]
-pub fn get_log_stats() -> Template {
+pub fn get_log_stats(_viewer: Viewer) -> Template {
     // Query database for log counts by level
-    let error_count = sqlite::query_logs(Some("ERROR"), None, None, None, None)
+    let error_count = sqlite::query_logs(Some("ERROR"), None, None, None, None, None)
         .map(|logs| logs.len())
         .unwrap_or(0);
 
-    let warn_count = sqlite::query_logs(Some("WARN"), None, None, None, None)
+    let warn_count = sqlite::query_logs(Some("WARN"), None, None, None, None, None)
         .map(|logs| logs.len())
         .unwrap_or(0);
 
-    let info_count = sqlite::query_logs(Some("INFO"), None, None, None, None)
+    let info_count = sqlite::query_logs(Some("INFO"), None, None, None, None, None)
         .map(|logs| logs.len())
         .unwrap_or(0);
 
-    let debug_count = sqlite::query_logs(Some("DEBUG"), None, None, None, None)
+    let debug_count = sqlite::query_logs(Some("DEBUG"), None, None, None, None, None)
         .map(|logs| logs.len())
         .unwrap_or(0);
 
-    let trace_count = sqlite::query_logs(Some("TRACE"), None, None, None, None)
+    let trace_count = sqlite::query_logs(Some("TRACE"), None, None, None, None, None)
         .map(|logs| logs.len())
         .unwrap_or(0);
 
@@ -761,23 +1236,669 @@ pub fn get_log_stats() -> Template {
     )
 }
 
+/// GET /api/unclaimed - List catalogued unclaimed destination files
+#[get("/unclaimed?<destination>")]
+pub fn get_unclaimed_files(
+    _viewer: Viewer,
+    destination: Option<String>,
+) -> Result<Json<Vec<UnclaimedFile>>, Status> {
+    sqlite::select_unclaimed_files(destination.as_deref())
+        .map(Json)
+        .map_err(|_| Status::InternalServerError)
+}
+
+/// Longest audit log the API returns at once
+const MAX_AUDIT_EVENTS: usize = 1000;
+
+/// GET /api/prune/plans - Prune plans, newest first
+#[get("/prune/plans?<limit>")]
+pub fn get_prune_plans(
+    _viewer: Viewer,
+    limit: Option<usize>,
+) -> Result<Json<Vec<PrunePlan>>, Status> {
+    sqlite::select_prune_plans(limit.unwrap_or(50).min(MAX_AUDIT_EVENTS))
+        .map(Json)
+        .map_err(|_| Status::InternalServerError)
+}
+
+/// GET /api/prune/plans/<id> - One prune plan with the files it would move
+#[get("/prune/plans/<id>")]
+pub fn get_prune_plan(_viewer: Viewer, id: i64) -> Result<Json<PrunePlanDetail>, Status> {
+    let plan = sqlite::select_prune_plan(id)
+        .map_err(|_| Status::InternalServerError)?
+        .ok_or(Status::NotFound)?;
+    let files = sqlite::select_prune_plan_files(id).map_err(|_| Status::InternalServerError)?;
+    Ok(Json(PrunePlanDetail { plan, files }))
+}
+
+/// POST /api/prune/plans/<id>/approve - Move a pending plan's files that are still unclaimed
+#[post("/prune/plans/<id>/approve?<by>")]
+pub fn approve_prune_plan(
+    admin: Admin,
+    id: i64,
+    by: Option<String>,
+    state: &State<AppState>,
+) -> Result<Json<PruneDecisionResponse>, Status> {
+    let config = state.get_config().ok_or(Status::ServiceUnavailable)?;
+    if let Some(refusal) = refuse_decided_plan(id)? {
+        return Ok(Json(refusal));
+    }
+    if matches!(
+        state.get_status(),
+        BackupStatus::Running | BackupStatus::Paused | BackupStatus::Stopping
+    ) {
+        return Ok(Json(prune_failure(
+            "Cannot approve a prune plan while a backup is running".to_string(),
+        )));
+    }
+
+    let actor = admin.actor(by);
+    match crate::service::prune::approve_prune_plan(&config, id, &actor) {
+        Ok(Some(moved)) => Ok(Json(PruneDecisionResponse {
+            success: true,
+            message: format!("Plan {} approved, {} file(s) moved", id, moved.len()),
+            moved,
+        })),
+        Ok(None) => Ok(Json(prune_failure(format!(
+            "Plan {} is no longer pending",
+            id
+        )))),
+        Err(e) => Ok(Json(prune_failure(format!(
+            "Failed to approve plan {}: {}",
+            id, e
+        )))),
+    }
+}
+
+/// POST /api/prune/plans/<id>/reject - Drop a pending plan without moving anything
+#[post("/prune/plans/<id>/reject?<by>")]
+pub fn reject_prune_plan(
+    admin: Admin,
+    id: i64,
+    by: Option<String>,
+) -> Result<Json<PruneDecisionResponse>, Status> {
+    if let Some(refusal) = refuse_decided_plan(id)? {
+        return Ok(Json(refusal));
+    }
+
+    let actor = admin.actor(by);
+    match crate::service::prune::reject_prune_plan(id, &actor) {
+        Ok(true) => Ok(Json(PruneDecisionResponse {
+            success: true,
+            message: format!("Plan {} rejected", id),
+            moved: Vec::new(),
+        })),
+        Ok(false) => Ok(Json(prune_failure(format!(
+            "Plan {} is no longer pending",
+            id
+        )))),
+        Err(e) => Ok(Json(prune_failure(format!(
+            "Failed to reject plan {}: {}",
+            id, e
+        )))),
+    }
+}
+
+/// A failure response if the plan was already decided; 404 if there is no such plan
+fn refuse_decided_plan(id: i64) -> Result<Option<PruneDecisionResponse>, Status> {
+    let plan = sqlite::select_prune_plan(id)
+        .map_err(|_| Status::InternalServerError)?
+        .ok_or(Status::NotFound)?;
+    Ok((plan.status != PlanStatus::Pending)
+        .then(|| prune_failure(format!("Plan {} is already {}", id, plan.status.as_str()))))
+}
+
+fn prune_failure(message: String) -> PruneDecisionResponse {
+    PruneDecisionResponse {
+        success: false,
+        message,
+        moved: Vec::new(),
+    }
+}
+
+/// GET /api/audit - Recorded decisions, newest first
+#[get("/audit?<limit>")]
+pub fn get_audit_events(
+    _viewer: Viewer,
+    limit: Option<usize>,
+) -> Result<Json<Vec<AuditEvent>>, Status> {
+    sqlite::select_audit_events(limit.unwrap_or(100).min(MAX_AUDIT_EVENTS))
+        .map(Json)
+        .map_err(|_| Status::InternalServerError)
+}
+
+/// GET /api/post-processing - Stored post-processor verdicts, newest first
+#[get("/post-processing?<backup_path>&<status>&<limit>&<offset>")]
+pub fn get_post_process_results(
+    _viewer: Viewer,
+    backup_path: Option<String>,
+    status: Option<&str>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> Result<Json<Vec<PostProcessResult>>, Status> {
+    let status = match status {
+        Some(status) => Some(ProcessorStatus::parse(status).ok_or(Status::BadRequest)?),
+        None => None,
+    };
+
+    sqlite::select_post_process_results(
+        backup_path.as_deref(),
+        status,
+        limit.unwrap_or(100).min(MAX_RUN_FILES_LIMIT),
+        offset.unwrap_or(0),
+    )
+    .map(Json)
+    .map_err(|_| Status::InternalServerError)
+}
+
+/// Page size of the Files page
+const FILES_PAGE_SIZE: usize = 50;
+
+/// Catalogued source files whose path contains `search`, with each backup checked on disk
+fn catalog_page(
+    search: Option<&str>,
+    limit: usize,
+    offset: usize,
+) -> Result<FilesResponse, Status> {
+    let search = search.map(str::trim).filter(|search| !search.is_empty());
+    let total = catalog::count_sources_matching(search).map_err(|_| Status::InternalServerError)?;
+    let entries = catalog::select_sources_matching(search, limit, offset)
+        .map_err(|_| Status::InternalServerError)?
+        .into_iter()
+        .map(|source| {
+            let backups = catalog::select_backups_of_source(source.id)?;
+            Ok(CatalogFile::new(source, &backups))
+        })
+        .collect::<crate::models::error::Result<Vec<_>>>()
+        .map_err(|_| Status::InternalServerError)?;
+
+    Ok(FilesResponse {
+        entries,
+        total,
+        limit,
+        offset,
+    })
+}
+
+/// GET /api/files - Page through catalogued source files, searching by name or path
+#[get("/files?<search>&<limit>&<offset>")]
+pub fn get_files(
+    _viewer: Viewer,
+    search: Option<&str>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+) -> Result<Json<FilesResponse>, Status> {
+    catalog_page(
+        search,
+        limit.unwrap_or(100).min(MAX_RUN_FILES_LIMIT),
+        offset.unwrap_or(0),
+    )
+    .map(Json)
+}
+
+/// GET /api/files/<id> - One catalogued source file with its backups
+#[get("/files/<id>")]
+pub fn get_file(_viewer: Viewer, id: i32) -> Result<Json<CatalogFile>, Status> {
+    let source = catalog::select_source_by_id(id)
+        .map_err(|_| Status::InternalServerError)?
+        .ok_or(Status::NotFound)?;
+    let backups =
+        catalog::select_backups_of_source(source.id).map_err(|_| Status::InternalServerError)?;
+    Ok(Json(CatalogFile::new(source, &backups)))
+}
+
+/// GET /api/files/rows - One page of the Files page (HTML)
+#[get("/files/rows?<search>&<offset>")]
+pub fn get_file_rows(
+    _viewer: Viewer,
+    search: Option<&str>,
+    offset: Option<usize>,
+) -> Result<Template, Status> {
+    use chrono::DateTime;
+
+    let page = catalog_page(search, FILES_PAGE_SIZE, offset.unwrap_or(0))?;
+    let format_time = |timestamp: u64| {
+        DateTime::from_timestamp(timestamp as i64, 0)
+            .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|| "Unknown".to_string())
+    };
+
+    let files: Vec<serde_json::Value> = page
+        .entries
+        .iter()
+        .map(|file| {
+            let backups: Vec<serde_json::Value> = file
+                .backups
+                .iter()
+                .map(|backup| {
+                    json!({
+                        "path": backup.path,
+                        "status": backup.status,
+                        "formatted_time": format_time(backup.last_modified),
+                    })
+                })
+                .collect();
+            json!({
+                "id": file.id,
+                "path": std::path::Path::new(&file.file_path).join(&file.file_name).to_string_lossy(),
+                "hash": file.hash.get(..16).unwrap_or(&file.hash),
+                "size_formatted": sqlite::format_bytes(file.file_size),
+                "formatted_time": format_time(file.last_modified),
+                "backups": backups,
+            })
+        })
+        .collect();
+
+    let shown_to = page.offset + page.entries.len();
+    Ok(Template::render(
+        "partials/file_entries",
+        context! {
+            files,
+            total: page.total,
+            shown_from: if shown_to > page.offset { page.offset + 1 } else { 0 },
+            shown_to,
+            previous_offset: (page.offset > 0).then(|| page.offset.saturating_sub(FILES_PAGE_SIZE)),
+            next_offset: ((shown_to as u64) < page.total).then_some(shown_to),
+        },
+    ))
+}
+
+/// GET /api/stats/runs - Duration and throughput of recent runs, oldest first, for charting
+#[get("/stats/runs?<since>&<limit>")]
+pub fn get_run_stats(
+    _viewer: Viewer,
+    since: Option<i64>,
+    limit: Option<usize>,
+) -> Result<Json<RunStatsResponse>, Status> {
+    let runs = sqlite::select_timed_runs(since, limit.unwrap_or(100).min(MAX_RUN_FILES_LIMIT))
+        .map_err(|_| Status::InternalServerError)?;
+
+    let runs = runs
+        .iter()
+        .filter_map(|run| {
+            let timing = run.timing?;
+            Some(RunStats {
+                id: run.id.clone(),
+                started_at: run.started_at,
+                status: run.status.clone(),
+                dry_run: run.dry_run,
+                checkpoint: run.checkpoint,
+                duration_secs: timing.duration_ms as f64 / 1000.0,
+                files_processed: timing.files_processed,
+                bytes_copied: run.bytes_copied,
+                mib_per_sec: run.mib_per_sec().unwrap_or_default(),
+                files_per_sec: run.files_per_sec().unwrap_or_default(),
+                hash_mib_per_sec: run.hash_mib_per_sec(),
+                anomalies: run.anomalies.clone(),
+            })
+        })
+        .collect();
+    Ok(Json(RunStatsResponse { runs }))
+}
+
+/// GET /api/coverage - Source files without a valid backup in each destination they are routed to
+#[get("/coverage?<destination>")]
+pub fn get_coverage(
+    _viewer: Viewer,
+    destination: Option<String>,
+    state: &State<AppState>,
+) -> Result<Json<CoverageReport>, Status> {
+    let config = configured_destination(state, destination.as_deref())?;
+    build_coverage_report(&config, destination.as_deref())
+        .map(Json)
+        .map_err(|_| Status::InternalServerError)
+}
+
+/// GET /api/orphans - Destination files that no backup record points at
+#[get("/orphans?<destination>")]
+pub fn get_orphans(
+    _viewer: Viewer,
+    destination: Option<String>,
+    state: &State<AppState>,
+) -> Result<Json<OrphanReport>, Status> {
+    let config = configured_destination(state, destination.as_deref())?;
+    find_orphans(&config, destination.as_deref())
+        .map(Json)
+        .map_err(|_| Status::InternalServerError)
+}
+
+/// POST /api/orphans/clean - Quarantine or delete the orphans found now
+#[post("/orphans/clean?<action>&<destination>&<by>")]
+pub fn clean_orphans(
+    admin: Admin,
+    action: &str,
+    destination: Option<String>,
+    by: Option<String>,
+    state: &State<AppState>,
+) -> Result<Json<OrphanCleanup>, Status> {
+    let action = OrphanAction::parse(action).ok_or(Status::BadRequest)?;
+    let config = configured_destination(state, destination.as_deref())?;
+    // Files being copied have no record yet, so a running backup would look like orphans
+    if matches!(
+        state.get_status(),
+        BackupStatus::Running | BackupStatus::Paused | BackupStatus::Stopping
+    ) {
+        return Err(Status::Conflict);
+    }
+
+    let actor = admin.actor(by);
+    crate::service::orphans::clean_orphans(&config, destination.as_deref(), action, &actor)
+        .map(Json)
+        .map_err(|_| Status::InternalServerError)
+}
+
+/// The loaded config, after checking `destination` names one of its destinations
+fn configured_destination(
+    state: &State<AppState>,
+    destination: Option<&str>,
+) -> Result<Config, Status> {
+    let config = state.get_config().ok_or(Status::ServiceUnavailable)?;
+    if let Some(destination) = destination {
+        if !config
+            .backup_destinations
+            .iter()
+            .any(|d| d.matches(destination))
+        {
+            return Err(Status::NotFound);
+        }
+    }
+    Ok(config)
+}
+
+/// GET /api/manifest - Hash manifest of every backed-up source file, for comparing instances
+#[get("/manifest")]
+pub fn get_manifest(
+    _viewer: Viewer,
+    state: &State<AppState>,
+) -> Result<Json<HashManifest>, Status> {
+    let config = state.get_config().ok_or(Status::ServiceUnavailable)?;
+    build_manifest(&config)
+        .map(Json)
+        .map_err(|_| Status::InternalServerError)
+}
+
+/// POST /api/manifest/compare - Compare this instance's manifest with a peer's
+#[post("/manifest/compare", format = "json", data = "<peer>")]
+pub fn compare_manifest(
+    _viewer: Viewer,
+    peer: Json<HashManifest>,
+    state: &State<AppState>,
+) -> Result<Json<ManifestComparison>, Status> {
+    let config = state.get_config().ok_or(Status::ServiceUnavailable)?;
+    let manifest = build_manifest(&config).map_err(|_| Status::InternalServerError)?;
+    Ok(Json(manifest.compare(&peer)))
+}
+
+/// GET /api/schedule - The configuration's schedule and its next run
+#[get("/schedule")]
+pub fn get_schedule(_viewer: Viewer, state: &State<AppState>) -> Json<ScheduleResponse> {
+    Json(state.schedule_response())
+}
+
+/// POST /api/schedule/enable - Let the configuration's schedule start backups again
+#[post("/schedule/enable")]
+pub fn enable_schedule(_admin: Admin, state: &State<AppState>) -> Json<ScheduleResponse> {
+    log::info!("Schedule enabled via API");
+    Json(state.set_schedule_enabled(true))
+}
+
+/// POST /api/schedule/disable - Hold the configuration's schedule; manual runs still start
+#[post("/schedule/disable")]
+pub fn disable_schedule(_admin: Admin, state: &State<AppState>) -> Json<ScheduleResponse> {
+    log::info!("Schedule disabled via API");
+    Json(state.set_schedule_enabled(false))
+}
+
+fn profiles_response(state: &AppState, success: bool, message: String) -> ProfilesResponse {
+    ProfilesResponse {
+        success,
+        message,
+        active: state.get_profile(),
+        profiles: state
+            .get_base_config()
+            .map(|config| config.profiles.into_keys().collect())
+            .unwrap_or_default(),
+    }
+}
+
+/// GET /api/profiles - The configuration's profiles and the one in use
+#[get("/profiles")]
+pub fn get_profiles(_viewer: Viewer, state: &State<AppState>) -> Json<ProfilesResponse> {
+    let message = match state.get_profile() {
+        Some(name) => format!("Using profile {}", name),
+        None => "Using the configuration without a profile".to_string(),
+    };
+    Json(profiles_response(state, true, message))
+}
+
+/// POST /api/profiles/select - Use a profile's settings until the server restarts
+#[post("/profiles/select", format = "json", data = "<request>")]
+pub fn select_profile(
+    admin: Admin,
+    request: Json<SelectProfileRequest>,
+    state: &State<AppState>,
+) -> Json<ProfilesResponse> {
+    let name = request.profile.as_deref().filter(|name| !name.is_empty());
+    if let Err(e) = state.select_profile(name) {
+        return Json(profiles_response(state, false, e));
+    }
+    let actor = admin.actor(None);
+    let detail = name.unwrap_or("none").to_string();
+    if let Err(e) = crate::service::audit::record_audit_event("profile_selected", &actor, detail) {
+        log::warn!("Failed to record the profile selection: {}", e);
+    }
+    let message = match name {
+        Some(name) => format!("Using profile {}", name),
+        None => "Using the configuration without a profile".to_string(),
+    };
+    Json(profiles_response(state, true, message))
+}
+
+/// GET /api/profiles/options - Profile picker for the quick actions panel
+#[get("/profiles/options")]
+pub fn get_profile_options(viewer: Viewer, state: &State<AppState>) -> Template {
+    let profiles = profiles_response(state, true, String::new());
+    Template::render(
+        "partials/profile_select",
+        context! {
+            active: profiles.active,
+            profiles: profiles.profiles,
+            can_change: can_change(viewer.0.as_ref()),
+        },
+    )
+}
+
+/// GET /api/jobs - All stored jobs with their last result and next run
+#[get("/jobs")]
+pub fn list_jobs(_viewer: Viewer) -> Result<Json<Vec<Job>>, Status> {
+    sqlite::select_jobs()
+        .map(Json)
+        .map_err(|_| Status::InternalServerError)
+}
+
+/// GET /api/jobs/<id> - One stored job
+#[get("/jobs/<id>")]
+pub fn get_job(_viewer: Viewer, id: i64) -> Result<Json<Job>, Status> {
+    match sqlite::select_job(id) {
+        Ok(Some(job)) => Ok(Json(job)),
+        Ok(None) => Err(Status::NotFound),
+        Err(_) => Err(Status::InternalServerError),
+    }
+}
+
+/// POST /api/jobs - Store a new job
+#[post("/jobs", format = "json", data = "<definition>")]
+pub fn create_job(
+    _admin: Admin,
+    definition: Json<JobDefinition>,
+    state: &State<AppState>,
+) -> Json<JobResponse> {
+    if let Err(message) = check_job(&definition, state) {
+        return Json(job_failure(message));
+    }
+
+    let next_run = definition.next_run_after(chrono::Utc::now());
+    match sqlite::insert_job(&definition, next_run).and_then(sqlite::select_job) {
+        Ok(job) => {
+            log::info!("Job '{}' created via API", definition.name);
+            Json(JobResponse {
+                success: true,
+                message: format!("Job '{}' created", definition.name),
+                job,
+            })
+        }
+        Err(e) => Json(job_failure(format!("Failed to create job: {}", e))),
+    }
+}
+
+/// PUT /api/jobs/<id> - Replace a job's definition, keeping its last result
+#[put("/jobs/<id>", format = "json", data = "<definition>")]
+pub fn update_job(
+    _admin: Admin,
+    id: i64,
+    definition: Json<JobDefinition>,
+    state: &State<AppState>,
+) -> Result<Json<JobResponse>, Status> {
+    if let Err(message) = check_job(&definition, state) {
+        return Ok(Json(job_failure(message)));
+    }
+    save_job(id, &definition, "updated")
+}
+
+/// POST /api/jobs/<id>/enable - Let a job run on its schedule again
+#[post("/jobs/<id>/enable")]
+pub fn enable_job(_admin: Admin, id: i64) -> Result<Json<JobResponse>, Status> {
+    set_job_enabled(id, true)
+}
+
+/// POST /api/jobs/<id>/disable - Stop a job from running on its schedule
+#[post("/jobs/<id>/disable")]
+pub fn disable_job(_admin: Admin, id: i64) -> Result<Json<JobResponse>, Status> {
+    set_job_enabled(id, false)
+}
+
+/// DELETE /api/jobs/<id> - Remove a job; a run it already started carries on
+#[delete("/jobs/<id>")]
+pub fn delete_job(_admin: Admin, id: i64) -> Result<Json<JobResponse>, Status> {
+    match sqlite::delete_job(id) {
+        Ok(true) => Ok(Json(JobResponse {
+            success: true,
+            message: format!("Job {} deleted", id),
+            job: None,
+        })),
+        Ok(false) => Err(Status::NotFound),
+        Err(_) => Err(Status::InternalServerError),
+    }
+}
+
+/// POST /api/jobs/<id>/run - Start a job now, whether or not it is enabled
+#[post("/jobs/<id>/run")]
+pub fn run_job(
+    _admin: Admin,
+    id: i64,
+    state: &State<AppState>,
+) -> Result<Json<StartBackupResponse>, Status> {
+    let job = match sqlite::select_job(id) {
+        Ok(Some(job)) => job,
+        Ok(None) => return Err(Status::NotFound),
+        Err(_) => return Err(Status::InternalServerError),
+    };
+
+    Ok(Json(match state.run_job(&job) {
+        Ok(backup_id) => StartBackupResponse {
+            success: true,
+            message: format!("Job '{}' started", job.definition.name),
+            backup_id: Some(backup_id),
+            status: Some(BackupStatus::Running),
+        },
+        Err(message) => StartBackupResponse {
+            success: false,
+            message,
+            backup_id: None,
+            status: None,
+        },
+    }))
+}
+
+/// Check a submitted job, including that its selection matches the current configuration
+fn check_job(definition: &JobDefinition, state: &AppState) -> Result<(), String> {
+    definition.validate().map_err(|e| e.to_string())?;
+    if let Some(config) = state.get_config() {
+        definition
+            .selection()
+            .resolve(&config)
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn set_job_enabled(id: i64, enabled: bool) -> Result<Json<JobResponse>, Status> {
+    let mut definition = match sqlite::select_job(id) {
+        Ok(Some(job)) => job.definition,
+        Ok(None) => return Err(Status::NotFound),
+        Err(_) => return Err(Status::InternalServerError),
+    };
+    definition.enabled = enabled;
+    save_job(
+        id,
+        &definition,
+        if enabled { "enabled" } else { "disabled" },
+    )
+}
+
+/// Store a changed definition with a fresh next run and return the updated job
+fn save_job(
+    id: i64,
+    definition: &JobDefinition,
+    action: &str,
+) -> Result<Json<JobResponse>, Status> {
+    let next_run = definition.next_run_after(chrono::Utc::now());
+    match sqlite::update_job(id, definition, next_run) {
+        Ok(true) => {}
+        Ok(false) => return Err(Status::NotFound),
+        Err(e) => return Ok(Json(job_failure(format!("Failed to update job: {}", e)))),
+    }
+
+    log::info!("Job '{}' {} via API", definition.name, action);
+    Ok(Json(JobResponse {
+        success: true,
+        message: format!("Job '{}' {}", definition.name, action),
+        job: sqlite::select_job(id).ok().flatten(),
+    }))
+}
+
+fn job_failure(message: String) -> JobResponse {
+    JobResponse {
+        success: false,
+        message,
+        job: None,
+    }
+}
+
+/// GET /metrics - Counters for Prometheus, labelled by destination and job
+#[get("/metrics")]
+pub fn metrics(state: &State<AppState>) -> (ContentType, String) {
+    (ContentType::Plain, state.metrics_text())
+}
+
 /// GET /api/storage/overview - Get storage overview
 #[get("/storage/overview")]
-pub fn get_storage_overview(state: &State<AppState>) -> Template {
+pub fn get_storage_overview(_viewer: Viewer, state: &State<AppState>) -> Template {
     let config = state.get_config();
 
     // Get destination paths from config
     let destinations = config
         .as_ref()
-        .map(|c| c.backup_destinations.clone())
+        .map(|c| c.destination_paths())
         .unwrap_or_default();
 
     // Query database for storage statistics
-    let storage_stats = sqlite::get_storage_overview(&destinations).unwrap_or_else(|_| {
+    let storage_stats = catalog::get_storage_overview(&destinations).unwrap_or_else(|_| {
         crate::models::storage::StorageStats {
             total_source_files: 0,
             total_source_size: 0,
             destination_stats: vec![],
+            content: Default::default(),
         }
     });
 
@@ -791,19 +1912,392 @@ pub fn get_storage_overview(state: &State<AppState>) -> Template {
             let percentage =
                 ((dest.total_size as f64 / 1_000_000_000_000.0) * 100.0).min(100.0) as u32;
 
+            // Copies in progress, or left by a run that crashed until the next run cleans up
+            let workspace = workspace_size(std::path::Path::new(&dest.destination_root));
+
             json!({
                 "path": dest.destination_root,
                 "size_formatted": size_formatted,
                 "file_count": dest.file_count,
                 "percentage": percentage,
+                "workspace_formatted": (workspace > 0).then(|| sqlite::format_bytes(workspace)),
             })
         })
         .collect();
 
+    // What storing each content once would save, shown once any file has a duplicate
+    let content = storage_stats.content;
+    let duplicates = (content.distinct_hashes < content.hashed_files).then(|| {
+        json!({
+            "hashed_files": content.hashed_files,
+            "distinct_hashes": content.distinct_hashes,
+            "unique_formatted": sqlite::format_bytes(content.unique_size),
+            "duplicate_formatted": sqlite::format_bytes(content.duplicate_size()),
+            "ratio": format!("{:.2}", content.dedup_ratio()),
+        })
+    });
+
     Template::render(
         "partials/storage_overview",
         context! {
             destinations: formatted_destinations,
+            duplicates: duplicates,
+        },
+    )
+}
+
+/// Extensions shown for each source in the file types card
+const DASHBOARD_FILE_TYPES: usize = 8;
+
+/// GET /api/stats/file-types - Files and bytes of each extension in each source, as the last run
+/// with a report found them (HTML partial)
+#[get("/stats/file-types")]
+pub fn get_file_types(_viewer: Viewer, state: &State<AppState>) -> Template {
+    let history = state.get_history();
+    let latest = history.iter().find_map(|entry| {
+        let report = entry.report.as_ref()?;
+        (!report.file_types.is_empty()).then_some((entry, report))
+    });
+
+    let mut sources: Vec<serde_json::Value> = Vec::new();
+    if let Some((_, report)) = latest {
+        let mut names: Vec<&str> = report
+            .file_types
+            .iter()
+            .map(|t| t.source.as_str())
+            .collect();
+        names.dedup();
+        for name in names {
+            let types: Vec<_> = report
+                .file_types
+                .iter()
+                .filter(|t| t.source == name)
+                .collect();
+            let total: u64 = types.iter().map(|t| t.bytes).sum();
+            let shown: Vec<serde_json::Value> = types
+                .iter()
+                .take(DASHBOARD_FILE_TYPES)
+                .map(|t| {
+                    json!({
+                        "extension": match t.extension.as_str() {
+                            "" => "(none)".to_string(),
+                            "*" => "(other)".to_string(),
+                            extension => format!(".{}", extension),
+                        },
+                        "files": t.files,
+                        "size_formatted": sqlite::format_bytes(t.bytes),
+                        "percentage": (t.bytes as f64 * 100.0 / total.max(1) as f64).round() as u32,
+                    })
+                })
+                .collect();
+            sources.push(json!({
+                "name": name,
+                "size_formatted": sqlite::format_bytes(total),
+                "types": shown,
+            }));
+        }
+    }
+
+    Template::render(
+        "partials/file_types",
+        context! {
+            run_id: latest.map(|(entry, _)| entry.id.clone()),
+            sources: sources,
         },
     )
 }
+
+/// Every route mounted under `/api`
+pub fn routes() -> Vec<rocket::Route> {
+    routes![
+        get_config,
+        get_config_form,
+        set_config,
+        set_config_form,
+        save_config_to_file,
+        load_config_from_file,
+        reload_config,
+        get_status,
+        start_backup,
+        trigger_backup,
+        login,
+        logout,
+        get_me,
+        stop_backup,
+        pause_backup,
+        resume_backup,
+        confirm_mass_change,
+        get_history,
+        get_run_files,
+        stream_run_files,
+        get_run_diff,
+        progress_events,
+        validate_config_endpoint,
+        validate_submitted_config,
+        health_check,
+        get_dashboard_metrics,
+        get_progress,
+        get_file_progress,
+        get_logs,
+        get_recent_logs,
+        export_logs,
+        get_log_stats,
+        clear_logs,
+        prune_logs,
+        stream_logs,
+        get_storage_overview,
+        get_file_types,
+        get_unclaimed_files,
+        get_prune_plans,
+        get_prune_plan,
+        approve_prune_plan,
+        reject_prune_plan,
+        get_audit_events,
+        get_post_process_results,
+        get_files,
+        get_file_rows,
+        get_file,
+        get_run_stats,
+        get_coverage,
+        get_orphans,
+        clean_orphans,
+        get_manifest,
+        compare_manifest,
+        list_jobs,
+        create_job,
+        get_job,
+        update_job,
+        delete_job,
+        enable_job,
+        disable_job,
+        run_job,
+        get_schedule,
+        enable_schedule,
+        disable_schedule,
+        get_profiles,
+        select_profile,
+        get_profile_options,
+        openapi_spec,
+        api_docs,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::run_report::RunReport;
+    use crate::models::user::{Role, User};
+    use serde_json::Value;
+    use std::collections::BTreeSet;
+
+    fn spec() -> Value {
+        serde_json::from_str(OPENAPI_SPEC).unwrap()
+    }
+
+    /// Serialize `value` and check its fields against the spec's schema called `name`
+    fn assert_matches_schema(spec: &Value, name: &str, value: impl serde::Serialize) {
+        let schema = &spec["components"]["schemas"][name];
+        let documented: BTreeSet<&str> = schema["properties"]
+            .as_object()
+            .unwrap_or_else(|| panic!("{} has no documented properties", name))
+            .keys()
+            .map(String::as_str)
+            .collect();
+        let value = serde_json::to_value(value).unwrap();
+        let fields: BTreeSet<&str> = value
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        assert!(
+            fields.is_subset(&documented),
+            "{} has undocumented fields {:?}",
+            name,
+            fields.difference(&documented).collect::<Vec<_>>()
+        );
+        for required in schema["required"].as_array().into_iter().flatten() {
+            assert!(
+                fields.contains(required.as_str().unwrap()),
+                "{} is missing required field {}",
+                name,
+                required
+            );
+        }
+    }
+
+    #[test]
+    fn test_spec_documents_every_mounted_route() {
+        let spec = spec();
+        let documented: BTreeSet<String> = spec["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .flat_map(|(path, operations)| {
+                operations
+                    .as_object()
+                    .unwrap()
+                    .keys()
+                    .map(move |method| format!("{} {}", method.to_uppercase(), path))
+            })
+            .collect();
+        // Rocket writes path parameters as <id>, OpenAPI as {id}
+        let mounted: BTreeSet<String> = routes()
+            .iter()
+            .map(|route| {
+                let path = route.uri.path().replace('<', "{").replace('>', "}");
+                format!("{} {}", route.method, path)
+            })
+            .collect();
+
+        assert_eq!(
+            mounted.difference(&documented).collect::<Vec<_>>(),
+            Vec::<&String>::new(),
+            "routes missing from openapi.json"
+        );
+        assert_eq!(
+            documented.difference(&mounted).collect::<Vec<_>>(),
+            Vec::<&String>::new(),
+            "openapi.json documents routes that aren't mounted"
+        );
+        assert_eq!(spec["info"]["version"], env!("CARGO_PKG_VERSION"));
+    }
+
+    #[test]
+    fn test_early_triggers_say_when_to_retry() {
+        use rocket::local::blocking::Client;
+        use rocket::response::Responder;
+
+        let client = Client::untracked(rocket::build()).unwrap();
+        let request = client.post("/api/trigger/0123456789abcdef");
+        let rejection = TriggerRejection::TooSoon(
+            Json(TriggerTooSoonResponse {
+                message: "The trigger may fire again in 42 second(s)".to_string(),
+                retry_after_secs: 42,
+            }),
+            Header::new("Retry-After", "42"),
+        );
+        let mut response = rejection.respond_to(request.inner()).unwrap();
+        assert_eq!(response.status(), Status::TooManyRequests);
+        assert_eq!(response.headers().get_one("Retry-After"), Some("42"));
+        let body = rocket::tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(response.body_mut().to_string())
+            .unwrap();
+        let body: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(body["retry_after_secs"], 42);
+
+        let response = TriggerRejection::UnknownToken(())
+            .respond_to(request.inner())
+            .unwrap();
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(response.headers().get_one("Retry-After"), None);
+    }
+
+    #[test]
+    fn test_spec_schemas_match_responses() {
+        let spec = spec();
+        assert_matches_schema(
+            &spec,
+            "TriggerTooSoonResponse",
+            TriggerTooSoonResponse {
+                message: String::new(),
+                retry_after_secs: 1,
+            },
+        );
+        assert_matches_schema(
+            &spec,
+            "StatusResponse",
+            StatusResponse {
+                status: BackupStatus::Running,
+                progress: Some(BackupProgress::default()),
+                started_at: None,
+                completed_at: None,
+                error: None,
+                dry_run_mode: None,
+                destination_mismatches: Vec::new(),
+                mass_change: None,
+            },
+        );
+        assert_matches_schema(&spec, "BackupProgress", BackupProgress::default());
+        assert_matches_schema(
+            &spec,
+            "FileProgressResponse",
+            FileProgressResponse {
+                files: vec![FileProgress {
+                    source: "photos".to_string(),
+                    file: "/photos/a.jpg".to_string(),
+                    destination: "/mnt/backup/photos/a.jpg".to_string(),
+                    file_size: 100,
+                    bytes_copied: 25,
+                    percentage: 25.0,
+                    elapsed_ms: 800,
+                    bytes_per_second: Some(31.25),
+                }],
+                total: 1,
+            },
+        );
+        assert_matches_schema(
+            &spec,
+            "BackupHistoryEntry",
+            BackupHistoryEntry {
+                id: "run".to_string(),
+                started_at: String::new(),
+                completed_at: None,
+                status: BackupStatus::Completed,
+                files_processed: 0,
+                bytes_processed: None,
+                error: None,
+                dry_run: false,
+                note: Some("nightly".to_string()),
+                report: Some(RunReport::default()),
+            },
+        );
+        assert_matches_schema(&spec, "RunReport", RunReport::default());
+        assert_matches_schema(
+            &spec,
+            "StartBackupResponse",
+            StartBackupResponse {
+                success: true,
+                message: String::new(),
+                backup_id: Some("run".to_string()),
+                status: Some(BackupStatus::Running),
+            },
+        );
+        assert_matches_schema(
+            &spec,
+            "ScheduleResponse",
+            ScheduleResponse {
+                schedule: None,
+                enabled: true,
+                next_run: None,
+            },
+        );
+        assert_matches_schema(&spec, "CoverageReport", CoverageReport::default());
+        assert_matches_schema(&spec, "OrphanReport", OrphanReport::default());
+        assert_matches_schema(
+            &spec,
+            "ProfilesResponse",
+            ProfilesResponse {
+                success: true,
+                message: String::new(),
+                active: Some("quick-docs".to_string()),
+                profiles: vec!["quick-docs".to_string()],
+            },
+        );
+        assert_matches_schema(
+            &spec,
+            "SessionResponse",
+            SessionResponse {
+                success: true,
+                message: String::new(),
+                user: Some(User {
+                    name: "alice".to_string(),
+                    role: Role::Viewer,
+                    created_at: 0,
+                }),
+                sign_in_required: true,
+            },
+        );
+    }
+}
@@ -1,21 +1,57 @@
-use crate::models::api::{BackupHistoryEntry, BackupProgress, BackupStatus, ProgressEvent};
+use crate::models::active_run::ActiveRun;
+use crate::models::api::{
+    BackupHistoryEntry, BackupProgress, BackupStatus, FileProgress, FileProgressResponse,
+    ProgressEvent, ScheduleResponse, StatusResponse,
+};
 use crate::models::config::Config;
 use crate::models::dry_run_mode::DryRunMode;
+use crate::models::job::{Job, JobResult};
+use crate::models::run_record::{clean_note, RunRecord};
+use crate::models::run_report::{MassChange, RunReport};
+use crate::models::run_selection::RunSelection;
+use crate::repo::sqlite;
+use crate::service::audit::record_audit_event;
+use crate::service::metrics::Metrics;
+use crate::service::scrub::run_scheduled_scrub;
+use crate::utils::db_logger::apply_log_retention;
+use crate::utils::directory::long_path;
 use chrono::{DateTime, Utc};
-use std::collections::VecDeque;
-use std::sync::atomic::{AtomicBool, Ordering};
+use cron::Schedule;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
 /// Maximum number of history entries to keep in memory
 const MAX_HISTORY_ENTRIES: usize = 100;
 
+/// How often a paused backup checks whether it may continue
+const PAUSE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// How often the server checks for scheduled jobs that are due
+const JOB_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// How often the server checks whether the configuration's schedule is due
+const SCHEDULE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Least time between saving the progress of a running backup to the database
+const RUN_STATE_SAVE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// How often the server prunes the Logs table to the configured retention
+const LOG_PRUNE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
 /// Shared application state
 #[derive(Clone)]
 pub struct AppState {
     /// Current configuration (None if not set)
     config: Arc<Mutex<Option<Config>>>,
 
+    /// Selected profile and the configuration it makes, used in place of `config` while set
+    profile: Arc<Mutex<Option<(String, Config)>>>,
+
     /// Config file path for persistence
     config_file_path: Arc<Mutex<Option<String>>>,
 
@@ -25,9 +61,19 @@ pub struct AppState {
     /// Current progress information
     progress: Arc<Mutex<Option<BackupProgress>>>,
 
+    /// Copies the running backup's workers are making, by the ID each was given
+    copies: Arc<Mutex<HashMap<u64, CopyInProgress>>>,
+    next_copy_id: Arc<AtomicU64>,
+
     /// Flag to signal backup should stop
     stop_signal: Arc<AtomicBool>,
 
+    /// Flag to hold the running backup between files
+    pause_signal: Arc<AtomicBool>,
+
+    /// Changes past `mass_change_guard` the paused run waits to have confirmed
+    mass_change: Arc<Mutex<Option<MassChange>>>,
+
     /// Backup run information
     current_run: Arc<Mutex<Option<BackupRunInfo>>>,
 
@@ -36,6 +82,123 @@ pub struct AppState {
 
     /// Subscribers for progress events (SSE)
     progress_subscribers: Arc<Mutex<Vec<tokio::sync::broadcast::Sender<ProgressEvent>>>>,
+
+    /// Held while a start request checks for an existing run and launches a new one
+    launch_lock: Arc<Mutex<()>>,
+
+    /// Counters for the Prometheus endpoint
+    metrics: Arc<Mutex<Metrics>>,
+
+    /// Next run of the configuration's own schedule
+    config_schedule: Arc<Mutex<ConfigSchedule>>,
+
+    /// ID of the run kept in the database's `Active_Run` table, and when its progress was saved
+    persisted_run: Arc<Mutex<Option<(String, Instant)>>>,
+
+    /// When each webhook trigger last started a run, by trigger name
+    trigger_fired: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+/// A copy a worker started, for `/api/progress/files`
+#[derive(Debug, Clone)]
+struct CopyInProgress {
+    source: String,
+    file: String,
+    backup_path: PathBuf,
+    file_size: u64,
+    started: Instant,
+}
+
+/// Takes a copy off the files in progress when dropped
+pub struct TrackedCopy<'a> {
+    state: &'a AppState,
+    id: u64,
+}
+
+impl Drop for TrackedCopy<'_> {
+    fn drop(&mut self) {
+        self.state.copies.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Where the configuration's `schedule` stands in API mode
+#[derive(Debug, Clone)]
+struct ConfigSchedule {
+    /// Turned off through the API to hold scheduled runs; runs started by hand are unaffected
+    enabled: bool,
+    /// Expression `next_run` was worked out from
+    expression: Option<String>,
+    next_run: Option<DateTime<Utc>>,
+}
+
+impl ConfigSchedule {
+    fn new() -> Self {
+        Self {
+            enabled: true,
+            expression: None,
+            next_run: None,
+        }
+    }
+
+    /// Follow the configured expression and report whether a run is due at `now`
+    ///
+    /// A changed expression or a re-enabled schedule plans from `now`, so times missed in
+    /// between don't start a run.
+    fn advance(&mut self, expression: Option<&str>, now: DateTime<Utc>) -> bool {
+        let changed = self.expression.as_deref() != expression;
+        let due = !changed && self.enabled && self.next_run.is_some_and(|next| next <= now);
+
+        if changed || due || self.next_run.is_none() {
+            self.expression = expression.map(str::to_string);
+            self.next_run = expression
+                .filter(|_| self.enabled)
+                .and_then(|expression| Schedule::from_str(expression).ok())
+                .and_then(|schedule| schedule.after(&now).next());
+        }
+        due
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        if self.enabled != enabled {
+            self.enabled = enabled;
+            self.next_run = None;
+        }
+    }
+}
+
+/// What a start request did
+#[derive(Debug, Clone, PartialEq)]
+pub enum LaunchOutcome {
+    /// A new run was started with this ID
+    Started(String),
+    /// The idempotency key belongs to a run that was already started
+    Existing {
+        backup_id: String,
+        status: BackupStatus,
+    },
+}
+
+/// What a request to `/api/trigger/<token>` did
+#[derive(Debug, Clone, PartialEq)]
+pub enum TriggerOutcome {
+    /// No configured trigger has the token
+    UnknownToken,
+    /// The trigger started a run less than its `min_interval_secs` ago
+    TooSoon {
+        retry_after_secs: u64,
+    },
+    Launched(LaunchOutcome),
+    /// The token is valid but the run couldn't start
+    Refused(String),
+}
+
+/// Run ID for a client-provided idempotency key, the same every time the key is sent
+pub fn run_id_for_key(idempotency_key: &str) -> String {
+    Uuid::new_v5(
+        &Uuid::NAMESPACE_URL,
+        format!("urn:rustyhashbackup:run:{}", idempotency_key).as_bytes(),
+    )
+    .to_string()
 }
 
 /// Information about the current backup run
@@ -46,6 +209,8 @@ pub struct BackupRunInfo {
     pub completed_at: Option<DateTime<Utc>>,
     pub dry_run_mode: DryRunMode,
     pub error: Option<String>,
+    /// Note given when the run was started
+    pub note: Option<String>,
 }
 
 impl AppState {
@@ -53,26 +218,76 @@ impl AppState {
     pub fn new() -> Self {
         Self {
             config: Arc::new(Mutex::new(None)),
+            profile: Arc::new(Mutex::new(None)),
             config_file_path: Arc::new(Mutex::new(None)),
             status: Arc::new(Mutex::new(BackupStatus::Idle)),
             progress: Arc::new(Mutex::new(None)),
+            copies: Arc::new(Mutex::new(HashMap::new())),
+            next_copy_id: Arc::new(AtomicU64::new(0)),
             stop_signal: Arc::new(AtomicBool::new(false)),
+            pause_signal: Arc::new(AtomicBool::new(false)),
+            mass_change: Arc::new(Mutex::new(None)),
             current_run: Arc::new(Mutex::new(None)),
             history: Arc::new(Mutex::new(VecDeque::new())),
             progress_subscribers: Arc::new(Mutex::new(Vec::new())),
+            launch_lock: Arc::new(Mutex::new(())),
+            metrics: Arc::new(Mutex::new(Metrics::default())),
+            config_schedule: Arc::new(Mutex::new(ConfigSchedule::new())),
+            persisted_run: Arc::new(Mutex::new(None)),
+            trigger_fired: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
-    /// Get the current configuration
+    /// Get the current configuration, with the selected profile's settings in place
     pub fn get_config(&self) -> Option<Config> {
+        if let Some((_, config)) = &*self.profile.lock().unwrap() {
+            return Some(config.clone());
+        }
+        self.config.lock().unwrap().clone()
+    }
+
+    /// Get the configuration as loaded, with all of its profiles
+    pub fn get_base_config(&self) -> Option<Config> {
         self.config.lock().unwrap().clone()
     }
 
-    /// Set the configuration
+    /// Set the configuration, keeping the selected profile if it still has one by that name
     pub fn set_config(&self, config: Config) {
+        let mut profile = self.profile.lock().unwrap();
+        if let Some((name, _)) = profile.take() {
+            match config.with_profile(&name) {
+                Ok(selected) => *profile = Some((name, selected)),
+                Err(e) => log::warn!("Back to the configuration without a profile: {}", e),
+            }
+        }
         *self.config.lock().unwrap() = Some(config);
     }
 
+    /// Name of the selected profile
+    pub fn get_profile(&self) -> Option<String> {
+        let profile = self.profile.lock().unwrap();
+        profile.as_ref().map(|(name, _)| name.clone())
+    }
+
+    /// Use the settings of profile `name` until another is selected, or none with `None`
+    ///
+    /// A running backup carries on with the settings it started with.
+    pub fn select_profile(&self, name: Option<&str>) -> Result<(), String> {
+        let selected = match name {
+            Some(name) => {
+                let config = self
+                    .get_base_config()
+                    .ok_or_else(|| "No configuration set".to_string())?;
+                let selected = config.with_profile(name).map_err(|e| e.to_string())?;
+                Some((name.to_string(), selected))
+            }
+            None => None,
+        };
+        *self.profile.lock().unwrap() = selected;
+        log::info!("Selected profile: {}", name.unwrap_or("none"));
+        Ok(())
+    }
+
     /// Get the config file path
     pub fn get_config_file_path(&self) -> Option<String> {
         self.config_file_path.lock().unwrap().clone()
@@ -83,39 +298,6 @@ impl AppState {
         *self.config_file_path.lock().unwrap() = Some(path);
     }
 
-    /// Save current configuration to file
-    pub fn save_config_to_file(&self) -> Result<(), String> {
-        use std::fs;
-        use std::path::Path;
-
-        let config = self
-            .get_config()
-            .ok_or_else(|| "No configuration to save".to_string())?;
-
-        let file_path = self
-            .get_config_file_path()
-            .ok_or_else(|| "No config file path set".to_string())?;
-
-        // Ensure parent directory exists
-        if let Some(parent) = Path::new(&file_path).parent() {
-            if !parent.exists() {
-                fs::create_dir_all(parent)
-                    .map_err(|e| format!("Failed to create config directory: {}", e))?;
-            }
-        }
-
-        // Serialize config to JSON
-        let config_json = serde_json::to_string_pretty(&config)
-            .map_err(|e| format!("Failed to serialize config: {}", e))?;
-
-        // Write to file
-        fs::write(&file_path, config_json)
-            .map_err(|e| format!("Failed to write config file: {}", e))?;
-
-        log::info!("Configuration saved to {}", file_path);
-        Ok(())
-    }
-
     /// Load configuration from file
     pub fn load_config_from_file(&self, file_path: String) -> Result<(), String> {
         use std::fs;
@@ -148,6 +330,7 @@ impl AppState {
     /// Set the current status
     pub fn set_status(&self, status: BackupStatus) {
         *self.status.lock().unwrap() = status;
+        self.save_run_state(true);
         self.notify_progress_update();
     }
 
@@ -156,12 +339,88 @@ impl AppState {
         self.progress.lock().unwrap().clone()
     }
 
+    /// List a copy among the files in progress until the returned guard is dropped
+    pub fn track_copy(
+        &self,
+        source: &str,
+        file: &Path,
+        backup_path: &Path,
+        file_size: u64,
+    ) -> TrackedCopy<'_> {
+        let id = self.next_copy_id.fetch_add(1, Ordering::Relaxed);
+        self.copies.lock().unwrap().insert(
+            id,
+            CopyInProgress {
+                source: source.to_string(),
+                file: file.to_string_lossy().to_string(),
+                backup_path: backup_path.to_path_buf(),
+                file_size,
+                started: Instant::now(),
+            },
+        );
+        TrackedCopy { state: self, id }
+    }
+
+    /// The copies in progress, longest-running first, with how far each has got
+    ///
+    /// Copies don't report their bytes as they go, so each backup's size on disk is read instead.
+    pub fn files_in_progress(&self, limit: usize) -> FileProgressResponse {
+        let mut copies: Vec<CopyInProgress> =
+            self.copies.lock().unwrap().values().cloned().collect();
+        copies.sort_by_key(|copy| copy.started);
+        let total = copies.len();
+        let files = copies
+            .into_iter()
+            .take(limit)
+            .map(|copy| {
+                let bytes_copied = std::fs::metadata(long_path(&copy.backup_path))
+                    .map_or(0, |m| m.len().min(copy.file_size));
+                let elapsed = copy.started.elapsed();
+                FileProgress {
+                    source: copy.source,
+                    file: copy.file,
+                    destination: copy.backup_path.to_string_lossy().to_string(),
+                    file_size: copy.file_size,
+                    bytes_copied,
+                    percentage: if copy.file_size == 0 {
+                        0.0
+                    } else {
+                        bytes_copied as f32 / copy.file_size as f32 * 100.0
+                    },
+                    elapsed_ms: elapsed.as_millis() as u64,
+                    bytes_per_second: (elapsed >= Duration::from_millis(500))
+                        .then(|| bytes_copied as f64 / elapsed.as_secs_f64()),
+                }
+            })
+            .collect();
+        FileProgressResponse { files, total }
+    }
+
     /// Set the current progress
     pub fn set_progress(&self, progress: Option<BackupProgress>) {
         *self.progress.lock().unwrap() = progress;
+        self.save_run_state(false);
         self.notify_progress_update();
     }
 
+    /// Save the status and progress of the run kept in the database, at most every
+    /// `RUN_STATE_SAVE_INTERVAL` unless `now` is set
+    fn save_run_state(&self, now: bool) {
+        let mut persisted = self.persisted_run.lock().unwrap();
+        let Some((run_id, saved_at)) = persisted.as_mut() else {
+            return;
+        };
+        if !now && saved_at.elapsed() < RUN_STATE_SAVE_INTERVAL {
+            return;
+        }
+        *saved_at = Instant::now();
+
+        let progress = self.get_progress();
+        if let Err(e) = sqlite::update_active_run(run_id, &self.get_status(), progress.as_ref()) {
+            log::debug!("Failed to save the state of run {}: {}", run_id, e);
+        }
+    }
+
     /// Update progress incrementally
     #[allow(dead_code)]
     pub fn update_progress<F>(&self, updater: F)
@@ -190,6 +449,7 @@ impl AppState {
     /// Signal that backup should stop
     pub fn request_stop(&self) {
         self.stop_signal.store(true, Ordering::SeqCst);
+        self.pause_signal.store(false, Ordering::SeqCst);
         self.set_status(BackupStatus::Stopping);
     }
 
@@ -203,25 +463,93 @@ impl AppState {
         self.stop_signal.load(Ordering::SeqCst)
     }
 
+    /// Hold the running backup before its next file
+    pub fn pause_backup(&self) -> Result<(), String> {
+        if self.get_status() != BackupStatus::Running {
+            return Err("No backup is currently running".to_string());
+        }
+        self.pause_signal.store(true, Ordering::SeqCst);
+        self.set_status(BackupStatus::Paused);
+        Ok(())
+    }
+
+    /// Let a paused backup continue
+    pub fn resume_backup(&self) -> Result<(), String> {
+        if self.get_status() != BackupStatus::Paused {
+            return Err("The backup is not paused".to_string());
+        }
+        if self.mass_change.lock().unwrap().is_some() {
+            return Err(
+                "The backup is waiting for its mass change to be confirmed at \
+                 /api/confirm-mass-change"
+                    .to_string(),
+            );
+        }
+        self.pause_signal.store(false, Ordering::SeqCst);
+        self.set_status(BackupStatus::Running);
+        Ok(())
+    }
+
+    /// Pause the run until its mass change is confirmed or the run is stopped; whether it was
+    /// confirmed
+    pub fn await_mass_change_confirmation(&self, change: MassChange) -> bool {
+        *self.mass_change.lock().unwrap() = Some(change);
+        self.pause_signal.store(true, Ordering::SeqCst);
+        self.set_status(BackupStatus::Paused);
+        self.wait_while_paused();
+        self.mass_change.lock().unwrap().take();
+        !self.is_stop_requested()
+    }
+
+    /// Let the run waiting on its mass change go ahead; the change it was waiting on
+    pub fn confirm_mass_change(&self) -> Result<MassChange, String> {
+        let change =
+            self.mass_change.lock().unwrap().clone().ok_or_else(|| {
+                "No backup is waiting for a mass change to be confirmed".to_string()
+            })?;
+        self.pause_signal.store(false, Ordering::SeqCst);
+        self.set_status(BackupStatus::Running);
+        Ok(change)
+    }
+
+    /// Block the calling worker while the backup is paused, returning early if a stop is requested
+    pub fn wait_while_paused(&self) {
+        while self.pause_signal.load(Ordering::SeqCst) && !self.is_stop_requested() {
+            std::thread::sleep(PAUSE_POLL_INTERVAL);
+        }
+    }
+
     /// Start a new backup run
-    pub fn start_backup_run(&self, dry_run_mode: DryRunMode) -> String {
-        let id = Uuid::new_v4().to_string();
+    pub fn start_backup_run(
+        &self,
+        dry_run_mode: DryRunMode,
+        id: String,
+        note: Option<String>,
+    ) -> String {
         let run_info = BackupRunInfo {
             id: id.clone(),
             started_at: Utc::now(),
             completed_at: None,
             dry_run_mode,
             error: None,
+            note,
         };
         *self.current_run.lock().unwrap() = Some(run_info);
         self.reset_stop_signal();
+        self.pause_signal.store(false, Ordering::SeqCst);
         self.set_status(BackupStatus::Running);
         self.set_progress(Some(BackupProgress::default()));
         id
     }
 
     /// Complete the current backup run
-    pub fn complete_backup_run(&self, error: Option<String>) {
+    pub fn complete_backup_run(&self, error: Option<String>, report: Option<RunReport>) {
+        if let Some((run_id, _)) = self.persisted_run.lock().unwrap().take() {
+            if let Err(e) = sqlite::delete_active_run(&run_id) {
+                log::warn!("Failed to clear the saved state of run {}: {}", run_id, e);
+            }
+        }
+
         let mut current_run_guard = self.current_run.lock().unwrap();
         if let Some(run_info) = current_run_guard.as_mut() {
             run_info.completed_at = Some(Utc::now());
@@ -233,6 +561,18 @@ impl AppState {
                 BackupStatus::Completed
             };
 
+            let job = sqlite::select_job_for_run(&run_info.id).unwrap_or_else(|e| {
+                log::warn!("Failed to look up the job for run {}: {}", run_info.id, e);
+                None
+            });
+            self.metrics.lock().unwrap().record_run(
+                job.as_ref().map(|job| job.definition.name.as_str()),
+                &status,
+                run_info.dry_run_mode.is_dry_run(),
+                report.as_ref(),
+                Utc::now().timestamp(),
+            );
+
             // Add to history
             let progress = self.get_progress().unwrap_or_default();
             let history_entry = BackupHistoryEntry {
@@ -244,19 +584,614 @@ impl AppState {
                 bytes_processed: progress.bytes_processed,
                 error: error.clone(),
                 dry_run: run_info.dry_run_mode.is_dry_run(),
+                note: run_info.note.clone(),
+                report,
             };
 
             let mut history_guard = self.history.lock().unwrap();
             history_guard.push_front(history_entry);
             if history_guard.len() > MAX_HISTORY_ENTRIES {
-                history_guard.pop_back();
+                if let Some(expired) = history_guard.pop_back() {
+                    if let Err(e) = sqlite::delete_run_files(&expired.id) {
+                        log::warn!(
+                            "Failed to delete file outcomes for run {}: {}",
+                            expired.id,
+                            e
+                        );
+                    }
+                }
             }
             drop(history_guard);
 
+            if job.is_some() {
+                if let Err(e) = sqlite::record_job_finished(&run_info.id, &status, error.as_deref())
+                {
+                    log::warn!("Failed to record job result for run {}: {}", run_info.id, e);
+                }
+            }
+
             self.set_status(status);
         }
     }
 
+    /// Counters, per-destination and per-job series in the Prometheus text format
+    pub fn metrics_text(&self) -> String {
+        let destinations: Vec<String> = self
+            .get_config()
+            .map(|config| {
+                config
+                    .backup_destinations
+                    .iter()
+                    .map(|d| d.name().unwrap_or(d.path()).to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        let jobs = sqlite::select_jobs().unwrap_or_else(|e| {
+            log::warn!("Failed to load jobs for metrics: {}", e);
+            Vec::new()
+        });
+
+        self.metrics
+            .lock()
+            .unwrap()
+            .render(&self.get_status(), &destinations, &jobs)
+    }
+
+    /// Status of the current or a remembered run with this ID
+    fn run_status(&self, backup_id: &str) -> Option<BackupStatus> {
+        let is_current = self
+            .current_run
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|run| run.id == backup_id);
+        if is_current {
+            return Some(self.get_status());
+        }
+        self.history
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|entry| entry.id == backup_id)
+            .map(|entry| entry.status.clone())
+    }
+
+    /// Check that a backup can start and run it in the background
+    ///
+    /// A request carrying an idempotency key that was already used returns that run instead of
+    /// starting another, for as long as the run is current or in the history. `note` is kept
+    /// with the run and shown in its history. Must be called from within the Tokio runtime;
+    /// shared by the REST and gRPC start endpoints.
+    pub fn launch_backup(
+        &self,
+        dry_run_mode: DryRunMode,
+        quiet: bool,
+        selection: RunSelection,
+        note: Option<&str>,
+        idempotency_key: Option<&str>,
+    ) -> Result<LaunchOutcome, String> {
+        // Retries of the same request may arrive together
+        let _launching = self.launch_lock.lock().unwrap();
+
+        let backup_id = match idempotency_key {
+            Some(key) => {
+                let backup_id = run_id_for_key(key);
+                if let Some(status) = self.run_status(&backup_id) {
+                    return Ok(LaunchOutcome::Existing { backup_id, status });
+                }
+                backup_id
+            }
+            None => Uuid::new_v4().to_string(),
+        };
+
+        if matches!(
+            self.get_status(),
+            BackupStatus::Running | BackupStatus::Paused
+        ) {
+            return Err("A backup is already running".to_string());
+        }
+
+        let config = self
+            .get_config()
+            .ok_or_else(|| "No configuration set. Please set configuration first.".to_string())?;
+        selection.resolve(&config).map_err(|e| e.to_string())?;
+
+        let note = clean_note(note);
+        let backup_id = self.start_backup_run(dry_run_mode, backup_id, note.clone());
+        self.save_active_run(&backup_id, dry_run_mode, &selection, note.clone());
+        let run_id = backup_id.clone();
+        let state = self.clone();
+
+        rocket::tokio::spawn(async move {
+            log::info!("Backup task started with ID: {}", run_id);
+
+            let state_for_blocking = state.clone();
+            let result = rocket::tokio::task::spawn_blocking(move || {
+                crate::run_backup(
+                    &config,
+                    dry_run_mode,
+                    quiet,
+                    &selection,
+                    note.as_deref(),
+                    None,
+                    Some(&state_for_blocking),
+                    false,
+                    false,
+                    false,
+                )
+            })
+            .await;
+
+            match result {
+                Ok(Ok(report)) => {
+                    state.complete_backup_run(None, Some(report));
+                    state.notify_message("Backup completed successfully".to_string());
+                }
+                Ok(Err(e)) => {
+                    let error_msg = format!("Backup failed: {}", e);
+                    state.complete_backup_run(Some(error_msg.clone()), None);
+                    state.notify_message(error_msg);
+                }
+                Err(e) => {
+                    let error_msg = format!("Backup task panicked: {}", e);
+                    state.complete_backup_run(Some(error_msg.clone()), None);
+                    state.notify_message(error_msg);
+                }
+            }
+        });
+
+        Ok(LaunchOutcome::Started(backup_id))
+    }
+
+    /// Keep the started run in the database so a restart can report it as interrupted
+    fn save_active_run(
+        &self,
+        run_id: &str,
+        dry_run_mode: DryRunMode,
+        selection: &RunSelection,
+        note: Option<String>,
+    ) {
+        let now = Utc::now().timestamp();
+        let run = ActiveRun {
+            id: run_id.to_string(),
+            started_at: now,
+            dry_run_mode,
+            selection: selection.clone(),
+            note,
+            status: BackupStatus::Running,
+            files_processed: 0,
+            total_files: 0,
+            bytes_processed: 0,
+            updated_at: now,
+        };
+        match sqlite::save_active_run(&run) {
+            Ok(()) => {
+                *self.persisted_run.lock().unwrap() = Some((run.id, Instant::now()));
+            }
+            Err(e) => log::warn!("Failed to save the state of run {}: {}", run_id, e),
+        }
+    }
+
+    /// Report a run left over from before a restart as interrupted, and start it again if
+    /// `resume_interrupted_run` is set
+    ///
+    /// Must be called once at startup, from within the Tokio runtime, before any run starts.
+    pub fn recover_interrupted_run(&self) {
+        let run = match sqlite::take_active_run() {
+            Ok(Some(run)) => run,
+            Ok(None) => return,
+            Err(e) => {
+                log::warn!("Failed to check for an interrupted run: {}", e);
+                return;
+            }
+        };
+
+        let error = format!(
+            "Interrupted by a server restart while {} after {} of {} files",
+            run.status.as_str(),
+            run.files_processed,
+            run.total_files
+        );
+        log::warn!("Backup run {}: {}", run.id, error);
+        let now = Utc::now();
+        let started_at = DateTime::from_timestamp(run.started_at, 0).unwrap_or(now);
+
+        *self.current_run.lock().unwrap() = Some(BackupRunInfo {
+            id: run.id.clone(),
+            started_at,
+            completed_at: Some(now),
+            dry_run_mode: run.dry_run_mode,
+            error: Some(error.clone()),
+            note: run.note.clone(),
+        });
+        *self.progress.lock().unwrap() = Some(BackupProgress {
+            files_processed: run.files_processed,
+            total_files: run.total_files,
+            bytes_processed: Some(run.bytes_processed),
+            ..Default::default()
+        });
+        self.history.lock().unwrap().push_front(BackupHistoryEntry {
+            id: run.id.clone(),
+            started_at: started_at.to_rfc3339(),
+            completed_at: Some(now.to_rfc3339()),
+            status: BackupStatus::Interrupted,
+            files_processed: run.files_processed,
+            bytes_processed: Some(run.bytes_processed),
+            error: Some(error.clone()),
+            dry_run: run.dry_run_mode.is_dry_run(),
+            note: run.note.clone(),
+            report: None,
+        });
+
+        let record = RunRecord {
+            id: run.id.clone(),
+            started_at: run.started_at,
+            finished_at: run.updated_at,
+            status: BackupStatus::Interrupted,
+            dry_run: run.dry_run_mode.is_dry_run(),
+            files_copied: 0,
+            bytes_copied: run.bytes_processed,
+            errors: 0,
+            error: Some(error.clone()),
+            note: run.note.clone(),
+            checkpoint: false,
+            timing: None,
+            anomalies: Vec::new(),
+        };
+        if let Err(e) = sqlite::insert_run(&record) {
+            log::warn!("Failed to record interrupted run {}: {}", run.id, e);
+        }
+
+        let job = sqlite::select_job_for_run(&run.id).unwrap_or_else(|e| {
+            log::warn!("Failed to look up the job for run {}: {}", run.id, e);
+            None
+        });
+        if job.is_some() {
+            if let Err(e) =
+                sqlite::record_job_finished(&run.id, &BackupStatus::Interrupted, Some(&error))
+            {
+                log::warn!("Failed to record job result for run {}: {}", run.id, e);
+            }
+        }
+        self.metrics.lock().unwrap().record_run(
+            job.as_ref().map(|job| job.definition.name.as_str()),
+            &BackupStatus::Interrupted,
+            run.dry_run_mode.is_dry_run(),
+            None,
+            now.timestamp(),
+        );
+        self.set_status(BackupStatus::Interrupted);
+
+        if !self
+            .get_config()
+            .is_some_and(|config| config.resume_interrupted_run)
+        {
+            return;
+        }
+        let resumed = match &job {
+            Some(job) => self.run_job(job),
+            None => self
+                .launch_backup(
+                    run.dry_run_mode,
+                    true,
+                    run.selection,
+                    run.note.as_deref(),
+                    None,
+                )
+                .map(|outcome| match outcome {
+                    LaunchOutcome::Started(id) | LaunchOutcome::Existing { backup_id: id, .. } => {
+                        id
+                    }
+                }),
+        };
+        match resumed {
+            Ok(backup_id) => {
+                log::info!("Resumed interrupted run {} as {}", run.id, backup_id);
+                self.notify_message(format!("Resumed interrupted backup as run {}", backup_id));
+            }
+            Err(e) => log::warn!("Failed to resume interrupted run {}: {}", run.id, e),
+        }
+    }
+
+    /// Start a job's backup now and record it as the job's last result
+    pub fn run_job(&self, job: &Job) -> Result<String, String> {
+        if matches!(
+            self.get_status(),
+            BackupStatus::Running | BackupStatus::Paused
+        ) {
+            return Err("A backup is already running".to_string());
+        }
+
+        let started_at = Utc::now();
+        let next_run = job.definition.next_run_after(started_at);
+
+        // The run ID is fixed through a one-off idempotency key so the launch can be recorded
+        // before the run starts; otherwise a short run could finish before its job knows of it
+        let key = format!("job-{}-{}", job.id, Uuid::new_v4());
+        let backup_id = run_id_for_key(&key);
+        let mut result = JobResult {
+            backup_id: Some(backup_id.clone()),
+            status: BackupStatus::Running,
+            started_at: started_at.timestamp(),
+            finished_at: None,
+            error: None,
+        };
+        sqlite::record_job_launch(job.id, &result, next_run).map_err(|e| e.to_string())?;
+
+        match self.launch_backup(
+            job.definition.dry_run_mode(),
+            true,
+            job.definition.selection(),
+            None,
+            Some(&key),
+        ) {
+            Ok(_) => Ok(backup_id),
+            Err(message) => {
+                result.backup_id = None;
+                result.status = BackupStatus::Failed;
+                result.finished_at = Some(result.started_at);
+                result.error = Some(message.clone());
+                if let Err(e) = sqlite::record_job_launch(job.id, &result, next_run) {
+                    log::warn!(
+                        "Failed to record job result for '{}': {}",
+                        job.definition.name,
+                        e
+                    );
+                }
+                Err(message)
+            }
+        }
+    }
+
+    /// Start the backup of the trigger `token` belongs to, at most once per its interval
+    ///
+    /// Every request with a known token is kept in the audit log under the trigger's name.
+    /// Retries with the idempotency key of a run already started skip the interval.
+    pub fn fire_trigger(&self, token: &str, idempotency_key: Option<&str>) -> TriggerOutcome {
+        let trigger = self
+            .get_config()
+            .and_then(|config| config.trigger_for_token(token).cloned());
+        let Some(trigger) = trigger else {
+            log::warn!("Refused a trigger request with an unknown token");
+            return TriggerOutcome::UnknownToken;
+        };
+        let actor = format!("trigger:{}", trigger.name);
+        let audit = |action: &str, detail: String| {
+            if let Err(e) = record_audit_event(action, &actor, detail) {
+                log::warn!("Failed to audit trigger '{}': {}", trigger.name, e);
+            }
+        };
+
+        // Held until the run is launched, so simultaneous requests can't both pass the interval
+        let mut fired = self.trigger_fired.lock().unwrap();
+        let retried =
+            idempotency_key.is_some_and(|key| self.run_status(&run_id_for_key(key)).is_some());
+        let wait = fired.get(&trigger.name).and_then(|last| {
+            Duration::from_secs(trigger.min_interval_secs).checked_sub(last.elapsed())
+        });
+        if let Some(wait) = wait.filter(|_| !retried) {
+            let retry_after_secs = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+            audit(
+                "trigger_refused",
+                format!("Fired again within {} s", trigger.min_interval_secs),
+            );
+            return TriggerOutcome::TooSoon { retry_after_secs };
+        }
+
+        let dry_run_mode = if trigger.dry_run {
+            DryRunMode::Quick
+        } else {
+            DryRunMode::None
+        };
+        match self.launch_backup(
+            dry_run_mode,
+            true,
+            trigger.selection(),
+            Some(&format!("Triggered by {}", trigger.name)),
+            idempotency_key,
+        ) {
+            Ok(outcome) => {
+                if let LaunchOutcome::Started(backup_id) = &outcome {
+                    fired.insert(trigger.name.clone(), Instant::now());
+                    audit("backup_triggered", format!("Run {}", backup_id));
+                }
+                TriggerOutcome::Launched(outcome)
+            }
+            Err(message) => {
+                audit("trigger_refused", message.clone());
+                TriggerOutcome::Refused(message)
+            }
+        }
+    }
+
+    /// Start the longest overdue job unless a backup is running; other due jobs wait their turn
+    pub fn run_due_jobs(&self) {
+        if matches!(
+            self.get_status(),
+            BackupStatus::Running | BackupStatus::Paused
+        ) {
+            return;
+        }
+
+        let job = match sqlite::select_due_jobs(Utc::now().timestamp()) {
+            Ok(jobs) => jobs.into_iter().next(),
+            Err(e) => {
+                log::warn!("Failed to look up scheduled jobs: {}", e);
+                None
+            }
+        };
+
+        if let Some(job) = job {
+            log::info!("Starting scheduled job '{}'", job.definition.name);
+            if let Err(e) = self.run_job(&job) {
+                log::warn!(
+                    "Scheduled job '{}' did not start: {}",
+                    job.definition.name,
+                    e
+                );
+            }
+        }
+    }
+
+    /// Start scheduled jobs as they become due, for as long as the server runs
+    ///
+    /// A job that came due while the server was down runs once on the first check.
+    pub async fn run_job_scheduler(self) {
+        let mut interval = rocket::tokio::time::interval(JOB_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            self.run_due_jobs();
+        }
+    }
+
+    /// Prune the Logs table to the configuration's retention limits, returning how many went
+    pub fn prune_logs(&self) -> Result<usize, String> {
+        let config = self
+            .get_config()
+            .ok_or_else(|| "No configuration set".to_string())?;
+        apply_log_retention(&config).map_err(|e| e.to_string())
+    }
+
+    /// Prune the Logs table once an hour, for as long as the server runs
+    pub async fn run_log_pruner(self) {
+        let mut interval = rocket::tokio::time::interval(LOG_PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if self.get_config().is_some() {
+                if let Err(e) = self.prune_logs() {
+                    log::warn!("Failed to prune logs: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Whether the configuration's schedule is on, and when it next runs
+    pub fn schedule_response(&self) -> ScheduleResponse {
+        let schedule = self.config_schedule.lock().unwrap();
+        ScheduleResponse {
+            schedule: self.get_config().and_then(|config| config.schedule),
+            enabled: schedule.enabled,
+            next_run: schedule.next_run.map(|next| next.timestamp()),
+        }
+    }
+
+    /// Turn the configuration's schedule on or off until the server restarts
+    pub fn set_schedule_enabled(&self, enabled: bool) -> ScheduleResponse {
+        {
+            let mut schedule = self.config_schedule.lock().unwrap();
+            schedule.set_enabled(enabled);
+            let expression = self.get_config().and_then(|config| config.schedule);
+            schedule.advance(expression.as_deref(), Utc::now());
+        }
+        self.schedule_response()
+    }
+
+    /// Start a full backup for the configuration's schedule, skipping it if one is running
+    fn run_scheduled_backup(&self) {
+        match self.launch_backup(DryRunMode::None, true, RunSelection::default(), None, None) {
+            Ok(_) => log::info!("Started scheduled backup"),
+            Err(e) => {
+                log::warn!("Scheduled backup skipped: {}", e);
+                self.notify_message(format!("Scheduled backup skipped: {}", e));
+            }
+        }
+    }
+
+    /// Run backups on the configuration's `schedule`, for as long as the server runs
+    ///
+    /// With `run_on_startup`, a scheduled configuration also backs up once when the server
+    /// starts. The schedule is read on every check, so a changed or reloaded configuration
+    /// takes effect without a restart.
+    pub async fn run_config_scheduler(self) {
+        let startup = self.get_config().filter(|config| config.schedule.is_some());
+        if startup.is_some_and(|config| config.run_on_startup) {
+            log::info!("Running initial backup on startup...");
+            self.run_scheduled_backup();
+        }
+
+        let mut interval = rocket::tokio::time::interval(SCHEDULE_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let expression = self.get_config().and_then(|config| config.schedule);
+            let due = self
+                .config_schedule
+                .lock()
+                .unwrap()
+                .advance(expression.as_deref(), Utc::now());
+            if due {
+                self.run_scheduled_backup();
+            }
+        }
+    }
+
+    /// Scrub backups on the configuration's `scrub.schedule`, for as long as the server runs
+    ///
+    /// A scrub waits for a running backup to finish, and backups started during a scrub are
+    /// refused like during any other run.
+    pub async fn run_scrub_scheduler(self) {
+        let mut schedule = ConfigSchedule::new();
+        let mut interval = rocket::tokio::time::interval(SCHEDULE_POLL_INTERVAL);
+        loop {
+            interval.tick().await;
+            let Some(config) = self.get_config() else {
+                continue;
+            };
+            let expression = config.scrub.as_ref().map(|scrub| scrub.schedule.as_str());
+            if !schedule.advance(expression, Utc::now()) {
+                continue;
+            }
+            log::info!("Running scheduled scrub...");
+            let state = self.clone();
+            let _ =
+                rocket::tokio::task::spawn_blocking(move || match run_scheduled_scrub(&config) {
+                    Some(report) if !report.problems.is_empty() => state.notify_message(format!(
+                        "Scrub found {} damaged or missing backup(s); see the logs",
+                        report.problems.len()
+                    )),
+                    Some(_) => {}
+                    None => {
+                        state.notify_message("Scheduled scrub failed; see the logs".to_string())
+                    }
+                })
+                .await;
+        }
+    }
+
+    /// Ask the running backup to stop, or explain why there is nothing to stop
+    pub fn stop_backup(&self) -> Result<(), String> {
+        if !matches!(
+            self.get_status(),
+            BackupStatus::Running | BackupStatus::Paused
+        ) {
+            return Err("No backup is currently running".to_string());
+        }
+        self.request_stop();
+        Ok(())
+    }
+
+    /// Snapshot of the status, progress and current run for status endpoints
+    pub fn status_response(&self) -> StatusResponse {
+        let current_run = self.get_current_run();
+
+        StatusResponse {
+            status: self.get_status(),
+            progress: self.get_progress(),
+            started_at: current_run.as_ref().map(|r| r.started_at.to_rfc3339()),
+            completed_at: current_run
+                .as_ref()
+                .and_then(|r| r.completed_at.map(|dt| dt.to_rfc3339())),
+            error: current_run.as_ref().and_then(|r| r.error.clone()),
+            dry_run_mode: current_run
+                .as_ref()
+                .map(|r| format!("{:?}", r.dry_run_mode)),
+            destination_mismatches: sqlite::select_destination_mismatches().unwrap_or_else(|e| {
+                log::warn!("Failed to read destination mismatches: {}", e);
+                Vec::new()
+            }),
+            mass_change: self.mass_change.lock().unwrap().clone(),
+        }
+    }
+
     /// Get current backup run info
     pub fn get_current_run(&self) -> Option<BackupRunInfo> {
         self.current_run.lock().unwrap().clone()
@@ -267,11 +1202,6 @@ impl AppState {
         self.history.lock().unwrap().iter().cloned().collect()
     }
 
-    /// Clear backup history
-    pub fn clear_history(&self) {
-        self.history.lock().unwrap().clear();
-    }
-
     /// Subscribe to progress events
     pub fn subscribe_progress(&self) -> tokio::sync::broadcast::Receiver<ProgressEvent> {
         let (tx, rx) = tokio::sync::broadcast::channel(100);
@@ -315,3 +1245,243 @@ impl Default for AppState {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pause_and_resume_running_backup() {
+        let state = AppState::new();
+        assert!(state.pause_backup().is_err());
+
+        state.start_backup_run(DryRunMode::None, "run".to_string(), None);
+        state.pause_backup().unwrap();
+        assert_eq!(state.get_status(), BackupStatus::Paused);
+        assert!(state.resume_backup().is_ok());
+        assert_eq!(state.get_status(), BackupStatus::Running);
+        assert!(state.resume_backup().is_err());
+
+        // Stopping releases paused workers
+        state.pause_backup().unwrap();
+        state.stop_backup().unwrap();
+        state.wait_while_paused();
+        assert_eq!(state.get_status(), BackupStatus::Stopping);
+    }
+
+    #[test]
+    fn test_files_in_progress_read_backup_sizes() {
+        let state = AppState::new();
+        let dir = tempfile::TempDir::new().unwrap();
+        let first = dir.path().join("first.bin");
+        let second = dir.path().join("second.bin");
+        let first_copy = state.track_copy("photos", Path::new("/photos/first.bin"), &first, 100);
+        std::fs::write(&first, vec![0u8; 25]).unwrap();
+        let second_copy = state.track_copy("photos", Path::new("/photos/second.bin"), &second, 10);
+
+        let progress = state.files_in_progress(10);
+        assert_eq!(progress.total, 2);
+        assert_eq!(progress.files[0].file, "/photos/first.bin");
+        assert_eq!(progress.files[0].bytes_copied, 25);
+        assert_eq!(progress.files[0].percentage, 25.0);
+        // Not created yet
+        assert_eq!(progress.files[1].bytes_copied, 0);
+        assert_eq!(state.files_in_progress(1).files.len(), 1);
+
+        drop(first_copy);
+        drop(second_copy);
+        assert_eq!(state.files_in_progress(10).total, 0);
+    }
+
+    #[test]
+    fn test_repeated_idempotency_key_returns_existing_run() {
+        let state = AppState::new();
+        let backup_id = run_id_for_key("retry-me");
+        assert_eq!(backup_id, run_id_for_key("retry-me"));
+        assert_ne!(backup_id, run_id_for_key("another"));
+
+        state.start_backup_run(DryRunMode::None, backup_id.clone(), None);
+        let outcome = state.launch_backup(
+            DryRunMode::None,
+            true,
+            RunSelection::default(),
+            None,
+            Some("retry-me"),
+        );
+        assert_eq!(
+            outcome,
+            Ok(LaunchOutcome::Existing {
+                backup_id: backup_id.clone(),
+                status: BackupStatus::Running,
+            })
+        );
+
+        // Finished runs are still found through the history
+        state.complete_backup_run(None, None);
+        state.start_backup_run(DryRunMode::None, "later".to_string(), None);
+        assert_eq!(state.run_status(&backup_id), Some(BackupStatus::Completed));
+        assert_eq!(state.run_status("unknown"), None);
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn test_trigger_refuses_unknown_tokens_and_early_repeats() {
+        sqlite::set_db_pool("file::memory:?cache=shared").unwrap();
+        sqlite::setup_database().unwrap();
+        let state = AppState::new();
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "database_file": "",
+            "backup_sources": [],
+            "backup_destinations": [],
+            "triggers": [{"name": "ci", "token": "0123456789abcdef", "min_interval_secs": 600}]
+        }))
+        .unwrap();
+        state.set_config(config);
+        assert_eq!(
+            state.fire_trigger("0123456789abcdeX", None),
+            TriggerOutcome::UnknownToken
+        );
+
+        // A valid token is still refused while another backup runs
+        state.start_backup_run(DryRunMode::None, run_id_for_key("ci-build-7"), None);
+        assert_eq!(
+            state.fire_trigger("0123456789abcdef", None),
+            TriggerOutcome::Refused("A backup is already running".to_string())
+        );
+
+        state
+            .trigger_fired
+            .lock()
+            .unwrap()
+            .insert("ci".to_string(), Instant::now());
+        assert!(matches!(
+            state.fire_trigger("0123456789abcdef", None),
+            TriggerOutcome::TooSoon {
+                retry_after_secs: 591..=600
+            }
+        ));
+        // A retry of a run the trigger already started isn't held to the interval
+        assert!(matches!(
+            state.fire_trigger("0123456789abcdef", Some("ci-build-7")),
+            TriggerOutcome::Launched(LaunchOutcome::Existing { .. })
+        ));
+
+        let audit = sqlite::select_audit_events(10).unwrap();
+        let actions: Vec<(&str, &str)> = audit
+            .iter()
+            .map(|event| (event.action.as_str(), event.actor.as_str()))
+            .collect();
+        assert_eq!(
+            actions,
+            [
+                ("trigger_refused", "trigger:ci"),
+                ("trigger_refused", "trigger:ci")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_selected_profile_follows_config_changes() {
+        let state = AppState::new();
+        let config = |profiles: serde_json::Value| -> Config {
+            serde_json::from_value(serde_json::json!({
+                "database_file": "",
+                "max_threads": 8,
+                "backup_sources": [],
+                "backup_destinations": [],
+                "profiles": profiles
+            }))
+            .unwrap()
+        };
+        assert!(state.select_profile(Some("quick")).is_err());
+
+        state.set_config(config(serde_json::json!({"quick": {"max_threads": 2}})));
+        assert!(state.select_profile(Some("nightly")).is_err());
+        state.select_profile(Some("quick")).unwrap();
+        assert_eq!(state.get_profile().as_deref(), Some("quick"));
+        assert_eq!(state.get_config().unwrap().max_threads, 2);
+        assert_eq!(state.get_base_config().unwrap().max_threads, 8);
+
+        // A changed profile applies at once; a removed one is no longer selected
+        state.set_config(config(serde_json::json!({"quick": {"max_threads": 3}})));
+        assert_eq!(state.get_config().unwrap().max_threads, 3);
+        state.set_config(config(serde_json::json!({})));
+        assert_eq!(state.get_profile(), None);
+        assert_eq!(state.get_config().unwrap().max_threads, 8);
+    }
+
+    #[test]
+    fn test_config_schedule_runs_when_due() {
+        let at = |time: &str| {
+            DateTime::parse_from_rfc3339(time)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+        let daily = Some("0 0 2 * * *");
+        let mut schedule = ConfigSchedule::new();
+
+        assert!(!schedule.advance(daily, at("2025-03-01T10:00:00Z")));
+        assert_eq!(schedule.next_run, Some(at("2025-03-02T02:00:00Z")));
+        assert!(!schedule.advance(daily, at("2025-03-02T01:59:59Z")));
+        assert!(schedule.advance(daily, at("2025-03-02T02:00:00Z")));
+        assert_eq!(schedule.next_run, Some(at("2025-03-03T02:00:00Z")));
+
+        // A changed expression plans again instead of running
+        let hourly = Some("0 0 * * * *");
+        assert!(!schedule.advance(hourly, at("2025-03-03T05:00:00Z")));
+        assert_eq!(schedule.next_run, Some(at("2025-03-03T06:00:00Z")));
+
+        // Times missed while disabled don't run once it is enabled again
+        schedule.set_enabled(false);
+        assert!(!schedule.advance(hourly, at("2025-03-03T09:00:00Z")));
+        assert_eq!(schedule.next_run, None);
+        schedule.set_enabled(true);
+        assert!(!schedule.advance(hourly, at("2025-03-03T09:30:00Z")));
+        assert_eq!(schedule.next_run, Some(at("2025-03-03T10:00:00Z")));
+
+        assert!(!schedule.advance(None, at("2025-03-03T11:00:00Z")));
+        assert_eq!(schedule.next_run, None);
+    }
+
+    #[test]
+    #[serial_test::serial]
+    fn test_recover_interrupted_run() {
+        sqlite::set_db_pool("file::memory:?cache=shared").unwrap();
+        sqlite::setup_database().unwrap();
+        let state = AppState::new();
+        state.recover_interrupted_run();
+        assert_eq!(state.get_status(), BackupStatus::Idle);
+
+        sqlite::save_active_run(&ActiveRun {
+            id: "cut-short".to_string(),
+            started_at: 1000,
+            dry_run_mode: DryRunMode::None,
+            selection: RunSelection::default(),
+            note: Some("before OS upgrade".to_string()),
+            status: BackupStatus::Running,
+            files_processed: 3,
+            total_files: 9,
+            bytes_processed: 300,
+            updated_at: 1060,
+        })
+        .unwrap();
+        state.recover_interrupted_run();
+
+        assert_eq!(state.get_status(), BackupStatus::Interrupted);
+        let history = state.get_history();
+        assert_eq!(history[0].id, "cut-short");
+        assert_eq!(history[0].status, BackupStatus::Interrupted);
+        assert_eq!(history[0].files_processed, 3);
+        assert_eq!(history[0].note.as_deref(), Some("before OS upgrade"));
+        assert!(history[0]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("3 of 9 files"));
+        assert!(sqlite::take_active_run().unwrap().is_none());
+        let runs = sqlite::select_runs(100).unwrap();
+        assert!(runs.iter().any(|run| run.id == "cut-short"
+            && run.status == BackupStatus::Interrupted
+            && run.note.as_deref() == Some("before OS upgrade")));
+    }
+}
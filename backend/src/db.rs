@@ -0,0 +1,142 @@
+use crate::models::catalog_export::CatalogExport;
+use crate::models::config::Config;
+use crate::output::OutputFormat;
+use crate::repo::catalog::{import_catalog, select_catalog};
+use crate::repo::sqlite::SCHEMA_VERSION;
+use crate::service::hash::HASH_ALGORITHM;
+use anyhow::{bail, Context, Result};
+use clap::{Subcommand, ValueEnum};
+use log::warn;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// What `db` does with the catalog of source files and backups
+#[derive(Subcommand)]
+pub enum DbCommand {
+    /// Write the catalog with its schema version and hash algorithm, to move it to another machine
+    /// or look at it with other tools
+    Export {
+        #[arg(long = "format", value_enum, default_value = "json")]
+        format: CatalogFormat,
+        /// File to write instead of standard output
+        #[arg(short = 'o', long = "output")]
+        output: Option<PathBuf>,
+    },
+    /// Add the rows of an export to the catalog, updating rows recorded for the same paths
+    Import {
+        /// Export file, or `-` to read it from standard input
+        file: PathBuf,
+        /// Format of the export; taken from the file extension when not given
+        #[arg(long = "format", value_enum)]
+        format: Option<CatalogFormat>,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CatalogFormat {
+    Json,
+    Csv,
+}
+
+/// Export or import the catalog
+pub fn run(command: &DbCommand, config: &Config, output: OutputFormat) -> Result<()> {
+    match command {
+        DbCommand::Export {
+            format,
+            output: file,
+        } => {
+            let export = CatalogExport {
+                schema_version: SCHEMA_VERSION,
+                hash_algorithm: HASH_ALGORITHM.to_string(),
+                max_mebibytes_for_hash: config.max_mebibytes_for_hash,
+                hash_tail_mebibytes: config.hash_tail_mebibytes,
+                exported_at: chrono::Utc::now().timestamp(),
+                sources: select_catalog().context("Failed to read the backup catalog")?,
+            };
+            let contents = match format {
+                CatalogFormat::Json => serde_json::to_string_pretty(&export)? + "\n",
+                CatalogFormat::Csv => export.to_csv(),
+            };
+            match file {
+                Some(path) => fs::write(path, contents)
+                    .with_context(|| format!("Failed to write {}", path.display()))?,
+                None => print!("{}", contents),
+            }
+        }
+        DbCommand::Import { file, format } => {
+            let format = format.unwrap_or_else(|| format_of(file));
+            let contents = read_export(file)?;
+            let export = match format {
+                CatalogFormat::Json => serde_json::from_str(&contents)
+                    .context("Export is not valid catalog export JSON")?,
+                CatalogFormat::Csv => CatalogExport::from_csv(&contents)?,
+            };
+            check_compatible(&export, config)?;
+
+            let summary =
+                import_catalog(&export.sources).context("Failed to import the catalog")?;
+            output.print(&summary, || {
+                format!(
+                    "Imported {} source file(s) and {} backup(s)\n",
+                    summary.sources, summary.backups
+                )
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// CSV for `.csv` files, JSON otherwise
+fn format_of(file: &Path) -> CatalogFormat {
+    match file.extension() {
+        Some(extension) if extension.eq_ignore_ascii_case("csv") => CatalogFormat::Csv,
+        _ => CatalogFormat::Json,
+    }
+}
+
+fn read_export(path: &Path) -> Result<String> {
+    let mut contents = String::new();
+    if path.as_os_str() == "-" {
+        std::io::stdin()
+            .read_to_string(&mut contents)
+            .context("Failed to read the export from standard input")?;
+    } else {
+        contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+    }
+    Ok(contents)
+}
+
+/// Refuse exports this version can't read or whose hashes it can't compare with its own
+fn check_compatible(export: &CatalogExport, config: &Config) -> Result<()> {
+    if export.schema_version > SCHEMA_VERSION {
+        bail!(
+            "Export has schema version {}, newer than this version's {}",
+            export.schema_version,
+            SCHEMA_VERSION
+        );
+    }
+    if export.hash_algorithm != HASH_ALGORITHM {
+        bail!(
+            "Export hashes are {}, not {}",
+            export.hash_algorithm,
+            HASH_ALGORITHM
+        );
+    }
+    if export.max_mebibytes_for_hash != config.max_mebibytes_for_hash {
+        warn!(
+            "Export hashes cover the first {} MiB of each file but max_mebibytes_for_hash is {}; \
+             files hashed differently will be treated as changed",
+            export.max_mebibytes_for_hash, config.max_mebibytes_for_hash
+        );
+    }
+    if export.hash_tail_mebibytes != config.hash_tail_mebibytes {
+        warn!(
+            "Export hashes cover the last {} MiB of longer files but hash_tail_mebibytes is {}; \
+             files hashed differently will be treated as changed",
+            export.hash_tail_mebibytes, config.hash_tail_mebibytes
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,273 @@
+use crate::models::run_report::RunReport;
+use chrono::{DateTime, Utc};
+use std::sync::{Arc, Mutex};
+
+/// Where the CLI scheduler stands, for the tray icon of `--scheduler-tray`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SchedulerStatus {
+    pub next_run: Option<DateTime<Utc>>,
+    pub running: bool,
+    pub last_run: Option<RunResult>,
+}
+
+/// How a finished run ended, in the words shown on the desktop
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunResult {
+    pub finished_at: DateTime<Utc>,
+    pub failed: bool,
+    pub title: String,
+    pub summary: String,
+}
+
+impl RunResult {
+    pub fn new(dry_run: bool, outcome: &anyhow::Result<RunReport>) -> Self {
+        let kind = if dry_run { "Dry run" } else { "Backup" };
+        let (failed, title, summary) = match outcome {
+            Ok(report) => {
+                let copied: u64 = report.sources.iter().map(|s| s.files_copied).sum();
+                let errors: u64 = report.sources.iter().map(|s| s.errors).sum();
+                let failed = errors > 0;
+                let title = if failed {
+                    format!("{} finished with errors", kind)
+                } else {
+                    format!("{} finished", kind)
+                };
+                let mut summary = format!("{} files copied, {} errors", copied, errors);
+                if !report.locked_files.is_empty() {
+                    summary.push_str(&format!(", {} locked", report.locked_files.len()));
+                }
+                if !report.skipped_entries.is_empty() {
+                    summary.push_str(&format!(", {} unreadable", report.skipped_entries.len()));
+                }
+                if !report.changed_during_run.is_empty() {
+                    summary.push_str(&format!(
+                        ", {} changed mid-run",
+                        report.changed_during_run.len()
+                    ));
+                }
+                if let Some(exhausted) = &report.budget_exhausted {
+                    summary.push_str(&format!(", {} left for next run", exhausted.files_deferred));
+                }
+                (failed, title, summary)
+            }
+            Err(e) => (true, format!("{} failed", kind), format!("{:#}", e)),
+        };
+        RunResult {
+            finished_at: Utc::now(),
+            failed,
+            title,
+            summary,
+        }
+    }
+}
+
+/// Reports CLI runs on the desktop: as notifications with `--notify`, and to the scheduler's tray
+#[derive(Debug, Clone, Default)]
+pub struct DesktopFeedback {
+    notify: bool,
+    status: Arc<Mutex<SchedulerStatus>>,
+}
+
+impl DesktopFeedback {
+    pub fn new(notify: bool) -> Self {
+        DesktopFeedback {
+            notify,
+            status: Arc::default(),
+        }
+    }
+
+    /// Shared scheduler status, kept up to date for as long as runs are reported
+    #[cfg(feature = "tray")]
+    pub fn status(&self) -> Arc<Mutex<SchedulerStatus>> {
+        self.status.clone()
+    }
+
+    pub fn scheduled(&self, next_run: DateTime<Utc>) {
+        self.status.lock().unwrap().next_run = Some(next_run);
+    }
+
+    pub fn started(&self) {
+        self.status.lock().unwrap().running = true;
+    }
+
+    pub fn finished(&self, dry_run: bool, outcome: &anyhow::Result<RunReport>) {
+        let result = RunResult::new(dry_run, outcome);
+        if self.notify {
+            show_notification(&result);
+        }
+        let mut status = self.status.lock().unwrap();
+        status.running = false;
+        status.last_run = Some(result);
+    }
+}
+
+#[cfg(feature = "notify")]
+fn show_notification(result: &RunResult) {
+    if let Err(e) = platform::notify(&result.title, &result.summary) {
+        log::warn!("Failed to show desktop notification: {}", e);
+    }
+}
+
+#[cfg(not(feature = "notify"))]
+fn show_notification(_result: &RunResult) {}
+
+/// Desktop notifications through the notification service on Linux
+#[cfg(all(feature = "notify", target_os = "linux"))]
+mod platform {
+    use std::collections::HashMap;
+    use zbus::blocking::Connection;
+    use zbus::zvariant::Value;
+
+    /// How long the notification stays up, in milliseconds
+    const EXPIRE_TIMEOUT: i32 = 10_000;
+
+    pub fn notify(title: &str, body: &str) -> Result<(), String> {
+        let connection = Connection::session().map_err(|e| e.to_string())?;
+        connection
+            .call_method(
+                Some("org.freedesktop.Notifications"),
+                "/org/freedesktop/Notifications",
+                Some("org.freedesktop.Notifications"),
+                "Notify",
+                &(
+                    "RustyHashBackup",
+                    0u32,
+                    "",
+                    title,
+                    body,
+                    Vec::<&str>::new(),
+                    HashMap::<&str, Value>::new(),
+                    EXPIRE_TIMEOUT,
+                ),
+            )
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Desktop notifications through Notification Center on macOS
+#[cfg(all(feature = "notify", target_os = "macos"))]
+mod platform {
+    use std::process::Command;
+
+    pub fn notify(title: &str, body: &str) -> Result<(), String> {
+        let script = format!(
+            "display notification {} with title \"RustyHashBackup\" subtitle {}",
+            quoted(body),
+            quoted(title)
+        );
+        let status = Command::new("osascript")
+            .args(["-e", &script])
+            .status()
+            .map_err(|e| e.to_string())?;
+        status
+            .success()
+            .then_some(())
+            .ok_or_else(|| format!("osascript exited with {}", status))
+    }
+
+    fn quoted(text: &str) -> String {
+        format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+/// Toast notifications on Windows
+#[cfg(all(feature = "notify", windows))]
+mod platform {
+    use std::os::windows::process::CommandExt;
+    use std::process::Command;
+
+    /// Keeps PowerShell from opening a console window
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    /// Shows the toast as PowerShell, whose app ID every Windows install has registered
+    const APP_ID: &str =
+        "{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\\WindowsPowerShell\\v1.0\\powershell.exe";
+
+    pub fn notify(title: &str, body: &str) -> Result<(), String> {
+        let script = format!(
+            "$t = [Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime]::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02); \
+             $t.GetElementsByTagName('text')[0].AppendChild($t.CreateTextNode({})) > $null; \
+             $t.GetElementsByTagName('text')[1].AppendChild($t.CreateTextNode({})) > $null; \
+             [Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier({}).Show([Windows.UI.Notifications.ToastNotification]::new($t))",
+            quoted(title),
+            quoted(body),
+            quoted(APP_ID)
+        );
+        let status = Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+            .creation_flags(CREATE_NO_WINDOW)
+            .status()
+            .map_err(|e| e.to_string())?;
+        status
+            .success()
+            .then_some(())
+            .ok_or_else(|| format!("powershell exited with {}", status))
+    }
+
+    fn quoted(text: &str) -> String {
+        format!("'{}'", text.replace('\'', "''"))
+    }
+}
+
+#[cfg(all(
+    feature = "notify",
+    not(any(target_os = "linux", target_os = "macos", windows))
+))]
+mod platform {
+    pub fn notify(_title: &str, _body: &str) -> Result<(), String> {
+        Err("desktop notifications are not supported on this platform".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::run_report::SourceSummary;
+
+    fn report(errors: u64) -> RunReport {
+        RunReport {
+            sources: vec![SourceSummary {
+                name: "photos".to_string(),
+                files_prepared: 10,
+                files_copied: 4,
+                bytes_copied: 400,
+                files_moved: 0,
+                errors,
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_run_result() {
+        let result = RunResult::new(false, &Ok(report(0)));
+        assert!(!result.failed);
+        assert_eq!(result.title, "Backup finished");
+        assert_eq!(result.summary, "4 files copied, 0 errors");
+
+        let result = RunResult::new(true, &Ok(report(2)));
+        assert!(result.failed);
+        assert_eq!(result.title, "Dry run finished with errors");
+
+        let result = RunResult::new(false, &Err(anyhow::anyhow!("destination is full")));
+        assert!(result.failed);
+        assert_eq!(result.title, "Backup failed");
+        assert_eq!(result.summary, "destination is full");
+    }
+
+    #[test]
+    fn test_feedback_tracks_the_scheduler() {
+        let feedback = DesktopFeedback::new(false);
+        let next = Utc::now();
+        feedback.scheduled(next);
+        feedback.started();
+        assert!(feedback.status.lock().unwrap().running);
+
+        feedback.finished(false, &Ok(report(0)));
+        let status = feedback.status.lock().unwrap();
+        assert_eq!(status.next_run, Some(next));
+        assert!(!status.running);
+        assert!(!status.last_run.as_ref().unwrap().failed);
+    }
+}
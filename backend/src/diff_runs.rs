@@ -0,0 +1,77 @@
+use crate::models::run_diff::RunDiff;
+use crate::output::OutputFormat;
+use crate::service::run_diff::diff_runs;
+use anyhow::{Context, Result};
+use clap::Args;
+use std::fmt::Write;
+
+/// The two runs `diff-runs` compares, as listed by `list runs`
+#[derive(Args)]
+pub struct DiffRunsArgs {
+    run: String,
+    other_run: String,
+    /// Most paths listed for each of the new, changed and deleted files
+    #[arg(short = 'n', long = "limit", default_value_t = 20)]
+    limit: usize,
+}
+
+/// Print the files that appeared, changed or disappeared between two runs
+pub fn run(args: &DiffRunsArgs, output: OutputFormat) -> Result<()> {
+    let diff =
+        diff_runs(&args.run, &args.other_run, args.limit).context("Failed to compare runs")?;
+    output.print(&diff, || describe_diff(&diff))
+}
+
+fn describe_diff(diff: &RunDiff) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{} ({} file(s)) -> {} ({} file(s))",
+        diff.from_run, diff.files_before, diff.to_run, diff.files_after
+    );
+    for (label, count, paths) in [
+        ("new", diff.new_files, &diff.new),
+        ("changed", diff.changed_files, &diff.changed),
+        ("deleted", diff.deleted_files, &diff.deleted),
+    ] {
+        let _ = writeln!(out, "{} {}", count, label);
+        for path in paths {
+            let _ = writeln!(out, "  {}", path);
+        }
+        if count > paths.len() as u64 {
+            let _ = writeln!(out, "  ... and {} more", count - paths.len() as u64);
+        }
+    }
+    let _ = writeln!(
+        out,
+        "{:.1}% of the earlier run's files changed or deleted",
+        diff.changed_percent
+    );
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_diff_notes_paths_left_out() {
+        let text = describe_diff(&RunDiff {
+            from_run: "first".to_string(),
+            to_run: "last".to_string(),
+            files_before: 4,
+            files_after: 4,
+            new_files: 0,
+            changed_files: 3,
+            deleted_files: 0,
+            changed_percent: 75.0,
+            new: Vec::new(),
+            changed: vec!["/photos/a.jpg".to_string()],
+            deleted: Vec::new(),
+        });
+
+        assert!(text.starts_with("first (4 file(s)) -> last (4 file(s))\n0 new\n"));
+        assert!(text.contains("3 changed\n  /photos/a.jpg\n  ... and 2 more\n"));
+        assert!(text.ends_with("75.0% of the earlier run's files changed or deleted\n"));
+    }
+}
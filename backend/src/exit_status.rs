@@ -0,0 +1,81 @@
+use crate::models::run_report::RunReport;
+use crate::utils::cancel::is_cancel_requested;
+use std::process::ExitCode;
+use thiserror::Error;
+
+/// Exit code of a command that failed
+pub const FATAL: u8 = 1;
+/// Exit code of a backup that finished but failed to back up some files
+pub const COMPLETED_WITH_ERRORS: u8 = 2;
+/// Exit code of a backup cancelled with Ctrl+C before it got to every file
+pub const CANCELLED: u8 = 3;
+
+/// How a CLI backup ended short of success, for its own exit code
+#[derive(Error, Debug)]
+pub enum RunEnded {
+    #[error("Backup completed with {0} error(s); some files were not backed up")]
+    WithErrors(u64),
+    #[error("Backup cancelled before every file was backed up")]
+    Cancelled,
+}
+
+impl RunEnded {
+    /// How the run that gave `report` ended, if short of success
+    pub fn check(report: &RunReport) -> Result<(), RunEnded> {
+        let errors: u64 = report.sources.iter().map(|s| s.errors).sum();
+        if is_cancel_requested() {
+            Err(RunEnded::Cancelled)
+        } else if errors > 0 {
+            Err(RunEnded::WithErrors(errors))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Exit code for what the CLI returned: 0 on success, 2 or 3 for a backup that ended short of
+/// it, and 1 for any other failure
+pub fn exit_code(result: &anyhow::Result<()>) -> ExitCode {
+    let code = match result {
+        Ok(()) => 0,
+        Err(e) => match e.downcast_ref::<RunEnded>() {
+            Some(RunEnded::WithErrors(_)) => COMPLETED_WITH_ERRORS,
+            Some(RunEnded::Cancelled) => CANCELLED,
+            None => FATAL,
+        },
+    };
+    ExitCode::from(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::run_report::SourceSummary;
+
+    #[test]
+    fn test_runs_ending_short_of_success_get_their_own_exit_code() {
+        let mut report = RunReport::default();
+        assert!(RunEnded::check(&report).is_ok());
+        report.sources.push(SourceSummary {
+            name: "photos".to_string(),
+            errors: 4,
+            ..Default::default()
+        });
+        let ended = RunEnded::check(&report).unwrap_err();
+        assert!(matches!(ended, RunEnded::WithErrors(4)));
+
+        assert_eq!(exit_code(&Ok(())), ExitCode::from(0));
+        assert_eq!(
+            exit_code(&Err(anyhow::Error::new(ended).context("Scheduled run"))),
+            ExitCode::from(COMPLETED_WITH_ERRORS)
+        );
+        assert_eq!(
+            exit_code(&Err(RunEnded::Cancelled.into())),
+            ExitCode::from(CANCELLED)
+        );
+        assert_eq!(
+            exit_code(&Err(anyhow::anyhow!("Failed to load configuration"))),
+            ExitCode::from(FATAL)
+        );
+    }
+}
@@ -0,0 +1,295 @@
+use crate::api_state::{AppState, LaunchOutcome};
+use crate::models::api;
+use crate::models::dry_run_mode::DryRunMode;
+use crate::models::run_selection::RunSelection;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::{BroadcastStream, ReceiverStream};
+use tokio_stream::{Stream, StreamExt};
+use tonic::{Request, Response, Status, Streaming};
+
+/// Types and service traits generated from proto/control.proto
+pub mod proto {
+    tonic::include_proto!("rustyhashbackup.control.v1");
+}
+
+use proto::backup_control_server::{BackupControl, BackupControlServer};
+use proto::{control_command, control_event};
+
+/// Buffered events per Control stream before the sender waits for the client
+const CONTROL_STREAM_BUFFER: usize = 64;
+
+/// Serve the control service on `addr` until the process exits
+pub async fn serve(addr: SocketAddr, state: AppState) -> Result<(), tonic::transport::Error> {
+    log::info!("gRPC control service listening on {}", addr);
+    tonic::transport::Server::builder()
+        .add_service(BackupControlServer::new(ControlService { state }))
+        .serve(addr)
+        .await
+}
+
+struct ControlService {
+    state: AppState,
+}
+
+type EventStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl BackupControl for ControlService {
+    async fn start_backup(
+        &self,
+        request: Request<proto::StartBackupRequest>,
+    ) -> Result<Response<proto::CommandResult>, Status> {
+        Ok(Response::new(start(&self.state, request.into_inner())))
+    }
+
+    async fn stop_backup(
+        &self,
+        _request: Request<proto::StopBackupRequest>,
+    ) -> Result<Response<proto::CommandResult>, Status> {
+        Ok(Response::new(stop(&self.state)))
+    }
+
+    async fn get_status(
+        &self,
+        _request: Request<proto::GetStatusRequest>,
+    ) -> Result<Response<proto::StatusResponse>, Status> {
+        Ok(Response::new(self.state.status_response().into()))
+    }
+
+    type WatchProgressStream = EventStream<proto::ProgressEvent>;
+
+    // Stream items are `Result<_, tonic::Status>` by the generated trait's definition
+    #[allow(clippy::result_large_err)]
+    async fn watch_progress(
+        &self,
+        _request: Request<proto::WatchProgressRequest>,
+    ) -> Result<Response<Self::WatchProgressStream>, Status> {
+        let snapshot = tokio_stream::once(Ok(current_event(&self.state)));
+        // A subscriber that falls behind skips the events it missed rather than failing
+        let updates = BroadcastStream::new(self.state.subscribe_progress())
+            .filter_map(|event| event.ok())
+            .map(|event| Ok(event.into()));
+
+        Ok(Response::new(Box::pin(snapshot.chain(updates))))
+    }
+
+    type ControlStream = EventStream<proto::ControlEvent>;
+
+    async fn control(
+        &self,
+        request: Request<Streaming<proto::ControlCommand>>,
+    ) -> Result<Response<Self::ControlStream>, Status> {
+        let mut commands = request.into_inner();
+        let mut events = self.state.subscribe_progress();
+        let state = self.state.clone();
+        let (tx, rx) = mpsc::channel(CONTROL_STREAM_BUFFER);
+
+        rocket::tokio::spawn(async move {
+            loop {
+                let event = rocket::tokio::select! {
+                    command = commands.message() => match command {
+                        Ok(Some(command)) => control_event::Event::Result(run_command(&state, command)),
+                        Ok(None) => break,
+                        Err(status) => {
+                            let _ = tx.send(Err(status)).await;
+                            break;
+                        }
+                    },
+                    event = events.recv() => match event {
+                        Ok(event) => control_event::Event::Progress(event.into()),
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => break,
+                    },
+                };
+
+                let event = proto::ControlEvent { event: Some(event) };
+                if tx.send(Ok(event)).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+}
+
+fn run_command(state: &AppState, command: proto::ControlCommand) -> proto::CommandResult {
+    match command.command {
+        Some(control_command::Command::Start(request)) => start(state, request),
+        Some(control_command::Command::Stop(_)) => stop(state),
+        None => proto::CommandResult {
+            success: false,
+            message: "Empty command".to_string(),
+            ..Default::default()
+        },
+    }
+}
+
+fn start(state: &AppState, request: proto::StartBackupRequest) -> proto::CommandResult {
+    let dry_run_mode = match request.dry_run_mode() {
+        proto::DryRunMode::None => DryRunMode::None,
+        proto::DryRunMode::Quick => DryRunMode::Quick,
+        proto::DryRunMode::Full => DryRunMode::Full,
+    };
+    let selection = RunSelection {
+        source: request.source,
+        path: request.path,
+        ..Default::default()
+    };
+
+    match state.launch_backup(
+        dry_run_mode,
+        request.quiet,
+        selection,
+        request.note.as_deref(),
+        request.idempotency_key.as_deref(),
+    ) {
+        Ok(LaunchOutcome::Started(backup_id)) => proto::CommandResult {
+            success: true,
+            message: format!("Backup started with mode: {:?}", dry_run_mode),
+            backup_id: Some(backup_id),
+            status: Some(proto::BackupStatus::Running.into()),
+        },
+        Ok(LaunchOutcome::Existing { backup_id, status }) => proto::CommandResult {
+            success: true,
+            message: "A backup was already started with this idempotency key".to_string(),
+            backup_id: Some(backup_id),
+            status: Some(proto::BackupStatus::from(status).into()),
+        },
+        Err(message) => proto::CommandResult {
+            success: false,
+            message,
+            ..Default::default()
+        },
+    }
+}
+
+fn stop(state: &AppState) -> proto::CommandResult {
+    let (success, message) = match state.stop_backup() {
+        Ok(()) => (
+            true,
+            "Stop signal sent. Backup will stop after current operation.".to_string(),
+        ),
+        Err(message) => (false, message),
+    };
+    proto::CommandResult {
+        success,
+        message,
+        ..Default::default()
+    }
+}
+
+fn current_event(state: &AppState) -> proto::ProgressEvent {
+    proto::ProgressEvent {
+        status: proto::BackupStatus::from(state.get_status()).into(),
+        progress: state.get_progress().map(Into::into),
+        message: None,
+    }
+}
+
+impl From<api::BackupStatus> for proto::BackupStatus {
+    fn from(status: api::BackupStatus) -> Self {
+        match status {
+            api::BackupStatus::Idle => proto::BackupStatus::Idle,
+            api::BackupStatus::Running => proto::BackupStatus::Running,
+            api::BackupStatus::Paused => proto::BackupStatus::Paused,
+            api::BackupStatus::Stopping => proto::BackupStatus::Stopping,
+            api::BackupStatus::Failed => proto::BackupStatus::Failed,
+            api::BackupStatus::Completed => proto::BackupStatus::Completed,
+            api::BackupStatus::Interrupted => proto::BackupStatus::Interrupted,
+        }
+    }
+}
+
+impl From<api::BackupProgress> for proto::BackupProgress {
+    fn from(progress: api::BackupProgress) -> Self {
+        proto::BackupProgress {
+            phase: progress.phase.into(),
+            phase_description: progress.phase_description,
+            files_processed: progress.files_processed,
+            total_files: progress.total_files,
+            discovery_complete: progress.discovery_complete,
+            bytes_processed: progress.bytes_processed,
+            total_bytes: progress.total_bytes,
+            percentage: progress.percentage,
+            current_file: progress.current_file,
+            current_source: progress.current_source,
+            elapsed_seconds: progress.elapsed_seconds,
+            bytes_per_second: progress.bytes_per_second,
+            eta_seconds: progress.eta_seconds,
+        }
+    }
+}
+
+impl From<api::ProgressEvent> for proto::ProgressEvent {
+    fn from(event: api::ProgressEvent) -> Self {
+        proto::ProgressEvent {
+            status: proto::BackupStatus::from(event.status).into(),
+            progress: event.progress.map(Into::into),
+            message: event.message,
+        }
+    }
+}
+
+impl From<api::StatusResponse> for proto::StatusResponse {
+    fn from(status: api::StatusResponse) -> Self {
+        proto::StatusResponse {
+            status: proto::BackupStatus::from(status.status).into(),
+            progress: status.progress.map(Into::into),
+            started_at: status.started_at,
+            completed_at: status.completed_at,
+            error: status.error,
+            dry_run_mode: status.dry_run_mode,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_event_conversion() {
+        let event = api::ProgressEvent {
+            status: api::BackupStatus::Running,
+            progress: Some(api::BackupProgress {
+                phase: 3,
+                files_processed: 4,
+                total_files: 10,
+                bytes_processed: Some(2048),
+                percentage: 40.0,
+                ..Default::default()
+            }),
+            message: Some("Copying".to_string()),
+        };
+
+        let converted = proto::ProgressEvent::from(event);
+
+        assert_eq!(converted.status(), proto::BackupStatus::Running);
+        let progress = converted.progress.unwrap();
+        assert_eq!(progress.phase, 3);
+        assert_eq!(progress.total_files, 10);
+        assert_eq!(progress.bytes_processed, Some(2048));
+        assert_eq!(progress.total_bytes, None);
+        assert_eq!(converted.message.as_deref(), Some("Copying"));
+    }
+
+    #[rocket::async_test]
+    async fn test_stop_without_running_backup_fails() {
+        let service = ControlService {
+            state: AppState::new(),
+        };
+
+        let result = service
+            .stop_backup(Request::new(proto::StopBackupRequest {}))
+            .await
+            .unwrap()
+            .into_inner();
+
+        assert!(!result.success);
+        assert_eq!(result.message, "No backup is currently running");
+    }
+}
@@ -0,0 +1,350 @@
+use crate::models::config::Config;
+use crate::models::config_validator::check_config;
+use crate::models::validation_issue::ValidationIssue;
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// Choices of the schedule question and their cron expressions
+const SCHEDULES: [(&str, Option<&str>); 3] = [
+    ("Only when I start them", None),
+    ("Every night at 02:00", Some("0 0 2 * * *")),
+    ("Every hour", Some("0 0 * * * *")),
+];
+
+#[derive(Args)]
+pub struct InitArgs {
+    /// Replace the file at the --config path without asking
+    #[arg(long = "force")]
+    force: bool,
+}
+
+/// Ask for sources, destinations, a schedule and a database file, and write them to `config_file`
+pub fn run(args: &InitArgs, config_file: &str) -> Result<()> {
+    let stdin = std::io::stdin();
+    let mut wizard = Wizard {
+        input: stdin.lock(),
+        output: std::io::stdout(),
+    };
+    wizard.run(
+        Path::new(config_file.trim_matches(|c| c == '"' || c == '\'')),
+        args.force,
+    )
+}
+
+struct Wizard<R, W> {
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> Wizard<R, W> {
+    fn run(&mut self, config_file: &Path, force: bool) -> Result<()> {
+        let config_file = std::path::absolute(config_file)
+            .with_context(|| format!("Failed to resolve {}", config_file.display()))?;
+        writeln!(
+            self.output,
+            "RustyHashBackup setup\n\
+             Answer a few questions to write a configuration to {}.\n\
+             Press Enter to take the answer in [brackets].\n",
+            config_file.display()
+        )?;
+        if config_file.exists()
+            && !force
+            && !self.confirm(
+                &format!("{} already exists. Replace it?", config_file.display()),
+                false,
+            )?
+        {
+            bail!("Kept the existing {}", config_file.display());
+        }
+
+        let config = self.ask_config(&config_file)?;
+        if let Some(parent) = config_file.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        fs::write(&config_file, serde_json::to_string_pretty(&config)? + "\n")
+            .with_context(|| format!("Failed to write {}", config_file.display()))?;
+
+        writeln!(
+            self.output,
+            "\nWrote {0}\n\
+             Back up once now with:  RustyHashBackUp --config \"{0}\" --once\n\
+             Or open the web UI with: RustyHashBackUp --config \"{0}\" --api",
+            config_file.display()
+        )?;
+        Ok(())
+    }
+
+    /// The answers, as the configuration file's JSON
+    fn ask_config(&mut self, config_file: &Path) -> Result<Value> {
+        let mut draft = json!({
+            "database_file": "",
+            "backup_sources": [],
+            "backup_destinations": [],
+        });
+
+        let default_database = config_file
+            .with_file_name("rustyhashbackup.db")
+            .display()
+            .to_string();
+        loop {
+            let answer = self.ask(
+                "Database file that records what has been backed up",
+                Some(&default_database),
+            )?;
+            let path = resolve_path(&answer)?;
+            if let Some(parent) = path.parent() {
+                self.offer_to_create(parent)?;
+            }
+            draft["database_file"] = json!(path);
+            if self.accept(&draft, "database_file")? {
+                break;
+            }
+        }
+
+        self.ask_folders(
+            &mut draft,
+            "backup_sources",
+            "Folder to back up",
+            |path| json!({ "parent_directory": path }),
+        )?;
+        self.ask_folders(
+            &mut draft,
+            "backup_destinations",
+            "Folder or drive to keep the backups in",
+            |path| json!(path),
+        )?;
+
+        writeln!(self.output, "When should backups run?")?;
+        for (idx, (label, _)) in SCHEDULES.iter().enumerate() {
+            writeln!(self.output, "  {}) {}", idx + 1, label)?;
+        }
+        writeln!(
+            self.output,
+            "  {}) On a cron schedule of my own",
+            SCHEDULES.len() + 1
+        )?;
+        loop {
+            let answer = self.ask("Choice", Some("1"))?;
+            let schedule = match answer.parse::<usize>() {
+                Ok(choice) if (1..=SCHEDULES.len()).contains(&choice) => {
+                    SCHEDULES[choice - 1].1.map(str::to_string)
+                }
+                Ok(choice) if choice == SCHEDULES.len() + 1 => Some(self.ask(
+                    "Cron expression with seconds, e.g. 0 30 1 * * Sat for Saturdays at 01:30",
+                    None,
+                )?),
+                _ => {
+                    writeln!(
+                        self.output,
+                        "  Answer with a number from 1 to {}",
+                        SCHEDULES.len() + 1
+                    )?;
+                    continue;
+                }
+            };
+            match schedule {
+                Some(schedule) => draft["schedule"] = json!(schedule),
+                None => {
+                    draft.as_object_mut().unwrap().remove("schedule");
+                }
+            }
+            if self.accept(&draft, "schedule")? {
+                break;
+            }
+        }
+
+        Ok(draft)
+    }
+
+    /// Ask for folders until an empty answer, keeping the ones that pass validation
+    fn ask_folders(
+        &mut self,
+        draft: &mut Value,
+        field: &str,
+        question: &str,
+        entry: impl Fn(&Path) -> Value,
+    ) -> Result<()> {
+        loop {
+            let count = draft[field].as_array().map_or(0, Vec::len);
+            let answer = if count == 0 {
+                self.ask(question, None)?
+            } else {
+                self.ask(
+                    &format!("Another {} (Enter when done)", question.to_lowercase()),
+                    Some(""),
+                )?
+            };
+            if answer.is_empty() {
+                if count == 0 {
+                    writeln!(self.output, "  At least one is needed")?;
+                    continue;
+                }
+                writeln!(self.output)?;
+                return Ok(());
+            }
+
+            let path = resolve_path(&answer)?;
+            if field == "backup_destinations" {
+                self.offer_to_create(&path)?;
+            }
+            let mut candidate = draft.clone();
+            candidate[field].as_array_mut().unwrap().push(entry(&path));
+            if self.accept(&candidate, &format!("{}[{}]", field, count))? {
+                *draft = candidate;
+            }
+        }
+    }
+
+    /// Show what validation finds in `field` of the draft; true if it has no errors
+    fn accept(&mut self, draft: &Value, field: &str) -> Result<bool> {
+        let issues = issues_in(draft, field)?;
+        for issue in &issues {
+            writeln!(self.output, "  {}", issue.message.replace('\n', "\n  "))?;
+        }
+        Ok(!issues.iter().any(ValidationIssue::is_error))
+    }
+
+    fn offer_to_create(&mut self, dir: &Path) -> Result<()> {
+        if dir.as_os_str().is_empty()
+            || dir.exists()
+            || !self.confirm(
+                &format!("{} doesn't exist. Create it?", dir.display()),
+                true,
+            )?
+        {
+            return Ok(());
+        }
+        if let Err(e) = fs::create_dir_all(dir) {
+            writeln!(self.output, "  Failed to create {}: {}", dir.display(), e)?;
+        }
+        Ok(())
+    }
+
+    fn confirm(&mut self, question: &str, default: bool) -> Result<bool> {
+        loop {
+            let hint = if default { "Y/n" } else { "y/N" };
+            let answer = self.ask(&format!("{} [{}]", question, hint), None)?;
+            match answer.to_lowercase().as_str() {
+                "" => return Ok(default),
+                "y" | "yes" => return Ok(true),
+                "n" | "no" => return Ok(false),
+                _ => writeln!(self.output, "  Answer y or n")?,
+            }
+        }
+    }
+
+    /// One line of input, or `default` for an empty one
+    fn ask(&mut self, question: &str, default: Option<&str>) -> Result<String> {
+        match default {
+            Some("") | None => write!(self.output, "{}: ", question)?,
+            Some(default) => write!(self.output, "{} [{}]: ", question, default)?,
+        }
+        self.output.flush()?;
+
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            bail!("Input ended before the setup was finished; nothing was written");
+        }
+        let answer = line.trim();
+        Ok(match default {
+            Some(default) if answer.is_empty() => default.to_string(),
+            _ => answer.to_string(),
+        })
+    }
+}
+
+/// Findings of the configuration validator in `field` of the draft, or in fields under it
+fn issues_in(draft: &Value, field: &str) -> Result<Vec<ValidationIssue>> {
+    let config: Config =
+        serde_json::from_value(draft.clone()).context("The answers don't make a configuration")?;
+    Ok(check_config(&config)
+        .into_iter()
+        .filter(|issue| {
+            issue.field == field
+                || issue.field.starts_with(&format!("{}.", field))
+                || issue.field.starts_with(&format!("{}[", field))
+        })
+        .collect())
+}
+
+/// An absolute path for an answer, which may be quoted by drag and drop or start with `~`
+fn resolve_path(answer: &str) -> Result<PathBuf> {
+    let answer = answer.trim_matches(|c| c == '"' || c == '\'');
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+    let path = match (answer.strip_prefix("~"), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+            PathBuf::from(home).join(rest.trim_start_matches(['/', '\\']))
+        }
+        _ => PathBuf::from(answer),
+    };
+    std::path::absolute(&path).with_context(|| format!("Failed to resolve {}", answer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_wizard_writes_validated_answers() {
+        let dir = TempDir::new().unwrap();
+        let photos = dir.path().join("photos");
+        fs::create_dir(&photos).unwrap();
+        let usb = dir.path().join("usb");
+        let config_file = dir.path().join("setup").join("config.json");
+
+        let answers = [
+            "",                                                // database next to the config file
+            "y",                                               // create its folder
+            "",                                                // a source is required
+            &dir.path().join("missing").display().to_string(), // refused, doesn't exist
+            &format!("\"{}\"", photos.display()),              // quoted by drag and drop
+            "",                                                // no more sources
+            &usb.display().to_string(),
+            "y",           // create the destination
+            "",            // no more destinations
+            "4",           // own cron expression
+            "every night", // refused, not cron
+            "2",           // every night at 02:00
+        ];
+        let input = answers.join("\n") + "\n";
+        let mut output = Vec::new();
+        let mut wizard = Wizard {
+            input: input.as_bytes(),
+            output: &mut output,
+        };
+        wizard.run(&config_file, false).unwrap();
+
+        let written: Value =
+            serde_json::from_str(&fs::read_to_string(&config_file).unwrap()).unwrap();
+        assert_eq!(
+            written,
+            json!({
+                "database_file": config_file.with_file_name("rustyhashbackup.db"),
+                "backup_sources": [{ "parent_directory": photos }],
+                "backup_destinations": [usb],
+                "schedule": "0 0 2 * * *",
+            })
+        );
+        assert!(usb.is_dir());
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("At least one is needed"), "{}", output);
+        assert!(output.contains("does not exist"), "{}", output);
+        assert!(output.contains("Invalid cron expression"), "{}", output);
+
+        // An existing file is only replaced when the user agrees
+        let mut wizard = Wizard {
+            input: "\n".as_bytes(),
+            output: Vec::new(),
+        };
+        assert!(wizard.run(&config_file, false).is_err());
+        assert!(fs::read_to_string(&config_file)
+            .unwrap()
+            .contains("0 0 2 * * *"));
+    }
+}
@@ -0,0 +1,304 @@
+use crate::models::backup_row::BackupRow;
+use crate::models::catalog_file::CatalogFile;
+use crate::models::config::Config;
+use crate::models::coverage::CoverageReport;
+use crate::models::run_record::RunRecord;
+use crate::models::source_row::SourceRow;
+use crate::output::OutputFormat;
+use crate::repo::catalog::{select_backups_of_source, select_source, select_sources};
+use crate::repo::sqlite::{format_bytes, select_runs};
+use crate::service::coverage::build_coverage_report;
+use crate::service::regression::describe;
+use anyhow::{bail, Context, Result};
+use chrono::{Local, TimeZone};
+use clap::Subcommand;
+use serde::Serialize;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What `list` prints from the catalog database
+#[derive(Subcommand)]
+pub enum ListCommand {
+    /// Every recorded source file with its hash and number of backups
+    Sources {
+        /// Only list files in this directory or below it
+        #[arg(long = "under")]
+        under: Option<String>,
+    },
+    /// Where a source file is backed up, with its recorded hash and modification time
+    Backups { file: PathBuf },
+    /// Recent runs, newest first
+    Runs {
+        #[arg(short = 'n', long = "limit", default_value_t = 20)]
+        limit: usize,
+    },
+    /// Source files without a valid backup in every destination their source is routed to
+    Coverage {
+        /// Only check this destination, by name or path
+        #[arg(long = "destination")]
+        destination: Option<String>,
+    },
+}
+
+/// Print what the catalog database knows; fails if `list backups` names an unknown file
+pub fn run(command: &ListCommand, config: &Config, output: OutputFormat) -> Result<()> {
+    match command {
+        ListCommand::Sources { under } => {
+            let sources =
+                select_sources(under.as_deref()).context("Failed to read source files")?;
+            let listed: Vec<ListedSource> = sources
+                .iter()
+                .map(|(source, backups)| ListedSource::new(source, *backups))
+                .collect();
+            output.print(&listed, || {
+                let mut out = String::new();
+                for (source, backups) in &sources {
+                    describe_source(&mut out, source, *backups);
+                }
+                let _ = writeln!(out, "{} source file(s)", sources.len());
+                out
+            })
+        }
+        ListCommand::Backups { file } => {
+            let Some(source) = find_source(file)? else {
+                bail!("{} is not in the backup catalog", file.display());
+            };
+            let backups =
+                select_backups_of_source(source.id).context("Failed to read backup records")?;
+            let text = describe_backups(&source, &backups);
+            output.print(&CatalogFile::new(source, &backups), || text)
+        }
+        ListCommand::Runs { limit } => {
+            let runs = select_runs(*limit).context("Failed to read runs")?;
+            output.print(&runs, || {
+                let mut out = String::new();
+                for run in &runs {
+                    describe_run(&mut out, run);
+                }
+                if runs.is_empty() {
+                    let _ = writeln!(out, "No runs recorded yet");
+                }
+                out
+            })
+        }
+        ListCommand::Coverage { destination } => {
+            if let Some(destination) = destination {
+                if !config
+                    .backup_destinations
+                    .iter()
+                    .any(|d| d.matches(destination))
+                {
+                    bail!("No destination named {} is configured", destination);
+                }
+            }
+            let report = build_coverage_report(config, destination.as_deref())
+                .context("Failed to read the backup catalog")?;
+            output.print(&report, || describe_coverage(&report))
+        }
+    }
+}
+
+/// A recorded source file as `list sources` prints it in JSON
+#[derive(Serialize)]
+struct ListedSource {
+    path: String,
+    hash: String,
+    file_size: u64,
+    /// Unix timestamp of the modification time last recorded
+    last_modified: u64,
+    backups: u64,
+}
+
+impl ListedSource {
+    fn new(source: &SourceRow, backups: u64) -> Self {
+        ListedSource {
+            path: Path::new(&source.file_path)
+                .join(&source.file_name)
+                .to_string_lossy()
+                .to_string(),
+            hash: source.hash.clone(),
+            file_size: source.file_size,
+            last_modified: source.last_modified.as_secs(),
+            backups,
+        }
+    }
+}
+
+/// Look a file up as given, then as an absolute and a canonical path
+fn find_source(file: &Path) -> Result<Option<SourceRow>> {
+    let mut candidates = vec![file.to_path_buf()];
+    candidates.extend(std::path::absolute(file).ok());
+    candidates.extend(fs::canonicalize(file).ok());
+
+    for candidate in candidates {
+        let (Some(name), Some(dir)) = (candidate.file_name(), candidate.parent()) else {
+            continue;
+        };
+        let found = select_source(&name.to_string_lossy(), &dir.to_string_lossy())
+            .context("Failed to read source files")?;
+        if found.is_some() {
+            return Ok(found);
+        }
+    }
+    Ok(None)
+}
+
+fn describe_source(out: &mut String, source: &SourceRow, backups: u64) {
+    let _ = writeln!(
+        out,
+        "{}\n  {}, modified {}, hash {}, {} backup(s)",
+        Path::new(&source.file_path)
+            .join(&source.file_name)
+            .display(),
+        format_bytes(source.file_size),
+        local_time(source.last_modified.as_secs() as i64),
+        source.hash.get(..16).unwrap_or(&source.hash),
+        backups
+    );
+}
+
+/// The source's record and each recorded backup, noting backups no longer on disk
+fn describe_backups(source: &SourceRow, backups: &[BackupRow]) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{}\n  size:     {}\n  modified: {}\n  hash:     {}",
+        Path::new(&source.file_path)
+            .join(&source.file_name)
+            .display(),
+        format_bytes(source.file_size),
+        local_time(source.last_modified.as_secs() as i64),
+        source.hash
+    );
+
+    if backups.is_empty() {
+        let _ = writeln!(out, "  no backups recorded");
+    }
+    for backup in backups {
+        let path = Path::new(&backup.file_path).join(&backup.file_name);
+        let present = if path.exists() { "" } else { ", MISSING" };
+        let _ = writeln!(
+            out,
+            "  backup:   {} (modified {}{})",
+            path.display(),
+            local_time(backup.last_modified.as_secs() as i64),
+            present
+        );
+    }
+    out
+}
+
+/// Coverage of each destination, then every file with a gap
+fn describe_coverage(report: &CoverageReport) -> String {
+    let mut out = String::new();
+    for destination in &report.destinations {
+        let _ = writeln!(
+            out,
+            "{}: {} of {} file(s) backed up, {} missing ({})",
+            destination.name,
+            destination.covered,
+            destination.expected,
+            destination.missing,
+            format_bytes(destination.missing_bytes)
+        );
+    }
+    for gap in &report.gaps {
+        let where_missing = if gap.no_valid_backup {
+            "no valid backup".to_string()
+        } else {
+            format!("missing from {}", gap.missing_from.join(", "))
+        };
+        let _ = writeln!(
+            out,
+            "  {} ({}): {}",
+            gap.path,
+            format_bytes(gap.file_size),
+            where_missing
+        );
+    }
+    let _ = writeln!(
+        out,
+        "{} of {} source file(s) missing from a destination",
+        report.gaps.len(),
+        report.files_checked
+    );
+    out
+}
+
+fn describe_run(out: &mut String, run: &RunRecord) {
+    let _ = write!(
+        out,
+        "{}  {}  {}{}{}: {} file(s), {} copied, {} error(s), {}s",
+        local_time(run.started_at),
+        run.id,
+        run.status.as_str(),
+        if run.dry_run { " (dry run)" } else { "" },
+        if run.checkpoint { " [checkpoint]" } else { "" },
+        run.files_copied,
+        format_bytes(run.bytes_copied),
+        run.errors,
+        run.finished_at - run.started_at
+    );
+    if let Some(note) = &run.note {
+        let _ = write!(out, "\n  note: {}", note);
+    }
+    if let Some(error) = &run.error {
+        let _ = write!(out, "\n  {}", error);
+    }
+    for anomaly in &run.anomalies {
+        let _ = write!(out, "\n  anomaly: {}", describe(anomaly));
+    }
+    let _ = writeln!(out);
+}
+
+fn local_time(timestamp: i64) -> String {
+    Local
+        .timestamp_opt(timestamp, 0)
+        .single()
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_describe_backups_flags_missing_copies() {
+        let destination = TempDir::new().unwrap();
+        fs::write(destination.path().join("kept.txt"), "data").unwrap();
+        let source = SourceRow {
+            id: 1,
+            file_name: "kept.txt".to_string(),
+            file_path: "/photos".to_string(),
+            hash: "ab".repeat(32),
+            file_size: 4,
+            last_modified: Duration::from_secs(1_700_000_000),
+        };
+        let backup = |dir: &Path| BackupRow {
+            source_id: 1,
+            file_name: "kept.txt".to_string(),
+            file_path: dir.to_string_lossy().to_string(),
+            last_modified: Duration::from_secs(1_700_000_000),
+            original_path: None,
+            physical_size: None,
+        };
+
+        let text = describe_backups(
+            &source,
+            &[
+                backup(destination.path()),
+                backup(&destination.path().join("gone")),
+            ],
+        );
+
+        assert!(text.contains(&format!("hash:     {}", "ab".repeat(32))));
+        assert_eq!(text.matches("backup:").count(), 2);
+        assert_eq!(text.matches(", MISSING").count(), 1);
+        assert!(text.contains("gone"));
+        assert!(describe_backups(&source, &[]).contains("no backups recorded"));
+    }
+}
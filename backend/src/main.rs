@@ -1,25 +1,79 @@
+mod api_auth;
 mod api_routes;
 mod api_state;
+mod db;
+mod desktop;
+mod diff_runs;
+mod exit_status;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod init;
+mod list;
+mod manifest;
 mod models;
+mod orphans;
+mod output;
+mod replicate;
 mod repo;
+mod restore;
+mod scrub;
 mod service;
+mod status;
+mod system_service;
+#[cfg(feature = "tray")]
+mod tray;
+mod users;
 mod utils;
+mod validate;
 mod web_routes;
 
-use crate::models::config::{setup_config, BackupSource};
+use crate::desktop::DesktopFeedback;
+use crate::exit_status::{exit_code, RunEnded};
+use crate::models::config::{setup_config, UnavailableDestinationAction};
 use crate::models::dry_run_mode::DryRunMode;
-use crate::repo::sqlite::set_db_pool;
+use crate::models::run_record::{clean_note, RunRecord, RunTiming};
+use crate::models::run_report::{RunReport, UnavailableDestination};
+use crate::models::run_selection::RunSelection;
+use crate::output::OutputFormat;
+use crate::repo::catalog::open_catalog;
+use crate::repo::sqlite::{
+    delete_run_file_actions, insert_run, prune_run_file_actions, select_runs, set_db_pool,
+    shard_catalog,
+};
 use crate::service::backup::backup_files;
-use crate::utils::directory::get_files_in_path;
+use crate::service::catalog_backup::back_up_catalog;
+use crate::service::checksum_manifest::write_checksum_manifests;
+use crate::service::destination_check::{accept_destination, check_destinations};
+use crate::service::destination_mount::DestinationMounts;
+use crate::service::discovery::{detect_new_directories, discover_source_files, discovery_channel};
+use crate::service::hash::HashTotals;
+use crate::service::marker::{check_marker_age, write_backup_marker};
+use crate::service::mass_change::MassChangeCheck;
+use crate::service::plugin::{notify_run_finished, PluginFilters};
+use crate::service::prune::{prune_unclaimed, UNCLAIMED_DIR};
+use crate::service::regression::{describe, detect_anomalies};
+use crate::service::reload::reload_config;
+use crate::service::report_sink::ReportSink;
+use crate::service::run_budget::RunBudget;
+use crate::service::scrub::{next_scrub_after, run_scheduled_scrub, verify_sample};
+use crate::service::trash::empty_expired_trash;
+use crate::service::volume_snapshot::SourceSnapshots;
+use crate::utils::cancel::{is_cancel_requested, request_cancel};
+use crate::utils::db_logger::{apply_log_retention, init_logging};
 use crate::utils::progress::{create_progress_bar, create_progress_bar_with_bytes, create_spinner};
-use anyhow::{Context, Result};
-use clap::Parser;
+use crate::utils::resource_group::enter_low_priority_group;
+use crate::utils::run_lock::RunLock;
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
 use indicatif::MultiProgress;
 use log::{debug, info, warn};
 use models::config::Config;
 use repo::sqlite::setup_database;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::thread;
+use tracing::level_filters::LevelFilter;
+use tracing::{info_span, Span};
 
 #[macro_use]
 extern crate rocket;
@@ -44,6 +98,12 @@ fn build_rocket(args: Cli) -> rocket::Rocket<rocket::Build> {
             info!("Loaded configuration from: {}", config_file_path);
             app_state.set_config(config.clone());
             app_state.set_config_file_path(config_file_path.clone());
+            if let Some(profile) = &args.profile {
+                if let Err(e) = app_state.select_profile(Some(profile)) {
+                    warn!("Not using a profile: {}", e);
+                }
+            }
+            apply_low_priority_group(&config);
             Some(config)
         }
         Err(e) => {
@@ -70,6 +130,11 @@ fn build_rocket(args: Cli) -> rocket::Rocket<rocket::Build> {
             }
         } else if let Err(e) = setup_database() {
             eprintln!("Failed to setup database schema: {}", e);
+        } else if let Err(e) = shard_catalog(config.catalog_sharding, &config.catalog_shard_roots())
+        {
+            eprintln!("Failed to set up catalog shards: {}", e);
+        } else if let Err(e) = open_catalog(config.catalog_database_url.as_deref()) {
+            eprintln!("Failed to open the catalog: {}", e);
         } else {
             info!(
                 "Database initialized successfully: {}",
@@ -98,48 +163,83 @@ fn build_rocket(args: Cli) -> rocket::Rocket<rocket::Build> {
                 web_routes::dashboard,
                 web_routes::configuration,
                 web_routes::logs,
+                web_routes::files,
                 web_routes::help,
+                web_routes::login_page,
+                web_routes::login,
+                web_routes::logout,
+                api_routes::metrics,
             ],
         )
-        .mount(
-            "/api",
-            routes![
-                api_routes::get_config,
-                api_routes::get_config_form,
-                api_routes::set_config,
-                api_routes::set_config_form,
-                api_routes::save_config_to_file,
-                api_routes::load_config_from_file,
-                api_routes::get_status,
-                api_routes::start_backup,
-                api_routes::stop_backup,
-                api_routes::get_history,
-                api_routes::progress_events,
-                api_routes::validate_config_endpoint,
-                api_routes::health_check,
-                api_routes::get_dashboard_metrics,
-                api_routes::get_progress,
-                api_routes::get_logs,
-                api_routes::get_recent_logs,
-                api_routes::get_log_stats,
-                api_routes::clear_logs,
-                api_routes::get_storage_overview,
-            ],
-        )
+        .mount("/api", api_routes::routes())
+        .register("/", catchers![web_routes::unauthorized])
 }
 
 #[rocket::main]
-async fn main() -> Result<()> {
-    // Initialize logging
-    env_logger::Builder::from_default_env()
-        .filter_level(log::LevelFilter::Info)
-        .format_timestamp_secs()
-        .init();
+async fn main() -> ExitCode {
+    let result = start(Cli::parse()).await;
+    if let Err(e) = &result {
+        eprintln!("Error: {:?}", e);
+    }
+    exit_code(&result)
+}
+
+async fn start(args: Cli) -> Result<()> {
+    #[cfg(windows)]
+    if let Some(working_dir) = &args.windows_service {
+        std::env::set_current_dir(working_dir)
+            .with_context(|| format!("Failed to change to {}", working_dir.display()))?;
+        system_service::windows::spawn_dispatcher();
+    }
+
+    if args.serves_api() {
+        // CLI mode sets up its own logger using --log-level
+        init_logging(LevelFilter::INFO);
+        enable_chaos(&args);
+
+        #[cfg(feature = "grpc")]
+        let grpc_addr = args.grpc_addr;
+        #[cfg(feature = "tray")]
+        let tray = args.tray;
+        let rocket = build_rocket(args).ignite().await?;
+
+        let state = rocket
+            .state::<AppState>()
+            .expect("application state is managed")
+            .clone();
+        state.recover_interrupted_run();
+        rocket::tokio::spawn(state.clone().run_config_scheduler());
+        rocket::tokio::spawn(state.clone().run_job_scheduler());
+        rocket::tokio::spawn(state.clone().run_scrub_scheduler());
+        rocket::tokio::spawn(state.run_log_pruner());
+
+        #[cfg(feature = "grpc")]
+        if let Some(addr) = grpc_addr {
+            let state = rocket
+                .state::<AppState>()
+                .expect("application state is managed")
+                .clone();
+            rocket::tokio::spawn(async move {
+                if let Err(e) = grpc::serve(addr, state).await {
+                    log::error!("gRPC control service stopped: {}", e);
+                }
+            });
+        }
 
-    let args = Cli::parse();
+        #[cfg(feature = "tray")]
+        if tray {
+            let state = rocket
+                .state::<AppState>()
+                .expect("application state is managed")
+                .clone();
+            tray::spawn(
+                state,
+                dashboard_url(rocket.config()),
+                rocket::tokio::runtime::Handle::current(),
+            );
+        }
 
-    if args.api_mode {
-        build_rocket(args).launch().await?;
+        rocket.launch().await?;
         Ok(())
     } else {
         cli_main(args)
@@ -169,6 +269,16 @@ struct Cli {
     #[arg(short = 'q', long = "quiet")]
     quiet: bool,
 
+    /// Print each command's results, and the report of each backup run, as a line of JSON on
+    /// standard output; logs stay on standard error
+    #[arg(
+        long = "output",
+        value_enum,
+        default_value = "text",
+        value_name = "FORMAT"
+    )]
+    output: OutputFormat,
+
     #[arg(short = 'v', long = "validate-only")]
     validate_only: bool,
 
@@ -181,32 +291,280 @@ struct Cli {
     #[arg(short = 'o', long = "once")]
     once: bool,
 
+    /// Wait for a backup already running against the same database to finish instead of failing
+    #[arg(long = "wait")]
+    wait: bool,
+
     #[arg(long = "api")]
     api_mode: bool,
+
+    /// Run the web server with a system tray icon for status and quick actions
+    #[cfg(feature = "tray")]
+    #[arg(long = "tray")]
+    tray: bool,
+
+    /// Run the CLI scheduler with a tray icon showing the next scheduled run and the last result
+    #[cfg(feature = "tray")]
+    #[arg(long = "scheduler-tray")]
+    scheduler_tray: bool,
+
+    /// Show a desktop notification when a run finishes or fails
+    #[cfg(feature = "notify")]
+    #[arg(long = "notify")]
+    notify: bool,
+
+    /// Also serve the gRPC control interface on this address in API or tray mode, e.g. 127.0.0.1:50051
+    #[cfg(feature = "grpc")]
+    #[arg(long = "grpc-addr", env = "RUSTYHASHBACKUP_GRPC_ADDR")]
+    grpc_addr: Option<std::net::SocketAddr>,
+
+    /// Answer the Windows service control manager, after changing to this working directory
+    #[cfg(windows)]
+    #[arg(long = "windows-service", hide = true, value_name = "WORKING_DIR")]
+    windows_service: Option<PathBuf>,
+
+    /// Only back up the configured source with this name or parent directory
+    #[arg(long = "source")]
+    source: Option<String>,
+
+    /// Only back up this subdirectory (absolute or relative to the source)
+    #[arg(long = "path")]
+    path: Option<String>,
+
+    /// Write a per-file NDJSON report of each run to this file
+    #[arg(long = "report")]
+    report: Option<PathBuf>,
+
+    /// Note to keep with the run, e.g. "before OS upgrade"; shown by `list runs`
+    #[arg(long = "note")]
+    note: Option<String>,
+
+    /// Back up to this destination, by name or path, although its ID marker isn't the recorded
+    /// one, and record the drive now there as the destination from then on
+    #[arg(long = "accept-destination", value_name = "DESTINATION")]
+    accept_destination: Vec<String>,
+
+    /// Back up a run's files although more changed or were deleted since the last run than
+    /// `mass_change_guard` allows
+    #[arg(long = "confirm-mass-change")]
+    confirm_mass_change: bool,
+
+    /// Stop a run at the first file that fails to back up and exit 1, instead of backing up the
+    /// rest and exiting 2
+    #[arg(long = "fail-on-error")]
+    fail_on_error: bool,
+
+    /// Inject faults into copies to test failure handling, e.g. copy=0.1,locked=0.05,hash=0.02,
+    /// slow-ms=200,seed=7
+    #[arg(long = "chaos", hide = true, env = "RUSTYHASHBACKUP_CHAOS")]
+    chaos: Option<utils::chaos::Chaos>,
+
+    /// Use the settings of this profile from the configuration's `profiles`
+    #[arg(long = "profile", env = "RUSTYHASHBACKUP_PROFILE")]
+    profile: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Show what the database records about source files, their backups and past runs
+    #[command(subcommand)]
+    List(list::ListCommand),
+    /// Export the hash manifest of backed-up files, or compare it with another instance's
+    #[command(subcommand)]
+    Manifest(manifest::ManifestCommand),
+    /// Export the catalog of source files and backups as JSON or CSV, or import such an export
+    #[command(subcommand)]
+    Db(db::DbCommand),
+    /// Find destination files no backup record points at, and optionally quarantine or delete them
+    Orphans(orphans::OrphansArgs),
+    /// Back up the configured critical paths now, ignoring the schedule and the low-priority
+    /// group, and tag the run as a checkpoint, e.g. right before an OS update
+    Checkpoint,
+    /// Run the scheduler, or the web server with --api, as a systemd unit or Windows service
+    /// that starts at boot, using this configuration file
+    InstallService(system_service::InstallArgs),
+    /// Stop and remove the service installed by install-service
+    UninstallService(system_service::UninstallArgs),
+    /// Show what a running --api instance is doing and how its last run went
+    Status(status::StatusArgs),
+    /// Add, change or remove the admins and viewers who sign in to the web UI and API
+    #[command(subcommand)]
+    User(users::UserCommand),
+    /// Answer a few questions about what to back up, where and when, and write them to the
+    /// --config path
+    Init(init::InitArgs),
+    /// Read back the backups verified longest ago and check they still match their source's hash
+    Scrub(scrub::ScrubArgs),
+    /// Copy the backups scrubs found damaged or missing again, from their source or another
+    /// destination's copy
+    Repair(scrub::RepairArgs),
+    /// Copy the backups recorded under one destination to another, checking each against its
+    /// source's hash, e.g. to seed a new destination after a source disk died
+    Replicate(replicate::ReplicateArgs),
+    /// Summarize the files that appeared, changed or disappeared between two runs, e.g. to spot
+    /// mass modifications
+    DiffRuns(diff_runs::DiffRunsArgs),
+    /// Plan restoring the sources as they were at a time or run, from the current backups and
+    /// the earlier versions in trash, and optionally carry it out
+    #[command(subcommand)]
+    Restore(restore::RestoreCommand),
+}
+
+impl Cli {
+    /// Whether to start the web server instead of running backups from the command line
+    fn serves_api(&self) -> bool {
+        if self.command.is_some() {
+            return false;
+        }
+        #[cfg(feature = "tray")]
+        if self.tray {
+            return true;
+        }
+        self.api_mode
+    }
+
+    /// Whether finished runs are announced with a desktop notification
+    fn notifies(&self) -> bool {
+        #[cfg(feature = "notify")]
+        return self.notify;
+        #[cfg(not(feature = "notify"))]
+        false
+    }
+}
+
+/// Browser address of the dashboard served with this Rocket configuration
+fn dashboard_url(config: &rocket::Config) -> String {
+    let host = if config.address.is_unspecified() {
+        std::net::IpAddr::from([127, 0, 0, 1])
+    } else {
+        config.address
+    };
+    format!("http://{}:{}", host, config.port)
+}
+
+/// Inject the faults of `--chaos` into this process's copies, if given
+fn enable_chaos(args: &Cli) {
+    if let Some(chaos) = &args.chaos {
+        warn!(
+            "--chaos is set: injecting faults into copies ({:?}); don't use this run's backups",
+            chaos
+        );
+        utils::chaos::enable(chaos.clone());
+    }
 }
 
 fn cli_main(args: Cli) -> Result<()> {
+    // Before logging starts, so no log lines land between the questions
+    if let Some(Command::Init(command)) = &args.command {
+        return init::run(command, &args.config_file);
+    }
+
     let log_level = match args.log_level.to_lowercase().as_str() {
-        "trace" => log::LevelFilter::Trace,
-        "debug" => log::LevelFilter::Debug,
-        "info" => log::LevelFilter::Info,
-        "warn" => log::LevelFilter::Warn,
-        "error" => log::LevelFilter::Error,
-        _ => log::LevelFilter::Info,
+        "trace" => LevelFilter::TRACE,
+        "debug" => LevelFilter::DEBUG,
+        "info" => LevelFilter::INFO,
+        "warn" => LevelFilter::WARN,
+        "error" => LevelFilter::ERROR,
+        _ => LevelFilter::INFO,
     };
+    init_logging(log_level);
+    enable_chaos(&args);
 
-    env_logger::Builder::from_default_env()
-        .filter_level(log_level)
-        .format_timestamp_secs()
-        .init();
+    if let Some(Command::UninstallService(command)) = &args.command {
+        return system_service::uninstall(command);
+    }
+
+    // Only talks to the server, so it needs no configuration file of its own
+    if let Some(Command::Status(command)) = &args.command {
+        let rocket_config = rocket::Config::from(rocket::Config::figment());
+        return status::run(command, dashboard_url(&rocket_config), args.output);
+    }
+
+    if args.validate_only {
+        return validate::run(&args.config_file, args.profile.as_deref(), args.output);
+    }
 
     info!("RustyHashBackup starting...");
-    let config: Config = setup_config(args.config_file).context("Failed to load configuration")?;
+    let config: Config =
+        setup_config(args.config_file.clone()).context("Failed to load configuration")?;
+    let config = match &args.profile {
+        Some(profile) => {
+            info!("Using profile {}", profile);
+            config.with_profile(profile)?
+        }
+        None => config,
+    };
     debug!("Loaded config: {:?}", &config);
 
-    if args.validate_only {
-        info!("Configuration is valid. Exiting (--validate-only mode).");
-        return Ok(());
+    if let Some(Command::InstallService(command)) = &args.command {
+        return system_service::install(
+            command,
+            &args.config_file,
+            args.profile.as_deref(),
+            &config,
+            &args.log_level,
+        );
+    }
+
+    if let Some(Command::List(command)) = &args.command {
+        open_database(&config)?;
+        return list::run(command, &config, args.output);
+    }
+
+    if let Some(Command::Manifest(command)) = &args.command {
+        open_database(&config)?;
+        return manifest::run(command, &config, args.output);
+    }
+
+    if let Some(Command::Db(command)) = &args.command {
+        open_database(&config)?;
+        return db::run(command, &config, args.output);
+    }
+
+    if let Some(Command::Orphans(command)) = &args.command {
+        open_database(&config)?;
+        return orphans::run(command, &config, args.output);
+    }
+
+    if let Some(Command::User(command)) = &args.command {
+        open_database(&config)?;
+        return users::run(command, args.output);
+    }
+
+    if let Some(Command::Scrub(command)) = &args.command {
+        open_database(&config)?;
+        return scrub::run(command, &config, args.wait, args.output);
+    }
+
+    if let Some(Command::Repair(command)) = &args.command {
+        open_database(&config)?;
+        return scrub::run_repair(command, &config, args.wait, args.output);
+    }
+
+    if let Some(Command::Replicate(command)) = &args.command {
+        open_database(&config)?;
+        return replicate::run(command, &config, args.wait, args.output);
+    }
+
+    if let Some(Command::DiffRuns(command)) = &args.command {
+        open_database(&config)?;
+        return diff_runs::run(command, args.output);
+    }
+
+    if let Some(Command::Restore(command)) = &args.command {
+        open_database(&config)?;
+        return restore::run(command, &config, args.output);
+    }
+
+    let checkpoint = matches!(args.command, Some(Command::Checkpoint));
+    if checkpoint && (args.source.is_some() || args.path.is_some()) {
+        bail!("checkpoint backs up the configured critical_paths and takes no --source or --path");
+    }
+    if checkpoint && config.critical_paths.is_empty() {
+        bail!("checkpoint needs critical_paths in the configuration");
     }
 
     let dry_run_mode = if args.dry_run_full {
@@ -219,32 +577,303 @@ fn cli_main(args: Cli) -> Result<()> {
         DryRunMode::None
     };
 
+    if !args.accept_destination.is_empty() && dry_run_mode.is_dry_run() {
+        bail!(
+            "--accept-destination records the drive at a destination, so a dry run can't take it"
+        );
+    }
+
     rayon::ThreadPoolBuilder::new()
         .num_threads(config.max_threads)
         .build_global()
         .context("Failed to build thread pool")?;
 
-    set_db_pool(&config.database_file).context("Failed to initialize database connection pool")?;
+    // A checkpoint is waited on by whatever runs next, so it isn't throttled
+    if checkpoint {
+        info!("Running checkpoint of the configured critical paths");
+    } else {
+        apply_low_priority_group(&config);
+    }
 
-    setup_database().context("Failed to set up database schema")?;
+    open_database(&config)?;
+
+    for reference in &args.accept_destination {
+        accept_destination(&config, reference, "cli")
+            .with_context(|| format!("Failed to accept destination {}", reference))?;
+    }
+
+    let desktop = DesktopFeedback::new(args.notifies());
+    let selection = RunSelection {
+        source: args.source,
+        path: args.path,
+        checkpoint,
+    };
 
-    let run_once = args.once || config.schedule.is_none();
+    let note = clean_note(args.note.as_deref());
+    let run_once = args.once || checkpoint || config.schedule.is_none();
+    // Confirming every scheduled run would leave the guard off for good
+    if args.confirm_mass_change && !run_once {
+        bail!("--confirm-mass-change confirms a single run; add --once");
+    }
 
     if run_once {
-        run_backup(&config, dry_run_mode, args.quiet, None)?;
+        ctrlc::set_handler(|| {
+            if is_cancel_requested() {
+                std::process::exit(exit_status::CANCELLED.into());
+            }
+            warn!("Cancelling the run after the files in progress; press Ctrl+C again to quit now");
+            request_cancel();
+        })
+        .context("Failed to set Ctrl+C handler")?;
+        let outcome = run_backup(
+            &config,
+            dry_run_mode,
+            args.quiet,
+            &selection,
+            note.as_deref(),
+            args.report.as_deref(),
+            None,
+            args.wait,
+            args.confirm_mass_change,
+            args.fail_on_error,
+        );
+        desktop.finished(dry_run_mode.is_dry_run(), &outcome);
+        let report = outcome?;
+        args.output.print(&report, String::new)?;
+        RunEnded::check(&report)?;
     } else {
-        run_scheduled(&config, dry_run_mode, args.quiet)?;
+        #[cfg(feature = "tray")]
+        if args.scheduler_tray {
+            tray::spawn_scheduler(desktop.status());
+        }
+        run_scheduled(
+            &config,
+            &args.config_file,
+            dry_run_mode,
+            args.quiet,
+            &selection,
+            note.as_deref(),
+            args.report.as_deref(),
+            args.wait,
+            args.fail_on_error,
+            &desktop,
+            args.output,
+        )?;
     }
 
     Ok(())
 }
 
+/// Open the configured database, with its catalog shards
+fn open_database(config: &Config) -> Result<()> {
+    set_db_pool(&config.database_file).context("Failed to initialize database connection pool")?;
+    setup_database().context("Failed to set up database schema")?;
+    shard_catalog(config.catalog_sharding, &config.catalog_shard_roots())
+        .context("Failed to set up catalog shards")?;
+    open_catalog(config.catalog_database_url.as_deref()).context("Failed to open the catalog")
+}
+
+/// Enter the configured low-priority resource group, continuing at normal priority on failure
+fn apply_low_priority_group(config: &Config) {
+    if let Some(group) = &config.low_priority_group {
+        if let Err(e) = enter_low_priority_group(group) {
+            warn!(
+                "Failed to enter low-priority resource group, running with normal priority: {}",
+                e
+            );
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn run_backup(
     config: &Config,
     dry_run_mode: DryRunMode,
     quiet: bool,
+    selection: &RunSelection,
+    note: Option<&str>,
+    report_file: Option<&Path>,
     state: Option<&AppState>,
-) -> Result<()> {
+    wait_for_lock: bool,
+    confirm_mass_change: bool,
+    fail_on_error: bool,
+) -> Result<RunReport> {
+    // Runs racing each other would both decide the same backups are required
+    let _run_lock = RunLock::acquire(&config.database_file, wait_for_lock)?;
+    let run_id = state
+        .and_then(|st| st.get_current_run())
+        .map(|run| run.id)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    // Everything logged during the run, on any thread that enters this span, carries its ID
+    let _run_span = info_span!("run", run_id = %run_id).entered();
+    let started_at = chrono::Utc::now().timestamp();
+    let started = std::time::Instant::now();
+    let hashed_before = HashTotals::now();
+
+    let mut outcome = execute_backup(
+        config,
+        dry_run_mode,
+        quiet,
+        selection,
+        report_file,
+        state,
+        &run_id,
+        confirm_mass_change,
+        fail_on_error,
+    );
+
+    let hashed = HashTotals::now().since(hashed_before);
+    let timing = RunTiming {
+        duration_ms: started.elapsed().as_millis() as u64,
+        files_processed: outcome
+            .as_ref()
+            .map(|report| report.sources.iter().map(|s| s.files_prepared).sum())
+            .unwrap_or_default(),
+        bytes_hashed: hashed.bytes,
+        hash_micros: hashed.nanos / 1000,
+    };
+    let mut record = RunRecord {
+        note: note.map(str::to_string),
+        checkpoint: selection.checkpoint,
+        timing: Some(timing),
+        ..RunRecord::finished(
+            &run_id,
+            started_at,
+            chrono::Utc::now().timestamp(),
+            dry_run_mode.is_dry_run(),
+            outcome.as_ref().ok(),
+            outcome.as_ref().err().map(|e| format!("{:#}", e)),
+        )
+    };
+    // Selective runs cover only part of the sources, so they aren't held against the others
+    if config.regression_detection.enabled && selection.is_empty() {
+        // Extra runs are loaded because dry runs and checkpoints in the history are passed over
+        match select_runs(config.regression_detection.baseline_runs * 4) {
+            Ok(history) => {
+                record.anomalies = detect_anomalies(&config.regression_detection, &record, &history)
+            }
+            Err(e) => warn!("Failed to load run history for regression detection: {}", e),
+        }
+    }
+    for anomaly in &record.anomalies {
+        let message = format!("Run {}: {}", run_id, describe(anomaly));
+        warn!("{}", message);
+        if let Some(st) = state {
+            st.notify_message(message);
+        }
+    }
+    if let Ok(report) = &mut outcome {
+        report.anomalies = record.anomalies.clone();
+    }
+    if let Err(e) = insert_run(&record) {
+        warn!("Failed to record run {}: {}", run_id, e);
+    }
+    if let Err(e) = keep_file_actions(config, &run_id, &outcome, state) {
+        warn!("Failed to clean up run file actions: {}", e);
+    }
+    if let Err(e) = apply_log_retention(config) {
+        warn!("Failed to prune logs: {}", e);
+    }
+
+    notify_run_finished(config, dry_run_mode.is_dry_run(), &outcome);
+    outcome
+}
+
+/// Drop the file actions of a run that didn't get to every file, and those of runs past
+/// `file_action_runs`, so runs are only compared with runs that saw all their files
+fn keep_file_actions(
+    config: &Config,
+    run_id: &str,
+    outcome: &Result<RunReport>,
+    state: Option<&AppState>,
+) -> models::error::Result<()> {
+    if outcome.is_err() || is_cancel_requested() || state.is_some_and(|st| st.is_stop_requested()) {
+        delete_run_file_actions(run_id)?;
+    }
+    prune_run_file_actions(config.file_action_runs)?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn execute_backup(
+    config: &Config,
+    dry_run_mode: DryRunMode,
+    quiet: bool,
+    selection: &RunSelection,
+    report_file: Option<&Path>,
+    state: Option<&AppState>,
+    run_id: &str,
+    confirm_mass_change: bool,
+    fail_on_error: bool,
+) -> Result<RunReport> {
+    let mut report = RunReport::default();
+    let selected_sources = selection
+        .resolve(config)
+        .context("Failed to resolve run selection")?;
+    if !selection.is_empty() {
+        info!(
+            "Selective run limited to: {}",
+            selected_sources
+                .iter()
+                .map(|s| s.scan_root.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+
+    // Removable destinations stay mounted until the run returns, however it ends. All are
+    // checked before anything is written, so a missing drive doesn't get a new tree on its
+    // empty mount point.
+    let (_mounts, offline) = DestinationMounts::mount(config);
+    let unavailable: Vec<_> = check_destinations(config, dry_run_mode)
+        .into_iter()
+        .filter(|u| !offline.iter().any(|o| o.destination == u.destination))
+        .collect();
+    let describe = |skipped: &[UnavailableDestination]| {
+        skipped
+            .iter()
+            .map(|u| format!("{} ({})", u.destination, u.reason))
+            .collect::<Vec<_>>()
+            .join("; ")
+    };
+    if !unavailable.is_empty()
+        && config.destination_checks.action == UnavailableDestinationAction::Fail
+    {
+        bail!(
+            "Destination check failed, nothing was copied: {}",
+            describe(&unavailable)
+        );
+    }
+    report.skipped_destinations = offline.into_iter().chain(unavailable).collect();
+    let available;
+    let config = if report.skipped_destinations.is_empty() {
+        config
+    } else {
+        let described = describe(&report.skipped_destinations);
+        warn!("Skipping unavailable destination(s): {}", described);
+        if let Some(st) = state {
+            st.notify_message(format!("Skipped destination(s): {}", described));
+        }
+        let mut remaining = config.clone();
+        remaining.backup_destinations.retain(|d| {
+            let name = d.name().unwrap_or(d.path());
+            !report
+                .skipped_destinations
+                .iter()
+                .any(|u| u.destination == name)
+        });
+        if remaining.backup_destinations.is_empty() {
+            // Rotated drives are all away now and then; that isn't a failed run
+            if report.skipped_destinations.iter().all(|u| u.offline) {
+                warn!("Every destination is offline, nothing was backed up");
+                return Ok(report);
+            }
+            bail!("No destination is available: {}", described);
+        }
+        available = remaining;
+        &available
+    };
+
     let multi_progress = if !quiet {
         Some(MultiProgress::new())
     } else {
@@ -257,10 +886,15 @@ fn run_backup(
             phase_description: "Discovering source files".to_string(),
             files_processed: 0,
             total_files: 0,
+            discovery_complete: false,
             bytes_processed: None,
             total_bytes: None,
             percentage: 0.0,
             current_file: None,
+            current_source: None,
+            elapsed_seconds: 0,
+            bytes_per_second: None,
+            eta_seconds: None,
         }));
     }
 
@@ -271,29 +905,64 @@ fn run_backup(
         )))
     });
 
-    let backup_candidates = get_source_files(&config.backup_sources, discovery_progress.as_ref())?;
-
-    if let Some(progress) = discovery_progress {
-        let total: usize = backup_candidates.values().map(|v| v.len()).sum();
-        progress.finish_with_message(format!(
-            "{}[1/3] Found {} files across {} directories",
-            dry_run_mode.progress_prefix(),
-            total,
-            backup_candidates.len()
-        ));
+    if let Some(max_age_hours) = config.backup_marker_max_age_hours {
+        for selected in &selected_sources {
+            let root = &selected.source.parent_directory;
+            if let (Some(age_hours), Some(st)) = (check_marker_age(root, max_age_hours), state) {
+                st.notify_message(format!(
+                    "Source {} was last backed up {} hours ago",
+                    root, age_hours
+                ));
+            }
+        }
     }
 
-    if backup_candidates.is_empty() {
-        warn!("No source files found to backup");
-        return Ok(());
+    let sources: Vec<_> = selected_sources.iter().map(|s| s.source.clone()).collect();
+    report.new_directories = detect_new_directories(&sources, dry_run_mode)
+        .context("Failed to detect new directories")?;
+    if let Some(st) = state {
+        for dir in &report.new_directories {
+            st.notify_message(format!(
+                "New directory discovered in {}: {}",
+                dir.parent_directory, dir.directory_name
+            ));
+        }
     }
 
-    // Phase 2 & 3: Preparation and Backup
-    let total_files: u64 = backup_candidates.values().map(|v| v.len() as u64).sum();
+    // Released when the run returns, however it ends
+    let snapshots = if dry_run_mode.is_dry_run() {
+        SourceSnapshots::default()
+    } else {
+        SourceSnapshots::take(&selected_sources, run_id)
+    };
 
+    // A checkpoint is waited on by whatever runs next, so it isn't cut short
+    let budget = if selection.checkpoint {
+        RunBudget::unlimited()
+    } else {
+        RunBudget::start(
+            config,
+            &selected_sources,
+            run_id,
+            dry_run_mode.should_update_database(),
+        )
+    };
+    let mass_change = MassChangeCheck::new(
+        config,
+        selection,
+        &selected_sources,
+        dry_run_mode,
+        confirm_mass_change,
+    );
+
+    // Per-file outcomes are persisted for API runs so the history endpoints can page through them
+    let sink = ReportSink::new(report_file, state.map(|_| run_id.to_string()))
+        .context("Failed to create run report")?;
+
+    // Phase 2 & 3: Preparation and Backup, with preparation starting on files as they're found
     let prep_progress = multi_progress.as_ref().map(|mp| {
         mp.add(create_progress_bar(
-            total_files,
+            0,
             &format!("{}[2/3] Preparing backups", dry_run_mode.progress_prefix()),
         ))
     });
@@ -305,20 +974,84 @@ fn run_backup(
             "Simulating file copy"
         };
         mp.add(create_progress_bar_with_bytes(
-            total_files,
+            0,
             &format!("{}[3/3] {}", dry_run_mode.progress_prefix(), action),
         ))
     });
 
-    backup_files(
-        backup_candidates,
-        config,
-        prep_progress.as_ref(),
-        backup_progress.as_ref(),
-        dry_run_mode,
-        state,
-    )
-    .context("Backup operation failed")?;
+    let plugin_filters = PluginFilters::start(config);
+    let (sender, discovered) = discovery_channel();
+    let run_span = Span::current();
+    let (discovery, backup) = thread::scope(|scope| {
+        let discovery = scope.spawn(|| {
+            let _run_span = run_span.enter();
+            let found = discover_source_files(
+                &selected_sources,
+                &snapshots,
+                &plugin_filters,
+                budget.backlog(),
+                config.max_threads,
+                discovery_progress.as_ref(),
+                sender,
+            );
+            if let (Some(progress), Ok(found)) = (&discovery_progress, &found) {
+                progress.finish_with_message(format!(
+                    "{}[1/3] Found {} files across {} directories",
+                    dry_run_mode.progress_prefix(),
+                    found.found,
+                    selected_sources.len()
+                ));
+            }
+            found
+        });
+        let backup = backup_files(
+            run_id,
+            discovered,
+            config,
+            prep_progress.as_ref(),
+            backup_progress.as_ref(),
+            dry_run_mode,
+            state,
+            &sink,
+            &budget,
+            &mass_change,
+            fail_on_error,
+        );
+        (discovery.join(), backup)
+    });
+    plugin_filters.finish();
+
+    let discovery = discovery
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+        .context("Failed to discover source files")?;
+    let copied = backup.context("Backup operation failed")?;
+    if let (false, Some(st)) = (discovery.skipped_entries.is_empty(), state) {
+        st.notify_message(format!(
+            "{} unreadable director(ies) or file(s) were skipped during discovery",
+            discovery.skipped_entries.len()
+        ));
+    }
+    let mut report = RunReport {
+        new_directories: report.new_directories,
+        skipped_destinations: report.skipped_destinations,
+        skipped_entries: discovery.skipped_entries,
+        budget_exhausted: budget.finish(),
+        ..copied
+    };
+    if let (Some(exhausted), Some(st)) = (&report.budget_exhausted, state) {
+        st.notify_message(format!(
+            "Run reached its {} budget; {} file(s) left for the next run",
+            exhausted.limit.as_str(),
+            exhausted.files_deferred
+        ));
+    }
+
+    if discovery.found == 0 {
+        warn!("No source files found to backup");
+        sink.finish(&report);
+        return Ok(report);
+    }
+    let total_files: u64 = report.sources.iter().map(|s| s.files_prepared).sum();
 
     if let Some(progress) = prep_progress {
         progress.finish();
@@ -336,23 +1069,111 @@ fn run_backup(
         progress.finish_with_message(message);
     }
 
+    let stopped = is_cancel_requested() || state.is_some_and(|st| st.is_stop_requested());
     if dry_run_mode.is_dry_run() {
         info!("DRY RUN completed - no files were actually copied or database updated");
     } else {
         info!("Backup operation completed successfully");
+
+        // Markers describe whole sources, so a run limited to a subtree doesn't update them, and
+        // only a run that got every file across says the sources were backed up
+        let errors: u64 = report.sources.iter().map(|s| s.errors).sum();
+        let complete = errors == 0 && !stopped && report.budget_exhausted.is_none();
+        if config.write_backup_marker && complete && !selection.limits_paths() {
+            for selected in &selected_sources {
+                if let Err(e) = write_backup_marker(
+                    &selected.source.parent_directory,
+                    run_id,
+                    &config.destination_paths(),
+                ) {
+                    warn!(
+                        "Failed to write backup marker in {}: {}",
+                        selected.source.parent_directory, e
+                    );
+                }
+            }
+        }
     }
-    Ok(())
+
+    // Only a full run has looked at every source, so partial runs leave destinations alone
+    if config.prune_unclaimed && selection.is_empty() {
+        let outcome = prune_unclaimed(config, dry_run_mode, run_id)
+            .context("Failed to move unclaimed destination files")?;
+        report.unclaimed_files = outcome.moved;
+        report.prune_plan = outcome.pending_plan;
+        if let Some(st) = state {
+            if !report.unclaimed_files.is_empty() {
+                st.notify_message(format!(
+                    "{} unclaimed destination file(s) moved to {}/",
+                    report.unclaimed_files.len(),
+                    UNCLAIMED_DIR
+                ));
+            }
+            if let Some(plan) = &report.prune_plan {
+                st.notify_message(format!(
+                    "Prune plan {} awaits approval: {} unclaimed destination file(s)",
+                    plan.id, plan.files
+                ));
+            }
+        }
+    }
+
+    if !dry_run_mode.is_dry_run() {
+        if let Err(e) = empty_expired_trash(config, chrono::Utc::now().date_naive()) {
+            warn!("Failed to empty expired trash: {}", e);
+        }
+        write_checksum_manifests(config);
+        // Sampled before the catalog is snapshotted, so the snapshot has the sample's stamps
+        if let (Some(percent), false) = (config.verify_sample_percent, stopped) {
+            match verify_sample(config, percent) {
+                Ok(sample) => {
+                    if let (false, Some(st)) = (sample.problems.is_empty(), state) {
+                        st.notify_message(format!(
+                            "Sample verification found {} damaged or missing backup(s); run repair to copy them again",
+                            sample.problems.len()
+                        ));
+                    }
+                    report.verified_sample = Some(sample);
+                }
+                Err(e) => warn!("Failed to verify a sample of the backups: {}", e),
+            }
+        }
+        back_up_catalog(config, chrono::Utc::now());
+    }
+
+    if !report.new_directories.is_empty() {
+        info!(
+            "{} new director(ies) discovered and backed up this run",
+            report.new_directories.len()
+        );
+    }
+    sink.finish(&report);
+    Ok(report)
 }
 
-fn run_scheduled(config: &Config, dry_run_mode: DryRunMode, quiet: bool) -> Result<()> {
+#[allow(clippy::too_many_arguments)]
+fn run_scheduled(
+    config: &Config,
+    config_file: &str,
+    dry_run_mode: DryRunMode,
+    quiet: bool,
+    selection: &RunSelection,
+    note: Option<&str>,
+    report_file: Option<&Path>,
+    wait_for_lock: bool,
+    fail_on_error: bool,
+    desktop: &DesktopFeedback,
+    output: OutputFormat,
+) -> Result<()> {
     use chrono::Utc;
     use cron::Schedule;
     use std::str::FromStr;
     use std::sync::atomic::{AtomicBool, Ordering};
     use std::sync::Arc;
 
-    let schedule_str = config.schedule.as_ref().unwrap();
-    let schedule = Schedule::from_str(schedule_str).context("Invalid cron expression")?;
+    let mut config = config.clone();
+    let schedule_str = config.schedule.clone().unwrap();
+    let mut schedule = Schedule::from_str(&schedule_str).context("Invalid cron expression")?;
 
     info!(
         "Starting scheduled backup mode with schedule: {}",
@@ -368,17 +1189,82 @@ fn run_scheduled(config: &Config, dry_run_mode: DryRunMode, quiet: bool) -> Resu
     })
     .context("Failed to set Ctrl+C handler")?;
 
+    let reload_requested = Arc::new(AtomicBool::new(false));
+    #[cfg(unix)]
+    {
+        let flag = reload_requested.clone();
+        // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+        unsafe {
+            signal_hook_registry::register(libc::SIGHUP, move || {
+                flag.store(true, Ordering::SeqCst);
+            })
+        }
+        .context("Failed to set SIGHUP handler")?;
+    }
+
+    let run = |config: &Config| {
+        desktop.started();
+        let outcome = run_backup(
+            config,
+            dry_run_mode,
+            quiet,
+            selection,
+            note,
+            report_file,
+            None,
+            wait_for_lock,
+            false,
+            fail_on_error,
+        );
+        desktop.finished(dry_run_mode.is_dry_run(), &outcome);
+        let report = outcome?;
+        output.print(&report, String::new)?;
+        RunEnded::check(&report).map_err(anyhow::Error::from)
+    };
+
+    let mut next_scrub = next_scrub_after(&config, Utc::now());
+
     if config.run_on_startup {
         info!("Running initial backup on startup...");
-        if let Err(e) = run_backup(config, dry_run_mode, quiet, None) {
+        if let Err(e) = run(&config) {
             warn!("Initial backup failed: {}", e);
         }
     }
 
     while running.load(Ordering::SeqCst) {
+        if reload_requested.swap(false, Ordering::SeqCst) {
+            info!(
+                "Received SIGHUP, reloading configuration from {}",
+                config_file
+            );
+            match reload_config(config_file, &config) {
+                Ok(new_config) => match new_config.schedule.as_deref().map(Schedule::from_str) {
+                    Some(Ok(new_schedule)) => {
+                        schedule = new_schedule;
+                        config = new_config;
+                        next_scrub = next_scrub_after(&config, Utc::now());
+                    }
+                    _ => warn!(
+                        "Reloaded configuration has no valid schedule, keeping current configuration"
+                    ),
+                },
+                Err(e) => warn!(
+                    "Failed to reload configuration, keeping current configuration: {}",
+                    e
+                ),
+            }
+        }
+
+        if next_scrub.is_some_and(|next| Utc::now() >= next) && running.load(Ordering::SeqCst) {
+            info!("Running scheduled scrub...");
+            run_scheduled_scrub(&config);
+            next_scrub = next_scrub_after(&config, Utc::now());
+        }
+
         let now = Utc::now();
 
         if let Some(next) = schedule.upcoming(Utc).take(1).next() {
+            desktop.scheduled(next);
             let duration_until_next = (next - now)
                 .to_std()
                 .unwrap_or(std::time::Duration::from_secs(0));
@@ -398,7 +1284,7 @@ fn run_scheduled(config: &Config, dry_run_mode: DryRunMode, quiet: bool) -> Resu
 
             if Utc::now() >= next && running.load(Ordering::SeqCst) {
                 info!("Running scheduled backup...");
-                if let Err(e) = run_backup(config, dry_run_mode, quiet, None) {
+                if let Err(e) = run(&config) {
                     warn!("Scheduled backup failed: {}", e);
                 }
             }
@@ -411,49 +1297,3 @@ fn run_scheduled(config: &Config, dry_run_mode: DryRunMode, quiet: bool) -> Resu
     info!("Scheduler stopped");
     Ok(())
 }
-
-fn get_source_files(
-    backup_sources: &Vec<BackupSource>,
-    progress: Option<&indicatif::ProgressBar>,
-) -> Result<HashMap<PathBuf, Vec<PathBuf>>> {
-    info!(
-        "Discovering files in {} source directories...",
-        backup_sources.len()
-    );
-
-    let mut result_map = HashMap::<PathBuf, Vec<PathBuf>>::new();
-    let mut total_files = 0;
-
-    for source in backup_sources {
-        if let Some(pb) = progress {
-            pb.set_message(format!("Scanning: {}", source.parent_directory));
-        }
-
-        let files = get_files_in_path(
-            &source.parent_directory,
-            &source.skip_dirs,
-            &source.max_depth,
-        )
-        .with_context(|| format!("Failed to read directory: {}", source.parent_directory))?;
-
-        if !files.is_empty() {
-            let file_count = files.len();
-            total_files += file_count;
-            result_map.insert(PathBuf::from(&source.parent_directory), files);
-
-            if let Some(pb) = progress {
-                pb.set_message(format!(
-                    "Found {} files in {}",
-                    file_count, source.parent_directory
-                ));
-            }
-        }
-    }
-
-    info!(
-        "Found {} files across {} directories",
-        total_files,
-        result_map.len()
-    );
-    Ok(result_map)
-}
@@ -0,0 +1,167 @@
+use crate::models::config::Config;
+use crate::models::manifest::{HashManifest, ManifestComparison, ManifestEntry};
+use crate::output::OutputFormat;
+use crate::repo::sqlite::format_bytes;
+use crate::service::manifest::build_manifest;
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use std::fmt::Write;
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+
+/// What `manifest` does with the catalog's hash manifest
+#[derive(Subcommand)]
+pub enum ManifestCommand {
+    /// Write the manifest of backed-up files as JSON, for another instance to compare with
+    Export {
+        /// File to write instead of standard output
+        #[arg(short = 'o', long = "output")]
+        output: Option<PathBuf>,
+    },
+    /// Compare with a peer's manifest: files both back up and files only one of them does
+    Compare {
+        /// Peer manifest file, or `-` to read it from standard input
+        peer: PathBuf,
+        /// List every shared and unique file instead of only the totals
+        #[arg(long = "files")]
+        files: bool,
+    },
+}
+
+/// Export or compare the catalog's manifest
+pub fn run(command: &ManifestCommand, config: &Config, output: OutputFormat) -> Result<()> {
+    let manifest = build_manifest(config).context("Failed to read the backup catalog")?;
+    match command {
+        ManifestCommand::Export { output: file } => {
+            let json = serde_json::to_string_pretty(&manifest)?;
+            match file {
+                Some(path) => fs::write(path, json)
+                    .with_context(|| format!("Failed to write {}", path.display()))?,
+                None => println!("{}", json),
+            }
+        }
+        ManifestCommand::Compare { peer, files } => {
+            let peer = read_manifest(peer)?;
+            let comparison = manifest.compare(&peer);
+            output.print(&comparison, || describe_comparison(&comparison, *files))?;
+        }
+    }
+    Ok(())
+}
+
+fn read_manifest(path: &PathBuf) -> Result<HashManifest> {
+    let mut contents = String::new();
+    if path.as_os_str() == "-" {
+        std::io::stdin()
+            .read_to_string(&mut contents)
+            .context("Failed to read the peer manifest from standard input")?;
+    } else {
+        contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+    }
+    serde_json::from_str(&contents).context("Peer manifest is not valid manifest JSON")
+}
+
+/// Totals for each side, followed by the files themselves if `files` is set
+fn describe_comparison(comparison: &ManifestComparison, files: bool) -> String {
+    let summary = &comparison.summary;
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{} and {}\n  {} {} file(s), {}\n  {} {} file(s), {}\n  {} {} file(s), {}",
+        comparison.local_host,
+        comparison.peer_host,
+        label("both"),
+        summary.shared_files,
+        format_bytes(summary.shared_bytes),
+        label(&format!("{} only", comparison.local_host)),
+        summary.local_only_files,
+        format_bytes(summary.local_only_bytes),
+        label(&format!("{} only", comparison.peer_host)),
+        summary.peer_only_files,
+        format_bytes(summary.peer_only_bytes)
+    );
+    if !files {
+        return out;
+    }
+
+    for shared in &comparison.shared {
+        let _ = writeln!(
+            out,
+            "both {} ({})",
+            shared.hash.get(..16).unwrap_or(&shared.hash),
+            format_bytes(shared.file_size)
+        );
+        for path in &shared.local_paths {
+            let _ = writeln!(out, "  {}: {}", comparison.local_host, path);
+        }
+        for path in &shared.peer_paths {
+            let _ = writeln!(out, "  {}: {}", comparison.peer_host, path);
+        }
+    }
+    describe_unique(&mut out, &comparison.local_host, &comparison.local_only);
+    describe_unique(&mut out, &comparison.peer_host, &comparison.peer_only);
+    out
+}
+
+fn describe_unique(out: &mut String, host: &str, entries: &[ManifestEntry]) {
+    for entry in entries {
+        let _ = writeln!(
+            out,
+            "only on {}: {} ({})",
+            host,
+            entry.path,
+            format_bytes(entry.file_size)
+        );
+    }
+}
+
+/// Row label padded so short host names line up
+fn label(name: &str) -> String {
+    format!("{:<10}", format!("{}:", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::manifest::{ComparisonSummary, SharedContent};
+
+    #[test]
+    fn test_describe_comparison_lists_files_on_request() {
+        let comparison = ManifestComparison {
+            local_host: "nas".to_string(),
+            peer_host: "pc".to_string(),
+            summary: ComparisonSummary {
+                shared_files: 1,
+                shared_bytes: 2048,
+                peer_only_files: 1,
+                peer_only_bytes: 10,
+                ..Default::default()
+            },
+            shared: vec![SharedContent {
+                hash: "abcdef".to_string(),
+                file_size: 2048,
+                local_paths: vec!["/data/a.jpg".to_string()],
+                peer_paths: vec!["/home/b.jpg".to_string()],
+            }],
+            local_only: vec![],
+            peer_only: vec![ManifestEntry {
+                path: "/home/c.txt".to_string(),
+                hash: "123".to_string(),
+                file_size: 10,
+                hash_mebibytes: 1,
+                hash_tail_mebibytes: 0,
+            }],
+        };
+
+        let totals = describe_comparison(&comparison, false);
+        assert!(totals.contains("both:      1 file(s)"));
+        assert!(totals.contains("pc only:   1 file(s)"));
+        assert!(!totals.contains("/home/c.txt"));
+
+        let listed = describe_comparison(&comparison, true);
+        assert!(listed.contains("  nas: /data/a.jpg\n  pc: /home/b.jpg"));
+        assert!(listed.contains("only on pc: /home/c.txt"));
+    }
+}
@@ -0,0 +1,20 @@
+use crate::models::api::BackupStatus;
+use crate::models::dry_run_mode::DryRunMode;
+use crate::models::run_selection::RunSelection;
+
+/// The API run in progress, kept in the database so a restart can tell it was cut short
+#[derive(Debug, Clone)]
+pub struct ActiveRun {
+    pub id: String,
+    /// Unix timestamp of when the run started
+    pub started_at: i64,
+    pub dry_run_mode: DryRunMode,
+    pub selection: RunSelection,
+    pub note: Option<String>,
+    pub status: BackupStatus,
+    pub files_processed: u64,
+    pub total_files: u64,
+    pub bytes_processed: u64,
+    /// Unix timestamp of the last status or progress update
+    pub updated_at: i64,
+}
@@ -1,3 +1,4 @@
+use crate::models::run_report::RunAnomaly;
 use serde::{Deserialize, Serialize};
 
 /// Request parameters for starting a backup
@@ -26,6 +27,22 @@ pub struct StartBackupRequest {
     /// Run once instead of using schedule
     #[serde(default)]
     pub once: bool,
+
+    /// Limit the run to one configured source (matched by name or path)
+    #[serde(default)]
+    pub source: Option<String>,
+
+    /// Limit the run to one subdirectory of a configured source
+    #[serde(default)]
+    pub path: Option<String>,
+
+    /// Client-chosen key; retrying with the same key returns the run it started
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+
+    /// Free-text note kept with the run, e.g. "before OS upgrade"
+    #[serde(default)]
+    pub note: Option<String>,
 }
 
 fn default_log_level() -> String {
@@ -38,6 +55,17 @@ pub struct StartBackupResponse {
     pub success: bool,
     pub message: String,
     pub backup_id: Option<String>,
+    /// Status of the started run, or of the existing run for a repeated idempotency key
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<BackupStatus>,
+}
+
+/// Body of the 429 for a trigger fired again within its interval
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TriggerTooSoonResponse {
+    pub message: String,
+    /// Seconds until the trigger may fire again, as in the `Retry-After` header
+    pub retry_after_secs: u64,
 }
 
 /// Response for stop backup request
@@ -47,15 +75,53 @@ pub struct StopBackupResponse {
     pub message: String,
 }
 
+/// Response for POST /api/pause and POST /api/resume
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PauseBackupResponse {
+    pub success: bool,
+    pub message: String,
+}
+
 /// Current backup status
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum BackupStatus {
     Idle,
     Running,
+    /// Running, but holding between files until resumed
+    Paused,
     Stopping,
     Failed,
     Completed,
+    /// Cut short by a server restart
+    Interrupted,
+}
+
+impl BackupStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BackupStatus::Idle => "idle",
+            BackupStatus::Running => "running",
+            BackupStatus::Paused => "paused",
+            BackupStatus::Stopping => "stopping",
+            BackupStatus::Failed => "failed",
+            BackupStatus::Completed => "completed",
+            BackupStatus::Interrupted => "interrupted",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "idle" => Some(BackupStatus::Idle),
+            "running" => Some(BackupStatus::Running),
+            "paused" => Some(BackupStatus::Paused),
+            "stopping" => Some(BackupStatus::Stopping),
+            "failed" => Some(BackupStatus::Failed),
+            "completed" => Some(BackupStatus::Completed),
+            "interrupted" => Some(BackupStatus::Interrupted),
+            _ => None,
+        }
+    }
 }
 
 /// Progress information for a backup operation
@@ -70,9 +136,13 @@ pub struct BackupProgress {
     /// Files processed in current phase
     pub files_processed: u64,
 
-    /// Total files to process
+    /// Total files to process; files found so far until discovery is complete, so it only grows
     pub total_files: u64,
 
+    /// Whether discovery has finished, so `total_files` is final
+    #[serde(default)]
+    pub discovery_complete: bool,
+
     /// Bytes processed (for copy phase)
     pub bytes_processed: Option<u64>,
 
@@ -84,6 +154,22 @@ pub struct BackupProgress {
 
     /// Current file being processed
     pub current_file: Option<String>,
+
+    /// Name of the source the current file belongs to
+    #[serde(default)]
+    pub current_source: Option<String>,
+
+    /// Seconds since the run started working on files
+    #[serde(default)]
+    pub elapsed_seconds: u64,
+
+    /// Bytes copied per second over the last half minute
+    #[serde(default)]
+    pub bytes_per_second: Option<f64>,
+
+    /// Seconds until the files found so far are done, at the rate of the last half minute
+    #[serde(default)]
+    pub eta_seconds: Option<u64>,
 }
 
 impl Default for BackupProgress {
@@ -93,14 +179,46 @@ impl Default for BackupProgress {
             phase_description: "Not started".to_string(),
             files_processed: 0,
             total_files: 0,
+            discovery_complete: false,
             bytes_processed: None,
             total_bytes: None,
             percentage: 0.0,
             current_file: None,
+            current_source: None,
+            elapsed_seconds: 0,
+            bytes_per_second: None,
+            eta_seconds: None,
         }
     }
 }
 
+/// A file being copied to one destination
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileProgress {
+    pub source: String,
+    pub file: String,
+    /// Backup path the file is being copied to
+    pub destination: String,
+    pub file_size: u64,
+    /// Bytes written to the backup so far, read from its size on disk
+    pub bytes_copied: u64,
+    /// Percentage copied (0-100)
+    pub percentage: f32,
+    /// Milliseconds since the copy started
+    pub elapsed_ms: u64,
+    /// Average rate of this copy so far; `null` for its first half second
+    pub bytes_per_second: Option<f64>,
+}
+
+/// Response for GET /progress/files
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FileProgressResponse {
+    /// The longest-running copies first, up to the limit asked for
+    pub files: Vec<FileProgress>,
+    /// Copies in progress, listed or not
+    pub total: usize,
+}
+
 /// Status response for GET /status
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StatusResponse {
@@ -110,6 +228,12 @@ pub struct StatusResponse {
     pub completed_at: Option<String>,
     pub error: Option<String>,
     pub dry_run_mode: Option<String>,
+    /// Destinations whose last check found another drive's marker, or none
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub destination_mismatches: Vec<crate::models::backup_marker::DestinationMismatch>,
+    /// Changes past `mass_change_guard` the paused run is waiting to have confirmed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mass_change: Option<crate::models::run_report::MassChange>,
 }
 
 /// Configuration response
@@ -119,6 +243,9 @@ pub struct ConfigResponse {
     pub message: String,
     pub config: Option<crate::models::config::Config>,
     pub config_file_path: Option<String>,
+    /// Per-field validation errors and warnings
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub issues: Vec<crate::models::validation_issue::ValidationIssue>,
 }
 
 /// Backup history entry
@@ -132,6 +259,11 @@ pub struct BackupHistoryEntry {
     pub bytes_processed: Option<u64>,
     pub error: Option<String>,
     pub dry_run: bool,
+    /// Note given when the run was started
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report: Option<crate::models::run_report::RunReport>,
 }
 
 /// Backup history response
@@ -141,6 +273,109 @@ pub struct BackupHistoryResponse {
     pub total: usize,
 }
 
+/// One page of per-file outcomes for a run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunFilesResponse {
+    pub entries: Vec<crate::models::run_report::FileOutcome>,
+    pub total: u64,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+/// One page of catalogued source files matching a search, with their backups
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilesResponse {
+    pub entries: Vec<crate::models::catalog_file::CatalogFile>,
+    pub total: u64,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+/// Duration and throughput of one finished run, a point in the run statistics series
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunStats {
+    pub id: String,
+    /// Unix timestamp of when the run started
+    pub started_at: i64,
+    pub status: BackupStatus,
+    pub dry_run: bool,
+    /// Whether the run was a checkpoint of the configured critical paths
+    pub checkpoint: bool,
+    pub duration_secs: f64,
+    pub files_processed: u64,
+    pub bytes_copied: u64,
+    /// MiB copied per second of the run
+    pub mib_per_sec: f64,
+    pub files_per_sec: f64,
+    /// MiB read and hashed per second, per hashing thread; unset if the run hashed nothing
+    pub hash_mib_per_sec: Option<f64>,
+    /// Ways the run was far slower or larger than the runs before it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub anomalies: Vec<RunAnomaly>,
+}
+
+/// Run statistics, oldest first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunStatsResponse {
+    pub runs: Vec<RunStats>,
+}
+
+/// Response for the job create, update, enable, disable and delete endpoints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobResponse {
+    pub success: bool,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job: Option<crate::models::job::Job>,
+}
+
+/// A prune plan with every file it would move
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrunePlanDetail {
+    pub plan: crate::models::prune_plan::PrunePlan,
+    pub files: Vec<crate::models::prune_plan::PlannedFile>,
+}
+
+/// Response for approving or rejecting a prune plan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PruneDecisionResponse {
+    pub success: bool,
+    pub message: String,
+    /// Files moved into the unclaimed area once the plan was approved
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub moved: Vec<crate::models::unclaimed_file::UnclaimedFile>,
+}
+
+/// Profiles of the configuration and the one in use, for the profile endpoints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfilesResponse {
+    pub success: bool,
+    pub message: String,
+    /// Selected profile; unset while the configuration is used as it is
+    pub active: Option<String>,
+    /// Every profile of the configuration, by name
+    pub profiles: Vec<String>,
+}
+
+/// Request for POST /api/profiles/select
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelectProfileRequest {
+    /// Profile to use; unset or empty for none
+    #[serde(default)]
+    pub profile: Option<String>,
+}
+
+/// State of the configuration's own schedule in API mode, for the schedule endpoints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleResponse {
+    /// Cron expression from the configuration; unset if backups only start on request
+    pub schedule: Option<String>,
+    /// Whether scheduled runs may start; turned off and on through the API
+    pub enabled: bool,
+    /// Unix timestamp of the next scheduled run; unset if disabled or unscheduled
+    pub next_run: Option<i64>,
+}
+
 /// Generic API error response
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ErrorResponse {
@@ -156,33 +391,20 @@ pub struct ProgressEvent {
     pub message: Option<String>,
 }
 
-/// Dashboard metric card
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct DashboardMetric {
-    pub title: String,
-    pub value: String,
-    pub subtitle: String,
-    pub icon: String,
-    pub color: String,
-}
-
-/// Dashboard metrics response
+/// Request for POST /api/login
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct DashboardMetrics {
-    pub metrics: Vec<DashboardMetric>,
+pub struct LoginRequest {
+    pub name: String,
+    pub password: String,
 }
 
-/// Log entry
+/// Response for POST /api/login and GET /api/me
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LogEntry {
-    pub timestamp: String,
-    pub level: String,
+pub struct SessionResponse {
+    pub success: bool,
     pub message: String,
-}
-
-/// Logs response
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct LogsResponse {
-    pub logs: Vec<LogEntry>,
-    pub total: usize,
+    /// The signed-in user; none while no users are set up
+    pub user: Option<crate::models::user::User>,
+    /// Whether the web UI and API ask for a sign-in, which they do once a user exists
+    pub sign_in_required: bool,
 }
@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+/// A decision kept in the `Audit_Log` table, such as approving a prune plan
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    /// Unix timestamp of when it happened
+    pub at: i64,
+    /// What was done, e.g. `prune_plan_approved`
+    pub action: String,
+    /// Who did it: `run` or `config` for automatic decisions, otherwise the name given to the API
+    pub actor: String,
+    pub detail: String,
+}
@@ -7,5 +7,4 @@ pub struct BackedUpFile {
     #[allow(dead_code)]
     pub file_path: String,
     pub last_modified: Duration,
-    pub hash: String,
 }
@@ -0,0 +1,27 @@
+use serde::{Deserialize, Serialize};
+
+/// File name of the marker written into each source root after a successful run
+pub const BACKUP_MARKER_FILE: &str = ".last-backup.json";
+
+/// File name of the ID written into each destination when `destination_checks.markers` is on
+pub const DESTINATION_MARKER_FILE: &str = ".rustyhashbackup-id";
+
+/// A destination whose last check found another drive's marker, or none, instead of its own
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DestinationMismatch {
+    pub path: String,
+    pub recorded_id: String,
+    /// Marker found in its place; absent when the destination had none
+    pub found_id: Option<String>,
+    /// Unix timestamp of the check that found it
+    pub found_at: i64,
+}
+
+/// Contents of the `.last-backup.json` marker
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupMarker {
+    /// RFC 3339 timestamp of when the run completed
+    pub completed_at: String,
+    pub run_id: String,
+    pub destinations: Vec<String>,
+}
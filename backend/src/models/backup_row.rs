@@ -6,4 +6,8 @@ pub struct BackupRow {
     pub file_name: String,
     pub file_path: String,
     pub last_modified: Duration,
+    /// Path the backup has under the source's own names, if its destination needed others
+    pub original_path: Option<String>,
+    /// Disk space the backup takes up, less than the source's size if it is sparse
+    pub physical_size: Option<u64>,
 }
@@ -0,0 +1,304 @@
+use crate::models::error::{BackupError, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+/// Columns of the CSV export, one row per backup or per source without one
+const CSV_HEADER: [&str; 8] = [
+    "source_name",
+    "source_path",
+    "hash",
+    "file_size",
+    "last_modified",
+    "backup_name",
+    "backup_path",
+    "backup_last_modified",
+];
+
+/// The catalog of source files and their backups, portable between machines
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CatalogExport {
+    /// Layout of the catalog tables the export was taken from
+    pub schema_version: u32,
+    /// Algorithm of every `hash`, e.g. `blake2b-512`
+    pub hash_algorithm: String,
+    /// MiB at the start of each file covered by its hash
+    pub max_mebibytes_for_hash: usize,
+    /// MiB at the end of longer files covered by their hash as well; 0 in older exports
+    #[serde(default)]
+    pub hash_tail_mebibytes: usize,
+    /// Unix timestamp of when the export was taken
+    pub exported_at: i64,
+    pub sources: Vec<ExportedSource>,
+}
+
+/// A row of `Source_Files` with its rows of `Backup_Files`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportedSource {
+    pub file_name: String,
+    pub file_path: String,
+    pub hash: String,
+    pub file_size: u64,
+    /// Unix timestamp of the modification time last recorded
+    pub last_modified: u64,
+    #[serde(default)]
+    pub backups: Vec<ExportedBackup>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportedBackup {
+    pub file_name: String,
+    pub file_path: String,
+    pub last_modified: u64,
+}
+
+/// Rows written by an import; rows that already existed are updated and counted too
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImportSummary {
+    pub sources: u64,
+    pub backups: u64,
+}
+
+impl CatalogExport {
+    /// Metadata as `# key=value` lines, then a header and one row per backup
+    pub fn to_csv(&self) -> String {
+        let mut out = String::new();
+        for (key, value) in self.metadata() {
+            let _ = writeln!(out, "# {}={}", key, value);
+        }
+        let _ = writeln!(out, "{}", CSV_HEADER.join(","));
+
+        for source in &self.sources {
+            let source_fields = [
+                csv_field(&source.file_name),
+                csv_field(&source.file_path),
+                csv_field(&source.hash),
+                source.file_size.to_string(),
+                source.last_modified.to_string(),
+            ]
+            .join(",");
+            if source.backups.is_empty() {
+                let _ = writeln!(out, "{},,,", source_fields);
+            }
+            for backup in &source.backups {
+                let _ = writeln!(
+                    out,
+                    "{},{},{},{}",
+                    source_fields,
+                    csv_field(&backup.file_name),
+                    csv_field(&backup.file_path),
+                    backup.last_modified
+                );
+            }
+        }
+        out
+    }
+
+    /// Read an export written by `to_csv`
+    pub fn from_csv(text: &str) -> Result<Self> {
+        let mut metadata = Vec::new();
+        let mut body = text;
+        while let Some(line) = body.strip_prefix("# ") {
+            let (line, rest) = line.split_once('\n').unwrap_or((line, ""));
+            let (key, value) = line
+                .trim_end_matches('\r')
+                .split_once('=')
+                .ok_or_else(|| invalid(format!("metadata line '# {}' has no '='", line)))?;
+            metadata.push((key.to_string(), value.to_string()));
+            body = rest;
+        }
+        let meta = |key: &str| {
+            metadata
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.as_str())
+                .ok_or_else(|| invalid(format!("metadata '{}' is missing", key)))
+        };
+
+        let mut records = parse_csv(body)?.into_iter();
+        if records.next().as_deref() != Some(&CSV_HEADER.map(str::to_string)[..]) {
+            return Err(invalid(format!("header must be {}", CSV_HEADER.join(","))));
+        }
+
+        let mut sources: Vec<ExportedSource> = Vec::new();
+        for (idx, record) in records.enumerate() {
+            let line = idx + 1;
+            let [name, path, hash, size, modified, backup_name, backup_path, backup_modified] =
+                <[String; 8]>::try_from(record).map_err(|record| {
+                    invalid(format!(
+                        "row {} has {} fields instead of 8",
+                        line,
+                        record.len()
+                    ))
+                })?;
+
+            let same_source = sources
+                .last()
+                .is_some_and(|s| s.file_name == name && s.file_path == path);
+            if !same_source {
+                sources.push(ExportedSource {
+                    file_name: name,
+                    file_path: path,
+                    hash,
+                    file_size: number(&size, "file_size", line)?,
+                    last_modified: number(&modified, "last_modified", line)?,
+                    backups: Vec::new(),
+                });
+            }
+            if !backup_path.is_empty() {
+                let backup = ExportedBackup {
+                    file_name: backup_name,
+                    file_path: backup_path,
+                    last_modified: number(&backup_modified, "backup_last_modified", line)?,
+                };
+                sources.last_mut().unwrap().backups.push(backup);
+            }
+        }
+
+        Ok(CatalogExport {
+            schema_version: number(meta("schema_version")?, "schema_version", 0)?,
+            hash_algorithm: meta("hash_algorithm")?.to_string(),
+            max_mebibytes_for_hash: number(
+                meta("max_mebibytes_for_hash")?,
+                "max_mebibytes_for_hash",
+                0,
+            )?,
+            hash_tail_mebibytes: metadata
+                .iter()
+                .find(|(key, _)| key == "hash_tail_mebibytes")
+                .map_or(Ok(0), |(_, value)| number(value, "hash_tail_mebibytes", 0))?,
+            exported_at: number(meta("exported_at")?, "exported_at", 0)?,
+            sources,
+        })
+    }
+
+    fn metadata(&self) -> [(&'static str, String); 5] {
+        [
+            ("schema_version", self.schema_version.to_string()),
+            ("hash_algorithm", self.hash_algorithm.clone()),
+            (
+                "max_mebibytes_for_hash",
+                self.max_mebibytes_for_hash.to_string(),
+            ),
+            ("hash_tail_mebibytes", self.hash_tail_mebibytes.to_string()),
+            ("exported_at", self.exported_at.to_string()),
+        ]
+    }
+}
+
+fn invalid(reason: String) -> BackupError {
+    BackupError::CatalogImport(reason)
+}
+
+/// Parse a number from row `line`, or from the metadata if `line` is 0
+fn number<T: std::str::FromStr>(value: &str, field: &str, line: usize) -> Result<T> {
+    value.parse().map_err(|_| match line {
+        0 => invalid(format!("metadata '{}' is not a number: '{}'", field, value)),
+        _ => invalid(format!(
+            "row {} {} is not a number: '{}'",
+            line, field, value
+        )),
+    })
+}
+
+/// A field quoted if it holds a comma, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Records of RFC 4180 CSV, where quoted fields may hold commas, quotes and line breaks
+fn parse_csv(text: &str) -> Result<Vec<Vec<String>>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, in_quotes) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => in_quotes = false,
+            ('"', false) if field.is_empty() => in_quotes = true,
+            (',', false) => record.push(std::mem::take(&mut field)),
+            ('\r', false) if chars.peek() == Some(&'\n') => {}
+            ('\n', false) => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        return Err(invalid("a quoted field is not closed".to_string()));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_round_trip() {
+        let export = CatalogExport {
+            schema_version: 1,
+            hash_algorithm: "blake2b-512".to_string(),
+            max_mebibytes_for_hash: 1,
+            hash_tail_mebibytes: 4,
+            exported_at: 1_700_000_000,
+            sources: vec![
+                ExportedSource {
+                    file_name: "report, \"final\".txt".to_string(),
+                    file_path: "/data/docs".to_string(),
+                    hash: "abc".to_string(),
+                    file_size: 12,
+                    last_modified: 100,
+                    backups: vec![
+                        ExportedBackup {
+                            file_name: "report, \"final\".txt".to_string(),
+                            file_path: "/mnt/one/docs".to_string(),
+                            last_modified: 101,
+                        },
+                        ExportedBackup {
+                            file_name: "report, \"final\".txt".to_string(),
+                            file_path: "/mnt/two/docs".to_string(),
+                            last_modified: 102,
+                        },
+                    ],
+                },
+                ExportedSource {
+                    file_name: "line\nbreak.txt".to_string(),
+                    file_path: "/data".to_string(),
+                    hash: "def".to_string(),
+                    file_size: 0,
+                    last_modified: 200,
+                    backups: vec![],
+                },
+            ],
+        };
+
+        let csv = export.to_csv();
+        assert!(csv.starts_with("# schema_version=1\n# hash_algorithm=blake2b-512\n"));
+        assert_eq!(CatalogExport::from_csv(&csv).unwrap(), export);
+        assert_eq!(
+            CatalogExport::from_csv(&csv.replace("# hash_algorithm=blake2b-512\n", ""))
+                .unwrap_err()
+                .to_string(),
+            "Invalid catalog export: metadata 'hash_algorithm' is missing"
+        );
+        // Exports from before tail hashing have no tail
+        let untailed =
+            CatalogExport::from_csv(&csv.replace("# hash_tail_mebibytes=4\n", "")).unwrap();
+        assert_eq!(untailed.hash_tail_mebibytes, 0);
+    }
+}
@@ -0,0 +1,133 @@
+use crate::models::backup_row::BackupRow;
+use crate::models::source_row::SourceRow;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// A source file in the catalog with its recorded backups, for the file browser
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogFile {
+    pub id: i32,
+    pub file_name: String,
+    pub file_path: String,
+    pub hash: String,
+    pub file_size: u64,
+    /// Unix timestamp of the source's recorded modification time
+    pub last_modified: u64,
+    pub backups: Vec<CatalogBackup>,
+}
+
+/// A recorded backup and how the copy on disk compares with the record
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CatalogBackup {
+    pub path: String,
+    /// Unix timestamp of the backup's modification time when it was recorded
+    pub last_modified: u64,
+    pub status: BackupCheck,
+}
+
+/// Whether a recorded backup is still on disk as it was written
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupCheck {
+    /// On disk with the source's size and the recorded modification time
+    Ok,
+    /// On disk, but its size or modification time changed since it was recorded
+    Changed,
+    /// No longer on disk
+    Missing,
+}
+
+impl CatalogFile {
+    /// The source's record, checking each of its backups on disk
+    pub fn new(source: SourceRow, backups: &[BackupRow]) -> Self {
+        let backups = backups
+            .iter()
+            .map(|backup| CatalogBackup::check(backup, source.file_size))
+            .collect();
+
+        CatalogFile {
+            id: source.id,
+            file_name: source.file_name,
+            file_path: source.file_path,
+            hash: source.hash,
+            file_size: source.file_size,
+            last_modified: source.last_modified.as_secs(),
+            backups,
+        }
+    }
+}
+
+impl CatalogBackup {
+    /// Compare a recorded backup of a `file_size`-byte source with the copy on disk
+    pub fn check(backup: &BackupRow, file_size: u64) -> Self {
+        let path = Path::new(&backup.file_path).join(&backup.file_name);
+        let status = match fs::metadata(&path) {
+            Ok(metadata) => {
+                let modified = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                    .map(|time| time.as_secs());
+                if metadata.len() == file_size && modified == Some(backup.last_modified.as_secs()) {
+                    BackupCheck::Ok
+                } else {
+                    BackupCheck::Changed
+                }
+            }
+            Err(_) => BackupCheck::Missing,
+        };
+
+        CatalogBackup {
+            path: path.to_string_lossy().to_string(),
+            last_modified: backup.last_modified.as_secs(),
+            status,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_check_compares_backups_on_disk() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("photo.jpg");
+        fs::write(&path, "data").unwrap();
+        let modified = fs::metadata(&path)
+            .unwrap()
+            .modified()
+            .unwrap()
+            .duration_since(UNIX_EPOCH)
+            .unwrap();
+        let backup = |file_name: &str, last_modified: Duration| BackupRow {
+            source_id: 1,
+            file_name: file_name.to_string(),
+            file_path: dir.path().to_string_lossy().to_string(),
+            last_modified: Duration::from_secs(last_modified.as_secs()),
+            original_path: None,
+            physical_size: None,
+        };
+
+        let ok = CatalogBackup::check(&backup("photo.jpg", modified), 4);
+        assert_eq!(ok.status, BackupCheck::Ok);
+        assert_eq!(ok.path, path.to_string_lossy());
+        assert_eq!(
+            CatalogBackup::check(&backup("photo.jpg", modified), 5).status,
+            BackupCheck::Changed
+        );
+        assert_eq!(
+            CatalogBackup::check(&backup("photo.jpg", modified + Duration::from_secs(60)), 4)
+                .status,
+            BackupCheck::Changed
+        );
+        assert_eq!(
+            CatalogBackup::check(&backup("gone.jpg", modified), 4).status,
+            BackupCheck::Missing
+        );
+    }
+}
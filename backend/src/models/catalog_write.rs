@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// A catalog change made by a run, held in the run's journal until the run finishes
+///
+/// Sources are named by path rather than ID because a source first seen in the run has no ID
+/// until its row is written.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CatalogWrite {
+    /// Record a source file, or update the row for its path
+    Source {
+        file_name: String,
+        file_path: String,
+        hash: String,
+        file_size: u64,
+        last_modified: Duration,
+    },
+    /// A source file whose content is unchanged was touched
+    SourceLastModified {
+        file_name: String,
+        file_path: String,
+        last_modified: Duration,
+    },
+    /// Record a backup of a source file, or update the row for its path
+    Backup {
+        source_name: String,
+        source_path: String,
+        file_name: String,
+        file_path: String,
+        last_modified: Duration,
+        /// Where the backup would be had the destination taken the source's names as they are
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        original_path: Option<String>,
+        /// Disk space the backup takes up, where the platform reports it
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        physical_size: Option<u64>,
+    },
+    /// A backup was moved away from this path
+    RemoveBackup {
+        source_name: String,
+        source_path: String,
+        file_name: String,
+        file_path: String,
+    },
+    /// A source file was moved away from this path; its row goes once no backups are left
+    RemoveSource {
+        file_name: String,
+        file_path: String,
+    },
+}
@@ -1,17 +1,27 @@
 use crate::models::config_validator::validate_config;
 use crate::models::error::{BackupError, Result};
+use crate::models::run_selection::RunSelection;
+use crate::utils::directory::{decode_windows_file_name, windows_file_name};
+use crate::utils::file_copy::DEFAULT_COPY_BUFFER_KIB;
 use log::info;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     pub database_file: String,
     #[serde(default = "usize_one")]
     pub max_mebibytes_for_hash: usize,
+    /// MiB at the end of files larger than `max_mebibytes_for_hash` that are hashed as well,
+    /// along with the file's size, so changes near the end show; 0 hashes only the start
+    #[serde(default)]
+    pub hash_tail_mebibytes: usize,
     pub backup_sources: Vec<BackupSource>,
-    pub backup_destinations: Vec<String>,
+    pub backup_destinations: Vec<BackupDestination>,
     #[serde(default = "bool_true")]
     pub skip_source_hash_check_if_newer: bool,
     #[serde(default = "bool_false")]
@@ -24,20 +34,1042 @@ pub struct Config {
     pub schedule: Option<String>,
     #[serde(default = "bool_true")]
     pub run_on_startup: bool,
+    #[serde(default = "bool_false")]
+    pub write_backup_marker: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup_marker_max_age_hours: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub low_priority_group: Option<LowPriorityGroup>,
+    #[serde(default = "bool_false")]
+    pub prune_unclaimed: bool,
+    /// Carry out prune plans as soon as they are made instead of waiting for approval
+    #[serde(default = "bool_false")]
+    pub auto_approve_pruning: bool,
+    /// How SQLite databases that another program has open are captured instead of copied
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sqlite_snapshot: Option<SqliteSnapshot>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_processors: Vec<PostProcessorConfig>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub plugins: Vec<PluginConfig>,
+    /// Buffer size for copies the kernel can't perform or clone directly
+    #[serde(default = "default_copy_buffer_kib")]
+    pub copy_buffer_kib: usize,
+    /// How copies are verified, by file size; the first matching tier applies
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub verification_tiers: Vec<VerificationTier>,
+    /// Skip files whose size and modification time match their last recorded backup, without
+    /// hashing them or looking at the destinations
+    #[serde(default = "bool_false")]
+    pub quick_skip_unchanged: bool,
+    /// Files smaller than this many bytes are copied and verified every run without looking at
+    /// their backups, as that costs more than copying them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub always_copy_below_bytes: Option<u64>,
+    /// Reuse the hash an earlier run took of a file that still has the same identity, size and
+    /// modification time, even when the catalog has no record of it
+    #[serde(default = "bool_true")]
+    pub hash_cache: bool,
+    /// Start a run cut short by a server restart again once the server is back
+    #[serde(default = "bool_false")]
+    pub resume_interrupted_run: bool,
+    /// Seconds a timestamp may be ahead of the system clock, or a backup's modification time may
+    /// trail its recorded one, before it is treated as clock skew
+    #[serde(default = "default_clock_skew_tolerance_secs")]
+    pub clock_skew_tolerance_secs: u64,
+    /// Seconds two modification times may differ by and still count as the same, for
+    /// filesystems that round them, such as FAT's 2 seconds, or shift them with the time zone
+    #[serde(default)]
+    pub mtime_tolerance_secs: u64,
+    /// Directories inside the sources that `checkpoint` backs up, in this order
+    #[serde(default = "vec_default", skip_serializing_if = "Vec::is_empty")]
+    pub critical_paths: Vec<String>,
+    /// When a run is flagged for being far slower or larger than recent runs
+    #[serde(default)]
+    pub regression_detection: RegressionDetection,
+    /// Keep source and backup rows in a database file per source or per destination
+    #[serde(default)]
+    pub catalog_sharding: CatalogSharding,
+    /// PostgreSQL database to keep the catalog in instead of `database_file`, so that several
+    /// machines can share it; the password may be left to the `PGPASSWORD` environment variable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub catalog_database_url: Option<String>,
+    /// Days log entries are kept in the Logs table; `null` keeps them however old
+    #[serde(default = "default_log_retention_days")]
+    pub log_retention_days: Option<u64>,
+    /// Most log entries kept in the Logs table, the oldest going first; `null` for no limit
+    #[serde(default = "default_max_log_rows")]
+    pub max_log_rows: Option<u64>,
+    /// Most recent runs whose per-file actions are kept for comparing runs; 0 records none
+    #[serde(default = "default_file_action_runs")]
+    pub file_action_runs: u64,
+    /// How much may change since the last run before a run waits for confirmation to copy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mass_change_guard: Option<MassChangeGuard>,
+    /// Days backups replaced by changed source files are kept under each destination's `.trash/`
+    /// before being deleted; unset replaces them in place
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trash_retention_days: Option<u64>,
+    /// JSON Lines file every copy, skip, verification and removal of a file is appended to,
+    /// kept apart from the database
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_operation_log: Option<String>,
+    /// Snapshots of the database kept under each destination's `.catalog/`, one taken after
+    /// every run; unset takes none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub catalog_backups_kept: Option<usize>,
+    /// Keep a `SHA256SUMS` in every destination directory, so backups can be checked with
+    /// `sha256sum -c` where this program isn't installed
+    #[serde(default = "bool_false")]
+    pub checksum_manifests: bool,
+    /// Prepared files a run keeps in memory when it holds them back, for `copy_order` or
+    /// `mass_change_guard`, before writing them out sorted to its workspace; also how
+    /// many files left for the next run are saved at a time
+    #[serde(default = "default_candidate_batch_size")]
+    pub candidate_batch_size: usize,
+    /// Copy files whose source changed while they were copied again at the end of the run,
+    /// rather than leaving their possibly torn copies for the next run to replace
+    #[serde(default = "bool_false")]
+    pub recopy_changed_files: bool,
+    /// Where runs keep their workspaces for files they hold back, instead of `.work` next to
+    /// `database_file`; copies are always staged in `.work` at their destination
+    #[serde(default)]
+    pub workspace_dir: Option<String>,
+    /// What happens to source files another program has locked
+    #[serde(default)]
+    pub locked_files: LockedFiles,
+    /// How destinations are checked before a run copies to them
+    #[serde(default)]
+    pub destination_checks: DestinationChecks,
+    /// Order prepared files are copied in
+    #[serde(default)]
+    pub copy_order: CopyOrder,
+    /// Minutes a run may take before the files it hasn't got to are left for the next run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_run_duration_minutes: Option<u64>,
+    /// Bytes a run may copy before the files it hasn't got to are left for the next run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_bytes_per_run: Option<u64>,
+    /// Tokens external systems can POST to `/api/trigger/<token>` to start a backup
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub triggers: Vec<TriggerConfig>,
+    /// When recorded backups are read back to catch corruption at the destinations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scrub: Option<ScrubConfig>,
+    /// Share of the recorded backups, in percent, picked at random and read back at the end of
+    /// every run, between scrubs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verify_sample_percent: Option<f64>,
+    /// Named sets of settings that replace the ones above while the profile is selected
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, serde_json::Map<String, serde_json::Value>>,
 }
 
+/// Settings every profile shares with the rest of the configuration, so they keep one catalog
+pub const PROFILE_SHARED_FIELDS: [&str; 4] = [
+    "database_file",
+    "catalog_database_url",
+    "catalog_sharding",
+    "profiles",
+];
+
+/// A token that starts a backup of a chosen source when it is posted to the API
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TriggerConfig {
+    /// Identifies the trigger in logs and the audit log, so the token itself is never shown
+    pub name: String,
+    pub token: String,
+    /// Configured source to back up, matched by name or parent directory; all sources if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Subdirectory to back up, absolute or relative to the source
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default = "bool_false")]
+    pub dry_run: bool,
+    /// Shortest time between two runs this trigger starts; requests in between are refused
+    #[serde(default = "default_trigger_interval_secs")]
+    pub min_interval_secs: u64,
+}
+
+impl TriggerConfig {
+    pub fn selection(&self) -> RunSelection {
+        RunSelection {
+            source: self.source.clone(),
+            path: self.path.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// How often backups are scrubbed and how much of them each scrub reads
+///
+/// Each scrub takes the backups that were verified longest ago, so over enough scrubs every
+/// backup is read again.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScrubConfig {
+    /// Cron expression of when scrubs run
+    pub schedule: String,
+    /// Share of the recorded backups, in percent, that one scrub checks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub percent: Option<f64>,
+    /// Most gibibytes of backups one scrub reads
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_gibibytes: Option<f64>,
+}
+
+/// Order the copy phase takes prepared files in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CopyOrder {
+    /// As soon as each file is prepared, while the rest are still being hashed
+    #[default]
+    Discovered,
+    SmallestFirst,
+    NewestFirst,
+    /// Sources with a higher `priority` first, in the order their files were prepared
+    SourcePriority,
+}
+
+/// How the catalog of source files and backups is split across database files
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CatalogSharding {
+    /// Everything in `database_file`
+    #[default]
+    None,
+    /// Source files and their backups in a file per backup source
+    PerSource,
+    /// Backups in a file per destination, source files in `database_file`
+    PerDestination,
+}
+
+/// Thresholds for flagging a run against the median of the runs before it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegressionDetection {
+    #[serde(default = "bool_true")]
+    pub enabled: bool,
+    /// How many recent completed runs the baseline is taken from
+    #[serde(default = "default_baseline_runs")]
+    pub baseline_runs: usize,
+    /// Runs needed before anything is flagged
+    #[serde(default = "default_min_baseline_runs")]
+    pub min_baseline_runs: usize,
+    /// Flag hashing or copying this many times slower than the baseline
+    #[serde(default = "default_slowdown_factor")]
+    pub slowdown_factor: f64,
+    /// Flag copying this many times more bytes or files than the baseline
+    #[serde(default = "default_growth_factor")]
+    pub growth_factor: f64,
+    /// Runs that read or copied less than this are never flagged
+    #[serde(default = "default_regression_min_mebibytes")]
+    pub min_mebibytes: u64,
+    /// Runs that copied fewer files than this are never flagged for file count
+    #[serde(default = "default_regression_min_files")]
+    pub min_files: u64,
+}
+
+impl Default for RegressionDetection {
+    fn default() -> Self {
+        RegressionDetection {
+            enabled: true,
+            baseline_runs: default_baseline_runs(),
+            min_baseline_runs: default_min_baseline_runs(),
+            slowdown_factor: default_slowdown_factor(),
+            growth_factor: default_growth_factor(),
+            min_mebibytes: default_regression_min_mebibytes(),
+            min_files: default_regression_min_files(),
+        }
+    }
+}
+
+/// How source files that another program has locked are retried and then given up on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockedFiles {
+    /// Further attempts at reading a locked file before giving up on it
+    #[serde(default = "default_locked_file_retries")]
+    pub retries: u32,
+    /// Milliseconds to wait before each further attempt
+    #[serde(default = "default_locked_file_retry_delay_ms")]
+    pub retry_delay_ms: u64,
+    #[serde(default)]
+    pub action: LockedFileAction,
+}
+
+impl Default for LockedFiles {
+    fn default() -> Self {
+        LockedFiles {
+            retries: default_locked_file_retries(),
+            retry_delay_ms: default_locked_file_retry_delay_ms(),
+            action: LockedFileAction::default(),
+        }
+    }
+}
+
+/// What a run does with a file that is still locked after every retry
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LockedFileAction {
+    /// Leave the file out of the run with a warning
+    #[default]
+    Skip,
+    /// Stop the run and fail it
+    Fail,
+}
+
+/// How destinations are checked before a run copies to them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DestinationChecks {
+    /// Keep a file with an ID in each destination and refuse a destination whose ID doesn't
+    /// match the one recorded on an earlier run, e.g. another drive mounted in its place
+    #[serde(default = "bool_true")]
+    pub markers: bool,
+    #[serde(default)]
+    pub action: UnavailableDestinationAction,
+}
+
+impl Default for DestinationChecks {
+    fn default() -> Self {
+        DestinationChecks {
+            markers: true,
+            action: UnavailableDestinationAction::default(),
+        }
+    }
+}
+
+/// What a run does with a destination that isn't there, isn't writable or isn't the one recorded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnavailableDestinationAction {
+    /// Stop the run before it copies anything
+    #[default]
+    Fail,
+    /// Back up to the other destinations, with a warning
+    Skip,
+}
+
+/// Limits on the changes since the last run, past which the run holds its copies back, e.g.
+/// when ransomware has encrypted a source
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MassChangeGuard {
+    /// Most changed files, in percent of the files the last run found
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_changed_percent: Option<f64>,
+    /// Most files the last run found that may be gone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_deleted_files: Option<u64>,
+}
+
+/// Verification used for copied files up to a size limit
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VerificationTier {
+    /// Largest file this tier applies to; a tier without a limit matches every size
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size_mebibytes: Option<u64>,
+    pub mode: VerificationMode,
+    /// Number of evenly spaced blocks compared in `sampled` mode
+    #[serde(default = "default_verification_samples")]
+    pub samples: u32,
+    /// Size of each compared block in `sampled` mode
+    #[serde(default = "default_sample_kibibytes")]
+    pub sample_kibibytes: u32,
+}
+
+/// How a copy is compared with its source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VerificationMode {
+    /// Compare every byte of the copy with the source
+    Full,
+    /// Compare the first and last blocks and evenly spaced ones in between
+    Sampled,
+    /// Compare the hash of the first `max_mebibytes_for_hash` MiB, the default
+    Hash,
+    /// Hash the data while it is copied instead of reading the copy back
+    Stream,
+}
+
+/// An external executable that extends runs over the JSON line plugin protocol
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginConfig {
+    /// Identifies the plugin in logs and, for destinations, in run reports
+    pub name: String,
+    pub kind: PluginKind,
+    /// Program to run, looked up on `PATH` if not absolute
+    pub command: String,
+    #[serde(default = "vec_default", skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Give up on a plugin that takes longer than this to answer one request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_seconds: Option<u64>,
+}
+
+/// What a plugin is asked to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PluginKind {
+    /// Decides whether each discovered source file is backed up
+    Filter,
+    /// Receives every new or changed source file, e.g. to upload it elsewhere
+    Destination,
+    /// Is told when a run finishes
+    Notifier,
+}
+
+/// An external command run against every file copied into a destination
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostProcessorConfig {
+    /// Identifies the processor in stored results
+    pub name: String,
+    /// Program to run, looked up on `PATH` if not absolute
+    pub command: String,
+    /// Arguments, where `{backup}` and `{source}` are replaced with the copied file's paths.
+    /// When empty the backup path is passed as the only argument.
+    #[serde(default = "vec_default", skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Only run for files whose sniffed content type matches one of these MIME patterns
+    #[serde(default = "vec_default", skip_serializing_if = "Vec::is_empty")]
+    pub content_types: Vec<String>,
+    /// Kill the command and record an error if it runs longer than this
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_seconds: Option<u64>,
+}
+
+/// How a consistent snapshot of a SQLite database in use is taken
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum SqliteSnapshot {
+    /// SQLite's online backup API, through a read-only connection
+    BackupApi,
+    /// An external dump command, e.g. `sqlite3 {source} ".backup {target}"`
+    Command {
+        /// Program to run, looked up on `PATH` if not absolute
+        command: String,
+        /// Arguments, where `{source}` and `{target}` are replaced with the database's path and
+        /// the path the snapshot must be written to
+        #[serde(default = "vec_default")]
+        args: Vec<String>,
+        /// Kill the command and fail the file if it runs longer than this
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        timeout_seconds: Option<u64>,
+    },
+}
+
+/// Resource limits applied to the backup process through a cgroup (Linux) or Job Object (Windows)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LowPriorityGroup {
+    /// cgroup v2 directory the process is moved into (Linux only)
+    #[serde(default = "default_cgroup_path")]
+    pub cgroup_path: String,
+    /// Value written to `cpu.weight`, 1-10000 (Linux only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_weight: Option<u32>,
+    /// Default value written to `io.weight`, 1-10000 (Linux only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub io_weight: Option<u32>,
+    /// Hard cap on CPU usage as a percentage of the machine, 1-100 (Windows only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_rate_percent: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct BackupSource {
     pub parent_directory: String,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub max_depth: Option<usize>,
     #[serde(default = "vec_default")]
     pub skip_dirs: Vec<String>,
+    #[serde(default = "bool_false")]
+    pub auto_discover_children: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub force_overwrite_backup: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_mebibytes_for_hash: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_tail_mebibytes: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destinations: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination_subpath: Option<String>,
+    /// MIME patterns such as `video/*` for files that are skipped based on their content
+    #[serde(default = "vec_default", skip_serializing_if = "Vec::is_empty")]
+    pub exclude_content_types: Vec<String>,
+    /// Files smaller than this many bytes are skipped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_size: Option<u64>,
+    /// Files larger than this many bytes are skipped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>,
+    /// Only files modified within this many days are backed up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_within_days: Option<u64>,
+    /// Only files with one of these extensions (e.g. `pdf` or `.docx`) are backed up
+    #[serde(default = "vec_default", skip_serializing_if = "Vec::is_empty")]
+    pub extensions: Vec<String>,
+    /// Names of gitignore-style files, e.g. `.gitignore` or `.backupignore`, honored while scanning
+    #[serde(default = "vec_default", skip_serializing_if = "Vec::is_empty")]
+    pub ignore_files: Vec<String>,
+    /// Read the source from a snapshot of its volume, so files other programs hold open or locked
+    /// can be backed up; the live files are read if no snapshot can be taken
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<VolumeSnapshot>,
+    /// Files of sources with a higher priority are copied first when `copy_order` is
+    /// `source_priority`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i32>,
+    /// Files and directories with any of these attributes are skipped, e.g. `hidden` for
+    /// dotfiles and OS cruft
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_attributes: Vec<FileAttribute>,
+    /// What discovery does with a directory or file it can't read, e.g. for lack of permission
+    #[serde(default)]
+    pub on_error: WalkErrorPolicy,
+}
+
+/// What a source's discovery does when it can't read part of the tree
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WalkErrorPolicy {
+    /// Fail the run, so a partial listing never counts as the whole source
+    #[default]
+    Abort,
+    /// Leave the entry out, list it in the run report and carry on with the rest
+    Skip,
+}
+
+/// A file attribute that `exclude_attributes` skips files and directories by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileAttribute {
+    /// Names starting with a dot, and the hidden attribute or flag on Windows and macOS
+    Hidden,
+    /// The system attribute on Windows
+    System,
+    /// The `nodump` flag set by `chattr +d` on Linux or `chflags nodump` on macOS and BSD
+    Nodump,
+}
+
+impl FileAttribute {
+    /// Whether files can carry this attribute on the platform the binary was built for
+    pub fn is_supported(&self) -> bool {
+        match self {
+            FileAttribute::Hidden => true,
+            FileAttribute::System => cfg!(windows),
+            FileAttribute::Nodump => cfg!(any(
+                target_os = "linux",
+                target_os = "macos",
+                target_os = "freebsd",
+                target_os = "netbsd",
+                target_os = "openbsd",
+                target_os = "dragonfly"
+            )),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileAttribute::Hidden => "hidden",
+            FileAttribute::System => "system",
+            FileAttribute::Nodump => "nodump",
+        }
+    }
+}
+
+/// How a source's volume is snapshotted for the length of a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VolumeSnapshot {
+    /// Volume Shadow Copy, on Windows
+    Vss,
+    /// A snapshot of the LVM logical volume the source is mounted from, on Linux
+    Lvm,
+    /// A read-only snapshot of the btrfs subvolume holding the source, on Linux
+    Btrfs,
+}
+
+impl VolumeSnapshot {
+    /// Whether this kind of snapshot can be taken on the platform the binary was built for
+    pub fn is_supported(&self) -> bool {
+        match self {
+            VolumeSnapshot::Vss => cfg!(windows),
+            VolumeSnapshot::Lvm | VolumeSnapshot::Btrfs => cfg!(target_os = "linux"),
+        }
+    }
+}
+
+impl std::fmt::Display for VolumeSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            VolumeSnapshot::Vss => "Volume Shadow Copy",
+            VolumeSnapshot::Lvm => "LVM",
+            VolumeSnapshot::Btrfs => "btrfs",
+        })
+    }
+}
+
+/// A backup destination, given either as a bare path or as an object with its settings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum BackupDestination {
+    Path(String),
+    Detailed {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        path: String,
+        /// Most files copied to or verified at the destination at once, e.g. 1 for a spinning
+        /// disk; otherwise as many as there are copy workers
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_parallel_files: Option<usize>,
+        /// Kind of filesystem the destination is on, deciding which names it takes as they are
+        #[serde(default, skip_serializing_if = "Filesystem::is_posix")]
+        filesystem: Filesystem,
+        /// On a separately mounted drive or share: a run needs the path to exist on another
+        /// filesystem than `/`, rather than creating it on an empty mount point
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        mounted: bool,
+        /// Mounts the destination before a run; if it fails the destination is skipped as offline
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mount_command: Option<Box<DestinationCommand>>,
+        /// Unmounts the destination after a run that mounted it
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        unmount_command: Option<Box<DestinationCommand>>,
+        /// Block device udisks mounts before a run and unmounts after it, e.g.
+        /// `/dev/disk/by-label/BACKUP1` (Linux only)
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        udisks_device: Option<String>,
+        /// Most bytes of backups the destination may hold; once a run would go over it, it stops
+        /// copying to the destination and reports what was left out
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_total_bytes: Option<u64>,
+    },
+}
+
+/// A command run for a destination around a run, e.g. to mount a removable drive
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DestinationCommand {
+    /// Program to run, looked up on `PATH` if not absolute
+    pub command: String,
+    /// Arguments, where `{path}` is replaced with the destination's path
+    #[serde(default = "vec_default", skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Kill the command and count it as failed if it runs longer than this
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_seconds: Option<u64>,
+}
+
+/// Filesystem a destination is on, and so which file and folder names it can hold
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Filesystem {
+    /// Any name a Linux or macOS source can have
+    #[default]
+    Posix,
+    /// NTFS, FAT32 or exFAT, which refuse `:`, `?`, `*` and the like, and names such as `CON`
+    #[serde(alias = "ntfs", alias = "fat", alias = "fat32", alias = "exfat")]
+    Windows,
+}
+
+impl Filesystem {
+    fn is_posix(&self) -> bool {
+        *self == Filesystem::Posix
+    }
+
+    /// The name a file or folder called `name` gets on this filesystem
+    pub fn file_name<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        match self {
+            Filesystem::Posix => Cow::Borrowed(name),
+            Filesystem::Windows => windows_file_name(name),
+        }
+    }
+
+    /// `relative_dir` with each folder named as this filesystem can hold it
+    pub fn dir_names<'a>(&self, relative_dir: &'a Path) -> Cow<'a, Path> {
+        if self.is_posix() {
+            return Cow::Borrowed(relative_dir);
+        }
+        Cow::Owned(
+            relative_dir
+                .iter()
+                .map(|name| self.file_name(&name.to_string_lossy()).into_owned())
+                .collect(),
+        )
+    }
+
+    /// The name of the file or folder this filesystem holds as `name`
+    pub fn source_name<'a>(&self, name: &'a str) -> Cow<'a, str> {
+        match self {
+            Filesystem::Posix => Cow::Borrowed(name),
+            Filesystem::Windows => decode_windows_file_name(name),
+        }
+    }
+
+    /// `relative_path` as found in the source, each of its names decoded from this filesystem
+    pub fn source_names<'a>(&self, relative_path: &'a Path) -> Cow<'a, Path> {
+        if self.is_posix() {
+            return Cow::Borrowed(relative_path);
+        }
+        Cow::Owned(
+            relative_path
+                .iter()
+                .map(|name| self.source_name(&name.to_string_lossy()).into_owned())
+                .collect(),
+        )
+    }
+}
+
+impl BackupDestination {
+    pub fn path(&self) -> &str {
+        match self {
+            BackupDestination::Path(path) => path,
+            BackupDestination::Detailed { path, .. } => path,
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            BackupDestination::Path(_) => None,
+            BackupDestination::Detailed { name, .. } => name.as_deref(),
+        }
+    }
+
+    pub fn max_parallel_files(&self) -> Option<usize> {
+        match self {
+            BackupDestination::Path(_) => None,
+            BackupDestination::Detailed {
+                max_parallel_files, ..
+            } => *max_parallel_files,
+        }
+    }
+
+    pub fn filesystem(&self) -> Filesystem {
+        match self {
+            BackupDestination::Path(_) => Filesystem::Posix,
+            BackupDestination::Detailed { filesystem, .. } => *filesystem,
+        }
+    }
+
+    /// Whether the destination is a separately mounted drive or share, as it is when a run
+    /// mounts it
+    pub fn mounted(&self) -> bool {
+        match self {
+            BackupDestination::Path(_) => false,
+            BackupDestination::Detailed { mounted, .. } => {
+                *mounted || self.mount_command().is_some() || self.udisks_device().is_some()
+            }
+        }
+    }
+
+    pub fn mount_command(&self) -> Option<&DestinationCommand> {
+        match self {
+            BackupDestination::Path(_) => None,
+            BackupDestination::Detailed { mount_command, .. } => mount_command.as_deref(),
+        }
+    }
+
+    pub fn unmount_command(&self) -> Option<&DestinationCommand> {
+        match self {
+            BackupDestination::Path(_) => None,
+            BackupDestination::Detailed {
+                unmount_command, ..
+            } => unmount_command.as_deref(),
+        }
+    }
+
+    pub fn max_total_bytes(&self) -> Option<u64> {
+        match self {
+            BackupDestination::Path(_) => None,
+            BackupDestination::Detailed {
+                max_total_bytes, ..
+            } => *max_total_bytes,
+        }
+    }
+
+    pub fn udisks_device(&self) -> Option<&str> {
+        match self {
+            BackupDestination::Path(_) => None,
+            BackupDestination::Detailed { udisks_device, .. } => udisks_device.as_deref(),
+        }
+    }
+
+    /// Returns true if a source's destination reference points at this destination
+    pub fn matches(&self, reference: &str) -> bool {
+        self.name() == Some(reference) || self.path() == reference
+    }
+}
+
+impl From<String> for BackupDestination {
+    fn from(path: String) -> Self {
+        BackupDestination::Path(path)
+    }
+}
+
+/// How much of a file its hash covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HashLimit {
+    /// MiB at the start of the file
+    pub max_mebibytes: usize,
+    /// MiB at the end of a file longer than `max_mebibytes`, hashed along with its size
+    #[serde(default)]
+    pub tail_mebibytes: usize,
+}
+
+impl HashLimit {
+    /// The first `max_mebibytes` MiB and nothing of the end
+    pub fn new(max_mebibytes: usize) -> Self {
+        HashLimit {
+            max_mebibytes,
+            tail_mebibytes: 0,
+        }
+    }
+
+    pub fn with_tail(self, tail_mebibytes: usize) -> Self {
+        HashLimit {
+            tail_mebibytes,
+            ..self
+        }
+    }
+
+    /// Bytes read to hash a file `file_size` bytes long
+    pub fn bytes_hashed(&self, file_size: u64) -> u64 {
+        file_size.min((self.max_mebibytes + self.tail_mebibytes) as u64 * 1024 * 1024)
+    }
+}
+
+/// Effective settings for one source after applying its overrides to the global config
+#[derive(Debug, Clone)]
+pub struct SourceSettings {
+    pub name: String,
+    pub max_mebibytes_for_hash: usize,
+    pub hash_tail_mebibytes: usize,
+    pub force_overwrite_backup: bool,
+    /// Destination root paths this source is routed to
+    pub destinations: Vec<String>,
+    /// Subdirectory under each destination that receives this source's files
+    pub destination_subpath: Option<String>,
+}
+
+impl SourceSettings {
+    pub fn hash_limit(&self) -> HashLimit {
+        HashLimit::new(self.max_mebibytes_for_hash).with_tail(self.hash_tail_mebibytes)
+    }
+}
+
+impl BackupSource {
+    /// Name used in progress events and reports, falling back to the directory
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.parent_directory)
+    }
+}
+
+impl Config {
+    /// Paths of every configured destination
+    pub fn destination_paths(&self) -> Vec<String> {
+        self.backup_destinations
+            .iter()
+            .map(|d| d.path().to_string())
+            .collect()
+    }
+
+    /// Name of the configured destination a backup path is under, or its path if unnamed
+    ///
+    /// Paths outside every destination, such as plugin destination labels, are returned as is.
+    pub fn destination_name(&self, backup_path: &str) -> String {
+        self.destination_of(Path::new(backup_path))
+            .map_or(backup_path, |d| d.name().unwrap_or(d.path()))
+            .to_string()
+    }
+
+    /// Whether modification time `modified` is later than `than` by more than
+    /// `mtime_tolerance_secs`
+    pub fn modified_after(&self, modified: &Duration, than: &Duration) -> bool {
+        modified.as_secs() > than.as_secs() + self.mtime_tolerance_secs
+    }
+
+    /// The configured destination a backup path is under
+    pub fn destination_of(&self, backup_path: &Path) -> Option<&BackupDestination> {
+        self.backup_destinations
+            .iter()
+            .filter(|d| backup_path.starts_with(d.path()))
+            .max_by_key(|d| d.path().len())
+    }
+
+    /// Filesystem of the configured destination at `path`; POSIX if none is configured there
+    pub fn filesystem_of(&self, path: &str) -> Filesystem {
+        self.backup_destinations
+            .iter()
+            .find(|d| d.path() == path)
+            .map_or(Filesystem::Posix, BackupDestination::filesystem)
+    }
+
+    /// The trigger `token` belongs to
+    ///
+    /// Every token is compared in full so the time taken doesn't tell how much of one matched.
+    pub fn trigger_for_token(&self, token: &str) -> Option<&TriggerConfig> {
+        self.triggers.iter().fold(None, |found, trigger| {
+            let matches = constant_time_eq(trigger.token.as_bytes(), token.as_bytes());
+            found.or(matches.then_some(trigger))
+        })
+    }
+
+    /// This configuration with the settings of profile `name` in place of its own
+    ///
+    /// The result has no profiles of its own; settings the profile leaves out keep their values.
+    pub fn with_profile(&self, name: &str) -> Result<Config> {
+        let overrides = self.profiles.get(name).ok_or_else(|| {
+            BackupError::Profile(format!(
+                "no profile named '{}'; the configuration has {}",
+                name,
+                self.profile_list()
+            ))
+        })?;
+        if let Some(field) = PROFILE_SHARED_FIELDS
+            .iter()
+            .find(|field| overrides.contains_key(**field))
+        {
+            return Err(BackupError::Profile(format!(
+                "profile '{}' sets {}, which every profile shares",
+                name, field
+            )));
+        }
+
+        let mut value = serde_json::to_value(self)
+            .map_err(|e| BackupError::Profile(format!("profile '{}': {}", name, e)))?;
+        if let serde_json::Value::Object(fields) = &mut value {
+            fields.remove("profiles");
+            fields.extend(overrides.clone());
+        }
+        serde_json::from_value(value)
+            .map_err(|e| BackupError::Profile(format!("profile '{}': {}", name, e)))
+    }
+
+    fn profile_list(&self) -> String {
+        if self.profiles.is_empty() {
+            return "no profiles".to_string();
+        }
+        let names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+        format!("profiles {}", names.join(", "))
+    }
+
+    /// Roots of the catalog shards `catalog_sharding` asks for
+    pub fn catalog_shard_roots(&self) -> Vec<String> {
+        match self.catalog_sharding {
+            CatalogSharding::None => Vec::new(),
+            CatalogSharding::PerSource => self
+                .backup_sources
+                .iter()
+                .map(|s| s.parent_directory.clone())
+                .collect(),
+            CatalogSharding::PerDestination => self.destination_paths(),
+        }
+    }
+
+    /// Whether a file of `file_size` bytes is copied every run, see `always_copy_below_bytes`
+    pub fn always_copies(&self, file_size: u64) -> bool {
+        self.always_copy_below_bytes
+            .is_some_and(|below| file_size < below)
+    }
+
+    /// Hash limit of files outside every source's overrides
+    pub fn hash_limit(&self) -> HashLimit {
+        HashLimit::new(self.max_mebibytes_for_hash).with_tail(self.hash_tail_mebibytes)
+    }
+
+    /// Resolve the effective settings for the source rooted at `parent_directory`
+    pub fn source_settings(&self, parent_directory: &Path) -> SourceSettings {
+        let source = self
+            .backup_sources
+            .iter()
+            .find(|s| Path::new(&s.parent_directory) == parent_directory);
+
+        SourceSettings {
+            name: source
+                .map(|s| s.display_name().to_string())
+                .unwrap_or_else(|| parent_directory.to_string_lossy().to_string()),
+            max_mebibytes_for_hash: source
+                .and_then(|s| s.max_mebibytes_for_hash)
+                .unwrap_or(self.max_mebibytes_for_hash),
+            hash_tail_mebibytes: source
+                .and_then(|s| s.hash_tail_mebibytes)
+                .unwrap_or(self.hash_tail_mebibytes),
+            force_overwrite_backup: source
+                .and_then(|s| s.force_overwrite_backup)
+                .unwrap_or(self.force_overwrite_backup),
+            destinations: match source.and_then(|s| s.destinations.as_ref()) {
+                Some(references) => self
+                    .backup_destinations
+                    .iter()
+                    .filter(|d| references.iter().any(|r| d.matches(r)))
+                    .map(|d| d.path().to_string())
+                    .collect(),
+                None => self.destination_paths(),
+            },
+            destination_subpath: source.and_then(|s| s.destination_subpath.clone()),
+        }
+    }
 }
 
 const fn vec_default() -> Vec<String> {
     Vec::new()
 }
+/// Whether `a` and `b` are equal, taking as long wherever they differ
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+const fn default_trigger_interval_secs() -> u64 {
+    60
+}
+
+const fn default_clock_skew_tolerance_secs() -> u64 {
+    2
+}
+const fn default_baseline_runs() -> usize {
+    10
+}
+const fn default_min_baseline_runs() -> usize {
+    3
+}
+const fn default_slowdown_factor() -> f64 {
+    3.0
+}
+const fn default_growth_factor() -> f64 {
+    5.0
+}
+const fn default_regression_min_mebibytes() -> u64 {
+    100
+}
+const fn default_regression_min_files() -> u64 {
+    1000
+}
+const fn default_log_retention_days() -> Option<u64> {
+    Some(30)
+}
+const fn default_max_log_rows() -> Option<u64> {
+    Some(100_000)
+}
+const fn default_file_action_runs() -> u64 {
+    10
+}
+const fn default_candidate_batch_size() -> usize {
+    10_000
+}
+const fn default_locked_file_retries() -> u32 {
+    3
+}
+const fn default_locked_file_retry_delay_ms() -> u64 {
+    1000
+}
+const fn default_copy_buffer_kib() -> usize {
+    DEFAULT_COPY_BUFFER_KIB
+}
+const fn default_verification_samples() -> u32 {
+    16
+}
+const fn default_sample_kibibytes() -> u32 {
+    64
+}
 const fn usize_one() -> usize {
     1
 }
@@ -50,8 +1082,21 @@ const fn bool_true() -> bool {
 fn default_max_threads() -> usize {
     num_cpus::get_physical()
 }
+fn default_cgroup_path() -> String {
+    "/sys/fs/cgroup/rustyhashbackup".to_string()
+}
 
 pub fn setup_config(config_file: String) -> Result<Config> {
+    let config = load_config(config_file)?;
+
+    // Validate configuration
+    validate_config(&config)?;
+
+    Ok(config)
+}
+
+/// Read and parse the config file without validating it
+pub fn load_config(config_file: String) -> Result<Config> {
     let config_path = PathBuf::from(config_file);
     info!("Loading config from: {}", config_path.display());
 
@@ -66,9 +1111,6 @@ pub fn setup_config(config_file: String) -> Result<Config> {
             cause,
         })?;
 
-    // Validate configuration
-    validate_config(&config)?;
-
     Ok(config)
 }
 
@@ -160,6 +1202,116 @@ mod tests {
         assert_eq!(config.backup_sources[0].skip_dirs.len(), 0); // default empty vec
     }
 
+    #[test]
+    fn test_source_settings_apply_overrides() {
+        let config: Config = serde_json::from_str(
+            r#"{
+            "database_file": "",
+            "max_mebibytes_for_hash": 4,
+            "backup_sources": [
+                {
+                    "parent_directory": "/photos",
+                    "name": "photos",
+                    "force_overwrite_backup": true,
+                    "max_mebibytes_for_hash": 16,
+                    "destinations": ["/mnt/nas"]
+                },
+                { "parent_directory": "/documents" }
+            ],
+            "backup_destinations": ["/mnt/nas", "/mnt/usb"]
+        }"#,
+        )
+        .unwrap();
+
+        let photos = config.source_settings(Path::new("/photos"));
+        assert_eq!(photos.name, "photos");
+        assert_eq!(photos.max_mebibytes_for_hash, 16);
+        assert!(photos.force_overwrite_backup);
+        assert_eq!(photos.destinations, vec!["/mnt/nas"]);
+
+        let documents = config.source_settings(Path::new("/documents"));
+        assert_eq!(documents.name, "/documents");
+        assert_eq!(documents.max_mebibytes_for_hash, 4);
+        assert!(!documents.force_overwrite_backup);
+        assert_eq!(documents.destinations.len(), 2);
+    }
+
+    #[test]
+    fn test_named_destinations_route_sources() {
+        let config: Config = serde_json::from_str(
+            r#"{
+            "database_file": "",
+            "backup_sources": [
+                {
+                    "parent_directory": "/photos",
+                    "destinations": ["usb"],
+                    "destination_subpath": "photos"
+                }
+            ],
+            "backup_destinations": [
+                { "name": "nas", "path": "/mnt/nas" },
+                { "name": "usb", "path": "/mnt/usb" },
+                "/mnt/legacy"
+            ]
+        }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.destination_paths(),
+            vec!["/mnt/nas", "/mnt/usb", "/mnt/legacy"]
+        );
+        assert_eq!(
+            config.backup_destinations[2],
+            BackupDestination::Path("/mnt/legacy".to_string())
+        );
+
+        let photos = config.source_settings(Path::new("/photos"));
+        assert_eq!(photos.destinations, vec!["/mnt/usb"]);
+        assert_eq!(photos.destination_subpath.as_deref(), Some("photos"));
+
+        assert_eq!(config.destination_name("/mnt/usb/photos/a.jpg"), "usb");
+        assert_eq!(config.destination_name("/mnt/legacy/a.jpg"), "/mnt/legacy");
+        assert_eq!(
+            config.destination_name("/mnt/nas2/a.jpg"),
+            "/mnt/nas2/a.jpg"
+        );
+    }
+
+    #[test]
+    fn test_profiles_replace_settings() {
+        let config: Config = serde_json::from_str(
+            r#"{
+            "database_file": "/var/lib/backup.db",
+            "max_threads": 8,
+            "backup_sources": [{ "parent_directory": "/home" }],
+            "backup_destinations": ["/mnt/nas"],
+            "profiles": {
+                "quick-docs": {
+                    "backup_sources": [{ "parent_directory": "/home/docs", "max_depth": 3 }],
+                    "quick_skip_unchanged": true
+                },
+                "elsewhere": { "database_file": "/tmp/other.db" }
+            }
+        }"#,
+        )
+        .unwrap();
+
+        let docs = config.with_profile("quick-docs").unwrap();
+        assert_eq!(docs.backup_sources[0].parent_directory, "/home/docs");
+        assert_eq!(docs.backup_sources[0].max_depth, Some(3));
+        assert!(docs.quick_skip_unchanged);
+        assert_eq!(docs.max_threads, 8);
+        assert_eq!(docs.destination_paths(), vec!["/mnt/nas"]);
+        assert_eq!(docs.database_file, "/var/lib/backup.db");
+        assert!(docs.profiles.is_empty());
+
+        let err = config.with_profile("elsewhere").unwrap_err().to_string();
+        assert!(err.contains("sets database_file"), "{}", err);
+        let err = config.with_profile("nightly").unwrap_err().to_string();
+        assert!(err.contains("profiles elsewhere, quick-docs"), "{}", err);
+    }
+
     #[test]
     fn test_error_on_missing_config_file() {
         let result = setup_config("/this/does/not/exist/config.json".to_string());
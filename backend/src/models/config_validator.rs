@@ -1,71 +1,273 @@
-use crate::models::config::{BackupSource, Config};
+use crate::models::config::{
+    BackupDestination, BackupSource, CatalogSharding, Config, LowPriorityGroup, MassChangeGuard,
+    PluginConfig, PostProcessorConfig, RegressionDetection, ScrubConfig, SqliteSnapshot,
+    TriggerConfig, VerificationMode, VerificationTier,
+};
 use crate::models::error::{BackupError, Result};
+use crate::models::run_selection::RunSelection;
+use crate::models::validation_issue::{Severity, ValidationIssue};
+use crate::repo::postgres::parse_url;
+use crate::repo::sqlite::MAX_CATALOG_SHARDS;
+use crate::utils::file_filter::normalize_extension;
 use log::{info, warn};
+use std::collections::HashSet;
 use std::fs;
 use std::path::Path;
 use std::str::FromStr;
 
+/// Findings collected while validating a configuration
+#[derive(Debug, Default)]
+struct Issues(Vec<ValidationIssue>);
+
+impl Issues {
+    fn error(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.push(field, message, Severity::Error);
+    }
+
+    fn warning(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.push(field, message, Severity::Warning);
+    }
+
+    fn push(&mut self, field: impl Into<String>, message: impl Into<String>, severity: Severity) {
+        self.0.push(ValidationIssue {
+            field: field.into(),
+            message: message.into(),
+            severity,
+        });
+    }
+}
+
 /// Validates the entire configuration
+///
+/// Warnings are logged; any errors are returned together as `BackupError::ConfigValidation`.
 pub fn validate_config(config: &Config) -> Result<()> {
     info!("Validating configuration...");
 
+    let (errors, warnings): (Vec<_>, Vec<_>) = check_config(config)
+        .into_iter()
+        .partition(ValidationIssue::is_error);
+
+    for warning in &warnings {
+        warn!("{}", warning);
+    }
+
+    if !errors.is_empty() {
+        return Err(BackupError::ConfigValidation(errors));
+    }
+
+    info!("Configuration validation passed");
+    Ok(())
+}
+
+/// Collect every error and warning for a configuration, tagged with the offending field
+pub fn check_config(config: &Config) -> Vec<ValidationIssue> {
+    let mut issues = Issues::default();
+
     // Validate numeric values
-    validate_numeric_values(config)?;
+    validate_numeric_values(config, &mut issues);
 
     // Validate backup sources
-    validate_backup_sources(&config.backup_sources)?;
+    validate_backup_sources(&config.backup_sources, &mut issues);
 
     // Validate backup destinations
-    validate_backup_destinations(&config.backup_destinations)?;
+    validate_backup_destinations(&config.backup_destinations, &mut issues);
+
+    // Validate per-source overrides
+    validate_source_overrides(config, &mut issues);
+
+    // Validate checkpoint paths
+    validate_critical_paths(config, &mut issues);
+
+    // Validate how live SQLite databases are captured
+    validate_sqlite_snapshot(config.sqlite_snapshot.as_ref(), &mut issues);
+    validate_regression_detection(&config.regression_detection, &mut issues);
+    if let Some(guard) = &config.mass_change_guard {
+        validate_mass_change_guard(guard, config.file_action_runs, &mut issues);
+    }
+
+    // Validate post-copy processors
+    validate_post_processors(&config.post_processors, &mut issues);
+
+    // Validate plugins
+    validate_plugins(&config.plugins, &mut issues);
+
+    // Validate webhook triggers
+    validate_triggers(&config.triggers, &config.backup_sources, &mut issues);
+
+    // Validate copy verification tiers
+    validate_verification_tiers(&config.verification_tiers, &mut issues);
 
     // Validate database file
-    validate_database_path(&config.database_file)?;
+    validate_database_path(&config.database_file, &mut issues);
+    validate_catalog_sharding(config, &mut issues);
+    validate_catalog_database_url(config, &mut issues);
 
     // Validate schedule if present
-    validate_schedule(config)?;
+    validate_schedule(config, &mut issues);
+    if let Some(scrub) = &config.scrub {
+        validate_scrub(scrub, &mut issues);
+    }
 
     // Check for conflicting flags
-    check_conflicting_flags(config)?;
+    check_conflicting_flags(config, &mut issues);
 
-    info!("Configuration validation passed");
-    Ok(())
+    // Validate profiles last, so their findings can be told apart from the ones above
+    validate_profiles(config, &mut issues);
+
+    issues.0
 }
 
 /// Validate numeric configuration values
-fn validate_numeric_values(config: &Config) -> Result<()> {
+fn validate_numeric_values(config: &Config, issues: &mut Issues) {
     if config.max_mebibytes_for_hash == 0 {
-        return Err(BackupError::DirectoryRead(
-            "max_mebibytes_for_hash must be greater than 0".to_string(),
-        ));
+        issues.error(
+            "max_mebibytes_for_hash",
+            "max_mebibytes_for_hash must be greater than 0",
+        );
     }
 
     if config.max_threads == 0 {
-        return Err(BackupError::DirectoryRead(
-            "max_threads must be greater than 0".to_string(),
-        ));
+        issues.error("max_threads", "max_threads must be greater than 0");
+    }
+
+    if config.copy_buffer_kib == 0 {
+        issues.error("copy_buffer_kib", "copy_buffer_kib must be greater than 0");
+    }
+
+    if config.trash_retention_days == Some(0) {
+        issues.error(
+            "trash_retention_days",
+            "trash_retention_days must be greater than 0, or left out to replace backups in place",
+        );
+    }
+
+    if config.always_copy_below_bytes == Some(0) {
+        issues.error(
+            "always_copy_below_bytes",
+            "always_copy_below_bytes must be greater than 0, or left out to check every file",
+        );
+    }
+
+    if config.candidate_batch_size == 0 {
+        issues.error(
+            "candidate_batch_size",
+            "candidate_batch_size must be greater than 0",
+        );
+    }
+
+    if config.catalog_backups_kept == Some(0) {
+        issues.error(
+            "catalog_backups_kept",
+            "catalog_backups_kept must be greater than 0, or left out to take no snapshots",
+        );
+    }
+
+    if let Some(percent) = config.verify_sample_percent {
+        if !(percent > 0.0 && percent <= 100.0) {
+            issues.error(
+                "verify_sample_percent",
+                format!(
+                    "verify_sample_percent must be above 0 and at most 100, got {}",
+                    percent
+                ),
+            );
+        }
+    }
+
+    if config.backup_marker_max_age_hours == Some(0) {
+        issues.error(
+            "backup_marker_max_age_hours",
+            "backup_marker_max_age_hours must be greater than 0",
+        );
+    }
+
+    for (field, value) in [
+        ("log_retention_days", config.log_retention_days),
+        ("max_log_rows", config.max_log_rows),
+        ("max_run_duration_minutes", config.max_run_duration_minutes),
+        ("max_bytes_per_run", config.max_bytes_per_run),
+    ] {
+        if value == Some(0) {
+            issues.error(
+                field,
+                format!("{} must be greater than 0, or null for no limit", field),
+            );
+        }
+    }
+
+    if let Some(group) = &config.low_priority_group {
+        validate_low_priority_group(group, issues);
+    }
+
+    // A time zone shift is an hour; beyond that, real edits start going unnoticed
+    if config.mtime_tolerance_secs > 3600 {
+        issues.warning(
+            "mtime_tolerance_secs",
+            format!(
+                "mtime_tolerance_secs ({}) is more than an hour; files changed within it without changing size are only found by a full hash",
+                config.mtime_tolerance_secs
+            ),
+        );
     }
 
     // Warn if max_threads is excessive
     let cpu_count = num_cpus::get_physical();
     if config.max_threads > cpu_count * 2 {
-        warn!(
-            "max_threads ({}) is more than 2x the number of physical CPUs ({}). This may not improve performance.",
-            config.max_threads, cpu_count
+        issues.warning(
+            "max_threads",
+            format!(
+                "max_threads ({}) is more than 2x the number of physical CPUs ({}). This may not improve performance.",
+                config.max_threads, cpu_count
+            ),
         );
     }
+}
 
-    Ok(())
+/// Validate the weights and limits of the low-priority resource group
+fn validate_low_priority_group(group: &LowPriorityGroup, issues: &mut Issues) {
+    for (field, value) in [
+        ("cpu_weight", group.cpu_weight),
+        ("io_weight", group.io_weight),
+    ] {
+        if let Some(weight) = value {
+            if !(1..=10000).contains(&weight) {
+                issues.error(
+                    format!("low_priority_group.{}", field),
+                    format!("{} must be between 1 and 10000", field),
+                );
+            }
+        }
+    }
+
+    if let Some(percent) = group.cpu_rate_percent {
+        if !(1..=100).contains(&percent) {
+            issues.error(
+                "low_priority_group.cpu_rate_percent",
+                "cpu_rate_percent must be between 1 and 100",
+            );
+        }
+    }
+
+    if group.cgroup_path.trim().is_empty() {
+        issues.error(
+            "low_priority_group.cgroup_path",
+            "cgroup_path cannot be empty",
+        );
+    }
 }
 
 /// Validate backup source directories
-fn validate_backup_sources(sources: &[BackupSource]) -> Result<()> {
+fn validate_backup_sources(sources: &[BackupSource], issues: &mut Issues) {
     if sources.is_empty() {
-        return Err(BackupError::DirectoryRead(
-            "At least one backup source must be configured".to_string(),
-        ));
+        issues.error(
+            "backup_sources",
+            "At least one backup source must be configured",
+        );
+        return;
     }
 
     for (idx, source) in sources.iter().enumerate() {
+        let field = format!("backup_sources[{}]", idx);
         let path = Path::new(&source.parent_directory);
 
         // Check if directory exists
@@ -75,56 +277,171 @@ fn validate_backup_sources(sources: &[BackupSource]) -> Result<()> {
             #[cfg(not(windows))]
             let suggestion = format!("mkdir -p \"{}\"", source.parent_directory);
 
-            return Err(BackupError::DirectoryRead(format!(
-                "Backup source #{} does not exist: {}\nSuggestion: Create the directory with: {}",
-                idx + 1,
-                source.parent_directory,
-                suggestion
-            )));
+            issues.error(
+                format!("{}.parent_directory", field),
+                format!(
+                    "Backup source #{} does not exist: {}\nSuggestion: Create the directory with: {}",
+                    idx + 1,
+                    source.parent_directory,
+                    suggestion
+                ),
+            );
+        } else if !path.is_dir() {
+            // Check if it's a directory
+            issues.error(
+                format!("{}.parent_directory", field),
+                format!(
+                    "Backup source #{} is not a directory: {}",
+                    idx + 1,
+                    source.parent_directory
+                ),
+            );
+        } else if let Err(e) = fs::read_dir(path) {
+            // Check if readable
+            issues.error(
+                format!("{}.parent_directory", field),
+                format!(
+                    "Backup source #{} is not readable: {}\nError: {}",
+                    idx + 1,
+                    source.parent_directory,
+                    e
+                ),
+            );
         }
 
-        // Check if it's a directory
-        if !path.is_dir() {
-            return Err(BackupError::DirectoryRead(format!(
-                "Backup source #{} is not a directory: {}",
-                idx + 1,
-                source.parent_directory
-            )));
+        // Validate max_depth
+        if source.max_depth == Some(0) {
+            issues.error(
+                format!("{}.max_depth", field),
+                format!(
+                    "Backup source #{} has max_depth of 0, which means no files will be found. Set max_depth to at least 1.",
+                    idx + 1
+                ),
+            );
         }
 
-        // Check if readable
-        if let Err(e) = fs::read_dir(path) {
-            return Err(BackupError::DirectoryRead(format!(
-                "Backup source #{} is not readable: {}\nError: {}",
-                idx + 1,
-                source.parent_directory,
-                e
-            )));
+        if let Some(snapshot) = source.snapshot {
+            if !snapshot.is_supported() {
+                issues.warning(
+                    format!("{}.snapshot", field),
+                    format!(
+                        "Backup source #{} asks for a {} snapshot, which this platform can't take; its live files will be read",
+                        idx + 1,
+                        snapshot
+                    ),
+                );
+            }
         }
 
-        // Validate max_depth
-        if source.max_depth == Some(0) {
-            return Err(BackupError::DirectoryRead(format!(
-                "Backup source #{} has max_depth of 0, which means no files will be found. Set max_depth to at least 1.",
-                idx + 1
-            )));
+        for attribute in &source.exclude_attributes {
+            if !attribute.is_supported() {
+                issues.warning(
+                    format!("{}.exclude_attributes", field),
+                    format!(
+                        "Backup source #{} excludes {} files, which this platform doesn't mark; none will be skipped for it",
+                        idx + 1,
+                        attribute.as_str()
+                    ),
+                );
+            }
         }
     }
-
-    Ok(())
 }
 
 /// Validate backup destination directories
-fn validate_backup_destinations(destinations: &[String]) -> Result<()> {
+fn validate_backup_destinations(destinations: &[BackupDestination], issues: &mut Issues) {
     if destinations.is_empty() {
-        return Err(BackupError::DirectoryRead(
-            "At least one backup destination must be configured".to_string(),
-        ));
+        issues.error(
+            "backup_destinations",
+            "At least one backup destination must be configured",
+        );
+        return;
     }
 
-    for (idx, dest) in destinations.iter().enumerate() {
+    let mut names = HashSet::new();
+    for (idx, destination) in destinations.iter().enumerate() {
+        if let Some(name) = destination.name() {
+            let field = format!("backup_destinations[{}].name", idx);
+            if name.trim().is_empty() {
+                issues.error(
+                    field,
+                    format!(
+                        "Backup destination {} has an empty name",
+                        destination.path()
+                    ),
+                );
+            } else if !names.insert(name) {
+                issues.error(
+                    field,
+                    format!("Backup destination name '{}' is used more than once", name),
+                );
+            }
+        }
+        if destination.max_parallel_files() == Some(0) {
+            issues.error(
+                format!("backup_destinations[{}].max_parallel_files", idx),
+                format!(
+                    "Backup destination {} must allow at least one file at a time",
+                    destination.path()
+                ),
+            );
+        }
+        if destination.max_total_bytes() == Some(0) {
+            issues.error(
+                format!("backup_destinations[{}].max_total_bytes", idx),
+                format!(
+                    "Backup destination {} must be allowed to hold some bytes",
+                    destination.path()
+                ),
+            );
+        }
+        for (key, command) in [
+            ("mount_command", destination.mount_command()),
+            ("unmount_command", destination.unmount_command()),
+        ] {
+            if command.is_some_and(|command| command.command.trim().is_empty()) {
+                issues.error(
+                    format!("backup_destinations[{}].{}.command", idx, key),
+                    format!(
+                        "Backup destination {} has an empty {}",
+                        destination.path(),
+                        key
+                    ),
+                );
+            }
+        }
+        if destination.udisks_device().is_some()
+            && (destination.mount_command().is_some() || destination.unmount_command().is_some())
+        {
+            issues.error(
+                format!("backup_destinations[{}].udisks_device", idx),
+                format!(
+                    "Backup destination {} is mounted either by udisks or by its own commands",
+                    destination.path()
+                ),
+            );
+        } else if destination.udisks_device().is_some() && !cfg!(target_os = "linux") {
+            issues.error(
+                format!("backup_destinations[{}].udisks_device", idx),
+                "udisks is only available on Linux",
+            );
+        }
+    }
+
+    for (idx, destination) in destinations.iter().enumerate() {
+        let dest = destination.path();
+        let field = format!("backup_destinations[{}]", idx);
         let path = Path::new(dest);
 
+        // An unplugged drive is expected now and then; each run checks for it again
+        if destination.mounted() && !path.exists() {
+            issues.warning(
+                field,
+                format!("Backup destination #{} is not mounted: {}", idx + 1, dest),
+            );
+            continue;
+        }
+
         // Check if destination exists
         if !path.exists() {
             // Check if parent exists (we can create the destination)
@@ -135,67 +452,603 @@ fn validate_backup_destinations(destinations: &[String]) -> Result<()> {
                     #[cfg(not(windows))]
                     let suggestion = format!("mkdir -p \"{}\"", parent.display());
 
-                    return Err(BackupError::DirectoryRead(format!(
-                        "Backup destination #{} parent directory does not exist: {}\nSuggestion: Create the parent directory with: {}",
-                        idx + 1,
-                        dest,
-                        suggestion
-                    )));
+                    issues.error(
+                        field,
+                        format!(
+                            "Backup destination #{} parent directory does not exist: {}\nSuggestion: Create the parent directory with: {}",
+                            idx + 1,
+                            dest,
+                            suggestion
+                        ),
+                    );
+                } else if let Err(e) = check_writable(parent) {
+                    // Check if parent is writable
+                    issues.error(
+                        field,
+                        format!(
+                            "Backup destination #{} parent directory is not writable: {}\nError: {}",
+                            idx + 1,
+                            dest,
+                            e
+                        ),
+                    );
+                } else {
+                    issues.warning(
+                        field,
+                        format!(
+                            "Backup destination #{} does not exist but will be created: {}",
+                            idx + 1,
+                            dest
+                        ),
+                    );
                 }
-
-                // Check if parent is writable
-                if let Err(e) = check_writable(parent) {
-                    return Err(BackupError::DirectoryRead(format!(
-                        "Backup destination #{} parent directory is not writable: {}\nError: {}",
+            } else {
+                issues.error(
+                    field,
+                    format!(
+                        "Backup destination #{} has no parent directory: {}",
                         idx + 1,
-                        dest,
-                        e
-                    )));
-                }
-
-                warn!(
-                    "Backup destination #{} does not exist but will be created: {}",
-                    idx + 1,
-                    dest
+                        dest
+                    ),
                 );
-            } else {
-                return Err(BackupError::DirectoryRead(format!(
-                    "Backup destination #{} has no parent directory: {}",
-                    idx + 1,
-                    dest
-                )));
             }
-        } else {
+        } else if !path.is_dir() {
             // Destination exists, check if it's a directory and writable
-            if !path.is_dir() {
-                return Err(BackupError::DirectoryRead(format!(
+            issues.error(
+                field,
+                format!(
                     "Backup destination #{} exists but is not a directory: {}",
                     idx + 1,
                     dest
-                )));
-            }
-
+                ),
+            );
+        } else if let Err(e) = check_writable(path) {
             // Check if writable
-            if let Err(e) = check_writable(path) {
-                return Err(BackupError::DirectoryRead(format!(
+            issues.error(
+                field,
+                format!(
                     "Backup destination #{} is not writable: {}\nError: {}",
                     idx + 1,
                     dest,
                     e
-                )));
+                ),
+            );
+        }
+    }
+}
+
+/// Validate source names and per-source overrides
+fn validate_source_overrides(config: &Config, issues: &mut Issues) {
+    let mut names = HashSet::new();
+
+    for (idx, source) in config.backup_sources.iter().enumerate() {
+        let field = format!("backup_sources[{}]", idx);
+
+        if let Some(name) = &source.name {
+            if name.trim().is_empty() {
+                issues.error(
+                    format!("{}.name", field),
+                    format!("Backup source #{} has an empty name", idx + 1),
+                );
+            } else if !names.insert(name.as_str()) {
+                issues.error(
+                    format!("{}.name", field),
+                    format!("Backup source name '{}' is used more than once", name),
+                );
+            }
+        }
+
+        if source.max_mebibytes_for_hash == Some(0) {
+            issues.error(
+                format!("{}.max_mebibytes_for_hash", field),
+                format!(
+                    "Backup source '{}' has max_mebibytes_for_hash of 0; it must be greater than 0",
+                    source.display_name()
+                ),
+            );
+        }
+
+        if let Some(subpath) = &source.destination_subpath {
+            let subpath = Path::new(subpath);
+            if subpath.is_absolute()
+                || subpath
+                    .components()
+                    .any(|c| !matches!(c, std::path::Component::Normal(_)))
+            {
+                issues.error(
+                    format!("{}.destination_subpath", field),
+                    format!(
+                        "Backup source '{}' has an invalid destination_subpath: it must be a relative path without '..'",
+                        source.display_name()
+                    ),
+                );
+            }
+        }
+
+        if let Some(destinations) = &source.destinations {
+            if destinations.is_empty() {
+                issues.error(
+                    format!("{}.destinations", field),
+                    format!(
+                        "Backup source '{}' has an empty destinations list",
+                        source.display_name()
+                    ),
+                );
+            }
+            for dest in destinations {
+                if !config.backup_destinations.iter().any(|d| d.matches(dest)) {
+                    issues.error(
+                        format!("{}.destinations", field),
+                        format!(
+                            "Backup source '{}' references unknown destination: {}",
+                            source.display_name(),
+                            dest
+                        ),
+                    );
+                }
+            }
+        }
+
+        for pattern in &source.exclude_content_types {
+            if !is_valid_content_type_pattern(pattern) {
+                issues.error(
+                    format!("{}.exclude_content_types", field),
+                    format!(
+                        "Backup source '{}' has an invalid exclude_content_types entry '{}': expected 'type/subtype' or 'type/*'",
+                        source.display_name(),
+                        pattern
+                    ),
+                );
             }
         }
+
+        validate_file_filters(source, &field, issues);
     }
+}
 
-    Ok(())
+/// Validate a source's size, age, extension and ignore file filters
+fn validate_file_filters(source: &BackupSource, field: &str, issues: &mut Issues) {
+    if let (Some(min), Some(max)) = (source.min_size, source.max_size) {
+        if min > max {
+            issues.error(
+                format!("{}.min_size", field),
+                format!(
+                    "Backup source '{}' has min_size ({}) greater than max_size ({}); no file would be backed up",
+                    source.display_name(),
+                    min,
+                    max
+                ),
+            );
+        }
+    }
+
+    if source.modified_within_days == Some(0) {
+        issues.error(
+            format!("{}.modified_within_days", field),
+            format!(
+                "Backup source '{}' has modified_within_days of 0; use at least 1",
+                source.display_name()
+            ),
+        );
+    }
+
+    for extension in &source.extensions {
+        let normalized = normalize_extension(extension);
+        if normalized.is_empty() || normalized.contains(['/', '\\', '*']) {
+            issues.error(
+                format!("{}.extensions", field),
+                format!(
+                    "Backup source '{}' has an invalid extensions entry '{}': expected e.g. 'pdf' or '.pdf'",
+                    source.display_name(),
+                    extension
+                ),
+            );
+        }
+    }
+
+    for name in &source.ignore_files {
+        if name.trim().is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+            issues.error(
+                format!("{}.ignore_files", field),
+                format!(
+                    "Backup source '{}' has an invalid ignore_files entry '{}': expected a file name such as '.gitignore'",
+                    source.display_name(),
+                    name
+                ),
+            );
+        }
+    }
+}
+
+/// Validate that each critical path is an absolute directory inside a configured source
+fn validate_critical_paths(config: &Config, issues: &mut Issues) {
+    for (idx, critical) in config.critical_paths.iter().enumerate() {
+        let field = format!("critical_paths[{}]", idx);
+        let path = Path::new(critical);
+
+        if !path.is_absolute() {
+            issues.error(
+                field,
+                format!("Critical path '{}' must be an absolute path", critical),
+            );
+        } else if !config
+            .backup_sources
+            .iter()
+            .any(|source| path.starts_with(&source.parent_directory))
+        {
+            issues.error(
+                field,
+                format!(
+                    "Critical path '{}' is not inside a configured backup source",
+                    critical
+                ),
+            );
+        } else if !path.is_dir() {
+            issues.warning(
+                field,
+                format!("Critical path '{}' is not an existing directory", critical),
+            );
+        }
+    }
+}
+
+/// Validate post-copy processor commands
+fn validate_sqlite_snapshot(snapshot: Option<&SqliteSnapshot>, issues: &mut Issues) {
+    let Some(SqliteSnapshot::Command {
+        command,
+        args,
+        timeout_seconds,
+    }) = snapshot
+    else {
+        return;
+    };
+
+    if command.trim().is_empty() {
+        issues.error(
+            "sqlite_snapshot.command",
+            "SQLite snapshot command is empty",
+        );
+    }
+    if !args.iter().any(|arg| arg.contains("{target}")) {
+        issues.error(
+            "sqlite_snapshot.args",
+            "SQLite snapshot args must include {target}, where the snapshot is written",
+        );
+    }
+    if *timeout_seconds == Some(0) {
+        issues.error(
+            "sqlite_snapshot.timeout_seconds",
+            "SQLite snapshot timeout_seconds must be greater than 0",
+        );
+    }
+}
+
+fn validate_regression_detection(settings: &RegressionDetection, issues: &mut Issues) {
+    if !settings.enabled {
+        return;
+    }
+
+    if settings.min_baseline_runs == 0 || settings.min_baseline_runs > settings.baseline_runs {
+        issues.error(
+            "regression_detection.min_baseline_runs",
+            format!(
+                "Regression detection min_baseline_runs must be between 1 and baseline_runs ({})",
+                settings.baseline_runs
+            ),
+        );
+    }
+    for (field, factor) in [
+        ("slowdown_factor", settings.slowdown_factor),
+        ("growth_factor", settings.growth_factor),
+    ] {
+        if factor.is_nan() || factor <= 1.0 {
+            issues.error(
+                format!("regression_detection.{}", field),
+                format!("Regression detection {} must be greater than 1", field),
+            );
+        }
+    }
+}
+
+fn validate_mass_change_guard(guard: &MassChangeGuard, file_action_runs: u64, issues: &mut Issues) {
+    if guard.max_changed_percent.is_none() && guard.max_deleted_files.is_none() {
+        issues.error(
+            "mass_change_guard",
+            "mass_change_guard needs max_changed_percent, max_deleted_files or both",
+        );
+    }
+    if let Some(percent) = guard.max_changed_percent {
+        if !(percent > 0.0 && percent <= 100.0) {
+            issues.error(
+                "mass_change_guard.max_changed_percent",
+                "max_changed_percent must be above 0 and at most 100",
+            );
+        }
+    }
+    // The last run's file actions are what the run is compared with
+    if file_action_runs == 0 {
+        issues.error(
+            "file_action_runs",
+            "file_action_runs must be greater than 0 for mass_change_guard to compare runs",
+        );
+    }
+}
+
+fn validate_post_processors(processors: &[PostProcessorConfig], issues: &mut Issues) {
+    let mut names = HashSet::new();
+
+    for (idx, processor) in processors.iter().enumerate() {
+        let field = format!("post_processors[{}]", idx);
+
+        if processor.name.trim().is_empty() {
+            issues.error(
+                format!("{}.name", field),
+                format!("Post-processor #{} has an empty name", idx + 1),
+            );
+        } else if !names.insert(processor.name.as_str()) {
+            issues.error(
+                format!("{}.name", field),
+                format!(
+                    "Post-processor name '{}' is used more than once",
+                    processor.name
+                ),
+            );
+        }
+
+        if processor.command.trim().is_empty() {
+            issues.error(
+                format!("{}.command", field),
+                format!("Post-processor #{} has an empty command", idx + 1),
+            );
+        }
+
+        if processor.timeout_seconds == Some(0) {
+            issues.error(
+                format!("{}.timeout_seconds", field),
+                format!(
+                    "Post-processor '{}' has timeout_seconds of 0; it must be greater than 0",
+                    processor.name
+                ),
+            );
+        }
+
+        for pattern in &processor.content_types {
+            if !is_valid_content_type_pattern(pattern) {
+                issues.error(
+                    format!("{}.content_types", field),
+                    format!(
+                        "Post-processor '{}' has an invalid content_types entry '{}': expected 'type/subtype' or 'type/*'",
+                        processor.name, pattern
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// Validate copy verification tiers: ascending limits, with only the last tier unbounded
+fn validate_verification_tiers(tiers: &[VerificationTier], issues: &mut Issues) {
+    let mut previous_max: Option<u64> = None;
+
+    for (idx, tier) in tiers.iter().enumerate() {
+        let field = format!("verification_tiers[{}]", idx);
+
+        match tier.max_size_mebibytes {
+            None if idx + 1 < tiers.len() => issues.error(
+                format!("{}.max_size_mebibytes", field),
+                format!(
+                    "Verification tier #{} has no size limit, so the tiers after it are never used",
+                    idx + 1
+                ),
+            ),
+            Some(max) if previous_max.is_some_and(|previous| max <= previous) => issues.error(
+                format!("{}.max_size_mebibytes", field),
+                format!(
+                    "Verification tier #{} limit of {} MiB must be larger than the tier before it",
+                    idx + 1,
+                    max
+                ),
+            ),
+            _ => {}
+        }
+        previous_max = tier.max_size_mebibytes.or(previous_max);
+
+        if tier.mode == VerificationMode::Sampled {
+            if tier.samples == 0 {
+                issues.error(
+                    format!("{}.samples", field),
+                    format!(
+                        "Verification tier #{} must sample at least one block",
+                        idx + 1
+                    ),
+                );
+            }
+            if tier.sample_kibibytes == 0 {
+                issues.error(
+                    format!("{}.sample_kibibytes", field),
+                    format!(
+                        "Verification tier #{} has sample_kibibytes of 0; it must be greater than 0",
+                        idx + 1
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// Validate external plugin declarations
+fn validate_plugins(plugins: &[PluginConfig], issues: &mut Issues) {
+    let mut names = HashSet::new();
+
+    for (idx, plugin) in plugins.iter().enumerate() {
+        let field = format!("plugins[{}]", idx);
+
+        if plugin.name.trim().is_empty() {
+            issues.error(
+                format!("{}.name", field),
+                format!("Plugin #{} has an empty name", idx + 1),
+            );
+        } else if !names.insert(plugin.name.as_str()) {
+            issues.error(
+                format!("{}.name", field),
+                format!("Plugin name '{}' is used more than once", plugin.name),
+            );
+        }
+
+        if plugin.command.trim().is_empty() {
+            issues.error(
+                format!("{}.command", field),
+                format!("Plugin #{} has an empty command", idx + 1),
+            );
+        }
+
+        if plugin.timeout_seconds == Some(0) {
+            issues.error(
+                format!("{}.timeout_seconds", field),
+                format!(
+                    "Plugin '{}' has timeout_seconds of 0; it must be greater than 0",
+                    plugin.name
+                ),
+            );
+        }
+    }
+}
+
+/// Shortest token a trigger may have, so it can't be guessed
+const MIN_TRIGGER_TOKEN_LEN: usize = 16;
+
+/// Validate webhook triggers: named, with long URL-safe tokens and a source that exists
+fn validate_triggers(triggers: &[TriggerConfig], sources: &[BackupSource], issues: &mut Issues) {
+    let mut names = HashSet::new();
+    let mut tokens = HashSet::new();
+
+    for (idx, trigger) in triggers.iter().enumerate() {
+        let field = format!("triggers[{}]", idx);
+
+        if trigger.name.trim().is_empty() {
+            issues.error(
+                format!("{}.name", field),
+                format!("Trigger #{} has an empty name", idx + 1),
+            );
+        } else if !names.insert(trigger.name.as_str()) {
+            issues.error(
+                format!("{}.name", field),
+                format!("Trigger name '{}' is used more than once", trigger.name),
+            );
+        }
+
+        if trigger.token.len() < MIN_TRIGGER_TOKEN_LEN {
+            issues.error(
+                format!("{}.token", field),
+                format!(
+                    "Trigger '{}' has a token shorter than {} characters",
+                    trigger.name, MIN_TRIGGER_TOKEN_LEN
+                ),
+            );
+        } else if !trigger
+            .token
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '~'))
+        {
+            issues.error(
+                format!("{}.token", field),
+                format!(
+                    "Trigger '{}' has a token with characters other than letters, digits, '-', '_', '.' and '~'",
+                    trigger.name
+                ),
+            );
+        } else if !tokens.insert(trigger.token.as_str()) {
+            issues.error(
+                format!("{}.token", field),
+                format!("Trigger '{}' reuses another trigger's token", trigger.name),
+            );
+        }
+
+        if trigger.source.is_some() {
+            let selection = RunSelection {
+                source: trigger.source.clone(),
+                ..Default::default()
+            };
+            if let Err(e) = selection.select_sources(sources) {
+                issues.error(
+                    format!("{}.source", field),
+                    format!("Trigger '{}': {}", trigger.name, e),
+                );
+            }
+        }
+    }
+}
+
+/// Each profile must make a valid configuration with the settings it leaves alone; only findings
+/// the profile brings in are reported, under `profiles.<name>`
+fn validate_profiles(config: &Config, issues: &mut Issues) {
+    let own: Vec<(String, String)> = issues
+        .0
+        .iter()
+        .map(|issue| (issue.field.clone(), issue.message.clone()))
+        .collect();
+    let sharded_by = match config.catalog_sharding {
+        CatalogSharding::None => None,
+        CatalogSharding::PerSource => Some("backup_sources"),
+        CatalogSharding::PerDestination => Some("backup_destinations"),
+    };
+
+    for (name, overrides) in &config.profiles {
+        let field = format!("profiles.{}", name);
+        if name.trim().is_empty() {
+            issues.error(field, "Profile names can't be empty");
+            continue;
+        }
+        if let Some(sharded_by) = sharded_by.filter(|f| overrides.contains_key(*f)) {
+            issues.error(
+                format!("{}.{}", field, sharded_by),
+                format!(
+                    "Profile '{}' can't change {} while the catalog is sharded by them",
+                    name, sharded_by
+                ),
+            );
+            continue;
+        }
+
+        match config.with_profile(name) {
+            Ok(profile) => {
+                for issue in check_config(&profile) {
+                    if !own.contains(&(issue.field.clone(), issue.message.clone())) {
+                        issues.push(
+                            format!("{}.{}", field, issue.field),
+                            format!("Profile '{}': {}", name, issue.message),
+                            issue.severity,
+                        );
+                    }
+                }
+            }
+            Err(e) => issues.error(field, e.to_string()),
+        }
+    }
+}
+
+/// Returns true for MIME patterns of the form `type/subtype` or `type/*`
+fn is_valid_content_type_pattern(pattern: &str) -> bool {
+    match pattern.split_once('/') {
+        Some((kind, subtype)) => {
+            !kind.is_empty()
+                && kind != "*"
+                && !subtype.is_empty()
+                && !subtype.contains('/')
+                && (subtype == "*" || !subtype.contains('*'))
+        }
+        None => false,
+    }
 }
 
 /// Validate database file path
-fn validate_database_path(db_file: &str) -> Result<()> {
+fn validate_database_path(db_file: &str, issues: &mut Issues) {
+    const FIELD: &str = "database_file";
+
     if db_file.is_empty() {
         // Empty string means in-memory database, which is valid
         info!("Using in-memory database (no database_file specified)");
-        return Ok(());
+        return;
     }
 
     let path = Path::new(db_file);
@@ -204,61 +1057,122 @@ fn validate_database_path(db_file: &str) -> Result<()> {
     if path.exists() {
         // Check if it's a file
         if !path.is_file() {
-            return Err(BackupError::DirectoryRead(format!(
-                "Database path exists but is not a file: {}",
-                db_file
-            )));
-        }
-
-        // Check if readable and writable
-        if let Err(e) = fs::OpenOptions::new().read(true).write(true).open(path) {
-            return Err(BackupError::DirectoryRead(format!(
-                "Database file is not readable/writable: {}\nError: {}",
-                db_file, e
-            )));
+            issues.error(
+                FIELD,
+                format!("Database path exists but is not a file: {}", db_file),
+            );
+        } else if let Err(e) = fs::OpenOptions::new().read(true).write(true).open(path) {
+            // Check if readable and writable
+            issues.error(
+                FIELD,
+                format!(
+                    "Database file is not readable/writable: {}\nError: {}",
+                    db_file, e
+                ),
+            );
         }
-    } else {
+    } else if let Some(parent) = path.parent() {
         // Database doesn't exist, check if parent directory exists and is writable
-        if let Some(parent) = path.parent() {
-            // Handle edge case: if parent is empty (current directory), it always exists
-            let parent_exists = if parent.as_os_str().is_empty() {
-                true // Current directory always exists
-            } else {
-                parent.exists()
-            };
+        // Handle edge case: if parent is empty (current directory), it always exists
+        let parent_exists = if parent.as_os_str().is_empty() {
+            true // Current directory always exists
+        } else {
+            parent.exists()
+        };
 
-            if !parent_exists {
-                #[cfg(windows)]
-                let suggestion = format!("mkdir \"{}\"", parent.display());
-                #[cfg(not(windows))]
-                let suggestion = format!("mkdir -p \"{}\"", parent.display());
+        if !parent_exists {
+            #[cfg(windows)]
+            let suggestion = format!("mkdir \"{}\"", parent.display());
+            #[cfg(not(windows))]
+            let suggestion = format!("mkdir -p \"{}\"", parent.display());
 
-                return Err(BackupError::DirectoryRead(format!(
+            issues.error(
+                FIELD,
+                format!(
                     "Database parent directory does not exist: {}\nSuggestion: Create the directory with: {}",
                     db_file,
                     suggestion
-                )));
-            }
-
-            if let Err(e) = check_writable(parent) {
-                return Err(BackupError::DirectoryRead(format!(
+                ),
+            );
+        } else if let Err(e) = check_writable(parent) {
+            issues.error(
+                FIELD,
+                format!(
                     "Database parent directory is not writable: {}\nError: {}",
                     db_file, e
-                )));
-            }
-        } else {
-            return Err(BackupError::DirectoryRead(format!(
-                "Database path has no parent directory: {}",
-                db_file
-            )));
+                ),
+            );
         }
+    } else {
+        issues.error(
+            FIELD,
+            format!("Database path has no parent directory: {}", db_file),
+        );
     }
+}
 
-    Ok(())
+/// Shards need a database file to sit next to, and roots that don't contain one another so that
+/// every path belongs to one shard
+fn validate_catalog_sharding(config: &Config, issues: &mut Issues) {
+    const FIELD: &str = "catalog_sharding";
+    if config.catalog_sharding == CatalogSharding::None {
+        return;
+    }
+
+    if config.database_file.is_empty() {
+        issues.error(
+            FIELD,
+            "Catalog sharding needs a database_file; an in-memory database can't be sharded",
+        );
+    }
+    let roots = config.catalog_shard_roots();
+    if roots.len() > MAX_CATALOG_SHARDS {
+        issues.error(
+            FIELD,
+            format!(
+                "Catalog sharding supports up to {} shards, not {}",
+                MAX_CATALOG_SHARDS,
+                roots.len()
+            ),
+        );
+    }
+    for (idx, root) in roots.iter().enumerate() {
+        if let Some(outer) = roots[..idx]
+            .iter()
+            .find(|other| Path::new(root).starts_with(other) || Path::new(other).starts_with(root))
+        {
+            issues.error(
+                FIELD,
+                format!(
+                    "Catalog shards can't be nested, but {} and {} overlap",
+                    outer, root
+                ),
+            );
+        }
+    }
+}
+
+/// A PostgreSQL catalog must be reachable by a URL this client can use, and holds the whole
+/// catalog in one database
+fn validate_catalog_database_url(config: &Config, issues: &mut Issues) {
+    const FIELD: &str = "catalog_database_url";
+    let Some(url) = &config.catalog_database_url else {
+        return;
+    };
+
+    if let Err(reason) = parse_url(url) {
+        issues.error(FIELD, format!("Catalog database URL {}", reason));
+    }
+    if config.catalog_sharding != CatalogSharding::None {
+        issues.error(
+            FIELD,
+            "A PostgreSQL catalog can't be sharded; set catalog_sharding to none",
+        );
+    }
 }
 
 /// Validate schedule configuration
-fn validate_schedule(config: &Config) -> Result<()> {
+fn validate_schedule(config: &Config, issues: &mut Issues) {
     if let Some(schedule_str) = &config.schedule {
         // Try to parse the cron expression
         match cron::Schedule::from_str(schedule_str) {
@@ -266,38 +1180,80 @@ fn validate_schedule(config: &Config) -> Result<()> {
                 info!("Schedule validated: {}", schedule_str);
             }
             Err(e) => {
-                return Err(BackupError::DirectoryRead(format!(
-                    "Invalid cron expression in schedule: {}\nError: {}\nExample: '0 2 * * *' for daily at 2am",
-                    schedule_str, e
-                )));
+                issues.error(
+                    "schedule",
+                    format!(
+                        "Invalid cron expression in schedule: {}\nError: {}\nExample: '0 2 * * *' for daily at 2am",
+                        schedule_str, e
+                    ),
+                );
             }
         }
     }
-    Ok(())
+}
+
+/// Validate the scrub schedule and that each scrub has a limit
+fn validate_scrub(scrub: &ScrubConfig, issues: &mut Issues) {
+    if let Err(e) = cron::Schedule::from_str(&scrub.schedule) {
+        issues.error(
+            "scrub.schedule",
+            format!(
+                "Invalid cron expression in scrub.schedule: {}\nError: {}\nExample: '0 0 3 1 * *' for the 1st of each month at 3am",
+                scrub.schedule, e
+            ),
+        );
+    }
+    if scrub.percent.is_none() && scrub.max_gibibytes.is_none() {
+        issues.error(
+            "scrub",
+            "scrub needs percent or max_gibibytes to limit how much one scrub reads",
+        );
+    }
+    if let Some(percent) = scrub.percent {
+        if !(percent > 0.0 && percent <= 100.0) {
+            issues.error(
+                "scrub.percent",
+                format!(
+                    "scrub.percent must be above 0 and at most 100, got {}",
+                    percent
+                ),
+            );
+        }
+    }
+    if let Some(max_gibibytes) = scrub.max_gibibytes {
+        if !(max_gibibytes > 0.0 && max_gibibytes.is_finite()) {
+            issues.error(
+                "scrub.max_gibibytes",
+                format!("scrub.max_gibibytes must be above 0, got {}", max_gibibytes),
+            );
+        }
+    }
 }
 
 /// Check for conflicting configuration flags
-fn check_conflicting_flags(config: &Config) -> Result<()> {
+fn check_conflicting_flags(config: &Config, issues: &mut Issues) {
     // If force_overwrite_backup is true, other backup flags are ignored
     if config.force_overwrite_backup {
         if config.overwrite_backup_if_existing_is_newer {
-            warn!(
-                "force_overwrite_backup is enabled, so overwrite_backup_if_existing_is_newer has no effect"
+            issues.warning(
+                "overwrite_backup_if_existing_is_newer",
+                "force_overwrite_backup is enabled, so overwrite_backup_if_existing_is_newer has no effect",
             );
         }
-        warn!("force_overwrite_backup is enabled - all backup files will be overwritten regardless of their state");
+        issues.warning(
+            "force_overwrite_backup",
+            "force_overwrite_backup is enabled - all backup files will be overwritten regardless of their state",
+        );
     }
 
     // If skip_source_hash_check_if_newer is true, newer source files won't be hashed
     if config.skip_source_hash_check_if_newer {
         info!("skip_source_hash_check_if_newer is enabled - newer source files will skip hash verification");
     }
-
-    Ok(())
 }
 
 /// Check if a directory is writable by attempting to create a temporary file
-fn check_writable(path: &Path) -> std::io::Result<()> {
+pub(crate) fn check_writable(path: &Path) -> std::io::Result<()> {
     let test_file = path.join(".rustyhashbackup_write_test");
 
     // Try to create a temporary file
@@ -312,6 +1268,11 @@ fn check_writable(path: &Path) -> std::io::Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::config::{
+        CopyOrder, DestinationChecks, DestinationCommand, Filesystem, LockedFiles, VolumeSnapshot,
+    };
+    use serde_json::json;
+    use std::collections::BTreeMap;
     use tempfile::TempDir;
 
     #[test]
@@ -325,12 +1286,8 @@ mod tests {
         let mut config = create_test_config();
         config.max_mebibytes_for_hash = 0;
 
-        let result = validate_numeric_values(&config);
-        assert!(result.is_err());
-        assert!(result
-            .unwrap_err()
-            .to_string()
-            .contains("max_mebibytes_for_hash"));
+        let fields = error_fields(|issues| validate_numeric_values(&config, issues));
+        assert_eq!(fields, vec!["max_mebibytes_for_hash"]);
     }
 
     #[test]
@@ -338,9 +1295,92 @@ mod tests {
         let mut config = create_test_config();
         config.max_threads = 0;
 
-        let result = validate_numeric_values(&config);
-        assert!(result.is_err());
-        assert!(result.unwrap_err().to_string().contains("max_threads"));
+        let fields = error_fields(|issues| validate_numeric_values(&config, issues));
+        assert_eq!(fields, vec!["max_threads"]);
+
+        config.max_threads = 4;
+        config.copy_buffer_kib = 0;
+        let fields = error_fields(|issues| validate_numeric_values(&config, issues));
+        assert_eq!(fields, vec!["copy_buffer_kib"]);
+    }
+
+    #[test]
+    fn test_validate_low_priority_group_ranges() {
+        let mut config = create_test_config();
+        config.low_priority_group = Some(LowPriorityGroup {
+            cgroup_path: "/sys/fs/cgroup/backup".to_string(),
+            cpu_weight: Some(20),
+            io_weight: Some(0),
+            cpu_rate_percent: None,
+        });
+
+        let fields = error_fields(|issues| validate_numeric_values(&config, issues));
+        assert_eq!(fields, vec!["low_priority_group.io_weight"]);
+
+        if let Some(group) = config.low_priority_group.as_mut() {
+            group.io_weight = Some(50);
+            group.cpu_rate_percent = Some(150);
+        }
+        let fields = error_fields(|issues| validate_numeric_values(&config, issues));
+        assert_eq!(fields, vec!["low_priority_group.cpu_rate_percent"]);
+    }
+
+    #[test]
+    fn test_validate_critical_paths() {
+        let root = TempDir::new().unwrap();
+        let mut config = create_test_config();
+        config.backup_sources = vec![BackupSource {
+            parent_directory: root.path().to_string_lossy().to_string(),
+            ..Default::default()
+        }];
+        config.critical_paths = vec![
+            root.path().to_string_lossy().to_string(),
+            "relative/dir".to_string(),
+            std::env::temp_dir()
+                .join("outside-any-source")
+                .to_string_lossy()
+                .to_string(),
+        ];
+
+        let fields = error_fields(|issues| validate_critical_paths(&config, issues));
+        assert_eq!(fields, vec!["critical_paths[1]", "critical_paths[2]"]);
+    }
+
+    #[test]
+    fn test_validate_numeric_values_zero_marker_age() {
+        let mut config = create_test_config();
+        config.backup_marker_max_age_hours = Some(0);
+        config.always_copy_below_bytes = Some(0);
+
+        let fields = error_fields(|issues| validate_numeric_values(&config, issues));
+        assert_eq!(
+            fields,
+            vec!["always_copy_below_bytes", "backup_marker_max_age_hours"]
+        );
+    }
+
+    #[test]
+    fn test_validate_numeric_values_verify_sample_out_of_range() {
+        let mut config = create_test_config();
+        config.verify_sample_percent = Some(0.5);
+        assert!(error_fields(|issues| validate_numeric_values(&config, issues)).is_empty());
+
+        config.verify_sample_percent = Some(120.0);
+        let fields = error_fields(|issues| validate_numeric_values(&config, issues));
+        assert_eq!(fields, vec!["verify_sample_percent"]);
+    }
+
+    #[test]
+    fn test_validate_numeric_values_zero_run_budget() {
+        let mut config = create_test_config();
+        config.max_run_duration_minutes = Some(0);
+        config.max_bytes_per_run = Some(0);
+
+        let fields = error_fields(|issues| validate_numeric_values(&config, issues));
+        assert_eq!(
+            fields,
+            vec!["max_run_duration_minutes", "max_bytes_per_run"]
+        );
     }
 
     #[test]
@@ -355,14 +1395,16 @@ mod tests {
                 parent_directory: temp_source.path().to_str().unwrap().to_string(),
                 max_depth: Some(10),
                 skip_dirs: vec![],
+                ..Default::default()
             }],
-            backup_destinations: vec![temp_dest.path().to_str().unwrap().to_string()],
+            backup_destinations: vec![temp_dest.path().to_str().unwrap().to_string().into()],
             skip_source_hash_check_if_newer: true,
             force_overwrite_backup: false,
             overwrite_backup_if_existing_is_newer: false,
             max_threads: 4,
             schedule: None,
             run_on_startup: true,
+            ..create_test_config()
         };
 
         let result = validate_config(&config);
@@ -380,14 +1422,16 @@ mod tests {
                 parent_directory: "/this/does/not/exist".to_string(),
                 max_depth: Some(10),
                 skip_dirs: vec![],
+                ..Default::default()
             }],
-            backup_destinations: vec![temp_dest.path().to_str().unwrap().to_string()],
+            backup_destinations: vec![temp_dest.path().to_str().unwrap().to_string().into()],
             skip_source_hash_check_if_newer: true,
             force_overwrite_backup: false,
             overwrite_backup_if_existing_is_newer: false,
             max_threads: 4,
             schedule: None,
             run_on_startup: true,
+            ..create_test_config()
         };
 
         let result = validate_config(&config);
@@ -403,13 +1447,14 @@ mod tests {
             database_file: ":memory:".to_string(),
             max_mebibytes_for_hash: 1,
             backup_sources: vec![], // Empty sources
-            backup_destinations: vec![temp_dest.path().to_str().unwrap().to_string()],
+            backup_destinations: vec![temp_dest.path().to_str().unwrap().to_string().into()],
             skip_source_hash_check_if_newer: true,
             force_overwrite_backup: false,
             overwrite_backup_if_existing_is_newer: false,
             max_threads: 4,
             schedule: None,
             run_on_startup: true,
+            ..create_test_config()
         };
 
         let result = validate_config(&config);
@@ -431,6 +1476,7 @@ mod tests {
                 parent_directory: temp_source.path().to_str().unwrap().to_string(),
                 max_depth: Some(10),
                 skip_dirs: vec![],
+                ..Default::default()
             }],
             backup_destinations: vec![], // Empty destinations
             skip_source_hash_check_if_newer: true,
@@ -439,6 +1485,7 @@ mod tests {
             max_threads: 4,
             schedule: None,
             run_on_startup: true,
+            ..create_test_config()
         };
 
         let result = validate_config(&config);
@@ -461,14 +1508,16 @@ mod tests {
                 parent_directory: temp_source.path().to_str().unwrap().to_string(),
                 max_depth: Some(10),
                 skip_dirs: vec![],
+                ..Default::default()
             }],
-            backup_destinations: vec![temp_dest.path().to_str().unwrap().to_string()],
+            backup_destinations: vec![temp_dest.path().to_str().unwrap().to_string().into()],
             skip_source_hash_check_if_newer: true,
             force_overwrite_backup: false,
             overwrite_backup_if_existing_is_newer: false,
             max_threads: 4,
             schedule: None,
             run_on_startup: true,
+            ..create_test_config()
         };
 
         let result = validate_config(&config);
@@ -487,14 +1536,16 @@ mod tests {
                 parent_directory: temp_source.path().to_str().unwrap().to_string(),
                 max_depth: Some(10),
                 skip_dirs: vec![],
+                ..Default::default()
             }],
-            backup_destinations: vec![temp_dest.path().to_str().unwrap().to_string()],
+            backup_destinations: vec![temp_dest.path().to_str().unwrap().to_string().into()],
             skip_source_hash_check_if_newer: true,
             force_overwrite_backup: true,
             overwrite_backup_if_existing_is_newer: true, // Conflicting with force_overwrite
             max_threads: 4,
             schedule: None,
             run_on_startup: true,
+            ..create_test_config()
         };
 
         // Note: This currently just logs a warning, doesn't error
@@ -515,14 +1566,16 @@ mod tests {
                 parent_directory: temp_source.path().to_str().unwrap().to_string(),
                 max_depth: Some(0), // Invalid
                 skip_dirs: vec![],
+                ..Default::default()
             }],
-            backup_destinations: vec![temp_dest.path().to_str().unwrap().to_string()],
+            backup_destinations: vec![temp_dest.path().to_str().unwrap().to_string().into()],
             skip_source_hash_check_if_newer: true,
             force_overwrite_backup: false,
             overwrite_backup_if_existing_is_newer: false,
             max_threads: 4,
             schedule: None,
             run_on_startup: true,
+            ..create_test_config()
         };
 
         let result = validate_config(&config);
@@ -542,14 +1595,16 @@ mod tests {
                 parent_directory: temp_source.path().to_str().unwrap().to_string(),
                 max_depth: Some(10),
                 skip_dirs: vec![],
+                ..Default::default()
             }],
-            backup_destinations: vec![temp_dest.path().to_str().unwrap().to_string()],
+            backup_destinations: vec![temp_dest.path().to_str().unwrap().to_string().into()],
             skip_source_hash_check_if_newer: true,
             force_overwrite_backup: false,
             overwrite_backup_if_existing_is_newer: false,
             max_threads: 4,
             schedule: Some("0 0 2 * * *".to_string()), // Daily at 2am
             run_on_startup: true,
+            ..create_test_config()
         };
 
         let result = validate_config(&config);
@@ -568,14 +1623,16 @@ mod tests {
                 parent_directory: temp_source.path().to_str().unwrap().to_string(),
                 max_depth: Some(10),
                 skip_dirs: vec![],
+                ..Default::default()
             }],
-            backup_destinations: vec![temp_dest.path().to_str().unwrap().to_string()],
+            backup_destinations: vec![temp_dest.path().to_str().unwrap().to_string().into()],
             skip_source_hash_check_if_newer: true,
             force_overwrite_backup: false,
             overwrite_backup_if_existing_is_newer: false,
             max_threads: 4,
             schedule: Some("invalid cron".to_string()), // Invalid
             run_on_startup: true,
+            ..create_test_config()
         };
 
         let result = validate_config(&config);
@@ -607,14 +1664,16 @@ mod tests {
                     parent_directory: temp_source.path().to_str().unwrap().to_string(),
                     max_depth: Some(10),
                     skip_dirs: vec![],
+                    ..Default::default()
                 }],
-                backup_destinations: vec![temp_dest.path().to_str().unwrap().to_string()],
+                backup_destinations: vec![temp_dest.path().to_str().unwrap().to_string().into()],
                 skip_source_hash_check_if_newer: true,
                 force_overwrite_backup: false,
                 overwrite_backup_if_existing_is_newer: false,
                 max_threads: 4,
                 schedule: Some(expr.to_string()),
                 run_on_startup: true,
+                ..create_test_config()
             };
 
             let result = validate_config(&config);
@@ -627,10 +1686,629 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_rejects_duplicate_source_names() {
+        let mut config = create_test_config();
+        config.backup_sources = vec![
+            BackupSource {
+                parent_directory: "/a".to_string(),
+                name: Some("docs".to_string()),
+                ..Default::default()
+            },
+            BackupSource {
+                parent_directory: "/b".to_string(),
+                name: Some("docs".to_string()),
+                ..Default::default()
+            },
+        ];
+
+        let fields = error_fields(|issues| validate_source_overrides(&config, issues));
+        assert_eq!(fields, vec!["backup_sources[1].name"]);
+    }
+
+    #[test]
+    fn test_rejects_unknown_source_destination() {
+        let mut config = create_test_config();
+        config.backup_destinations = vec![BackupDestination::Detailed {
+            name: Some("nas".to_string()),
+            path: "/mnt/nas".to_string(),
+            max_parallel_files: None,
+            filesystem: Filesystem::Posix,
+            mounted: false,
+            mount_command: None,
+            unmount_command: None,
+            udisks_device: None,
+            max_total_bytes: None,
+        }];
+        config.backup_sources = vec![BackupSource {
+            parent_directory: "/a".to_string(),
+            destinations: Some(vec!["usb".to_string()]),
+            ..Default::default()
+        }];
+
+        let fields = error_fields(|issues| validate_source_overrides(&config, issues));
+        assert_eq!(fields, vec!["backup_sources[0].destinations"]);
+    }
+
+    #[test]
+    fn test_rejects_escaping_destination_subpath() {
+        let mut config = create_test_config();
+        config.backup_sources = vec![BackupSource {
+            parent_directory: "/a".to_string(),
+            destination_subpath: Some("../elsewhere".to_string()),
+            ..Default::default()
+        }];
+
+        let fields = error_fields(|issues| validate_source_overrides(&config, issues));
+        assert_eq!(fields, vec!["backup_sources[0].destination_subpath"]);
+    }
+
+    #[test]
+    fn test_rejects_duplicate_destination_names() {
+        let temp_dest = TempDir::new().unwrap();
+        let path = temp_dest.path().to_str().unwrap().to_string();
+        let destinations = vec![
+            BackupDestination::Detailed {
+                name: Some("nas".to_string()),
+                path: path.clone(),
+                max_parallel_files: None,
+                filesystem: Filesystem::Posix,
+                mounted: false,
+                mount_command: None,
+                unmount_command: None,
+                udisks_device: None,
+                max_total_bytes: None,
+            },
+            BackupDestination::Detailed {
+                name: Some("nas".to_string()),
+                path,
+                max_parallel_files: None,
+                filesystem: Filesystem::Posix,
+                mounted: false,
+                mount_command: None,
+                unmount_command: None,
+                udisks_device: None,
+                max_total_bytes: None,
+            },
+        ];
+
+        let fields = error_fields(|issues| validate_backup_destinations(&destinations, issues));
+        assert_eq!(fields, vec!["backup_destinations[1].name"]);
+    }
+
+    #[test]
+    fn test_unmounted_destination_is_only_a_warning() {
+        let destinations = vec![BackupDestination::Detailed {
+            name: None,
+            path: "/nonexistent/usb/backups".to_string(),
+            max_parallel_files: None,
+            filesystem: Filesystem::Posix,
+            mounted: true,
+            mount_command: None,
+            unmount_command: None,
+            udisks_device: None,
+            max_total_bytes: None,
+        }];
+
+        let mut issues = Issues::default();
+        validate_backup_destinations(&destinations, &mut issues);
+        let severities: Vec<_> = issues.0.iter().map(|issue| issue.severity).collect();
+        assert_eq!(severities, vec![Severity::Warning]);
+    }
+
+    #[test]
+    fn test_rejects_udisks_next_to_mount_commands() {
+        let destinations = vec![BackupDestination::Detailed {
+            name: None,
+            path: "/media/backup/BACKUP1".to_string(),
+            max_parallel_files: None,
+            filesystem: Filesystem::Posix,
+            mounted: false,
+            mount_command: Some(Box::new(DestinationCommand {
+                command: "mount".to_string(),
+                args: vec!["{path}".to_string()],
+                timeout_seconds: None,
+            })),
+            unmount_command: None,
+            udisks_device: Some("/dev/disk/by-label/BACKUP1".to_string()),
+            max_total_bytes: None,
+        }];
+
+        let fields = error_fields(|issues| validate_backup_destinations(&destinations, issues));
+        assert_eq!(fields, vec!["backup_destinations[0].udisks_device"]);
+    }
+
+    #[test]
+    fn test_rejects_zero_parallel_files_for_a_destination() {
+        let temp_dest = TempDir::new().unwrap();
+        let destinations = vec![BackupDestination::Detailed {
+            name: None,
+            path: temp_dest.path().to_str().unwrap().to_string(),
+            max_parallel_files: Some(0),
+            filesystem: Filesystem::Posix,
+            mounted: false,
+            mount_command: None,
+            unmount_command: None,
+            udisks_device: None,
+            max_total_bytes: None,
+        }];
+
+        let fields = error_fields(|issues| validate_backup_destinations(&destinations, issues));
+        assert_eq!(fields, vec!["backup_destinations[0].max_parallel_files"]);
+    }
+
+    #[test]
+    fn test_validate_exclude_content_types() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = create_test_config();
+        config.backup_sources = vec![BackupSource {
+            parent_directory: temp_dir.path().to_str().unwrap().to_string(),
+            exclude_content_types: vec!["video/*".to_string(), "image/x-canon-cr2".to_string()],
+            ..Default::default()
+        }];
+        assert!(error_fields(|issues| validate_source_overrides(&config, issues)).is_empty());
+
+        config.backup_sources[0].exclude_content_types = vec!["video".to_string()];
+        let fields = error_fields(|issues| validate_source_overrides(&config, issues));
+        assert_eq!(fields, vec!["backup_sources[0].exclude_content_types"]);
+    }
+
+    #[test]
+    fn test_validate_file_filters() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = create_test_config();
+        config.backup_sources = vec![BackupSource {
+            parent_directory: temp_dir.path().to_str().unwrap().to_string(),
+            min_size: Some(1024),
+            max_size: Some(4096),
+            modified_within_days: Some(30),
+            extensions: vec!["pdf".to_string(), ".DOCX".to_string()],
+            ignore_files: vec![".gitignore".to_string(), ".backupignore".to_string()],
+            ..Default::default()
+        }];
+        assert!(error_fields(|issues| validate_source_overrides(&config, issues)).is_empty());
+
+        config.backup_sources[0].min_size = Some(8192);
+        config.backup_sources[0].modified_within_days = Some(0);
+        config.backup_sources[0].extensions = vec![".".to_string()];
+        config.backup_sources[0].ignore_files = vec!["config/.ignore".to_string()];
+        let fields = error_fields(|issues| validate_source_overrides(&config, issues));
+        assert_eq!(
+            fields,
+            vec![
+                "backup_sources[0].min_size",
+                "backup_sources[0].modified_within_days",
+                "backup_sources[0].extensions",
+                "backup_sources[0].ignore_files"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_post_processors() {
+        let processor = PostProcessorConfig {
+            name: "clamav".to_string(),
+            command: "clamdscan".to_string(),
+            args: vec!["--no-summary".to_string(), "{backup}".to_string()],
+            content_types: vec![],
+            timeout_seconds: Some(60),
+        };
+        assert!(error_fields(|issues| validate_post_processors(
+            std::slice::from_ref(&processor),
+            issues
+        ))
+        .is_empty());
+
+        let broken = PostProcessorConfig {
+            command: " ".to_string(),
+            content_types: vec!["image".to_string()],
+            ..processor.clone()
+        };
+        let fields = error_fields(|issues| validate_post_processors(&[processor, broken], issues));
+        assert_eq!(
+            fields,
+            vec![
+                "post_processors[1].name",
+                "post_processors[1].command",
+                "post_processors[1].content_types",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_triggers() {
+        let sources = vec![BackupSource {
+            parent_directory: "/photos".to_string(),
+            name: Some("photos".to_string()),
+            ..Default::default()
+        }];
+        let trigger = TriggerConfig {
+            name: "camera-import".to_string(),
+            token: "k3J9-xq2_Lm8.Zp0~".to_string(),
+            source: Some("photos".to_string()),
+            path: None,
+            dry_run: false,
+            min_interval_secs: 60,
+        };
+        assert!(error_fields(|issues| validate_triggers(
+            std::slice::from_ref(&trigger),
+            &sources,
+            issues
+        ))
+        .is_empty());
+
+        let reused = TriggerConfig {
+            name: "ci".to_string(),
+            source: None,
+            ..trigger.clone()
+        };
+        let short = TriggerConfig {
+            name: "ci".to_string(),
+            token: "secret".to_string(),
+            source: Some("music".to_string()),
+            ..trigger.clone()
+        };
+        let unsafe_token = TriggerConfig {
+            name: "ingest".to_string(),
+            token: "not/a/path/segment!".to_string(),
+            ..trigger.clone()
+        };
+        let fields = error_fields(|issues| {
+            validate_triggers(&[trigger, reused, short, unsafe_token], &sources, issues)
+        });
+        assert_eq!(
+            fields,
+            vec![
+                "triggers[1].token",
+                "triggers[2].name",
+                "triggers[2].token",
+                "triggers[2].source",
+                "triggers[3].token",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_profiles() {
+        let mut config = create_test_config();
+        let profile = |settings: serde_json::Value| settings.as_object().unwrap().clone();
+        // Only what the profiles add; the test config's own findings are left out
+        let profile_errors = |config: &Config| -> Vec<String> {
+            check_config(config)
+                .into_iter()
+                .filter(|issue| issue.is_error() && issue.field.starts_with("profiles."))
+                .map(|issue| issue.field)
+                .collect()
+        };
+        config.profiles = BTreeMap::from([
+            (
+                "quick-docs".to_string(),
+                profile(json!({"max_threads": 2, "quick_skip_unchanged": true})),
+            ),
+            ("broken".to_string(), profile(json!({"max_threads": 0}))),
+            (
+                "elsewhere".to_string(),
+                profile(json!({"database_file": "/tmp/other.db"})),
+            ),
+            (
+                "mistyped".to_string(),
+                profile(json!({"max_threads": "two"})),
+            ),
+        ]);
+
+        assert_eq!(
+            profile_errors(&config),
+            vec![
+                "profiles.broken.max_threads",
+                "profiles.elsewhere",
+                "profiles.mistyped",
+            ]
+        );
+
+        config.catalog_sharding = CatalogSharding::PerSource;
+        config.profiles =
+            BTreeMap::from([("photos".to_string(), profile(json!({"backup_sources": []})))]);
+        assert_eq!(
+            profile_errors(&config),
+            vec!["profiles.photos.backup_sources"]
+        );
+    }
+
+    #[test]
+    fn test_validate_verification_tiers() {
+        let tier = |max_size_mebibytes, mode| VerificationTier {
+            max_size_mebibytes,
+            mode,
+            samples: 16,
+            sample_kibibytes: 64,
+        };
+        let tiers = vec![
+            tier(Some(100), VerificationMode::Full),
+            tier(None, VerificationMode::Sampled),
+        ];
+        assert!(error_fields(|issues| validate_verification_tiers(&tiers, issues)).is_empty());
+
+        let tiers = vec![
+            tier(None, VerificationMode::Full),
+            tier(Some(100), VerificationMode::Hash),
+            tier(Some(50), VerificationMode::Sampled),
+            VerificationTier {
+                samples: 0,
+                ..tier(Some(500), VerificationMode::Sampled)
+            },
+        ];
+        let fields = error_fields(|issues| validate_verification_tiers(&tiers, issues));
+        assert_eq!(
+            fields,
+            vec![
+                "verification_tiers[0].max_size_mebibytes",
+                "verification_tiers[2].max_size_mebibytes",
+                "verification_tiers[3].samples",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_config_collects_every_issue() {
+        let temp_source = TempDir::new().unwrap();
+        let temp_dest = TempDir::new().unwrap();
+        let mut config = create_test_config();
+        config.max_threads = 0;
+        config.force_overwrite_backup = true;
+        config.schedule = Some("not a schedule".to_string());
+        config.backup_sources = vec![
+            BackupSource {
+                parent_directory: temp_source.path().to_str().unwrap().to_string(),
+                ..Default::default()
+            },
+            BackupSource {
+                parent_directory: "/nonexistent/source/path".to_string(),
+                max_depth: Some(0),
+                ..Default::default()
+            },
+        ];
+        config.backup_destinations = vec![BackupDestination::Path(
+            temp_dest.path().to_str().unwrap().to_string(),
+        )];
+
+        let issues = check_config(&config);
+        let errors: Vec<&str> = issues
+            .iter()
+            .filter(|issue| issue.is_error())
+            .map(|issue| issue.field.as_str())
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "max_threads",
+                "backup_sources[1].parent_directory",
+                "backup_sources[1].max_depth",
+                "schedule",
+            ]
+        );
+        assert!(issues
+            .iter()
+            .any(|issue| issue.field == "force_overwrite_backup"
+                && issue.severity == Severity::Warning));
+
+        let err = validate_config(&config).unwrap_err();
+        assert!(matches!(&err, BackupError::ConfigValidation(errors) if errors.len() == 4));
+    }
+
+    #[test]
+    fn test_warns_about_unsupported_volume_snapshots() {
+        let temp_source = TempDir::new().unwrap();
+        let source = |snapshot| BackupSource {
+            parent_directory: temp_source.path().to_str().unwrap().to_string(),
+            snapshot: Some(snapshot),
+            ..Default::default()
+        };
+        let sources = vec![
+            source(VolumeSnapshot::Vss),
+            source(VolumeSnapshot::Lvm),
+            source(VolumeSnapshot::Btrfs),
+        ];
+
+        let mut issues = Issues::default();
+        validate_backup_sources(&sources, &mut issues);
+        let warnings: Vec<&str> = issues
+            .0
+            .iter()
+            .filter(|issue| issue.severity == Severity::Warning)
+            .map(|issue| issue.field.as_str())
+            .collect();
+        let expected: Vec<&str> = if cfg!(windows) {
+            vec!["backup_sources[1].snapshot", "backup_sources[2].snapshot"]
+        } else if cfg!(target_os = "linux") {
+            vec!["backup_sources[0].snapshot"]
+        } else {
+            vec![
+                "backup_sources[0].snapshot",
+                "backup_sources[1].snapshot",
+                "backup_sources[2].snapshot",
+            ]
+        };
+        assert_eq!(warnings, expected);
+        assert!(!issues.0.iter().any(ValidationIssue::is_error));
+    }
+
+    #[test]
+    fn test_validate_sqlite_snapshot() {
+        let command = |command: &str, args: &[&str], timeout_seconds| SqliteSnapshot::Command {
+            command: command.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            timeout_seconds,
+        };
+
+        assert!(error_fields(|issues| validate_sqlite_snapshot(
+            Some(&SqliteSnapshot::BackupApi),
+            issues
+        ))
+        .is_empty());
+        assert!(error_fields(|issues| validate_sqlite_snapshot(
+            Some(&command("sqlite3", &["{source}", ".backup {target}"], None)),
+            issues
+        ))
+        .is_empty());
+        let fields = error_fields(|issues| {
+            validate_sqlite_snapshot(Some(&command(" ", &["{source}"], Some(0))), issues)
+        });
+        assert_eq!(
+            fields,
+            vec![
+                "sqlite_snapshot.command",
+                "sqlite_snapshot.args",
+                "sqlite_snapshot.timeout_seconds"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_scrub() {
+        let monthly = ScrubConfig {
+            schedule: "0 0 3 1 * *".to_string(),
+            percent: Some(5.0),
+            max_gibibytes: None,
+        };
+        assert!(error_fields(|issues| validate_scrub(&monthly, issues)).is_empty());
+
+        let unlimited = ScrubConfig {
+            schedule: "monthly".to_string(),
+            percent: None,
+            ..monthly.clone()
+        };
+        assert_eq!(
+            error_fields(|issues| validate_scrub(&unlimited, issues)),
+            vec!["scrub.schedule", "scrub"]
+        );
+
+        let out_of_range = ScrubConfig {
+            percent: Some(150.0),
+            max_gibibytes: Some(0.0),
+            ..monthly
+        };
+        assert_eq!(
+            error_fields(|issues| validate_scrub(&out_of_range, issues)),
+            vec!["scrub.percent", "scrub.max_gibibytes"]
+        );
+    }
+
+    #[test]
+    fn test_validate_regression_detection() {
+        assert!(error_fields(|issues| validate_regression_detection(
+            &RegressionDetection::default(),
+            issues
+        ))
+        .is_empty());
+
+        let settings = RegressionDetection {
+            baseline_runs: 2,
+            min_baseline_runs: 3,
+            slowdown_factor: 1.0,
+            growth_factor: f64::NAN,
+            ..RegressionDetection::default()
+        };
+        assert_eq!(
+            error_fields(|issues| validate_regression_detection(&settings, issues)),
+            vec![
+                "regression_detection.min_baseline_runs",
+                "regression_detection.slowdown_factor",
+                "regression_detection.growth_factor",
+            ]
+        );
+
+        let disabled = RegressionDetection {
+            enabled: false,
+            ..settings
+        };
+        assert!(error_fields(|issues| validate_regression_detection(&disabled, issues)).is_empty());
+    }
+
+    #[test]
+    fn test_validate_mass_change_guard() {
+        let guard = MassChangeGuard {
+            max_changed_percent: Some(20.0),
+            max_deleted_files: None,
+        };
+        assert!(error_fields(|issues| validate_mass_change_guard(&guard, 10, issues)).is_empty());
+
+        let guard = MassChangeGuard {
+            max_changed_percent: Some(120.0),
+            max_deleted_files: Some(0),
+        };
+        assert_eq!(
+            error_fields(|issues| validate_mass_change_guard(&guard, 0, issues)),
+            vec!["mass_change_guard.max_changed_percent", "file_action_runs"]
+        );
+
+        let empty = MassChangeGuard {
+            max_changed_percent: None,
+            max_deleted_files: None,
+        };
+        assert_eq!(
+            error_fields(|issues| validate_mass_change_guard(&empty, 10, issues)),
+            vec!["mass_change_guard"]
+        );
+    }
+
+    #[test]
+    fn test_validate_catalog_sharding() {
+        let mut config = create_test_config();
+        config.database_file = "/var/lib/backup.db".to_string();
+        config.backup_sources = vec![
+            BackupSource {
+                parent_directory: "/data/photos".to_string(),
+                ..Default::default()
+            },
+            BackupSource {
+                parent_directory: "/data/documents".to_string(),
+                ..Default::default()
+            },
+        ];
+        assert!(error_fields(|issues| validate_catalog_sharding(&config, issues)).is_empty());
+
+        config.catalog_sharding = CatalogSharding::PerSource;
+        assert!(error_fields(|issues| validate_catalog_sharding(&config, issues)).is_empty());
+
+        config.backup_sources[1].parent_directory = "/data/photos/2024".to_string();
+        config.database_file = String::new();
+        assert_eq!(
+            error_fields(|issues| validate_catalog_sharding(&config, issues)),
+            vec!["catalog_sharding", "catalog_sharding"]
+        );
+    }
+
+    #[test]
+    fn test_validate_catalog_database_url() {
+        let mut config = create_test_config();
+        config.catalog_database_url = Some("postgres://backup@nas.local/catalog".to_string());
+        assert!(error_fields(|issues| validate_catalog_database_url(&config, issues)).is_empty());
+
+        config.catalog_database_url = Some("postgres://nas.local/catalog".to_string());
+        config.catalog_sharding = CatalogSharding::PerDestination;
+        assert_eq!(
+            error_fields(|issues| validate_catalog_database_url(&config, issues)),
+            vec!["catalog_database_url", "catalog_database_url"]
+        );
+    }
+
+    /// Run one validation step and return the fields it reported errors for
+    fn error_fields(validate: impl FnOnce(&mut Issues)) -> Vec<String> {
+        let mut issues = Issues::default();
+        validate(&mut issues);
+        issues
+            .0
+            .into_iter()
+            .filter(ValidationIssue::is_error)
+            .map(|issue| issue.field)
+            .collect()
+    }
+
     fn create_test_config() -> Config {
         Config {
             database_file: String::new(),
             max_mebibytes_for_hash: 1,
+            hash_tail_mebibytes: 0,
             backup_sources: vec![],
             backup_destinations: vec![],
             skip_source_hash_check_if_newer: true,
@@ -639,6 +2317,46 @@ mod tests {
             max_threads: 4,
             schedule: None,
             run_on_startup: true,
+            write_backup_marker: false,
+            backup_marker_max_age_hours: None,
+            low_priority_group: None,
+            prune_unclaimed: false,
+            auto_approve_pruning: false,
+            sqlite_snapshot: None,
+            post_processors: vec![],
+            plugins: vec![],
+            copy_buffer_kib: 1024,
+            verification_tiers: vec![],
+            quick_skip_unchanged: false,
+            always_copy_below_bytes: None,
+            hash_cache: true,
+            resume_interrupted_run: false,
+            clock_skew_tolerance_secs: 2,
+            mtime_tolerance_secs: 0,
+            critical_paths: vec![],
+            regression_detection: RegressionDetection::default(),
+            catalog_sharding: CatalogSharding::None,
+            catalog_database_url: None,
+            log_retention_days: Some(30),
+            max_log_rows: Some(100_000),
+            file_action_runs: 10,
+            mass_change_guard: None,
+            trash_retention_days: None,
+            file_operation_log: None,
+            catalog_backups_kept: None,
+            checksum_manifests: false,
+            candidate_batch_size: 10_000,
+            recopy_changed_files: false,
+            workspace_dir: None,
+            locked_files: LockedFiles::default(),
+            destination_checks: DestinationChecks::default(),
+            copy_order: CopyOrder::default(),
+            max_run_duration_minutes: None,
+            max_bytes_per_run: None,
+            triggers: vec![],
+            scrub: None,
+            verify_sample_percent: None,
+            profiles: BTreeMap::new(),
         }
     }
 }
@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+/// Catalogued source files that lack a valid backup in a destination their source is routed to
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CoverageReport {
+    /// Catalogued files under a configured source
+    pub files_checked: u64,
+    pub destinations: Vec<DestinationCoverage>,
+    /// Files missing from at least one destination, by path
+    pub gaps: Vec<CoverageGap>,
+}
+
+/// How many of the files routed to one destination have a valid backup there
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DestinationCoverage {
+    pub name: String,
+    pub path: String,
+    pub expected: u64,
+    pub covered: u64,
+    pub missing: u64,
+    pub missing_bytes: u64,
+}
+
+/// A source file without a valid backup in some of its destinations
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CoverageGap {
+    pub path: String,
+    pub file_size: u64,
+    /// Names of the destinations without a valid backup
+    pub missing_from: Vec<String>,
+    /// No destination holds a valid backup of the file
+    pub no_valid_backup: bool,
+}
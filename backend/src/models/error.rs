@@ -1,3 +1,4 @@
+use crate::models::validation_issue::{format_issues, ValidationIssue};
 use std::io;
 use std::path::PathBuf;
 use thiserror::Error;
@@ -14,7 +15,6 @@ pub enum BackupError {
     },
 
     #[error("Failed to open or create database file '{path}': {cause}")]
-    #[allow(dead_code)]
     DatabaseConnection {
         path: String,
         cause: rusqlite::Error,
@@ -40,6 +40,20 @@ pub enum BackupError {
         cause: rusqlite::Error,
     },
 
+    #[error("'{file}' got source ID {id} in {inserted_into}, which has run out of IDs; that ID belongs to {named}")]
+    SourceIdOutOfSpan {
+        file: String,
+        id: i32,
+        inserted_into: String,
+        named: String,
+    },
+
+    #[error("Failed to connect to the PostgreSQL catalog at {server}: {reason}")]
+    PostgresConnection { server: String, reason: String },
+
+    #[error("PostgreSQL catalog query failed for '{operation}': {reason}")]
+    PostgresQuery { operation: String, reason: String },
+
     #[error("Failed to hash file '{path}': {cause}")]
     HashError { path: PathBuf, cause: io::Error },
 
@@ -55,6 +69,13 @@ pub enum BackupError {
         cause: std::time::SystemTimeError,
     },
 
+    #[error("File '{path}' is locked by another program after {attempts} attempt(s): {reason}")]
+    FileLocked {
+        path: PathBuf,
+        attempts: u32,
+        reason: String,
+    },
+
     #[error("Failed to copy file from '{from}' to '{to}': {cause}")]
     FileCopy {
         from: PathBuf,
@@ -62,6 +83,54 @@ pub enum BackupError {
         cause: io::Error,
     },
 
+    #[error("Backup verification failed for '{path}': {reason}")]
+    VerificationFailed { path: PathBuf, reason: String },
+
+    #[error("Invalid configuration:\n{}", format_issues(.0))]
+    ConfigValidation(Vec<ValidationIssue>),
+
+    #[error("Failed to snapshot SQLite database '{path}': {reason}")]
+    SqliteSnapshot { path: PathBuf, reason: String },
+
+    #[error("Plugin '{name}' failed: {message}")]
+    Plugin { name: String, message: String },
+
+    #[error("Invalid run selection: {0}")]
+    Selection(String),
+
+    #[error("Invalid profile: {0}")]
+    Profile(String),
+
+    #[error("Invalid job: {0}")]
+    Job(String),
+
+    #[error("Invalid destination: {0}")]
+    Destination(String),
+
+    #[error("Run stopped at its first failed file (--fail-on-error): {0}")]
+    FailedFile(String),
+
+    #[error("Run held back for a mass change: {0}")]
+    MassChange(String),
+
+    #[error("Cannot compare runs: {0}")]
+    RunDiff(String),
+
+    #[error("Cannot plan a restore: {0}")]
+    Restore(String),
+
+    #[error("Invalid catalog export: {0}")]
+    CatalogImport(String),
+
+    #[error(
+        "Another backup is already running against this database ({holder}); \
+         wait for it to finish, or pass --wait to start once it has"
+    )]
+    AlreadyRunning { lock_file: PathBuf, holder: String },
+
+    #[error("Failed to lock '{path}' for the run: {cause}")]
+    RunLock { path: PathBuf, cause: io::Error },
+
     #[error("IO error: {0}")]
     Io(#[from] io::Error),
 
@@ -69,4 +138,39 @@ pub enum BackupError {
     ThreadPool(#[from] rayon::ThreadPoolBuildError),
 }
 
+impl BackupError {
+    /// Whether the error comes from a file another program has opened exclusively or locked
+    pub fn is_locked_file(&self) -> bool {
+        match self {
+            BackupError::HashError { cause, .. }
+            | BackupError::MetadataError { cause, .. }
+            | BackupError::FileCopy { cause, .. }
+            | BackupError::Io(cause) => is_locked_file_error(cause),
+            _ => false,
+        }
+    }
+}
+
+/// Whether an I/O error means another program holds the file open exclusively or has it locked
+pub fn is_locked_file_error(error: &io::Error) -> bool {
+    #[cfg(windows)]
+    {
+        const ERROR_SHARING_VIOLATION: i32 = 32;
+        const ERROR_LOCK_VIOLATION: i32 = 33;
+        matches!(
+            error.raw_os_error(),
+            Some(ERROR_SHARING_VIOLATION | ERROR_LOCK_VIOLATION)
+        )
+    }
+    #[cfg(not(windows))]
+    {
+        matches!(
+            error.kind(),
+            io::ErrorKind::ResourceBusy
+                | io::ErrorKind::ExecutableFileBusy
+                | io::ErrorKind::WouldBlock
+        )
+    }
+}
+
 pub type Result<T> = std::result::Result<T, BackupError>;
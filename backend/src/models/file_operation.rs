@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+
+/// Something done to, or found about, one file, as kept in the `file_operation_log`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileOperation {
+    /// Written to a destination and verified
+    Copied,
+    /// A backup moved to follow its source file to a new path, instead of copied again
+    Moved,
+    /// The backup at a destination already matched the source
+    Skipped,
+    /// The copy to a destination failed, or failed its verification
+    CopyFailed,
+    /// A backup checked against the source's hash, after a copy or by a scrub
+    Verified,
+    VerificationFailed,
+    /// A backup removed for good, such as a corrupt copy or an orphan
+    Deleted,
+    /// A backup moved to the destination's `.trash/` before being replaced
+    Trashed,
+    /// A destination file no source claims, moved to `unclaimed/`
+    Unclaimed,
+    /// An orphan moved to the destination's `quarantine/`
+    Quarantined,
+}
+
+/// One line of the `file_operation_log`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileOperationEntry {
+    /// RFC 3339 time the operation was recorded
+    pub at: String,
+    pub operation: FileOperation,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_id: Option<String>,
+    /// Source file the operation was for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    /// Backup path at the destination
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backup: Option<String>,
+    /// Hash of the file's contents the operation went by
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
+    /// Why, or where the file was moved to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl FileOperationEntry {
+    /// An entry for `operation` with nothing else filled in; the time is set when it is recorded
+    pub fn new(operation: FileOperation) -> Self {
+        FileOperationEntry {
+            at: String::new(),
+            operation,
+            run_id: None,
+            source: None,
+            backup: None,
+            hash: None,
+            bytes: None,
+            detail: None,
+        }
+    }
+}
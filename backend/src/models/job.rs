@@ -0,0 +1,163 @@
+use crate::models::api::BackupStatus;
+use crate::models::dry_run_mode::DryRunMode;
+use crate::models::error::{BackupError, Result};
+use crate::models::run_selection::RunSelection;
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Editable part of a backup job, as submitted to the jobs API
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobDefinition {
+    /// Unique name shown in the UI
+    pub name: String,
+    /// Configured source to back up, matched by name or parent directory; all sources if unset
+    #[serde(default)]
+    pub source: Option<String>,
+    /// Subdirectory to back up, absolute or relative to the selected source
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default)]
+    pub dry_run_full: bool,
+    /// Cron expression; jobs without one only run when started by hand
+    #[serde(default)]
+    pub schedule: Option<String>,
+    #[serde(default = "bool_true")]
+    pub enabled: bool,
+}
+
+/// Outcome of the last run a job started
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobResult {
+    /// Run ID in the backup history; unset if the run couldn't be started
+    pub backup_id: Option<String>,
+    pub status: BackupStatus,
+    /// Unix timestamp of when the job was started
+    pub started_at: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A stored job with its run state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub id: i64,
+    #[serde(flatten)]
+    pub definition: JobDefinition,
+    pub last_result: Option<JobResult>,
+    /// Unix timestamp of when a run started by this job last completed without error
+    pub last_success_at: Option<i64>,
+    /// Unix timestamp of the next scheduled run; unset if disabled or unscheduled
+    pub next_run: Option<i64>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl JobDefinition {
+    /// Check the fields that don't depend on the current configuration
+    pub fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            return Err(BackupError::Job("Job name cannot be empty".to_string()));
+        }
+        if self.dry_run && self.dry_run_full {
+            return Err(BackupError::Job(
+                "dry_run and dry_run_full cannot both be set".to_string(),
+            ));
+        }
+        if let Some(schedule) = &self.schedule {
+            Schedule::from_str(schedule).map_err(|e| {
+                BackupError::Job(format!("Invalid cron expression '{}': {}", schedule, e))
+            })?;
+        }
+        Ok(())
+    }
+
+    pub fn selection(&self) -> RunSelection {
+        RunSelection {
+            source: self.source.clone(),
+            path: self.path.clone(),
+            ..Default::default()
+        }
+    }
+
+    pub fn dry_run_mode(&self) -> DryRunMode {
+        if self.dry_run_full {
+            DryRunMode::Full
+        } else if self.dry_run {
+            DryRunMode::Quick
+        } else {
+            DryRunMode::None
+        }
+    }
+
+    /// Unix timestamp of the first scheduled run after `after`, if the job runs on a schedule
+    pub fn next_run_after(&self, after: DateTime<Utc>) -> Option<i64> {
+        if !self.enabled {
+            return None;
+        }
+        let schedule = Schedule::from_str(self.schedule.as_deref()?).ok()?;
+        schedule.after(&after).next().map(|next| next.timestamp())
+    }
+}
+
+const fn bool_true() -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definition(schedule: Option<&str>) -> JobDefinition {
+        JobDefinition {
+            name: "nightly".to_string(),
+            source: None,
+            path: None,
+            dry_run: false,
+            dry_run_full: false,
+            schedule: schedule.map(str::to_string),
+            enabled: true,
+        }
+    }
+
+    #[test]
+    fn test_validate_job_definition() {
+        assert!(definition(Some("0 0 2 * * *")).validate().is_ok());
+        assert!(definition(None).validate().is_ok());
+        assert!(matches!(
+            definition(Some("every night")).validate(),
+            Err(BackupError::Job(_))
+        ));
+
+        let mut unnamed = definition(None);
+        unnamed.name = "  ".to_string();
+        assert!(unnamed.validate().is_err());
+
+        let mut both = definition(None);
+        both.dry_run = true;
+        both.dry_run_full = true;
+        assert!(both.validate().is_err());
+    }
+
+    #[test]
+    fn test_next_run_only_for_enabled_scheduled_jobs() {
+        let after = DateTime::parse_from_rfc3339("2025-03-01T10:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let expected = DateTime::parse_from_rfc3339("2025-03-02T02:00:00Z")
+            .unwrap()
+            .timestamp();
+
+        let mut job = definition(Some("0 0 2 * * *"));
+        assert_eq!(job.next_run_after(after), Some(expected));
+
+        job.enabled = false;
+        assert_eq!(job.next_run_after(after), None);
+        assert_eq!(definition(None).next_run_after(after), None);
+    }
+}
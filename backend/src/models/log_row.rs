@@ -9,3 +9,42 @@ pub struct LogRow {
     pub context: Option<String>,
     pub source: Option<String>,
 }
+
+impl LogRow {
+    /// Whether the entry passes the Logs page's filters, as `query_logs` applies them
+    pub fn matches(&self, level: Option<&str>, search: Option<&str>, run: Option<&str>) -> bool {
+        let level_matches = level.is_none_or(|level| level == "all" || level == self.level);
+        let search_matches = search
+            .is_none_or(|search| self.message.to_lowercase().contains(&search.to_lowercase()));
+        let run_matches = run.is_none_or(|run| {
+            self.context
+                .as_deref()
+                .and_then(|context| serde_json::from_str::<serde_json::Value>(context).ok())
+                .is_some_and(|context| context["run_id"] == run)
+        });
+        level_matches && search_matches && run_matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let log = LogRow {
+            id: 1,
+            timestamp: 0,
+            level: "WARN".to_string(),
+            message: "Copy failed for a.jpg".to_string(),
+            context: Some(r#"{"run_id":"run-1","source":"photos"}"#.to_string()),
+            source: None,
+        };
+
+        assert!(log.matches(None, None, None));
+        assert!(log.matches(Some("all"), Some("copy FAILED"), Some("run-1")));
+        assert!(!log.matches(Some("ERROR"), None, None));
+        assert!(!log.matches(None, Some("copied"), None));
+        assert!(!log.matches(None, None, Some("run-2")));
+    }
+}
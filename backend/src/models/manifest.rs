@@ -0,0 +1,211 @@
+use crate::models::tombstone::Tombstone;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The backed-up files of one instance's catalog, for comparing with another instance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HashManifest {
+    /// Name of the machine the manifest was taken on
+    pub host: String,
+    /// Unix timestamp of when the manifest was taken
+    pub generated_at: i64,
+    pub files: Vec<ManifestEntry>,
+    /// Source files found deleted, oldest first, so the sources can be told as of any run
+    #[serde(default)]
+    pub deleted: Vec<Tombstone>,
+}
+
+/// A catalogued source file with at least one recorded backup
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub hash: String,
+    pub file_size: u64,
+    /// MiB at the start of the file covered by `hash`
+    pub hash_mebibytes: usize,
+    /// MiB at the end of the file covered by `hash` too, with the file's size
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub hash_tail_mebibytes: usize,
+}
+
+fn is_zero(value: &usize) -> bool {
+    *value == 0
+}
+
+/// Content held by both instances, with where each keeps it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SharedContent {
+    pub hash: String,
+    pub file_size: u64,
+    pub local_paths: Vec<String>,
+    pub peer_paths: Vec<String>,
+}
+
+/// Counts and sizes of each side of a comparison; shared sizes count each content once
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ComparisonSummary {
+    pub shared_files: u64,
+    pub shared_bytes: u64,
+    pub local_only_files: u64,
+    pub local_only_bytes: u64,
+    pub peer_only_files: u64,
+    pub peer_only_bytes: u64,
+}
+
+/// Which files two instances both back up and which only one of them does
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestComparison {
+    pub local_host: String,
+    pub peer_host: String,
+    pub summary: ComparisonSummary,
+    pub shared: Vec<SharedContent>,
+    pub local_only: Vec<ManifestEntry>,
+    pub peer_only: Vec<ManifestEntry>,
+}
+
+/// Hash, size, and how much of the start and end was hashed unless the hash covers the whole file
+type ContentKey = (String, u64, Option<(usize, usize)>);
+
+impl ManifestEntry {
+    /// Entries match when their hashes cover the same part of files of the same size
+    fn content_key(&self) -> ContentKey {
+        let whole_file = self.file_size <= self.hash_mebibytes as u64 * 1024 * 1024;
+        (
+            self.hash.clone(),
+            self.file_size,
+            (!whole_file).then_some((self.hash_mebibytes, self.hash_tail_mebibytes)),
+        )
+    }
+}
+
+impl HashManifest {
+    /// Match this manifest's files against a peer's by content, whatever their paths
+    pub fn compare(&self, peer: &HashManifest) -> ManifestComparison {
+        let mut contents: BTreeMap<ContentKey, (Vec<&ManifestEntry>, Vec<&ManifestEntry>)> =
+            BTreeMap::new();
+        for entry in &self.files {
+            contents
+                .entry(entry.content_key())
+                .or_default()
+                .0
+                .push(entry);
+        }
+        for entry in &peer.files {
+            contents
+                .entry(entry.content_key())
+                .or_default()
+                .1
+                .push(entry);
+        }
+
+        let mut summary = ComparisonSummary::default();
+        let mut shared = Vec::new();
+        let mut local_only = Vec::new();
+        let mut peer_only = Vec::new();
+        for ((hash, file_size, _), (local, remote)) in contents {
+            match (local.is_empty(), remote.is_empty()) {
+                (false, false) => {
+                    summary.shared_files += 1;
+                    summary.shared_bytes += file_size;
+                    shared.push(SharedContent {
+                        hash,
+                        file_size,
+                        local_paths: local.iter().map(|e| e.path.clone()).collect(),
+                        peer_paths: remote.iter().map(|e| e.path.clone()).collect(),
+                    });
+                }
+                (false, true) => {
+                    summary.local_only_files += local.len() as u64;
+                    summary.local_only_bytes += file_size * local.len() as u64;
+                    local_only.extend(local.into_iter().cloned());
+                }
+                _ => {
+                    summary.peer_only_files += remote.len() as u64;
+                    summary.peer_only_bytes += file_size * remote.len() as u64;
+                    peer_only.extend(remote.into_iter().cloned());
+                }
+            }
+        }
+        local_only.sort_by(|a, b| a.path.cmp(&b.path));
+        peer_only.sort_by(|a, b| a.path.cmp(&b.path));
+
+        ManifestComparison {
+            local_host: self.host.clone(),
+            peer_host: peer.host.clone(),
+            summary,
+            shared,
+            local_only,
+            peer_only,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, hash: &str, file_size: u64, hash_mebibytes: usize) -> ManifestEntry {
+        ManifestEntry {
+            path: path.to_string(),
+            hash: hash.to_string(),
+            file_size,
+            hash_mebibytes,
+            hash_tail_mebibytes: 0,
+        }
+    }
+
+    fn manifest(host: &str, files: Vec<ManifestEntry>) -> HashManifest {
+        HashManifest {
+            host: host.to_string(),
+            generated_at: 0,
+            files,
+            deleted: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_compare_matches_content_across_paths() {
+        let big = 3 * 1024 * 1024;
+        let local = manifest(
+            "laptop",
+            vec![
+                entry("/home/a/photo.jpg", "aaa", 100, 1),
+                entry("/home/a/copy-of-photo.jpg", "aaa", 100, 1),
+                entry("/home/a/notes.txt", "bbb", 10, 1),
+                entry("/home/a/video.mp4", "ccc", big, 1),
+            ],
+        );
+        let peer = manifest(
+            "desktop",
+            vec![
+                // Small files are hashed whole, so differing limits still match
+                entry("/home/b/photo.jpg", "aaa", 100, 4),
+                // Only the first MiB of the laptop's video was hashed
+                entry("/home/b/video.mp4", "ccc", big, 4),
+                entry("/home/b/music.flac", "ddd", 50, 1),
+            ],
+        );
+
+        let comparison = local.compare(&peer);
+
+        assert_eq!(comparison.local_host, "laptop");
+        assert_eq!(comparison.shared.len(), 1);
+        assert_eq!(comparison.shared[0].local_paths.len(), 2);
+        assert_eq!(comparison.shared[0].peer_paths, ["/home/b/photo.jpg"]);
+        let local_only: Vec<_> = comparison.local_only.iter().map(|e| &e.path).collect();
+        assert_eq!(local_only, ["/home/a/notes.txt", "/home/a/video.mp4"]);
+        let peer_only: Vec<_> = comparison.peer_only.iter().map(|e| &e.path).collect();
+        assert_eq!(peer_only, ["/home/b/music.flac", "/home/b/video.mp4"]);
+        assert_eq!(
+            comparison.summary,
+            ComparisonSummary {
+                shared_files: 1,
+                shared_bytes: 100,
+                local_only_files: 2,
+                local_only_bytes: 10 + big,
+                peer_only_files: 2,
+                peer_only_bytes: 50 + big,
+            }
+        );
+    }
+}
@@ -1,11 +1,35 @@
+pub mod active_run;
 pub mod api;
+pub mod audit_event;
 pub mod backed_up_file;
+pub mod backup_marker;
 pub mod backup_row;
+pub mod catalog_export;
+pub mod catalog_file;
+pub mod catalog_write;
 pub mod config;
 pub mod config_validator;
+pub mod coverage;
 pub mod dry_run_mode;
 pub mod error;
+pub mod file_operation;
+pub mod job;
 pub mod log_row;
+pub mod manifest;
+pub mod orphan;
+pub mod post_process_result;
 pub mod prepped_backup;
+pub mod prune_plan;
+pub mod replication;
+pub mod restore_plan;
+pub mod run_diff;
+pub mod run_record;
+pub mod run_report;
+pub mod run_selection;
+pub mod scrub;
 pub mod source_row;
 pub mod storage;
+pub mod tombstone;
+pub mod unclaimed_file;
+pub mod user;
+pub mod validation_issue;
@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+
+/// Destination files that no backup record points at, by destination
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OrphanReport {
+    pub destinations: Vec<DestinationOrphans>,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// Orphaned files found in one destination
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DestinationOrphans {
+    pub name: String,
+    pub path: String,
+    pub files: Vec<OrphanFile>,
+    pub bytes: u64,
+}
+
+/// A destination file with no Backup_Files row
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrphanFile {
+    pub path: String,
+    pub file_size: u64,
+    /// Unix timestamp of the file's modification time
+    pub modified_at: i64,
+}
+
+/// What to do with orphaned files once found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrphanAction {
+    /// Move into the destination's `quarantine/` folder
+    Quarantine,
+    Delete,
+}
+
+impl OrphanAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrphanAction::Quarantine => "quarantine",
+            OrphanAction::Delete => "delete",
+        }
+    }
+
+    pub fn parse(action: &str) -> Option<Self> {
+        match action {
+            "quarantine" => Some(OrphanAction::Quarantine),
+            "delete" => Some(OrphanAction::Delete),
+            _ => None,
+        }
+    }
+}
+
+/// What a cleanup did with the orphans it found
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrphanCleanup {
+    pub action: OrphanAction,
+    /// Files quarantined or deleted
+    pub files: u64,
+    pub bytes: u64,
+    /// Files that could not be moved or deleted
+    pub failed: u64,
+}
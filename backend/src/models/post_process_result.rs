@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+/// Verdict of a post-processor for one copied file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProcessorStatus {
+    /// The processor ran and accepted the file (exit status 0)
+    Passed,
+    /// The processor ran and reported a problem, e.g. an antivirus hit
+    Flagged,
+    /// The processor couldn't be run or timed out
+    Error,
+}
+
+impl ProcessorStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProcessorStatus::Passed => "passed",
+            ProcessorStatus::Flagged => "flagged",
+            ProcessorStatus::Error => "error",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "passed" => Some(ProcessorStatus::Passed),
+            "flagged" => Some(ProcessorStatus::Flagged),
+            "error" => Some(ProcessorStatus::Error),
+            _ => None,
+        }
+    }
+}
+
+/// Stored outcome of running one post-processor against one backup copy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostProcessResult {
+    pub processor: String,
+    pub source_path: String,
+    pub backup_path: String,
+    pub status: ProcessorStatus,
+    /// Output or error message from the processor, trimmed and truncated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// Unix timestamp of when the processor finished
+    pub processed_at: i64,
+}
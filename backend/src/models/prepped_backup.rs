@@ -1,16 +1,64 @@
+use crate::models::config::HashLimit;
+use crate::models::run_diff::FileAction;
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::time::Duration;
 
-#[derive(Debug)]
+/// A source file prepared for copying; written out when a run holds more than it keeps in memory
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PreppedBackup {
-    pub db_id: i32,
+    /// Directory of the source file, as recorded in the catalog
+    pub source_path: String,
+    pub source_name: String,
+    pub hash_limit: HashLimit,
+    pub force_overwrite_backup: bool,
     pub source_file: PathBuf,
+    /// Where the source file's content is read from: a snapshot of it, or `source_file` itself
+    pub read_from: PathBuf,
+    /// Path of the file below the source root's parent, as mirrored into destinations
+    pub relative_path: PathBuf,
     pub file_name: String,
     pub backup_paths: Vec<PathBuf>,
+    /// Backup paths whose destination can't hold the source's names, each with the path it
+    /// would have under them
+    pub renamed_backups: Vec<(PathBuf, PathBuf)>,
     pub hash: String,
     pub file_size: u64,
-    #[allow(dead_code)]
     pub source_last_modified_date: Duration,
-    #[allow(dead_code)]
+    /// Had no record in the catalog before this run
+    pub new: bool,
     pub updated: bool,
+    /// Matches its recorded size and modification time with every backup on record, so the
+    /// destinations aren't checked
+    pub unchanged: bool,
+    /// Below `always_copy_below_bytes`, so copied to every destination without checking them
+    #[serde(default)]
+    pub always_copy: bool,
+    /// Recorded source file this one was moved or renamed from, whose backups are moved along
+    /// with it instead of the file being copied again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moved_from: Option<MovedFrom>,
+}
+
+/// A source file that is gone from where it was recorded, with the content of a new one
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MovedFrom {
+    pub file_name: String,
+    /// Directory of the file, as recorded in the catalog
+    pub file_path: String,
+    /// Its recorded backups still at their destinations
+    pub backups: Vec<PathBuf>,
+}
+
+impl PreppedBackup {
+    /// What the run found of the file, for comparing it with other runs
+    pub fn action(&self) -> FileAction {
+        if self.new {
+            FileAction::Copied
+        } else if self.updated {
+            FileAction::Updated
+        } else {
+            FileAction::Skipped
+        }
+    }
 }
@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+
+/// Destination files a pruning run would move, held until the plan is approved
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrunePlan {
+    pub id: i64,
+    /// Unix timestamp of when the plan was made
+    pub created_at: i64,
+    /// Run that made the plan
+    pub run_id: String,
+    pub status: PlanStatus,
+    pub files: u64,
+    pub bytes: u64,
+    /// Unix timestamps of the oldest and newest modification time among the files
+    pub oldest_modified_at: Option<i64>,
+    pub newest_modified_at: Option<i64>,
+    /// Unix timestamp of when the plan was approved, rejected or superseded
+    pub decided_at: Option<i64>,
+    /// Who decided: `config` for auto-approval, `run` when superseded, otherwise the API caller
+    pub decided_by: Option<String>,
+}
+
+/// Where a prune plan stands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlanStatus {
+    /// Waiting for approval
+    Pending,
+    /// Approved and carried out
+    Approved,
+    Rejected,
+    /// Replaced by a newer plan before anyone decided
+    Superseded,
+}
+
+/// A destination file in a prune plan
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlannedFile {
+    pub destination: String,
+    pub path: String,
+    pub file_size: u64,
+    /// Unix timestamp of the file's modification time when the plan was made
+    pub modified_at: i64,
+}
+
+impl PlanStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlanStatus::Pending => "pending",
+            PlanStatus::Approved => "approved",
+            PlanStatus::Rejected => "rejected",
+            PlanStatus::Superseded => "superseded",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "pending" => Some(PlanStatus::Pending),
+            "approved" => Some(PlanStatus::Approved),
+            "rejected" => Some(PlanStatus::Rejected),
+            "superseded" => Some(PlanStatus::Superseded),
+            _ => None,
+        }
+    }
+}
+
+impl PrunePlan {
+    /// A pending plan for `files`, summarising their count, size and age
+    pub fn pending(run_id: &str, created_at: i64, files: &[PlannedFile]) -> Self {
+        PrunePlan {
+            id: 0,
+            created_at,
+            run_id: run_id.to_string(),
+            status: PlanStatus::Pending,
+            files: files.len() as u64,
+            bytes: files.iter().map(|f| f.file_size).sum(),
+            oldest_modified_at: files.iter().map(|f| f.modified_at).min(),
+            newest_modified_at: files.iter().map(|f| f.modified_at).max(),
+            decided_at: None,
+            decided_by: None,
+        }
+    }
+}
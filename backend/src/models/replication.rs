@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+
+/// What `replicate` copied from one destination to another
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReplicationReport {
+    /// Backups recorded under the destination copied from
+    pub backups: u64,
+    pub copied: u64,
+    pub bytes_copied: u64,
+    /// Backups the other destination already held intact
+    pub already_present: u64,
+    pub failed: Vec<FailedReplication>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FailedReplication {
+    pub backup: String,
+    pub reason: String,
+}
@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+/// Which backup of each catalogued source file to restore to get the sources back as they were at
+/// a point in time
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RestorePlan {
+    /// Unix timestamp the plan restores the sources as of
+    pub as_of: i64,
+    /// The run the time was taken from, if one was asked for rather than a time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run: Option<String>,
+    /// Files with a backup made by then, by path
+    pub files: Vec<RestoreItem>,
+    /// Files deleted by then, as their tombstones tell
+    pub deleted: Vec<String>,
+    /// Files with no backup made by then, such as ones first backed up later
+    pub unavailable: Vec<String>,
+    /// What carrying out the plan did, if it was carried out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub restored: Option<RestoreOutcome>,
+}
+
+/// One source file and the backup holding its content as of the plan's time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RestoreItem {
+    pub path: String,
+    pub backup: String,
+    /// Unix timestamp of when the backup was written
+    pub backed_up_at: i64,
+    /// Whether the backup is an earlier version kept in a destination's `.trash/`
+    pub from_trash: bool,
+}
+
+/// Files a restore copied to its target directory
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RestoreOutcome {
+    pub target: String,
+    pub restored: u64,
+    /// Files already at their place under the target, which are left as they are
+    pub skipped: u64,
+    pub failed: u64,
+}
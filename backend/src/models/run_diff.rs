@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+
+/// What a run found of one source file, kept in `Run_File_Actions` to compare runs by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileAction {
+    /// Not in the catalog before the run, so copied for the first time
+    Copied,
+    /// Changed since it was last backed up, so copied again
+    Updated,
+    /// Unchanged since it was last backed up
+    Skipped,
+}
+
+impl FileAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileAction::Copied => "copied",
+            FileAction::Updated => "updated",
+            FileAction::Skipped => "skipped",
+        }
+    }
+}
+
+/// Source files that appeared, changed or disappeared between two runs
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunDiff {
+    /// The run that started first, whichever order the runs were given in
+    pub from_run: String,
+    pub to_run: String,
+    /// Files each run found
+    pub files_before: u64,
+    pub files_after: u64,
+    pub new_files: u64,
+    /// Files both runs found that a run after the first one, up to the second, copied again
+    pub changed_files: u64,
+    /// Files the first run found and the second didn't, under the sources the second backed up
+    pub deleted_files: u64,
+    /// Changed and deleted files as a share of the files the first run found, in percent
+    pub changed_percent: f64,
+    /// The paths of the files in each group, by path, up to the limit asked for
+    pub new: Vec<String>,
+    pub changed: Vec<String>,
+    pub deleted: Vec<String>,
+}
+
+/// One source file as a run found it
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunFileAction {
+    /// Name of the source the file was backed up under
+    pub source: String,
+    pub file: String,
+    pub action: FileAction,
+}
@@ -0,0 +1,142 @@
+use crate::models::api::BackupStatus;
+use crate::models::run_report::{RunAnomaly, RunReport};
+use serde::{Deserialize, Serialize};
+
+/// A finished run as kept in the `Runs` table, for CLI and API runs alike
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub id: String,
+    /// Unix timestamps of when the run started and finished
+    pub started_at: i64,
+    pub finished_at: i64,
+    pub status: BackupStatus,
+    pub dry_run: bool,
+    pub files_copied: u64,
+    pub bytes_copied: u64,
+    pub errors: u64,
+    pub error: Option<String>,
+    /// Note given when the run was started
+    pub note: Option<String>,
+    /// Whether the run was a `checkpoint` of the configured critical paths
+    pub checkpoint: bool,
+    /// How long the run took and how fast it hashed; unset for runs cut short by a restart
+    pub timing: Option<RunTiming>,
+    /// Ways the run was far slower or larger than the runs before it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub anomalies: Vec<RunAnomaly>,
+}
+
+/// Measurements taken while a run was going, for throughput statistics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct RunTiming {
+    pub duration_ms: u64,
+    /// Files the run prepared, whether or not they needed a copy
+    pub files_processed: u64,
+    /// Bytes read to hash source files and backups
+    pub bytes_hashed: u64,
+    /// Time spent reading and hashing, summed over worker threads
+    pub hash_micros: u64,
+}
+
+const MIB: f64 = 1024.0 * 1024.0;
+
+impl RunRecord {
+    /// Record of a run that produced `report`, failed if it ended with an error
+    pub fn finished(
+        id: &str,
+        started_at: i64,
+        finished_at: i64,
+        dry_run: bool,
+        report: Option<&RunReport>,
+        error: Option<String>,
+    ) -> Self {
+        let status = if error.is_some() {
+            BackupStatus::Failed
+        } else {
+            BackupStatus::Completed
+        };
+        let sources = report.map(|r| r.sources.as_slice()).unwrap_or_default();
+
+        RunRecord {
+            id: id.to_string(),
+            started_at,
+            finished_at,
+            status,
+            dry_run,
+            files_copied: sources.iter().map(|s| s.files_copied).sum(),
+            bytes_copied: sources.iter().map(|s| s.bytes_copied).sum(),
+            errors: sources.iter().map(|s| s.errors).sum(),
+            error,
+            note: None,
+            checkpoint: false,
+            timing: None,
+            anomalies: Vec::new(),
+        }
+    }
+
+    /// MiB copied per second of the run
+    pub fn mib_per_sec(&self) -> Option<f64> {
+        let seconds = self.seconds()?;
+        Some(self.bytes_copied as f64 / MIB / seconds)
+    }
+
+    /// Files prepared per second of the run
+    pub fn files_per_sec(&self) -> Option<f64> {
+        let seconds = self.seconds()?;
+        Some(self.timing?.files_processed as f64 / seconds)
+    }
+
+    /// MiB read and hashed per second, per hashing thread; unset if the run hashed nothing
+    pub fn hash_mib_per_sec(&self) -> Option<f64> {
+        let timing = self.timing.filter(|timing| timing.hash_micros > 0)?;
+        Some(timing.bytes_hashed as f64 / MIB / (timing.hash_micros as f64 / 1_000_000.0))
+    }
+
+    fn seconds(&self) -> Option<f64> {
+        let timing = self.timing.filter(|timing| timing.duration_ms > 0)?;
+        Some(timing.duration_ms as f64 / 1000.0)
+    }
+}
+
+/// A run note with surrounding whitespace removed; blank notes are dropped
+pub fn clean_note(note: Option<&str>) -> Option<String> {
+    note.map(str::trim)
+        .filter(|note| !note.is_empty())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rates_from_timing() {
+        let mut run = RunRecord::finished("rates", 0, 4, false, None, None);
+        run.bytes_copied = 8 * 1024 * 1024;
+        assert_eq!(run.mib_per_sec(), None);
+
+        run.timing = Some(RunTiming {
+            duration_ms: 4000,
+            files_processed: 100,
+            bytes_hashed: 3 * 1024 * 1024,
+            hash_micros: 0,
+        });
+        assert_eq!(run.mib_per_sec(), Some(2.0));
+        assert_eq!(run.files_per_sec(), Some(25.0));
+        assert_eq!(run.hash_mib_per_sec(), None);
+
+        run.timing.as_mut().unwrap().hash_micros = 500_000;
+        assert_eq!(run.hash_mib_per_sec(), Some(6.0));
+    }
+
+    #[test]
+    fn test_clean_note_trims_and_drops_blank_notes() {
+        assert_eq!(
+            clean_note(Some("  before OS upgrade\n")).as_deref(),
+            Some("before OS upgrade")
+        );
+        assert_eq!(clean_note(Some(" \t ")), None);
+        assert_eq!(clean_note(Some("")), None);
+        assert_eq!(clean_note(None), None);
+    }
+}
@@ -0,0 +1,299 @@
+use crate::models::prune_plan::PrunePlan;
+use crate::models::scrub::ScrubReport;
+use crate::models::unclaimed_file::UnclaimedFile;
+use serde::{Deserialize, Serialize};
+
+/// Summary of noteworthy events collected during a backup run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunReport {
+    /// Top-level directories that appeared under auto-discovering sources since the last run
+    pub new_directories: Vec<DiscoveredDirectory>,
+    /// Per-source results, keyed by source name
+    pub sources: Vec<SourceSummary>,
+    /// Per-destination results, keyed by destination name, or path for unnamed destinations
+    #[serde(default)]
+    pub destinations: Vec<DestinationSummary>,
+    /// Destination files moved into the unclaimed area because no source matched them
+    #[serde(default)]
+    pub unclaimed_files: Vec<UnclaimedFile>,
+    /// Unclaimed files found this run and held back until the plan is approved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prune_plan: Option<PrunePlan>,
+    /// Where timestamps were ahead of the system clock by more than the configured tolerance
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub clock_skew: Vec<ClockSkew>,
+    /// Ways the run was far slower or larger than the runs before it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub anomalies: Vec<RunAnomaly>,
+    /// Source files another program kept locked through every retry
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub locked_files: Vec<LockedFile>,
+    /// Directories and files discovery couldn't read and left out, for sources with
+    /// `on_error` set to `skip`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped_entries: Vec<SkippedEntry>,
+    /// Set when the run used up its time or byte budget and left files for the next run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub budget_exhausted: Option<BudgetExhausted>,
+    /// Destinations left out of the run because their pre-flight check failed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped_destinations: Vec<UnavailableDestination>,
+    /// Destinations that reached their `max_total_bytes`, and the copies they didn't take
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub quota_exceeded: Vec<QuotaExceeded>,
+    /// Changes since the last run past the `mass_change_guard` limits, which were confirmed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mass_change: Option<MassChange>,
+    /// Source files that changed while they were being copied, so their copies may be torn
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changed_during_run: Vec<ChangedDuringRun>,
+    /// Backups picked at random and read back as the run ended, with `verify_sample_percent`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verified_sample: Option<ScrubReport>,
+    /// Files prepared and their bytes by extension, per source, largest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub file_types: Vec<FileTypeSummary>,
+    /// Catalogued source files found deleted since the last run, each left a tombstone
+    #[serde(default)]
+    pub tombstones: u64,
+}
+
+/// Results for a single backup source
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SourceSummary {
+    pub name: String,
+    pub files_prepared: u64,
+    pub files_copied: u64,
+    pub bytes_copied: u64,
+    /// Backups moved to follow a source file that was moved or renamed, rather than copied
+    #[serde(default)]
+    pub files_moved: u64,
+    pub errors: u64,
+}
+
+/// Results for a single backup destination
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DestinationSummary {
+    pub name: String,
+    pub files_copied: u64,
+    pub bytes_copied: u64,
+    /// Disk space the copies take up, less than `bytes_copied` when sparse files kept their holes
+    #[serde(default)]
+    pub physical_bytes_copied: u64,
+    pub errors: u64,
+}
+
+/// Timestamps ahead of the system clock, found for one source or destination
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ClockSkew {
+    pub kind: SkewKind,
+    /// Name of the source or destination the timestamps belong to
+    pub location: String,
+    pub files: u64,
+    /// Furthest a timestamp was ahead of the system clock
+    pub max_ahead_secs: u64,
+}
+
+/// Where a timestamp ahead of the system clock was read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SkewKind {
+    /// A source file's modification time
+    Source,
+    /// A backup's modification time, as set by the destination's clock
+    Destination,
+    /// A source file's modification time recorded in the database
+    Database,
+}
+
+impl SkewKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SkewKind::Source => "source",
+            SkewKind::Destination => "destination",
+            SkewKind::Database => "database",
+        }
+    }
+}
+
+/// A source file that couldn't be read because another program had it locked
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockedFile {
+    pub source: String,
+    pub file: String,
+    /// Times reading the file was tried
+    pub attempts: u32,
+    pub error: String,
+}
+
+/// A source file that changed between being prepared and its copy finishing
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChangedDuringRun {
+    pub source: String,
+    pub file: String,
+    /// Copied again with `recopy_changed_files` at the end of the run, without changing again
+    pub recopied: bool,
+}
+
+/// The files of one extension a source had in a run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileTypeSummary {
+    pub source: String,
+    /// Lowercased, without the dot; empty for files without one, and `*` for the extensions
+    /// beyond a source's largest
+    pub extension: String,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// Part of a source that discovery couldn't read and passed over
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkippedEntry {
+    pub source: String,
+    /// The directory or file, when the error names one
+    pub path: Option<String>,
+    pub error: String,
+}
+
+/// A destination that failed its check before the run copied anything
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnavailableDestination {
+    /// Destination name, or path for unnamed destinations
+    pub destination: String,
+    pub reason: String,
+    /// Set for a removable destination that couldn't be mounted, which a run always skips
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub offline: bool,
+}
+
+/// A destination the run stopped copying to because it reached its `max_total_bytes`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuotaExceeded {
+    /// Destination name, or path for unnamed destinations
+    pub destination: String,
+    pub max_total_bytes: u64,
+    /// Bytes of backups the destination holds, as far as the run knows
+    pub used_bytes: u64,
+    /// Copies not made because they would have gone over the quota
+    pub files_skipped: u64,
+    pub bytes_skipped: u64,
+}
+
+/// Changes since the last run that went past the `mass_change_guard` limits
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MassChange {
+    /// The run compared with
+    pub since_run: String,
+    /// Files the last run found under the sources this run backs up
+    pub files_before: u64,
+    pub changed_files: u64,
+    /// Changed files as a share of `files_before`
+    pub changed_percent: f64,
+    pub deleted_files: u64,
+    /// Whether the run was let go ahead and copy the changes
+    pub confirmed: bool,
+}
+
+/// Which budget a run used up, and how many files it left for the next run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BudgetExhausted {
+    pub limit: BudgetLimit,
+    pub files_deferred: u64,
+}
+
+/// A limit on how much a single run does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetLimit {
+    /// `max_run_duration_minutes`
+    Duration,
+    /// `max_bytes_per_run`
+    Bytes,
+}
+
+impl BudgetLimit {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BudgetLimit::Duration => "time",
+            BudgetLimit::Bytes => "byte",
+        }
+    }
+}
+
+/// A measurement of a run that was far off the median of recent runs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunAnomaly {
+    pub metric: AnomalyMetric,
+    /// This run's value, in MiB/s for rates and bytes or files for volumes
+    pub value: f64,
+    /// Median of the same measurement over the baseline runs
+    pub baseline: f64,
+    /// How many times slower or larger than the baseline the run was
+    pub factor: f64,
+}
+
+/// What a run anomaly measures
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnomalyMetric {
+    /// MiB read and hashed per second, per hashing thread
+    HashRate,
+    /// MiB copied per second of the run
+    CopyRate,
+    BytesCopied,
+    FilesCopied,
+}
+
+/// A new top-level directory found under a watched source
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveredDirectory {
+    pub parent_directory: String,
+    pub directory_name: String,
+}
+
+/// Result of copying one source file to one destination
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileOutcome {
+    pub source: String,
+    pub file: String,
+    pub destination: String,
+    pub status: FileStatus,
+    pub bytes: u64,
+    /// Disk space the copy takes up, where the platform reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub physical_bytes: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileStatus {
+    Copied,
+    /// An existing backup moved to the source file's new path
+    Moved,
+    /// Would have been copied, recorded during dry runs
+    Simulated,
+    Failed,
+}
+
+impl FileStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileStatus::Copied => "copied",
+            FileStatus::Moved => "moved",
+            FileStatus::Simulated => "simulated",
+            FileStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "copied" => Some(FileStatus::Copied),
+            "moved" => Some(FileStatus::Moved),
+            "simulated" => Some(FileStatus::Simulated),
+            "failed" => Some(FileStatus::Failed),
+            _ => None,
+        }
+    }
+}
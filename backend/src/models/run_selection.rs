@@ -0,0 +1,263 @@
+use crate::models::config::{BackupSource, Config};
+use crate::models::error::{BackupError, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Restricts a backup run to one configured source and/or one subtree
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunSelection {
+    /// Configured source to back up, matched by name or parent directory
+    pub source: Option<String>,
+    /// Subdirectory to back up, absolute or relative to the selected source
+    pub path: Option<String>,
+    /// Back up only the configured `critical_paths`, in order, and tag the run as a checkpoint
+    #[serde(default)]
+    pub checkpoint: bool,
+}
+
+/// A source chosen for a run along with where its directory walk starts
+#[derive(Debug, Clone)]
+pub struct SelectedSource {
+    pub source: BackupSource,
+    pub scan_root: String,
+    pub max_depth: Option<usize>,
+}
+
+impl RunSelection {
+    /// Returns true if no restriction was requested
+    pub fn is_empty(&self) -> bool {
+        self.source.is_none() && self.path.is_none() && !self.checkpoint
+    }
+
+    /// Returns true if the run only covers part of each selected source
+    pub fn limits_paths(&self) -> bool {
+        self.path.is_some() || self.checkpoint
+    }
+
+    /// Resolve the selection against the configuration's sources and critical paths
+    pub fn resolve(&self, config: &Config) -> Result<Vec<SelectedSource>> {
+        if !self.checkpoint {
+            return self.select_sources(&config.backup_sources);
+        }
+        if config.critical_paths.is_empty() {
+            return Err(BackupError::Selection(
+                "No critical_paths are configured for a checkpoint".to_string(),
+            ));
+        }
+
+        let mut selected = Vec::new();
+        for path in &config.critical_paths {
+            let critical = RunSelection {
+                path: Some(path.clone()),
+                ..Default::default()
+            };
+            selected.extend(critical.select_sources(&config.backup_sources)?);
+        }
+        Ok(selected)
+    }
+
+    /// Returns true if the given source matches the requested source
+    pub fn matches_source(&self, source: &BackupSource) -> bool {
+        match &self.source {
+            Some(requested) => {
+                source.name.as_deref() == Some(requested.as_str())
+                    || same_path(Path::new(requested), Path::new(&source.parent_directory))
+            }
+            None => true,
+        }
+    }
+
+    /// Resolve the selection against the configured sources
+    pub fn select_sources(&self, sources: &[BackupSource]) -> Result<Vec<SelectedSource>> {
+        let candidates: Vec<&BackupSource> =
+            sources.iter().filter(|s| self.matches_source(s)).collect();
+
+        if candidates.is_empty() {
+            return Err(BackupError::Selection(format!(
+                "No configured source matches '{}'",
+                self.source.as_deref().unwrap_or_default()
+            )));
+        }
+
+        let Some(path) = &self.path else {
+            return Ok(candidates
+                .into_iter()
+                .map(|source| SelectedSource {
+                    source: source.clone(),
+                    scan_root: source.parent_directory.clone(),
+                    max_depth: source.max_depth,
+                })
+                .collect());
+        };
+
+        let requested = Path::new(path);
+        for source in candidates {
+            let parent = Path::new(&source.parent_directory);
+            let scan_root = if requested.is_absolute() {
+                requested.to_path_buf()
+            } else {
+                parent.join(requested)
+            };
+
+            if let Some(depth) = relative_depth(parent, &scan_root) {
+                if !scan_root.is_dir() {
+                    return Err(BackupError::Selection(format!(
+                        "Path '{}' is not a directory",
+                        scan_root.display()
+                    )));
+                }
+
+                return Ok(vec![SelectedSource {
+                    source: source.clone(),
+                    scan_root: scan_root.to_string_lossy().to_string(),
+                    max_depth: source.max_depth.map(|max| max.saturating_sub(depth)),
+                }]);
+            }
+        }
+
+        Err(BackupError::Selection(format!(
+            "Path '{}' is not inside a selected source",
+            path
+        )))
+    }
+}
+
+/// Number of path components between a source parent and a directory inside it
+fn relative_depth(parent: &Path, dir: &Path) -> Option<usize> {
+    let parent = canonical_or_self(parent);
+    let dir = canonical_or_self(dir);
+    dir.strip_prefix(&parent)
+        .ok()
+        .map(|relative| relative.components().count())
+}
+
+fn same_path(a: &Path, b: &Path) -> bool {
+    canonical_or_self(a) == canonical_or_self(b)
+}
+
+fn canonical_or_self(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn source(dir: &TempDir, max_depth: Option<usize>) -> BackupSource {
+        BackupSource {
+            parent_directory: dir.path().to_str().unwrap().to_string(),
+            max_depth,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_empty_selection_keeps_all_sources() {
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+        let sources = vec![source(&first, None), source(&second, Some(3))];
+
+        let selected = RunSelection::default().select_sources(&sources).unwrap();
+
+        assert_eq!(selected.len(), 2);
+        assert_eq!(selected[1].max_depth, Some(3));
+    }
+
+    #[test]
+    fn test_selects_source_by_path() {
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+        let sources = vec![source(&first, None), source(&second, None)];
+
+        let selection = RunSelection {
+            source: Some(second.path().to_str().unwrap().to_string()),
+            ..Default::default()
+        };
+        let selected = selection.select_sources(&sources).unwrap();
+
+        assert_eq!(selected.len(), 1);
+        assert_eq!(
+            selected[0].source.parent_directory,
+            sources[1].parent_directory
+        );
+    }
+
+    #[test]
+    fn test_relative_path_narrows_scan_root_and_depth() {
+        let root = TempDir::new().unwrap();
+        fs::create_dir_all(root.path().join("projects").join("app")).unwrap();
+        let sources = vec![source(&root, Some(5))];
+
+        let selection = RunSelection {
+            source: None,
+            path: Some(format!("projects{}app", std::path::MAIN_SEPARATOR)),
+            ..Default::default()
+        };
+        let selected = selection.select_sources(&sources).unwrap();
+
+        assert_eq!(selected.len(), 1);
+        assert!(selected[0].scan_root.ends_with("app"));
+        assert_eq!(selected[0].max_depth, Some(3));
+    }
+
+    #[test]
+    fn test_rejects_unknown_source_and_outside_path() {
+        let root = TempDir::new().unwrap();
+        let other = TempDir::new().unwrap();
+        let sources = vec![source(&root, None)];
+
+        let unknown = RunSelection {
+            source: Some("/no/such/source".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            unknown.select_sources(&sources),
+            Err(BackupError::Selection(_))
+        ));
+
+        let outside = RunSelection {
+            source: None,
+            path: Some(other.path().to_str().unwrap().to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            outside.select_sources(&sources),
+            Err(BackupError::Selection(_))
+        ));
+    }
+
+    #[test]
+    fn test_checkpoint_selects_critical_paths_in_order() {
+        let root = TempDir::new().unwrap();
+        fs::create_dir_all(root.path().join("etc")).unwrap();
+        fs::create_dir_all(root.path().join("db")).unwrap();
+        let mut config: Config = serde_json::from_str(
+            r#"{"database_file": "", "backup_sources": [], "backup_destinations": []}"#,
+        )
+        .unwrap();
+        config.backup_sources = vec![source(&root, Some(4))];
+        let checkpoint = RunSelection {
+            checkpoint: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            checkpoint.resolve(&config),
+            Err(BackupError::Selection(_))
+        ));
+
+        config.critical_paths = ["db", "etc"]
+            .iter()
+            .map(|dir| root.path().join(dir).to_string_lossy().to_string())
+            .collect();
+        let selected = checkpoint.resolve(&config).unwrap();
+
+        assert_eq!(selected.len(), 2);
+        assert!(selected[0].scan_root.ends_with("db"));
+        assert!(selected[1].scan_root.ends_with("etc"));
+        assert_eq!(selected[1].max_depth, Some(3));
+        assert!(!checkpoint.is_empty());
+        assert!(checkpoint.limits_paths());
+    }
+}
@@ -0,0 +1,95 @@
+use crate::models::backup_row::BackupRow;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// A recorded backup with what a scrub compares it against
+#[derive(Debug)]
+pub struct ScrubCandidate {
+    pub backup: BackupRow,
+    /// Full path of the backup's source file
+    pub source_path: String,
+    pub hash: String,
+    pub file_size: u64,
+    pub source_last_modified: Duration,
+    /// Unix timestamp of when a scrub last found the backup intact
+    pub last_verified: Option<i64>,
+}
+
+/// What one scrub read back and what it found
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScrubReport {
+    pub started_at: i64,
+    pub finished_at: i64,
+    /// Backups recorded under the configured destinations
+    pub backups: u64,
+    /// Backups read back by this scrub
+    pub checked: u64,
+    pub bytes_read: u64,
+    /// Backups passed over because their source changed after they were copied
+    pub skipped: u64,
+    pub problems: Vec<ScrubProblem>,
+}
+
+/// A backup that no longer holds what was copied
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScrubProblem {
+    pub backup: String,
+    pub source: String,
+    pub kind: ScrubProblemKind,
+    pub detail: String,
+    /// Unix timestamp of when a scrub found it
+    pub found_at: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScrubProblemKind {
+    /// Same size and modification time, different contents: silent corruption
+    Corrupt,
+    /// Written to since it was recorded
+    Changed,
+    Missing,
+    Unreadable,
+}
+
+impl ScrubProblemKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScrubProblemKind::Corrupt => "corrupt",
+            ScrubProblemKind::Changed => "changed",
+            ScrubProblemKind::Missing => "missing",
+            ScrubProblemKind::Unreadable => "unreadable",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "corrupt" => Some(ScrubProblemKind::Corrupt),
+            "changed" => Some(ScrubProblemKind::Changed),
+            "missing" => Some(ScrubProblemKind::Missing),
+            "unreadable" => Some(ScrubProblemKind::Unreadable),
+            _ => None,
+        }
+    }
+}
+
+/// What `repair` did with the problems scrubs had found
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RepairReport {
+    pub repaired: Vec<RepairedBackup>,
+    pub unrepaired: Vec<UnrepairedBackup>,
+}
+
+/// A backup copied again from a good copy of its file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RepairedBackup {
+    pub backup: String,
+    /// The source file, or a healthy backup in another destination
+    pub copied_from: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnrepairedBackup {
+    pub backup: String,
+    pub reason: String,
+}
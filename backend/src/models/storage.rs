@@ -5,6 +5,7 @@ pub struct StorageStats {
     pub total_source_files: u64,
     pub total_source_size: u64,
     pub destination_stats: Vec<DestinationStorageStats>,
+    pub content: ContentStats,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -13,3 +14,30 @@ pub struct DestinationStorageStats {
     pub file_count: u64,
     pub total_size: u64,
 }
+
+/// How much of the cataloged source content is unique, by hash
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ContentStats {
+    /// Source files with a hash recorded
+    pub hashed_files: u64,
+    pub hashed_size: u64,
+    pub distinct_hashes: u64,
+    /// Size of one file of each distinct hash, what storing each content once would take
+    pub unique_size: u64,
+}
+
+impl ContentStats {
+    /// Bytes taken by files whose content another file has too
+    pub fn duplicate_size(&self) -> u64 {
+        self.hashed_size.saturating_sub(self.unique_size)
+    }
+
+    /// Bytes stored for every byte of unique content, 1.0 without duplicates
+    pub fn dedup_ratio(&self) -> f64 {
+        if self.unique_size == 0 {
+            1.0
+        } else {
+            self.hashed_size as f64 / self.unique_size as f64
+        }
+    }
+}
@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+/// A catalogued source file found gone from its path, so the sources can be told as they were
+/// at any run rather than as everything ever backed up
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tombstone {
+    pub path: String,
+    /// Hash and size the file was last recorded with
+    pub hash: String,
+    pub file_size: u64,
+    /// Unix timestamp of when the run that found the file gone finished
+    pub deleted_at: i64,
+    pub run_id: String,
+    /// Unix timestamp of when a later run found a file at the path again
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub returned_at: Option<i64>,
+}
@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+
+/// A destination file that matched no source and was moved into the `unclaimed/` area
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnclaimedFile {
+    pub destination: String,
+    pub original_path: String,
+    pub unclaimed_path: String,
+    /// Hash of the file, absent when the run skipped hashing
+    pub hash: Option<String>,
+    pub file_size: u64,
+    /// Unix timestamp of when the file was moved
+    pub moved_at: i64,
+}
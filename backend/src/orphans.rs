@@ -0,0 +1,92 @@
+use crate::models::config::Config;
+use crate::models::orphan::{OrphanAction, OrphanReport};
+use crate::output::OutputFormat;
+use crate::repo::sqlite::format_bytes;
+use crate::service::orphans::{clean_orphans, find_orphans, QUARANTINE_DIR};
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use std::fmt::Write;
+
+/// Which destinations `orphans` scans and what it does with the files it finds
+#[derive(Args)]
+pub struct OrphansArgs {
+    /// Only scan this destination, by name or path
+    #[arg(long = "destination")]
+    destination: Option<String>,
+    /// Move the orphans into each destination's `quarantine/` folder
+    #[arg(long = "quarantine", conflicts_with = "delete")]
+    quarantine: bool,
+    /// Delete the orphans
+    #[arg(long = "delete")]
+    delete: bool,
+}
+
+/// List orphaned destination files, or quarantine or delete them
+pub fn run(args: &OrphansArgs, config: &Config, output: OutputFormat) -> Result<()> {
+    if let Some(destination) = &args.destination {
+        if !config
+            .backup_destinations
+            .iter()
+            .any(|d| d.matches(destination))
+        {
+            bail!("No destination named {} is configured", destination);
+        }
+    }
+
+    let action = match (args.quarantine, args.delete) {
+        (true, _) => OrphanAction::Quarantine,
+        (_, true) => OrphanAction::Delete,
+        _ => {
+            let report = find_orphans(config, args.destination.as_deref())
+                .context("Failed to read the backup catalog")?;
+            return output.print(&report, || describe_orphans(&report));
+        }
+    };
+
+    let cleanup = clean_orphans(config, args.destination.as_deref(), action, "cli")
+        .context("Failed to clean up orphaned files")?;
+    output.print(&cleanup, || match action {
+        OrphanAction::Quarantine => format!(
+            "Moved {} orphaned file(s), {}, into {}/\n",
+            cleanup.files,
+            format_bytes(cleanup.bytes),
+            QUARANTINE_DIR
+        ),
+        OrphanAction::Delete => format!(
+            "Deleted {} orphaned file(s), {}\n",
+            cleanup.files,
+            format_bytes(cleanup.bytes)
+        ),
+    })?;
+    if cleanup.failed > 0 {
+        bail!(
+            "{} orphaned file(s) could not be cleaned up",
+            cleanup.failed
+        );
+    }
+    Ok(())
+}
+
+/// Each destination's orphans with their total size
+fn describe_orphans(report: &OrphanReport) -> String {
+    let mut out = String::new();
+    for destination in &report.destinations {
+        let _ = writeln!(
+            out,
+            "{}: {} orphaned file(s), {}",
+            destination.name,
+            destination.files.len(),
+            format_bytes(destination.bytes)
+        );
+        for file in &destination.files {
+            let _ = writeln!(out, "  {} ({})", file.path, format_bytes(file.file_size));
+        }
+    }
+    let _ = writeln!(
+        out,
+        "{} orphaned file(s), {}",
+        report.files,
+        format_bytes(report.bytes)
+    );
+    out
+}
@@ -0,0 +1,51 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// How CLI commands print their results on standard output; logs always go to standard error
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Text for people to read
+    #[default]
+    Text,
+    /// One line of JSON for each result, for scripts to parse
+    Json,
+}
+
+impl OutputFormat {
+    /// Print `value` as a line of JSON, or the text `describe` gives for it
+    pub fn print<T: Serialize>(self, value: &T, describe: impl FnOnce() -> String) -> Result<()> {
+        print!("{}", self.render(value, describe)?);
+        Ok(())
+    }
+
+    fn render<T: Serialize>(self, value: &T, describe: impl FnOnce() -> String) -> Result<String> {
+        Ok(match self {
+            OutputFormat::Text => describe(),
+            OutputFormat::Json => serde_json::to_string(value)? + "\n",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_output_is_a_line_per_result() {
+        let value = serde_json::json!({"files": 2, "paths": ["a\nb"]});
+
+        assert_eq!(
+            OutputFormat::Json
+                .render(&value, || unreachable!())
+                .unwrap(),
+            "{\"files\":2,\"paths\":[\"a\\nb\"]}\n"
+        );
+        assert_eq!(
+            OutputFormat::Text
+                .render(&value, || "2 file(s)\n".to_string())
+                .unwrap(),
+            "2 file(s)\n"
+        );
+    }
+}
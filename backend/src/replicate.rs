@@ -0,0 +1,75 @@
+use crate::models::config::{BackupDestination, Config};
+use crate::models::replication::ReplicationReport;
+use crate::output::OutputFormat;
+use crate::repo::sqlite::format_bytes;
+use crate::service::replicate::replicate_backups;
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use std::fmt::Write;
+
+/// Which destination `replicate` copies from and which it seeds
+#[derive(Args)]
+pub struct ReplicateArgs {
+    /// Destination holding the backups, by name or path
+    #[arg(long = "from")]
+    from: String,
+    /// Destination to copy them to, by name or path
+    #[arg(long = "to")]
+    to: String,
+}
+
+/// Copy the backups recorded under one destination to another; fails if any couldn't be
+pub fn run(
+    args: &ReplicateArgs,
+    config: &Config,
+    wait_for_lock: bool,
+    output: OutputFormat,
+) -> Result<()> {
+    let from = configured_destination(config, &args.from)?;
+    let to = configured_destination(config, &args.to)?;
+    if from.path() == to.path() {
+        bail!("--from and --to are the same destination");
+    }
+
+    let report = replicate_backups(config, from, to, "cli", wait_for_lock)
+        .context("Failed to replicate backups")?;
+    output.print(&report, || describe_replication(&report))?;
+    if !report.failed.is_empty() {
+        bail!("{} backup(s) could not be replicated", report.failed.len());
+    }
+    Ok(())
+}
+
+fn configured_destination<'a>(
+    config: &'a Config,
+    reference: &str,
+) -> Result<&'a BackupDestination> {
+    match config
+        .backup_destinations
+        .iter()
+        .find(|d| d.matches(reference))
+    {
+        Some(destination) => Ok(destination),
+        None => bail!("No destination named {} is configured", reference),
+    }
+}
+
+fn describe_replication(report: &ReplicationReport) -> String {
+    let mut out = String::new();
+    for failed in &report.failed {
+        let _ = writeln!(
+            out,
+            "not replicated: {}\n  {}",
+            failed.backup, failed.reason
+        );
+    }
+    let _ = writeln!(
+        out,
+        "Copied {} of {} backup(s), {}; {} already there",
+        report.copied,
+        report.backups,
+        format_bytes(report.bytes_copied),
+        report.already_present
+    );
+    out
+}
@@ -0,0 +1,463 @@
+use crate::models::backed_up_file::BackedUpFile;
+use crate::models::backup_row::BackupRow;
+use crate::models::catalog_export::{ExportedSource, ImportSummary};
+use crate::models::error::Result;
+use crate::models::scrub::ScrubCandidate;
+use crate::models::source_row::SourceRow;
+use crate::models::storage::StorageStats;
+use crate::repo::postgres::PostgresCatalog;
+use crate::repo::sqlite::SqliteCatalog;
+use log::info;
+use once_cell::sync::Lazy;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// Where the source files and their backups are recorded
+///
+/// Runs, jobs, logs and the other state of this machine stay in the SQLite `database_file`
+/// whichever store holds the catalog.
+pub trait CatalogStore: Send + Sync {
+    fn select_source(&self, source_file: &str, source_path: &str) -> Result<Option<SourceRow>>;
+
+    fn select_source_by_id(&self, id: i32) -> Result<Option<SourceRow>>;
+
+    /// Source files below `under`, or all of them, by path, each with its number of backups
+    fn select_sources(&self, under: Option<&str>) -> Result<Vec<(SourceRow, u64)>>;
+
+    /// Number of source files whose full path contains `search`, or of all source files
+    fn count_sources_matching(&self, search: Option<&str>) -> Result<u64>;
+
+    /// One page of the source files whose full path contains `search`, ordered by path
+    fn select_sources_matching(
+        &self,
+        search: Option<&str>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<SourceRow>>;
+
+    /// Record a source file, or update the row for its path, and return its ID
+    fn insert_source_row(&self, source_row: &SourceRow) -> Result<i32>;
+
+    fn update_source_last_modified(&self, row_id: i32, last_modified: &Duration) -> Result<()>;
+
+    /// Source files recorded with this hash and size, by path
+    fn select_sources_with_content(&self, hash: &str, file_size: u64) -> Result<Vec<SourceRow>>;
+
+    /// Forget a source file that has no backups left, returning whether it was
+    fn delete_source_row(&self, id: i32) -> Result<bool>;
+
+    /// The backup at this path with the hash of its source file
+    fn select_backed_up_file(&self, filename: &str, filepath: &str)
+        -> Result<Option<BackedUpFile>>;
+
+    fn select_backup_paths(&self, source_id: i32) -> Result<Vec<PathBuf>>;
+
+    /// Backups recorded for a source file, by path
+    fn select_backups_of_source(&self, source_id: i32) -> Result<Vec<BackupRow>>;
+
+    /// Every recorded backup, grouped by source
+    fn select_all_backups(&self) -> Result<Vec<BackupRow>>;
+
+    /// Record a backup, or update the row for its path
+    fn insert_backup_row(&self, backup_row: BackupRow) -> Result<()>;
+
+    /// Forget the backup of a source file at this path
+    fn delete_backup_row(&self, source_id: i32, file_name: &str, file_path: &str) -> Result<()>;
+
+    /// Every recorded backup with its source file, for a scrub or a replication to choose from
+    fn select_scrub_candidates(&self) -> Result<Vec<ScrubCandidate>>;
+
+    /// Record that a scrub found the backup at this path intact at `verified_at`
+    fn update_backup_verified(
+        &self,
+        source_id: i32,
+        file_name: &str,
+        file_path: &str,
+        verified_at: i64,
+    ) -> Result<()>;
+
+    /// Every source file with its backups, by path, for a catalog export
+    fn select_catalog(&self) -> Result<Vec<ExportedSource>>;
+
+    /// Add catalog rows from an export, updating rows for the same paths
+    fn import_catalog(&self, sources: &[ExportedSource]) -> Result<ImportSummary>;
+
+    fn get_storage_overview(&self, destinations: &[String]) -> Result<StorageStats>;
+
+    fn get_total_source_files(&self) -> Result<u64>;
+
+    fn get_total_source_size(&self) -> Result<u64>;
+}
+
+static STORE: Lazy<RwLock<Arc<dyn CatalogStore>>> =
+    Lazy::new(|| RwLock::new(Arc::new(SqliteCatalog)));
+
+/// Keep the catalog in the PostgreSQL database at `database_url`, or in `database_file` when
+/// there is none
+pub fn open_catalog(database_url: Option<&str>) -> Result<()> {
+    let store: Arc<dyn CatalogStore> = match database_url {
+        Some(url) => {
+            let catalog = PostgresCatalog::connect(url)?;
+            info!("Catalog kept in PostgreSQL at {}", catalog.server());
+            Arc::new(catalog)
+        }
+        None => Arc::new(SqliteCatalog),
+    };
+    *STORE.write().unwrap() = store;
+    Ok(())
+}
+
+fn store() -> Arc<dyn CatalogStore> {
+    STORE.read().unwrap().clone()
+}
+
+pub fn select_source(source_file: &str, source_path: &str) -> Result<Option<SourceRow>> {
+    store().select_source(source_file, source_path)
+}
+
+pub fn select_source_by_id(id: i32) -> Result<Option<SourceRow>> {
+    store().select_source_by_id(id)
+}
+
+pub fn select_sources(under: Option<&str>) -> Result<Vec<(SourceRow, u64)>> {
+    store().select_sources(under)
+}
+
+pub fn count_sources_matching(search: Option<&str>) -> Result<u64> {
+    store().count_sources_matching(search)
+}
+
+pub fn select_sources_matching(
+    search: Option<&str>,
+    limit: usize,
+    offset: usize,
+) -> Result<Vec<SourceRow>> {
+    store().select_sources_matching(search, limit, offset)
+}
+
+pub fn insert_source_row(source_row: &SourceRow) -> Result<i32> {
+    store().insert_source_row(source_row)
+}
+
+pub fn update_source_last_modified(row_id: i32, last_modified: &Duration) -> Result<()> {
+    store().update_source_last_modified(row_id, last_modified)
+}
+
+pub fn select_sources_with_content(hash: &str, file_size: u64) -> Result<Vec<SourceRow>> {
+    store().select_sources_with_content(hash, file_size)
+}
+
+pub fn delete_source_row(id: i32) -> Result<bool> {
+    store().delete_source_row(id)
+}
+
+pub fn select_backed_up_file(filename: &str, filepath: &str) -> Result<Option<BackedUpFile>> {
+    store().select_backed_up_file(filename, filepath)
+}
+
+pub fn select_backup_paths(source_id: i32) -> Result<Vec<PathBuf>> {
+    store().select_backup_paths(source_id)
+}
+
+pub fn select_backups_of_source(source_id: i32) -> Result<Vec<BackupRow>> {
+    store().select_backups_of_source(source_id)
+}
+
+pub fn select_all_backups() -> Result<Vec<BackupRow>> {
+    store().select_all_backups()
+}
+
+pub fn insert_backup_row(backup_row: BackupRow) -> Result<()> {
+    store().insert_backup_row(backup_row)
+}
+
+pub fn delete_backup_row(source_id: i32, file_name: &str, file_path: &str) -> Result<()> {
+    store().delete_backup_row(source_id, file_name, file_path)
+}
+
+pub fn select_scrub_candidates() -> Result<Vec<ScrubCandidate>> {
+    store().select_scrub_candidates()
+}
+
+pub fn update_backup_verified(
+    source_id: i32,
+    file_name: &str,
+    file_path: &str,
+    verified_at: i64,
+) -> Result<()> {
+    store().update_backup_verified(source_id, file_name, file_path, verified_at)
+}
+
+pub fn select_catalog() -> Result<Vec<ExportedSource>> {
+    store().select_catalog()
+}
+
+pub fn import_catalog(sources: &[ExportedSource]) -> Result<ImportSummary> {
+    store().import_catalog(sources)
+}
+
+pub fn get_storage_overview(destinations: &[String]) -> Result<StorageStats> {
+    store().get_storage_overview(destinations)
+}
+
+pub fn get_total_source_files() -> Result<u64> {
+    store().get_total_source_files()
+}
+
+pub fn get_total_source_size() -> Result<u64> {
+    store().get_total_source_size()
+}
+
+/// Checks every catalog store must pass, whichever database keeps it
+#[cfg(test)]
+pub(crate) mod contract {
+    use super::*;
+    use crate::models::catalog_export::ExportedBackup;
+
+    /// Exercise each method of `store` on files below `root`, which has none catalogued yet;
+    /// files elsewhere in the catalog are left alone and don't change the outcome
+    pub fn check_catalog_store(store: &dyn CatalogStore, root: &str) {
+        let photos = format!("{}/photos", root);
+        let destination = format!("{}/backup", root);
+        let source = |name: &str, hash: &str, file_size: u64| SourceRow {
+            id: 0,
+            file_name: name.to_string(),
+            file_path: photos.clone(),
+            hash: hash.to_string(),
+            file_size,
+            last_modified: Duration::from_secs(1000),
+        };
+        let backup = |source_id: i32, name: &str| BackupRow {
+            source_id,
+            file_name: name.to_string(),
+            file_path: format!("{}/photos", destination),
+            last_modified: Duration::from_secs(2000),
+            original_path: None,
+            physical_size: None,
+        };
+        let files_before = store.get_total_source_files().unwrap();
+        let size_before = store.get_total_source_size().unwrap();
+        let hash_a = format!("{}-a", root);
+
+        let a = store
+            .insert_source_row(&source("a.jpg", "stale", 1))
+            .unwrap();
+        assert_eq!(
+            store
+                .insert_source_row(&source("a.jpg", &hash_a, 100))
+                .unwrap(),
+            a
+        );
+        let b = store
+            .insert_source_row(&source("b.JPG", &hash_a, 100))
+            .unwrap();
+        let nested = SourceRow {
+            file_path: format!("{}/2024", photos),
+            ..source("c.jpg", "", 50)
+        };
+        let c = store.insert_source_row(&nested).unwrap();
+        store
+            .insert_source_row(&SourceRow {
+                file_path: format!("{}/photos-old", root),
+                ..source("d.jpg", "", 7)
+            })
+            .unwrap();
+        assert_eq!(store.get_total_source_files().unwrap(), files_before + 4);
+        assert_eq!(store.get_total_source_size().unwrap(), size_before + 257);
+
+        let found = store.select_source("a.jpg", &photos).unwrap().unwrap();
+        assert_eq!(
+            (found.id, found.hash.as_str(), found.file_size),
+            (a, hash_a.as_str(), 100)
+        );
+        assert_eq!(found.last_modified, Duration::from_secs(1000));
+        assert!(store
+            .select_source("missing.jpg", &photos)
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            store.select_source_by_id(c).unwrap().unwrap().file_name,
+            "c.jpg"
+        );
+        store
+            .update_source_last_modified(c, &Duration::from_secs(1500))
+            .unwrap();
+        assert_eq!(
+            store.select_source_by_id(c).unwrap().unwrap().last_modified,
+            Duration::from_secs(1500)
+        );
+        let with_content = store.select_sources_with_content(&hash_a, 100).unwrap();
+        assert_eq!(
+            with_content.iter().map(|s| s.id).collect::<Vec<_>>(),
+            vec![a, b]
+        );
+        assert!(store
+            .select_sources_with_content(&hash_a, 99)
+            .unwrap()
+            .is_empty());
+
+        store.insert_backup_row(backup(a, "a.jpg")).unwrap();
+        store
+            .insert_backup_row(BackupRow {
+                original_path: Some(format!("{}/a.jpg", photos)),
+                physical_size: Some(4096),
+                ..backup(a, "a copy.jpg")
+            })
+            .unwrap();
+        // Recording it again keeps what isn't given this time
+        store.insert_backup_row(backup(a, "a copy.jpg")).unwrap();
+        store.insert_backup_row(backup(c, "c.jpg")).unwrap();
+
+        let under: Vec<(String, u64)> = store
+            .select_sources(Some(&photos))
+            .unwrap()
+            .into_iter()
+            .map(|(s, backups)| (s.file_name, backups))
+            .collect();
+        assert_eq!(
+            under,
+            vec![
+                ("a.jpg".to_string(), 2),
+                ("b.JPG".to_string(), 0),
+                ("c.jpg".to_string(), 1)
+            ]
+        );
+        let search = format!("{}/photos/B.jpg", root);
+        assert_eq!(store.count_sources_matching(Some(&search)).unwrap(), 1);
+        assert_eq!(store.count_sources_matching(Some(&photos)).unwrap(), 4);
+        assert!(store.count_sources_matching(None).unwrap() >= 4);
+        // Sorted byte by byte, so photos-old comes before photos/2024
+        let page = store.select_sources_matching(Some(&photos), 2, 1).unwrap();
+        assert_eq!(
+            page.iter()
+                .map(|s| s.file_name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["b.JPG", "d.jpg"]
+        );
+        assert!(store
+            .select_sources_matching(Some(&format!("{}/%", root)), 10, 0)
+            .unwrap()
+            .is_empty());
+
+        let backups = store.select_backups_of_source(a).unwrap();
+        assert_eq!(
+            backups
+                .iter()
+                .map(|b| b.file_name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["a copy.jpg", "a.jpg"]
+        );
+        assert_eq!(backups[0].original_path, Some(format!("{}/a.jpg", photos)));
+        assert_eq!(backups[0].physical_size, Some(4096));
+        assert_eq!(backups[1].last_modified, Duration::from_secs(2000));
+        let mut paths = store.select_backup_paths(a).unwrap();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from(format!("{}/photos/a copy.jpg", destination)),
+                PathBuf::from(format!("{}/photos/a.jpg", destination))
+            ]
+        );
+        let backed_up = store
+            .select_backed_up_file("a.jpg", &format!("{}/photos", destination))
+            .unwrap()
+            .unwrap();
+        assert_eq!(backed_up.last_modified, Duration::from_secs(2000));
+        assert_eq!(
+            store
+                .select_all_backups()
+                .unwrap()
+                .iter()
+                .filter(|b| b.file_path.starts_with(&destination))
+                .count(),
+            3
+        );
+
+        assert!(!store.delete_source_row(c).unwrap());
+        store
+            .delete_backup_row(c, "c.jpg", &format!("{}/photos", destination))
+            .unwrap();
+        assert!(store.delete_source_row(c).unwrap());
+        assert!(store.select_source_by_id(c).unwrap().is_none());
+
+        let mine = |store: &dyn CatalogStore| -> Vec<ScrubCandidate> {
+            store
+                .select_scrub_candidates()
+                .unwrap()
+                .into_iter()
+                .filter(|candidate| candidate.backup.file_path.starts_with(&destination))
+                .collect()
+        };
+        let candidates = mine(store);
+        assert_eq!(candidates.len(), 2);
+        assert!(candidates
+            .iter()
+            .all(|candidate| candidate.last_verified.is_none()));
+        assert_eq!(candidates[0].source_path, format!("{}/a.jpg", photos));
+        assert_eq!(candidates[0].hash, hash_a);
+        store
+            .update_backup_verified(a, "a.jpg", &format!("{}/photos", destination), 3000)
+            .unwrap();
+        let verified: Vec<Option<i64>> = mine(store)
+            .iter()
+            .filter(|candidate| candidate.backup.file_name == "a.jpg")
+            .map(|candidate| candidate.last_verified)
+            .collect();
+        assert_eq!(verified, vec![Some(3000)]);
+
+        let overview = store
+            .get_storage_overview(std::slice::from_ref(&destination))
+            .unwrap();
+        assert_eq!(overview.total_source_files, files_before + 3);
+        assert_eq!(overview.destination_stats[0].file_count, 1);
+        assert_eq!(overview.destination_stats[0].total_size, 200);
+
+        let imported = format!("{}/imported", root);
+        let exported = ExportedSource {
+            file_name: "e.jpg".to_string(),
+            file_path: imported.clone(),
+            hash: format!("{}-e", root),
+            file_size: 10,
+            last_modified: 500,
+            backups: vec![ExportedBackup {
+                file_name: "e.jpg".to_string(),
+                file_path: format!("{}/imported", destination),
+                last_modified: 600,
+            }],
+        };
+        let summary = store
+            .import_catalog(std::slice::from_ref(&exported))
+            .unwrap();
+        assert_eq!((summary.sources, summary.backups), (1, 1));
+        let catalog = store.select_catalog().unwrap();
+        assert!(catalog.contains(&exported));
+        let a_exported = catalog
+            .iter()
+            .find(|s| s.file_path == photos && s.file_name == "a.jpg")
+            .unwrap();
+        assert_eq!(a_exported.backups.len(), 2);
+        assert!(catalog
+            .iter()
+            .any(|s| s.file_path == photos && s.file_name == "b.JPG" && s.backups.is_empty()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::contract::check_catalog_store;
+    use super::*;
+    use crate::repo::sqlite::{set_db_pool, setup_database};
+    use serial_test::serial;
+    use tempfile::TempDir;
+
+    #[test]
+    #[serial]
+    fn test_sqlite_catalog_store() {
+        let database = TempDir::new().unwrap();
+        set_db_pool(database.path().join("backup.db").to_str().unwrap()).unwrap();
+        setup_database().unwrap();
+        check_catalog_store(&SqliteCatalog, "/contract");
+    }
+}
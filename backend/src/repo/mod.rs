@@ -1 +1,3 @@
+pub mod catalog;
+pub mod postgres;
 pub mod sqlite;
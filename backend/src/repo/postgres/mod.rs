@@ -0,0 +1,830 @@
+use crate::models::backed_up_file::BackedUpFile;
+use crate::models::backup_row::BackupRow;
+use crate::models::catalog_export::{ExportedBackup, ExportedSource, ImportSummary};
+use crate::models::error::{BackupError, Result};
+use crate::models::scrub::ScrubCandidate;
+use crate::models::source_row::SourceRow;
+use crate::models::storage::{ContentStats, DestinationStorageStats, StorageStats};
+use crate::repo::catalog::CatalogStore;
+use crate::repo::sqlite::{contains_pattern, nested_under};
+use log::debug;
+use native_tls::{Certificate, TlsConnector};
+use postgres::config::Host;
+use postgres::{Client, Config, GenericClient, Row};
+use postgres_native_tls::MakeTlsConnector;
+use rocket::tokio::runtime::Handle;
+use std::fs;
+use std::path::{PathBuf, MAIN_SEPARATOR};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+type PgResult<T> = std::result::Result<T, postgres::Error>;
+
+/// Connections kept open between queries
+const MAX_IDLE_CONNECTIONS: usize = 8;
+
+const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The catalog tables, as in SQLite but with 64-bit sizes and times
+const CATALOG_TABLES: &str = "
+    CREATE TABLE IF NOT EXISTS Source_Files(
+        ID            serial  primary key,
+        File_Name     TEXT    not null,
+        File_Path     TEXT    not null,
+        Hash          TEXT,
+        File_Size     bigint,
+        Last_Modified bigint,
+        constraint Source_Files_File_Key
+            unique (File_Name, File_Path));
+
+    CREATE INDEX IF NOT EXISTS Source_Files_File_Name_index
+            on Source_Files (File_Name);
+
+    CREATE INDEX IF NOT EXISTS Source_Files_Hash_index
+            on Source_Files (Hash);
+
+    CREATE TABLE IF NOT EXISTS Backup_Files(
+        ID            serial  primary key,
+        Source_ID     integer not null
+            constraint Backup_Files_Source_Files_ID_fk
+                references Source_Files,
+        File_Name     TEXT    not null,
+        File_Path     TEXT    not null,
+        Last_Modified bigint,
+        Original_Path TEXT,
+        Physical_Size bigint,
+        Last_Verified bigint,
+        constraint Backup_Files_pk
+            unique (File_Name, File_Path));
+
+    CREATE INDEX IF NOT EXISTS Backup_Files_Source_ID_index
+            on Backup_Files (Source_ID);
+
+    ALTER TABLE Backup_Files ADD COLUMN IF NOT EXISTS Original_Path TEXT;
+    ALTER TABLE Backup_Files ADD COLUMN IF NOT EXISTS Physical_Size bigint;
+    ALTER TABLE Backup_Files ADD COLUMN IF NOT EXISTS Last_Verified bigint;
+";
+
+const SOURCE_COLUMNS: &str = "sf.ID, sf.File_Name, sf.File_Path, COALESCE(sf.Hash, ''),
+    COALESCE(sf.File_Size, 0), COALESCE(sf.Last_Modified, 0)";
+
+/// Paths sorted byte by byte, as SQLite sorts them, whatever the database's collation
+const BY_SOURCE_PATH: &str = "sf.File_Path COLLATE \"C\", sf.File_Name COLLATE \"C\"";
+
+/// A catalog shared by several machines in a PostgreSQL database
+pub struct PostgresCatalog {
+    config: Config,
+    tls: MakeTlsConnector,
+    idle: Mutex<Vec<Client>>,
+}
+
+impl PostgresCatalog {
+    /// Connect to the database at `url` and create the catalog tables it doesn't have yet
+    ///
+    /// TLS is used whenever the server offers it, and required with `sslmode=require`. The
+    /// server's certificate is checked against the system's roots, or the one in the file
+    /// `PGSSLROOTCERT` names.
+    pub fn connect(url: &str) -> Result<Self> {
+        let config = parse_url(url).map_err(|reason| BackupError::PostgresConnection {
+            server: "catalog_database_url".to_string(),
+            reason: format!("the URL {}", reason),
+        })?;
+        let mut catalog = PostgresCatalog {
+            config,
+            tls: MakeTlsConnector::new(TlsConnector::new().map_err(|e| tls_error(url, e))?),
+            idle: Mutex::new(Vec::new()),
+        };
+        if let Ok(root) = std::env::var("PGSSLROOTCERT") {
+            let certificate = fs::read(&root)
+                .map_err(|e| e.to_string())
+                .and_then(|pem| Certificate::from_pem(&pem).map_err(|e| e.to_string()))
+                .map_err(|reason| BackupError::PostgresConnection {
+                    server: catalog.server(),
+                    reason: format!("PGSSLROOTCERT {}: {}", root, reason),
+                })?;
+            let connector = TlsConnector::builder()
+                .add_root_certificate(certificate)
+                .build()
+                .map_err(|e| tls_error(&catalog.server(), e))?;
+            catalog.tls = MakeTlsConnector::new(connector);
+        }
+        catalog.with_connection("create catalog tables", |conn| {
+            conn.batch_execute(CATALOG_TABLES)
+        })?;
+        Ok(catalog)
+    }
+
+    /// `host:port/database` of the server
+    pub fn server(&self) -> String {
+        let host = match self.config.get_hosts().first() {
+            Some(Host::Tcp(host)) => host.clone(),
+            #[cfg(unix)]
+            Some(Host::Unix(path)) => path.to_string_lossy().to_string(),
+            None => "localhost".to_string(),
+        };
+        format!(
+            "{}:{}/{}",
+            host,
+            self.config.get_ports().first().copied().unwrap_or(5432),
+            self.config
+                .get_dbname()
+                .or(self.config.get_user())
+                .unwrap_or_default()
+        )
+    }
+
+    fn open(&self) -> Result<Client> {
+        self.config
+            .connect(self.tls.clone())
+            .map_err(|e| BackupError::PostgresConnection {
+                server: self.server(),
+                reason: describe(&e),
+            })
+    }
+
+    /// Run `work` on an idle connection, or on a new one when there is none or the idle one
+    /// turns out to be closed
+    ///
+    /// The client drives its connection on a runtime of its own, which can't run on a thread of
+    /// another runtime, such as the web server's, so from there the work is done on a thread of
+    /// its own.
+    fn with_connection<T: Send>(
+        &self,
+        operation: &str,
+        work: impl FnMut(&mut Client) -> PgResult<T> + Send,
+    ) -> Result<T> {
+        if Handle::try_current().is_err() {
+            return self.on_connection(operation, work);
+        }
+        thread::scope(|scope| {
+            scope
+                .spawn(|| self.on_connection(operation, work))
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+        })
+    }
+
+    fn on_connection<T>(
+        &self,
+        operation: &str,
+        mut work: impl FnMut(&mut Client) -> PgResult<T>,
+    ) -> Result<T> {
+        let idle = self.idle.lock().unwrap().pop();
+        let reused = idle.is_some();
+        let mut conn = match idle {
+            Some(conn) => conn,
+            None => self.open()?,
+        };
+        let mut result = work(&mut conn);
+        if reused && result.is_err() && conn.is_closed() {
+            debug!(
+                "Reconnecting to the PostgreSQL catalog after: {}",
+                operation
+            );
+            conn = self.open()?;
+            result = work(&mut conn);
+        }
+
+        if !conn.is_closed() {
+            let mut idle = self.idle.lock().unwrap();
+            if idle.len() < MAX_IDLE_CONNECTIONS {
+                idle.push(conn);
+            }
+        }
+        result.map_err(|e| BackupError::PostgresQuery {
+            operation: operation.to_string(),
+            reason: describe(&e),
+        })
+    }
+}
+
+impl Drop for PostgresCatalog {
+    /// Closing a connection waits on its runtime too, so a catalog replaced on a runtime's thread
+    /// closes its connections on another
+    fn drop(&mut self) {
+        let idle = std::mem::take(self.idle.get_mut().unwrap());
+        if !idle.is_empty() && Handle::try_current().is_ok() {
+            thread::spawn(move || drop(idle));
+        }
+    }
+}
+
+/// The connection settings in `url`, with the password taken from `PGPASSWORD` when the URL has
+/// none
+pub fn parse_url(url: &str) -> std::result::Result<Config, String> {
+    let mut config = Config::from_str(url).map_err(|e| format!("is invalid: {}", e))?;
+    if config.get_user().is_none_or(str::is_empty) {
+        return Err("names no user".to_string());
+    }
+    if config.get_password().is_none() {
+        if let Ok(password) = std::env::var("PGPASSWORD") {
+            config.password(password);
+        }
+    }
+    if config.get_connect_timeout().is_none() {
+        config.connect_timeout(DEFAULT_CONNECT_TIMEOUT);
+    }
+    Ok(config)
+}
+
+/// The error with its cause, which holds the server's message or why the handshake failed
+fn describe(e: &postgres::Error) -> String {
+    match std::error::Error::source(e) {
+        Some(cause) => format!("{}: {}", e, cause),
+        None => e.to_string(),
+    }
+}
+
+fn tls_error(server: &str, e: native_tls::Error) -> BackupError {
+    BackupError::PostgresConnection {
+        server: server.to_string(),
+        reason: format!("TLS isn't available: {}", e),
+    }
+}
+
+/// A `bigint` column, which holds sizes, times and counts that are never negative
+fn get_u64(row: &Row, column: usize) -> PgResult<u64> {
+    Ok(row.try_get::<_, i64>(column)? as u64)
+}
+
+fn get_opt_u64(row: &Row, column: usize) -> PgResult<Option<u64>> {
+    Ok(row
+        .try_get::<_, Option<i64>>(column)?
+        .map(|value| value as u64))
+}
+
+fn source_from_row(row: &Row) -> PgResult<SourceRow> {
+    Ok(SourceRow {
+        id: row.try_get(0)?,
+        file_name: row.try_get(1)?,
+        file_path: row.try_get(2)?,
+        hash: row.try_get(3)?,
+        file_size: get_u64(row, 4)?,
+        last_modified: Duration::from_secs(get_u64(row, 5)?),
+    })
+}
+
+fn backup_from_row(row: &Row) -> PgResult<BackupRow> {
+    Ok(BackupRow {
+        source_id: row.try_get(0)?,
+        file_name: row.try_get(1)?,
+        file_path: row.try_get(2)?,
+        last_modified: Duration::from_secs(get_opt_u64(row, 3)?.unwrap_or(0)),
+        original_path: row.try_get(4)?,
+        physical_size: get_opt_u64(row, 5)?,
+    })
+}
+
+fn upsert_source(
+    conn: &mut impl GenericClient,
+    file_name: &str,
+    file_path: &str,
+    hash: &str,
+    file_size: u64,
+    last_modified: u64,
+) -> PgResult<i32> {
+    conn.query_one(
+        "INSERT INTO Source_Files (File_Name, File_Path, Hash, File_Size, Last_Modified)
+         VALUES ($1, $2, $3, $4, $5)
+         ON CONFLICT (File_Name, File_Path) DO UPDATE SET
+             Hash = excluded.Hash,
+             File_Size = excluded.File_Size,
+             Last_Modified = excluded.Last_Modified
+         RETURNING ID",
+        &[
+            &file_name,
+            &file_path,
+            &hash,
+            &(file_size as i64),
+            &(last_modified as i64),
+        ],
+    )?
+    .try_get(0)
+}
+
+fn upsert_backup(
+    conn: &mut impl GenericClient,
+    source_id: i32,
+    file_name: &str,
+    file_path: &str,
+    last_modified: u64,
+    original_path: Option<&str>,
+    physical_size: Option<u64>,
+) -> PgResult<u64> {
+    conn.execute(
+        "INSERT INTO Backup_Files
+                (Source_ID, File_Name, File_Path, Last_Modified, Original_Path, Physical_Size)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (File_Name, File_Path) DO UPDATE SET
+            Source_ID = excluded.Source_ID,
+            Last_Modified = excluded.Last_Modified,
+            Original_Path = COALESCE(excluded.Original_Path, Backup_Files.Original_Path),
+            Physical_Size = COALESCE(excluded.Physical_Size, Backup_Files.Physical_Size)",
+        &[
+            &source_id,
+            &file_name,
+            &file_path,
+            &(last_modified as i64),
+            &original_path,
+            &physical_size.map(|size| size as i64),
+        ],
+    )
+}
+
+impl CatalogStore for PostgresCatalog {
+    fn select_source(&self, source_file: &str, source_path: &str) -> Result<Option<SourceRow>> {
+        let operation = format!(
+            "select source {}{}{}",
+            source_path, MAIN_SEPARATOR, source_file
+        );
+        self.with_connection(&operation, |conn| {
+            conn.query_opt(
+                &format!(
+                    "SELECT {} FROM Source_Files sf WHERE sf.File_Name = $1 AND sf.File_Path = $2",
+                    SOURCE_COLUMNS
+                ),
+                &[&source_file, &source_path],
+            )?
+            .as_ref()
+            .map(source_from_row)
+            .transpose()
+        })
+    }
+
+    fn select_source_by_id(&self, id: i32) -> Result<Option<SourceRow>> {
+        self.with_connection("select source", |conn| {
+            conn.query_opt(
+                &format!(
+                    "SELECT {} FROM Source_Files sf WHERE sf.ID = $1",
+                    SOURCE_COLUMNS
+                ),
+                &[&id],
+            )?
+            .as_ref()
+            .map(source_from_row)
+            .transpose()
+        })
+    }
+
+    fn select_sources(&self, under: Option<&str>) -> Result<Vec<(SourceRow, u64)>> {
+        let nested = under.map(nested_under);
+        let dir = nested.as_ref().map(|(dir, _)| dir.as_str());
+        let pattern = nested.as_ref().map(|(_, pattern)| pattern.as_str());
+        self.with_connection("select sources", |conn| {
+            conn.query(
+                &format!(
+                    "SELECT {}, COUNT(bf.ID)
+                        FROM Source_Files sf
+                        LEFT JOIN Backup_Files bf ON bf.Source_ID = sf.ID
+                        WHERE $1::text IS NULL OR sf.File_Path = $1
+                            OR left(sf.File_Path, length($2::text)) = $2
+                        GROUP BY sf.ID
+                        ORDER BY {}",
+                    SOURCE_COLUMNS, BY_SOURCE_PATH
+                ),
+                &[&dir, &pattern],
+            )?
+            .iter()
+            .map(|row| Ok((source_from_row(row)?, get_u64(row, 6)?)))
+            .collect()
+        })
+    }
+
+    fn count_sources_matching(&self, search: Option<&str>) -> Result<u64> {
+        let pattern = search.map(contains_pattern);
+        let separator = MAIN_SEPARATOR.to_string();
+        self.with_connection("count sources", |conn| {
+            get_u64(
+                &conn.query_one(
+                    "SELECT COUNT(*) FROM Source_Files
+                        WHERE $1::text IS NULL OR File_Path || $2::text || File_Name ILIKE $1",
+                    &[&pattern, &separator],
+                )?,
+                0,
+            )
+        })
+    }
+
+    fn select_sources_matching(
+        &self,
+        search: Option<&str>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<SourceRow>> {
+        let pattern = search.map(contains_pattern);
+        let separator = MAIN_SEPARATOR.to_string();
+        self.with_connection("search sources", |conn| {
+            conn.query(
+                &format!(
+                    "SELECT {}
+                        FROM Source_Files sf
+                        WHERE $1::text IS NULL
+                            OR sf.File_Path || $2::text || sf.File_Name ILIKE $1
+                        ORDER BY {}
+                        LIMIT $3 OFFSET $4",
+                    SOURCE_COLUMNS, BY_SOURCE_PATH
+                ),
+                &[&pattern, &separator, &(limit as i64), &(offset as i64)],
+            )?
+            .iter()
+            .map(source_from_row)
+            .collect()
+        })
+    }
+
+    fn insert_source_row(&self, source_row: &SourceRow) -> Result<i32> {
+        debug!(
+            "Inserting source record: {}/{}",
+            source_row.file_path, source_row.file_name
+        );
+        let operation = format!(
+            "insert source {}/{}",
+            source_row.file_path, source_row.file_name
+        );
+        self.with_connection(&operation, |conn| {
+            upsert_source(
+                conn,
+                &source_row.file_name,
+                &source_row.file_path,
+                &source_row.hash,
+                source_row.file_size,
+                source_row.last_modified.as_secs(),
+            )
+        })
+    }
+
+    fn update_source_last_modified(&self, row_id: i32, last_modified: &Duration) -> Result<()> {
+        self.with_connection(&format!("update source {}", row_id), |conn| {
+            conn.execute(
+                "UPDATE Source_Files SET Last_Modified = $1 WHERE ID = $2",
+                &[&(last_modified.as_secs() as i64), &row_id],
+            )
+        })?;
+        Ok(())
+    }
+
+    fn select_sources_with_content(&self, hash: &str, file_size: u64) -> Result<Vec<SourceRow>> {
+        let operation = format!("select sources with hash {}", hash);
+        self.with_connection(&operation, |conn| {
+            conn.query(
+                &format!(
+                    "SELECT {} FROM Source_Files sf
+                        WHERE sf.Hash = $1 AND sf.File_Size = $2
+                        ORDER BY {}",
+                    SOURCE_COLUMNS, BY_SOURCE_PATH
+                ),
+                &[&hash, &(file_size as i64)],
+            )?
+            .iter()
+            .map(source_from_row)
+            .collect()
+        })
+    }
+
+    fn delete_source_row(&self, id: i32) -> Result<bool> {
+        let deleted = self.with_connection(&format!("delete source {}", id), |conn| {
+            conn.execute(
+                "DELETE FROM Source_Files sf
+                    WHERE sf.ID = $1
+                        AND NOT EXISTS (SELECT 1 FROM Backup_Files bf WHERE bf.Source_ID = sf.ID)",
+                &[&id],
+            )
+        })?;
+        Ok(deleted > 0)
+    }
+
+    fn select_backed_up_file(
+        &self,
+        filename: &str,
+        filepath: &str,
+    ) -> Result<Option<BackedUpFile>> {
+        let operation = format!("select backup {}{}{}", filepath, MAIN_SEPARATOR, filename);
+        self.with_connection(&operation, |conn| {
+            conn.query_opt(
+                "SELECT File_Name, File_Path, COALESCE(Last_Modified, 0)
+                    FROM Backup_Files
+                    WHERE File_Name = $1 AND File_Path = $2",
+                &[&filename, &filepath],
+            )?
+            .map(|row| {
+                Ok(BackedUpFile {
+                    file_name: row.try_get(0)?,
+                    file_path: row.try_get(1)?,
+                    last_modified: Duration::from_secs(get_u64(&row, 2)?),
+                })
+            })
+            .transpose()
+        })
+    }
+
+    fn select_backup_paths(&self, source_id: i32) -> Result<Vec<PathBuf>> {
+        let operation = format!("select backups of source {}", source_id);
+        self.with_connection(&operation, |conn| {
+            conn.query(
+                "SELECT File_Path, File_Name FROM Backup_Files WHERE Source_ID = $1",
+                &[&source_id],
+            )?
+            .iter()
+            .map(|row| {
+                Ok(PathBuf::from(row.try_get::<_, String>(0)?).join(row.try_get::<_, String>(1)?))
+            })
+            .collect()
+        })
+    }
+
+    fn select_backups_of_source(&self, source_id: i32) -> Result<Vec<BackupRow>> {
+        self.with_connection("select backups", |conn| {
+            conn.query(
+                "SELECT Source_ID, File_Name, File_Path, Last_Modified, Original_Path, Physical_Size
+                    FROM Backup_Files
+                    WHERE Source_ID = $1
+                    ORDER BY File_Path COLLATE \"C\", File_Name COLLATE \"C\"",
+                &[&source_id],
+            )?
+            .iter()
+            .map(backup_from_row)
+            .collect()
+        })
+    }
+
+    fn select_all_backups(&self) -> Result<Vec<BackupRow>> {
+        self.with_connection("select all backups", |conn| {
+            conn.query(
+                "SELECT Source_ID, File_Name, File_Path, Last_Modified, Original_Path, Physical_Size
+                    FROM Backup_Files
+                    ORDER BY Source_ID, File_Path COLLATE \"C\", File_Name COLLATE \"C\"",
+                &[],
+            )?
+            .iter()
+            .map(backup_from_row)
+            .collect()
+        })
+    }
+
+    fn insert_backup_row(&self, backup_row: BackupRow) -> Result<()> {
+        let operation = format!("insert backup {}", backup_row.file_name);
+        self.with_connection(&operation, |conn| {
+            upsert_backup(
+                conn,
+                backup_row.source_id,
+                &backup_row.file_name,
+                &backup_row.file_path,
+                backup_row.last_modified.as_secs(),
+                backup_row.original_path.as_deref(),
+                backup_row.physical_size,
+            )
+        })?;
+        debug!("Inserted backup record: {}", backup_row.file_name);
+        Ok(())
+    }
+
+    fn delete_backup_row(&self, source_id: i32, file_name: &str, file_path: &str) -> Result<()> {
+        let operation = format!("delete backup {}{}{}", file_path, MAIN_SEPARATOR, file_name);
+        self.with_connection(&operation, |conn| {
+            conn.execute(
+                "DELETE FROM Backup_Files
+                    WHERE Source_ID = $1 AND File_Name = $2 AND File_Path = $3",
+                &[&source_id, &file_name, &file_path],
+            )
+        })?;
+        Ok(())
+    }
+
+    fn select_scrub_candidates(&self) -> Result<Vec<ScrubCandidate>> {
+        self.with_connection("select backups to scrub", |conn| {
+            conn.query(
+                "SELECT b.Source_ID, b.File_Name, b.File_Path, b.Last_Modified, b.Original_Path,
+                        b.Physical_Size, sf.File_Path, sf.File_Name, sf.Hash,
+                        COALESCE(sf.File_Size, 0), COALESCE(sf.Last_Modified, 0), b.Last_Verified
+                    FROM Backup_Files b
+                    JOIN Source_Files sf ON sf.ID = b.Source_ID
+                    WHERE sf.Hash IS NOT NULL",
+                &[],
+            )?
+            .iter()
+            .map(|row| {
+                Ok(ScrubCandidate {
+                    backup: backup_from_row(row)?,
+                    source_path: PathBuf::from(row.try_get::<_, String>(6)?)
+                        .join(row.try_get::<_, String>(7)?)
+                        .to_string_lossy()
+                        .to_string(),
+                    hash: row.try_get(8)?,
+                    file_size: get_u64(row, 9)?,
+                    source_last_modified: Duration::from_secs(get_u64(row, 10)?),
+                    last_verified: row.try_get(11)?,
+                })
+            })
+            .collect()
+        })
+    }
+
+    fn update_backup_verified(
+        &self,
+        _source_id: i32,
+        file_name: &str,
+        file_path: &str,
+        verified_at: i64,
+    ) -> Result<()> {
+        let operation = format!(
+            "record scrub of {}{}{}",
+            file_path, MAIN_SEPARATOR, file_name
+        );
+        self.with_connection(&operation, |conn| {
+            conn.execute(
+                "UPDATE Backup_Files SET Last_Verified = $1 WHERE File_Name = $2 AND File_Path = $3",
+                &[&verified_at, &file_name, &file_path],
+            )
+        })?;
+        Ok(())
+    }
+
+    fn select_catalog(&self) -> Result<Vec<ExportedSource>> {
+        self.with_connection("select catalog", |conn| {
+            let rows = conn.query(
+                &format!(
+                    "SELECT {}, b.File_Name, b.File_Path, COALESCE(b.Last_Modified, 0)
+                        FROM Source_Files sf
+                        LEFT JOIN Backup_Files b ON b.Source_ID = sf.ID
+                        ORDER BY {}, sf.ID,
+                            b.File_Path COLLATE \"C\", b.File_Name COLLATE \"C\"",
+                    SOURCE_COLUMNS, BY_SOURCE_PATH
+                ),
+                &[],
+            )?;
+
+            let mut sources: Vec<(i32, ExportedSource)> = Vec::new();
+            for row in &rows {
+                let source = source_from_row(row)?;
+                if sources.last().map(|(id, _)| *id) != Some(source.id) {
+                    sources.push((
+                        source.id,
+                        ExportedSource {
+                            file_name: source.file_name,
+                            file_path: source.file_path,
+                            hash: source.hash,
+                            file_size: source.file_size,
+                            last_modified: source.last_modified.as_secs(),
+                            backups: Vec::new(),
+                        },
+                    ));
+                }
+                if let Some(file_path) = row.try_get(7)? {
+                    let backup = ExportedBackup {
+                        file_name: row.try_get(6)?,
+                        file_path,
+                        last_modified: get_u64(row, 8)?,
+                    };
+                    sources.last_mut().unwrap().1.backups.push(backup);
+                }
+            }
+            Ok(sources.into_iter().map(|(_, source)| source).collect())
+        })
+    }
+
+    fn import_catalog(&self, sources: &[ExportedSource]) -> Result<ImportSummary> {
+        self.with_connection("import catalog", |conn| {
+            let mut transaction = conn.transaction()?;
+            let mut summary = ImportSummary::default();
+            for source in sources {
+                let source_id = upsert_source(
+                    &mut transaction,
+                    &source.file_name,
+                    &source.file_path,
+                    &source.hash,
+                    source.file_size,
+                    source.last_modified,
+                )?;
+                summary.sources += 1;
+                for backup in &source.backups {
+                    upsert_backup(
+                        &mut transaction,
+                        source_id,
+                        &backup.file_name,
+                        &backup.file_path,
+                        backup.last_modified,
+                        None,
+                        None,
+                    )?;
+                    summary.backups += 1;
+                }
+            }
+            transaction.commit()?;
+            Ok(summary)
+        })
+    }
+
+    fn get_storage_overview(&self, destinations: &[String]) -> Result<StorageStats> {
+        let (total_source_files, total_source_size) =
+            self.with_connection("get total source stats", |conn| {
+                let row = conn.query_one(
+                    "SELECT COUNT(*), COALESCE(SUM(File_Size), 0)::bigint FROM Source_Files",
+                    &[],
+                )?;
+                Ok((get_u64(&row, 0)?, get_u64(&row, 1)?))
+            })?;
+        let content = self.with_connection("get content stats", |conn| {
+            let row = conn.query_one(
+                "SELECT COALESCE(SUM(Files), 0)::bigint, COALESCE(SUM(Total), 0)::bigint,
+                        COUNT(*), COALESCE(SUM(Size), 0)::bigint
+                 FROM (SELECT COUNT(*) AS Files, SUM(File_Size) AS Total, MAX(File_Size) AS Size
+                       FROM Source_Files
+                       WHERE Hash IS NOT NULL AND Hash != ''
+                       GROUP BY Hash) AS Contents",
+                &[],
+            )?;
+            Ok(ContentStats {
+                hashed_files: get_u64(&row, 0)?,
+                hashed_size: get_u64(&row, 1)?,
+                distinct_hashes: get_u64(&row, 2)?,
+                unique_size: get_u64(&row, 3)?,
+            })
+        })?;
+
+        let mut destination_stats = Vec::new();
+        for dest in destinations {
+            let (file_count, total_size) =
+                self.with_connection(&format!("get storage stats for {}", dest), |conn| {
+                    let row = conn.query_one(
+                        "SELECT COUNT(DISTINCT bf.Source_ID), COALESCE(SUM(sf.File_Size), 0)::bigint
+                         FROM Backup_Files bf
+                         INNER JOIN Source_Files sf ON bf.Source_ID = sf.ID
+                         WHERE bf.File_Path LIKE $1::text || '%'",
+                        &[dest],
+                    )?;
+                    Ok((get_u64(&row, 0)?, get_u64(&row, 1)?))
+                })?;
+            destination_stats.push(DestinationStorageStats {
+                destination_root: dest.clone(),
+                file_count,
+                total_size,
+            });
+        }
+
+        Ok(StorageStats {
+            total_source_files,
+            total_source_size,
+            destination_stats,
+            content,
+        })
+    }
+
+    fn get_total_source_files(&self) -> Result<u64> {
+        self.with_connection("get_total_source_files", |conn| {
+            get_u64(
+                &conn.query_one("SELECT COUNT(*) FROM Source_Files", &[])?,
+                0,
+            )
+        })
+    }
+
+    fn get_total_source_size(&self) -> Result<u64> {
+        self.with_connection("get_total_source_size", |conn| {
+            get_u64(
+                &conn.query_one(
+                    "SELECT COALESCE(SUM(File_Size), 0)::bigint FROM Source_Files",
+                    &[],
+                )?,
+                0,
+            )
+        })
+    }
+}
+
+/// Run with `--features postgres-tests` and `RHB_TEST_POSTGRES_URL` naming a database the tests
+/// may write to
+#[cfg(all(test, feature = "postgres-tests"))]
+mod tests {
+    use super::*;
+    use crate::repo::catalog::contract::check_catalog_store;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn connect() -> PostgresCatalog {
+        let url = std::env::var("RHB_TEST_POSTGRES_URL")
+            .expect("RHB_TEST_POSTGRES_URL must name the database to test against");
+        PostgresCatalog::connect(&url).unwrap()
+    }
+
+    /// A root no earlier run has catalogued files under
+    fn fresh_root() -> String {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        format!("/contract-{}", now.as_nanos())
+    }
+
+    #[test]
+    fn test_postgres_catalog_store() {
+        check_catalog_store(&connect(), &fresh_root());
+    }
+
+    #[test]
+    fn test_postgres_catalog_store_on_a_runtime_thread() {
+        let runtime = rocket::tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let catalog = connect();
+            check_catalog_store(&catalog, &fresh_root());
+            drop(catalog);
+        });
+    }
+}
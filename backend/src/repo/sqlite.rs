@@ -1,21 +1,116 @@
+use crate::models::active_run::ActiveRun;
+use crate::models::api::{BackupProgress, BackupStatus};
+use crate::models::audit_event::AuditEvent;
 use crate::models::backed_up_file::BackedUpFile;
+use crate::models::backup_marker::DestinationMismatch;
 use crate::models::backup_row::BackupRow;
+use crate::models::catalog_export::{ExportedBackup, ExportedSource, ImportSummary};
+use crate::models::catalog_write::CatalogWrite;
+use crate::models::config::{CatalogSharding, HashLimit};
+use crate::models::dry_run_mode::DryRunMode;
 use crate::models::error::{BackupError, Result};
+use crate::models::job::{Job, JobDefinition, JobResult};
 use crate::models::log_row::LogRow;
+use crate::models::post_process_result::{PostProcessResult, ProcessorStatus};
+use crate::models::prune_plan::{PlanStatus, PlannedFile, PrunePlan};
+use crate::models::run_diff::{RunDiff, RunFileAction};
+use crate::models::run_record::{RunRecord, RunTiming};
+use crate::models::run_report::{FileOutcome, FileStatus};
+use crate::models::run_selection::RunSelection;
+use crate::models::scrub::{ScrubCandidate, ScrubProblem, ScrubProblemKind};
 use crate::models::source_row::SourceRow;
-use crate::models::storage::{DestinationStorageStats, StorageStats};
-use log::{debug, info};
+use crate::models::storage::{ContentStats, DestinationStorageStats, StorageStats};
+use crate::models::tombstone::Tombstone;
+use crate::models::unclaimed_file::UnclaimedFile;
+use crate::models::user::{Role, User};
+use crate::repo::catalog::CatalogStore;
+use log::{debug, info, warn};
 use once_cell::sync::Lazy;
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::{Error, OptionalExtension};
+use rusqlite::{named_params, Error, OptionalExtension};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
 use std::sync::{Arc, RwLock};
 use std::time::Duration;
 
 type DbPool = Pool<SqliteConnectionManager>;
+type PooledConnection = r2d2::PooledConnection<SqliteConnectionManager>;
+
+/// Layout of `Source_Files` and `Backup_Files` as written to catalog exports; raise it when
+/// those tables change in a way older imports can't handle
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Source IDs a catalog shard hands out start at its number times this, so an ID names its shard;
+/// an insert given an ID past its shard's span is refused
+const SHARD_ID_SPAN: i64 = 1 << 24;
+
+/// Shards whose IDs fit in a source ID, after the span `database_file` hands out
+pub const MAX_CATALOG_SHARDS: usize = 127;
 
 static DB_POOL: Lazy<RwLock<Option<Arc<DbPool>>>> = Lazy::new(|| RwLock::new(None));
 
+/// The database file and the shards that rows of `Source_Files` and `Backup_Files` are routed to
+static CATALOG: Lazy<RwLock<Option<Arc<Catalog>>>> = Lazy::new(|| RwLock::new(None));
+
+struct Catalog {
+    file: String,
+    sharding: CatalogSharding,
+    shards: Vec<Shard>,
+}
+
+/// A database file holding the catalog rows under `root`
+struct Shard {
+    number: i64,
+    root: PathBuf,
+    pool: DbPool,
+}
+
+impl Catalog {
+    fn shard_under(&self, path: &str) -> Option<&Shard> {
+        self.shards
+            .iter()
+            .find(|shard| Path::new(path).starts_with(&shard.root))
+    }
+
+    fn shard_numbered(&self, source_id: i32) -> Option<&Shard> {
+        self.shards
+            .iter()
+            .find(|shard| shard.number == source_id as i64 / SHARD_ID_SPAN)
+    }
+
+    /// The shard a catalog row is kept in, or `None` for `database_file`
+    fn route(&self, route: Route) -> Option<&Shard> {
+        match (self.sharding, route) {
+            (CatalogSharding::PerSource, Route::SourcePath(path)) => self.shard_under(path),
+            (CatalogSharding::PerSource, Route::SourceId(id) | Route::Backup(id, _)) => {
+                self.shard_numbered(id)
+            }
+            (CatalogSharding::PerDestination, Route::Backup(_, path)) => self.shard_under(path),
+            _ => None,
+        }
+    }
+
+    fn connection(&self, shard: Option<i64>) -> Result<PooledConnection> {
+        match self.shards.iter().find(|s| Some(s.number) == shard) {
+            Some(shard) => pooled(&shard.pool),
+            None => get_connection(),
+        }
+    }
+}
+
+/// Where a catalog row is kept
+#[derive(Clone, Copy)]
+enum Route<'a> {
+    /// The source file in this directory
+    SourcePath(&'a str),
+    /// The source file with this ID
+    SourceId(i32),
+    /// A backup in this directory of the source file with this ID
+    Backup(i32, &'a str),
+}
+
 pub fn set_db_pool(db_file: &str) -> Result<()> {
     if db_file.is_empty() {
         return Err(BackupError::DirectoryRead(
@@ -25,50 +120,93 @@ pub fn set_db_pool(db_file: &str) -> Result<()> {
 
     info!("Initializing database connection pool: {}", db_file);
 
-    let is_in_memory = db_file == ":memory:" || db_file.starts_with("file::memory:");
-    let use_wal = !is_in_memory;
+    let pool = open_pool(db_file, None)?;
+    info!("Database pool created with {} connections", pool.max_size());
+
+    // Store pool in global
+    let mut global_pool = DB_POOL.write().unwrap();
+    *global_pool = Some(Arc::new(pool));
+    *CATALOG.write().unwrap() = Some(Arc::new(Catalog {
+        file: db_file.to_string(),
+        sharding: CatalogSharding::None,
+        shards: Vec::new(),
+    }));
+
+    Ok(())
+}
+
+/// `database_file` and the files of its catalog shards, or nothing for an in-memory database
+pub fn database_files() -> Vec<PathBuf> {
+    let Some(catalog) = catalog() else {
+        return Vec::new();
+    };
+    if is_in_memory(&catalog.file) {
+        return Vec::new();
+    }
+    std::iter::once(catalog.file.clone())
+        .chain(
+            catalog
+                .shards
+                .iter()
+                .map(|shard| shard_file(&catalog.file, shard.number)),
+        )
+        .map(PathBuf::from)
+        .collect()
+}
+
+fn is_in_memory(db_file: &str) -> bool {
+    db_file == ":memory:" || db_file.starts_with("file::memory:")
+}
+
+/// Pool of connections to `db_file`, with `attach_catalog` attached as `catalog` if given
+fn open_pool(db_file: &str, attach_catalog: Option<&str>) -> Result<DbPool> {
+    let use_wal = !is_in_memory(db_file);
+    let attach_catalog = attach_catalog.map(str::to_string);
 
     let manager = SqliteConnectionManager::file(db_file).with_init(move |conn| {
         let mut pragmas = String::from(
             "PRAGMA busy_timeout = 5000;
-                 PRAGMA synchronous = NORMAL;
-                 PRAGMA foreign_keys = ON;",
+                 PRAGMA synchronous = NORMAL;",
         );
 
         if use_wal {
             pragmas.push_str(" PRAGMA journal_mode = WAL;");
         }
 
-        conn.execute_batch(&pragmas)
+        // Backups in a destination shard belong to source files in the attached database, which
+        // foreign keys can't refer to
+        match &attach_catalog {
+            Some(catalog) => {
+                conn.execute_batch(&(pragmas + " PRAGMA foreign_keys = OFF;"))?;
+                conn.execute("ATTACH DATABASE ?1 AS catalog", [catalog])
+                    .map(|_| ())
+            }
+            None => conn.execute_batch(&(pragmas + " PRAGMA foreign_keys = ON;")),
+        }
     });
 
     // Build connection pool
     // Pool size: num_physical_cpus + 7 for good mix of reads/writes
     let pool_size = num_cpus::get_physical() + 7;
-    let pool = r2d2::Pool::builder()
+    r2d2::Pool::builder()
         .max_size(pool_size as u32)
         .build(manager)
         .map_err(|e| {
             BackupError::DirectoryRead(format!("Failed to create database connection pool: {}", e))
-        })?;
-
-    info!("Database pool created with {} connections", pool_size);
-
-    // Store pool in global
-    let mut global_pool = DB_POOL.write().unwrap();
-    *global_pool = Some(Arc::new(pool));
-
-    Ok(())
+        })
 }
 
-fn get_connection() -> Result<r2d2::PooledConnection<SqliteConnectionManager>> {
+fn get_connection() -> Result<PooledConnection> {
     let pool_lock = DB_POOL.read().unwrap();
     let pool = pool_lock.as_ref().ok_or_else(|| {
         BackupError::DirectoryRead(
             "Database pool not initialized. Call set_db_pool() first.".to_string(),
         )
     })?;
+    pooled(pool)
+}
 
+fn pooled(pool: &DbPool) -> Result<PooledConnection> {
     pool.get().map_err(|e| {
         BackupError::DirectoryRead(format!(
             "Failed to get database connection from pool: {}",
@@ -77,11 +215,375 @@ fn get_connection() -> Result<r2d2::PooledConnection<SqliteConnectionManager>> {
     })
 }
 
-pub fn setup_database() -> Result<()> {
-    info!("Initializing database schema");
-    let setup_queries = "BEGIN;
-    PRAGMA ENCODING = 'UTF-8';
+fn catalog() -> Option<Arc<Catalog>> {
+    CATALOG.read().unwrap().clone()
+}
+
+/// Connection to the database file a catalog row is kept in
+fn catalog_connection(route: Route) -> Result<PooledConnection> {
+    match catalog() {
+        Some(catalog) => catalog.connection(catalog.route(route).map(|shard| shard.number)),
+        None => get_connection(),
+    }
+}
+
+/// Connections to every database file with rows of `Source_Files`, or of `Backup_Files`
+///
+/// Destination shards see the source files of `database_file` too, so a query joining both
+/// tables returns a source file once for each of them.
+fn catalog_connections(backups: bool) -> Result<Vec<PooledConnection>> {
+    let mut connections = vec![get_connection()?];
+    if let Some(catalog) = catalog() {
+        if backups || catalog.sharding == CatalogSharding::PerSource {
+            for shard in &catalog.shards {
+                connections.push(pooled(&shard.pool)?);
+            }
+        }
+    }
+    Ok(connections)
+}
+
+/// Connections to the database files that may hold backups of a source file
+fn backup_connections_of(source_id: i32) -> Result<Vec<PooledConnection>> {
+    match catalog() {
+        Some(catalog) if catalog.sharding == CatalogSharding::PerDestination => {
+            catalog_connections(true)
+        }
+        _ => Ok(vec![catalog_connection(Route::SourceId(source_id))?]),
+    }
+}
+
+/// Keep the source files and backups under each root in a database file of their own, next to
+/// `database_file` and named after the shard's number
+///
+/// Rows already recorded are moved to the file they now belong in. Shards that are no longer
+/// configured are merged back into `database_file` and their files deleted.
+pub fn shard_catalog(sharding: CatalogSharding, roots: &[String]) -> Result<()> {
+    let file = catalog()
+        .map(|catalog| catalog.file.clone())
+        .ok_or_else(|| {
+            BackupError::DirectoryRead(
+                "Database pool not initialized. Call set_db_pool() first.".to_string(),
+            )
+        })?;
+    if is_in_memory(&file) {
+        if sharding != CatalogSharding::None {
+            warn!("An in-memory database can't be sharded; keeping the catalog in one database");
+        }
+        return Ok(());
+    }
+
+    // Nothing is routed to the current shards while rows move between files
+    *CATALOG.write().unwrap() = Some(Arc::new(Catalog {
+        file: file.clone(),
+        sharding: CatalogSharding::None,
+        shards: Vec::new(),
+    }));
+
+    let conn = get_connection()?;
+    let kind = match sharding {
+        CatalogSharding::None => "",
+        CatalogSharding::PerSource => "source",
+        CatalogSharding::PerDestination => "destination",
+    };
+    let registered = select_catalog_shards(&conn)?;
+    for (number, shard_kind, root) in &registered {
+        if shard_kind == kind && roots.contains(root) {
+            continue;
+        }
+        let shard_file = shard_file(&file, *number);
+        if Path::new(&shard_file).exists() {
+            info!("Merging catalog shard for {} back into {}", root, file);
+            // A shard last opened by an older version lacks the newer columns
+            let shard = rusqlite::Connection::open(&shard_file).map_err(|cause| {
+                BackupError::DatabaseConnection {
+                    path: shard_file.clone(),
+                    cause,
+                }
+            })?;
+            add_missing_backup_columns(&shard)?;
+            drop(shard);
+            move_catalog_rows(&file, &shard_file, shard_kind == "source", None, false)?;
+            remove_shard_file(&shard_file);
+        }
+        conn.execute("DELETE FROM Catalog_Shards WHERE Number = ?1", [number])
+            .map_err(|cause| BackupError::DatabaseQuery {
+                operation: "delete catalog shard".to_string(),
+                cause,
+            })?;
+    }
+
+    let mut shards = Vec::new();
+    for root in roots.iter().filter(|_| !kind.is_empty()) {
+        let number = match registered
+            .iter()
+            .find(|(_, shard_kind, shard_root)| shard_kind == kind && shard_root == root)
+        {
+            Some((number, _, _)) => *number,
+            None => insert_catalog_shard(&conn, kind, root)?,
+        };
+        let shard_file = shard_file(&file, number);
+        let attach_catalog = (sharding == CatalogSharding::PerDestination).then_some(file.as_str());
+        let pool = open_pool(&shard_file, attach_catalog)?;
+        setup_shard(&*pooled(&pool)?, sharding, number)?;
+        move_catalog_rows(&file, &shard_file, kind == "source", Some(root), true)?;
+
+        debug!("Catalog rows under {} are kept in {}", root, shard_file);
+        shards.push(Shard {
+            number,
+            root: PathBuf::from(root),
+            pool,
+        });
+    }
+
+    if !shards.is_empty() {
+        info!(
+            "Catalog sharded per {} across {} file(s)",
+            kind,
+            shards.len()
+        );
+    }
+    *CATALOG.write().unwrap() = Some(Arc::new(Catalog {
+        file,
+        sharding,
+        shards,
+    }));
+    Ok(())
+}
+
+/// `backup.db` becomes `backup.shard-3.db`
+fn shard_file(db_file: &str, number: i64) -> String {
+    let path = Path::new(db_file);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}.shard-{}.{}", stem, number, extension.to_string_lossy()),
+        None => format!("{}.shard-{}", stem, number),
+    };
+    path.with_file_name(name).to_string_lossy().to_string()
+}
+
+fn remove_shard_file(shard_file: &str) {
+    for suffix in ["", "-wal", "-shm"] {
+        let path = format!("{}{}", shard_file, suffix);
+        if let Err(e) = fs::remove_file(&path) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!("Failed to delete merged catalog shard {}: {}", path, e);
+            }
+        }
+    }
+}
+
+/// Number, kind and root of each registered shard
+fn select_catalog_shards(conn: &rusqlite::Connection) -> Result<Vec<(i64, String, String)>> {
+    let query_error = |cause| BackupError::DatabaseQuery {
+        operation: "select catalog shards".to_string(),
+        cause,
+    };
+    let mut stmt = conn
+        .prepare("SELECT Number, Kind, Root FROM Catalog_Shards ORDER BY Number")
+        .map_err(query_error)?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+        .map_err(query_error)?;
+    rows.collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(query_error)
+}
+
+/// Register a shard under the lowest free number above the IDs `database_file` has handed out
+fn insert_catalog_shard(conn: &rusqlite::Connection, kind: &str, root: &str) -> Result<i64> {
+    let query_error = |cause| BackupError::DatabaseQuery {
+        operation: "register catalog shard".to_string(),
+        cause,
+    };
+    let last_id: i64 = conn
+        .query_row(
+            "SELECT COALESCE(MAX(seq), 0) FROM sqlite_sequence WHERE name = 'Source_Files'",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(query_error)?;
+    let used: Vec<i64> = select_catalog_shards(conn)?
+        .into_iter()
+        .map(|(number, _, _)| number)
+        .collect();
+    let number = (last_id / SHARD_ID_SPAN + 1..=MAX_CATALOG_SHARDS as i64)
+        .find(|number| !used.contains(number))
+        .ok_or_else(|| {
+            BackupError::DirectoryRead(format!(
+                "No catalog shard number left for {}; merge shards by turning catalog_sharding off",
+                root
+            ))
+        })?;
+    conn.execute(
+        "INSERT INTO Catalog_Shards (Number, Kind, Root) VALUES (?1, ?2, ?3)",
+        (number, kind, root),
+    )
+    .map_err(query_error)?;
+    Ok(number)
+}
+
+/// Create a shard's tables, with its source IDs starting in its own span
+fn setup_shard(conn: &rusqlite::Connection, sharding: CatalogSharding, number: i64) -> Result<()> {
+    let query_error = |cause| BackupError::DatabaseQuery {
+        operation: format!("create tables of catalog shard {}", number),
+        cause,
+    };
+    if sharding == CatalogSharding::PerDestination {
+        conn.execute_batch(BACKUP_FILES_TABLE)
+            .map_err(query_error)?;
+        return add_missing_backup_columns(conn);
+    }
+    conn.execute_batch(&format!("{}{}", SOURCE_FILES_TABLE, BACKUP_FILES_TABLE))
+        .map_err(query_error)?;
+    add_missing_backup_columns(conn)?;
+    conn.execute(
+        "INSERT INTO sqlite_sequence (name, seq)
+            SELECT 'Source_Files', ?1
+            WHERE NOT EXISTS (SELECT 1 FROM sqlite_sequence WHERE name = 'Source_Files')",
+        [number * SHARD_ID_SPAN],
+    )
+    .map_err(query_error)?;
+    Ok(())
+}
+
+/// Move the catalog rows under `root`, or all of them, from `database_file` into a shard or
+/// back again
+///
+/// Source files get IDs in the span of the shard they move into, and keep them when moved back
+/// so that IDs stay unique.
+fn move_catalog_rows(
+    db_file: &str,
+    shard_file: &str,
+    with_sources: bool,
+    root: Option<&str>,
+    into_shard: bool,
+) -> Result<()> {
+    let conn =
+        rusqlite::Connection::open(db_file).map_err(|cause| BackupError::DatabaseConnection {
+            path: db_file.to_string(),
+            cause,
+        })?;
+    let move_error = |cause| BackupError::DatabaseQuery {
+        operation: format!("move catalog rows between {} and {}", db_file, shard_file),
+        cause,
+    };
+    conn.execute_batch("PRAGMA busy_timeout = 5000; PRAGMA foreign_keys = OFF;")
+        .map_err(move_error)?;
+    conn.execute("ATTACH DATABASE ?1 AS shard", [shard_file])
+        .map_err(move_error)?;
+
+    let (from, to, id) = match into_shard {
+        true => ("main", "shard", ""),
+        false => ("shard", "main", "ID, "),
+    };
+    let under = |column: &str| {
+        format!(
+            "(:dir IS NULL OR {0} = :dir OR substr({0}, 1, length(:nested)) = :nested)",
+            column
+        )
+    };
+    let statements = if with_sources {
+        vec![
+            format!(
+                "INSERT INTO {to}.Source_Files ({id}File_Name, File_Path, Hash, File_Size, Last_Modified)
+                    SELECT {id}File_Name, File_Path, Hash, File_Size, Last_Modified
+                    FROM {from}.Source_Files WHERE {under}
+                    ON CONFLICT (File_Name, File_Path) DO UPDATE SET
+                        Hash = excluded.Hash,
+                        File_Size = excluded.File_Size,
+                        Last_Modified = excluded.Last_Modified",
+                under = under("File_Path")
+            ),
+            format!(
+                "INSERT INTO {to}.Backup_Files
+                        (Source_ID, File_Name, File_Path, Last_Modified, Original_Path, Physical_Size,
+                        Last_Verified)
+                    SELECT t.ID, b.File_Name, b.File_Path, b.Last_Modified, b.Original_Path,
+                        b.Physical_Size, b.Last_Verified
+                    FROM {from}.Backup_Files b
+                    JOIN {from}.Source_Files s ON s.ID = b.Source_ID
+                    JOIN {to}.Source_Files t
+                        ON t.File_Name = s.File_Name AND t.File_Path = s.File_Path
+                    WHERE {under}
+                    ON CONFLICT (File_Name, File_Path) DO UPDATE SET
+                        Source_ID = excluded.Source_ID,
+                        Last_Modified = excluded.Last_Modified,
+                        Original_Path = excluded.Original_Path,
+                        Physical_Size = excluded.Physical_Size,
+                        Last_Verified = excluded.Last_Verified",
+                under = under("s.File_Path")
+            ),
+            format!(
+                "DELETE FROM {from}.Backup_Files
+                    WHERE Source_ID IN (SELECT ID FROM {from}.Source_Files WHERE {under})",
+                under = under("File_Path")
+            ),
+            format!(
+                "DELETE FROM {from}.Source_Files WHERE {under}",
+                under = under("File_Path")
+            ),
+        ]
+    } else {
+        vec![
+            format!(
+                "INSERT INTO {to}.Backup_Files
+                        (Source_ID, File_Name, File_Path, Last_Modified, Original_Path, Physical_Size,
+                        Last_Verified)
+                    SELECT Source_ID, File_Name, File_Path, Last_Modified, Original_Path,
+                        Physical_Size, Last_Verified
+                    FROM {from}.Backup_Files WHERE {under}
+                    ON CONFLICT (File_Name, File_Path) DO UPDATE SET
+                        Source_ID = excluded.Source_ID,
+                        Last_Modified = excluded.Last_Modified,
+                        Original_Path = excluded.Original_Path,
+                        Physical_Size = excluded.Physical_Size,
+                        Last_Verified = excluded.Last_Verified",
+                under = under("File_Path")
+            ),
+            format!(
+                "DELETE FROM {from}.Backup_Files WHERE {under}",
+                under = under("File_Path")
+            ),
+        ]
+    };
+
+    let (dir, nested) = match root.map(nested_under) {
+        Some((dir, nested)) => (Some(dir), Some(nested)),
+        None => (None, None),
+    };
+    let tx = conn.unchecked_transaction().map_err(move_error)?;
+    let mut counts = Vec::new();
+    for statement in &statements {
+        counts.push(
+            tx.execute(statement, named_params! {":dir": dir, ":nested": nested})
+                .map_err(move_error)?,
+        );
+    }
+    tx.commit().map_err(move_error)?;
+
+    let moved = counts[0];
+    if moved > 0 {
+        let rows = if with_sources {
+            "source files"
+        } else {
+            "backups"
+        };
+        match into_shard {
+            true => info!("Moved {} {} into {}", moved, rows, shard_file),
+            false => info!("Moved {} {} back into {}", moved, rows, db_file),
+        }
+    }
+    Ok(())
+}
+
+/// A directory and the prefix of paths nested in it, but not of siblings sharing its name as a
+/// prefix
+pub(crate) fn nested_under(dir: &str) -> (String, String) {
+    let dir = dir.trim_end_matches(['/', '\\']);
+    (dir.to_string(), format!("{}{}", dir, MAIN_SEPARATOR))
+}
 
+const SOURCE_FILES_TABLE: &str = "
     CREATE TABLE IF NOT EXISTS Source_Files(
         ID            integer not null
             constraint Source_Files_ID
@@ -97,6 +599,12 @@ pub fn setup_database() -> Result<()> {
     CREATE INDEX IF NOT EXISTS Source_Files_File_Name_index
             on Source_Files (File_Name);
 
+    CREATE INDEX IF NOT EXISTS Source_Files_Hash_index
+            on Source_Files (Hash);
+
+";
+
+const BACKUP_FILES_TABLE: &str = "
     CREATE TABLE IF NOT EXISTS Backup_Files(
         ID            integer not null
             constraint Backup_Files_ID_pk
@@ -107,6 +615,9 @@ pub fn setup_database() -> Result<()> {
         File_Name     TEXT    not null,
         File_Path     TEXT    not null,
         Last_Modified integer,
+        Original_Path TEXT,
+        Physical_Size integer,
+        Last_Verified integer,
         constraint Backup_Files_pk
             unique (File_Name, File_Path));
 
@@ -116,6 +627,11 @@ pub fn setup_database() -> Result<()> {
     CREATE INDEX IF NOT EXISTS Backup_Files_Source_ID_index
             on Backup_Files (Source_ID);
 
+";
+
+pub fn setup_database() -> Result<()> {
+    info!("Initializing database schema");
+    let setup_queries = "
     CREATE TABLE IF NOT EXISTS Logs(
         ID            integer not null
             constraint Logs_ID_pk
@@ -131,185 +647,3227 @@ pub fn setup_database() -> Result<()> {
     CREATE INDEX IF NOT EXISTS Logs_Timestamp_index
             on Logs (Timestamp DESC);
 
-    CREATE INDEX IF NOT EXISTS Logs_Level_index
-            on Logs (Level);
+    CREATE INDEX IF NOT EXISTS Logs_Level_index
+            on Logs (Level);
+
+    CREATE INDEX IF NOT EXISTS Logs_Timestamp_Level_index
+            on Logs (Timestamp DESC, Level);
+
+    CREATE TABLE IF NOT EXISTS Known_Directories(
+        ID               integer not null
+            constraint Known_Directories_ID_pk
+                primary key autoincrement,
+        Parent_Directory TEXT    not null,
+        Directory_Name   TEXT    not null,
+        First_Seen       integer not null,
+        constraint Known_Directories_pk
+            unique (Parent_Directory, Directory_Name));
+
+    CREATE TABLE IF NOT EXISTS Unclaimed_Files(
+        ID             integer not null
+            constraint Unclaimed_Files_ID_pk
+                primary key autoincrement,
+        Destination    TEXT    not null,
+        Original_Path  TEXT    not null,
+        Unclaimed_Path TEXT    not null,
+        Hash           TEXT,
+        File_Size      integer not null,
+        Moved_At       integer not null);
+
+    CREATE INDEX IF NOT EXISTS Unclaimed_Files_Hash_index
+        on Unclaimed_Files (Hash);
+
+    CREATE TABLE IF NOT EXISTS Run_Files(
+        ID          integer not null
+            constraint Run_Files_ID_pk
+                primary key autoincrement,
+        Run_ID      TEXT    not null,
+        Source_Name TEXT    not null,
+        File_Path   TEXT    not null,
+        Destination TEXT    not null,
+        Status      TEXT    not null,
+        Bytes       integer not null,
+        Error       TEXT,
+        Physical_Bytes integer);
+
+    CREATE INDEX IF NOT EXISTS Run_Files_Run_ID_Status_index
+        on Run_Files (Run_ID, Status);
+
+    CREATE TABLE IF NOT EXISTS Run_File_Actions(
+        ID          integer not null
+            constraint Run_File_Actions_ID_pk
+                primary key autoincrement,
+        Run_ID      TEXT    not null,
+        Source_Name TEXT    not null,
+        File_Path   TEXT    not null,
+        Action      TEXT    not null);
+
+    CREATE INDEX IF NOT EXISTS Run_File_Actions_Run_ID_File_Path_index
+        on Run_File_Actions (Run_ID, File_Path);
+
+    CREATE INDEX IF NOT EXISTS Run_File_Actions_File_Path_Action_index
+        on Run_File_Actions (File_Path, Action);
+
+    CREATE TABLE IF NOT EXISTS Post_Process_Results(
+        ID           integer not null
+            constraint Post_Process_Results_ID_pk
+                primary key autoincrement,
+        Processor    TEXT    not null,
+        Source_Path  TEXT    not null,
+        Backup_Path  TEXT    not null,
+        Status       TEXT    not null,
+        Detail       TEXT,
+        Processed_At integer not null);
+
+    CREATE INDEX IF NOT EXISTS Post_Process_Results_Backup_Path_index
+        on Post_Process_Results (Backup_Path);
+
+    CREATE INDEX IF NOT EXISTS Post_Process_Results_Status_index
+        on Post_Process_Results (Status, Processed_At DESC);
+
+    CREATE TABLE IF NOT EXISTS Jobs(
+        ID               integer not null
+            constraint Jobs_ID_pk
+                primary key autoincrement,
+        Name             TEXT    not null
+            constraint Jobs_Name_uk
+                unique,
+        Source           TEXT,
+        Path             TEXT,
+        Dry_Run          integer not null,
+        Dry_Run_Full     integer not null,
+        Schedule         TEXT,
+        Enabled          integer not null,
+        Next_Run         integer,
+        Last_Run_ID      TEXT,
+        Last_Status      TEXT,
+        Last_Started_At  integer,
+        Last_Finished_At integer,
+        Last_Error       TEXT,
+        Last_Success_At  integer,
+        Created_At       integer not null,
+        Updated_At       integer not null);
+
+    CREATE INDEX IF NOT EXISTS Jobs_Last_Run_ID_index
+        on Jobs (Last_Run_ID);
+
+    CREATE TABLE IF NOT EXISTS Runs(
+        ID              TEXT    not null
+            constraint Runs_ID_pk
+                primary key,
+        Started_At      integer not null,
+        Finished_At     integer not null,
+        Status          TEXT    not null,
+        Dry_Run         integer not null,
+        Files_Copied    integer not null,
+        Bytes_Copied    integer not null,
+        Errors          integer not null,
+        Error           TEXT,
+        Note            TEXT,
+        Duration_Ms     integer,
+        Files_Processed integer,
+        Bytes_Hashed    integer,
+        Hash_Micros     integer,
+        Checkpoint      integer,
+        Anomalies       TEXT);
+
+    CREATE INDEX IF NOT EXISTS Runs_Started_At_index
+        on Runs (Started_At DESC);
+
+    CREATE TABLE IF NOT EXISTS Active_Run(
+        ID              integer not null
+            constraint Active_Run_ID_pk
+                primary key
+            constraint Active_Run_Single_Row_Check
+                check (ID = 1),
+        Run_ID          TEXT    not null,
+        Started_At      integer not null,
+        Dry_Run         integer not null,
+        Dry_Run_Full    integer not null,
+        Source          TEXT,
+        Path            TEXT,
+        Status          TEXT    not null,
+        Files_Processed integer not null,
+        Total_Files     integer not null,
+        Bytes_Processed integer not null,
+        Updated_At      integer not null,
+        Note            TEXT);
+
+    CREATE TABLE IF NOT EXISTS Prune_Plans(
+        ID                 integer not null
+            constraint Prune_Plans_ID_pk
+                primary key autoincrement,
+        Created_At         integer not null,
+        Run_ID             TEXT    not null,
+        Status             TEXT    not null,
+        Files              integer not null,
+        Bytes              integer not null,
+        Oldest_Modified_At integer,
+        Newest_Modified_At integer,
+        Decided_At         integer,
+        Decided_By         TEXT);
+
+    CREATE TABLE IF NOT EXISTS Prune_Plan_Files(
+        ID          integer not null
+            constraint Prune_Plan_Files_ID_pk
+                primary key autoincrement,
+        Plan_ID     integer not null
+            constraint Prune_Plan_Files_Prune_Plans_ID_fk
+                references Prune_Plans
+                on delete cascade,
+        Destination TEXT    not null,
+        File_Path   TEXT    not null,
+        File_Size   integer not null,
+        Modified_At integer not null);
+
+    CREATE INDEX IF NOT EXISTS Prune_Plan_Files_Plan_ID_index
+        on Prune_Plan_Files (Plan_ID);
+
+    CREATE TABLE IF NOT EXISTS Audit_Log(
+        ID     integer not null
+            constraint Audit_Log_ID_pk
+                primary key autoincrement,
+        At     integer not null,
+        Action TEXT    not null,
+        Actor  TEXT    not null,
+        Detail TEXT    not null);
+
+    CREATE TABLE IF NOT EXISTS Tombstones(
+        ID          integer not null
+            constraint Tombstones_ID_pk
+                primary key autoincrement,
+        File_Path   TEXT    not null,
+        Hash        TEXT    not null,
+        File_Size   integer not null,
+        Deleted_At  integer not null,
+        Run_ID      TEXT    not null,
+        Returned_At integer);
+
+    CREATE INDEX IF NOT EXISTS Tombstones_File_Path_index
+        on Tombstones (File_Path);
+
+    CREATE TABLE IF NOT EXISTS Scrub_Problems(
+        Backup_Path TEXT    not null
+            constraint Scrub_Problems_pk
+                primary key,
+        Source_Path TEXT    not null,
+        Kind        TEXT    not null,
+        Detail      TEXT    not null,
+        Found_At    integer not null);
+
+    CREATE TABLE IF NOT EXISTS Destination_Markers(
+        Path        TEXT    not null
+            constraint Destination_Markers_pk
+                primary key,
+        Marker_ID   TEXT    not null,
+        Recorded_At integer not null,
+        Found_ID    TEXT,
+        Mismatch_At integer);
+
+    CREATE TABLE IF NOT EXISTS Catalog_Journal(
+        ID        integer not null
+            constraint Catalog_Journal_ID_pk
+                primary key autoincrement,
+        Run_ID    TEXT    not null,
+        Write     TEXT    not null,
+        Committed integer not null default 0);
+
+    CREATE INDEX IF NOT EXISTS Catalog_Journal_Run_ID_index
+        on Catalog_Journal (Run_ID, ID);
+
+    CREATE TABLE IF NOT EXISTS Catalog_Shards(
+        Number integer not null
+            constraint Catalog_Shards_Number_pk
+                primary key,
+        Kind   TEXT    not null,
+        Root   TEXT    not null,
+        constraint Catalog_Shards_pk
+            unique (Kind, Root));
+
+    CREATE TABLE IF NOT EXISTS Hash_Cache(
+        File_Key         TEXT    not null
+            constraint Hash_Cache_pk
+                primary key,
+        File_Size        integer not null,
+        Modified_Nanos   integer not null,
+        Hashed_Mebibytes integer not null,
+        Tail_Mebibytes   integer,
+        Hash             TEXT    not null,
+        Cached_At        integer not null);
+
+    CREATE INDEX IF NOT EXISTS Hash_Cache_Cached_At_index
+        on Hash_Cache (Cached_At);
+
+    CREATE TABLE IF NOT EXISTS Run_Backlog(
+        File_Path   TEXT    not null
+            constraint Run_Backlog_pk
+                primary key,
+        Run_ID      TEXT    not null,
+        Deferred_At integer not null);
+
+    CREATE TABLE IF NOT EXISTS Users(
+        Name          TEXT    not null
+            constraint Users_pk
+                primary key,
+        Role          TEXT    not null,
+        Password_Hash TEXT    not null,
+        Created_At    integer not null);
+
+    CREATE TABLE IF NOT EXISTS Sessions(
+        Token_Hash TEXT    not null
+            constraint Sessions_pk
+                primary key,
+        User_Name  TEXT    not null,
+        Created_At integer not null,
+        Expires_At integer not null);
+
+    CREATE INDEX IF NOT EXISTS Sessions_User_Name_index
+        on Sessions (User_Name);
+";
+
+    let conn = get_connection()?;
+    conn.execute_batch(&format!(
+        "BEGIN;
+    PRAGMA ENCODING = 'UTF-8';
+{}{}{}
+    COMMIT;",
+        SOURCE_FILES_TABLE, BACKUP_FILES_TABLE, setup_queries
+    ))
+    .map_err(|cause| BackupError::DatabaseQuery {
+        operation: "create tables".to_string(),
+        cause,
+    })?;
+
+    // Columns added after their table first shipped
+    for (table, column, column_type) in [
+        ("Runs", "Note", "TEXT"),
+        ("Active_Run", "Note", "TEXT"),
+        ("Runs", "Duration_Ms", "integer"),
+        ("Runs", "Files_Processed", "integer"),
+        ("Runs", "Bytes_Hashed", "integer"),
+        ("Runs", "Hash_Micros", "integer"),
+        ("Runs", "Checkpoint", "integer"),
+        ("Runs", "Anomalies", "TEXT"),
+        ("Run_Files", "Physical_Bytes", "integer"),
+        ("Destination_Markers", "Found_ID", "TEXT"),
+        ("Destination_Markers", "Mismatch_At", "integer"),
+        ("Hash_Cache", "Tail_Mebibytes", "integer"),
+    ] {
+        add_missing_column(&conn, table, column, column_type)?;
+    }
+    add_missing_backup_columns(&conn)?;
+    info!("Database schema initialized successfully");
+    Ok(())
+}
+
+/// Columns `Backup_Files` gained after it was first created, which older shards lack too
+const BACKUP_FILES_ADDED_COLUMNS: [(&str, &str); 3] = [
+    ("Original_Path", "TEXT"),
+    ("Physical_Size", "integer"),
+    ("Last_Verified", "integer"),
+];
+
+fn add_missing_backup_columns(conn: &rusqlite::Connection) -> Result<()> {
+    for (column, column_type) in BACKUP_FILES_ADDED_COLUMNS {
+        add_missing_column(conn, "Backup_Files", column, column_type)?;
+    }
+    Ok(())
+}
+
+/// Add a nullable column to a table created by an older version
+fn add_missing_column(
+    conn: &rusqlite::Connection,
+    table: &str,
+    column: &str,
+    column_type: &str,
+) -> Result<()> {
+    let exists =
+        conn.query_row(
+            "SELECT count(*) FROM pragma_table_info(?1) WHERE name = ?2",
+            [table, column],
+            |row| row.get::<_, i64>(0),
+        )
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: format!("inspect table {}", table),
+            cause,
+        })? > 0;
+    if !exists {
+        conn.execute_batch(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table, column, column_type
+        ))
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: format!("add column {}.{}", table, column),
+            cause,
+        })?;
+    }
+    Ok(())
+}
+
+pub fn select_source(source_file: &str, source_path: &str) -> Result<Option<SourceRow>> {
+    let conn = catalog_connection(Route::SourcePath(source_path))?;
+    conn.query_row(
+        "SELECT *
+                FROM Source_Files
+                WHERE File_Name=?1
+                    AND File_Path=?2",
+        [source_file, source_path],
+        |row| {
+            Ok(SourceRow {
+                id: row.get(0)?,
+                file_name: row.get(1)?,
+                file_path: row.get(2)?,
+                hash: row.get(3)?,
+                file_size: row.get(4)?,
+                last_modified: Duration::from_secs(row.get(5)?),
+            })
+        },
+    )
+    .optional()
+    .map_err(|cause| BackupError::DatabaseQuery {
+        operation: format!(
+            "select source {}{}{}",
+            source_path, MAIN_SEPARATOR, source_file
+        ),
+        cause,
+    })
+}
+
+pub fn select_backed_up_file(filename: &str, filepath: &str) -> Result<Option<BackedUpFile>> {
+    // A backup path doesn't say which source shard its row is in
+    let connections = match catalog() {
+        Some(catalog) if catalog.sharding == CatalogSharding::PerSource => {
+            catalog_connections(true)?
+        }
+        _ => vec![catalog_connection(Route::Backup(0, filepath))?],
+    };
+    let query_error = |cause| BackupError::DatabaseQuery {
+        operation: format!("select backup {}{}{}", filepath, MAIN_SEPARATOR, filename),
+        cause,
+    };
+
+    for conn in connections {
+        let backed_up = conn
+            .query_row(
+                "SELECT File_Name, File_Path, Last_Modified
+                    FROM Backup_Files
+                    WHERE File_Name=?1 AND File_Path=?2",
+                [filename, filepath],
+                |row| {
+                    Ok(BackedUpFile {
+                        file_name: row.get(0)?,
+                        file_path: row.get(1)?,
+                        last_modified: Duration::from_secs(row.get(2)?),
+                    })
+                },
+            )
+            .optional()
+            .map_err(query_error)?;
+        if backed_up.is_some() {
+            return Ok(backed_up);
+        }
+    }
+    Ok(None)
+}
+
+/// Paths of the backups recorded for a source file
+pub fn select_backup_paths(source_id: i32) -> Result<Vec<PathBuf>> {
+    let query_error = |cause| BackupError::DatabaseQuery {
+        operation: format!("select backups of source {}", source_id),
+        cause,
+    };
+    let mut paths = Vec::new();
+    for conn in backup_connections_of(source_id)? {
+        let mut query = conn
+            .prepare("SELECT File_Path, File_Name FROM Backup_Files WHERE Source_ID=?1")
+            .map_err(query_error)?;
+        let rows = query
+            .query_map([source_id], |row| {
+                Ok(PathBuf::from(row.get::<_, String>(0)?).join(row.get::<_, String>(1)?))
+            })
+            .map_err(query_error)?;
+        for path in rows {
+            paths.push(path.map_err(query_error)?);
+        }
+    }
+    Ok(paths)
+}
+
+/// Source files below `under`, or all of them, by path, each with its number of recorded backups
+pub fn select_sources(under: Option<&str>) -> Result<Vec<(SourceRow, u64)>> {
+    let nested = under.map(nested_under);
+    let mut sources = Vec::new();
+    for conn in catalog_connections(true)? {
+        let mut stmt = conn
+            .prepare(
+                "SELECT sf.ID, sf.File_Name, sf.File_Path, sf.Hash, sf.File_Size, sf.Last_Modified,
+                        COUNT(bf.ID)
+                    FROM Source_Files sf
+                    LEFT JOIN Backup_Files bf ON bf.Source_ID = sf.ID
+                    WHERE ?1 IS NULL OR sf.File_Path = ?1
+                        OR substr(sf.File_Path, 1, length(?2)) = ?2
+                    GROUP BY sf.ID
+                    ORDER BY sf.File_Path, sf.File_Name",
+            )
+            .map_err(|cause| BackupError::DatabaseQuery {
+                operation: "select sources".to_string(),
+                cause,
+            })?;
+
+        let rows = stmt
+            .query_map(
+                (
+                    nested.as_ref().map(|(dir, _)| dir),
+                    nested.as_ref().map(|(_, pattern)| pattern),
+                ),
+                |row| {
+                    Ok((
+                        SourceRow {
+                            id: row.get(0)?,
+                            file_name: row.get(1)?,
+                            file_path: row.get(2)?,
+                            hash: row.get(3)?,
+                            file_size: row.get(4)?,
+                            last_modified: Duration::from_secs(row.get(5)?),
+                        },
+                        row.get(6)?,
+                    ))
+                },
+            )
+            .map_err(|cause| BackupError::DatabaseQuery {
+                operation: "select sources".to_string(),
+                cause,
+            })?;
+
+        for row in rows {
+            sources.push(row.map_err(|cause| BackupError::DatabaseQuery {
+                operation: "collect sources".to_string(),
+                cause,
+            })?);
+        }
+    }
+
+    let mut sources = merge_by_source(
+        sources,
+        |(source, _)| source.id,
+        |into, (_, count)| into.1 += count,
+    );
+    sources
+        .sort_by(|(a, _), (b, _)| (&a.file_path, &a.file_name).cmp(&(&b.file_path, &b.file_name)));
+    Ok(sources)
+}
+
+/// Rows from several database files with those of the same source file merged into the first
+fn merge_by_source<T>(rows: Vec<T>, id: impl Fn(&T) -> i32, merge: impl Fn(&mut T, T)) -> Vec<T> {
+    let mut positions = HashMap::new();
+    let mut merged: Vec<T> = Vec::new();
+    for row in rows {
+        match positions.get(&id(&row)) {
+            Some(&idx) => merge(&mut merged[idx], row),
+            None => {
+                positions.insert(id(&row), merged.len());
+                merged.push(row);
+            }
+        }
+    }
+    merged
+}
+
+/// Backups recorded for a source file, by path
+pub fn select_backups_of_source(source_id: i32) -> Result<Vec<BackupRow>> {
+    let mut backups = Vec::new();
+    for conn in backup_connections_of(source_id)? {
+        let mut stmt = conn
+            .prepare(
+                "SELECT Source_ID, File_Name, File_Path, Last_Modified, Original_Path, Physical_Size
+                    FROM Backup_Files
+                    WHERE Source_ID = ?1
+                    ORDER BY File_Path, File_Name",
+            )
+            .map_err(|cause| BackupError::DatabaseQuery {
+                operation: "select backups".to_string(),
+                cause,
+            })?;
+
+        let rows = stmt
+            .query_map([source_id], backup_from_row)
+            .map_err(|cause| BackupError::DatabaseQuery {
+                operation: "select backups".to_string(),
+                cause,
+            })?;
+
+        for row in rows {
+            backups.push(row.map_err(|cause| BackupError::DatabaseQuery {
+                operation: "collect backups".to_string(),
+                cause,
+            })?);
+        }
+    }
+    backups.sort_by(|a, b| (&a.file_path, &a.file_name).cmp(&(&b.file_path, &b.file_name)));
+    Ok(backups)
+}
+
+fn backup_from_row(row: &rusqlite::Row) -> rusqlite::Result<BackupRow> {
+    Ok(BackupRow {
+        source_id: row.get(0)?,
+        file_name: row.get(1)?,
+        file_path: row.get(2)?,
+        last_modified: Duration::from_secs(row.get(3)?),
+        original_path: row.get(4)?,
+        physical_size: row.get(5)?,
+    })
+}
+
+/// Every recorded backup, grouped by source
+pub fn select_all_backups() -> Result<Vec<BackupRow>> {
+    let mut backups = Vec::new();
+    for conn in catalog_connections(true)? {
+        let mut stmt = conn
+            .prepare(
+                "SELECT Source_ID, File_Name, File_Path, Last_Modified, Original_Path, Physical_Size
+                    FROM Backup_Files
+                    ORDER BY Source_ID, File_Path, File_Name",
+            )
+            .map_err(|cause| BackupError::DatabaseQuery {
+                operation: "select all backups".to_string(),
+                cause,
+            })?;
+
+        let rows =
+            stmt.query_map([], backup_from_row)
+                .map_err(|cause| BackupError::DatabaseQuery {
+                    operation: "select all backups".to_string(),
+                    cause,
+                })?;
+
+        for row in rows {
+            backups.push(row.map_err(|cause| BackupError::DatabaseQuery {
+                operation: "collect all backups".to_string(),
+                cause,
+            })?);
+        }
+    }
+    backups.sort_by(|a, b| {
+        (a.source_id, &a.file_path, &a.file_name).cmp(&(b.source_id, &b.file_path, &b.file_name))
+    });
+    Ok(backups)
+}
+
+/// Every source file with its backups, for a catalog export
+pub fn select_catalog() -> Result<Vec<ExportedSource>> {
+    let query_error = |cause| BackupError::DatabaseQuery {
+        operation: "select catalog".to_string(),
+        cause,
+    };
+    let mut sources: Vec<(i32, ExportedSource)> = Vec::new();
+    for conn in catalog_connections(true)? {
+        let mut stmt = conn
+            .prepare(
+                "SELECT s.ID, s.File_Name, s.File_Path, s.Hash, s.File_Size, s.Last_Modified,
+                        b.File_Name, b.File_Path, b.Last_Modified
+                    FROM Source_Files s
+                    LEFT JOIN Backup_Files b ON b.Source_ID = s.ID
+                    ORDER BY s.File_Path, s.File_Name, s.ID, b.File_Path, b.File_Name",
+            )
+            .map_err(query_error)?;
+        let mut rows = stmt.query([]).map_err(query_error)?;
+
+        let first = sources.len();
+        while let Some(row) = rows.next().map_err(query_error)? {
+            let id: i32 = row.get(0).map_err(query_error)?;
+            if sources.len() == first || sources.last().unwrap().0 != id {
+                sources.push((
+                    id,
+                    ExportedSource {
+                        file_name: row.get(1).map_err(query_error)?,
+                        file_path: row.get(2).map_err(query_error)?,
+                        hash: row
+                            .get::<_, Option<String>>(3)
+                            .map_err(query_error)?
+                            .unwrap_or_default(),
+                        file_size: row
+                            .get::<_, Option<u64>>(4)
+                            .map_err(query_error)?
+                            .unwrap_or_default(),
+                        last_modified: row
+                            .get::<_, Option<u64>>(5)
+                            .map_err(query_error)?
+                            .unwrap_or_default(),
+                        backups: Vec::new(),
+                    },
+                ));
+            }
+            if let Some(file_path) = row.get::<_, Option<String>>(7).map_err(query_error)? {
+                let backup = ExportedBackup {
+                    file_name: row.get(6).map_err(query_error)?,
+                    file_path,
+                    last_modified: row
+                        .get::<_, Option<u64>>(8)
+                        .map_err(query_error)?
+                        .unwrap_or_default(),
+                };
+                sources.last_mut().unwrap().1.backups.push(backup);
+            }
+        }
+    }
+
+    let mut sources: Vec<ExportedSource> = merge_by_source(
+        sources,
+        |(id, _)| *id,
+        |into, (_, source)| into.1.backups.extend(source.backups),
+    )
+    .into_iter()
+    .map(|(_, source)| source)
+    .collect();
+    sources.sort_by(|a, b| (&a.file_path, &a.file_name).cmp(&(&b.file_path, &b.file_name)));
+    for source in &mut sources {
+        source
+            .backups
+            .sort_by(|a, b| (&a.file_path, &a.file_name).cmp(&(&b.file_path, &b.file_name)));
+    }
+    Ok(sources)
+}
+
+/// Add catalog rows from an export, updating rows for the same paths, in a single transaction
+/// for each database file
+pub fn import_catalog(sources: &[ExportedSource]) -> Result<ImportSummary> {
+    let Some(catalog) = catalog() else {
+        let sources: Vec<&ExportedSource> = sources.iter().collect();
+        return import_sources(&mut *get_connection()?, &sources, true);
+    };
+    let mut summary = ImportSummary::default();
+
+    if catalog.sharding == CatalogSharding::PerDestination {
+        // Source files first, for the IDs their backups are recorded with
+        let all: Vec<&ExportedSource> = sources.iter().collect();
+        summary = import_sources(&mut *get_connection()?, &all, false)?;
+        let mut by_shard: HashMap<Option<i64>, Vec<(&str, &str, &ExportedBackup)>> = HashMap::new();
+        for source in sources {
+            for backup in &source.backups {
+                let shard = catalog.route(Route::Backup(0, &backup.file_path));
+                by_shard.entry(shard.map(|s| s.number)).or_default().push((
+                    &source.file_name,
+                    &source.file_path,
+                    backup,
+                ));
+            }
+        }
+        for (shard, backups) in by_shard {
+            summary.backups += import_backups(&mut *catalog.connection(shard)?, &backups)?;
+        }
+        return Ok(summary);
+    }
+
+    let mut by_shard: HashMap<Option<i64>, Vec<&ExportedSource>> = HashMap::new();
+    for source in sources {
+        let shard = catalog.route(Route::SourcePath(&source.file_path));
+        by_shard
+            .entry(shard.map(|s| s.number))
+            .or_default()
+            .push(source);
+    }
+    for (shard, sources) in by_shard {
+        let imported = import_sources(&mut *catalog.connection(shard)?, &sources, true)?;
+        summary.sources += imported.sources;
+        summary.backups += imported.backups;
+    }
+    Ok(summary)
+}
+
+fn import_error(table: &str, file: String) -> impl FnOnce(Error) -> BackupError {
+    let table = table.to_string();
+    move |cause| BackupError::DatabaseInsert { table, file, cause }
+}
+
+/// Upsert source files, and their backups if `with_backups`, in one transaction
+fn import_sources(
+    conn: &mut rusqlite::Connection,
+    sources: &[&ExportedSource],
+    with_backups: bool,
+) -> Result<ImportSummary> {
+    let tx = conn
+        .transaction()
+        .map_err(import_error("Source_Files", "import".to_string()))?;
+    let mut summary = ImportSummary::default();
+
+    for source in sources {
+        let file = format!("{}/{}", source.file_path, source.file_name);
+        let source_id: i32 = tx
+            .query_row(
+                "INSERT INTO Source_Files (File_Name, File_Path, Hash, File_Size, Last_Modified)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT (File_Name, File_Path) DO UPDATE SET
+                     Hash = excluded.Hash,
+                     File_Size = excluded.File_Size,
+                     Last_Modified = excluded.Last_Modified
+                 RETURNING ID",
+                (
+                    &source.file_name,
+                    &source.file_path,
+                    &source.hash,
+                    source.file_size,
+                    source.last_modified,
+                ),
+                |row| row.get(0),
+            )
+            .map_err(import_error("Source_Files", file.clone()))?;
+        check_source_id(&source.file_path, source_id, &file)?;
+        summary.sources += 1;
+
+        for backup in source.backups.iter().filter(|_| with_backups) {
+            upsert_backup(&tx, source_id, backup)?;
+            summary.backups += 1;
+        }
+    }
+
+    tx.commit()
+        .map_err(import_error("Source_Files", "import".to_string()))?;
+    Ok(summary)
+}
+
+/// Upsert backups, each given with the name and directory of its source file, in one transaction
+fn import_backups(
+    conn: &mut rusqlite::Connection,
+    backups: &[(&str, &str, &ExportedBackup)],
+) -> Result<u64> {
+    let tx = conn
+        .transaction()
+        .map_err(import_error("Backup_Files", "import".to_string()))?;
+    for (source_name, source_path, backup) in backups {
+        let source_id: i32 = tx
+            .query_row(
+                "SELECT ID FROM Source_Files WHERE File_Name = ?1 AND File_Path = ?2",
+                [source_name, source_path],
+                |row| row.get(0),
+            )
+            .map_err(import_error(
+                "Backup_Files",
+                format!("{}/{}", backup.file_path, backup.file_name),
+            ))?;
+        upsert_backup(&tx, source_id, backup)?;
+    }
+    tx.commit()
+        .map_err(import_error("Backup_Files", "import".to_string()))?;
+    Ok(backups.len() as u64)
+}
+
+fn upsert_backup(
+    tx: &rusqlite::Transaction,
+    source_id: i32,
+    backup: &ExportedBackup,
+) -> Result<()> {
+    tx.execute(
+        "INSERT INTO Backup_Files (Source_ID, File_Name, File_Path, Last_Modified)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT (File_Name, File_Path) DO UPDATE SET
+            Source_ID=excluded.Source_ID,
+            Last_Modified=excluded.Last_Modified",
+        (
+            source_id,
+            &backup.file_name,
+            &backup.file_path,
+            backup.last_modified,
+        ),
+    )
+    .map_err(import_error(
+        "Backup_Files",
+        format!("{}/{}", backup.file_path, backup.file_name),
+    ))?;
+    Ok(())
+}
+
+/// `LIKE` pattern matching `term` anywhere, with its wildcards taken literally
+pub(crate) fn contains_pattern(term: &str) -> String {
+    let escaped = term
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// Number of source files whose full path contains `search`, or of all source files
+pub fn count_sources_matching(search: Option<&str>) -> Result<u64> {
+    let mut count = 0;
+    for conn in catalog_connections(false)? {
+        count += conn
+            .query_row(
+                "SELECT COUNT(*) FROM Source_Files
+                    WHERE ?1 IS NULL OR File_Path || ?2 || File_Name LIKE ?1 ESCAPE '\\'",
+                (search.map(contains_pattern), MAIN_SEPARATOR.to_string()),
+                |row| row.get::<_, u64>(0),
+            )
+            .map_err(|cause| BackupError::DatabaseQuery {
+                operation: "count sources".to_string(),
+                cause,
+            })?;
+    }
+    Ok(count)
+}
+
+/// One page of the source files whose full path contains `search` (case-insensitive for
+/// ASCII), ordered by path
+pub fn select_sources_matching(
+    search: Option<&str>,
+    limit: usize,
+    offset: usize,
+) -> Result<Vec<SourceRow>> {
+    let connections = catalog_connections(false)?;
+    // With several files, each is asked for everything up to the end of the page
+    let (query_limit, query_offset) = match connections.len() {
+        1 => (limit, offset),
+        _ => (limit + offset, 0),
+    };
+    let mut sources = Vec::new();
+    for conn in &connections {
+        let mut stmt = conn
+            .prepare(
+                "SELECT ID, File_Name, File_Path, Hash, File_Size, Last_Modified
+                    FROM Source_Files
+                    WHERE ?1 IS NULL OR File_Path || ?2 || File_Name LIKE ?1 ESCAPE '\\'
+                    ORDER BY File_Path, File_Name
+                    LIMIT ?3 OFFSET ?4",
+            )
+            .map_err(|cause| BackupError::DatabaseQuery {
+                operation: "search sources".to_string(),
+                cause,
+            })?;
+
+        let rows = stmt
+            .query_map(
+                (
+                    search.map(contains_pattern),
+                    MAIN_SEPARATOR.to_string(),
+                    query_limit as i64,
+                    query_offset as i64,
+                ),
+                |row| {
+                    Ok(SourceRow {
+                        id: row.get(0)?,
+                        file_name: row.get(1)?,
+                        file_path: row.get(2)?,
+                        hash: row.get(3)?,
+                        file_size: row.get(4)?,
+                        last_modified: Duration::from_secs(row.get(5)?),
+                    })
+                },
+            )
+            .map_err(|cause| BackupError::DatabaseQuery {
+                operation: "search sources".to_string(),
+                cause,
+            })?;
+
+        for row in rows {
+            sources.push(row.map_err(|cause| BackupError::DatabaseQuery {
+                operation: "collect sources".to_string(),
+                cause,
+            })?);
+        }
+    }
+
+    if connections.len() > 1 {
+        sources.sort_by(|a, b| (&a.file_path, &a.file_name).cmp(&(&b.file_path, &b.file_name)));
+        sources = sources.into_iter().skip(offset).take(limit).collect();
+    }
+    Ok(sources)
+}
+
+/// The source file with this ID
+pub fn select_source_by_id(id: i32) -> Result<Option<SourceRow>> {
+    let conn = catalog_connection(Route::SourceId(id))?;
+    conn.query_row(
+        "SELECT ID, File_Name, File_Path, Hash, File_Size, Last_Modified
+            FROM Source_Files
+            WHERE ID = ?1",
+        [id],
+        |row| {
+            Ok(SourceRow {
+                id: row.get(0)?,
+                file_name: row.get(1)?,
+                file_path: row.get(2)?,
+                hash: row.get(3)?,
+                file_size: row.get(4)?,
+                last_modified: Duration::from_secs(row.get(5)?),
+            })
+        },
+    )
+    .optional()
+    .map_err(|cause| BackupError::DatabaseQuery {
+        operation: "select source".to_string(),
+        cause,
+    })
+}
+
+pub fn insert_source_row(source_row: &SourceRow) -> Result<i32> {
+    let conn = catalog_connection(Route::SourcePath(&source_row.file_path))?;
+    debug!(
+        "Inserting source record: {}/{}",
+        source_row.file_path, source_row.file_name
+    );
+
+    let file = format!("{}/{}", source_row.file_path, source_row.file_name);
+    let insert_error = |cause| BackupError::DatabaseInsert {
+        table: "Source_Files".to_string(),
+        file: file.clone(),
+        cause,
+    };
+    // Rolled back when dropped, if the ID turns out to belong to another catalog file
+    let tx = conn.unchecked_transaction().map_err(insert_error)?;
+    let id = tx
+        .query_row(
+            "INSERT INTO Source_Files (File_Name, File_Path, Hash, File_Size, Last_Modified)
+             VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT (File_Name, File_Path) DO UPDATE SET
+                 Hash = excluded.Hash,
+                 File_Size = excluded.File_Size,
+                 Last_Modified = excluded.Last_Modified
+             RETURNING ID",
+            (
+                &source_row.file_name,
+                &source_row.file_path,
+                &source_row.hash,
+                &source_row.file_size,
+                source_row.last_modified.as_secs(),
+            ),
+            |row| row.get(0),
+        )
+        .map_err(insert_error)?;
+    check_source_id(&source_row.file_path, id, &file)?;
+    tx.commit().map_err(insert_error)?;
+    Ok(id)
+}
+
+/// Fail when a source file in `path` got an ID that names another catalog file than the one it
+/// was inserted into, since lookups by that ID would go there
+///
+/// A shard runs into the next span once it has handed out `SHARD_ID_SPAN` IDs.
+fn check_source_id(path: &str, id: i32, file: &str) -> Result<()> {
+    let Some(catalog) = catalog() else {
+        return Ok(());
+    };
+    let name = |shard: Option<&Shard>| match shard {
+        Some(shard) => format!("catalog shard {}", shard.number),
+        None => "database_file".to_string(),
+    };
+    let inserted_into = catalog.route(Route::SourcePath(path));
+    let named = catalog.route(Route::SourceId(id));
+    if inserted_into.map(|shard| shard.number) == named.map(|shard| shard.number) {
+        return Ok(());
+    }
+    Err(BackupError::SourceIdOutOfSpan {
+        file: file.to_string(),
+        id,
+        inserted_into: name(inserted_into),
+        named: name(named),
+    })
+}
+
+pub fn update_source_last_modified(row_id: i32, last_modified: &Duration) -> Result<()> {
+    let conn = catalog_connection(Route::SourceId(row_id))?;
+    conn.execute(
+        "UPDATE Source_Files SET Last_Modified=?1 WHERE ID=?2",
+        (last_modified.as_secs(), row_id),
+    )
+    .map_err(|cause| BackupError::DatabaseUpdate {
+        table: "Source_Files".to_string(),
+        id: row_id as i64,
+        cause,
+    })?;
+    Ok(())
+}
+
+/// Source files recorded with this hash and size, by path
+pub fn select_sources_with_content(hash: &str, file_size: u64) -> Result<Vec<SourceRow>> {
+    let query_error = |cause| BackupError::DatabaseQuery {
+        operation: format!("select sources with hash {}", hash),
+        cause,
+    };
+    let mut sources = Vec::new();
+    for conn in catalog_connections(false)? {
+        let mut stmt = conn
+            .prepare(
+                "SELECT ID, File_Name, File_Path, Hash, File_Size, Last_Modified
+                    FROM Source_Files
+                    WHERE Hash = ?1 AND File_Size = ?2",
+            )
+            .map_err(query_error)?;
+        let rows = stmt
+            .query_map((hash, file_size), |row| {
+                Ok(SourceRow {
+                    id: row.get(0)?,
+                    file_name: row.get(1)?,
+                    file_path: row.get(2)?,
+                    hash: row.get(3)?,
+                    file_size: row.get(4)?,
+                    last_modified: Duration::from_secs(row.get(5)?),
+                })
+            })
+            .map_err(query_error)?;
+        for row in rows {
+            sources.push(row.map_err(query_error)?);
+        }
+    }
+    sources.sort_by(|a, b| (&a.file_path, &a.file_name).cmp(&(&b.file_path, &b.file_name)));
+    Ok(sources)
+}
+
+/// Forget a source file that has no backups left, returning whether it was
+pub fn delete_source_row(id: i32) -> Result<bool> {
+    let query_error = |cause| BackupError::DatabaseQuery {
+        operation: format!("delete source {}", id),
+        cause,
+    };
+    for conn in backup_connections_of(id)? {
+        let backups: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM Backup_Files WHERE Source_ID = ?1",
+                [id],
+                |row| row.get(0),
+            )
+            .map_err(query_error)?;
+        if backups > 0 {
+            return Ok(false);
+        }
+    }
+    let conn = catalog_connection(Route::SourceId(id))?;
+    let deleted = conn
+        .execute("DELETE FROM Source_Files WHERE ID = ?1", [id])
+        .map_err(query_error)?;
+    Ok(deleted > 0)
+}
+
+pub fn insert_backup_row(backup_row: BackupRow) -> Result<()> {
+    let conn = catalog_connection(Route::Backup(backup_row.source_id, &backup_row.file_path))?;
+    conn.execute(
+        "INSERT INTO Backup_Files
+                    (Source_ID, File_Name, File_Path, Last_Modified, Original_Path, Physical_Size)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                ON CONFLICT (File_Name, File_Path) DO UPDATE SET
+                Source_ID=excluded.Source_ID,
+                Last_Modified=excluded.Last_Modified,
+                Original_Path=COALESCE(excluded.Original_Path, Original_Path),
+                Physical_Size=COALESCE(excluded.Physical_Size, Physical_Size);",
+        (
+            backup_row.source_id,
+            &backup_row.file_name,
+            &backup_row.file_path,
+            backup_row.last_modified.as_secs(),
+            &backup_row.original_path,
+            backup_row.physical_size,
+        ),
+    )
+    .map_err(|cause| BackupError::DatabaseInsert {
+        table: "Backup_Files".to_string(),
+        file: backup_row.file_name.clone(),
+        cause,
+    })?;
+    debug!("Inserted backup record: {}", backup_row.file_name);
+    Ok(())
+}
+
+/// Forget the backup of a source file at this path
+pub fn delete_backup_row(source_id: i32, file_name: &str, file_path: &str) -> Result<()> {
+    let conn = catalog_connection(Route::Backup(source_id, file_path))?;
+    conn.execute(
+        "DELETE FROM Backup_Files WHERE Source_ID = ?1 AND File_Name = ?2 AND File_Path = ?3",
+        (source_id, file_name, file_path),
+    )
+    .map_err(|cause| BackupError::DatabaseQuery {
+        operation: format!("delete backup {}{}{}", file_path, MAIN_SEPARATOR, file_name),
+        cause,
+    })?;
+    Ok(())
+}
+
+/// Every recorded backup with its source file, for a scrub or a replication to choose from
+pub fn select_scrub_candidates() -> Result<Vec<ScrubCandidate>> {
+    let query_error = |cause| BackupError::DatabaseQuery {
+        operation: "select backups to scrub".to_string(),
+        cause,
+    };
+    let mut candidates = Vec::new();
+    for conn in catalog_connections(true)? {
+        let mut stmt = conn
+            .prepare(
+                "SELECT bf.Source_ID, bf.File_Name, bf.File_Path, bf.Last_Modified,
+                        bf.Original_Path, bf.Physical_Size, sf.File_Path, sf.File_Name, sf.Hash,
+                        sf.File_Size, sf.Last_Modified, bf.Last_Verified
+                    FROM Backup_Files bf
+                    JOIN Source_Files sf ON sf.ID = bf.Source_ID
+                    WHERE sf.Hash IS NOT NULL",
+            )
+            .map_err(query_error)?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(ScrubCandidate {
+                    backup: backup_from_row(row)?,
+                    source_path: Path::new(&row.get::<_, String>(6)?)
+                        .join(row.get::<_, String>(7)?)
+                        .to_string_lossy()
+                        .to_string(),
+                    hash: row.get(8)?,
+                    file_size: row.get(9)?,
+                    source_last_modified: Duration::from_secs(row.get(10)?),
+                    last_verified: row.get(11)?,
+                })
+            })
+            .map_err(query_error)?;
+        for row in rows {
+            candidates.push(row.map_err(query_error)?);
+        }
+    }
+    Ok(candidates)
+}
+
+/// Record that a scrub found the backup at this path intact at `verified_at`
+pub fn update_backup_verified(
+    source_id: i32,
+    file_name: &str,
+    file_path: &str,
+    verified_at: i64,
+) -> Result<()> {
+    let conn = catalog_connection(Route::Backup(source_id, file_path))?;
+    conn.execute(
+        "UPDATE Backup_Files SET Last_Verified = ?1 WHERE File_Name = ?2 AND File_Path = ?3",
+        (verified_at, file_name, file_path),
+    )
+    .map_err(|cause| BackupError::DatabaseQuery {
+        operation: format!(
+            "record scrub of {}{}{}",
+            file_path, MAIN_SEPARATOR, file_name
+        ),
+        cause,
+    })?;
+    Ok(())
+}
+
+// ============================================================================
+// Known Directories Functions
+// ============================================================================
+
+/// Get the names of all top-level directories already seen under a source
+pub fn select_known_directories(parent_directory: &str) -> Result<Vec<String>> {
+    let conn = get_connection()?;
+    let mut stmt = conn
+        .prepare("SELECT Directory_Name FROM Known_Directories WHERE Parent_Directory=?1")
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: format!("select known directories for {}", parent_directory),
+            cause,
+        })?;
+
+    let rows = stmt
+        .query_map([parent_directory], |row| row.get(0))
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: format!("select known directories for {}", parent_directory),
+            cause,
+        })?;
+
+    rows.collect::<rusqlite::Result<Vec<String>>>()
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "collect known directories".to_string(),
+            cause,
+        })
+}
+
+/// Record a top-level directory as seen under a source
+pub fn insert_known_directory(parent_directory: &str, directory_name: &str) -> Result<()> {
+    let conn = get_connection()?;
+    conn.execute(
+        "INSERT INTO Known_Directories (Parent_Directory, Directory_Name, First_Seen)
+                VALUES (?1, ?2, ?3)
+                ON CONFLICT (Parent_Directory, Directory_Name) DO NOTHING",
+        (
+            parent_directory,
+            directory_name,
+            chrono::Utc::now().timestamp(),
+        ),
+    )
+    .map_err(|cause| BackupError::DatabaseInsert {
+        table: "Known_Directories".to_string(),
+        file: format!("{}/{}", parent_directory, directory_name),
+        cause,
+    })?;
+    Ok(())
+}
+
+// ============================================================================
+// Hash Cache Functions
+// ============================================================================
+
+/// The hash cached for a file, if it was taken at the same size, modification time and limit
+pub fn select_cached_hash(
+    file_key: &str,
+    file_size: u64,
+    modified_nanos: i64,
+    limit: HashLimit,
+) -> Result<Option<String>> {
+    let conn = get_connection()?;
+    conn.query_row(
+        "SELECT Hash FROM Hash_Cache
+                WHERE File_Key=?1 AND File_Size=?2 AND Modified_Nanos=?3 AND Hashed_Mebibytes=?4
+                    AND IFNULL(Tail_Mebibytes, 0)=?5",
+        (
+            file_key,
+            file_size as i64,
+            modified_nanos,
+            limit.max_mebibytes as i64,
+            limit.tail_mebibytes as i64,
+        ),
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|cause| BackupError::DatabaseQuery {
+        operation: format!("select cached hash of {}", file_key),
+        cause,
+    })
+}
+
+/// Cache a file's hash, replacing whatever was cached for it before
+pub fn insert_cached_hash(
+    file_key: &str,
+    file_size: u64,
+    modified_nanos: i64,
+    limit: HashLimit,
+    hash: &str,
+) -> Result<()> {
+    let conn = get_connection()?;
+    conn.execute(
+        "INSERT OR REPLACE INTO Hash_Cache
+                (File_Key, File_Size, Modified_Nanos, Hashed_Mebibytes, Tail_Mebibytes, Hash,
+                    Cached_At)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        (
+            file_key,
+            file_size as i64,
+            modified_nanos,
+            limit.max_mebibytes as i64,
+            limit.tail_mebibytes as i64,
+            hash,
+            chrono::Utc::now().timestamp(),
+        ),
+    )
+    .map_err(|cause| BackupError::DatabaseInsert {
+        table: "Hash_Cache".to_string(),
+        file: file_key.to_string(),
+        cause,
+    })?;
+    Ok(())
+}
+
+/// Drop cached hashes taken more than `max_age_days` ago, returning how many went
+pub fn prune_hash_cache(max_age_days: u64) -> Result<usize> {
+    let cutoff = chrono::Utc::now().timestamp() - (max_age_days * 86_400) as i64;
+    let conn = get_connection()?;
+    conn.execute("DELETE FROM Hash_Cache WHERE Cached_At < ?1", [cutoff])
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "prune hash cache".to_string(),
+            cause,
+        })
+}
+
+// ============================================================================
+// Run Backlog Functions
+// ============================================================================
+
+/// Source files earlier runs ran out of budget for, in the order they were left
+pub fn select_run_backlog() -> Result<Vec<String>> {
+    let conn = get_connection()?;
+    let mut stmt = conn
+        .prepare("SELECT File_Path FROM Run_Backlog ORDER BY Deferred_At, rowid")
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "select run backlog".to_string(),
+            cause,
+        })?;
+
+    let rows =
+        stmt.query_map([], |row| row.get(0))
+            .map_err(|cause| BackupError::DatabaseQuery {
+                operation: "select run backlog".to_string(),
+                cause,
+            })?;
+
+    rows.collect::<rusqlite::Result<Vec<String>>>()
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "collect run backlog".to_string(),
+            cause,
+        })
+}
+
+/// Drop the backlog files a run got to and add the ones it left, in one transaction
+///
+/// Files `offered` that the run itself left again are kept.
+pub fn replace_run_backlog(run_id: &str, offered: &[String], deferred: &[String]) -> Result<()> {
+    let insert_error = |file: &str| {
+        let file = file.to_string();
+        move |cause| BackupError::DatabaseInsert {
+            table: "Run_Backlog".to_string(),
+            file,
+            cause,
+        }
+    };
+    let conn = get_connection()?;
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(insert_error("transaction"))?;
+    for file in offered {
+        tx.execute(
+            "DELETE FROM Run_Backlog WHERE File_Path=?1 AND Run_ID != ?2",
+            (file, run_id),
+        )
+        .map_err(insert_error(file))?;
+    }
+    let now = chrono::Utc::now().timestamp();
+    for file in deferred {
+        tx.execute(
+            "INSERT OR REPLACE INTO Run_Backlog (File_Path, Run_ID, Deferred_At)
+                    VALUES (?1, ?2, ?3)",
+            (file, run_id, now),
+        )
+        .map_err(insert_error(file))?;
+    }
+    tx.commit().map_err(insert_error("transaction"))
+}
+
+// ============================================================================
+// Unclaimed Files Functions
+// ============================================================================
+
+/// Catalogue a destination file that was moved into the unclaimed area
+pub fn insert_unclaimed_file(file: &UnclaimedFile) -> Result<()> {
+    let conn = get_connection()?;
+    conn.execute(
+        "INSERT INTO Unclaimed_Files (Destination, Original_Path, Unclaimed_Path, Hash, File_Size, Moved_At)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        (
+            &file.destination,
+            &file.original_path,
+            &file.unclaimed_path,
+            &file.hash,
+            file.file_size,
+            file.moved_at,
+        ),
+    )
+    .map_err(|cause| BackupError::DatabaseInsert {
+        table: "Unclaimed_Files".to_string(),
+        file: file.original_path.clone(),
+        cause,
+    })?;
+    Ok(())
+}
+
+/// Get catalogued unclaimed files, newest first, optionally limited to one destination
+pub fn select_unclaimed_files(destination: Option<&str>) -> Result<Vec<UnclaimedFile>> {
+    let conn = get_connection()?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT Destination, Original_Path, Unclaimed_Path, Hash, File_Size, Moved_At
+                FROM Unclaimed_Files
+                WHERE ?1 IS NULL OR Destination = ?1
+                ORDER BY Moved_At DESC, ID DESC",
+        )
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "select unclaimed files".to_string(),
+            cause,
+        })?;
+
+    let rows = stmt
+        .query_map([destination], |row| {
+            Ok(UnclaimedFile {
+                destination: row.get(0)?,
+                original_path: row.get(1)?,
+                unclaimed_path: row.get(2)?,
+                hash: row.get(3)?,
+                file_size: row.get(4)?,
+                moved_at: row.get(5)?,
+            })
+        })
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "select unclaimed files".to_string(),
+            cause,
+        })?;
+
+    rows.collect::<rusqlite::Result<Vec<UnclaimedFile>>>()
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "collect unclaimed files".to_string(),
+            cause,
+        })
+}
+
+// ============================================================================
+// Run Files Functions
+// ============================================================================
+
+/// Record the outcome of one file copy for a run
+pub fn insert_run_file(run_id: &str, outcome: &FileOutcome) -> Result<()> {
+    let conn = get_connection()?;
+    conn.execute(
+        "INSERT INTO Run_Files
+                    (Run_ID, Source_Name, File_Path, Destination, Status, Bytes, Error, Physical_Bytes)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        (
+            run_id,
+            &outcome.source,
+            &outcome.file,
+            &outcome.destination,
+            outcome.status.as_str(),
+            outcome.bytes,
+            &outcome.error,
+            outcome.physical_bytes,
+        ),
+    )
+    .map_err(|cause| BackupError::DatabaseInsert {
+        table: "Run_Files".to_string(),
+        file: outcome.file.clone(),
+        cause,
+    })?;
+    Ok(())
+}
+
+/// Get one page of a run's file outcomes in the order they were recorded
+pub fn select_run_files(
+    run_id: &str,
+    status: Option<FileStatus>,
+    limit: usize,
+    offset: usize,
+) -> Result<Vec<FileOutcome>> {
+    let rows = query_run_files(
+        "SELECT ID, Source_Name, File_Path, Destination, Status, Bytes, Error, Physical_Bytes
+            FROM Run_Files
+            WHERE Run_ID = ?1 AND (?2 IS NULL OR Status = ?2)
+            ORDER BY ID
+            LIMIT ?3 OFFSET ?4",
+        (
+            run_id,
+            status.map(|s| s.as_str()),
+            limit as i64,
+            offset as i64,
+        ),
+    )?;
+    Ok(rows.into_iter().map(|(_, outcome)| outcome).collect())
+}
+
+/// Get up to `limit` file outcomes recorded after the row with ID `after_id`
+///
+/// Keyset pagination keeps each page cheap when walking runs with millions of rows.
+pub fn select_run_files_after(
+    run_id: &str,
+    status: Option<FileStatus>,
+    after_id: i64,
+    limit: usize,
+) -> Result<Vec<(i64, FileOutcome)>> {
+    query_run_files(
+        "SELECT ID, Source_Name, File_Path, Destination, Status, Bytes, Error, Physical_Bytes
+            FROM Run_Files
+            WHERE Run_ID = ?1 AND (?2 IS NULL OR Status = ?2) AND ID > ?3
+            ORDER BY ID
+            LIMIT ?4",
+        (run_id, status.map(|s| s.as_str()), after_id, limit as i64),
+    )
+}
+
+fn query_run_files<P: rusqlite::Params>(sql: &str, params: P) -> Result<Vec<(i64, FileOutcome)>> {
+    let conn = get_connection()?;
+    let mut stmt = conn
+        .prepare(sql)
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "select run files".to_string(),
+            cause,
+        })?;
+
+    let rows = stmt
+        .query_map(params, |row| {
+            let status: String = row.get(4)?;
+            Ok((
+                row.get(0)?,
+                FileOutcome {
+                    source: row.get(1)?,
+                    file: row.get(2)?,
+                    destination: row.get(3)?,
+                    status: FileStatus::parse(&status).unwrap_or(FileStatus::Failed),
+                    bytes: row.get(5)?,
+                    physical_bytes: row.get(7)?,
+                    error: row.get(6)?,
+                },
+            ))
+        })
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "select run files".to_string(),
+            cause,
+        })?;
+
+    rows.collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "collect run files".to_string(),
+            cause,
+        })
+}
+
+/// Count a run's file outcomes, optionally only those with the given status
+pub fn count_run_files(run_id: &str, status: Option<FileStatus>) -> Result<u64> {
+    let conn = get_connection()?;
+    conn.query_row(
+        "SELECT COUNT(*) FROM Run_Files WHERE Run_ID = ?1 AND (?2 IS NULL OR Status = ?2)",
+        (run_id, status.map(|s| s.as_str())),
+        |row| row.get(0),
+    )
+    .map_err(|cause| BackupError::DatabaseQuery {
+        operation: "count run files".to_string(),
+        cause,
+    })
+}
+
+/// Delete the file outcomes of a run that is no longer kept in history
+pub fn delete_run_files(run_id: &str) -> Result<usize> {
+    let conn = get_connection()?;
+    conn.execute("DELETE FROM Run_Files WHERE Run_ID = ?1", [run_id])
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "delete run files".to_string(),
+            cause,
+        })
+}
+
+/// When runs kept in history wrote each backup path: the finish times of the runs that copied or
+/// moved a file there, latest first
+pub fn select_backup_write_times() -> Result<HashMap<String, Vec<i64>>> {
+    let conn = get_connection()?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT Run_Files.Destination, Runs.Finished_At
+                FROM Run_Files
+                JOIN Runs ON Runs.ID = Run_Files.Run_ID
+                WHERE Run_Files.Status IN (?1, ?2) AND Runs.Dry_Run = 0
+                ORDER BY Runs.Finished_At DESC, Run_Files.ID DESC",
+        )
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "select backup write times".to_string(),
+            cause,
+        })?;
+    let rows = stmt
+        .query_map(
+            (FileStatus::Copied.as_str(), FileStatus::Moved.as_str()),
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)),
+        )
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "select backup write times".to_string(),
+            cause,
+        })?;
+
+    let mut times: HashMap<String, Vec<i64>> = HashMap::new();
+    for row in rows {
+        let (destination, finished_at) = row.map_err(|cause| BackupError::DatabaseQuery {
+            operation: "collect backup write times".to_string(),
+            cause,
+        })?;
+        times.entry(destination).or_default().push(finished_at);
+    }
+    Ok(times)
+}
+
+// ============================================================================
+// Run File Actions Functions
+// ============================================================================
+
+/// Record what a run found of a batch of source files, in one transaction
+pub fn insert_run_file_actions(run_id: &str, actions: &[RunFileAction]) -> Result<()> {
+    let insert_error = |file: &str| {
+        let file = file.to_string();
+        move |cause| BackupError::DatabaseInsert {
+            table: "Run_File_Actions".to_string(),
+            file,
+            cause,
+        }
+    };
+    let conn = get_connection()?;
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(insert_error("transaction"))?;
+    for action in actions {
+        tx.execute(
+            "INSERT INTO Run_File_Actions (Run_ID, Source_Name, File_Path, Action)
+                    VALUES (?1, ?2, ?3, ?4)",
+            (run_id, &action.source, &action.file, action.action.as_str()),
+        )
+        .map_err(insert_error(&action.file))?;
+    }
+    tx.commit().map_err(insert_error("transaction"))
+}
+
+/// Number of source files a run recorded actions for
+pub fn count_run_file_actions(run_id: &str) -> Result<u64> {
+    let conn = get_connection()?;
+    conn.query_row(
+        "SELECT COUNT(*) FROM Run_File_Actions WHERE Run_ID = ?1",
+        [run_id],
+        |row| row.get(0),
+    )
+    .map_err(|cause| BackupError::DatabaseQuery {
+        operation: "count run file actions".to_string(),
+        cause,
+    })
+}
+
+/// Which of `files` a run recorded actions for
+pub fn select_files_with_actions(run_id: &str, files: &[String]) -> Result<HashSet<String>> {
+    let query_error = |cause| BackupError::DatabaseQuery {
+        operation: "select files with run file actions".to_string(),
+        cause,
+    };
+    let conn = get_connection()?;
+    let mut stmt = conn
+        .prepare("SELECT 1 FROM Run_File_Actions WHERE Run_ID = ?1 AND File_Path = ?2 LIMIT 1")
+        .map_err(query_error)?;
+    let mut recorded = HashSet::new();
+    for file in files {
+        if stmt.exists((run_id, file)).map_err(query_error)? {
+            recorded.insert(file.clone());
+        }
+    }
+    Ok(recorded)
+}
+
+/// Delete the file actions of a run, e.g. one that failed partway
+pub fn delete_run_file_actions(run_id: &str) -> Result<usize> {
+    let conn = get_connection()?;
+    conn.execute("DELETE FROM Run_File_Actions WHERE Run_ID = ?1", [run_id])
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "delete run file actions".to_string(),
+            cause,
+        })
+}
+
+/// Keep the file actions of the `runs_kept` most recent finished runs that weren't dry runs,
+/// deleting the rest
+pub fn prune_run_file_actions(runs_kept: u64) -> Result<usize> {
+    let conn = get_connection()?;
+    conn.execute(
+        "DELETE FROM Run_File_Actions
+            WHERE Run_ID NOT IN (
+                SELECT ID FROM Runs WHERE Dry_Run = 0 ORDER BY Started_At DESC LIMIT ?1)",
+        [runs_kept as i64],
+    )
+    .map_err(|cause| BackupError::DatabaseQuery {
+        operation: "prune run file actions".to_string(),
+        cause,
+    })
+}
+
+/// The most recent finished run with file actions recorded
+pub fn select_last_file_action_run(excluding: &str) -> Result<Option<String>> {
+    let conn = get_connection()?;
+    conn.query_row(
+        "SELECT r.ID FROM Runs r
+            WHERE r.Dry_Run = 0 AND r.ID != ?1
+                AND EXISTS (SELECT 1 FROM Run_File_Actions a WHERE a.Run_ID = r.ID)
+            ORDER BY r.Started_At DESC
+            LIMIT 1",
+        [excluding],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|cause| BackupError::DatabaseQuery {
+        operation: "select last file action run".to_string(),
+        cause,
+    })
+}
+
+/// How many files of `source` the run `since_run` found, and how many of those the run `run_id`
+/// didn't
+pub fn count_files_gone_since(since_run: &str, run_id: &str, source: &str) -> Result<(u64, u64)> {
+    let conn = get_connection()?;
+    conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(NOT EXISTS (
+                SELECT 1 FROM Run_File_Actions c WHERE c.Run_ID = ?2 AND c.File_Path = a.File_Path
+            )), 0)
+            FROM Run_File_Actions a
+            WHERE a.Run_ID = ?1 AND a.Source_Name = ?3",
+        (since_run, run_id, source),
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .map_err(|cause| BackupError::DatabaseQuery {
+        operation: "count files gone since run".to_string(),
+        cause,
+    })
+}
+
+/// Compare the files the run `from` found with those of the later run `to`
+///
+/// Each group holds at most `limit` paths; the counts cover every file.
+pub fn select_run_diff(from: &RunRecord, to: &RunRecord, limit: usize) -> Result<RunDiff> {
+    let ids = (from.id.as_str(), to.id.as_str());
+    let (new_files, new) = query_diff_group(
+        "FROM Run_File_Actions f
+            WHERE f.Run_ID = ?2
+                AND NOT EXISTS (SELECT 1 FROM Run_File_Actions a
+                    WHERE a.Run_ID = ?1 AND a.File_Path = f.File_Path)",
+        ids,
+        limit,
+    )?;
+    // Copied again by any run in between, so a change isn't missed for being backed up already
+    let (changed_files, changed) = query_diff_group(
+        "FROM Run_File_Actions f
+            WHERE f.Run_ID = ?2
+                AND EXISTS (SELECT 1 FROM Run_File_Actions a
+                    WHERE a.Run_ID = ?1 AND a.File_Path = f.File_Path)
+                AND EXISTS (SELECT 1 FROM Run_File_Actions c JOIN Runs r ON r.ID = c.Run_ID
+                    WHERE c.File_Path = f.File_Path AND c.Action = 'updated' AND c.Run_ID != ?1
+                        AND r.Started_At BETWEEN ?3 AND ?4)",
+        (ids.0, ids.1, from.started_at, to.started_at),
+        limit,
+    )?;
+    // Sources the later run didn't back up say nothing about their files
+    let (deleted_files, deleted) = query_diff_group(
+        "FROM Run_File_Actions f
+            WHERE f.Run_ID = ?1
+                AND NOT EXISTS (SELECT 1 FROM Run_File_Actions b
+                    WHERE b.Run_ID = ?2 AND b.File_Path = f.File_Path)
+                AND f.Source_Name IN (SELECT Source_Name FROM Run_File_Actions WHERE Run_ID = ?2)",
+        ids,
+        limit,
+    )?;
+
+    let files_before = count_run_file_actions(&from.id)?;
+    Ok(RunDiff {
+        from_run: from.id.clone(),
+        to_run: to.id.clone(),
+        files_before,
+        files_after: count_run_file_actions(&to.id)?,
+        new_files,
+        changed_files,
+        deleted_files,
+        changed_percent: if files_before == 0 {
+            0.0
+        } else {
+            (changed_files + deleted_files) as f64 / files_before as f64 * 100.0
+        },
+        new,
+        changed,
+        deleted,
+    })
+}
+
+/// How many distinct files `from_where` selects as `f`, and the first `limit` of their paths
+fn query_diff_group<P: rusqlite::Params + Copy>(
+    from_where: &str,
+    params: P,
+    limit: usize,
+) -> Result<(u64, Vec<String>)> {
+    let query_error = |cause| BackupError::DatabaseQuery {
+        operation: "compare runs".to_string(),
+        cause,
+    };
+    let conn = get_connection()?;
+    let count = conn
+        .query_row(
+            &format!("SELECT COUNT(DISTINCT f.File_Path) {}", from_where),
+            params,
+            |row| row.get(0),
+        )
+        .map_err(query_error)?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT DISTINCT f.File_Path {} ORDER BY f.File_Path LIMIT {}",
+            from_where, limit
+        ))
+        .map_err(query_error)?;
+    let paths = stmt
+        .query_map(params, |row| row.get(0))
+        .map_err(query_error)?
+        .collect::<rusqlite::Result<Vec<String>>>()
+        .map_err(query_error)?;
+    Ok((count, paths))
+}
+
+/// Hold a catalog change in a run's journal until the run commits
+pub fn insert_catalog_write(run_id: &str, write: &CatalogWrite) -> Result<()> {
+    let json = serde_json::to_string(write).unwrap_or_default();
+    let conn = get_connection()?;
+    conn.execute(
+        "INSERT INTO Catalog_Journal (Run_ID, Write) VALUES (?1, ?2)",
+        (run_id, json),
+    )
+    .map_err(|cause| BackupError::DatabaseInsert {
+        table: "Catalog_Journal".to_string(),
+        file: run_id.to_string(),
+        cause,
+    })?;
+    Ok(())
+}
+
+/// The oldest `limit` changes in a run's journal, with their IDs, in the order they were made
+pub fn select_catalog_writes(run_id: &str, limit: usize) -> Result<Vec<(i64, CatalogWrite)>> {
+    let conn = get_connection()?;
+    let mut stmt = conn
+        .prepare("SELECT ID, Write FROM Catalog_Journal WHERE Run_ID = ?1 ORDER BY ID LIMIT ?2")
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "prepare select catalog writes".to_string(),
+            cause,
+        })?;
+    let rows = stmt
+        .query_map((run_id, limit as i64), |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })
+        .and_then(|rows| rows.collect::<std::result::Result<Vec<_>, _>>())
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "select catalog writes".to_string(),
+            cause,
+        })?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|(id, json)| match serde_json::from_str(&json) {
+            Ok(write) => Some((id, write)),
+            Err(e) => {
+                warn!("Skipping unreadable catalog journal entry {}: {}", id, e);
+                None
+            }
+        })
+        .collect())
+}
+
+/// Mark every change in a run's journal as committed, so it's promoted even after a crash
+pub fn commit_catalog_writes(run_id: &str) -> Result<usize> {
+    let conn = get_connection()?;
+    conn.execute(
+        "UPDATE Catalog_Journal SET Committed = 1 WHERE Run_ID = ?1",
+        [run_id],
+    )
+    .map_err(|cause| BackupError::DatabaseQuery {
+        operation: "commit catalog writes".to_string(),
+        cause,
+    })
+}
+
+/// Drop a run's journal entries up to and including `up_to`, or all of them
+pub fn delete_catalog_writes(run_id: &str, up_to: Option<i64>) -> Result<usize> {
+    let conn = get_connection()?;
+    conn.execute(
+        "DELETE FROM Catalog_Journal WHERE Run_ID = ?1 AND (?2 IS NULL OR ID <= ?2)",
+        (run_id, up_to),
+    )
+    .map_err(|cause| BackupError::DatabaseQuery {
+        operation: "delete catalog writes".to_string(),
+        cause,
+    })
+}
+
+/// Runs with changes left in the journal, oldest first, and whether each had committed
+pub fn select_catalog_journal_runs() -> Result<Vec<(String, bool)>> {
+    let conn = get_connection()?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT Run_ID, MAX(Committed) FROM Catalog_Journal
+                GROUP BY Run_ID
+                ORDER BY MIN(ID)",
+        )
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "prepare select catalog journal runs".to_string(),
+            cause,
+        })?;
+    stmt.query_map([], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? != 0)))
+        .and_then(|rows| rows.collect())
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "select catalog journal runs".to_string(),
+            cause,
+        })
+}
+
+// ============================================================================
+// Post-Process Results Functions
+// ============================================================================
+
+/// Store the verdict of a post-processor for one backup copy
+pub fn insert_post_process_result(result: &PostProcessResult) -> Result<()> {
+    let conn = get_connection()?;
+    conn.execute(
+        "INSERT INTO Post_Process_Results (Processor, Source_Path, Backup_Path, Status, Detail, Processed_At)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        (
+            &result.processor,
+            &result.source_path,
+            &result.backup_path,
+            result.status.as_str(),
+            &result.detail,
+            result.processed_at,
+        ),
+    )
+    .map_err(|cause| BackupError::DatabaseInsert {
+        table: "Post_Process_Results".to_string(),
+        file: result.backup_path.clone(),
+        cause,
+    })?;
+    Ok(())
+}
+
+/// Get one page of post-processor verdicts, newest first
+///
+/// Results can be narrowed to a single backup file and/or a status such as `flagged`.
+pub fn select_post_process_results(
+    backup_path: Option<&str>,
+    status: Option<ProcessorStatus>,
+    limit: usize,
+    offset: usize,
+) -> Result<Vec<PostProcessResult>> {
+    let conn = get_connection()?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT Processor, Source_Path, Backup_Path, Status, Detail, Processed_At
+                FROM Post_Process_Results
+                WHERE (?1 IS NULL OR Backup_Path = ?1) AND (?2 IS NULL OR Status = ?2)
+                ORDER BY Processed_At DESC, ID DESC
+                LIMIT ?3 OFFSET ?4",
+        )
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "select post-process results".to_string(),
+            cause,
+        })?;
+
+    let rows = stmt
+        .query_map(
+            (
+                backup_path,
+                status.map(|s| s.as_str()),
+                limit as i64,
+                offset as i64,
+            ),
+            |row| {
+                let status: String = row.get(3)?;
+                Ok(PostProcessResult {
+                    processor: row.get(0)?,
+                    source_path: row.get(1)?,
+                    backup_path: row.get(2)?,
+                    status: ProcessorStatus::parse(&status).unwrap_or(ProcessorStatus::Error),
+                    detail: row.get(4)?,
+                    processed_at: row.get(5)?,
+                })
+            },
+        )
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "select post-process results".to_string(),
+            cause,
+        })?;
+
+    rows.collect::<rusqlite::Result<Vec<PostProcessResult>>>()
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "collect post-process results".to_string(),
+            cause,
+        })
+}
+
+// ============================================================================
+// Runs Table Functions
+// ============================================================================
+
+/// Keep the outcome of a finished run
+pub fn insert_run(run: &RunRecord) -> Result<()> {
+    let conn = get_connection()?;
+    conn.execute(
+        "INSERT OR REPLACE INTO Runs (ID, Started_At, Finished_At, Status, Dry_Run, Files_Copied,
+                Bytes_Copied, Errors, Error, Note, Duration_Ms, Files_Processed, Bytes_Hashed,
+                Hash_Micros, Checkpoint, Anomalies)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+        rusqlite::params![
+            &run.id,
+            run.started_at,
+            run.finished_at,
+            run.status.as_str(),
+            run.dry_run,
+            run.files_copied,
+            run.bytes_copied,
+            run.errors,
+            &run.error,
+            &run.note,
+            run.timing.map(|t| t.duration_ms),
+            run.timing.map(|t| t.files_processed),
+            run.timing.map(|t| t.bytes_hashed),
+            run.timing.map(|t| t.hash_micros),
+            run.checkpoint,
+            (!run.anomalies.is_empty())
+                .then(|| serde_json::to_string(&run.anomalies).unwrap_or_default()),
+        ],
+    )
+    .map_err(|cause| BackupError::DatabaseInsert {
+        table: "Runs".to_string(),
+        file: run.id.clone(),
+        cause,
+    })?;
+    Ok(())
+}
+
+/// Columns read into a `RunRecord` by `run_from_row`
+const RUN_COLUMNS: &str = "ID, Started_At, Finished_At, Status, Dry_Run, Files_Copied,
+    Bytes_Copied, Errors, Error, Note, Duration_Ms, Files_Processed, Bytes_Hashed, Hash_Micros,
+    Checkpoint, Anomalies";
+
+fn run_from_row(row: &rusqlite::Row) -> rusqlite::Result<RunRecord> {
+    let status: String = row.get(3)?;
+    let duration_ms: Option<u64> = row.get(10)?;
+    let timing = match duration_ms {
+        Some(duration_ms) => Some(RunTiming {
+            duration_ms,
+            files_processed: row.get::<_, Option<u64>>(11)?.unwrap_or_default(),
+            bytes_hashed: row.get::<_, Option<u64>>(12)?.unwrap_or_default(),
+            hash_micros: row.get::<_, Option<u64>>(13)?.unwrap_or_default(),
+        }),
+        None => None,
+    };
+    Ok(RunRecord {
+        id: row.get(0)?,
+        started_at: row.get(1)?,
+        finished_at: row.get(2)?,
+        status: BackupStatus::parse(&status).unwrap_or(BackupStatus::Failed),
+        dry_run: row.get(4)?,
+        files_copied: row.get(5)?,
+        bytes_copied: row.get(6)?,
+        errors: row.get(7)?,
+        error: row.get(8)?,
+        note: row.get(9)?,
+        checkpoint: row.get::<_, Option<bool>>(14)?.unwrap_or_default(),
+        timing,
+        anomalies: row
+            .get::<_, Option<String>>(15)?
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default(),
+    })
+}
+
+/// The most recent finished runs, newest first
+pub fn select_runs(limit: usize) -> Result<Vec<RunRecord>> {
+    let conn = get_connection()?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {}
+                FROM Runs
+                ORDER BY Started_At DESC
+                LIMIT ?1",
+            RUN_COLUMNS
+        ))
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "select runs".to_string(),
+            cause,
+        })?;
+
+    let rows = stmt
+        .query_map([limit as i64], run_from_row)
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "select runs".to_string(),
+            cause,
+        })?;
+
+    rows.collect::<rusqlite::Result<Vec<RunRecord>>>()
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "collect runs".to_string(),
+            cause,
+        })
+}
+
+/// The finished run with this ID
+pub fn select_run(id: &str) -> Result<Option<RunRecord>> {
+    let conn = get_connection()?;
+    conn.query_row(
+        &format!("SELECT {} FROM Runs WHERE ID = ?1", RUN_COLUMNS),
+        [id],
+        run_from_row,
+    )
+    .optional()
+    .map_err(|cause| BackupError::DatabaseQuery {
+        operation: "select run".to_string(),
+        cause,
+    })
+}
+
+/// The latest `limit` runs with timing that started at or after `since`, oldest first
+pub fn select_timed_runs(since: Option<i64>, limit: usize) -> Result<Vec<RunRecord>> {
+    let conn = get_connection()?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT * FROM (
+                SELECT {}
+                    FROM Runs
+                    WHERE Duration_Ms IS NOT NULL AND (?1 IS NULL OR Started_At >= ?1)
+                    ORDER BY Started_At DESC
+                    LIMIT ?2)
+                ORDER BY Started_At",
+            RUN_COLUMNS
+        ))
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "select timed runs".to_string(),
+            cause,
+        })?;
+
+    let rows = stmt
+        .query_map((since, limit as i64), run_from_row)
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "select timed runs".to_string(),
+            cause,
+        })?;
+
+    rows.collect::<rusqlite::Result<Vec<RunRecord>>>()
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "collect timed runs".to_string(),
+            cause,
+        })
+}
+
+// ============================================================================
+// Active Run Functions
+// ============================================================================
+
+/// Remember the run that just started, replacing any earlier one
+pub fn save_active_run(run: &ActiveRun) -> Result<()> {
+    let conn = get_connection()?;
+    conn.execute(
+        "INSERT OR REPLACE INTO Active_Run (ID, Run_ID, Started_At, Dry_Run, Dry_Run_Full, Source,
+                Path, Status, Files_Processed, Total_Files, Bytes_Processed, Updated_At, Note)
+            VALUES (1, ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        (
+            &run.id,
+            run.started_at,
+            run.dry_run_mode == DryRunMode::Quick,
+            run.dry_run_mode == DryRunMode::Full,
+            &run.selection.source,
+            &run.selection.path,
+            run.status.as_str(),
+            run.files_processed,
+            run.total_files,
+            run.bytes_processed,
+            run.updated_at,
+            &run.note,
+        ),
+    )
+    .map_err(|cause| BackupError::DatabaseInsert {
+        table: "Active_Run".to_string(),
+        file: run.id.clone(),
+        cause,
+    })?;
+    Ok(())
+}
+
+/// Update the status and progress of the remembered run, if it is still `run_id`
+pub fn update_active_run(
+    run_id: &str,
+    status: &BackupStatus,
+    progress: Option<&BackupProgress>,
+) -> Result<()> {
+    let conn = get_connection()?;
+    conn.execute(
+        "UPDATE Active_Run
+            SET Status = ?2,
+                Files_Processed = coalesce(?3, Files_Processed),
+                Total_Files = coalesce(?4, Total_Files),
+                Bytes_Processed = coalesce(?5, Bytes_Processed),
+                Updated_At = ?6
+            WHERE Run_ID = ?1",
+        (
+            run_id,
+            status.as_str(),
+            progress.map(|p| p.files_processed),
+            progress.map(|p| p.total_files),
+            progress.and_then(|p| p.bytes_processed),
+            chrono::Utc::now().timestamp(),
+        ),
+    )
+    .map_err(|cause| BackupError::DatabaseUpdate {
+        table: "Active_Run".to_string(),
+        id: 1,
+        cause,
+    })?;
+    Ok(())
+}
+
+/// Forget the remembered run once it has finished, unless another run replaced it
+pub fn delete_active_run(run_id: &str) -> Result<()> {
+    let conn = get_connection()?;
+    conn.execute("DELETE FROM Active_Run WHERE Run_ID = ?1", [run_id])
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "delete active run".to_string(),
+            cause,
+        })?;
+    Ok(())
+}
+
+/// Take the remembered run out of the database; one left over at startup never finished
+pub fn take_active_run() -> Result<Option<ActiveRun>> {
+    let conn = get_connection()?;
+    let run = conn
+        .query_row(
+            "DELETE FROM Active_Run
+                RETURNING Run_ID, Started_At, Dry_Run, Dry_Run_Full, Source, Path, Status,
+                    Files_Processed, Total_Files, Bytes_Processed, Updated_At, Note",
+            [],
+            |row| {
+                let dry_run_mode = match (row.get(2)?, row.get(3)?) {
+                    (_, true) => DryRunMode::Full,
+                    (true, false) => DryRunMode::Quick,
+                    (false, false) => DryRunMode::None,
+                };
+                let status: String = row.get(6)?;
+                Ok(ActiveRun {
+                    id: row.get(0)?,
+                    started_at: row.get(1)?,
+                    dry_run_mode,
+                    selection: RunSelection {
+                        source: row.get(4)?,
+                        path: row.get(5)?,
+                        ..Default::default()
+                    },
+                    note: row.get(11)?,
+                    status: BackupStatus::parse(&status).unwrap_or(BackupStatus::Running),
+                    files_processed: row.get(7)?,
+                    total_files: row.get(8)?,
+                    bytes_processed: row.get(9)?,
+                    updated_at: row.get(10)?,
+                })
+            },
+        )
+        .optional()
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "take active run".to_string(),
+            cause,
+        })?;
+    Ok(run)
+}
+
+// ============================================================================
+// Jobs Table Functions
+// ============================================================================
+
+const JOB_COLUMNS: &str =
+    "ID, Name, Source, Path, Dry_Run, Dry_Run_Full, Schedule, Enabled, Next_Run,
+        Last_Run_ID, Last_Status, Last_Started_At, Last_Finished_At, Last_Error, Last_Success_At,
+        Created_At, Updated_At";
+
+fn job_from_row(row: &rusqlite::Row) -> rusqlite::Result<Job> {
+    let last_status: Option<String> = row.get(10)?;
+    let last_result = match (last_status, row.get::<_, Option<i64>>(11)?) {
+        (Some(status), Some(started_at)) => Some(JobResult {
+            backup_id: row.get(9)?,
+            status: BackupStatus::parse(&status).unwrap_or(BackupStatus::Failed),
+            started_at,
+            finished_at: row.get(12)?,
+            error: row.get(13)?,
+        }),
+        _ => None,
+    };
+
+    Ok(Job {
+        id: row.get(0)?,
+        definition: JobDefinition {
+            name: row.get(1)?,
+            source: row.get(2)?,
+            path: row.get(3)?,
+            dry_run: row.get(4)?,
+            dry_run_full: row.get(5)?,
+            schedule: row.get(6)?,
+            enabled: row.get(7)?,
+        },
+        next_run: row.get(8)?,
+        last_result,
+        last_success_at: row.get(14)?,
+        created_at: row.get(15)?,
+        updated_at: row.get(16)?,
+    })
+}
+
+fn query_jobs<P: rusqlite::Params>(sql: &str, params: P) -> Result<Vec<Job>> {
+    let conn = get_connection()?;
+    let mut stmt = conn
+        .prepare(sql)
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "select jobs".to_string(),
+            cause,
+        })?;
+
+    let rows =
+        stmt.query_map(params, job_from_row)
+            .map_err(|cause| BackupError::DatabaseQuery {
+                operation: "select jobs".to_string(),
+                cause,
+            })?;
+
+    rows.collect::<rusqlite::Result<Vec<Job>>>()
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "collect jobs".to_string(),
+            cause,
+        })
+}
+
+/// Store a new job definition and return its ID
+pub fn insert_job(definition: &JobDefinition, next_run: Option<i64>) -> Result<i64> {
+    let conn = get_connection()?;
+    let now = chrono::Utc::now().timestamp();
+    conn.execute(
+        "INSERT INTO Jobs (Name, Source, Path, Dry_Run, Dry_Run_Full, Schedule, Enabled, Next_Run,
+                Created_At, Updated_At)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?9)",
+        (
+            &definition.name,
+            &definition.source,
+            &definition.path,
+            definition.dry_run,
+            definition.dry_run_full,
+            &definition.schedule,
+            definition.enabled,
+            next_run,
+            now,
+        ),
+    )
+    .map_err(|cause| BackupError::DatabaseInsert {
+        table: "Jobs".to_string(),
+        file: definition.name.clone(),
+        cause,
+    })?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// All jobs, ordered by name
+pub fn select_jobs() -> Result<Vec<Job>> {
+    query_jobs(
+        &format!("SELECT {} FROM Jobs ORDER BY Name", JOB_COLUMNS),
+        (),
+    )
+}
+
+pub fn select_job(id: i64) -> Result<Option<Job>> {
+    Ok(query_jobs(
+        &format!("SELECT {} FROM Jobs WHERE ID = ?1", JOB_COLUMNS),
+        [id],
+    )?
+    .pop())
+}
+
+/// The job whose last run has this ID, if any
+pub fn select_job_for_run(backup_id: &str) -> Result<Option<Job>> {
+    Ok(query_jobs(
+        &format!("SELECT {} FROM Jobs WHERE Last_Run_ID = ?1", JOB_COLUMNS),
+        [backup_id],
+    )?
+    .pop())
+}
+
+/// Enabled jobs whose next run is at or before `now`, longest overdue first
+pub fn select_due_jobs(now: i64) -> Result<Vec<Job>> {
+    query_jobs(
+        &format!(
+            "SELECT {} FROM Jobs WHERE Enabled = 1 AND Next_Run <= ?1 ORDER BY Next_Run, ID",
+            JOB_COLUMNS
+        ),
+        [now],
+    )
+}
+
+/// Replace a job's definition; returns false if there is no job with this ID
+pub fn update_job(id: i64, definition: &JobDefinition, next_run: Option<i64>) -> Result<bool> {
+    let conn = get_connection()?;
+    let updated = conn
+        .execute(
+            "UPDATE Jobs SET Name = ?2, Source = ?3, Path = ?4, Dry_Run = ?5, Dry_Run_Full = ?6,
+                    Schedule = ?7, Enabled = ?8, Next_Run = ?9, Updated_At = ?10
+                    WHERE ID = ?1",
+            (
+                id,
+                &definition.name,
+                &definition.source,
+                &definition.path,
+                definition.dry_run,
+                definition.dry_run_full,
+                &definition.schedule,
+                definition.enabled,
+                next_run,
+                chrono::Utc::now().timestamp(),
+            ),
+        )
+        .map_err(|cause| BackupError::DatabaseUpdate {
+            table: "Jobs".to_string(),
+            id,
+            cause,
+        })?;
+    Ok(updated > 0)
+}
+
+/// Returns false if there is no job with this ID
+pub fn delete_job(id: i64) -> Result<bool> {
+    let conn = get_connection()?;
+    let deleted = conn
+        .execute("DELETE FROM Jobs WHERE ID = ?1", [id])
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "delete job".to_string(),
+            cause,
+        })?;
+    Ok(deleted > 0)
+}
+
+/// Record the run a job just started, or failed to start, and when it runs next
+pub fn record_job_launch(id: i64, result: &JobResult, next_run: Option<i64>) -> Result<()> {
+    let conn = get_connection()?;
+    conn.execute(
+        "UPDATE Jobs SET Last_Run_ID = ?2, Last_Status = ?3, Last_Started_At = ?4,
+                Last_Finished_At = ?5, Last_Error = ?6, Next_Run = ?7
+                WHERE ID = ?1",
+        (
+            id,
+            &result.backup_id,
+            result.status.as_str(),
+            result.started_at,
+            result.finished_at,
+            &result.error,
+            next_run,
+        ),
+    )
+    .map_err(|cause| BackupError::DatabaseUpdate {
+        table: "Jobs".to_string(),
+        id,
+        cause,
+    })?;
+    Ok(())
+}
+
+/// Record how a run finished on the job that started it, if any
+pub fn record_job_finished(
+    backup_id: &str,
+    status: &BackupStatus,
+    error: Option<&str>,
+) -> Result<()> {
+    let conn = get_connection()?;
+    conn.execute(
+        "UPDATE Jobs SET Last_Status = ?2, Last_Finished_At = ?3, Last_Error = ?4,
+                Last_Success_At = CASE WHEN ?2 = 'completed' THEN ?3 ELSE Last_Success_At END
+                WHERE Last_Run_ID = ?1",
+        (
+            backup_id,
+            status.as_str(),
+            chrono::Utc::now().timestamp(),
+            error,
+        ),
+    )
+    .map_err(|cause| BackupError::DatabaseQuery {
+        operation: "record job result".to_string(),
+        cause,
+    })?;
+    Ok(())
+}
+
+// ============================================================================
+// Prune Plan Functions
+// ============================================================================
+
+/// Store a plan with its files and return its ID
+pub fn insert_prune_plan(plan: &PrunePlan, files: &[PlannedFile]) -> Result<i64> {
+    let mut conn = get_connection()?;
+    let insert_error = |cause| BackupError::DatabaseInsert {
+        table: "Prune_Plans".to_string(),
+        file: plan.run_id.clone(),
+        cause,
+    };
+    let tx = conn.transaction().map_err(insert_error)?;
+    tx.execute(
+        "INSERT INTO Prune_Plans (Created_At, Run_ID, Status, Files, Bytes, Oldest_Modified_At,
+                Newest_Modified_At, Decided_At, Decided_By)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        rusqlite::params![
+            plan.created_at,
+            &plan.run_id,
+            plan.status.as_str(),
+            plan.files,
+            plan.bytes,
+            plan.oldest_modified_at,
+            plan.newest_modified_at,
+            plan.decided_at,
+            &plan.decided_by,
+        ],
+    )
+    .map_err(insert_error)?;
+    let id = tx.last_insert_rowid();
+
+    {
+        let mut stmt = tx
+            .prepare(
+                "INSERT INTO Prune_Plan_Files (Plan_ID, Destination, File_Path, File_Size, Modified_At)
+                    VALUES (?1, ?2, ?3, ?4, ?5)",
+            )
+            .map_err(insert_error)?;
+        for file in files {
+            stmt.execute((
+                id,
+                &file.destination,
+                &file.path,
+                file.file_size,
+                file.modified_at,
+            ))
+            .map_err(insert_error)?;
+        }
+    }
+    tx.commit().map_err(insert_error)?;
+    Ok(id)
+}
+
+const PRUNE_PLAN_COLUMNS: &str = "ID, Created_At, Run_ID, Status, Files, Bytes,
+    Oldest_Modified_At, Newest_Modified_At, Decided_At, Decided_By";
+
+fn prune_plan_from_row(row: &rusqlite::Row) -> rusqlite::Result<PrunePlan> {
+    let status: String = row.get(3)?;
+    Ok(PrunePlan {
+        id: row.get(0)?,
+        created_at: row.get(1)?,
+        run_id: row.get(2)?,
+        status: PlanStatus::parse(&status).unwrap_or(PlanStatus::Rejected),
+        files: row.get(4)?,
+        bytes: row.get(5)?,
+        oldest_modified_at: row.get(6)?,
+        newest_modified_at: row.get(7)?,
+        decided_at: row.get(8)?,
+        decided_by: row.get(9)?,
+    })
+}
+
+/// The most recent prune plans, newest first
+pub fn select_prune_plans(limit: usize) -> Result<Vec<PrunePlan>> {
+    let conn = get_connection()?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {} FROM Prune_Plans ORDER BY ID DESC LIMIT ?1",
+            PRUNE_PLAN_COLUMNS
+        ))
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "select prune plans".to_string(),
+            cause,
+        })?;
+
+    let rows = stmt
+        .query_map([limit as i64], prune_plan_from_row)
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "select prune plans".to_string(),
+            cause,
+        })?;
+
+    rows.collect::<rusqlite::Result<Vec<PrunePlan>>>()
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "collect prune plans".to_string(),
+            cause,
+        })
+}
+
+pub fn select_prune_plan(id: i64) -> Result<Option<PrunePlan>> {
+    let conn = get_connection()?;
+    conn.query_row(
+        &format!(
+            "SELECT {} FROM Prune_Plans WHERE ID = ?1",
+            PRUNE_PLAN_COLUMNS
+        ),
+        [id],
+        prune_plan_from_row,
+    )
+    .optional()
+    .map_err(|cause| BackupError::DatabaseQuery {
+        operation: "select prune plan".to_string(),
+        cause,
+    })
+}
+
+/// Files of a prune plan, ordered by path
+pub fn select_prune_plan_files(plan_id: i64) -> Result<Vec<PlannedFile>> {
+    let conn = get_connection()?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT Destination, File_Path, File_Size, Modified_At
+                FROM Prune_Plan_Files
+                WHERE Plan_ID = ?1
+                ORDER BY File_Path",
+        )
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "select prune plan files".to_string(),
+            cause,
+        })?;
+
+    let rows = stmt
+        .query_map([plan_id], |row| {
+            Ok(PlannedFile {
+                destination: row.get(0)?,
+                path: row.get(1)?,
+                file_size: row.get(2)?,
+                modified_at: row.get(3)?,
+            })
+        })
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "select prune plan files".to_string(),
+            cause,
+        })?;
+
+    rows.collect::<rusqlite::Result<Vec<PlannedFile>>>()
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "collect prune plan files".to_string(),
+            cause,
+        })
+}
+
+/// Settle a pending plan; returns false if there is no pending plan with this ID
+pub fn decide_prune_plan(id: i64, status: PlanStatus, decided_by: &str) -> Result<bool> {
+    let conn = get_connection()?;
+    let updated = conn
+        .execute(
+            "UPDATE Prune_Plans SET Status = ?2, Decided_At = ?3, Decided_By = ?4
+                WHERE ID = ?1 AND Status = 'pending'",
+            (
+                id,
+                status.as_str(),
+                chrono::Utc::now().timestamp(),
+                decided_by,
+            ),
+        )
+        .map_err(|cause| BackupError::DatabaseUpdate {
+            table: "Prune_Plans".to_string(),
+            id,
+            cause,
+        })?;
+    Ok(updated > 0)
+}
+
+/// Mark every pending plan as superseded, returning their IDs
+pub fn supersede_pending_prune_plans() -> Result<Vec<i64>> {
+    let conn = get_connection()?;
+    let mut stmt = conn
+        .prepare(
+            "UPDATE Prune_Plans SET Status = 'superseded', Decided_At = ?1, Decided_By = 'run'
+                WHERE Status = 'pending'
+                RETURNING ID",
+        )
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "supersede prune plans".to_string(),
+            cause,
+        })?;
+
+    let rows = stmt
+        .query_map([chrono::Utc::now().timestamp()], |row| row.get(0))
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "supersede prune plans".to_string(),
+            cause,
+        })?;
+
+    rows.collect::<rusqlite::Result<Vec<i64>>>()
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "supersede prune plans".to_string(),
+            cause,
+        })
+}
+
+// ============================================================================
+// User and Session Functions
+// ============================================================================
+
+/// Number of web UI users; with none, the web UI and API don't ask anyone to sign in
+pub fn count_users() -> Result<u64> {
+    let conn = get_connection()?;
+    conn.query_row("SELECT COUNT(*) FROM Users", [], |row| row.get(0))
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "count users".to_string(),
+            cause,
+        })
+}
+
+/// Add a user; returns false if one with the same name exists
+pub fn insert_user(user: &User, password_hash: &str) -> Result<bool> {
+    let conn = get_connection()?;
+    let inserted = conn
+        .execute(
+            "INSERT OR IGNORE INTO Users (Name, Role, Password_Hash, Created_At)
+                VALUES (?1, ?2, ?3, ?4)",
+            (
+                &user.name,
+                user.role.as_str(),
+                password_hash,
+                user.created_at,
+            ),
+        )
+        .map_err(|cause| BackupError::DatabaseInsert {
+            table: "Users".to_string(),
+            file: user.name.clone(),
+            cause,
+        })?;
+    Ok(inserted > 0)
+}
+
+/// Replace a user's password and sign them out everywhere; returns false if there is no such user
+pub fn update_user_password(name: &str, password_hash: &str) -> Result<bool> {
+    let conn = get_connection()?;
+    let updated = conn
+        .execute(
+            "UPDATE Users SET Password_Hash = ?2 WHERE Name = ?1",
+            (name, password_hash),
+        )
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "update user password".to_string(),
+            cause,
+        })?;
+    delete_user_sessions(&conn, name)?;
+    Ok(updated > 0)
+}
+
+/// Remove a user and their sessions; returns false if there is no such user
+pub fn delete_user(name: &str) -> Result<bool> {
+    let conn = get_connection()?;
+    let deleted = conn
+        .execute("DELETE FROM Users WHERE Name = ?1", [name])
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "delete user".to_string(),
+            cause,
+        })?;
+    delete_user_sessions(&conn, name)?;
+    Ok(deleted > 0)
+}
+
+fn delete_user_sessions(conn: &rusqlite::Connection, name: &str) -> Result<()> {
+    conn.execute("DELETE FROM Sessions WHERE User_Name = ?1", [name])
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "delete user sessions".to_string(),
+            cause,
+        })?;
+    Ok(())
+}
+
+fn user_from_row(row: &rusqlite::Row) -> rusqlite::Result<User> {
+    let role: String = row.get(1)?;
+    Ok(User {
+        name: row.get(0)?,
+        role: Role::parse(&role).unwrap_or(Role::Viewer),
+        created_at: row.get(2)?,
+    })
+}
+
+/// Every user, by name
+pub fn select_users() -> Result<Vec<User>> {
+    let conn = get_connection()?;
+    let mut stmt = conn
+        .prepare("SELECT Name, Role, Created_At FROM Users ORDER BY Name")
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "select users".to_string(),
+            cause,
+        })?;
+
+    let rows = stmt
+        .query_map([], user_from_row)
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "select users".to_string(),
+            cause,
+        })?;
+
+    rows.collect::<rusqlite::Result<Vec<User>>>()
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "collect users".to_string(),
+            cause,
+        })
+}
+
+/// A user with their password hash, to check a sign-in against
+pub fn select_user_credentials(name: &str) -> Result<Option<(User, String)>> {
+    let conn = get_connection()?;
+    conn.query_row(
+        "SELECT Name, Role, Created_At, Password_Hash FROM Users WHERE Name = ?1",
+        [name],
+        |row| Ok((user_from_row(row)?, row.get(3)?)),
+    )
+    .optional()
+    .map_err(|cause| BackupError::DatabaseQuery {
+        operation: "select user credentials".to_string(),
+        cause,
+    })
+}
+
+/// Start a session, dropping any that have expired
+pub fn insert_session(
+    token_hash: &str,
+    name: &str,
+    created_at: i64,
+    expires_at: i64,
+) -> Result<()> {
+    let conn = get_connection()?;
+    conn.execute("DELETE FROM Sessions WHERE Expires_At <= ?1", [created_at])
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "delete expired sessions".to_string(),
+            cause,
+        })?;
+    conn.execute(
+        "INSERT INTO Sessions (Token_Hash, User_Name, Created_At, Expires_At)
+            VALUES (?1, ?2, ?3, ?4)",
+        (token_hash, name, created_at, expires_at),
+    )
+    .map_err(|cause| BackupError::DatabaseInsert {
+        table: "Sessions".to_string(),
+        file: name.to_string(),
+        cause,
+    })?;
+    Ok(())
+}
+
+/// The user a session that hasn't expired by `now` belongs to
+pub fn select_session_user(token_hash: &str, now: i64) -> Result<Option<User>> {
+    let conn = get_connection()?;
+    conn.query_row(
+        "SELECT Users.Name, Users.Role, Users.Created_At FROM Sessions
+            JOIN Users ON Users.Name = Sessions.User_Name
+            WHERE Sessions.Token_Hash = ?1 AND Sessions.Expires_At > ?2",
+        (token_hash, now),
+        user_from_row,
+    )
+    .optional()
+    .map_err(|cause| BackupError::DatabaseQuery {
+        operation: "select session user".to_string(),
+        cause,
+    })
+}
+
+pub fn delete_session(token_hash: &str) -> Result<()> {
+    let conn = get_connection()?;
+    conn.execute("DELETE FROM Sessions WHERE Token_Hash = ?1", [token_hash])
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "delete session".to_string(),
+            cause,
+        })?;
+    Ok(())
+}
+
+// ============================================================================
+// Tombstone Functions
+// ============================================================================
+
+/// Record source files found gone from their paths
+pub fn insert_tombstones(tombstones: &[Tombstone]) -> Result<()> {
+    let insert_error = |file: &str| {
+        let file = file.to_string();
+        move |cause| BackupError::DatabaseInsert {
+            table: "Tombstones".to_string(),
+            file,
+            cause,
+        }
+    };
+    let conn = get_connection()?;
+    let tx = conn
+        .unchecked_transaction()
+        .map_err(insert_error("transaction"))?;
+    for tombstone in tombstones {
+        tx.execute(
+            "INSERT INTO Tombstones (File_Path, Hash, File_Size, Deleted_At, Run_ID, Returned_At)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            (
+                &tombstone.path,
+                &tombstone.hash,
+                tombstone.file_size,
+                tombstone.deleted_at,
+                &tombstone.run_id,
+                tombstone.returned_at,
+            ),
+        )
+        .map_err(insert_error(&tombstone.path))?;
+    }
+    tx.commit().map_err(insert_error("transaction"))
+}
+
+/// Close the open tombstones of these paths, found with a file at them again at `returned_at`
+pub fn close_tombstones(paths: &[String], returned_at: i64) -> Result<()> {
+    let update_error = |cause| BackupError::DatabaseQuery {
+        operation: "close tombstones".to_string(),
+        cause,
+    };
+    let conn = get_connection()?;
+    let tx = conn.unchecked_transaction().map_err(update_error)?;
+    for path in paths {
+        tx.execute(
+            "UPDATE Tombstones SET Returned_At = ?1 WHERE File_Path = ?2 AND Returned_At IS NULL",
+            (returned_at, path),
+        )
+        .map_err(update_error)?;
+    }
+    tx.commit().map_err(update_error)
+}
 
-    CREATE INDEX IF NOT EXISTS Logs_Timestamp_Level_index
-            on Logs (Timestamp DESC, Level);
+/// Every tombstone, or only those of files still gone, oldest first
+pub fn select_tombstones(open_only: bool) -> Result<Vec<Tombstone>> {
+    let query_error = |cause| BackupError::DatabaseQuery {
+        operation: "select tombstones".to_string(),
+        cause,
+    };
+    let conn = get_connection()?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT File_Path, Hash, File_Size, Deleted_At, Run_ID, Returned_At
+                FROM Tombstones
+                WHERE ?1 = 0 OR Returned_At IS NULL
+                ORDER BY Deleted_At, File_Path",
+        )
+        .map_err(query_error)?;
+    let rows = stmt
+        .query_map([open_only], |row| {
+            Ok(Tombstone {
+                path: row.get(0)?,
+                hash: row.get(1)?,
+                file_size: row.get(2)?,
+                deleted_at: row.get(3)?,
+                run_id: row.get(4)?,
+                returned_at: row.get(5)?,
+            })
+        })
+        .map_err(query_error)?;
+    rows.collect::<rusqlite::Result<_>>().map_err(query_error)
+}
 
-    COMMIT;";
+// ============================================================================
+// Scrub Problem Functions
+// ============================================================================
 
+/// Record a backup a scrub found damaged or missing, replacing what was found before
+pub fn upsert_scrub_problem(problem: &ScrubProblem) -> Result<()> {
     let conn = get_connection()?;
-    conn.execute_batch(setup_queries)
-        .map_err(|cause| BackupError::DatabaseQuery {
-            operation: "create tables".to_string(),
-            cause,
-        })?;
-    info!("Database schema initialized successfully");
+    conn.execute(
+        "INSERT INTO Scrub_Problems (Backup_Path, Source_Path, Kind, Detail, Found_At)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            ON CONFLICT (Backup_Path) DO UPDATE SET
+                Source_Path = excluded.Source_Path,
+                Kind = excluded.Kind,
+                Detail = excluded.Detail,
+                Found_At = excluded.Found_At",
+        (
+            &problem.backup,
+            &problem.source,
+            problem.kind.as_str(),
+            &problem.detail,
+            problem.found_at,
+        ),
+    )
+    .map_err(|cause| BackupError::DatabaseInsert {
+        table: "Scrub_Problems".to_string(),
+        file: problem.backup.clone(),
+        cause,
+    })?;
     Ok(())
 }
 
-pub fn select_source(source_file: &str, source_path: &str) -> rusqlite::Result<Option<SourceRow>> {
-    let conn = get_connection().map_err(|_| Error::InvalidParameterName("pool".to_string()))?;
-    let mut query = conn.prepare(
-        "SELECT *
-                FROM Source_Files
-                WHERE File_Name=?1
-                    AND File_Path=?2",
-    )?;
-    query
-        .query_row([source_file, source_path], |row| {
-            Ok(SourceRow {
-                id: row.get(0)?,
-                file_name: row.get(1)?,
-                file_path: row.get(2)?,
-                hash: row.get(3)?,
-                file_size: row.get(4)?,
-                last_modified: Duration::from_secs(row.get(5)?),
-            })
-        })
-        .optional()
+/// Forget the problem recorded for a backup, once it is intact again
+pub fn delete_scrub_problem(backup_path: &str) -> Result<()> {
+    let conn = get_connection()?;
+    conn.execute(
+        "DELETE FROM Scrub_Problems WHERE Backup_Path = ?1",
+        [backup_path],
+    )
+    .map_err(|cause| BackupError::DatabaseQuery {
+        operation: format!("delete scrub problem of {}", backup_path),
+        cause,
+    })?;
+    Ok(())
 }
 
-pub fn select_backed_up_file(
-    filename: &str,
-    filepath: &str,
-) -> rusqlite::Result<Option<BackedUpFile>> {
-    let conn = get_connection().map_err(|_| Error::InvalidParameterName("pool".to_string()))?;
-    let mut query = conn.prepare(
-        "SELECT bf.File_Name, bf.File_Path, bf.Last_Modified, sf.Hash
-            FROM Backup_Files bf
-            LEFT JOIN Source_Files sf
-            ON sf.ID = bf.Source_ID
-            WHERE bf.File_Name=?1 AND bf.File_Path=?2",
-    )?;
-    query
-        .query_row([filename, filepath], |row| {
-            Ok(BackedUpFile {
-                file_name: row.get(0)?,
-                file_path: row.get(1)?,
-                last_modified: Duration::from_secs(row.get(2)?),
-                hash: row.get(3)?,
+/// Every backup scrubs found damaged or missing and that hasn't been repaired, by path
+pub fn select_scrub_problems() -> Result<Vec<ScrubProblem>> {
+    let query_error = |cause| BackupError::DatabaseQuery {
+        operation: "select scrub problems".to_string(),
+        cause,
+    };
+    let conn = get_connection()?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT Backup_Path, Source_Path, Kind, Detail, Found_At
+                FROM Scrub_Problems ORDER BY Backup_Path",
+        )
+        .map_err(query_error)?;
+    let rows = stmt
+        .query_map([], |row| {
+            let kind: String = row.get(2)?;
+            Ok(ScrubProblem {
+                backup: row.get(0)?,
+                source: row.get(1)?,
+                kind: ScrubProblemKind::parse(&kind).unwrap_or(ScrubProblemKind::Unreadable),
+                detail: row.get(3)?,
+                found_at: row.get(4)?,
             })
         })
-        .optional()
+        .map_err(query_error)?;
+    rows.collect::<rusqlite::Result<_>>().map_err(query_error)
 }
 
-pub fn insert_source_row(source_row: &SourceRow) -> Result<i32> {
-    let conn = get_connection()?;
-    debug!(
-        "Inserting source record: {}/{}",
-        source_row.file_path, source_row.file_name
-    );
+// ============================================================================
+// Destination Marker Functions
+// ============================================================================
 
+/// ID of the marker recorded for the destination at `path`, if one was
+pub fn select_destination_marker(path: &str) -> Result<Option<String>> {
+    let conn = get_connection()?;
     conn.query_row(
-        "INSERT INTO Source_Files (File_Name, File_Path, Hash, File_Size, Last_Modified)
-         VALUES (?1, ?2, ?3, ?4, ?5)
-         ON CONFLICT (File_Name, File_Path) DO UPDATE SET
-             Hash = excluded.Hash,
-             File_Size = excluded.File_Size,
-             Last_Modified = excluded.Last_Modified
-         RETURNING ID",
-        (
-            &source_row.file_name,
-            &source_row.file_path,
-            &source_row.hash,
-            &source_row.file_size,
-            source_row.last_modified.as_secs(),
-        ),
+        "SELECT Marker_ID FROM Destination_Markers WHERE Path = ?1",
+        [path],
         |row| row.get(0),
     )
-    .map_err(|cause| BackupError::DatabaseInsert {
-        table: "Source_Files".to_string(),
-        file: format!("{}/{}", source_row.file_path, source_row.file_name),
+    .optional()
+    .map_err(|cause| BackupError::DatabaseQuery {
+        operation: format!("select destination marker of {}", path),
         cause,
     })
 }
 
-pub fn update_source_last_modified(row_id: i32, last_modified: &Duration) -> Result<()> {
+/// Record the ID of the marker in the destination at `path`, clearing any mismatch found there
+pub fn insert_destination_marker(path: &str, marker_id: &str) -> Result<()> {
     let conn = get_connection()?;
     conn.execute(
-        "UPDATE Source_Files SET Last_Modified=?1 WHERE ID=?2",
-        (last_modified.as_secs(), row_id),
+        "INSERT INTO Destination_Markers (Path, Marker_ID, Recorded_At) VALUES (?1, ?2, ?3)
+            ON CONFLICT (Path) DO UPDATE SET
+                Marker_ID = excluded.Marker_ID,
+                Recorded_At = excluded.Recorded_At,
+                Found_ID = NULL,
+                Mismatch_At = NULL",
+        (path, marker_id, chrono::Utc::now().timestamp()),
     )
-    .map_err(|cause| BackupError::DatabaseUpdate {
-        table: "Source_Files".to_string(),
-        id: row_id as i64,
+    .map_err(|cause| BackupError::DatabaseInsert {
+        table: "Destination_Markers".to_string(),
+        file: path.to_string(),
         cause,
     })?;
     Ok(())
 }
 
-pub fn update_source_row(
-    row_id: i32,
-    hash: &String,
-    file_size: &u64,
-    last_modified: &Duration,
-) -> Result<()> {
+/// Record that the destination at `path` held `found_id`, or no marker, instead of its own
+pub fn update_destination_mismatch(path: &str, found_id: Option<&str>) -> Result<()> {
     let conn = get_connection()?;
     conn.execute(
-        "UPDATE Source_Files SET Hash=?1, File_Size=?2, Last_Modified=?3 WHERE ID=?4",
-        (hash, file_size, last_modified.as_secs(), row_id),
+        "UPDATE Destination_Markers SET Found_ID = ?2, Mismatch_At = ?3 WHERE Path = ?1",
+        (path, found_id, chrono::Utc::now().timestamp()),
     )
-    .map_err(|cause| BackupError::DatabaseUpdate {
-        table: "Source_Files".to_string(),
-        id: row_id as i64,
+    .map_err(|cause| BackupError::DatabaseQuery {
+        operation: format!("record destination mismatch of {}", path),
         cause,
     })?;
     Ok(())
 }
 
-pub fn insert_backup_row(backup_row: BackupRow) -> Result<()> {
+/// Forget a mismatch found at `path`, once its own marker is back
+pub fn clear_destination_mismatch(path: &str) -> Result<()> {
     let conn = get_connection()?;
     conn.execute(
-        "INSERT INTO Backup_Files (Source_ID, File_Name, File_Path, Last_Modified)
-                VALUES (?1, ?2, ?3, ?4)
-                ON CONFLICT (File_Name, File_Path) DO UPDATE SET
-                Source_ID=excluded.Source_ID,
-                Last_Modified=excluded.Last_Modified;",
-        (
-            backup_row.source_id,
-            &backup_row.file_name,
-            &backup_row.file_path,
-            backup_row.last_modified.as_secs(),
-        ),
+        "UPDATE Destination_Markers SET Found_ID = NULL, Mismatch_At = NULL
+            WHERE Path = ?1 AND Mismatch_At IS NOT NULL",
+        [path],
     )
-    .map_err(|cause| BackupError::DatabaseInsert {
-        table: "Backup_Files".to_string(),
-        file: backup_row.file_name.clone(),
+    .map_err(|cause| BackupError::DatabaseQuery {
+        operation: format!("clear destination mismatch of {}", path),
         cause,
     })?;
-    debug!("Inserted backup record: {}", backup_row.file_name);
     Ok(())
 }
 
+/// Destinations whose last check found another marker or none, by path
+pub fn select_destination_mismatches() -> Result<Vec<DestinationMismatch>> {
+    let conn = get_connection()?;
+    let query_error = |cause| BackupError::DatabaseQuery {
+        operation: "select destination mismatches".to_string(),
+        cause,
+    };
+    let mut stmt = conn
+        .prepare(
+            "SELECT Path, Marker_ID, Found_ID, Mismatch_At FROM Destination_Markers
+                WHERE Mismatch_At IS NOT NULL
+                ORDER BY Path",
+        )
+        .map_err(query_error)?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok(DestinationMismatch {
+                path: row.get(0)?,
+                recorded_id: row.get(1)?,
+                found_id: row.get(2)?,
+                found_at: row.get(3)?,
+            })
+        })
+        .map_err(query_error)?;
+    rows.collect::<rusqlite::Result<_>>().map_err(query_error)
+}
+
 // ============================================================================
-// Logs Table Functions
+// Audit Log Functions
 // ============================================================================
 
-/// Insert a log entry into the Logs table
-pub fn insert_log_entry(level: &str, message: &str, source: Option<&str>) -> Result<()> {
+pub fn insert_audit_event(event: &AuditEvent) -> Result<()> {
     let conn = get_connection()?;
-    let timestamp = chrono::Utc::now().timestamp();
-
     conn.execute(
-        "INSERT INTO Logs (Timestamp, Level, Message, Source) VALUES (?1, ?2, ?3, ?4)",
-        (timestamp, level, message, source),
+        "INSERT INTO Audit_Log (At, Action, Actor, Detail) VALUES (?1, ?2, ?3, ?4)",
+        (event.at, &event.action, &event.actor, &event.detail),
     )
     .map_err(|cause| BackupError::DatabaseInsert {
-        table: "Logs".to_string(),
-        file: message.to_string(),
+        table: "Audit_Log".to_string(),
+        file: event.action.clone(),
         cause,
     })?;
+    Ok(())
+}
+
+/// The most recent audit events, newest first
+pub fn select_audit_events(limit: usize) -> Result<Vec<AuditEvent>> {
+    let conn = get_connection()?;
+    let mut stmt = conn
+        .prepare("SELECT At, Action, Actor, Detail FROM Audit_Log ORDER BY ID DESC LIMIT ?1")
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "select audit events".to_string(),
+            cause,
+        })?;
+
+    let rows = stmt
+        .query_map([limit as i64], |row| {
+            Ok(AuditEvent {
+                at: row.get(0)?,
+                action: row.get(1)?,
+                actor: row.get(2)?,
+                detail: row.get(3)?,
+            })
+        })
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "select audit events".to_string(),
+            cause,
+        })?;
+
+    rows.collect::<rusqlite::Result<Vec<AuditEvent>>>()
+        .map_err(|cause| BackupError::DatabaseQuery {
+            operation: "collect audit events".to_string(),
+            cause,
+        })
+}
+
+// ============================================================================
+// Logs Table Functions
+// ============================================================================
+
+/// Insert log entries into the Logs table in one transaction, setting their IDs to the new rows
+pub fn insert_log_entries(entries: &mut [LogRow]) -> Result<()> {
+    let mut conn = get_connection()?;
+    let count = entries.len();
+    let insert_error = |cause| BackupError::DatabaseInsert {
+        table: "Logs".to_string(),
+        file: format!("{} log entries", count),
+        cause,
+    };
+
+    let tx = conn.transaction().map_err(insert_error)?;
+    {
+        let mut stmt = tx
+            .prepare(
+                "INSERT INTO Logs (Timestamp, Level, Message, Context, Source) VALUES (?1, ?2, ?3, ?4, ?5)",
+            )
+            .map_err(insert_error)?;
+        for entry in entries.iter_mut() {
+            entry.id = stmt
+                .insert((
+                    entry.timestamp,
+                    &entry.level,
+                    &entry.message,
+                    &entry.context,
+                    &entry.source,
+                ))
+                .map_err(insert_error)?;
+        }
+    }
+    tx.commit().map_err(insert_error)?;
 
     Ok(())
 }
 
-/// Query logs with optional filtering
+/// Query logs with optional filtering, `run` matching the run ID recorded in their context
 pub fn query_logs(
     level: Option<&str>,
     since: Option<i64>,
     search: Option<&str>,
+    run: Option<&str>,
     limit: Option<usize>,
     offset: Option<usize>,
 ) -> Result<Vec<LogRow>> {
@@ -337,6 +3895,11 @@ pub fn query_logs(
         params.push(Box::new(format!("%{}%", search_term)));
     }
 
+    if let Some(run_id) = run {
+        query.push_str(" AND json_extract(Context, '$.run_id') = ?");
+        params.push(Box::new(run_id.to_string()));
+    }
+
     query.push_str(" ORDER BY Timestamp DESC");
 
     if let Some(lim) = limit {
@@ -381,6 +3944,34 @@ pub fn query_logs(
         })
 }
 
+/// Delete log entries older than `retention_days`, then all but the newest `max_rows`
+pub fn prune_logs(retention_days: Option<u64>, max_rows: Option<u64>) -> Result<usize> {
+    let conn = get_connection()?;
+    let prune_error = |cause| BackupError::DatabaseQuery {
+        operation: "prune logs".to_string(),
+        cause,
+    };
+
+    let mut deleted = 0;
+    if let Some(days) = retention_days {
+        let cutoff = chrono::Utc::now().timestamp() - (days * 86_400) as i64;
+        deleted += conn
+            .execute("DELETE FROM Logs WHERE Timestamp < ?1", [cutoff])
+            .map_err(prune_error)?;
+    }
+    if let Some(rows) = max_rows {
+        deleted += conn
+            .execute(
+                "DELETE FROM Logs WHERE ID <= (SELECT ID FROM Logs ORDER BY ID DESC LIMIT 1 OFFSET ?1)",
+                [rows as i64],
+            )
+            .map_err(prune_error)?;
+    }
+
+    debug!("Pruned {} log entries", deleted);
+    Ok(deleted)
+}
+
 /// Delete all log entries
 pub fn delete_all_logs() -> Result<usize> {
     let conn = get_connection()?;
@@ -402,101 +3993,289 @@ pub fn delete_all_logs() -> Result<usize> {
 
 /// Get storage overview statistics from database
 pub fn get_storage_overview(destinations: &[String]) -> Result<StorageStats> {
-    let conn = get_connection()?;
-
     // Get total source files and size
-    let (total_files, total_size): (u64, u64) = conn
-        .query_row(
-            "SELECT COUNT(*), COALESCE(SUM(File_Size), 0) FROM Source_Files",
-            [],
-            |row| Ok((row.get(0)?, row.get(1)?)),
-        )
-        .map_err(|cause| BackupError::DatabaseQuery {
-            operation: "get total source stats".to_string(),
-            cause,
-        })?;
+    let (mut total_files, mut total_size) = (0, 0);
+    for conn in catalog_connections(false)? {
+        let (files, size): (u64, u64) = conn
+            .query_row(
+                "SELECT COUNT(*), COALESCE(SUM(File_Size), 0) FROM Source_Files",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|cause| BackupError::DatabaseQuery {
+                operation: "get total source stats".to_string(),
+                cause,
+            })?;
+        total_files += files;
+        total_size += size;
+    }
+    let content = get_content_stats()?;
+
+    // Get per-destination stats
+    let mut dest_stats = Vec::new();
+    let connections = catalog_connections(true)?;
+
+    for dest in destinations {
+        let (mut count, mut size) = (0, 0);
+        for conn in &connections {
+            let (files, bytes): (u64, u64) = conn
+                .query_row(
+                    "SELECT COUNT(DISTINCT bf.Source_ID), COALESCE(SUM(sf.File_Size), 0)
+                     FROM Backup_Files bf
+                     INNER JOIN Source_Files sf ON bf.Source_ID = sf.ID
+                     WHERE bf.File_Path LIKE ?1 || '%'",
+                    [dest],
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .map_err(|cause| BackupError::DatabaseQuery {
+                    operation: format!("get storage stats for {}", dest),
+                    cause,
+                })?;
+            count += files;
+            size += bytes;
+        }
+
+        dest_stats.push(DestinationStorageStats {
+            destination_root: dest.clone(),
+            file_count: count,
+            total_size: size,
+        });
+    }
+
+    Ok(StorageStats {
+        total_source_files: total_files,
+        total_source_size: total_size,
+        destination_stats: dest_stats,
+        content,
+    })
+}
+
+/// How much of the source content is unique; with per-source shards each file's distinct hashes
+/// are merged, so content two sources share is counted once
+fn get_content_stats() -> Result<ContentStats> {
+    let connections = catalog_connections(false)?;
+    let query_error = |cause| BackupError::DatabaseQuery {
+        operation: "get content stats".to_string(),
+        cause,
+    };
+    if let [conn] = connections.as_slice() {
+        return conn
+            .query_row(
+                "SELECT COALESCE(SUM(Files), 0), COALESCE(SUM(Total), 0), COUNT(*),
+                        COALESCE(SUM(Size), 0)
+                 FROM (SELECT COUNT(*) AS Files, SUM(File_Size) AS Total, MAX(File_Size) AS Size
+                       FROM Source_Files
+                       WHERE Hash IS NOT NULL AND Hash != ''
+                       GROUP BY Hash)",
+                [],
+                |row| {
+                    Ok(ContentStats {
+                        hashed_files: row.get(0)?,
+                        hashed_size: row.get(1)?,
+                        distinct_hashes: row.get(2)?,
+                        unique_size: row.get(3)?,
+                    })
+                },
+            )
+            .map_err(query_error);
+    }
+
+    let mut content = ContentStats::default();
+    let mut sizes: HashMap<String, u64> = HashMap::new();
+    for conn in &connections {
+        let mut stmt = conn
+            .prepare(
+                "SELECT Hash, COUNT(*), COALESCE(SUM(File_Size), 0), COALESCE(MAX(File_Size), 0)
+                 FROM Source_Files
+                 WHERE Hash IS NOT NULL AND Hash != ''
+                 GROUP BY Hash",
+            )
+            .map_err(query_error)?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, u64>(1)?,
+                    row.get::<_, u64>(2)?,
+                    row.get::<_, u64>(3)?,
+                ))
+            })
+            .map_err(query_error)?;
+        for row in rows {
+            let (hash, files, total, size) = row.map_err(query_error)?;
+            content.hashed_files += files;
+            content.hashed_size += total;
+            let largest = sizes.entry(hash).or_default();
+            *largest = (*largest).max(size);
+        }
+    }
+    content.distinct_hashes = sizes.len() as u64;
+    content.unique_size = sizes.values().sum();
+    Ok(content)
+}
+
+/// Helper function to format bytes as human-readable string
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit_idx = 0;
+
+    while size >= 1024.0 && unit_idx < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit_idx += 1;
+    }
+
+    format!("{:.1} {}", size, UNITS[unit_idx])
+}
+
+/// Get total count of source files in the database
+pub fn get_total_source_files() -> Result<u64> {
+    let mut count = 0;
+    for conn in catalog_connections(false)? {
+        count += conn
+            .query_row("SELECT COUNT(*) FROM Source_Files", [], |row| {
+                row.get::<_, u64>(0)
+            })
+            .map_err(|cause| BackupError::DatabaseQuery {
+                operation: "get_total_source_files".to_string(),
+                cause,
+            })?;
+    }
+
+    Ok(count)
+}
+
+/// Get total size of all source files in the database (in bytes)
+pub fn get_total_source_size() -> Result<u64> {
+    let mut size = 0;
+    for conn in catalog_connections(false)? {
+        size += conn
+            .query_row(
+                "SELECT COALESCE(SUM(File_Size), 0) FROM Source_Files",
+                [],
+                |row| row.get::<_, i64>(0),
+            )
+            .map_err(|cause| BackupError::DatabaseQuery {
+                operation: "get_total_source_size".to_string(),
+                cause,
+            })?;
+    }
+
+    Ok(size as u64)
+}
+
+/// The catalog in `database_file` and its shards
+pub struct SqliteCatalog;
+
+impl CatalogStore for SqliteCatalog {
+    fn select_source(&self, source_file: &str, source_path: &str) -> Result<Option<SourceRow>> {
+        select_source(source_file, source_path)
+    }
+
+    fn select_source_by_id(&self, id: i32) -> Result<Option<SourceRow>> {
+        select_source_by_id(id)
+    }
+
+    fn select_sources(&self, under: Option<&str>) -> Result<Vec<(SourceRow, u64)>> {
+        select_sources(under)
+    }
+
+    fn count_sources_matching(&self, search: Option<&str>) -> Result<u64> {
+        count_sources_matching(search)
+    }
+
+    fn select_sources_matching(
+        &self,
+        search: Option<&str>,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<SourceRow>> {
+        select_sources_matching(search, limit, offset)
+    }
+
+    fn insert_source_row(&self, source_row: &SourceRow) -> Result<i32> {
+        insert_source_row(source_row)
+    }
+
+    fn update_source_last_modified(&self, row_id: i32, last_modified: &Duration) -> Result<()> {
+        update_source_last_modified(row_id, last_modified)
+    }
+
+    fn select_sources_with_content(&self, hash: &str, file_size: u64) -> Result<Vec<SourceRow>> {
+        select_sources_with_content(hash, file_size)
+    }
+
+    fn delete_source_row(&self, id: i32) -> Result<bool> {
+        delete_source_row(id)
+    }
 
-    // Get per-destination stats
-    let mut dest_stats = Vec::new();
+    fn select_backed_up_file(
+        &self,
+        filename: &str,
+        filepath: &str,
+    ) -> Result<Option<BackedUpFile>> {
+        select_backed_up_file(filename, filepath)
+    }
 
-    for dest in destinations {
-        let (count, size): (u64, u64) = conn
-            .query_row(
-                "SELECT COUNT(DISTINCT bf.Source_ID), COALESCE(SUM(sf.File_Size), 0)
-                 FROM Backup_Files bf
-                 INNER JOIN Source_Files sf ON bf.Source_ID = sf.ID
-                 WHERE bf.File_Path LIKE ?1 || '%'",
-                [dest],
-                |row| Ok((row.get(0)?, row.get(1)?)),
-            )
-            .map_err(|cause| BackupError::DatabaseQuery {
-                operation: format!("get storage stats for {}", dest),
-                cause,
-            })?;
+    fn select_backup_paths(&self, source_id: i32) -> Result<Vec<PathBuf>> {
+        select_backup_paths(source_id)
+    }
 
-        dest_stats.push(DestinationStorageStats {
-            destination_root: dest.clone(),
-            file_count: count,
-            total_size: size,
-        });
+    fn select_backups_of_source(&self, source_id: i32) -> Result<Vec<BackupRow>> {
+        select_backups_of_source(source_id)
     }
 
-    Ok(StorageStats {
-        total_source_files: total_files,
-        total_source_size: total_size,
-        destination_stats: dest_stats,
-    })
-}
+    fn select_all_backups(&self) -> Result<Vec<BackupRow>> {
+        select_all_backups()
+    }
 
-/// Helper function to format bytes as human-readable string
-pub fn format_bytes(bytes: u64) -> String {
-    const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
-    let mut size = bytes as f64;
-    let mut unit_idx = 0;
+    fn insert_backup_row(&self, backup_row: BackupRow) -> Result<()> {
+        insert_backup_row(backup_row)
+    }
 
-    while size >= 1024.0 && unit_idx < UNITS.len() - 1 {
-        size /= 1024.0;
-        unit_idx += 1;
+    fn delete_backup_row(&self, source_id: i32, file_name: &str, file_path: &str) -> Result<()> {
+        delete_backup_row(source_id, file_name, file_path)
     }
 
-    format!("{:.1} {}", size, UNITS[unit_idx])
-}
+    fn select_scrub_candidates(&self) -> Result<Vec<ScrubCandidate>> {
+        select_scrub_candidates()
+    }
 
-/// Get total count of source files in the database
-pub fn get_total_source_files() -> Result<u64> {
-    let conn = get_connection()?;
+    fn update_backup_verified(
+        &self,
+        source_id: i32,
+        file_name: &str,
+        file_path: &str,
+        verified_at: i64,
+    ) -> Result<()> {
+        update_backup_verified(source_id, file_name, file_path, verified_at)
+    }
 
-    let count: u64 = conn
-        .query_row("SELECT COUNT(*) FROM Source_Files", [], |row| row.get(0))
-        .map_err(|cause| BackupError::DatabaseQuery {
-            operation: "get_total_source_files".to_string(),
-            cause,
-        })?;
+    fn select_catalog(&self) -> Result<Vec<ExportedSource>> {
+        select_catalog()
+    }
 
-    Ok(count)
-}
+    fn import_catalog(&self, sources: &[ExportedSource]) -> Result<ImportSummary> {
+        import_catalog(sources)
+    }
 
-/// Get total size of all source files in the database (in bytes)
-pub fn get_total_source_size() -> Result<u64> {
-    let conn = get_connection()?;
+    fn get_storage_overview(&self, destinations: &[String]) -> Result<StorageStats> {
+        get_storage_overview(destinations)
+    }
 
-    let size: i64 = conn
-        .query_row(
-            "SELECT COALESCE(SUM(File_Size), 0) FROM Source_Files",
-            [],
-            |row| row.get(0),
-        )
-        .map_err(|cause| BackupError::DatabaseQuery {
-            operation: "get_total_source_size".to_string(),
-            cause,
-        })?;
+    fn get_total_source_files(&self) -> Result<u64> {
+        get_total_source_files()
+    }
 
-    Ok(size as u64)
+    fn get_total_source_size(&self) -> Result<u64> {
+        get_total_source_size()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::run_report::{AnomalyMetric, RunAnomaly};
     use serial_test::serial;
     use std::time::Duration;
 
@@ -571,7 +4350,7 @@ mod tests {
         assert_eq!(id1, id2);
 
         // Verify the hash was updated
-        let retrieved = select_source(&"test.txt".to_string(), &"/test/path".to_string()).unwrap();
+        let retrieved = select_source("test.txt", "/test/path").unwrap();
         assert!(retrieved.is_some());
         assert_eq!(retrieved.unwrap().hash, "def456");
     }
@@ -592,7 +4371,7 @@ mod tests {
 
         insert_source_row(&source_row).unwrap();
 
-        let result = select_source(&"exists.txt".to_string(), &"/exists".to_string()).unwrap();
+        let result = select_source("exists.txt", "/exists").unwrap();
 
         assert!(result.is_some());
         let retrieved = result.unwrap();
@@ -607,8 +4386,7 @@ mod tests {
     fn test_select_source_returns_none_for_missing() {
         setup_test_db();
 
-        let result =
-            select_source(&"nonexistent.txt".to_string(), &"/nowhere".to_string()).unwrap();
+        let result = select_source("nonexistent.txt", "/nowhere").unwrap();
 
         assert!(result.is_none());
     }
@@ -634,7 +4412,7 @@ mod tests {
         update_source_last_modified(id, &new_time).unwrap();
 
         // Verify update
-        let retrieved = select_source(&"update_test.txt".to_string(), &"/update".to_string())
+        let retrieved = select_source("update_test.txt", "/update")
             .unwrap()
             .unwrap();
         assert_eq!(retrieved.last_modified.as_secs(), 2000);
@@ -642,38 +4420,6 @@ mod tests {
         assert_eq!(retrieved.hash, "original_hash");
     }
 
-    #[test]
-    #[serial]
-    fn test_update_source_row() {
-        setup_test_db();
-
-        let source_row = SourceRow {
-            id: 0,
-            file_name: "full_update.txt".to_string(),
-            file_path: "/full_update".to_string(),
-            hash: "old_hash".to_string(),
-            file_size: 100,
-            last_modified: Duration::from_secs(1000),
-        };
-
-        let id = insert_source_row(&source_row).unwrap();
-
-        // Update hash, size, and time
-        let new_hash = "new_hash".to_string();
-        let new_size = 200u64;
-        let new_time = Duration::from_secs(3000);
-
-        update_source_row(id, &new_hash, &new_size, &new_time).unwrap();
-
-        // Verify all fields updated
-        let retrieved = select_source(&"full_update.txt".to_string(), &"/full_update".to_string())
-            .unwrap()
-            .unwrap();
-        assert_eq!(retrieved.hash, "new_hash");
-        assert_eq!(retrieved.file_size, 200);
-        assert_eq!(retrieved.last_modified.as_secs(), 3000);
-    }
-
     #[test]
     #[serial]
     fn test_insert_backup_row() {
@@ -697,6 +4443,8 @@ mod tests {
             file_name: "source.txt".to_string(),
             file_path: "/backup/dest".to_string(),
             last_modified: Duration::from_secs(1500),
+            original_path: None,
+            physical_size: None,
         };
 
         let result = insert_backup_row(backup_row);
@@ -726,19 +4474,19 @@ mod tests {
             file_name: "joined.txt".to_string(),
             file_path: "/backup".to_string(),
             last_modified: Duration::from_secs(2500),
+            original_path: None,
+            physical_size: None,
         };
 
         insert_backup_row(backup_row).unwrap();
 
-        // Select backed up file (should join with source to get hash)
-        let result =
-            select_backed_up_file(&"joined.txt".to_string(), &"/backup".to_string()).unwrap();
+        // Select backed up file
+        let result = select_backed_up_file("joined.txt", "/backup").unwrap();
 
         assert!(result.is_some());
         let backed_up = result.unwrap();
         assert_eq!(backed_up.file_name, "joined.txt");
         assert_eq!(backed_up.file_path, "/backup");
-        assert_eq!(backed_up.hash, "joined_hash"); // Hash from joined Source_Files table
         assert_eq!(backed_up.last_modified.as_secs(), 2500);
     }
 
@@ -747,9 +4495,731 @@ mod tests {
     fn test_select_backed_up_file_returns_none_for_missing() {
         setup_test_db();
 
-        let result =
-            select_backed_up_file(&"missing.txt".to_string(), &"/nowhere".to_string()).unwrap();
+        let result = select_backed_up_file("missing.txt", "/nowhere").unwrap();
 
         assert!(result.is_none());
     }
+
+    #[test]
+    #[serial]
+    fn test_insert_known_directory_is_idempotent() {
+        setup_test_db();
+
+        insert_known_directory("/photos", "2024-trip").unwrap();
+        insert_known_directory("/photos", "2024-trip").unwrap();
+        insert_known_directory("/photos", "2025-trip").unwrap();
+        insert_known_directory("/documents", "taxes").unwrap();
+
+        let mut known = select_known_directories("/photos").unwrap();
+        known.sort();
+        assert_eq!(known, vec!["2024-trip", "2025-trip"]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_insert_and_select_unclaimed_files() {
+        setup_test_db();
+
+        let file = UnclaimedFile {
+            destination: "/mnt/unclaimed-test".to_string(),
+            original_path: "/mnt/unclaimed-test/old/report.pdf".to_string(),
+            unclaimed_path: "/mnt/unclaimed-test/unclaimed/old/report.pdf".to_string(),
+            hash: Some("abc123".to_string()),
+            file_size: 42,
+            moved_at: 1000,
+        };
+        insert_unclaimed_file(&file).unwrap();
+
+        let files = select_unclaimed_files(Some("/mnt/unclaimed-test")).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].unclaimed_path, file.unclaimed_path);
+        assert_eq!(files[0].hash.as_deref(), Some("abc123"));
+
+        assert!(select_unclaimed_files(Some("/mnt/elsewhere"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    #[serial]
+    fn test_run_files_pagination_and_status_filter() {
+        setup_test_db();
+
+        let run_id = "run-files-pagination";
+        for i in 0..5 {
+            let outcome = FileOutcome {
+                source: "photos".to_string(),
+                file: format!("/photos/{}.jpg", i),
+                destination: "/mnt/nas".to_string(),
+                status: if i == 3 {
+                    FileStatus::Failed
+                } else {
+                    FileStatus::Copied
+                },
+                bytes: 10,
+                physical_bytes: (i == 0).then_some(4096),
+                error: (i == 3).then(|| "disk full".to_string()),
+            };
+            insert_run_file(run_id, &outcome).unwrap();
+        }
+
+        assert_eq!(count_run_files(run_id, None).unwrap(), 5);
+        assert_eq!(
+            count_run_files(run_id, Some(FileStatus::Failed)).unwrap(),
+            1
+        );
+
+        let page = select_run_files(run_id, None, 2, 2).unwrap();
+        assert_eq!(page.len(), 2);
+        assert_eq!(page[0].file, "/photos/2.jpg");
+
+        let first = select_run_files_after(run_id, None, 0, 3).unwrap();
+        assert_eq!(first[0].1.physical_bytes, Some(4096));
+        assert_eq!(first[1].1.physical_bytes, None);
+        let rest = select_run_files_after(run_id, None, first[2].0, 3).unwrap();
+        assert_eq!(rest.len(), 2);
+        assert_eq!(rest[1].1.file, "/photos/4.jpg");
+
+        let failed = select_run_files(run_id, Some(FileStatus::Failed), 10, 0).unwrap();
+        assert_eq!(failed[0].error.as_deref(), Some("disk full"));
+
+        assert_eq!(delete_run_files(run_id).unwrap(), 5);
+    }
+
+    #[test]
+    #[serial]
+    fn test_insert_and_select_post_process_results() {
+        setup_test_db();
+
+        let backup_path = "/mnt/post-process-test/photos/a.jpg";
+        for (processor, status) in [
+            ("thumbnail", ProcessorStatus::Passed),
+            ("clamav", ProcessorStatus::Flagged),
+        ] {
+            insert_post_process_result(&PostProcessResult {
+                processor: processor.to_string(),
+                source_path: "/photos/a.jpg".to_string(),
+                backup_path: backup_path.to_string(),
+                status,
+                detail: (status == ProcessorStatus::Flagged)
+                    .then(|| "Eicar-Signature FOUND".to_string()),
+                processed_at: 2000,
+            })
+            .unwrap();
+        }
+
+        let all = select_post_process_results(Some(backup_path), None, 10, 0).unwrap();
+        assert_eq!(all.len(), 2);
+
+        let flagged =
+            select_post_process_results(Some(backup_path), Some(ProcessorStatus::Flagged), 10, 0)
+                .unwrap();
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(flagged[0].processor, "clamav");
+        assert_eq!(flagged[0].detail.as_deref(), Some("Eicar-Signature FOUND"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_job_crud_and_results() {
+        setup_test_db();
+
+        let mut definition = JobDefinition {
+            name: "job-crud-test".to_string(),
+            source: Some("photos".to_string()),
+            path: None,
+            dry_run: false,
+            dry_run_full: true,
+            schedule: Some("0 0 2 * * *".to_string()),
+            enabled: true,
+        };
+        let id = insert_job(&definition, Some(100)).unwrap();
+        assert!(insert_job(&definition, None).is_err());
+
+        let job = select_job(id).unwrap().unwrap();
+        assert_eq!(job.definition, definition);
+        assert_eq!(job.next_run, Some(100));
+        assert!(job.last_result.is_none());
+        assert!(select_due_jobs(99).unwrap().iter().all(|job| job.id != id));
+        assert!(select_due_jobs(100).unwrap().iter().any(|job| job.id == id));
+
+        let started = JobResult {
+            backup_id: Some("job-crud-run".to_string()),
+            status: BackupStatus::Running,
+            started_at: 100,
+            finished_at: None,
+            error: None,
+        };
+        record_job_launch(id, &started, Some(200)).unwrap();
+        assert_eq!(select_job_for_run("job-crud-run").unwrap().unwrap().id, id);
+        record_job_finished("job-crud-run", &BackupStatus::Completed, None).unwrap();
+        let succeeded_at = select_job(id).unwrap().unwrap().last_success_at;
+        assert!(succeeded_at.is_some());
+
+        record_job_launch(id, &started, Some(200)).unwrap();
+        record_job_finished("job-crud-run", &BackupStatus::Failed, Some("disk full")).unwrap();
+        let job = select_job(id).unwrap().unwrap();
+        let result = job.last_result.unwrap();
+        assert_eq!(result.status, BackupStatus::Failed);
+        assert_eq!(result.error.as_deref(), Some("disk full"));
+        assert!(result.finished_at.is_some());
+        assert_eq!(job.last_success_at, succeeded_at);
+
+        definition.enabled = false;
+        assert!(update_job(id, &definition, None).unwrap());
+        assert!(select_due_jobs(i64::MAX)
+            .unwrap()
+            .iter()
+            .all(|job| job.id != id));
+        assert!(select_jobs().unwrap().iter().any(|job| job.id == id));
+
+        assert!(delete_job(id).unwrap());
+        assert!(!delete_job(id).unwrap());
+        assert!(!update_job(id, &definition, None).unwrap());
+        assert!(select_job(id).unwrap().is_none());
+    }
+
+    #[test]
+    #[serial]
+    fn test_list_sources_backups_and_runs() {
+        setup_test_db();
+        let source = |name: &str, path: &str| SourceRow {
+            id: 0,
+            file_name: name.to_string(),
+            file_path: path.to_string(),
+            hash: "listed_hash".to_string(),
+            file_size: 10,
+            last_modified: Duration::from_secs(100),
+        };
+        let listed = insert_source_row(&source("a.txt", "/catalog/list")).unwrap();
+        insert_source_row(&source("b.txt", "/catalog/list/nested")).unwrap();
+        insert_source_row(&source("c.txt", "/catalog/listing")).unwrap();
+        for destination in ["/mnt/one/list", "/mnt/two/list"] {
+            insert_backup_row(BackupRow {
+                source_id: listed,
+                file_name: "a.txt".to_string(),
+                file_path: destination.to_string(),
+                last_modified: Duration::from_secs(100),
+                original_path: None,
+                physical_size: None,
+            })
+            .unwrap();
+        }
+
+        let sources = select_sources(Some("/catalog/list/")).unwrap();
+        let names: Vec<_> = sources.iter().map(|(s, _)| s.file_name.as_str()).collect();
+        assert_eq!(names, ["a.txt", "b.txt"]);
+        assert_eq!(sources[0].1, 2);
+        assert_eq!(sources[1].1, 0);
+
+        let backups = select_backups_of_source(listed).unwrap();
+        assert_eq!(backups.len(), 2);
+        assert_eq!(backups[0].file_path, "/mnt/one/list");
+
+        let run = |id: &str, started_at: i64| RunRecord {
+            id: id.to_string(),
+            started_at,
+            finished_at: started_at + 5,
+            status: BackupStatus::Completed,
+            dry_run: false,
+            files_copied: 3,
+            bytes_copied: 30,
+            errors: 0,
+            error: None,
+            note: Some(format!("note for {}", id)),
+            checkpoint: id.ends_with("newer"),
+            timing: Some(RunTiming {
+                duration_ms: 5000,
+                files_processed: 8,
+                bytes_hashed: 80,
+                hash_micros: 20,
+            }),
+            anomalies: if id.ends_with("newer") {
+                vec![RunAnomaly {
+                    metric: AnomalyMetric::FilesCopied,
+                    value: 3.0,
+                    baseline: 0.5,
+                    factor: 6.0,
+                }]
+            } else {
+                Vec::new()
+            },
+        };
+        insert_run(&run("list-older", 4_000_000_000)).unwrap();
+        insert_run(&run("list-newer", 4_000_000_100)).unwrap();
+        insert_run(&RunRecord {
+            timing: None,
+            ..run("list-untimed", 4_000_000_200)
+        })
+        .unwrap();
+        let runs = select_runs(3).unwrap();
+        assert_eq!(runs[0].id, "list-untimed");
+        assert_eq!(runs[1], run("list-newer", 4_000_000_100));
+        assert_eq!(runs[2].id, "list-older");
+
+        // Timed runs come oldest first, keeping the latest when limited
+        let timed = select_timed_runs(Some(4_000_000_000), 10).unwrap();
+        let ids: Vec<_> = timed.iter().map(|run| run.id.as_str()).collect();
+        assert_eq!(ids, ["list-older", "list-newer"]);
+        assert_eq!(select_timed_runs(None, 1).unwrap()[0].id, "list-newer");
+    }
+
+    #[test]
+    #[serial]
+    fn test_catalog_import_and_export() {
+        setup_test_db();
+        let backup = |path: &str, last_modified| ExportedBackup {
+            file_name: "photo.jpg".to_string(),
+            file_path: path.to_string(),
+            last_modified,
+        };
+        let mut sources = vec![
+            ExportedSource {
+                file_name: "photo.jpg".to_string(),
+                file_path: "/catalog/pics".to_string(),
+                hash: "imported_hash".to_string(),
+                file_size: 20,
+                last_modified: 300,
+                backups: vec![backup("/catalog/mnt/one", 301)],
+            },
+            ExportedSource {
+                file_name: "unbacked.txt".to_string(),
+                file_path: "/catalog".to_string(),
+                hash: "unbacked_hash".to_string(),
+                file_size: 5,
+                last_modified: 100,
+                backups: vec![],
+            },
+        ];
+        let exported = |sources: Vec<ExportedSource>| -> Vec<ExportedSource> {
+            sources
+                .into_iter()
+                .filter(|s| s.file_path.starts_with("/catalog"))
+                .collect()
+        };
+
+        let summary = import_catalog(&sources).unwrap();
+        assert_eq!(
+            summary,
+            ImportSummary {
+                sources: 2,
+                backups: 1
+            }
+        );
+        sources.swap(0, 1);
+        assert_eq!(exported(select_catalog().unwrap()), sources);
+
+        // Importing again updates the rows for the same paths
+        sources[1].hash = "changed_hash".to_string();
+        sources[1].backups.push(backup("/catalog/mnt/two", 302));
+        import_catalog(&sources).unwrap();
+        assert_eq!(exported(select_catalog().unwrap()), sources);
+    }
+
+    #[test]
+    #[serial]
+    fn test_search_sources_by_path() {
+        setup_test_db();
+        let source = |name: &str, path: &str| SourceRow {
+            id: 0,
+            file_name: name.to_string(),
+            file_path: path.to_string(),
+            hash: "searched_hash".to_string(),
+            file_size: 10,
+            last_modified: Duration::from_secs(100),
+        };
+        let found = insert_source_row(&source("Tax_2024.pdf", "/search/docs")).unwrap();
+        insert_source_row(&source("tax2024.pdf", "/search/docs")).unwrap();
+        insert_source_row(&source("notes.txt", "/search/taxes")).unwrap();
+
+        let names = |search: &str| -> Vec<String> {
+            select_sources_matching(Some(search), 10, 0)
+                .unwrap()
+                .into_iter()
+                .map(|s| s.file_name)
+                .collect()
+        };
+        // `_` is matched literally rather than as a wildcard
+        assert_eq!(names("tax_"), ["Tax_2024.pdf"]);
+        assert_eq!(names("/search/tax"), ["notes.txt"]);
+        assert_eq!(
+            names(&format!("docs{}tax2", MAIN_SEPARATOR)),
+            ["tax2024.pdf"]
+        );
+        assert_eq!(count_sources_matching(Some("/search/")).unwrap(), 3);
+        assert_eq!(
+            select_sources_matching(Some("/search/"), 2, 2)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let by_id = select_source_by_id(found).unwrap().unwrap();
+        assert_eq!(by_id.file_name, "Tax_2024.pdf");
+        assert!(select_source_by_id(-1).unwrap().is_none());
+    }
+
+    #[test]
+    #[serial]
+    fn test_active_run_round_trip() {
+        setup_test_db();
+        let run = ActiveRun {
+            id: "active-run".to_string(),
+            started_at: 1000,
+            dry_run_mode: DryRunMode::Full,
+            selection: RunSelection {
+                source: Some("photos".to_string()),
+                ..Default::default()
+            },
+            note: Some("before OS upgrade".to_string()),
+            status: BackupStatus::Running,
+            files_processed: 0,
+            total_files: 0,
+            bytes_processed: 0,
+            updated_at: 1000,
+        };
+        save_active_run(&run).unwrap();
+
+        let progress = BackupProgress {
+            files_processed: 7,
+            total_files: 20,
+            bytes_processed: Some(700),
+            ..Default::default()
+        };
+        update_active_run("active-run", &BackupStatus::Paused, Some(&progress)).unwrap();
+        // Updates for a run that is no longer the active one are ignored
+        update_active_run("other-run", &BackupStatus::Stopping, None).unwrap();
+
+        let taken = take_active_run().unwrap().unwrap();
+        assert_eq!(taken.id, "active-run");
+        assert_eq!(taken.dry_run_mode, DryRunMode::Full);
+        assert_eq!(taken.selection.source.as_deref(), Some("photos"));
+        assert_eq!(taken.note.as_deref(), Some("before OS upgrade"));
+        assert_eq!(taken.status, BackupStatus::Paused);
+        assert_eq!(taken.files_processed, 7);
+        assert_eq!(taken.total_files, 20);
+        assert_eq!(taken.bytes_processed, 700);
+        assert!(take_active_run().unwrap().is_none());
+
+        save_active_run(&run).unwrap();
+        delete_active_run("active-run").unwrap();
+        assert!(take_active_run().unwrap().is_none());
+    }
+
+    #[test]
+    #[serial]
+    fn test_run_note_comes_back_with_the_run() {
+        setup_test_db();
+        let noted = RunRecord {
+            note: Some("before OS upgrade".to_string()),
+            ..RunRecord::finished("noted-run", 6_000_000_000, 6_000_000_010, false, None, None)
+        };
+        insert_run(&noted).unwrap();
+        insert_run(&RunRecord::finished(
+            "unnoted-run",
+            6_000_000_100,
+            6_000_000_110,
+            false,
+            None,
+            None,
+        ))
+        .unwrap();
+
+        let runs = select_runs(2).unwrap();
+        assert_eq!(runs[0].id, "unnoted-run");
+        assert_eq!(runs[0].note, None);
+        assert_eq!(runs[1].id, "noted-run");
+        assert_eq!(runs[1].note.as_deref(), Some("before OS upgrade"));
+    }
+
+    fn log_entry(timestamp: i64, message: &str, context: Option<&str>) -> LogRow {
+        LogRow {
+            id: 0,
+            timestamp,
+            level: "INFO".to_string(),
+            message: message.to_string(),
+            context: context.map(str::to_string),
+            source: Some("app".to_string()),
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_query_logs_by_run() {
+        setup_test_db();
+        // The shared in-memory database outlives each test
+        delete_all_logs().unwrap();
+        let now = chrono::Utc::now().timestamp();
+        insert_log_entries(&mut [
+            log_entry(now, "Starting backup", None),
+            log_entry(
+                now,
+                "Copy failed",
+                Some(r#"{"run_id":"run-1","source":"photos"}"#),
+            ),
+            log_entry(now, "Copied", Some(r#"{"run_id":"run-2"}"#)),
+        ])
+        .unwrap();
+
+        let logs = query_logs(None, None, None, Some("run-1"), None, None).unwrap();
+        assert_eq!(logs.len(), 1);
+        assert_eq!(logs[0].message, "Copy failed");
+        assert_eq!(
+            query_logs(None, None, None, None, None, None)
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_prune_logs() {
+        setup_test_db();
+        // The shared in-memory database outlives each test
+        delete_all_logs().unwrap();
+        let now = chrono::Utc::now().timestamp();
+        let mut entries: Vec<LogRow> = (0..5)
+            .rev()
+            .map(|day| log_entry(now - day * 86_400 - 60, &format!("day {}", day), None))
+            .collect();
+        insert_log_entries(&mut entries).unwrap();
+
+        assert_eq!(prune_logs(None, None).unwrap(), 0);
+        // Days 3 and 4 are past the retention, then day 2 is over the row limit
+        assert_eq!(prune_logs(Some(3), Some(2)).unwrap(), 3);
+        let kept: Vec<String> = query_logs(None, None, None, None, None, None)
+            .unwrap()
+            .into_iter()
+            .map(|log| log.message)
+            .collect();
+        assert_eq!(kept, vec!["day 0", "day 1"]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_shard_catalog() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_file = dir.path().join("backup.db").to_string_lossy().to_string();
+        set_db_pool(&db_file).unwrap();
+        setup_database().unwrap();
+
+        let source = |name: &str, path: &str| SourceRow {
+            id: 0,
+            file_name: name.to_string(),
+            file_path: path.to_string(),
+            hash: format!("{}_hash", name),
+            file_size: 10,
+            last_modified: Duration::from_secs(100),
+        };
+        let backup = |source_id, name: &str, path: &str| BackupRow {
+            source_id,
+            file_name: name.to_string(),
+            file_path: path.to_string(),
+            last_modified: Duration::from_secs(100),
+            original_path: None,
+            physical_size: None,
+        };
+        let photo = insert_source_row(&source("a.jpg", "/data/photos")).unwrap();
+        insert_backup_row(backup(photo, "a.jpg", "/mnt/one/photos")).unwrap();
+        insert_backup_row(backup(photo, "a.jpg", "/mnt/two/photos")).unwrap();
+        let doc = insert_source_row(&source("b.txt", "/data/docs")).unwrap();
+        insert_backup_row(backup(doc, "b.txt", "/mnt/one/docs")).unwrap();
+        insert_source_row(&source("c.txt", "/other")).unwrap();
+        let rows_in_database_file = || -> i64 {
+            get_connection()
+                .unwrap()
+                .query_row(
+                    "SELECT (SELECT COUNT(*) FROM Source_Files)
+                        + (SELECT COUNT(*) FROM Backup_Files)",
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap()
+        };
+        let backup_counts = || -> Vec<(String, u64)> {
+            select_sources(None)
+                .unwrap()
+                .into_iter()
+                .map(|(source, count)| (source.file_name, count))
+                .collect()
+        };
+        let expected_counts = vec![
+            ("b.txt".to_string(), 1),
+            ("a.jpg".to_string(), 2),
+            ("c.txt".to_string(), 0),
+        ];
+        let catalog = select_catalog().unwrap();
+
+        // Per source: rows move into a file per source and get IDs in its span
+        let roots = ["/data/photos".to_string(), "/data/docs".to_string()];
+        shard_catalog(CatalogSharding::PerSource, &roots).unwrap();
+        assert!(dir.path().join("backup.shard-1.db").exists());
+        assert!(dir.path().join("backup.shard-2.db").exists());
+        assert_eq!(rows_in_database_file(), 1);
+
+        let photo = select_source("a.jpg", "/data/photos").unwrap().unwrap();
+        assert_eq!(photo.id as i64 / SHARD_ID_SPAN, 1);
+        assert_eq!(
+            select_source_by_id(photo.id).unwrap().unwrap().hash,
+            "a.jpg_hash"
+        );
+        assert_eq!(select_backups_of_source(photo.id).unwrap().len(), 2);
+        assert_eq!(
+            select_backed_up_file("b.txt", "/mnt/one/docs")
+                .unwrap()
+                .unwrap()
+                .file_name,
+            "b.txt"
+        );
+        assert_eq!(backup_counts(), expected_counts);
+        assert_eq!(count_sources_matching(None).unwrap(), 3);
+        assert_eq!(
+            select_sources_matching(None, 1, 1).unwrap()[0].file_name,
+            "a.jpg"
+        );
+        assert_eq!(select_catalog().unwrap(), catalog);
+        let new_id = insert_source_row(&source("d.jpg", "/data/photos/2024")).unwrap();
+        assert_eq!(new_id as i64 / SHARD_ID_SPAN, 1);
+        insert_source_row(&SourceRow {
+            file_size: 5,
+            ..source("d.jpg", "/data/photos/2024")
+        })
+        .unwrap();
+        assert_eq!(get_total_source_size().unwrap(), 35);
+
+        // Per destination: source shards merge back, backups move into a file per destination
+        shard_catalog(CatalogSharding::PerDestination, &["/mnt/one".to_string()]).unwrap();
+        assert!(!dir.path().join("backup.shard-2.db").exists());
+        assert_eq!(rows_in_database_file(), 5);
+        let photo = select_source("a.jpg", "/data/photos").unwrap().unwrap();
+        assert_eq!(select_backup_paths(photo.id).unwrap().len(), 2);
+        assert_eq!(
+            select_backed_up_file("a.jpg", "/mnt/one/photos")
+                .unwrap()
+                .unwrap()
+                .file_name,
+            "a.jpg"
+        );
+        assert_eq!(get_total_source_files().unwrap(), 4);
+        assert_eq!(
+            backup_counts(),
+            vec![
+                ("b.txt".to_string(), 1),
+                ("a.jpg".to_string(), 2),
+                ("d.jpg".to_string(), 0),
+                ("c.txt".to_string(), 0),
+            ]
+        );
+        let storage = get_storage_overview(&["/mnt/one".to_string()]).unwrap();
+        assert_eq!(storage.destination_stats[0].file_count, 2);
+
+        // Off: everything is back in the database file
+        shard_catalog(CatalogSharding::None, &[]).unwrap();
+        assert_eq!(rows_in_database_file(), 7);
+        assert!(!dir.path().join("backup.shard-1.db").exists());
+        assert_eq!(select_all_backups().unwrap().len(), 3);
+    }
+
+    #[test]
+    #[serial]
+    fn test_source_ids_past_a_shards_span_are_refused() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let db_file = dir.path().join("backup.db").to_string_lossy().to_string();
+        set_db_pool(&db_file).unwrap();
+        setup_database().unwrap();
+        shard_catalog(CatalogSharding::PerSource, &["/data/photos".to_string()]).unwrap();
+        let seed = |conn: &rusqlite::Connection, seq: i64| {
+            conn.execute(
+                "DELETE FROM sqlite_sequence WHERE name = 'Source_Files'",
+                [],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO sqlite_sequence (name, seq) VALUES ('Source_Files', ?1)",
+                [seq],
+            )
+            .unwrap();
+        };
+        let source = |name: &str, path: &str| SourceRow {
+            id: 0,
+            file_name: name.to_string(),
+            file_path: path.to_string(),
+            hash: "hash".to_string(),
+            file_size: 10,
+            last_modified: Duration::from_secs(100),
+        };
+
+        // Shard 1 hands out its last ID, then would hand out the first of shard 2
+        seed(
+            &pooled(&catalog().unwrap().shards[0].pool).unwrap(),
+            2 * SHARD_ID_SPAN - 2,
+        );
+        let last = insert_source_row(&source("a.jpg", "/data/photos")).unwrap();
+        assert_eq!(last as i64, 2 * SHARD_ID_SPAN - 1);
+        let error = insert_source_row(&source("b.jpg", "/data/photos")).unwrap_err();
+        assert!(matches!(
+            &error,
+            BackupError::SourceIdOutOfSpan { id, .. } if *id as i64 == 2 * SHARD_ID_SPAN
+        ));
+        assert!(error.to_string().contains("catalog shard 1"));
+        assert!(select_source("b.jpg", "/data/photos").unwrap().is_none());
+        let export = ExportedSource {
+            file_name: "c.jpg".to_string(),
+            file_path: "/data/photos".to_string(),
+            hash: "hash".to_string(),
+            file_size: 10,
+            last_modified: 100,
+            backups: Vec::new(),
+        };
+        assert!(import_catalog(&[export]).is_err());
+        assert!(select_source("c.jpg", "/data/photos").unwrap().is_none());
+
+        // database_file runs into the span of shard 1
+        seed(&get_connection().unwrap(), SHARD_ID_SPAN - 1);
+        let error = insert_source_row(&source("d.txt", "/other")).unwrap_err();
+        assert!(error.to_string().contains("belongs to catalog shard 1"));
+        assert!(select_source("d.txt", "/other").unwrap().is_none());
+        assert_eq!(
+            select_source("a.jpg", "/data/photos").unwrap().unwrap().id,
+            last
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_storage_overview_counts_duplicate_content_across_shards() {
+        let dir = tempfile::TempDir::new().unwrap();
+        set_db_pool(dir.path().join("backup.db").to_str().unwrap()).unwrap();
+        setup_database().unwrap();
+        let source = |name: &str, path: &str, hash: &str, file_size| SourceRow {
+            id: 0,
+            file_name: name.to_string(),
+            file_path: path.to_string(),
+            hash: hash.to_string(),
+            file_size,
+            last_modified: Duration::from_secs(100),
+        };
+        insert_source_row(&source("a.jpg", "/data/photos", "same", 10)).unwrap();
+        insert_source_row(&source("a copy.jpg", "/data/photos", "same", 10)).unwrap();
+        insert_source_row(&source("a.jpg", "/data/docs", "same", 10)).unwrap();
+        insert_source_row(&source("b.txt", "/data/docs", "other", 5)).unwrap();
+        // Not hashed in a quick dry run, so left out
+        insert_source_row(&source("c.txt", "/data/docs", "", 7)).unwrap();
+        let expected = ContentStats {
+            hashed_files: 4,
+            hashed_size: 35,
+            distinct_hashes: 2,
+            unique_size: 15,
+        };
+
+        assert_eq!(get_storage_overview(&[]).unwrap().content, expected);
+        let roots = ["/data/photos".to_string(), "/data/docs".to_string()];
+        shard_catalog(CatalogSharding::PerSource, &roots).unwrap();
+        let content = get_storage_overview(&[]).unwrap().content;
+        assert_eq!(content, expected);
+        assert_eq!(content.duplicate_size(), 20);
+        assert!((content.dedup_ratio() - 35.0 / 15.0).abs() < 1e-9);
+        shard_catalog(CatalogSharding::None, &[]).unwrap();
+    }
 }
@@ -0,0 +1,94 @@
+use crate::models::config::Config;
+use crate::models::restore_plan::RestorePlan;
+use crate::output::OutputFormat;
+use crate::service::restore::{build_restore_plan, carry_out_restore, resolve_as_of};
+use anyhow::{bail, Context, Result};
+use chrono::DateTime;
+use clap::Subcommand;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+/// What `restore` does
+#[derive(Subcommand)]
+pub enum RestoreCommand {
+    /// Work out which backup of each source file holds it as it was at a time or run, and
+    /// optionally copy those backups to a directory
+    Plan {
+        /// Run ID, as listed by `list runs`, RFC 3339 time or Unix timestamp to restore as of
+        #[arg(long = "as-of", value_name = "TIME_OR_RUN")]
+        as_of: String,
+        /// Only plan the files below this source directory
+        #[arg(long = "path")]
+        path: Option<String>,
+        /// Copy the planned backups below this directory, each under its full source path
+        #[arg(long = "to", value_name = "DIRECTORY")]
+        to: Option<PathBuf>,
+        /// List every file and the backup it comes from instead of only the totals
+        #[arg(long = "files")]
+        files: bool,
+    },
+}
+
+/// Plan a point-in-time restore, and carry it out with `--to`; fails if any file couldn't be
+/// restored
+pub fn run(command: &RestoreCommand, config: &Config, output: OutputFormat) -> Result<()> {
+    let RestoreCommand::Plan {
+        as_of,
+        path,
+        to,
+        files,
+    } = command;
+    let (as_of, run) = resolve_as_of(as_of)?;
+    let mut plan = build_restore_plan(config, as_of, run, path.as_deref())
+        .context("Failed to plan the restore")?;
+    if let Some(target) = to {
+        plan.restored = Some(carry_out_restore(&plan, target));
+    }
+    output.print(&plan, || describe_plan(&plan, *files))?;
+    if let Some(outcome) = plan.restored.as_ref().filter(|outcome| outcome.failed > 0) {
+        bail!("{} file(s) could not be restored", outcome.failed);
+    }
+    Ok(())
+}
+
+fn describe_plan(plan: &RestorePlan, files: bool) -> String {
+    let mut out = String::new();
+    let _ = write!(out, "As of {}", format_time(plan.as_of));
+    if let Some(run) = &plan.run {
+        let _ = write!(out, " (end of run {})", run);
+    }
+    let from_trash = plan.files.iter().filter(|item| item.from_trash).count();
+    let _ = writeln!(
+        out,
+        "\n  {} file(s) to restore, {} of them from trash\n  {} deleted by then\n  {} with no backup by then",
+        plan.files.len(),
+        from_trash,
+        plan.deleted.len(),
+        plan.unavailable.len()
+    );
+    if files {
+        for item in &plan.files {
+            let _ = writeln!(
+                out,
+                "{}\n  <- {} ({})",
+                item.path,
+                item.backup,
+                format_time(item.backed_up_at)
+            );
+        }
+    }
+    if let Some(outcome) = &plan.restored {
+        let _ = writeln!(
+            out,
+            "Restored {} file(s) to {}, {} already there, {} failed",
+            outcome.restored, outcome.target, outcome.skipped, outcome.failed
+        );
+    }
+    out
+}
+
+fn format_time(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0)
+        .map(|time| time.to_rfc3339())
+        .unwrap_or_else(|| timestamp.to_string())
+}
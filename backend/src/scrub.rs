@@ -0,0 +1,154 @@
+use crate::models::config::{Config, ScrubConfig};
+use crate::models::scrub::{RepairReport, ScrubProblem, ScrubReport};
+use crate::output::OutputFormat;
+use crate::repo::sqlite::{format_bytes, select_scrub_problems};
+use crate::service::repair::repair_backups;
+use crate::service::scrub::scrub_backups;
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use std::fmt::Write;
+
+/// How much `scrub` reads, in place of the configuration's `scrub` limits
+#[derive(Args)]
+pub struct ScrubArgs {
+    /// Share of the recorded backups to check, in percent
+    #[arg(long = "percent")]
+    percent: Option<f64>,
+    /// Most gibibytes of backups to read
+    #[arg(long = "max-gibibytes")]
+    max_gibibytes: Option<f64>,
+}
+
+/// Scrub the backups verified longest ago now; fails if any turned out damaged or missing
+pub fn run(
+    args: &ScrubArgs,
+    config: &Config,
+    wait_for_lock: bool,
+    output: OutputFormat,
+) -> Result<()> {
+    let configured = config.scrub.clone();
+    let scrub = if args.percent.is_some() || args.max_gibibytes.is_some() {
+        ScrubConfig {
+            schedule: configured.map(|scrub| scrub.schedule).unwrap_or_default(),
+            percent: args.percent,
+            max_gibibytes: args.max_gibibytes,
+        }
+    } else if let Some(configured) = configured {
+        configured
+    } else {
+        bail!("Give --percent or --max-gibibytes, or configure scrub limits");
+    };
+    if scrub
+        .percent
+        .is_some_and(|percent| !(percent > 0.0 && percent <= 100.0))
+    {
+        bail!("--percent must be above 0 and at most 100");
+    }
+    if scrub
+        .max_gibibytes
+        .is_some_and(|max| !(max > 0.0 && max.is_finite()))
+    {
+        bail!("--max-gibibytes must be above 0");
+    }
+
+    let report = scrub_backups(config, &scrub, wait_for_lock).context("Failed to scrub backups")?;
+    output.print(&report, || describe_scrub(&report))?;
+    if !report.problems.is_empty() {
+        bail!(
+            "{} backup(s) no longer match what was copied; `repair` copies them again",
+            report.problems.len()
+        );
+    }
+    Ok(())
+}
+
+/// Whether `repair` copies anything
+#[derive(Args)]
+pub struct RepairArgs {
+    /// Only list the backups scrubs found damaged or missing
+    #[arg(long = "list")]
+    list: bool,
+}
+
+/// Copy the backups scrubs found damaged or missing again; fails if any couldn't be
+pub fn run_repair(
+    args: &RepairArgs,
+    config: &Config,
+    wait_for_lock: bool,
+    output: OutputFormat,
+) -> Result<()> {
+    if args.list {
+        let problems = select_scrub_problems().context("Failed to read scrub problems")?;
+        return output.print(&problems, || {
+            let mut out = String::new();
+            for problem in &problems {
+                describe_problem(&mut out, problem);
+            }
+            let _ = writeln!(out, "{} backup(s) to repair", problems.len());
+            out
+        });
+    }
+
+    let report =
+        repair_backups(config, "cli", wait_for_lock).context("Failed to repair backups")?;
+    output.print(&report, || describe_repair(&report))?;
+    if !report.unrepaired.is_empty() {
+        bail!(
+            "{} backup(s) could not be repaired",
+            report.unrepaired.len()
+        );
+    }
+    Ok(())
+}
+
+fn describe_problem(out: &mut String, problem: &ScrubProblem) {
+    let _ = writeln!(
+        out,
+        "{}: {}\n  backup of {}\n  {}",
+        problem.kind.as_str(),
+        problem.backup,
+        problem.source,
+        problem.detail
+    );
+}
+
+fn describe_repair(report: &RepairReport) -> String {
+    let mut out = String::new();
+    for repaired in &report.repaired {
+        let _ = writeln!(
+            out,
+            "repaired: {}\n  from {}",
+            repaired.backup, repaired.copied_from
+        );
+    }
+    for unrepaired in &report.unrepaired {
+        let _ = writeln!(
+            out,
+            "not repaired: {}\n  {}",
+            unrepaired.backup, unrepaired.reason
+        );
+    }
+    let _ = writeln!(
+        out,
+        "{} backup(s) repaired, {} not",
+        report.repaired.len(),
+        report.unrepaired.len()
+    );
+    out
+}
+
+fn describe_scrub(report: &ScrubReport) -> String {
+    let mut out = String::new();
+    for problem in &report.problems {
+        describe_problem(&mut out, problem);
+    }
+    let _ = writeln!(
+        out,
+        "Checked {} of {} backup(s), {} read; {} skipped because their source changed since",
+        report.checked,
+        report.backups,
+        format_bytes(report.bytes_read),
+        report.skipped
+    );
+    out
+}
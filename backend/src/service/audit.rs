@@ -0,0 +1,15 @@
+use crate::models::audit_event::AuditEvent;
+use crate::models::error::Result;
+use crate::repo::sqlite::insert_audit_event;
+use log::info;
+
+/// Keep a decision in the audit log
+pub fn record_audit_event(action: &str, actor: &str, detail: String) -> Result<()> {
+    info!("Audit: {} by {}: {}", action, actor, detail);
+    insert_audit_event(&AuditEvent {
+        at: chrono::Utc::now().timestamp(),
+        action: action.to_string(),
+        actor: actor.to_string(),
+        detail,
+    })
+}
@@ -0,0 +1,165 @@
+use crate::models::config::constant_time_eq;
+use crate::models::error::Result;
+use crate::models::user::User;
+use crate::repo::sqlite::{
+    count_users, delete_session, insert_session, select_session_user, select_user_credentials,
+};
+use log::warn;
+use pbkdf2::pbkdf2_hmac_array;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+/// PBKDF2 iterations for new password hashes, as OWASP recommends for PBKDF2-HMAC-SHA-256
+const PASSWORD_ITERATIONS: u32 = 600_000;
+
+/// Prefix of the stored password format, `pbkdf2-sha256$<iterations>$<salt>$<hash>` in hex
+const PASSWORD_SCHEME: &str = "pbkdf2-sha256";
+
+/// How long a sign-in lasts
+pub const SESSION_LIFETIME_SECS: i64 = 14 * 24 * 60 * 60;
+
+/// Hash `password` with a fresh random salt, for the `Users` table
+pub fn hash_password(password: &str) -> String {
+    hash_password_with(password, Uuid::new_v4().as_bytes(), PASSWORD_ITERATIONS)
+}
+
+fn hash_password_with(password: &str, salt: &[u8], iterations: u32) -> String {
+    format!(
+        "{}${}${}${}",
+        PASSWORD_SCHEME,
+        iterations,
+        hex::encode(salt),
+        hex::encode(pbkdf2_hmac_array::<Sha256, 32>(
+            password.as_bytes(),
+            salt,
+            iterations
+        ))
+    )
+}
+
+/// Whether `password` is the one `stored` was hashed from
+pub fn verify_password(password: &str, stored: &str) -> bool {
+    let mut parts = stored.split('$');
+    let (Some(PASSWORD_SCHEME), Some(iterations), Some(salt), Some(hash), None) = (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) else {
+        return false;
+    };
+    let (Ok(iterations), Ok(salt), Ok(hash)) =
+        (iterations.parse(), hex::decode(salt), hex::decode(hash))
+    else {
+        return false;
+    };
+    let computed = pbkdf2_hmac_array::<Sha256, 32>(password.as_bytes(), &salt, iterations);
+    constant_time_eq(&hash, &computed)
+}
+
+/// Whether anyone has to sign in; the web UI and API stay open until the first user is added
+///
+/// A database that can't be read asks for a sign-in rather than letting everyone in.
+pub fn sign_in_required() -> bool {
+    match count_users() {
+        Ok(users) => users > 0,
+        Err(e) => {
+            warn!("Failed to count users, asking for a sign-in: {}", e);
+            true
+        }
+    }
+}
+
+/// Check a user's password and start a session, returning the user and the session token
+pub fn sign_in(name: &str, password: &str) -> Result<Option<(User, String)>> {
+    let Some((user, stored)) = select_user_credentials(name)? else {
+        // Take as long as a wrong password would, so names can't be told apart by timing
+        hash_password(password);
+        return Ok(None);
+    };
+    if !verify_password(password, &stored) {
+        return Ok(None);
+    }
+
+    let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let now = chrono::Utc::now().timestamp();
+    insert_session(
+        &token_hash(&token),
+        &user.name,
+        now,
+        now + SESSION_LIFETIME_SECS,
+    )?;
+    Ok(Some((user, token)))
+}
+
+/// The user a session token belongs to, while the session lasts
+pub fn session_user(token: &str) -> Result<Option<User>> {
+    select_session_user(&token_hash(token), chrono::Utc::now().timestamp())
+}
+
+/// End the session a token belongs to
+pub fn sign_out(token: &str) -> Result<()> {
+    delete_session(&token_hash(token))
+}
+
+/// Sessions are stored by the hash of their token, so a copy of the database can't sign anyone in
+fn token_hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::user::Role;
+    use crate::repo::sqlite::{insert_user, set_db_pool, setup_database, update_user_password};
+    use serial_test::serial;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_verify_password() {
+        // RFC 7914, section 11
+        assert_eq!(
+            hash_password_with("password", b"salt", 4096),
+            "pbkdf2-sha256$4096$73616c74$\
+             c5e478d59288c841aa530db6845c4c8d962893a001ce4e11a4963873aa98134a"
+        );
+        let stored = hash_password_with("correct horse", b"salt", 10);
+        assert!(verify_password("correct horse", &stored));
+        assert!(!verify_password("correct horsE", &stored));
+        assert!(!verify_password("correct horse", "plain text"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_sessions_last_until_sign_out_or_password_change() {
+        let database = TempDir::new().unwrap();
+        set_db_pool(database.path().join("backup.db").to_str().unwrap()).unwrap();
+        setup_database().unwrap();
+        assert!(!sign_in_required());
+
+        let alice = User {
+            name: "alice".to_string(),
+            role: Role::Viewer,
+            created_at: 0,
+        };
+        let stored = hash_password_with("correct horse", b"salt", 10);
+        assert!(insert_user(&alice, &stored).unwrap());
+        assert!(!insert_user(&alice, &stored).unwrap());
+        assert!(sign_in_required());
+
+        assert!(sign_in("alice", "wrong").unwrap().is_none());
+        assert!(sign_in("bob", "correct horse").unwrap().is_none());
+        let (user, token) = sign_in("alice", "correct horse").unwrap().unwrap();
+        assert_eq!(user, alice);
+        assert_eq!(session_user(&token).unwrap(), Some(alice.clone()));
+        assert_eq!(session_user("forged").unwrap(), None);
+
+        sign_out(&token).unwrap();
+        assert_eq!(session_user(&token).unwrap(), None);
+
+        let (_, token) = sign_in("alice", "correct horse").unwrap().unwrap();
+        update_user_password("alice", &hash_password_with("battery staple", b"salt", 10)).unwrap();
+        assert_eq!(session_user(&token).unwrap(), None);
+    }
+}
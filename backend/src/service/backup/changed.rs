@@ -0,0 +1,132 @@
+use super::verify::{Verified, VerifiedFile};
+use super::Pipeline;
+use crate::models::catalog_write::CatalogWrite;
+use crate::models::error::{BackupError, Result};
+use crate::models::prepped_backup::PreppedBackup;
+use crate::models::run_report::ChangedDuringRun;
+use crate::service::hash_cache::hash_file_cached;
+use crate::utils::directory::{get_file_last_modified, get_file_size};
+use log::{info, warn};
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+/// Source files that changed between being prepared and their copy finishing, so the copy may
+/// hold part of the old content and part of the new
+#[derive(Default)]
+pub(super) struct ChangedFiles {
+    files: Mutex<Vec<PreppedBackup>>,
+}
+
+impl ChangedFiles {
+    /// Flag the file of `verified` if a copy of it was written and its source has changed since
+    /// it was prepared
+    ///
+    /// A copy of the changed content fails verification against the hash taken when the file
+    /// was prepared, so those are flagged too.
+    pub(super) fn check(&self, verified: &VerifiedFile) {
+        let copied = verified.writes.iter().any(|write| {
+            matches!(
+                write.result,
+                Ok(Verified::Copied { .. }) | Err(BackupError::VerificationFailed { .. })
+            )
+        });
+        if copied && changed_since_prepared(&verified.prepped) {
+            warn!(
+                "{:?} changed while it was being backed up, so its copy may be torn",
+                verified.prepped.source_file
+            );
+            self.files.lock().unwrap().push(verified.prepped.clone());
+        }
+    }
+
+    /// Copy each flagged file again with `recopy_changed_files`, once every other file is done,
+    /// returning what became of them
+    ///
+    /// A file is copied again from scratch to each of its destinations, and counts as settled if
+    /// it didn't change again while it was.
+    pub(super) fn recopy(&self, pipeline: &Pipeline) -> Vec<ChangedDuringRun> {
+        let files = std::mem::take(&mut *self.files.lock().unwrap());
+        if files.is_empty() {
+            return Vec::new();
+        }
+        let recopies = pipeline.config.recopy_changed_files && !pipeline.stopped();
+        if recopies {
+            info!(
+                "Copying {} file(s) that changed during the run again",
+                files.len()
+            );
+            pipeline
+                .files_requeued
+                .fetch_add(files.len() as u64, Ordering::Relaxed);
+        }
+
+        let mut report = Vec::new();
+        for prepped in files {
+            let mut entry = ChangedDuringRun {
+                source: prepped.source_name.clone(),
+                file: prepped.source_file.to_string_lossy().to_string(),
+                recopied: false,
+            };
+            if recopies && !pipeline.stopped() {
+                match refresh(pipeline, prepped) {
+                    Ok(prepped) => {
+                        let changed = ChangedFiles::default();
+                        pipeline.copy(prepped, &changed);
+                        entry.recopied = changed.files.into_inner().unwrap().is_empty();
+                        if !entry.recopied {
+                            warn!("{} changed again while it was copied again", entry.file);
+                        }
+                    }
+                    Err(e) => warn!("Failed to copy {} again: {}", entry.file, e),
+                }
+            }
+            report.push(entry);
+        }
+        report
+    }
+}
+
+/// Whether the source of `prepped` has a different size or modification time than when it was
+/// prepared, or is gone
+fn changed_since_prepared(prepped: &PreppedBackup) -> bool {
+    match (
+        get_file_size(&prepped.read_from),
+        get_file_last_modified(&prepped.read_from),
+    ) {
+        (Ok(size), Ok(modified)) => {
+            size != prepped.file_size || modified != prepped.source_last_modified_date
+        }
+        _ => true,
+    }
+}
+
+/// `prepped` as its source is now, hashed again and staged in the run's journal, set to be
+/// copied to every destination whatever is there
+fn refresh(pipeline: &Pipeline, prepped: PreppedBackup) -> Result<PreppedBackup> {
+    let file_size = get_file_size(&prepped.read_from)?;
+    let last_modified = get_file_last_modified(&prepped.read_from)?;
+    let hash = hash_file_cached(
+        &prepped.read_from,
+        prepped.hash_limit,
+        pipeline.config,
+        pipeline.dry_run_mode,
+    )?;
+    if pipeline.dry_run_mode.should_update_database() {
+        pipeline.journal.stage(CatalogWrite::Source {
+            file_name: prepped.file_name.clone(),
+            file_path: prepped.source_path.clone(),
+            hash: hash.clone(),
+            file_size,
+            last_modified,
+        })?;
+    }
+    Ok(PreppedBackup {
+        hash,
+        file_size,
+        source_last_modified_date: last_modified,
+        force_overwrite_backup: true,
+        updated: !prepped.new,
+        unchanged: false,
+        ..prepped
+    })
+}
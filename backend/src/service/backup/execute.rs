@@ -0,0 +1,262 @@
+use super::plan::FilePlan;
+use super::Pipeline;
+use crate::models::config::Config;
+use crate::models::error::{BackupError, Result};
+use crate::models::prepped_backup::PreppedBackup;
+use crate::service::hash::SampledHasher;
+use crate::service::sqlite_snapshot::{is_live_database, snapshot_database};
+use crate::service::trash::{restore_trashed_backup, trash_replaced_backup};
+use crate::service::verify::streams;
+use crate::utils::chaos;
+use crate::utils::directory::long_path;
+use crate::utils::file_copy::{copy_file, copy_file_through};
+use log::{info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// What was written for one planned file
+pub(super) struct ExecutedFile {
+    pub prepped: PreppedBackup,
+    pub writes: Vec<Write>,
+}
+
+/// One destination's write, named by its backup path or plugin label
+pub(super) struct Write {
+    pub destination: String,
+    pub result: Result<Written>,
+}
+
+pub(super) enum Written {
+    /// Copied to a destination; `streamed` holds the hash and length taken while copying, and
+    /// `trashed` where the backup it replaced went, to be put back if the copy fails verification
+    Copied {
+        backup_path: PathBuf,
+        streamed: Option<(String, u64)>,
+        trashed: Option<PathBuf>,
+    },
+    /// A SQLite database in use, written from a consistent snapshot
+    Snapshot { backup_path: PathBuf },
+    /// The backup of the path the source file was moved from, moved to `backup_path`
+    Moved { from: PathBuf, backup_path: PathBuf },
+    /// Handed to a plugin destination
+    Sent,
+    /// What a dry run would have written
+    Simulated,
+}
+
+impl Written {
+    /// A copy that replaced the backup now at `trashed`
+    fn trashed_to(self, trashed: Option<PathBuf>) -> Self {
+        match self {
+            Written::Copied {
+                backup_path,
+                streamed,
+                ..
+            } => Written::Copied {
+                backup_path,
+                streamed,
+                trashed,
+            },
+            written => written,
+        }
+    }
+}
+
+/// Write a file to every destination its plan names, or log what would be written in a dry run
+pub(super) fn execute(pipeline: &Pipeline, plan: FilePlan) -> ExecutedFile {
+    let FilePlan {
+        prepped,
+        copies,
+        moves,
+        send_to_plugins,
+    } = plan;
+    let copy_files = pipeline.dry_run_mode.should_copy_files();
+    let mut writes = Vec::new();
+
+    for (from, backup_path) in moves {
+        let result = if copy_files {
+            move_backup(&from, backup_path.clone()).or_else(|e| {
+                warn!(
+                    "Failed to move {:?} → {:?}, copying instead: {}",
+                    from, backup_path, e
+                );
+                copy(pipeline, &prepped, &backup_path)
+            })
+        } else {
+            info!("Would move: {:?} → {:?}", from, backup_path);
+            Ok(Written::Simulated)
+        };
+        writes.push(Write {
+            destination: backup_path.to_string_lossy().to_string(),
+            result,
+        });
+    }
+
+    for backup_path in copies {
+        let result = if copy_files {
+            copy(pipeline, &prepped, &backup_path)
+        } else {
+            info!("Would copy: {:?} → {:?}", prepped.source_file, backup_path);
+            Ok(Written::Simulated)
+        };
+        writes.push(Write {
+            destination: backup_path.to_string_lossy().to_string(),
+            result,
+        });
+    }
+
+    if send_to_plugins {
+        if copy_files {
+            for (destination, result) in pipeline.plugin_destinations.store(&prepped) {
+                writes.push(Write {
+                    destination,
+                    result: result.map(|_| Written::Sent),
+                });
+            }
+        } else {
+            for destination in pipeline.plugin_destinations.labels() {
+                info!("Would send {:?} to {}", prepped.source_file, destination);
+                writes.push(Write {
+                    destination,
+                    result: Ok(Written::Simulated),
+                });
+            }
+        }
+    }
+
+    ExecutedFile { prepped, writes }
+}
+
+/// Copy the file to `backup_path`, waiting for a slot at its destination
+fn copy(pipeline: &Pipeline, prepped: &PreppedBackup, backup_path: &Path) -> Result<Written> {
+    pipeline
+        .locked_files
+        .retry(&prepped.source_name, &prepped.source_file, || {
+            let _slot = pipeline.destination_slot(backup_path);
+            let _tracked = pipeline.state.map(|st| {
+                st.track_copy(
+                    &prepped.source_name,
+                    &prepped.source_file,
+                    backup_path,
+                    prepped.file_size,
+                )
+            });
+            write_backup(prepped, backup_path.to_path_buf(), pipeline)
+        })
+}
+
+/// Move the backup of a moved file's old path to its new one
+fn move_backup(from: &Path, backup_path: PathBuf) -> Result<Written> {
+    let parent = backup_path.parent().ok_or_else(|| {
+        BackupError::DirectoryRead(format!("No parent directory for {:?}", backup_path))
+    })?;
+    fs::create_dir_all(long_path(parent))?;
+    info!("Moving: {:?} → {:?}", from, backup_path);
+    fs::rename(long_path(from), long_path(&backup_path))?;
+    Ok(Written::Moved {
+        from: from.to_path_buf(),
+        backup_path,
+    })
+}
+
+/// Write a copy to the run's workspace at its destination and rename it into place, so a copy
+/// cut short never takes the place of a backup
+fn write_backup(
+    prepped_backup: &PreppedBackup,
+    backup_path: PathBuf,
+    pipeline: &Pipeline,
+) -> Result<Written> {
+    let config = pipeline.config;
+    let parent = backup_path.parent().ok_or_else(|| {
+        BackupError::DirectoryRead(format!("No parent directory for {:?}", backup_path))
+    })?;
+
+    if !fs::exists(long_path(parent)).unwrap_or(false) {
+        fs::create_dir_all(long_path(parent))?;
+    }
+
+    // A file copied every run replaces its backup with the same content unless it changed
+    let trash = |backup_path: &Path| {
+        if prepped_backup.always_copy && !prepped_backup.new && !prepped_backup.updated {
+            Ok(None)
+        } else {
+            trash_replaced_backup(config, backup_path)
+        }
+    };
+
+    let Some(staged) = pipeline.workspace.stage(config, &backup_path)? else {
+        // The backup being replaced is kept in the trash, and put back if its replacement fails
+        let trashed = trash(&backup_path)?;
+        let written = replace_backup(prepped_backup, &backup_path, backup_path.clone(), config);
+        if let (Err(_), Some(trashed)) = (&written, &trashed) {
+            restore_trashed_backup(config, trashed, &backup_path);
+        }
+        return written.map(|written| written.trashed_to(trashed));
+    };
+
+    let written =
+        replace_backup(prepped_backup, &staged, backup_path.clone(), config).and_then(|written| {
+            let trashed = trash(&backup_path)?;
+            if let Err(e) = fs::rename(long_path(&staged), long_path(&backup_path)) {
+                if let Some(trashed) = &trashed {
+                    restore_trashed_backup(config, trashed, &backup_path);
+                }
+                return Err(e.into());
+            }
+            Ok(written.trashed_to(trashed))
+        });
+    if written.is_err() {
+        let _ = fs::remove_file(long_path(&staged));
+    }
+    written
+}
+
+/// Write `backup_path`'s copy of the file to `target`
+fn replace_backup(
+    prepped_backup: &PreppedBackup,
+    target: &Path,
+    backup_path: PathBuf,
+    config: &Config,
+) -> Result<Written> {
+    if let Some(method) = &config.sqlite_snapshot {
+        if is_live_database(&prepped_backup.read_from) {
+            info!(
+                "Snapshotting live SQLite database: {:?} → {:?}",
+                &prepped_backup.source_file, backup_path
+            );
+            snapshot_database(method, &prepped_backup.read_from, &long_path(target))?;
+            return Ok(Written::Snapshot { backup_path });
+        }
+    }
+
+    info!(
+        "Copying: {:?} → {:?}",
+        &prepped_backup.source_file, backup_path
+    );
+
+    let from = long_path(&prepped_backup.read_from);
+    let to = long_path(target);
+    let copy_error = |cause| BackupError::FileCopy {
+        from: prepped_backup.source_file.clone(),
+        to: backup_path.clone(),
+        cause,
+    };
+    chaos::before_copy(&prepped_backup.source_file).map_err(copy_error)?;
+
+    let streamed = if streams(&config.verification_tiers, prepped_backup.file_size) {
+        let mut hasher = SampledHasher::new(prepped_backup.hash_limit, prepped_backup.file_size);
+        let bytes_copied = copy_file_through(&from, &to, config.copy_buffer_kib, &mut |chunk| {
+            hasher.update(chunk)
+        })
+        .map_err(copy_error)?;
+        Some((hasher.finish(), bytes_copied))
+    } else {
+        copy_file(&from, &to, config.copy_buffer_kib).map_err(copy_error)?;
+        None
+    };
+    Ok(Written::Copied {
+        backup_path,
+        streamed,
+        trashed: None,
+    })
+}
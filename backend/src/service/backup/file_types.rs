@@ -0,0 +1,141 @@
+use crate::models::prepped_backup::PreppedBackup;
+use crate::models::run_report::FileTypeSummary;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Extensions reported for each source, by bytes; the rest are counted together
+const MAX_FILE_TYPES_PER_SOURCE: usize = 25;
+
+/// Files and bytes of each extension in each source, counted as files are prepared
+#[derive(Default)]
+pub(super) struct FileTypeTally {
+    counts: Mutex<HashMap<(String, String), (u64, u64)>>,
+}
+
+impl FileTypeTally {
+    pub(super) fn count(&self, prepped: &PreppedBackup) {
+        let extension = Path::new(&prepped.file_name)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let mut counts = self.counts.lock().unwrap();
+        let (files, bytes) = counts
+            .entry((prepped.source_name.clone(), extension))
+            .or_default();
+        *files += 1;
+        *bytes += prepped.file_size;
+    }
+
+    /// Each source's extensions, sources by name and their extensions largest first
+    pub(super) fn into_report(self) -> Vec<FileTypeSummary> {
+        let mut by_source: HashMap<String, Vec<FileTypeSummary>> = HashMap::new();
+        for ((source, extension), (files, bytes)) in self.counts.into_inner().unwrap() {
+            by_source
+                .entry(source.clone())
+                .or_default()
+                .push(FileTypeSummary {
+                    source,
+                    extension,
+                    files,
+                    bytes,
+                });
+        }
+        let mut sources: Vec<_> = by_source.into_iter().collect();
+        sources.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut report = Vec::new();
+        for (source, mut types) in sources {
+            types.sort_by(|a, b| {
+                (b.bytes, b.files, &a.extension).cmp(&(a.bytes, a.files, &b.extension))
+            });
+            if types.len() > MAX_FILE_TYPES_PER_SOURCE {
+                let rest = types.split_off(MAX_FILE_TYPES_PER_SOURCE - 1);
+                types.push(FileTypeSummary {
+                    source,
+                    extension: "*".to_string(),
+                    files: rest.iter().map(|t| t.files).sum(),
+                    bytes: rest.iter().map(|t| t.bytes).sum(),
+                });
+            }
+            report.extend(types);
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::config::HashLimit;
+    use std::time::Duration;
+
+    fn prepped(source_name: &str, file_name: &str, file_size: u64) -> PreppedBackup {
+        PreppedBackup {
+            source_path: String::new(),
+            source_name: source_name.to_string(),
+            hash_limit: HashLimit::new(1),
+            force_overwrite_backup: false,
+            source_file: file_name.into(),
+            read_from: file_name.into(),
+            relative_path: file_name.into(),
+            file_name: file_name.to_string(),
+            backup_paths: Vec::new(),
+            renamed_backups: Vec::new(),
+            hash: String::new(),
+            file_size,
+            source_last_modified_date: Duration::ZERO,
+            new: true,
+            updated: false,
+            unchanged: false,
+            always_copy: false,
+            moved_from: None,
+        }
+    }
+
+    #[test]
+    fn test_file_types_are_counted_per_source_largest_first() {
+        let tally = FileTypeTally::default();
+        for (source, file, size) in [
+            ("photos", "a.JPG", 300),
+            ("photos", "b.jpg", 200),
+            ("photos", "notes.txt", 10),
+            ("photos", ".hidden", 4),
+            ("cache", "x.tmp", 7),
+        ] {
+            tally.count(&prepped(source, file, size));
+        }
+        // Past the most extensions reported, the smallest are counted together
+        for index in 0..=MAX_FILE_TYPES_PER_SOURCE {
+            tally.count(&prepped(
+                "many",
+                &format!("f.e{:02}", index),
+                index as u64 + 1,
+            ));
+        }
+
+        let report = tally.into_report();
+        let types: Vec<_> = report
+            .iter()
+            .filter(|t| t.source != "many")
+            .map(|t| (t.source.as_str(), t.extension.as_str(), t.files, t.bytes))
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                ("cache", "tmp", 1, 7),
+                ("photos", "jpg", 2, 500),
+                ("photos", "txt", 1, 10),
+                ("photos", "", 1, 4),
+            ]
+        );
+        let many: Vec<_> = report.iter().filter(|t| t.source == "many").collect();
+        assert_eq!(many.len(), MAX_FILE_TYPES_PER_SOURCE);
+        assert_eq!(many[0].extension, format!("e{}", MAX_FILE_TYPES_PER_SOURCE));
+        let rest = many.last().unwrap();
+        assert_eq!(
+            (rest.extension.as_str(), rest.files, rest.bytes),
+            ("*", 2, 3)
+        );
+    }
+}
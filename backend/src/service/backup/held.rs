@@ -0,0 +1,242 @@
+use super::order::CopyOrderKey;
+use crate::models::config::Config;
+use crate::models::error::{BackupError, Result};
+use crate::models::prepped_backup::PreppedBackup;
+use crate::service::workspace::RunWorkspace;
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Written-out batches merged at once; beyond this they are first merged into one, so only so
+/// many files are open at a time
+const MAX_MERGED_BATCHES: usize = 64;
+
+/// Prepared files held back until every file is prepared, released in the order `copy_order`
+/// asks for
+///
+/// Up to `candidate_batch_size` files are kept in memory. Each full batch is sorted and written
+/// out as JSON lines, and the batches are merged as the files are released, so memory stays
+/// flat however many files a run holds.
+pub(super) struct HeldFiles<'a> {
+    order: CopyOrderKey,
+    batch_size: usize,
+    workspace: &'a RunWorkspace,
+    batches: Mutex<Batches>,
+}
+
+#[derive(Default)]
+struct Batches {
+    /// Files held so far, so files with the same key keep the order they were prepared in
+    held: u64,
+    batch: Vec<Held>,
+    /// Where the batches are written out, in the run's workspace, once the first is
+    dir: Option<PathBuf>,
+    written: Vec<PathBuf>,
+    error: Option<BackupError>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Held {
+    key: (i64, u64),
+    prepped: PreppedBackup,
+}
+
+impl<'a> HeldFiles<'a> {
+    /// Files held by a run, written out to its local workspace
+    pub(super) fn new(config: &Config, workspace: &'a RunWorkspace) -> Self {
+        HeldFiles {
+            order: CopyOrderKey::new(config),
+            batch_size: config.candidate_batch_size.max(1),
+            workspace,
+            batches: Mutex::new(Batches::default()),
+        }
+    }
+
+    pub(super) fn hold(&self, prepped: PreppedBackup) {
+        let mut batches = self.batches.lock().unwrap();
+        let key = (self.order.key(&prepped), batches.held);
+        batches.held += 1;
+        batches.batch.push(Held { key, prepped });
+        if batches.batch.len() < self.batch_size || batches.error.is_some() {
+            return;
+        }
+        let batch = std::mem::take(&mut batches.batch);
+        if let Err(e) = self.write_out(&mut batches, batch) {
+            batches.error = Some(e);
+        }
+    }
+
+    /// Hand every held file to `send` in order, until it returns false
+    pub(super) fn release(&self, mut send: impl FnMut(PreppedBackup) -> bool) -> Result<()> {
+        let (mut batch, written) = {
+            let mut batches = self.batches.lock().unwrap();
+            if let Some(e) = batches.error.take() {
+                return Err(e);
+            }
+            (
+                std::mem::take(&mut batches.batch),
+                std::mem::take(&mut batches.written),
+            )
+        };
+        batch.sort_by_key(|held| held.key);
+        let mut sources = vec![in_memory(batch)];
+        for path in &written {
+            sources.push(from_file(path)?);
+        }
+        merge(sources, |held| Ok(send(held.prepped)))
+    }
+
+    fn write_out(&self, batches: &mut Batches, mut batch: Vec<Held>) -> Result<()> {
+        let dir = match &batches.dir {
+            Some(dir) => dir.clone(),
+            None => {
+                let dir = self.workspace.local_dir()?.join("held");
+                fs::create_dir_all(&dir)?;
+                batches.dir.insert(dir).clone()
+            }
+        };
+        batch.sort_by_key(|held| held.key);
+        let path = dir.join(format!("batch-{}.jsonl", batches.held));
+        let mut out = BufWriter::new(File::create(&path)?);
+        for held in &batch {
+            write_held(&mut out, held)?;
+        }
+        out.flush()?;
+        batches.written.push(path);
+
+        if batches.written.len() >= MAX_MERGED_BATCHES {
+            let merged_path = dir.join(format!("merged-{}.jsonl", batches.held));
+            let mut out = BufWriter::new(File::create(&merged_path)?);
+            let sources = batches
+                .written
+                .iter()
+                .map(|path| from_file(path))
+                .collect::<Result<Vec<_>>>()?;
+            merge(sources, |held| write_held(&mut out, &held))?;
+            out.flush()?;
+            for path in std::mem::replace(&mut batches.written, vec![merged_path]) {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for HeldFiles<'_> {
+    fn drop(&mut self) {
+        if let Some(dir) = &self.batches.get_mut().unwrap().dir {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
+
+type HeldSource = Box<dyn Iterator<Item = Result<Held>>>;
+
+fn in_memory(batch: Vec<Held>) -> HeldSource {
+    Box::new(batch.into_iter().map(Ok))
+}
+
+fn from_file(path: &Path) -> Result<HeldSource> {
+    let lines = BufReader::new(File::open(path)?).lines();
+    Ok(Box::new(lines.map(|line| {
+        serde_json::from_str(&line?).map_err(|e| BackupError::Io(e.into()))
+    })))
+}
+
+fn write_held(out: &mut impl Write, held: &Held) -> Result<bool> {
+    serde_json::to_writer(&mut *out, held).map_err(|e| BackupError::Io(e.into()))?;
+    out.write_all(b"\n")?;
+    Ok(true)
+}
+
+/// Feed the held files of `sources`, each already in order, to `sink` in order, until it
+/// returns false
+fn merge(mut sources: Vec<HeldSource>, mut sink: impl FnMut(Held) -> Result<bool>) -> Result<()> {
+    let mut heads: Vec<Option<Held>> = Vec::with_capacity(sources.len());
+    let mut queue = BinaryHeap::new();
+    for (index, source) in sources.iter_mut().enumerate() {
+        let head = source.next().transpose()?;
+        if let Some(held) = &head {
+            queue.push(Reverse((held.key, index)));
+        }
+        heads.push(head);
+    }
+    while let Some(Reverse((_, index))) = queue.pop() {
+        let held = heads[index].take().expect("every queued source has a head");
+        if let Some(next) = sources[index].next().transpose()? {
+            queue.push(Reverse((next.key, index)));
+            heads[index] = Some(next);
+        }
+        if !sink(held)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::config::{CopyOrder, HashLimit};
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_held_files_beyond_a_batch_are_written_out_and_merged_in_order() {
+        let database = TempDir::new().unwrap();
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "database_file": database.path().join("backup.db"),
+            "backup_sources": [],
+            "backup_destinations": [],
+            "copy_order": "smallest_first",
+            "candidate_batch_size": 3
+        }))
+        .unwrap();
+        assert_eq!(config.copy_order, CopyOrder::SmallestFirst);
+        let workspace = RunWorkspace::new(&config, "run");
+        let held = HeldFiles::new(&config, &workspace);
+
+        let sizes = [50, 7, 300, 7, 1, 90, 7, 42, 0, 8];
+        for (index, size) in sizes.iter().enumerate() {
+            held.hold(PreppedBackup {
+                source_path: String::new(),
+                source_name: String::new(),
+                hash_limit: HashLimit::new(1),
+                force_overwrite_backup: false,
+                source_file: format!("{}", index).into(),
+                read_from: PathBuf::new(),
+                relative_path: PathBuf::new(),
+                file_name: format!("{}", index),
+                backup_paths: Vec::new(),
+                renamed_backups: Vec::new(),
+                hash: String::new(),
+                file_size: *size,
+                source_last_modified_date: Duration::ZERO,
+                new: true,
+                updated: false,
+                unchanged: false,
+                always_copy: false,
+                moved_from: None,
+            });
+        }
+        let dir = database.path().join(".work").join("run").join("held");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 3);
+
+        let mut released = Vec::new();
+        held.release(|prepped| {
+            released.push((prepped.file_size, prepped.file_name));
+            true
+        })
+        .unwrap();
+        let names: Vec<&str> = released.iter().map(|(_, name)| name.as_str()).collect();
+        // The three files of 7 bytes keep the order they were held in
+        assert_eq!(names, ["8", "4", "1", "3", "6", "9", "7", "0", "5", "2"]);
+
+        drop(held);
+        assert!(!dir.exists());
+    }
+}
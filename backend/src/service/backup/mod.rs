@@ -0,0 +1,1327 @@
+mod changed;
+mod execute;
+mod file_types;
+mod held;
+mod moved;
+mod order;
+mod plan;
+mod prepare;
+mod reconcile;
+mod throughput;
+mod verify;
+
+use crate::models::config::{Config, CopyOrder};
+use crate::models::dry_run_mode::DryRunMode;
+use crate::models::error::{BackupError, Result};
+use crate::models::file_operation::FileOperationEntry;
+use crate::models::prepped_backup::PreppedBackup;
+use crate::models::run_diff::RunFileAction;
+use crate::models::run_report::{DestinationSummary, RunReport, SourceSummary};
+use crate::repo::sqlite::format_bytes;
+use crate::service::catalog_journal::{recover_catalog_journals, RunJournal};
+use crate::service::clock_skew::SkewTracker;
+use crate::service::destination_quota::QuotaTracker;
+use crate::service::discovery::{DiscoveredFiles, DiscoveryCount};
+use crate::service::hash_cache::expire_cached_hashes;
+use crate::service::locked_files::LockedFileTracker;
+use crate::service::mass_change::MassChangeCheck;
+use crate::service::operation_log::record_file_operation;
+use crate::service::plugin::PluginDestinations;
+use crate::service::post_process::PostProcessors;
+use crate::service::report_sink::ReportSink;
+use crate::service::run_budget::RunBudget;
+use crate::service::run_diff::FileActionLog;
+use crate::service::tombstones::record_tombstones;
+use crate::service::workspace::{clean_abandoned_workspaces, RunWorkspace};
+use crate::utils::cancel::is_cancel_requested;
+use crate::utils::semaphore::{Permit, Semaphore};
+use changed::ChangedFiles;
+use file_types::FileTypeTally;
+use held::HeldFiles;
+use indicatif::ProgressBar;
+use log::{debug, info, warn};
+use moved::MovedFiles;
+use prepare::prepare_backup_candidates;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use throughput::Throughput;
+use tracing::{info_span, Span};
+
+/// Prepared files buffered between preparation and the copy workers before preparation waits
+const COPY_QUEUE_SIZE: usize = 256;
+
+/// State shared by the stages of one run
+struct Pipeline<'a> {
+    run_id: &'a str,
+    config: &'a Config,
+    dry_run_mode: DryRunMode,
+    state: Option<&'a crate::api_state::AppState>,
+    sink: &'a ReportSink,
+    budget: &'a RunBudget,
+    backup_progress: Option<&'a ProgressBar>,
+    post_processors: PostProcessors,
+    plugin_destinations: PluginDestinations,
+    /// Files found by discovery so far, the total progress is reported against
+    discovered: Arc<DiscoveryCount>,
+    /// Cleared once every discovered file has been prepared
+    preparing: AtomicBool,
+    /// Set when discovery failed, so the remaining prepared files aren't copied
+    aborted: AtomicBool,
+    /// Stop the run at the first file that fails to prepare or copy
+    fail_on_error: bool,
+    /// The failure that stopped the run with `fail_on_error`
+    first_failure: Mutex<Option<String>>,
+    files_prepared: AtomicU64,
+    files_processed: AtomicU64,
+    /// Files found but not copied, e.g. left for the next run or failed to prepare, so progress
+    /// still reaches every file found
+    files_passed_over: AtomicU64,
+    /// Files that changed while they were copied and are copied again, added to the total
+    files_requeued: AtomicU64,
+    /// Highest percentage reported, so progress never goes back as discovery finds more files
+    percentage_reported: Mutex<f32>,
+    bytes_processed: AtomicU64,
+    /// Recent progress, for the speed and time left shown with it
+    throughput: Throughput,
+    error_count: AtomicU64,
+    source_summaries: Mutex<HashMap<String, SourceSummary>>,
+    destination_summaries: Mutex<HashMap<String, DestinationSummary>>,
+    clock_skew: SkewTracker,
+    locked_files: LockedFileTracker,
+    quotas: QuotaTracker,
+    /// What the run found of each prepared file, for comparing runs
+    file_actions: FileActionLog,
+    /// Limits on the files copied to or verified at once, by destination path
+    destination_slots: HashMap<String, Semaphore>,
+    /// Catalog changes of the run, applied once it finishes
+    journal: RunJournal,
+    /// Files whose source changed while they were copied
+    changed: ChangedFiles,
+    /// Recorded source files that new files were moved from
+    moved: MovedFiles,
+    /// Files prepared by source and extension
+    file_types: FileTypeTally,
+    /// Where the run stages its copies and writes out the files it holds
+    workspace: RunWorkspace,
+    /// Span of the run, entered by the threads working on it
+    run_span: Span,
+}
+
+/// Back up discovered files as they stream in
+///
+/// Each file passes through the stages in order, each handing a typed result to the next:
+///
+/// - Discover (`service::discovery`): `DiscoveredFile`
+/// - Prepare: hash the file and look up its record, giving a `PreppedBackup`
+/// - Plan: decide which destinations need a copy, or a moved file's backup moved, giving a
+///   `FilePlan`
+/// - Execute: copy, move, snapshot or send the file, giving an `ExecutedFile`
+/// - Verify: check the copies against the source, giving a `VerifiedFile`
+/// - Reconcile: record the backups and fold the outcomes into the report
+///
+/// The stages overlap: files are prepared on the rayon pool as discovery finds them, and each
+/// prepared file is handed to `max_threads` copy workers that run the remaining stages, so
+/// destinations are written while hashing continues. Each stage's progress bar grows as the
+/// stage before it finds more work. Returns a report with the per-source and per-destination
+/// results filled in.
+///
+/// Catalog changes are staged in the run's journal and applied together once the run finishes,
+/// so a run that fails partway leaves the catalog as it was before the run.
+#[allow(clippy::too_many_arguments)]
+pub fn backup_files(
+    run_id: &str,
+    backup_candidates: DiscoveredFiles,
+    config: &Config,
+    prep_progress: Option<&ProgressBar>,
+    backup_progress: Option<&ProgressBar>,
+    dry_run_mode: DryRunMode,
+    state: Option<&crate::api_state::AppState>,
+    sink: &ReportSink,
+    budget: &RunBudget,
+    mass_change: &MassChangeCheck,
+    fail_on_error: bool,
+) -> Result<RunReport> {
+    info!(
+        "Starting backup to {} destinations...",
+        config.backup_destinations.len()
+    );
+    if dry_run_mode.should_update_database() {
+        recover_catalog_journals()?;
+    }
+    if dry_run_mode.should_copy_files() {
+        clean_abandoned_workspaces(config);
+    }
+
+    let pipeline = Pipeline {
+        run_id,
+        config,
+        dry_run_mode,
+        state,
+        sink,
+        budget,
+        backup_progress,
+        post_processors: PostProcessors::from_config(config),
+        plugin_destinations: PluginDestinations::start(config),
+        discovered: backup_candidates.count(),
+        preparing: AtomicBool::new(true),
+        aborted: AtomicBool::new(false),
+        fail_on_error,
+        first_failure: Mutex::new(None),
+        files_prepared: AtomicU64::new(0),
+        files_processed: AtomicU64::new(0),
+        files_passed_over: AtomicU64::new(0),
+        files_requeued: AtomicU64::new(0),
+        percentage_reported: Mutex::new(0.0),
+        bytes_processed: AtomicU64::new(0),
+        throughput: Throughput::new(),
+        error_count: AtomicU64::new(0),
+        source_summaries: Mutex::new(HashMap::new()),
+        destination_summaries: Mutex::new(HashMap::new()),
+        clock_skew: SkewTracker::new(config.clock_skew_tolerance_secs),
+        locked_files: LockedFileTracker::new(&config.locked_files),
+        quotas: QuotaTracker::new(config)?,
+        file_actions: FileActionLog::new(run_id, config, dry_run_mode),
+        destination_slots: config
+            .backup_destinations
+            .iter()
+            .filter_map(|d| {
+                Some((
+                    d.path().to_string(),
+                    Semaphore::new(d.max_parallel_files()?),
+                ))
+            })
+            .collect(),
+        journal: RunJournal::begin(run_id),
+        changed: ChangedFiles::default(),
+        moved: MovedFiles::default(),
+        file_types: FileTypeTally::default(),
+        workspace: RunWorkspace::new(config, run_id),
+        run_span: Span::current(),
+    };
+    pipeline.report_progress(None, None);
+
+    let (prepped_sender, prepped_receiver) = mpsc::sync_channel(COPY_QUEUE_SIZE);
+    // Shared by the workers so the queue closes if they all exit early
+    let prepped_receiver = Arc::new(Mutex::new(prepped_receiver));
+    // Copy workers get their own threads so they never wait on the pool that's hashing
+    let prepared = thread::scope(|scope| {
+        for _ in 0..config.max_threads.max(1) {
+            let receiver = Arc::clone(&prepped_receiver);
+            let pipeline = &pipeline;
+            scope.spawn(move || {
+                let _run_span = pipeline.run_span.enter();
+                loop {
+                    let next = receiver.lock().unwrap().recv();
+                    match next {
+                        Ok(prepped) => pipeline.process(prepped),
+                        Err(_) => break,
+                    }
+                }
+            });
+        }
+        drop(prepped_receiver);
+
+        // Only fails once every copy worker has exited, which only a panic causes
+        let send = |prepped| prepped_sender.send(prepped).is_ok();
+        // Every file has to be prepared before the first one can be picked, or the changes since
+        // the last run counted, so with an order or a mass change guard set, the prepared files
+        // are held back until then
+        let holds = config.copy_order != CopyOrder::Discovered || mass_change.holds_files();
+        let held = HeldFiles::new(config, &pipeline.workspace);
+        let prepared =
+            prepare_backup_candidates(backup_candidates, &pipeline, prep_progress, |prepped| {
+                // Recorded as prepared, so a held run's changes can be counted from the records
+                pipeline.file_actions.record(RunFileAction {
+                    source: prepped.source_name.clone(),
+                    file: prepped.source_file.to_string_lossy().to_string(),
+                    action: prepped.action(),
+                });
+                mass_change.observe(&prepped);
+                if holds {
+                    held.hold(prepped);
+                } else {
+                    send(prepped);
+                }
+            });
+        pipeline.preparing.store(false, Ordering::Relaxed);
+        pipeline.report_progress(None, None);
+
+        let checked = prepared.and_then(|_| {
+            pipeline.file_actions.finish();
+            match mass_change.check(run_id, pipeline.file_actions.records())? {
+                Some(change) => mass_change.hold(change, state).map(Some),
+                None => Ok(None),
+            }
+        });
+        let checked = checked.and_then(|change| held.release(send).map(|_| change));
+        drop(prepped_sender);
+        checked
+    });
+    let mass_change = prepared?;
+    pipeline.file_actions.finish();
+    let changed_during_run = pipeline.changed.recopy(&pipeline);
+    let torn = changed_during_run.iter().filter(|c| !c.recopied).count();
+    if let (true, Some(st)) = (torn > 0, state) {
+        st.notify_message(format!(
+            "{} file(s) changed while they were copied and may be torn; the next run copies them again",
+            torn
+        ));
+    }
+    // Returning before the commit rolls back the run's catalog changes
+    if let Some(e) = pipeline.locked_files.failure() {
+        return Err(e);
+    }
+    if let Some(failure) = pipeline.first_failure.lock().unwrap().take() {
+        return Err(BackupError::FailedFile(failure));
+    }
+    info!(
+        "Prepared {} files for backup",
+        pipeline.files_prepared.load(Ordering::Relaxed)
+    );
+
+    let committed = pipeline.journal.commit()?;
+    debug!("Applied {} catalog change(s)", committed);
+    let mut tombstones = 0;
+    if dry_run_mode.should_update_database() {
+        expire_cached_hashes();
+        let prepared = pipeline.file_actions.records();
+        tombstones = record_tombstones(config, run_id, prepared).unwrap_or_else(|e| {
+            warn!("Failed to record deleted source files: {}", e);
+            0
+        });
+    }
+
+    let error_count = pipeline.error_count.into_inner();
+    if error_count > 0 {
+        // Return warning if some files failed but operation partially succeeded
+        warn!(
+            "Backup completed with {} error(s). Some files may not have been backed up.",
+            error_count
+        );
+
+        // Update API state with error information
+        if let Some(st) = state {
+            st.notify_message(format!(
+                "Backup completed with {} error(s). Check logs for details.",
+                error_count
+            ));
+        }
+
+        // The run still succeeds: the errors are counted in the report, which `RunEnded::check`
+        // turns into exit code 2, and `--fail-on-error` stops the run at the first one instead
+    }
+
+    let mut summaries: Vec<SourceSummary> = pipeline
+        .source_summaries
+        .into_inner()
+        .unwrap()
+        .into_values()
+        .collect();
+    summaries.sort_by(|a, b| a.name.cmp(&b.name));
+    for summary in &summaries {
+        info!(
+            "Source '{}': {} files prepared, {} copies made, {} backups moved, {} errors",
+            summary.name,
+            summary.files_prepared,
+            summary.files_copied,
+            summary.files_moved,
+            summary.errors
+        );
+    }
+
+    let mut destinations: Vec<DestinationSummary> = pipeline
+        .destination_summaries
+        .into_inner()
+        .unwrap()
+        .into_values()
+        .collect();
+    destinations.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let clock_skew = pipeline.clock_skew.into_report();
+    for skew in &clock_skew {
+        warn!(
+            "Clock skew: {} {} timestamp(s) for '{}' are ahead of the system clock by up to {}s",
+            skew.files,
+            skew.kind.as_str(),
+            skew.location,
+            skew.max_ahead_secs
+        );
+    }
+    if let (false, Some(st)) = (clock_skew.is_empty(), state) {
+        st.notify_message(format!(
+            "Clock skew detected for {} source(s) or destination(s); check their clocks",
+            clock_skew.len()
+        ));
+    }
+
+    let locked_files = pipeline.locked_files.into_report();
+    if !locked_files.is_empty() {
+        warn!(
+            "{} file(s) were locked by other programs and not backed up",
+            locked_files.len()
+        );
+        if let Some(st) = state {
+            st.notify_message(format!(
+                "{} file(s) were locked by other programs and not backed up",
+                locked_files.len()
+            ));
+        }
+    }
+
+    let quota_exceeded = pipeline.quotas.into_report();
+    for exceeded in &quota_exceeded {
+        let message = format!(
+            "Destination '{}' reached its quota of {}; {} file(s), {}, were not copied to it",
+            exceeded.destination,
+            format_bytes(exceeded.max_total_bytes),
+            exceeded.files_skipped,
+            format_bytes(exceeded.bytes_skipped)
+        );
+        warn!("{}", message);
+        if let Some(st) = state {
+            st.notify_message(message);
+        }
+    }
+
+    Ok(RunReport {
+        sources: summaries,
+        destinations,
+        clock_skew,
+        locked_files,
+        quota_exceeded,
+        mass_change,
+        changed_during_run,
+        file_types: pipeline.file_types.into_report(),
+        tombstones,
+        ..Default::default()
+    })
+}
+
+impl Pipeline<'_> {
+    /// Take one prepared file through the plan, execute, verify and reconcile stages
+    fn process(&self, prepped: PreppedBackup) {
+        self.source_summaries
+            .lock()
+            .unwrap()
+            .entry(prepped.source_name.clone())
+            .or_insert_with(|| SourceSummary {
+                name: prepped.source_name.clone(),
+                ..Default::default()
+            })
+            .files_prepared += 1;
+        self.file_types.count(&prepped);
+
+        // Hold while paused, then check stop signal
+        if let Some(st) = self.state {
+            st.wait_while_paused();
+        }
+        if self.stop_requested() {
+            warn!("Backup cancelled by user");
+            return;
+        }
+        if self.aborted.load(Ordering::Relaxed) {
+            return;
+        }
+        self.copy(prepped, &self.changed);
+    }
+
+    /// Plan, execute, verify and reconcile one file, flagging it in `changed` if its source
+    /// changed before the copy finished
+    fn copy(&self, prepped: PreppedBackup, changed: &ChangedFiles) {
+        let _source_span = info_span!("source", source = %prepped.source_name).entered();
+        if self.budget.is_exhausted() {
+            self.defer(&prepped);
+            return;
+        }
+        let plan = plan::plan_file(self, prepped);
+        let copy_bytes = plan.prepped.file_size * plan.copies.len() as u64;
+        if !plan.copies.is_empty() && !self.budget.spend(copy_bytes) {
+            self.defer(&plan.prepped);
+            return;
+        }
+        let executed = execute::execute(self, plan);
+        let verified = verify::verify(self, executed);
+        changed.check(&verified);
+        reconcile::reconcile(self, verified);
+    }
+
+    /// Whether the run was stopped or aborted, so no more files are taken on
+    fn stopped(&self) -> bool {
+        self.aborted.load(Ordering::Relaxed) || self.stop_requested()
+    }
+
+    /// Whether the API or Ctrl+C asked the run to stop
+    fn stop_requested(&self) -> bool {
+        is_cancel_requested() || self.state.is_some_and(|st| st.is_stop_requested())
+    }
+
+    /// Stop taking on files with `fail_on_error`, keeping the first failure as the run's error
+    fn fail(&self, error: &BackupError) {
+        if self.fail_on_error {
+            self.first_failure
+                .lock()
+                .unwrap()
+                .get_or_insert_with(|| error.to_string());
+            self.aborted.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Count a file that failed to prepare as an error of its source
+    fn prepare_failed(&self, source: &str, error: &BackupError) {
+        self.source_summaries
+            .lock()
+            .unwrap()
+            .entry(source.to_string())
+            .or_insert_with(|| SourceSummary {
+                name: source.to_string(),
+                ..Default::default()
+            })
+            .errors += 1;
+        self.fail(error);
+    }
+
+    /// Append a file operation of this run to the `file_operation_log`; dry runs record none
+    fn record_operation(&self, entry: FileOperationEntry) {
+        if !self.dry_run_mode.is_dry_run() {
+            let entry = FileOperationEntry {
+                run_id: Some(self.run_id.to_string()),
+                ..entry
+            };
+            record_file_operation(self.config, entry);
+        }
+    }
+
+    /// Leave a prepared file for the next run, as the run is out of budget
+    fn defer(&self, prepped: &PreppedBackup) {
+        self.budget.defer(&prepped.source_file);
+        self.pass_over();
+        if let Some(pb) = self.backup_progress {
+            pb.inc(1);
+        }
+    }
+
+    /// Count a file found by discovery as done with, though it wasn't copied
+    fn pass_over(&self) {
+        self.files_passed_over.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Wait for a turn to work on `backup_path` if its destination limits the files worked on at
+    /// once; hold the permit for as long as the destination is read or written
+    fn destination_slot(&self, backup_path: &Path) -> Option<Permit<'_>> {
+        let destination = self.config.destination_of(backup_path)?;
+        self.destination_slots
+            .get(destination.path())
+            .map(Semaphore::acquire)
+    }
+
+    /// Publish progress to the API; files count as processed once the copy stage is done with
+    /// them or they are passed over
+    ///
+    /// The total is what discovery has found so far, which only grows, and the percentage never
+    /// goes back, so progress moves one way while the sources are still being walked.
+    fn report_progress(&self, current_file: Option<String>, current_source: Option<String>) {
+        let Some(st) = self.state else {
+            return;
+        };
+
+        let processed = self.files_processed.load(Ordering::Relaxed)
+            + self.files_passed_over.load(Ordering::Relaxed);
+        let bytes = self.bytes_processed.load(Ordering::Relaxed);
+        let total_files = self.discovered.found() + self.files_requeued.load(Ordering::Relaxed);
+        let (phase, phase_description) = if self.preparing.load(Ordering::Relaxed) {
+            (2, "Preparing and copying files")
+        } else {
+            (3, "Copying files")
+        };
+        let estimate = self.throughput.record(processed, bytes, total_files);
+        let percentage = {
+            let mut reported = self.percentage_reported.lock().unwrap();
+            if total_files > 0 {
+                let current = (processed.min(total_files) as f32 / total_files as f32) * 100.0;
+                *reported = reported.max(current);
+            }
+            *reported
+        };
+
+        st.set_progress(Some(crate::models::api::BackupProgress {
+            phase,
+            phase_description: phase_description.to_string(),
+            files_processed: processed,
+            total_files,
+            discovery_complete: self.discovered.is_complete(),
+            bytes_processed: Some(bytes),
+            total_bytes: Some(bytes), // Progressive total
+            percentage,
+            current_file,
+            current_source,
+            elapsed_seconds: estimate.elapsed_seconds,
+            bytes_per_second: estimate.bytes_per_second,
+            eta_seconds: estimate.eta_seconds,
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::backup_marker::BACKUP_MARKER_FILE;
+    use crate::models::config::{HashLimit, MassChangeGuard, SqliteSnapshot};
+    use crate::models::error::BackupError;
+    use crate::models::run_record::RunRecord;
+    use crate::models::run_report::{ChangedDuringRun, QuotaExceeded, SkewKind};
+    use crate::models::run_selection::{RunSelection, SelectedSource};
+    use crate::repo::catalog::{
+        select_backups_of_source, select_source, update_source_last_modified,
+    };
+    use crate::repo::sqlite::{count_run_file_actions, insert_run, set_db_pool, setup_database};
+    use crate::service::discovery::{discover_source_files, discovery_channel};
+    use crate::service::hash::hash_file;
+    use crate::service::marker::write_backup_marker;
+    use crate::service::plugin::PluginFilters;
+    use crate::service::trash::TRASH_DIR;
+    use crate::service::volume_snapshot::SourceSnapshots;
+    use crate::utils::chaos;
+    use serial_test::serial;
+    use std::fs;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    /// Point the database pool at a new database in a temporary directory, which lasts as long
+    /// as the directory returned is kept
+    ///
+    /// A shared in-memory database rejects concurrent writers instead of waiting.
+    fn setup_test_db() -> TempDir {
+        let database = TempDir::new().unwrap();
+        set_db_pool(database.path().join("backup.db").to_str().unwrap()).unwrap();
+        setup_database().unwrap();
+        database
+    }
+
+    #[test]
+    #[serial]
+    fn test_pipeline_backs_up_files_while_they_are_discovered() {
+        let _database = setup_test_db();
+        let source = TempDir::new().unwrap();
+        let destination = TempDir::new().unwrap();
+        for dir in 0..5 {
+            let dir_path = source.path().join(format!("dir{}", dir));
+            fs::create_dir(&dir_path).unwrap();
+            for file in 0..10 {
+                fs::write(
+                    dir_path.join(format!("{}.txt", file)),
+                    format!("{}-{}", dir, file),
+                )
+                .unwrap();
+            }
+        }
+        // Left by a run that crashed partway through a copy
+        let abandoned = destination.path().join(".work").join("crashed");
+        fs::create_dir_all(&abandoned).unwrap();
+        fs::write(abandoned.join(".lock"), "").unwrap();
+        fs::write(abandoned.join("3.part"), "torn").unwrap();
+        let config = pipeline_config(&source, &destination, false);
+        let report = run_pipeline(&config);
+
+        // Copies are staged in the run's own workspace, gone with it
+        assert!(!destination.path().join(".work").exists());
+        assert_eq!(report.sources.len(), 1);
+        assert_eq!(report.sources[0].files_prepared, 50);
+        assert_eq!(report.sources[0].files_copied, 50);
+        assert_eq!(report.sources[0].errors, 0);
+        assert_eq!(report.destinations.len(), 1);
+        assert_eq!(
+            report.destinations[0].name,
+            destination.path().to_str().unwrap()
+        );
+        assert_eq!(report.destinations[0].files_copied, 50);
+        assert_eq!(report.file_types.len(), 1);
+        assert_eq!(
+            (
+                report.file_types[0].extension.as_str(),
+                report.file_types[0].files
+            ),
+            ("txt", 50)
+        );
+        let mirrored = destination
+            .path()
+            .join(source.path().file_name().unwrap())
+            .join("dir4")
+            .join("9.txt");
+        assert_eq!(fs::read_to_string(mirrored).unwrap(), "4-9");
+    }
+
+    #[test]
+    #[serial]
+    fn test_destination_quota_stops_copies_that_would_go_over_it() {
+        let _database = setup_test_db();
+        let source = TempDir::new().unwrap();
+        let destination = TempDir::new().unwrap();
+        for file in 0..4 {
+            fs::write(source.path().join(format!("{}.txt", file)), "ten bytes!").unwrap();
+        }
+        let mut config = pipeline_config(&source, &destination, false);
+        config.backup_destinations = vec![serde_json::from_value(serde_json::json!({
+            "name": "offsite",
+            "path": destination.path(),
+            "max_total_bytes": 25
+        }))
+        .unwrap()];
+
+        let report = run_pipeline(&config);
+        assert_eq!(report.sources[0].files_copied, 2);
+        assert_eq!(
+            report.quota_exceeded,
+            vec![QuotaExceeded {
+                destination: "offsite".to_string(),
+                max_total_bytes: 25,
+                used_bytes: 20,
+                files_skipped: 2,
+                bytes_skipped: 20,
+            }]
+        );
+
+        // The catalog counts what is there already, so a later run copies nothing more
+        let report = run_pipeline(&config);
+        assert_eq!(report.sources[0].files_copied, 0);
+        assert_eq!(report.quota_exceeded[0].files_skipped, 2);
+    }
+
+    #[test]
+    #[serial]
+    fn test_failed_files_are_counted_or_stop_the_run_with_fail_on_error() {
+        let _database = setup_test_db();
+        let source = TempDir::new().unwrap();
+        let destination = TempDir::new().unwrap();
+        fs::write(source.path().join("a.txt"), "kept").unwrap();
+        fs::create_dir(source.path().join("sub")).unwrap();
+        for file in 0..2 {
+            fs::write(
+                source.path().join("sub").join(format!("{}.txt", file)),
+                "lost",
+            )
+            .unwrap();
+        }
+        // A file where the copies of `sub` need a directory
+        let mirror = destination.path().join(source.path().file_name().unwrap());
+        fs::create_dir(&mirror).unwrap();
+        fs::write(mirror.join("sub"), "in the way").unwrap();
+        let config = pipeline_config(&source, &destination, false);
+        let source_dir = source.path().to_string_lossy().to_string();
+
+        let stopped = run_as(&config, "strict", false, true, None);
+        assert!(matches!(stopped, Err(BackupError::FailedFile(_))));
+        // The run's catalog changes are rolled back
+        assert!(select_source("a.txt", &source_dir).unwrap().is_none());
+
+        let report = run_pipeline(&config);
+        assert_eq!(report.sources[0].files_prepared, 3);
+        assert_eq!(report.sources[0].errors, 2);
+        assert!(select_source("a.txt", &source_dir).unwrap().is_some());
+    }
+
+    #[test]
+    #[serial]
+    fn test_mass_change_guard_holds_back_copies_until_confirmed() {
+        let _database = setup_test_db();
+        let source = TempDir::new().unwrap();
+        let destination = TempDir::new().unwrap();
+        for file in 0..4 {
+            fs::write(source.path().join(format!("{}.txt", file)), "original").unwrap();
+        }
+        let mut config = pipeline_config(&source, &destination, false);
+        config.mass_change_guard = Some(MassChangeGuard {
+            max_changed_percent: Some(50.0),
+            max_deleted_files: None,
+        });
+        let record_run = |id: &str, started_at: i64| {
+            insert_run(&RunRecord::finished(
+                id,
+                started_at,
+                started_at + 1,
+                false,
+                None,
+                None,
+            ))
+            .unwrap();
+        };
+
+        // Nothing to compare the first run with
+        let report = run_checked(&config, "first", false).unwrap();
+        record_run("first", 1_000);
+        assert_eq!(report.sources[0].files_copied, 4);
+        assert_eq!(report.mass_change, None);
+
+        for file in 0..3 {
+            let changed = source.path().join(format!("{}.txt", file));
+            fs::write(&changed, "encrypted").unwrap();
+            fs::File::options()
+                .write(true)
+                .open(&changed)
+                .unwrap()
+                .set_modified(std::time::SystemTime::now() + Duration::from_secs(60))
+                .unwrap();
+        }
+        let mirrored = destination.path().join(source.path().file_name().unwrap());
+        assert!(matches!(
+            run_checked(&config, "second", false),
+            Err(BackupError::MassChange(_))
+        ));
+        assert_eq!(
+            fs::read_to_string(mirrored.join("0.txt")).unwrap(),
+            "original"
+        );
+
+        // Counted from the records in the database
+        fs::remove_file(source.path().join("3.txt")).unwrap();
+        let report = run_checked(&config, "third", true).unwrap();
+        assert_eq!(report.sources[0].files_copied, 3);
+        let change = report.mass_change.unwrap();
+        assert_eq!(
+            (change.since_run.as_str(), change.files_before),
+            ("first", 4)
+        );
+        assert_eq!((change.changed_files, change.deleted_files), (3, 1));
+        assert_eq!(change.changed_percent, 75.0);
+        assert!(change.confirmed);
+        assert_eq!(
+            fs::read_to_string(mirrored.join("0.txt")).unwrap(),
+            "encrypted"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_files_changed_before_their_copy_finished_are_copied_again() {
+        let _database = setup_test_db();
+        let source = TempDir::new().unwrap();
+        let destination = TempDir::new().unwrap();
+        let file = source.path().join("notes.txt");
+        fs::write(&file, "first").unwrap();
+        let mut config = pipeline_config(&source, &destination, false);
+        config.recopy_changed_files = true;
+        // Holds the prepared file until the run is confirmed, giving it time to change
+        config.mass_change_guard = Some(MassChangeGuard {
+            max_changed_percent: Some(50.0),
+            max_deleted_files: None,
+        });
+        run_pipeline(&config);
+        insert_run(&RunRecord::finished(
+            "test-run", 1_000, 1_001, false, None, None,
+        ))
+        .unwrap();
+
+        let write_later = |content: &str, secs: u64| {
+            fs::write(&file, content).unwrap();
+            fs::File::options()
+                .write(true)
+                .open(&file)
+                .unwrap()
+                .set_modified(std::time::SystemTime::now() + Duration::from_secs(secs))
+                .unwrap();
+        };
+        write_later("second", 60);
+        let state = crate::api_state::AppState::new();
+        let report = thread::scope(|scope| {
+            let run = scope.spawn(|| run_as(&config, "changing", false, false, Some(&state)));
+            while state.get_status() != crate::models::api::BackupStatus::Paused {
+                thread::sleep(Duration::from_millis(5));
+            }
+            write_later("third", 120);
+            state.confirm_mass_change().unwrap();
+            run.join().unwrap().unwrap()
+        });
+
+        assert_eq!(
+            report.changed_during_run,
+            [ChangedDuringRun {
+                source: config.backup_sources[0].display_name().to_string(),
+                file: file.to_string_lossy().to_string(),
+                recopied: true,
+            }]
+        );
+        let mirrored = destination.path().join(source.path().file_name().unwrap());
+        assert_eq!(
+            fs::read_to_string(mirrored.join("notes.txt")).unwrap(),
+            "third"
+        );
+        // The catalog has the content that was copied
+        let recorded = select_source("notes.txt", &source.path().to_string_lossy())
+            .unwrap()
+            .unwrap();
+        assert_eq!(recorded.file_size, 5);
+    }
+
+    #[test]
+    #[serial]
+    fn test_replaced_backups_are_moved_to_trash() {
+        let _database = setup_test_db();
+        let source = TempDir::new().unwrap();
+        let destination = TempDir::new().unwrap();
+        let file = source.path().join("notes.txt");
+        fs::write(&file, "original").unwrap();
+        let mut config = pipeline_config(&source, &destination, false);
+        config.trash_retention_days = Some(30);
+        run_pipeline(&config);
+
+        fs::write(&file, "rewritten").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(run_pipeline(&config).sources[0].files_copied, 1);
+
+        let mirrored = source.path().file_name().unwrap();
+        assert_eq!(
+            fs::read_to_string(destination.path().join(mirrored).join("notes.txt")).unwrap(),
+            "rewritten"
+        );
+        let trashed = destination
+            .path()
+            .join(TRASH_DIR)
+            .join(chrono::Utc::now().format("%Y-%m-%d").to_string())
+            .join(mirrored)
+            .join("notes.txt");
+        assert_eq!(fs::read_to_string(trashed).unwrap(), "original");
+    }
+
+    #[test]
+    #[serial]
+    fn test_trashed_backups_are_put_back_when_their_replacement_fails_verification() {
+        let _database = setup_test_db();
+        let source = TempDir::new().unwrap();
+        let destination = TempDir::new().unwrap();
+        let file = source.path().join("notes.txt");
+        fs::write(&file, "original").unwrap();
+        let mut config = pipeline_config(&source, &destination, false);
+        config.trash_retention_days = Some(30);
+        run_pipeline(&config);
+
+        fs::write(&file, "rewritten").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        chaos::enable("hash=1,seed=1".parse().unwrap());
+        let report = run_pipeline(&config);
+        chaos::disable();
+        assert_eq!(
+            (report.sources[0].files_copied, report.sources[0].errors),
+            (0, 1)
+        );
+
+        let mirrored = source.path().file_name().unwrap();
+        assert_eq!(
+            fs::read_to_string(destination.path().join(mirrored).join("notes.txt")).unwrap(),
+            "original"
+        );
+        assert!(!destination
+            .path()
+            .join(TRASH_DIR)
+            .join(chrono::Utc::now().format("%Y-%m-%d").to_string())
+            .join(mirrored)
+            .join("notes.txt")
+            .exists());
+    }
+
+    #[test]
+    #[serial]
+    fn test_quick_skip_leaves_unchanged_files_alone() {
+        let _database = setup_test_db();
+        let source = TempDir::new().unwrap();
+        let destination = TempDir::new().unwrap();
+        for file in 0..4 {
+            fs::write(source.path().join(format!("{}.txt", file)), "original").unwrap();
+        }
+        let config = pipeline_config(&source, &destination, true);
+        assert_eq!(run_pipeline(&config).sources[0].files_copied, 4);
+
+        // Destinations aren't looked at, so a deleted backup stays missing
+        let mirrored = destination.path().join(source.path().file_name().unwrap());
+        fs::remove_file(mirrored.join("0.txt")).unwrap();
+        let changed = source.path().join("1.txt");
+        fs::write(&changed, "changed and longer").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&changed)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+
+        let report = run_pipeline(&config);
+        assert_eq!(report.sources[0].files_prepared, 4);
+        assert_eq!(report.sources[0].files_copied, 1);
+        assert!(!mirrored.join("0.txt").exists());
+        // Every prepared file is recorded for run comparisons, skipped or not
+        assert_eq!(count_run_file_actions("test-run").unwrap(), 8);
+        assert_eq!(
+            fs::read_to_string(mirrored.join("1.txt")).unwrap(),
+            "changed and longer"
+        );
+
+        // Without quick skip, the missing backup is noticed and restored
+        let config = pipeline_config(&source, &destination, false);
+        assert_eq!(run_pipeline(&config).sources[0].files_copied, 1);
+        assert!(mirrored.join("0.txt").exists());
+    }
+
+    #[test]
+    #[serial]
+    fn test_small_files_are_copied_every_run() {
+        let _database = setup_test_db();
+        let source = TempDir::new().unwrap();
+        let destination = TempDir::new().unwrap();
+        fs::write(source.path().join("small.txt"), "tiny").unwrap();
+        fs::write(source.path().join("large.txt"), "well over ten bytes").unwrap();
+        let mut config = pipeline_config(&source, &destination, false);
+        config.always_copy_below_bytes = Some(10);
+        config.trash_retention_days = Some(7);
+        assert_eq!(run_pipeline(&config).sources[0].files_copied, 2);
+
+        // The small file's backup isn't checked, so even one damaged at the same size is replaced
+        let mirrored = destination.path().join(source.path().file_name().unwrap());
+        fs::write(mirrored.join("small.txt"), "oops").unwrap();
+        let report = run_pipeline(&config);
+        assert_eq!(report.sources[0].files_copied, 1);
+        assert_eq!(
+            fs::read_to_string(mirrored.join("small.txt")).unwrap(),
+            "tiny"
+        );
+        // Copied again with the same content, so there is nothing worth keeping in trash
+        assert!(!destination.path().join(TRASH_DIR).exists());
+
+        fs::write(source.path().join("small.txt"), "tin2").unwrap();
+        assert_eq!(run_pipeline(&config).sources[0].files_copied, 1);
+        assert!(destination.path().join(TRASH_DIR).exists());
+        assert_eq!(
+            select_source("small.txt", source.path().to_str().unwrap())
+                .unwrap()
+                .unwrap()
+                .hash,
+            hash_file(&source.path().join("small.txt"), HashLimit::new(1)).unwrap()
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_backup_markers_are_never_backed_up() {
+        let _database = setup_test_db();
+        let source = TempDir::new().unwrap();
+        let destination = TempDir::new().unwrap();
+        fs::write(source.path().join("a.txt"), "a").unwrap();
+        let root = source.path().to_str().unwrap();
+        let config = pipeline_config(&source, &destination, false);
+
+        for run in ["run-1", "run-2"] {
+            let report = run_checked(&config, run, false).unwrap();
+            assert_eq!(report.sources[0].files_prepared, 1);
+            write_backup_marker(root, run, &config.destination_paths()).unwrap();
+        }
+        assert!(select_source(BACKUP_MARKER_FILE, root).unwrap().is_none());
+        let mirrored = destination.path().join(source.path().file_name().unwrap());
+        assert!(!mirrored.join(BACKUP_MARKER_FILE).exists());
+    }
+
+    #[test]
+    #[serial]
+    fn test_modification_times_within_the_tolerance_are_not_changes() {
+        let _database = setup_test_db();
+        let source = TempDir::new().unwrap();
+        let destination = TempDir::new().unwrap();
+        let file = source.path().join("photo.jpg");
+        fs::write(&file, "pixels").unwrap();
+        let mut config = pipeline_config(&source, &destination, false);
+        config.mtime_tolerance_secs = 2;
+        assert_eq!(run_pipeline(&config).sources[0].files_copied, 1);
+
+        // Same size and a second later, as a FAT copy rounded up to an even second looks; the
+        // contents change too, to show whether the file was looked at again
+        let modified = fs::metadata(&file).unwrap().modified().unwrap();
+        fs::write(&file, "pixelz").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(modified + Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(run_pipeline(&config).sources[0].files_copied, 0);
+
+        config.mtime_tolerance_secs = 0;
+        assert_eq!(run_pipeline(&config).sources[0].files_copied, 1);
+    }
+
+    #[test]
+    #[serial]
+    fn test_backups_of_moved_files_are_moved_instead_of_copied() {
+        let _database = setup_test_db();
+        let source = TempDir::new().unwrap();
+        let destination = TempDir::new().unwrap();
+        let old = source.path().join("photo.jpg");
+        fs::write(&old, "pixels").unwrap();
+        let config = pipeline_config(&source, &destination, false);
+        assert_eq!(run_pipeline(&config).sources[0].files_copied, 1);
+
+        let new_dir = source.path().join("2024");
+        fs::create_dir(&new_dir).unwrap();
+        fs::rename(&old, new_dir.join("beach.jpg")).unwrap();
+        let report = run_pipeline(&config);
+        assert_eq!(
+            (
+                report.sources[0].files_copied,
+                report.sources[0].files_moved
+            ),
+            (0, 1)
+        );
+
+        let mirrored = destination.path().join(source.path().file_name().unwrap());
+        assert!(!mirrored.join("photo.jpg").exists());
+        let moved = mirrored.join("2024").join("beach.jpg");
+        assert_eq!(fs::read_to_string(&moved).unwrap(), "pixels");
+        let source_path = source.path().to_string_lossy().to_string();
+        assert!(select_source("photo.jpg", &source_path).unwrap().is_none());
+        let row = select_source("beach.jpg", &new_dir.to_string_lossy())
+            .unwrap()
+            .unwrap();
+        let backups = select_backups_of_source(row.id).unwrap();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(
+            Path::new(&backups[0].file_path).join(&backups[0].file_name),
+            moved
+        );
+
+        let report = run_pipeline(&config);
+        assert_eq!(
+            (
+                report.sources[0].files_copied,
+                report.sources[0].files_moved
+            ),
+            (0, 0)
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_file_operations_are_appended_to_the_operation_log() {
+        let database = setup_test_db();
+        let source = TempDir::new().unwrap();
+        let destination = TempDir::new().unwrap();
+        fs::write(source.path().join("a.txt"), "a").unwrap();
+        fs::write(source.path().join("b.txt"), "b").unwrap();
+        let log = database.path().join("operations.jsonl");
+        let config = Config {
+            file_operation_log: Some(log.to_string_lossy().to_string()),
+            ..pipeline_config(&source, &destination, false)
+        };
+        run_pipeline(&config);
+        run_pipeline(&config);
+
+        let entries: Vec<serde_json::Value> = fs::read_to_string(&log)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let count = |operation: &str| {
+            entries
+                .iter()
+                .filter(|entry| entry["operation"] == operation)
+                .count()
+        };
+        assert_eq!(entries.len(), 6);
+        assert_eq!(
+            (count("copied"), count("verified"), count("skipped")),
+            (2, 2, 2)
+        );
+        assert!(entries.iter().all(|entry| entry["run_id"] == "test-run"));
+        assert!(entries[0]["hash"]
+            .as_str()
+            .is_some_and(|hash| !hash.is_empty()));
+        assert_eq!(entries[0]["bytes"], 1);
+    }
+
+    #[test]
+    #[serial]
+    fn test_recorded_times_in_the_future_do_not_hide_changes() {
+        let _database = setup_test_db();
+        let source = TempDir::new().unwrap();
+        let destination = TempDir::new().unwrap();
+        let file = source.path().join("ledger.txt");
+        fs::write(&file, "version 1").unwrap();
+        let config = pipeline_config(&source, &destination, false);
+        assert_eq!(run_pipeline(&config).sources[0].files_copied, 1);
+
+        // As if the database was written while the clock was an hour fast
+        let row = select_source("ledger.txt", &source.path().to_string_lossy())
+            .unwrap()
+            .unwrap();
+        let future = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            + Duration::from_secs(3600);
+        update_source_last_modified(row.id, &future).unwrap();
+        fs::write(&file, "version 2").unwrap();
+
+        let report = run_pipeline(&config);
+        assert_eq!(report.sources[0].files_copied, 1);
+        let mirrored = destination.path().join(source.path().file_name().unwrap());
+        assert_eq!(
+            fs::read_to_string(mirrored.join("ledger.txt")).unwrap(),
+            "version 2"
+        );
+        assert_eq!(report.clock_skew.len(), 1);
+        assert_eq!(report.clock_skew[0].kind, SkewKind::Database);
+        assert!(report.clock_skew[0].max_ahead_secs > 3500);
+
+        // The corrected record no longer looks skewed
+        assert!(run_pipeline(&config).clock_skew.is_empty());
+    }
+
+    #[test]
+    #[serial]
+    fn test_windows_destinations_get_names_they_can_hold() {
+        let _database = setup_test_db();
+        let source = TempDir::new().unwrap();
+        let destination = TempDir::new().unwrap();
+        fs::create_dir(source.path().join("what?")).unwrap();
+        fs::write(source.path().join("what?/12:30.txt"), "meeting").unwrap();
+        let mut config = pipeline_config(&source, &destination, false);
+        config.backup_destinations = vec![serde_json::from_value(serde_json::json!({
+            "path": destination.path(),
+            "filesystem": "ntfs"
+        }))
+        .unwrap()];
+
+        assert_eq!(run_pipeline(&config).sources[0].files_copied, 1);
+        let mirrored = destination.path().join(source.path().file_name().unwrap());
+        let copy = mirrored.join("what%3F/12%3A30.txt");
+        assert_eq!(fs::read_to_string(&copy).unwrap(), "meeting");
+
+        let row = select_source("12:30.txt", &source.path().join("what?").to_string_lossy())
+            .unwrap()
+            .unwrap();
+        let backups = select_backups_of_source(row.id).unwrap();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(backups[0].file_name, "12%3A30.txt");
+        assert_eq!(
+            backups[0].original_path.as_deref(),
+            Some(mirrored.join("what?/12:30.txt").to_str().unwrap())
+        );
+
+        // The encoded names are found again rather than copied anew
+        assert_eq!(run_pipeline(&config).sources[0].files_copied, 0);
+    }
+
+    fn pipeline_config(source: &TempDir, destination: &TempDir, quick_skip: bool) -> Config {
+        serde_json::from_value(serde_json::json!({
+            "database_file": "",
+            "max_threads": 2,
+            "backup_sources": [{"parent_directory": source.path()}],
+            "backup_destinations": [destination.path()],
+            "skip_source_hash_check_if_newer": false,
+            "quick_skip_unchanged": quick_skip
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    #[serial]
+    fn test_progress_totals_only_grow_and_count_files_passed_over() {
+        let _database = setup_test_db();
+        let source = TempDir::new().unwrap();
+        let destination = TempDir::new().unwrap();
+        for file in 0..5 {
+            fs::write(source.path().join(format!("{}.txt", file)), "content").unwrap();
+        }
+        let notes = source.path().join("notes.db");
+        rusqlite::Connection::open(&notes)
+            .unwrap()
+            .execute("CREATE TABLE notes (body TEXT)", [])
+            .unwrap();
+        // Read through the database's snapshot rather than copied
+        fs::write(source.path().join("notes.db-wal"), "").unwrap();
+        let mut config = pipeline_config(&source, &destination, false);
+        config.sqlite_snapshot = Some(SqliteSnapshot::BackupApi);
+
+        let state = crate::api_state::AppState::new();
+        let mut events = state.subscribe_progress();
+        run_as(&config, "test-run", false, false, Some(&state)).unwrap();
+
+        let mut last = (0, 0.0);
+        while let Ok(event) = events.try_recv() {
+            let Some(progress) = event.progress else {
+                continue;
+            };
+            assert!(progress.total_files >= last.0);
+            assert!(progress.percentage >= last.1);
+            last = (progress.total_files, progress.percentage);
+        }
+        let progress = state.get_progress().unwrap();
+        assert!(progress.discovery_complete);
+        assert_eq!((progress.files_processed, progress.total_files), (7, 7));
+        assert_eq!(progress.percentage, 100.0);
+    }
+
+    fn run_pipeline(config: &Config) -> RunReport {
+        run_checked(config, "test-run", false).unwrap()
+    }
+
+    fn run_checked(config: &Config, run_id: &str, confirm_mass_change: bool) -> Result<RunReport> {
+        run_as(config, run_id, confirm_mass_change, false, None)
+    }
+
+    fn run_as(
+        config: &Config,
+        run_id: &str,
+        confirm_mass_change: bool,
+        fail_on_error: bool,
+        state: Option<&crate::api_state::AppState>,
+    ) -> Result<RunReport> {
+        let sources = vec![SelectedSource {
+            source: config.backup_sources[0].clone(),
+            scan_root: config.backup_sources[0].parent_directory.clone(),
+            max_depth: None,
+        }];
+        let sink = ReportSink::new(None, None).unwrap();
+        let mass_change = MassChangeCheck::new(
+            config,
+            &RunSelection::default(),
+            &sources,
+            DryRunMode::None,
+            confirm_mass_change,
+        );
+
+        let (sender, discovered) = discovery_channel();
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                discover_source_files(
+                    &sources,
+                    &SourceSnapshots::default(),
+                    &PluginFilters::start(config),
+                    &[],
+                    2,
+                    None,
+                    sender,
+                )
+            });
+            backup_files(
+                run_id,
+                discovered,
+                config,
+                None,
+                None,
+                DryRunMode::None,
+                state,
+                &sink,
+                &RunBudget::unlimited(),
+                &mass_change,
+                fail_on_error,
+            )
+        })
+    }
+}
@@ -0,0 +1,48 @@
+use crate::models::error::Result;
+use crate::models::prepped_backup::MovedFrom;
+use crate::repo::catalog::{select_backup_paths, select_sources_with_content};
+use crate::utils::directory::{get_file_size, long_path};
+use log::debug;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Recorded source files that new files of the run turned out to be moved or renamed from
+///
+/// Each recorded file is claimed by one new file at most, so copies of it made in several places
+/// don't all take its backups.
+#[derive(Default)]
+pub(super) struct MovedFiles {
+    claimed: Mutex<HashSet<i32>>,
+}
+
+impl MovedFiles {
+    /// The recorded source file a new file with this content was moved from: one gone from its
+    /// recorded path with backups of the same size still at their destinations
+    pub(super) fn find(&self, hash: &str, file_size: u64) -> Result<Option<MovedFrom>> {
+        if hash.is_empty() {
+            return Ok(None);
+        }
+        for source in select_sources_with_content(hash, file_size)? {
+            let recorded_at = Path::new(&source.file_path).join(&source.file_name);
+            if fs::exists(long_path(&recorded_at)).unwrap_or(true) {
+                continue;
+            }
+            let backups: Vec<PathBuf> = select_backup_paths(source.id)?
+                .into_iter()
+                .filter(|backup| get_file_size(backup).is_ok_and(|size| size == file_size))
+                .collect();
+            if backups.is_empty() || !self.claimed.lock().unwrap().insert(source.id) {
+                continue;
+            }
+            debug!("{:?} was moved, its backups are moved with it", recorded_at);
+            return Ok(Some(MovedFrom {
+                file_name: source.file_name,
+                file_path: source.file_path,
+                backups,
+            }));
+        }
+        Ok(None)
+    }
+}
@@ -0,0 +1,125 @@
+use crate::models::config::{Config, CopyOrder};
+use crate::models::prepped_backup::PreppedBackup;
+use std::collections::HashMap;
+
+/// Sorts prepared files into the order `copy_order` asks for; files with the same key keep the
+/// order they were prepared in
+pub(super) struct CopyOrderKey {
+    order: CopyOrder,
+    priorities: HashMap<String, i32>,
+}
+
+impl CopyOrderKey {
+    pub(super) fn new(config: &Config) -> Self {
+        CopyOrderKey {
+            order: config.copy_order,
+            priorities: config
+                .backup_sources
+                .iter()
+                .map(|s| (s.display_name().to_string(), s.priority.unwrap_or(0)))
+                .collect(),
+        }
+    }
+
+    /// Files with smaller keys are copied first
+    pub(super) fn key(&self, prepped: &PreppedBackup) -> i64 {
+        match self.order {
+            CopyOrder::Discovered => 0,
+            CopyOrder::SmallestFirst => i64::try_from(prepped.file_size).unwrap_or(i64::MAX),
+            CopyOrder::NewestFirst => {
+                -i64::try_from(prepped.source_last_modified_date.as_nanos()).unwrap_or(i64::MAX)
+            }
+            CopyOrder::SourcePriority => {
+                -(self
+                    .priorities
+                    .get(&prepped.source_name)
+                    .copied()
+                    .unwrap_or(0) as i64)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::config::{BackupSource, HashLimit};
+    use std::time::Duration;
+
+    fn prepped(source_name: &str, file_name: &str, file_size: u64, modified: u64) -> PreppedBackup {
+        PreppedBackup {
+            source_path: String::new(),
+            source_name: source_name.to_string(),
+            hash_limit: HashLimit::new(1),
+            force_overwrite_backup: false,
+            source_file: file_name.into(),
+            read_from: file_name.into(),
+            relative_path: file_name.into(),
+            file_name: file_name.to_string(),
+            backup_paths: Vec::new(),
+            renamed_backups: Vec::new(),
+            hash: String::new(),
+            file_size,
+            source_last_modified_date: Duration::from_secs(modified),
+            new: false,
+            updated: false,
+            unchanged: false,
+            always_copy: false,
+            moved_from: None,
+        }
+    }
+
+    fn ordered(copy_order: CopyOrder) -> Vec<String> {
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "database_file": "",
+            "backup_sources": [],
+            "backup_destinations": []
+        }))
+        .unwrap();
+        let config = Config {
+            copy_order,
+            backup_sources: vec![
+                BackupSource {
+                    parent_directory: "/media".to_string(),
+                    ..Default::default()
+                },
+                BackupSource {
+                    parent_directory: "/documents".to_string(),
+                    name: Some("documents".to_string()),
+                    priority: Some(10),
+                    ..Default::default()
+                },
+            ],
+            ..config
+        };
+        let mut files = vec![
+            prepped("/media", "movie.mkv", 4000, 100),
+            prepped("documents", "taxes.pdf", 300, 300),
+            prepped("/media", "song.mp3", 20, 200),
+            prepped("documents", "notes.txt", 10, 50),
+        ];
+        let order = CopyOrderKey::new(&config);
+        files.sort_by_key(|p| order.key(p));
+        files.into_iter().map(|p| p.file_name).collect()
+    }
+
+    #[test]
+    fn test_orders_prepared_files_by_policy() {
+        assert_eq!(
+            ordered(CopyOrder::Discovered),
+            ["movie.mkv", "taxes.pdf", "song.mp3", "notes.txt"]
+        );
+        assert_eq!(
+            ordered(CopyOrder::SmallestFirst),
+            ["notes.txt", "song.mp3", "taxes.pdf", "movie.mkv"]
+        );
+        assert_eq!(
+            ordered(CopyOrder::NewestFirst),
+            ["taxes.pdf", "song.mp3", "movie.mkv", "notes.txt"]
+        );
+        assert_eq!(
+            ordered(CopyOrder::SourcePriority),
+            ["taxes.pdf", "notes.txt", "movie.mkv", "song.mp3"]
+        );
+    }
+}
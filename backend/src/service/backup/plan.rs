@@ -0,0 +1,248 @@
+use super::reconcile::backup_write;
+use super::Pipeline;
+use crate::models::config::Config;
+use crate::models::dry_run_mode::DryRunMode;
+use crate::models::error::{BackupError, Result};
+use crate::models::file_operation::{FileOperation, FileOperationEntry};
+use crate::models::prepped_backup::PreppedBackup;
+use crate::models::run_report::SkewKind;
+use crate::repo::catalog::select_backed_up_file;
+use crate::service::catalog_journal::RunJournal;
+use crate::service::clock_skew::SkewTracker;
+use crate::service::hash::hash_file;
+use crate::utils::directory::{get_file_last_modified, get_file_size, long_path};
+use log::{debug, info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Where one prepared file has to be written, decided before anything is written
+pub(super) struct FilePlan {
+    pub prepped: PreppedBackup,
+    /// Destination paths whose backup is missing or out of date
+    pub copies: Vec<PathBuf>,
+    /// Backups of the path the file was moved from, each with the missing backup path it is
+    /// moved to instead of copying the file there
+    pub moves: Vec<(PathBuf, PathBuf)>,
+    /// Whether plugin destinations receive the file, which they can't be asked about
+    pub send_to_plugins: bool,
+}
+
+/// Check every destination of a prepared file against the source
+///
+/// Copies that would take a destination over its `max_total_bytes` are left out. An unrecorded
+/// backup that matches the source is adopted into the run's catalog journal here
+/// rather than copied again, and a missing backup of a moved file is moved from where the
+/// destination holds the old one.
+pub(super) fn plan_file(pipeline: &Pipeline, prepped: PreppedBackup) -> FilePlan {
+    let mut copies = Vec::new();
+    let mut moves = Vec::new();
+    let mut movable = prepped
+        .moved_from
+        .as_ref()
+        .map(|moved_from| moved_from.backups.clone())
+        .unwrap_or_default();
+    for backup_path in &prepped.backup_paths {
+        if let Some(from) = take_movable(&mut movable, pipeline.config, backup_path) {
+            moves.push((from, backup_path.clone()));
+            continue;
+        }
+        let required = !prepped.unchanged
+            && (prepped.force_overwrite_backup
+                || prepped.always_copy
+                || is_backup_required(
+                    &prepped,
+                    backup_path,
+                    pipeline.config,
+                    pipeline.dry_run_mode,
+                    &pipeline.clock_skew,
+                    &pipeline.journal,
+                )
+                .unwrap_or(false));
+        let skipped = if !required {
+            None
+        } else if !pipeline
+            .quotas
+            .admit(pipeline.config, backup_path, prepped.file_size)
+        {
+            Some("destination reached its max_total_bytes".to_string())
+        } else {
+            copies.push(backup_path.clone());
+            continue;
+        };
+        pipeline.record_operation(FileOperationEntry {
+            source: Some(prepped.source_file.to_string_lossy().to_string()),
+            backup: Some(backup_path.to_string_lossy().to_string()),
+            hash: Some(prepped.hash.clone()),
+            bytes: Some(prepped.file_size),
+            detail: skipped,
+            ..FileOperationEntry::new(FileOperation::Skipped)
+        });
+    }
+
+    FilePlan {
+        send_to_plugins: prepped.updated || prepped.force_overwrite_backup,
+        copies,
+        moves,
+        prepped,
+    }
+}
+
+/// The backup of a moved file's old path in the same destination as `backup_path`, if there is
+/// one and nothing is at `backup_path` yet
+fn take_movable(
+    movable: &mut Vec<PathBuf>,
+    config: &Config,
+    backup_path: &Path,
+) -> Option<PathBuf> {
+    let destination = config.destination_of(backup_path)?;
+    if fs::exists(long_path(backup_path)).unwrap_or(true) {
+        return None;
+    }
+    let index = movable
+        .iter()
+        .position(|from| config.destination_of(from) == Some(destination))?;
+    Some(movable.swap_remove(index))
+}
+
+fn is_backup_required(
+    prepped_backup: &PreppedBackup,
+    back_up_path: &PathBuf,
+    config: &Config,
+    dry_run_mode: DryRunMode,
+    clock_skew: &SkewTracker,
+    journal: &RunJournal,
+) -> Result<bool> {
+    let exists = fs::exists(long_path(back_up_path)).unwrap_or(false);
+
+    if !exists {
+        debug!(
+            "{:?} backup does not exist at {:?}",
+            prepped_backup.source_file, back_up_path
+        );
+        return Ok(true);
+    }
+
+    debug!(
+        "{:?} backup exists at {:?}. Checking if update needed",
+        prepped_backup.source_file, back_up_path
+    );
+    existing_file_needs_updated(
+        prepped_backup,
+        back_up_path,
+        config,
+        dry_run_mode,
+        clock_skew,
+        journal,
+    )
+}
+
+fn existing_file_needs_updated(
+    prepped_backup: &PreppedBackup,
+    back_up_path: &PathBuf,
+    config: &Config,
+    dry_run_mode: DryRunMode,
+    clock_skew: &SkewTracker,
+    journal: &RunJournal,
+) -> Result<bool> {
+    if !fs::exists(long_path(back_up_path)).unwrap_or(false) {
+        return Ok(true);
+    }
+
+    if dry_run_mode.is_quick() {
+        let fs_file_size = get_file_size(back_up_path)?;
+        if prepped_backup.file_size != fs_file_size {
+            debug!(
+                "Quick mode: File size differs, would update: {:?}",
+                back_up_path
+            );
+            return Ok(true);
+        }
+        debug!(
+            "Quick mode: File size matches, would skip: {:?}",
+            back_up_path
+        );
+        return Ok(false);
+    }
+
+    let back_up_filename = back_up_path
+        .file_name()
+        .ok_or_else(|| BackupError::DirectoryRead(format!("No filename for {:?}", back_up_path)))?
+        .to_string_lossy()
+        .to_string();
+
+    let back_up_filepath = back_up_path
+        .parent()
+        .ok_or_else(|| BackupError::DirectoryRead(format!("No parent for {:?}", back_up_path)))?
+        .to_string_lossy()
+        .to_string();
+
+    let fs_last_modified = get_file_last_modified(back_up_path)?;
+    let fs_file_size = get_file_size(back_up_path)?;
+    clock_skew.check(
+        SkewKind::Destination,
+        &config.destination_name(&back_up_path.to_string_lossy()),
+        &fs_last_modified,
+    );
+
+    let dbase_backup_file_option = if dry_run_mode.should_update_database() {
+        select_backed_up_file(&back_up_filename, &back_up_filepath)?
+    } else {
+        None
+    };
+
+    match dbase_backup_file_option {
+        Some(backup_file) => {
+            // Within the tolerances, an older time on disk is clock or timestamp precision drift
+            let skew_allowed = Duration::from_secs(config.clock_skew_tolerance_secs);
+            if !config.modified_after(
+                &backup_file.last_modified,
+                &(fs_last_modified + skew_allowed),
+            ) {
+                if prepped_backup.file_size == fs_file_size {
+                    let fs_hash = hash_file(back_up_path, prepped_backup.hash_limit)?;
+                    // The recorded source hash may be stale until this run commits
+                    if prepped_backup.hash == fs_hash {
+                        debug!("Existing backup file is up to date: {:?}", back_up_path);
+                        return Ok(false);
+                    }
+                }
+                debug!("Existing backup file needs update: {:?}", back_up_path);
+                Ok(true)
+            } else if config.overwrite_backup_if_existing_is_newer {
+                warn!(
+                    "Existing backup file is newer than database, config forces override: {:?}",
+                    back_up_path
+                );
+                Ok(true)
+            } else {
+                warn!(
+                    "Existing backup file is newer than database, skipping: {:?}",
+                    back_up_path
+                );
+                Ok(false)
+            }
+        }
+        None => {
+            debug!(
+                "Unknown backup file found, checking if same as source: {:?}",
+                back_up_path
+            );
+            if prepped_backup.file_size == fs_file_size {
+                let fs_hash = hash_file(back_up_path, prepped_backup.hash_limit)?;
+                if *prepped_backup.hash == fs_hash {
+                    info!(
+                        "Unknown backup matches source, adding to database: {:?}",
+                        back_up_path
+                    );
+                    if dry_run_mode.should_update_database() {
+                        journal.stage(backup_write(prepped_backup, back_up_path)?)?;
+                    }
+                    return Ok(false);
+                }
+            }
+            debug!("Unknown backup differs from source: {:?}", back_up_path);
+            Ok(true)
+        }
+    }
+}
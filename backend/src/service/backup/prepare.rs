@@ -0,0 +1,659 @@
+use super::moved::MovedFiles;
+use super::Pipeline;
+use crate::models::catalog_write::CatalogWrite;
+use crate::models::config::{Config, Filesystem, SourceSettings};
+use crate::models::dry_run_mode::DryRunMode;
+use crate::models::error::{BackupError, Result};
+use crate::models::prepped_backup::PreppedBackup;
+use crate::models::run_report::SkewKind;
+use crate::models::source_row::SourceRow;
+use crate::repo::catalog::{select_backup_paths, select_source};
+use crate::service::catalog_journal::RunJournal;
+use crate::service::clock_skew::SkewTracker;
+use crate::service::discovery::{DiscoveredFile, DiscoveredFiles};
+use crate::service::hash::hash_file;
+use crate::service::hash_cache::hash_file_cached;
+use crate::service::sqlite_snapshot::is_database_sidecar;
+use crate::utils::directory::{
+    get_file_last_modified, get_file_size, long_path, normalize_path, path_relative_to,
+};
+use indicatif::ProgressBar;
+use log::{debug, error, warn};
+use rayon::iter::{ParallelBridge, ParallelIterator};
+use std::path::{Component, Path, PathBuf, MAIN_SEPARATOR};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::info_span;
+
+/// Prepare files as discovery streams them in, passing each one to `hand_off`
+pub(super) fn prepare_backup_candidates(
+    backup_candidates: DiscoveredFiles,
+    pipeline: &Pipeline,
+    progress: Option<&ProgressBar>,
+    hand_off: impl Fn(PreppedBackup) + Sync,
+) -> Result<()> {
+    let config = pipeline.config;
+    let errors = AtomicU64::new(0);
+    let discovery_error: Mutex<Option<BackupError>> = Mutex::new(None);
+
+    backup_candidates
+        .into_iter()
+        .par_bridge()
+        .for_each(|discovered| {
+            let _run_span = pipeline.run_span.enter();
+            let DiscoveredFile {
+                shared_path,
+                file: candidate,
+                snapshot,
+            } = match discovered {
+                Ok(discovered) => discovered,
+                Err(e) => {
+                    // A failed walk leaves the file list incomplete, so copying stops too
+                    pipeline.aborted.store(true, Ordering::Relaxed);
+                    discovery_error.lock().unwrap().get_or_insert(e);
+                    return;
+                }
+            };
+
+            // Hold while paused, then check stop signal
+            if let Some(st) = pipeline.state {
+                st.wait_while_paused();
+            }
+            if pipeline.stop_requested() {
+                warn!("Backup preparation cancelled by user");
+                return;
+            }
+            if pipeline.aborted.load(Ordering::Relaxed) {
+                return;
+            }
+            // Files left for the next run aren't hashed either
+            if pipeline.budget.is_exhausted() {
+                pipeline.budget.defer(&candidate);
+                pipeline.pass_over();
+                if let Some(pb) = progress {
+                    pb.inc(1);
+                }
+                return;
+            }
+
+            let read_from = snapshot.unwrap_or_else(|| candidate.clone());
+
+            // The database's snapshot already holds what its journal files contain
+            if config.sqlite_snapshot.is_some() && is_database_sidecar(&read_from) {
+                debug!("Skipping SQLite journal file: {:?}", candidate);
+                pipeline.pass_over();
+                if let Some(pb) = progress {
+                    pb.inc(1);
+                }
+                return;
+            }
+
+            let settings = config.source_settings(&shared_path);
+            let _source_span = info_span!("source", source = %settings.name).entered();
+            if let Some(pb) = progress {
+                pb.set_length(pipeline.discovered.found());
+            }
+
+            let prepared = pipeline.locked_files.retry(&settings.name, &candidate, || {
+                prepare_single_candidate(
+                    &candidate,
+                    &read_from,
+                    &shared_path,
+                    config,
+                    &settings,
+                    pipeline.dry_run_mode,
+                    &pipeline.clock_skew,
+                    &pipeline.journal,
+                    &pipeline.moved,
+                )
+            });
+            match prepared {
+                Ok(prepped) => {
+                    let prepared = pipeline.files_prepared.fetch_add(1, Ordering::Relaxed) + 1;
+                    if let Some(pb) = pipeline.backup_progress {
+                        pb.set_length(prepared);
+                    }
+                    hand_off(prepped);
+                }
+                // Already reported as locked rather than as an error
+                Err(BackupError::FileLocked { .. }) => {
+                    pipeline.pass_over();
+                    if pipeline.locked_files.fails_run() {
+                        pipeline.aborted.store(true, Ordering::Relaxed);
+                    }
+                }
+                // Logged as they happen, so a tree full of unreadable files isn't held in memory
+                Err(e) => {
+                    error!("Preparation error: {}", e);
+                    pipeline.pass_over();
+                    pipeline.prepare_failed(&settings.name, &e);
+                    errors.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            if let Some(pb) = progress {
+                pb.inc(1);
+            }
+            pipeline.report_progress(
+                Some(candidate.to_string_lossy().to_string()),
+                Some(settings.name),
+            );
+        });
+
+    if let Some(e) = discovery_error.into_inner().unwrap() {
+        return Err(e);
+    }
+
+    let error_count = errors.into_inner();
+    let prepared = pipeline.files_prepared.load(Ordering::Relaxed);
+
+    if error_count > 0 {
+        warn!(
+            "Preparation completed with {} error(s). {} files prepared successfully.",
+            error_count, prepared
+        );
+
+        // Update API state with error information
+        if let Some(st) = pipeline.state {
+            st.notify_message(format!(
+                "Preparation had {} error(s), but {} files were prepared successfully.",
+                error_count, prepared
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn prepare_single_candidate(
+    candidate: &PathBuf,
+    read_from: &PathBuf,
+    shared_path: &PathBuf,
+    config: &Config,
+    settings: &SourceSettings,
+    dry_run_mode: DryRunMode,
+    clock_skew: &SkewTracker,
+    journal: &RunJournal,
+    moved: &MovedFiles,
+) -> Result<PreppedBackup> {
+    let filename = candidate
+        .file_name()
+        .ok_or_else(|| BackupError::DirectoryRead(format!("No filename for {:?}", candidate)))?
+        .to_string_lossy()
+        .to_string();
+
+    let filepath = candidate
+        .parent()
+        .ok_or_else(|| BackupError::DirectoryRead(format!("No parent path for {:?}", candidate)))?
+        .to_string_lossy()
+        .to_string();
+
+    let fs_last_modified = get_file_last_modified(read_from)?;
+    let fs_file_size = get_file_size(read_from)?;
+    clock_skew.check(SkewKind::Source, &settings.name, &fs_last_modified);
+
+    let db_source_record_option = if dry_run_mode.should_update_database() {
+        select_source(&filename, &filepath)?
+    } else {
+        None
+    };
+
+    let destinations: Vec<(&str, Filesystem)> = settings
+        .destinations
+        .iter()
+        .map(|destination| (destination.as_str(), config.filesystem_of(destination)))
+        .collect();
+    let (backup_paths, original_paths): (Vec<PathBuf>, Vec<PathBuf>) = get_possible_backups(
+        &filename,
+        &filepath,
+        shared_path,
+        &destinations,
+        settings.destination_subpath.as_deref(),
+    )?
+    .into_iter()
+    .unzip();
+    let renamed_backups = backup_paths
+        .iter()
+        .zip(original_paths)
+        .filter(|(backup_path, original_path)| *backup_path != original_path)
+        .map(|(backup_path, original_path)| (backup_path.clone(), original_path))
+        .collect();
+
+    let always_copy = config.always_copies(fs_file_size);
+    let unchanged = match &db_source_record_option {
+        Some(db_source_record)
+            if config.quick_skip_unchanged && !settings.force_overwrite_backup && !always_copy =>
+        {
+            is_unchanged_since_backup(
+                db_source_record,
+                fs_file_size,
+                &fs_last_modified,
+                &backup_paths,
+                config,
+            )?
+        }
+        _ => false,
+    };
+
+    let new = db_source_record_option.is_none();
+    let mut moved_from = None;
+    let (updated, hash) = if always_copy {
+        prepare_always_copied(
+            db_source_record_option.as_ref(),
+            read_from,
+            &filename,
+            &filepath,
+            fs_file_size,
+            &fs_last_modified,
+            settings,
+            dry_run_mode,
+            journal,
+        )?
+    } else if let Some(db_source_record) = db_source_record_option {
+        if unchanged {
+            debug!("Quick skip: {:?} is unchanged since its backup", candidate);
+            (false, db_source_record.hash)
+        } else {
+            // A recorded time in the future would hide every change until the clock caught up
+            let recorded_in_future = clock_skew.check(
+                SkewKind::Database,
+                &settings.name,
+                &db_source_record.last_modified,
+            );
+            get_is_source_file_updated(
+                &db_source_record,
+                read_from,
+                &fs_last_modified,
+                recorded_in_future,
+                config,
+                settings,
+                dry_run_mode,
+                journal,
+            )?
+        }
+    } else {
+        let hash = if dry_run_mode.should_hash() {
+            hash_file_cached(read_from, settings.hash_limit(), config, dry_run_mode)?
+        } else {
+            debug!("Quick mode: skipping hash for {:?}", candidate);
+            String::from("dry-run-quick-no-hash")
+        };
+
+        if dry_run_mode.should_update_database() {
+            journal.stage(CatalogWrite::Source {
+                file_name: filename.clone(),
+                file_path: filepath.clone(),
+                hash: hash.clone(),
+                file_size: fs_file_size,
+                last_modified: fs_last_modified,
+            })?;
+            moved_from = moved.find(&hash, fs_file_size)?;
+        }
+
+        (true, hash)
+    };
+
+    let relative_path = path_relative_to(candidate, shared_path.parent().unwrap_or(shared_path))
+        .unwrap_or_else(|| candidate.clone());
+
+    Ok(PreppedBackup {
+        source_path: filepath,
+        source_name: settings.name.clone(),
+        hash_limit: settings.hash_limit(),
+        force_overwrite_backup: settings.force_overwrite_backup,
+        source_file: candidate.clone(),
+        read_from: read_from.clone(),
+        relative_path,
+        file_name: filename,
+        backup_paths,
+        renamed_backups,
+        hash,
+        file_size: fs_file_size,
+        source_last_modified_date: fs_last_modified,
+        new,
+        updated,
+        unchanged,
+        always_copy,
+        moved_from,
+    })
+}
+
+/// Hash a file copied every run, for checking its copies, and stage its record if it is new or
+/// changed, returning whether it changed and its hash
+///
+/// Only its catalog record is looked at: not its backups, the hash cache or whether it was moved.
+#[allow(clippy::too_many_arguments)]
+fn prepare_always_copied(
+    source_row: Option<&SourceRow>,
+    read_from: &Path,
+    file_name: &str,
+    file_path: &str,
+    file_size: u64,
+    last_modified: &Duration,
+    settings: &SourceSettings,
+    dry_run_mode: DryRunMode,
+    journal: &RunJournal,
+) -> Result<(bool, String)> {
+    let hash = if dry_run_mode.should_hash() {
+        hash_file(read_from, settings.hash_limit())?
+    } else {
+        source_row.map_or_else(
+            || String::from("dry-run-quick-no-hash"),
+            |row| row.hash.clone(),
+        )
+    };
+    let updated = source_row.is_some_and(|row| row.hash != hash || row.file_size != file_size);
+    let recorded = source_row.is_some_and(|row| !updated && row.last_modified == *last_modified);
+    if !recorded && dry_run_mode.should_update_database() {
+        journal.stage(CatalogWrite::Source {
+            file_name: file_name.to_string(),
+            file_path: file_path.to_string(),
+            hash: hash.clone(),
+            file_size,
+            last_modified: *last_modified,
+        })?;
+    }
+    Ok((updated, hash))
+}
+
+/// Whether a source file still has its recorded size and modification time and was backed up
+/// to every destination it goes to
+///
+/// Only the database is consulted, so a backup changed or deleted at the destination since it was
+/// recorded goes unnoticed.
+fn is_unchanged_since_backup(
+    source_row: &SourceRow,
+    file_size: u64,
+    last_modified: &Duration,
+    backup_paths: &[PathBuf],
+    config: &Config,
+) -> Result<bool> {
+    if source_row.file_size != file_size
+        || config.modified_after(&source_row.last_modified, last_modified)
+        || config.modified_after(last_modified, &source_row.last_modified)
+    {
+        return Ok(false);
+    }
+
+    let recorded = select_backup_paths(source_row.id)?;
+    Ok(backup_paths.iter().all(|path| recorded.contains(path)))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn get_is_source_file_updated(
+    source_candidate: &SourceRow,
+    backup_candidate: &PathBuf,
+    candidate_last_modified: &Duration,
+    recorded_in_future: bool,
+    config: &Config,
+    settings: &SourceSettings,
+    dry_run_mode: DryRunMode,
+    journal: &RunJournal,
+) -> Result<(bool, String)> {
+    let hash: String;
+    let backup_file_size = get_file_size(backup_candidate)?;
+
+    // A skewed recorded time is no guide, so the file is hashed and the record corrected
+    if recorded_in_future
+        || config.modified_after(candidate_last_modified, &source_candidate.last_modified)
+    {
+        if config.skip_source_hash_check_if_newer && !recorded_in_future {
+            hash = source_candidate.hash.clone();
+            Ok((true, hash))
+        } else {
+            // Skip hashing in Quick mode
+            hash = if dry_run_mode.should_hash() {
+                hash_file_cached(
+                    backup_candidate,
+                    settings.hash_limit(),
+                    config,
+                    dry_run_mode,
+                )?
+            } else {
+                debug!("Quick mode: skipping hash check for {:?}", backup_candidate);
+                source_candidate.hash.clone()
+            };
+
+            if hash == source_candidate.hash && backup_file_size == source_candidate.file_size {
+                if dry_run_mode.should_update_database() {
+                    journal.stage(CatalogWrite::SourceLastModified {
+                        file_name: source_candidate.file_name.clone(),
+                        file_path: source_candidate.file_path.clone(),
+                        last_modified: *candidate_last_modified,
+                    })?;
+                }
+                Ok((false, hash))
+            } else {
+                if dry_run_mode.should_update_database() {
+                    journal.stage(CatalogWrite::Source {
+                        file_name: source_candidate.file_name.clone(),
+                        file_path: source_candidate.file_path.clone(),
+                        hash: hash.clone(),
+                        file_size: backup_file_size,
+                        last_modified: *candidate_last_modified,
+                    })?;
+                }
+                Ok((true, hash))
+            }
+        }
+    } else {
+        hash = source_candidate.hash.clone();
+        Ok((false, hash))
+    }
+}
+
+/// Where a file goes in each destination, with where it would go under the source's own names
+fn get_possible_backups(
+    file_name: &str,
+    file_path: &str,
+    shared_path: &PathBuf,
+    destinations: &[(&str, Filesystem)],
+    subpath: Option<&str>,
+) -> Result<Vec<(PathBuf, PathBuf)>> {
+    // Directory of the file below the source, prefixed with the source's own folder name so
+    // the source folder itself is recreated under each destination
+    let inner_dir = path_relative_to(Path::new(file_path), shared_path).ok_or_else(|| {
+        BackupError::DirectoryRead(format!(
+            "File path {} is not inside source {:?}",
+            file_path, shared_path
+        ))
+    })?;
+    let relative_dir = match normalize_path(shared_path).file_name() {
+        Some(source_dir) => Path::new(source_dir).join(inner_dir),
+        None => inner_dir,
+    };
+
+    // Security: Only plain directory names may remain; anything else could escape the destination
+    if relative_dir
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return Err(BackupError::DirectoryRead(format!(
+            "Path traversal detected in relative path: {:?}. File path may contain '..' sequences.",
+            relative_dir
+        )));
+    }
+
+    // Security: Check file name for path traversal
+    if file_name == ".." || file_name.contains(MAIN_SEPARATOR) || file_name.contains('/') {
+        return Err(BackupError::DirectoryRead(format!(
+            "Invalid file name detected: {}. File names cannot be '..' or contain path separators.",
+            file_name
+        )));
+    }
+
+    let mut possible_backup_paths = Vec::new();
+    for (destination, filesystem) in destinations {
+        let dest_path = Path::new(destination);
+        let base = dest_path.join(subpath.unwrap_or_default());
+        let backup_path = base
+            .join(filesystem.dir_names(&relative_dir))
+            .join(&*filesystem.file_name(file_name));
+
+        // Security: Verify the constructed path is actually within the destination
+        // Canonicalize both paths to resolve any symbolic links or relative components.
+        // On Windows canonical paths carry a \\?\ prefix (\\?\UNC\ for shares) that the
+        // uncanonicalized fallback lacks, so both sides are normalized before comparing.
+        let canonical_dest = normalize_path(
+            &long_path(dest_path)
+                .canonicalize()
+                .unwrap_or_else(|_| dest_path.to_path_buf()),
+        );
+
+        // For the backup path, we can't canonicalize if it doesn't exist yet,
+        // so we check if its parent (when canonicalized) starts with the destination
+        if let Some(backup_parent) = backup_path.parent() {
+            // If parent exists, canonicalize it; otherwise use as-is
+            let canonical_parent = normalize_path(
+                &long_path(backup_parent)
+                    .canonicalize()
+                    .unwrap_or_else(|_| backup_parent.to_path_buf()),
+            );
+
+            if !canonical_parent.starts_with(&canonical_dest) {
+                return Err(BackupError::DirectoryRead(format!(
+                    "Security: Backup path escapes destination directory. Destination: {:?}, Attempted path: {:?}",
+                    destination, backup_path
+                )));
+            }
+        }
+
+        possible_backup_paths.push((backup_path, base.join(&relative_dir).join(file_name)));
+    }
+    Ok(possible_backup_paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn backups_for(file_path: &str, source: &str, destination: &TempDir) -> Result<Vec<PathBuf>> {
+        get_possible_backups(
+            "file.txt",
+            file_path,
+            &PathBuf::from(source),
+            &[(&destination.path().to_string_lossy(), Filesystem::Posix)],
+            None,
+        )
+        .map(|backups| backups.into_iter().map(|(path, _)| path).collect())
+    }
+
+    #[test]
+    fn test_possible_backups_keep_source_folder_and_subdirectories() {
+        let destination = TempDir::new().unwrap();
+
+        let backups = backups_for("/data/photos/2024/june", "/data/photos", &destination).unwrap();
+
+        assert_eq!(
+            backups,
+            vec![destination.path().join("photos/2024/june/file.txt")]
+        );
+    }
+
+    #[test]
+    fn test_possible_backups_with_unicode_names() {
+        let destination = TempDir::new().unwrap();
+
+        let backups = backups_for(
+            "/data/Fotos ñandú/日本/Café",
+            "/data/Fotos ñandú",
+            &destination,
+        )
+        .unwrap();
+        assert_eq!(
+            backups,
+            vec![destination.path().join("Fotos ñandú/日本/Café/file.txt")]
+        );
+
+        // Source configured in composed form, files walked in decomposed form; the destination
+        // folder keeps the configured spelling
+        let backups =
+            backups_for("/data/Cafe\u{301}/menus", "/data/Caf\u{e9}", &destination).unwrap();
+        assert_eq!(
+            backups,
+            vec![destination.path().join("Caf\u{e9}/menus/file.txt")]
+        );
+    }
+
+    #[test]
+    fn test_possible_backups_with_mixed_separators() {
+        let destination = TempDir::new().unwrap();
+
+        let backups = backups_for("/data//photos/./2024/", "/data/photos/", &destination).unwrap();
+
+        assert_eq!(
+            backups,
+            vec![destination.path().join("photos/2024/file.txt")]
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_possible_backups_with_windows_separators_and_case() {
+        let destination = TempDir::new().unwrap();
+
+        let backups = backups_for(
+            r"c:\users\ME/Pictures\2024",
+            r"C:\Users\me\Pictures",
+            &destination,
+        )
+        .unwrap();
+
+        assert_eq!(
+            backups,
+            vec![destination.path().join(r"Pictures\2024\file.txt")]
+        );
+    }
+
+    #[test]
+    fn test_possible_backups_reject_paths_outside_source() {
+        let destination = TempDir::new().unwrap();
+
+        assert!(backups_for("/data/other/2024", "/data/photos", &destination).is_err());
+        assert!(backups_for("/data/photos/../../etc", "/data/photos", &destination).is_err());
+    }
+
+    #[test]
+    fn test_possible_backups_allow_dots_inside_names() {
+        let destination = TempDir::new().unwrap();
+
+        let backups = backups_for("/data/photos/v1..v2", "/data/photos", &destination).unwrap();
+
+        assert_eq!(
+            backups,
+            vec![destination.path().join("photos/v1..v2/file.txt")]
+        );
+    }
+
+    #[test]
+    fn test_possible_backups_encode_names_windows_refuses() {
+        let posix = TempDir::new().unwrap();
+        let windows = TempDir::new().unwrap();
+
+        let backups = get_possible_backups(
+            "12:30?.txt",
+            "/data/notes/aux/done.",
+            &PathBuf::from("/data/notes"),
+            &[
+                (&posix.path().to_string_lossy(), Filesystem::Posix),
+                (&windows.path().to_string_lossy(), Filesystem::Windows),
+            ],
+            None,
+        )
+        .unwrap();
+
+        let original = |root: &Path| root.join("notes/aux/done./12:30?.txt");
+        assert_eq!(
+            backups,
+            vec![
+                (original(posix.path()), original(posix.path())),
+                (
+                    windows.path().join("notes/%61ux/done%2E/12%3A30%3F.txt"),
+                    original(windows.path())
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,266 @@
+use super::verify::{Verified, VerifiedFile};
+use super::Pipeline;
+use crate::models::catalog_write::CatalogWrite;
+use crate::models::error::{BackupError, Result};
+use crate::models::file_operation::{FileOperation, FileOperationEntry};
+use crate::models::prepped_backup::PreppedBackup;
+use crate::models::run_report::{DestinationSummary, FileOutcome, FileStatus, SkewKind};
+use crate::utils::directory::{get_file_last_modified, get_file_physical_size};
+use log::error;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+
+/// Record what happened to one file: backup rows, post-processing, summaries and progress
+pub(super) fn reconcile(pipeline: &Pipeline, verified: VerifiedFile) {
+    let VerifiedFile { prepped, writes } = verified;
+    let config = pipeline.config;
+    let mut files_copied = 0u64;
+    let mut files_moved = 0u64;
+    let mut bytes_copied = 0u64;
+    let mut file_errors = 0u64;
+
+    for write in writes {
+        let result = write
+            .result
+            .and_then(|verified| record_backup(pipeline, &prepped, &write.destination, verified));
+
+        let name = config.destination_name(&write.destination);
+        let mut destination_summaries = pipeline.destination_summaries.lock().unwrap();
+        let destination_summary = destination_summaries
+            .entry(name.clone())
+            .or_insert_with(|| DestinationSummary {
+                name,
+                ..Default::default()
+            });
+
+        let mut physical_bytes = None;
+        let (status, file_error) = match result {
+            Ok(FileStatus::Moved) => {
+                files_moved += 1;
+                (FileStatus::Moved, None)
+            }
+            Ok(status) => {
+                files_copied += 1;
+                bytes_copied += prepped.file_size;
+                destination_summary.files_copied += 1;
+                destination_summary.bytes_copied += prepped.file_size;
+                if status == FileStatus::Copied {
+                    physical_bytes =
+                        get_file_physical_size(Path::new(&write.destination)).unwrap_or(None);
+                }
+                destination_summary.physical_bytes_copied +=
+                    physical_bytes.unwrap_or(prepped.file_size);
+                (status, None)
+            }
+            // Listed with the run's locked files instead of counted as an error
+            Err(e @ BackupError::FileLocked { .. }) => {
+                if pipeline.locked_files.fails_run() {
+                    pipeline.aborted.store(true, Ordering::Relaxed);
+                }
+                (FileStatus::Failed, Some(e.to_string()))
+            }
+            Err(e) => {
+                file_errors += 1;
+                destination_summary.errors += 1;
+                error!("Backup error: {}", e);
+                pipeline.fail(&e);
+                (FileStatus::Failed, Some(e.to_string()))
+            }
+        };
+        drop(destination_summaries);
+        if let Some(error) = &file_error {
+            pipeline.record_operation(FileOperationEntry {
+                source: Some(prepped.source_file.to_string_lossy().to_string()),
+                backup: Some(write.destination.clone()),
+                bytes: Some(prepped.file_size),
+                detail: Some(error.clone()),
+                ..FileOperationEntry::new(FileOperation::CopyFailed)
+            });
+        }
+
+        pipeline.sink.record(FileOutcome {
+            source: prepped.source_name.clone(),
+            file: prepped.source_file.to_string_lossy().to_string(),
+            destination: write.destination,
+            status,
+            bytes: prepped.file_size,
+            physical_bytes,
+            error: file_error,
+        });
+    }
+
+    // The old path's row goes once none of its backups are left there
+    if let Some(moved_from) = &prepped.moved_from {
+        let forgotten = pipeline.journal.stage(CatalogWrite::RemoveSource {
+            file_name: moved_from.file_name.clone(),
+            file_path: moved_from.file_path.clone(),
+        });
+        if let Err(e) = forgotten {
+            error!("Backup error: {}", e);
+            pipeline.fail(&e);
+            file_errors += 1;
+        }
+    }
+
+    pipeline
+        .error_count
+        .fetch_add(file_errors, Ordering::Relaxed);
+    pipeline.files_processed.fetch_add(1, Ordering::Relaxed);
+    pipeline
+        .bytes_processed
+        .fetch_add(bytes_copied, Ordering::Relaxed);
+
+    if let Some(pb) = pipeline.backup_progress {
+        pb.inc(1);
+    }
+
+    if let Some(summary) = pipeline
+        .source_summaries
+        .lock()
+        .unwrap()
+        .get_mut(&prepped.source_name)
+    {
+        summary.files_copied += files_copied;
+        summary.files_moved += files_moved;
+        summary.bytes_copied += bytes_copied;
+        summary.errors += file_errors;
+    }
+
+    pipeline.report_progress(Some(prepped.file_name), Some(prepped.source_name));
+}
+
+/// Stage a verified backup in the run's catalog journal and run the post-processors on it
+fn record_backup(
+    pipeline: &Pipeline,
+    prepped_backup: &PreppedBackup,
+    destination: &str,
+    verified: Verified,
+) -> Result<FileStatus> {
+    let copied = |hash: &str| FileOperationEntry {
+        source: Some(prepped_backup.source_file.to_string_lossy().to_string()),
+        backup: Some(destination.to_string()),
+        hash: Some(hash.to_string()),
+        bytes: Some(prepped_backup.file_size),
+        ..FileOperationEntry::new(FileOperation::Copied)
+    };
+    let (backup_path, hash) = match verified {
+        Verified::Copied { backup_path } => (backup_path, prepped_backup.hash.clone()),
+        Verified::Snapshot {
+            backup_path,
+            hash,
+            file_size,
+        } => {
+            pipeline.journal.stage(CatalogWrite::Source {
+                file_name: prepped_backup.file_name.clone(),
+                file_path: prepped_backup.source_path.clone(),
+                hash: hash.clone(),
+                file_size,
+                last_modified: prepped_backup.source_last_modified_date,
+            })?;
+            (backup_path, hash)
+        }
+        Verified::Moved { from, backup_path } => {
+            record_move(pipeline, prepped_backup, &from, &backup_path)?;
+            return Ok(FileStatus::Moved);
+        }
+        Verified::Sent => {
+            pipeline.record_operation(copied(&prepped_backup.hash));
+            return Ok(FileStatus::Copied);
+        }
+        Verified::Simulated => return Ok(FileStatus::Simulated),
+    };
+
+    pipeline
+        .journal
+        .stage(backup_write(prepped_backup, &backup_path)?)?;
+    pipeline.record_operation(copied(&hash));
+
+    // A destination with a fast clock stamps new copies with future times
+    if let Ok(modified) = get_file_last_modified(&backup_path) {
+        pipeline.clock_skew.check(
+            SkewKind::Destination,
+            &pipeline
+                .config
+                .destination_name(&backup_path.to_string_lossy()),
+            &modified,
+        );
+    }
+    pipeline
+        .post_processors
+        .run(&prepped_backup.source_file, &backup_path);
+    Ok(FileStatus::Copied)
+}
+
+/// Stage a backup moved from the file's old path at its new one, in place of the old one
+fn record_move(
+    pipeline: &Pipeline,
+    prepped_backup: &PreppedBackup,
+    from: &Path,
+    backup_path: &PathBuf,
+) -> Result<()> {
+    pipeline
+        .journal
+        .stage(backup_write(prepped_backup, backup_path)?)?;
+    if let Some(moved_from) = &prepped_backup.moved_from {
+        let (file_name, file_path) = split_path(from)?;
+        pipeline.journal.stage(CatalogWrite::RemoveBackup {
+            source_name: moved_from.file_name.clone(),
+            source_path: moved_from.file_path.clone(),
+            file_name,
+            file_path,
+        })?;
+    }
+    pipeline.record_operation(FileOperationEntry {
+        source: Some(prepped_backup.source_file.to_string_lossy().to_string()),
+        backup: Some(backup_path.to_string_lossy().to_string()),
+        hash: Some(prepped_backup.hash.clone()),
+        bytes: Some(prepped_backup.file_size),
+        detail: Some(format!("moved from {}", from.to_string_lossy())),
+        ..FileOperationEntry::new(FileOperation::Moved)
+    });
+    Ok(())
+}
+
+/// The catalog change recording a backup of a prepared file
+pub(super) fn backup_write(
+    prepped_backup: &PreppedBackup,
+    backup_path: &PathBuf,
+) -> Result<CatalogWrite> {
+    let last_modified = get_file_last_modified(backup_path)?;
+    let (file_name, file_path) = split_path(backup_path)?;
+
+    let original_path = prepped_backup
+        .renamed_backups
+        .iter()
+        .find(|(renamed, _)| renamed == backup_path)
+        .map(|(_, original)| original.to_string_lossy().to_string());
+
+    Ok(CatalogWrite::Backup {
+        source_name: prepped_backup.file_name.clone(),
+        source_path: prepped_backup.source_path.clone(),
+        file_name,
+        file_path,
+        last_modified,
+        original_path,
+        physical_size: get_file_physical_size(backup_path)?,
+    })
+}
+
+/// A backup path's file name and directory, as the catalog records them
+fn split_path(backup_path: &Path) -> Result<(String, String)> {
+    let file_path = backup_path
+        .parent()
+        .ok_or_else(|| BackupError::DirectoryRead(format!("No parent for {:?}", backup_path)))?
+        .to_str()
+        .ok_or_else(|| {
+            BackupError::DirectoryRead(format!("Invalid path encoding for {:?}", backup_path))
+        })?
+        .to_string();
+
+    let file_name = backup_path
+        .file_name()
+        .ok_or_else(|| BackupError::DirectoryRead(format!("No filename for {:?}", backup_path)))?
+        .to_string_lossy()
+        .to_string();
+    Ok((file_name, file_path))
+}
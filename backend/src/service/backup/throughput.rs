@@ -0,0 +1,138 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How far back the speed and time left of a run are measured
+const WINDOW: Duration = Duration::from_secs(30);
+
+/// Samples closer together than this are merged, so each file done doesn't add one
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The speed and time left are only given once the samples span this long
+const MIN_SPAN: Duration = Duration::from_secs(1);
+
+/// Speed and time left of a run, from the progress of its last `WINDOW`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(super) struct Estimate {
+    pub elapsed_seconds: u64,
+    pub bytes_per_second: Option<f64>,
+    pub eta_seconds: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    at: Instant,
+    files: u64,
+    bytes: u64,
+}
+
+/// Rolling window of a run's progress
+pub(super) struct Throughput {
+    started: Instant,
+    samples: Mutex<VecDeque<Sample>>,
+}
+
+impl Throughput {
+    pub fn new() -> Self {
+        Self::starting_at(Instant::now())
+    }
+
+    fn starting_at(started: Instant) -> Self {
+        Self {
+            started,
+            samples: Mutex::new(VecDeque::from([Sample {
+                at: started,
+                files: 0,
+                bytes: 0,
+            }])),
+        }
+    }
+
+    /// Record that `files` of `total_files` are done and `bytes` copied, and estimate from it
+    pub fn record(&self, files: u64, bytes: u64, total_files: u64) -> Estimate {
+        self.record_at(Instant::now(), files, bytes, total_files)
+    }
+
+    fn record_at(&self, now: Instant, files: u64, bytes: u64, total_files: u64) -> Estimate {
+        let mut samples = self.samples.lock().unwrap();
+        if samples
+            .back()
+            .is_none_or(|last| now.duration_since(last.at) >= SAMPLE_INTERVAL)
+        {
+            samples.push_back(Sample {
+                at: now,
+                files,
+                bytes,
+            });
+        }
+        // Keep the newest sample older than the window, so the window stays covered
+        while samples
+            .get(1)
+            .is_some_and(|next| now.duration_since(next.at) >= WINDOW)
+        {
+            samples.pop_front();
+        }
+
+        let mut estimate = Estimate {
+            elapsed_seconds: now.duration_since(self.started).as_secs(),
+            ..Default::default()
+        };
+        let Some(oldest) = samples.front() else {
+            return estimate;
+        };
+        let span = now.duration_since(oldest.at);
+        if span < MIN_SPAN {
+            return estimate;
+        }
+        let seconds = span.as_secs_f64();
+        estimate.bytes_per_second = Some(bytes.saturating_sub(oldest.bytes) as f64 / seconds);
+
+        let remaining = total_files.saturating_sub(files);
+        let files_per_second = files.saturating_sub(oldest.files) as f64 / seconds;
+        estimate.eta_seconds = if remaining == 0 {
+            Some(0)
+        } else if files_per_second > 0.0 {
+            Some((remaining as f64 / files_per_second).ceil() as u64)
+        } else {
+            None
+        };
+        estimate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_follows_the_recent_rate() {
+        let start = Instant::now();
+        let throughput = Throughput::starting_at(start);
+        let at = |secs| start + Duration::from_secs(secs);
+
+        // Too early to tell
+        let first = throughput.record_at(start + Duration::from_millis(500), 1, 1000, 100);
+        assert_eq!((first.bytes_per_second, first.eta_seconds), (None, None));
+
+        // 10 files and 10 MB a second
+        let steady = throughput.record_at(at(10), 100, 100_000_000, 1000);
+        assert_eq!(steady.elapsed_seconds, 10);
+        assert_eq!(steady.bytes_per_second, Some(10_000_000.0));
+        assert_eq!(steady.eta_seconds, Some(90));
+
+        // Slowing down to 1 file a second only counts the last half minute
+        throughput.record_at(at(40), 130, 130_000_000, 1000);
+        let slow = throughput.record_at(at(70), 160, 160_000_000, 1000);
+        assert_eq!(slow.bytes_per_second, Some(1_000_000.0));
+        assert_eq!(slow.eta_seconds, Some(840));
+
+        // Stalled: no time left can be given, but the speed is known to be nothing
+        throughput.record_at(at(100), 160, 160_000_000, 1000);
+        let stalled = throughput.record_at(at(130), 160, 160_000_000, 1000);
+        assert_eq!(stalled.bytes_per_second, Some(0.0));
+        assert_eq!(stalled.eta_seconds, None);
+
+        let done = throughput.record_at(at(131), 1000, 160_000_000, 1000);
+        assert_eq!(done.eta_seconds, Some(0));
+    }
+}
@@ -0,0 +1,146 @@
+use super::execute::{ExecutedFile, Written};
+use super::Pipeline;
+use crate::models::error::{BackupError, Result};
+use crate::models::file_operation::{FileOperation, FileOperationEntry};
+use crate::models::prepped_backup::PreppedBackup;
+use crate::service::hash::hash_file;
+use crate::service::trash::restore_trashed_backup;
+use crate::service::verify::{verify_copy, verify_streamed};
+use crate::utils::chaos;
+use crate::utils::directory::{get_file_size, long_path};
+use log::{debug, error, warn};
+use std::fs;
+use std::path::PathBuf;
+
+/// The checked writes of one file
+pub(super) struct VerifiedFile {
+    pub prepped: PreppedBackup,
+    pub writes: Vec<VerifiedWrite>,
+}
+
+pub(super) struct VerifiedWrite {
+    pub destination: String,
+    pub result: Result<Verified>,
+}
+
+pub(super) enum Verified {
+    /// A copy that matches its source
+    Copied {
+        backup_path: PathBuf,
+    },
+    /// A snapshot, with the hash and size its source is recorded with from now on, so later runs
+    /// compare backups with what was written rather than with the live file
+    Snapshot {
+        backup_path: PathBuf,
+        hash: String,
+        file_size: u64,
+    },
+    /// A backup moved to follow its source file, already checked when it was written
+    Moved {
+        from: PathBuf,
+        backup_path: PathBuf,
+    },
+    Sent,
+    Simulated,
+}
+
+/// Check each copy against its source as the verification tiers ask, deleting copies that fail
+pub(super) fn verify(pipeline: &Pipeline, executed: ExecutedFile) -> VerifiedFile {
+    let ExecutedFile { prepped, writes } = executed;
+    let writes = writes
+        .into_iter()
+        .map(|write| VerifiedWrite {
+            destination: write.destination,
+            result: write
+                .result
+                .and_then(|written| verify_write(pipeline, &prepped, written)),
+        })
+        .collect();
+    VerifiedFile { prepped, writes }
+}
+
+fn verify_write(
+    pipeline: &Pipeline,
+    prepped_backup: &PreppedBackup,
+    written: Written,
+) -> Result<Verified> {
+    let (backup_path, streamed, trashed) = match written {
+        Written::Copied {
+            backup_path,
+            streamed,
+            trashed,
+        } => (backup_path, streamed, trashed),
+        // The snapshot was checked with SQLite's own integrity check before it was put in place
+        Written::Snapshot { backup_path } => {
+            return Ok(Verified::Snapshot {
+                hash: hash_file(&backup_path, prepped_backup.hash_limit)?,
+                file_size: get_file_size(&backup_path)?,
+                backup_path,
+            })
+        }
+        Written::Moved { from, backup_path } => return Ok(Verified::Moved { from, backup_path }),
+        Written::Sent => return Ok(Verified::Sent),
+        Written::Simulated => return Ok(Verified::Simulated),
+    };
+
+    let verified = match streamed {
+        _ if chaos::damages(&backup_path) => Err(BackupError::VerificationFailed {
+            path: backup_path.clone(),
+            reason: "hash mismatch injected by --chaos".to_string(),
+        }),
+        Some((copied_hash, bytes_copied)) => verify_streamed(
+            &backup_path,
+            &prepped_backup.hash,
+            &copied_hash,
+            bytes_copied,
+        ),
+        None => {
+            debug!("Verifying backup integrity: {:?}", backup_path);
+            let _slot = pipeline.destination_slot(&backup_path);
+            verify_copy(
+                &prepped_backup.read_from,
+                &backup_path,
+                &prepped_backup.hash,
+                prepped_backup.hash_limit,
+                &pipeline.config.verification_tiers,
+            )
+        }
+    };
+    let entry = |operation, detail| FileOperationEntry {
+        source: Some(prepped_backup.source_file.to_string_lossy().to_string()),
+        backup: Some(backup_path.to_string_lossy().to_string()),
+        hash: Some(prepped_backup.hash.clone()),
+        bytes: Some(prepped_backup.file_size),
+        detail,
+        ..FileOperationEntry::new(operation)
+    };
+    if let Err(e) = verified {
+        warn!(
+            "Backup verification FAILED for {:?}: {} Deleting corrupted backup.",
+            backup_path, e
+        );
+        pipeline.record_operation(entry(
+            FileOperation::VerificationFailed,
+            Some(e.to_string()),
+        ));
+        match fs::remove_file(long_path(&backup_path)) {
+            Ok(()) => pipeline.record_operation(entry(
+                FileOperation::Deleted,
+                Some("failed verification".to_string()),
+            )),
+            Err(e) => error!(
+                "Failed to delete corrupted backup file {:?}: {}",
+                backup_path, e
+            ),
+        }
+        // The backup the copy replaced is still good, so it takes its place again
+        if let Some(trashed) = &trashed {
+            restore_trashed_backup(pipeline.config, trashed, &backup_path);
+        }
+        return Err(e);
+    }
+
+    debug!("Backup verification passed: {:?}", backup_path);
+    pipeline.record_operation(entry(FileOperation::Verified, None));
+    Ok(Verified::Copied { backup_path })
+}
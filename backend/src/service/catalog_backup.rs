@@ -0,0 +1,150 @@
+use crate::models::config::{Config, SqliteSnapshot};
+use crate::models::error::Result;
+use crate::repo::sqlite::database_files;
+use crate::service::sqlite_snapshot::snapshot_database;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use log::{info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory under each destination root that receives snapshots of the database
+pub const CATALOG_BACKUP_DIR: &str = ".catalog";
+
+/// When a snapshot was taken, between the database file's stem and extension
+const STAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+/// Snapshot `database_file`, and the files of its catalog shards, to `.catalog/` under every
+/// destination, keeping the newest `catalog_backups_kept` of each; returns the snapshots written
+///
+/// Snapshots go through SQLite's backup API, so they are consistent while the database is in
+/// use. A destination that can't be written to is logged and skipped.
+pub fn back_up_catalog(config: &Config, now: DateTime<Utc>) -> Vec<PathBuf> {
+    let Some(kept) = config.catalog_backups_kept else {
+        return Vec::new();
+    };
+    let files = database_files();
+    let stamp = now.format(STAMP_FORMAT).to_string();
+
+    let mut written = Vec::new();
+    for destination in config.destination_paths() {
+        if !Path::new(&destination).is_dir() {
+            warn!(
+                "Database not backed up to {}: the destination is unavailable",
+                destination
+            );
+            continue;
+        }
+        let folder = Path::new(&destination).join(CATALOG_BACKUP_DIR);
+        for file in &files {
+            match back_up_to(file, &folder, &stamp, kept) {
+                Ok(snapshot) => written.push(snapshot),
+                Err(e) => warn!("Failed to back up {:?} to {:?}: {}", file, folder, e),
+            }
+        }
+    }
+    if !written.is_empty() {
+        info!("Database backed up to {} snapshot(s)", written.len());
+    }
+    written
+}
+
+fn back_up_to(file: &Path, folder: &Path, stamp: &str, kept: usize) -> Result<PathBuf> {
+    fs::create_dir_all(folder)?;
+    let (prefix, suffix) = snapshot_name_parts(file);
+    let snapshot = folder.join(format!("{}{}{}", prefix, stamp, suffix));
+    snapshot_database(&SqliteSnapshot::BackupApi, file, &snapshot)?;
+
+    // Stamps sort by time, so everything before the newest `kept` goes
+    let mut snapshots: Vec<PathBuf> = fs::read_dir(folder)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix(&prefix))
+                .and_then(|rest| rest.strip_suffix(&suffix))
+                .is_some_and(|stamp| NaiveDateTime::parse_from_str(stamp, STAMP_FORMAT).is_ok())
+        })
+        .collect();
+    snapshots.sort();
+    let expired = snapshots.len().saturating_sub(kept);
+    for old in &snapshots[..expired] {
+        fs::remove_file(old)?;
+    }
+    Ok(snapshot)
+}
+
+/// What comes before and after the stamp in the names of a database file's snapshots, e.g.
+/// `backup-` and `.db` for `backup.db`
+fn snapshot_name_parts(file: &Path) -> (String, String) {
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    let suffix = file
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    (format!("{}-", stem), suffix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::sqlite::{set_db_pool, setup_database};
+    use chrono::TimeZone;
+    use serial_test::serial;
+    use tempfile::TempDir;
+
+    #[test]
+    #[serial]
+    fn test_catalog_snapshots_are_rotated_at_each_destination() {
+        let database = TempDir::new().unwrap();
+        let database_file = database.path().join("backup.db");
+        set_db_pool(database_file.to_str().unwrap()).unwrap();
+        setup_database().unwrap();
+        let destination = TempDir::new().unwrap();
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "database_file": database_file,
+            "backup_sources": [],
+            "backup_destinations": [destination.path(), database.path().join("unmounted")],
+            "catalog_backups_kept": 2
+        }))
+        .unwrap();
+        let folder = destination.path().join(CATALOG_BACKUP_DIR);
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("notes.txt"), "kept").unwrap();
+
+        for hour in 1..=3 {
+            let now = Utc.with_ymd_and_hms(2026, 10, 15, hour, 0, 0).unwrap();
+            assert_eq!(back_up_catalog(&config, now).len(), 1);
+        }
+
+        let mut names: Vec<String> = fs::read_dir(&folder)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "backup-20261015-020000.db",
+                "backup-20261015-030000.db",
+                "notes.txt"
+            ]
+        );
+        let snapshot =
+            rusqlite::Connection::open(folder.join("backup-20261015-030000.db")).unwrap();
+        let tables: i64 = snapshot
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE name = 'Source_Files'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(tables, 1);
+
+        let without_backups = Config {
+            catalog_backups_kept: None,
+            ..config
+        };
+        assert!(back_up_catalog(&without_backups, Utc::now()).is_empty());
+    }
+}
@@ -0,0 +1,322 @@
+use crate::models::backup_row::BackupRow;
+use crate::models::catalog_write::CatalogWrite;
+use crate::models::error::Result;
+use crate::models::source_row::SourceRow;
+use crate::repo::catalog::{
+    delete_backup_row, delete_source_row, insert_backup_row, insert_source_row, select_source,
+    update_source_last_modified,
+};
+use crate::repo::sqlite::{
+    commit_catalog_writes, delete_catalog_writes, insert_catalog_write,
+    select_catalog_journal_runs, select_catalog_writes,
+};
+use log::{error, info, warn};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Journal entries applied to the catalog at a time
+const PROMOTE_BATCH: usize = 1000;
+
+/// The catalog changes of one run, held back until the run finishes
+///
+/// Changes are staged in `Catalog_Journal` and only reach the catalog when the run commits, so a
+/// run that fails or crashes leaves the catalog as it was. A journal dropped without committing
+/// is rolled back.
+pub struct RunJournal {
+    run_id: String,
+    staged: AtomicU64,
+    finished: bool,
+}
+
+impl RunJournal {
+    pub fn begin(run_id: &str) -> Self {
+        RunJournal {
+            run_id: run_id.to_string(),
+            staged: AtomicU64::new(0),
+            finished: false,
+        }
+    }
+
+    pub fn stage(&self, write: CatalogWrite) -> Result<()> {
+        insert_catalog_write(&self.run_id, &write)?;
+        self.staged.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Apply the staged changes to the catalog, returning how many there were
+    ///
+    /// The journal is marked committed before anything is applied, so if the process stops
+    /// partway the rest is applied by `recover_catalog_journals`.
+    pub fn commit(mut self) -> Result<u64> {
+        self.finished = true;
+        if self.staged.load(Ordering::Relaxed) == 0 {
+            return Ok(0);
+        }
+        commit_catalog_writes(&self.run_id)?;
+        promote(&self.run_id)
+    }
+}
+
+impl Drop for RunJournal {
+    fn drop(&mut self) {
+        if self.finished || self.staged.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        match delete_catalog_writes(&self.run_id, None) {
+            Ok(discarded) => warn!(
+                "Rolled back {} catalog change(s) of run {}",
+                discarded, self.run_id
+            ),
+            Err(e) => error!(
+                "Failed to roll back the catalog changes of run {}: {}",
+                self.run_id, e
+            ),
+        }
+    }
+}
+
+/// Finish the journals left behind by runs that stopped without cleaning up
+///
+/// Runs that committed have the rest of their changes applied; the others are rolled back.
+pub fn recover_catalog_journals() -> Result<()> {
+    for (run_id, committed) in select_catalog_journal_runs()? {
+        if committed {
+            let promoted = promote(&run_id)?;
+            info!(
+                "Applied {} remaining catalog change(s) of committed run {}",
+                promoted, run_id
+            );
+        } else {
+            let discarded = delete_catalog_writes(&run_id, None)?;
+            warn!(
+                "Rolled back {} catalog change(s) of interrupted run {}",
+                discarded, run_id
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Apply a run's journal to the catalog in order, removing entries once applied
+///
+/// Every change is an upsert or a removal by path, so entries applied again after a crash change
+/// nothing.
+fn promote(run_id: &str) -> Result<u64> {
+    let mut promoted = 0;
+    let mut source_ids = HashMap::new();
+    loop {
+        let writes = select_catalog_writes(run_id, PROMOTE_BATCH)?;
+        let Some(&(last_id, _)) = writes.last() else {
+            break;
+        };
+        for (_, write) in writes {
+            apply(write, &mut source_ids)?;
+            promoted += 1;
+        }
+        delete_catalog_writes(run_id, Some(last_id))?;
+        // Looked up again if a later batch needs them, so the cache stays a batch long
+        source_ids.clear();
+    }
+    Ok(promoted)
+}
+
+fn apply(write: CatalogWrite, source_ids: &mut HashMap<(String, String), i32>) -> Result<()> {
+    match write {
+        CatalogWrite::Source {
+            file_name,
+            file_path,
+            hash,
+            file_size,
+            last_modified,
+        } => {
+            let id = insert_source_row(&SourceRow {
+                id: 0,
+                file_name: file_name.clone(),
+                file_path: file_path.clone(),
+                hash,
+                file_size,
+                last_modified,
+            })?;
+            source_ids.insert((file_name, file_path), id);
+        }
+        CatalogWrite::SourceLastModified {
+            file_name,
+            file_path,
+            last_modified,
+        } => {
+            if let Some(id) = source_id(source_ids, file_name, file_path)? {
+                update_source_last_modified(id, &last_modified)?;
+            }
+        }
+        CatalogWrite::Backup {
+            source_name,
+            source_path,
+            file_name,
+            file_path,
+            last_modified,
+            original_path,
+            physical_size,
+        } => match source_id(source_ids, source_name, source_path)? {
+            Some(source_id) => insert_backup_row(BackupRow {
+                source_id,
+                file_name,
+                file_path,
+                last_modified,
+                original_path,
+                physical_size,
+            })?,
+            None => warn!(
+                "Skipping backup {}/{}: its source is no longer recorded",
+                file_path, file_name
+            ),
+        },
+        CatalogWrite::RemoveBackup {
+            source_name,
+            source_path,
+            file_name,
+            file_path,
+        } => {
+            if let Some(source_id) = source_id(source_ids, source_name, source_path)? {
+                delete_backup_row(source_id, &file_name, &file_path)?;
+            }
+        }
+        CatalogWrite::RemoveSource {
+            file_name,
+            file_path,
+        } => {
+            let key = (file_name, file_path);
+            if let Some(id) = source_id(source_ids, key.0.clone(), key.1.clone())? {
+                if delete_source_row(id)? {
+                    source_ids.remove(&key);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn source_id(
+    source_ids: &mut HashMap<(String, String), i32>,
+    file_name: String,
+    file_path: String,
+) -> Result<Option<i32>> {
+    let key = (file_name, file_path);
+    if let Some(id) = source_ids.get(&key) {
+        return Ok(Some(*id));
+    }
+    let id = select_source(&key.0, &key.1)?.map(|row| row.id);
+    if let Some(id) = id {
+        source_ids.insert(key, id);
+    }
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::catalog::select_backup_paths;
+    use crate::repo::sqlite::{set_db_pool, setup_database};
+    use serial_test::serial;
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn setup_test_db() -> TempDir {
+        let database = TempDir::new().unwrap();
+        set_db_pool(database.path().join("backup.db").to_str().unwrap()).unwrap();
+        setup_database().unwrap();
+        database
+    }
+
+    fn source(hash: &str) -> CatalogWrite {
+        CatalogWrite::Source {
+            file_name: "a.txt".to_string(),
+            file_path: "/src".to_string(),
+            hash: hash.to_string(),
+            file_size: 3,
+            last_modified: Duration::from_secs(100),
+        }
+    }
+
+    fn backup() -> CatalogWrite {
+        CatalogWrite::Backup {
+            source_name: "a.txt".to_string(),
+            source_path: "/src".to_string(),
+            file_name: "a.txt".to_string(),
+            file_path: "/dest/src".to_string(),
+            last_modified: Duration::from_secs(100),
+            original_path: None,
+            physical_size: None,
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_commit_applies_staged_writes() {
+        let _dir = setup_test_db();
+
+        let journal = RunJournal::begin("run-1");
+        journal.stage(source("h1")).unwrap();
+        journal.stage(backup()).unwrap();
+        assert!(select_source("a.txt", "/src").unwrap().is_none());
+
+        assert_eq!(journal.commit().unwrap(), 2);
+        let row = select_source("a.txt", "/src").unwrap().unwrap();
+        assert_eq!(row.hash, "h1");
+        assert_eq!(
+            select_backup_paths(row.id).unwrap(),
+            vec![PathBuf::from("/dest/src/a.txt")]
+        );
+        assert!(select_catalog_journal_runs().unwrap().is_empty());
+    }
+
+    #[test]
+    #[serial]
+    fn test_dropped_journal_rolls_back() {
+        let _dir = setup_test_db();
+        let journal = RunJournal::begin("run-1");
+        journal.stage(source("h1")).unwrap();
+        journal.commit().unwrap();
+
+        let journal = RunJournal::begin("run-2");
+        journal.stage(source("h2")).unwrap();
+        journal.stage(backup()).unwrap();
+        drop(journal);
+
+        let row = select_source("a.txt", "/src").unwrap().unwrap();
+        assert_eq!(row.hash, "h1");
+        assert!(select_backup_paths(row.id).unwrap().is_empty());
+        assert!(select_catalog_journal_runs().unwrap().is_empty());
+    }
+
+    #[test]
+    #[serial]
+    fn test_recovery_finishes_committed_runs_and_discards_the_rest() {
+        let _dir = setup_test_db();
+        let source_row = |hash: &str| SourceRow {
+            id: 0,
+            file_name: "a.txt".to_string(),
+            file_path: "/src".to_string(),
+            hash: hash.to_string(),
+            file_size: 3,
+            last_modified: Duration::from_secs(100),
+        };
+        let id = insert_source_row(&source_row("h0")).unwrap();
+
+        // As if the process stopped partway through promoting a committed run, after the
+        // source row was already written, and during a later run
+        insert_catalog_write("committed", &source("h1")).unwrap();
+        insert_catalog_write("committed", &backup()).unwrap();
+        commit_catalog_writes("committed").unwrap();
+        insert_source_row(&source_row("h1")).unwrap();
+        insert_catalog_write("interrupted", &source("h2")).unwrap();
+
+        recover_catalog_journals().unwrap();
+
+        let row = select_source("a.txt", "/src").unwrap().unwrap();
+        assert_eq!(row.id, id);
+        assert_eq!(row.hash, "h1");
+        assert_eq!(select_backup_paths(id).unwrap().len(), 1);
+        assert!(select_catalog_journal_runs().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,239 @@
+use crate::models::config::Config;
+use crate::service::prune::destination_files;
+use crate::utils::directory::long_path;
+use log::{debug, info, warn};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// File in each destination directory listing the SHA-256 of the backups beside it, in the
+/// format `sha256sum -c` reads
+pub const CHECKSUM_MANIFEST: &str = "SHA256SUMS";
+
+/// Where a manifest is written before it replaces the last one
+const PARTIAL_MANIFEST: &str = ".SHA256SUMS.partial";
+
+/// Whether `name` is a checksum manifest rather than a backup
+pub fn is_checksum_manifest(name: &std::ffi::OsStr) -> bool {
+    name == CHECKSUM_MANIFEST || name == PARTIAL_MANIFEST
+}
+
+/// Bring the `SHA256SUMS` of every directory with backups in it up to date, returning how many
+/// were written
+///
+/// Backups are written with the time they were copied, so only files modified since a
+/// directory's manifest, or missing from it, are hashed again. A directory whose manifest can't
+/// be written is logged and skipped.
+pub fn write_checksum_manifests(config: &Config) -> u64 {
+    if !config.checksum_manifests {
+        return 0;
+    }
+    let mut written = 0;
+    for destination in config.destination_paths() {
+        let root = Path::new(&destination);
+        if !root.is_dir() {
+            continue;
+        }
+        let mut directories: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+        for path in destination_files(root) {
+            let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+                continue;
+            };
+            match name.to_str() {
+                Some(name) => directories
+                    .entry(parent.to_path_buf())
+                    .or_default()
+                    .push(name.to_string()),
+                None => debug!(
+                    "{:?} isn't valid UTF-8, so it's left out of {}",
+                    path, CHECKSUM_MANIFEST
+                ),
+            }
+        }
+        for (directory, names) in directories {
+            match update_manifest(&directory, names) {
+                Ok(true) => written += 1,
+                Ok(false) => {}
+                Err(e) => warn!(
+                    "Failed to write {} in {:?}: {}",
+                    CHECKSUM_MANIFEST, directory, e
+                ),
+            }
+        }
+    }
+    if written > 0 {
+        info!("Updated {} {} file(s)", written, CHECKSUM_MANIFEST);
+    }
+    written
+}
+
+/// Rewrite the manifest of `directory` for the backups `names` if any of them changed, returning
+/// whether it was written
+fn update_manifest(directory: &Path, mut names: Vec<String>) -> io::Result<bool> {
+    let manifest = directory.join(CHECKSUM_MANIFEST);
+    let (mut known, written_at) = match fs::metadata(long_path(&manifest)) {
+        Ok(metadata) => (
+            parse_manifest(&fs::read_to_string(long_path(&manifest))?),
+            metadata.modified()?,
+        ),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => (HashMap::new(), SystemTime::UNIX_EPOCH),
+        Err(e) => return Err(e),
+    };
+
+    names.sort();
+    let mut changed = known.len() != names.len();
+    let mut contents = String::new();
+    for name in &names {
+        let path = directory.join(name);
+        // A backup written in the same tick as the manifest is hashed again to be safe
+        let hash = match known.remove(name) {
+            Some(hash) if fs::metadata(long_path(&path))?.modified()? < written_at => hash,
+            // Rewritten even if the hash is the same, so the file isn't read again next time
+            _ => {
+                changed = true;
+                sha256_file(&path)?
+            }
+        };
+        contents.push_str(&manifest_line(&hash, name));
+    }
+    if !changed {
+        return Ok(false);
+    }
+
+    let partial = directory.join(PARTIAL_MANIFEST);
+    fs::write(long_path(&partial), contents)?;
+    fs::rename(long_path(&partial), long_path(&manifest))?;
+    Ok(true)
+}
+
+fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(long_path(path))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 65536];
+    loop {
+        let count = file.read(&mut buffer)?;
+        if count == 0 {
+            break;
+        }
+        hasher.update(&buffer[..count]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// A line of `sha256sum` output; names with a backslash or line break are escaped and the line
+/// marked with a leading backslash, as GNU coreutils does
+fn manifest_line(hash: &str, name: &str) -> String {
+    if name.contains(['\\', '\n', '\r']) {
+        let escaped = name
+            .replace('\\', "\\\\")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r");
+        format!("\\{}  {}\n", hash, escaped)
+    } else {
+        format!("{}  {}\n", hash, name)
+    }
+}
+
+/// Hashes by file name from a manifest; lines that aren't `sha256sum` output are ignored
+fn parse_manifest(contents: &str) -> HashMap<String, String> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let (escaped, line) = match line.strip_prefix('\\') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            let (hash, name) = line.split_once("  ").or_else(|| line.split_once(" *"))?;
+            if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            let name = if escaped {
+                unescape(name)
+            } else {
+                name.to_string()
+            };
+            Some((name, hash.to_ascii_lowercase()))
+        })
+        .collect()
+}
+
+fn unescape(name: &str) -> String {
+    let mut unescaped = String::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(other) => unescaped.push(other),
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn manifest_config(destination: &TempDir) -> Config {
+        serde_json::from_value(serde_json::json!({
+            "database_file": "",
+            "backup_sources": [],
+            "backup_destinations": [destination.path()],
+            "checksum_manifests": true
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_manifests_list_the_sha256_of_each_backup_in_their_directory() {
+        let destination = TempDir::new().unwrap();
+        let photos = destination.path().join("photos");
+        fs::create_dir_all(photos.join("2024")).unwrap();
+        fs::write(photos.join("a.txt"), "abc").unwrap();
+        fs::write(photos.join("2024").join("b.txt"), "").unwrap();
+        let config = manifest_config(&destination);
+
+        assert_eq!(write_checksum_manifests(&config), 2);
+        assert_eq!(
+            fs::read_to_string(photos.join(CHECKSUM_MANIFEST)).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  a.txt\n"
+        );
+        assert_eq!(
+            fs::read_to_string(photos.join("2024").join(CHECKSUM_MANIFEST)).unwrap(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855  b.txt\n"
+        );
+        // Nothing changed, so nothing is written
+        assert_eq!(write_checksum_manifests(&config), 0);
+
+        fs::remove_file(photos.join("a.txt")).unwrap();
+        fs::write(photos.join("c\\d.txt"), "abc").unwrap();
+        assert_eq!(write_checksum_manifests(&config), 1);
+        let contents = fs::read_to_string(photos.join(CHECKSUM_MANIFEST)).unwrap();
+        assert_eq!(
+            contents,
+            "\\ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  c\\\\d.txt\n"
+        );
+        assert_eq!(
+            parse_manifest(&contents)
+                .get("c\\d.txt")
+                .map(String::as_str),
+            Some("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+
+        let without_manifests = Config {
+            checksum_manifests: false,
+            ..config
+        };
+        fs::write(photos.join("e.txt"), "e").unwrap();
+        assert_eq!(write_checksum_manifests(&without_manifests), 0);
+    }
+}
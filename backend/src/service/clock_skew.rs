@@ -0,0 +1,92 @@
+use crate::models::run_report::{ClockSkew, SkewKind};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Collects timestamps ahead of the system clock during a run, per source or destination
+pub struct SkewTracker {
+    tolerance_secs: u64,
+    found: Mutex<HashMap<(SkewKind, String), ClockSkew>>,
+}
+
+impl SkewTracker {
+    pub fn new(tolerance_secs: u64) -> Self {
+        SkewTracker {
+            tolerance_secs,
+            found: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Record `timestamp` if it's further ahead of the system clock than the tolerance allows,
+    /// returning whether it was
+    pub fn check(&self, kind: SkewKind, location: &str, timestamp: &Duration) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let ahead = timestamp.as_secs().saturating_sub(now);
+        if ahead <= self.tolerance_secs {
+            return false;
+        }
+
+        let mut found = self.found.lock().unwrap();
+        let skew = found
+            .entry((kind, location.to_string()))
+            .or_insert_with(|| ClockSkew {
+                kind,
+                location: location.to_string(),
+                files: 0,
+                max_ahead_secs: 0,
+            });
+        skew.files += 1;
+        skew.max_ahead_secs = skew.max_ahead_secs.max(ahead);
+        true
+    }
+
+    /// Everything found, ordered by kind and location
+    pub fn into_report(self) -> Vec<ClockSkew> {
+        let mut found: Vec<ClockSkew> = self.found.into_inner().unwrap().into_values().collect();
+        found.sort_by(|a, b| (a.kind, &a.location).cmp(&(b.kind, &b.location)));
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_ignores_skew_within_tolerance() {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let tracker = SkewTracker::new(60);
+
+        assert!(!tracker.check(SkewKind::Destination, "nas", &now));
+        assert!(!tracker.check(
+            SkewKind::Destination,
+            "nas",
+            &(now + Duration::from_secs(30))
+        ));
+        assert!(tracker.check(
+            SkewKind::Destination,
+            "nas",
+            &(now + Duration::from_secs(600))
+        ));
+        assert!(tracker.check(
+            SkewKind::Destination,
+            "nas",
+            &(now + Duration::from_secs(300))
+        ));
+        assert!(tracker.check(
+            SkewKind::Database,
+            "photos",
+            &(now + Duration::from_secs(3600))
+        ));
+
+        let report = tracker.into_report();
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].kind, SkewKind::Destination);
+        assert_eq!(report[0].files, 2);
+        assert!(report[0].max_ahead_secs >= 599);
+        assert_eq!(report[1].location, "photos");
+    }
+}
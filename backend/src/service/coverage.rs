@@ -0,0 +1,182 @@
+use crate::models::backup_row::BackupRow;
+use crate::models::catalog_file::{BackupCheck, CatalogBackup};
+use crate::models::config::{BackupSource, Config};
+use crate::models::coverage::{CoverageGap, CoverageReport, DestinationCoverage};
+use crate::models::error::Result;
+use crate::repo::catalog::{select_all_backups, select_sources};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Check every catalogued file against the destinations its source is routed to
+///
+/// A backup is valid when it is still on disk with the recorded size and modification time.
+/// Files outside every configured source are skipped, and `destination` limits the check to
+/// the destinations it names or points at.
+pub fn build_coverage_report(config: &Config, destination: Option<&str>) -> Result<CoverageReport> {
+    let mut backups: HashMap<i32, Vec<BackupRow>> = HashMap::new();
+    for backup in select_all_backups()? {
+        backups.entry(backup.source_id).or_default().push(backup);
+    }
+
+    let mut destinations: Vec<DestinationCoverage> = config
+        .backup_destinations
+        .iter()
+        .filter(|d| destination.is_none_or(|reference| d.matches(reference)))
+        .map(|d| DestinationCoverage {
+            name: d.name().unwrap_or(d.path()).to_string(),
+            path: d.path().to_string(),
+            ..Default::default()
+        })
+        .collect();
+
+    let mut report = CoverageReport::default();
+    for (source, _) in select_sources(None)? {
+        let path = Path::new(&source.file_path).join(&source.file_name);
+        let Some(configured) = configured_source(config, &path) else {
+            continue;
+        };
+        report.files_checked += 1;
+
+        let routed = config
+            .source_settings(Path::new(&configured.parent_directory))
+            .destinations;
+        let valid: Vec<String> = backups
+            .get(&source.id)
+            .into_iter()
+            .flatten()
+            .map(|backup| CatalogBackup::check(backup, source.file_size))
+            .filter(|backup| backup.status == BackupCheck::Ok)
+            .map(|backup| backup.path)
+            .collect();
+
+        let mut missing_from = Vec::new();
+        for coverage in destinations.iter_mut().filter(|d| routed.contains(&d.path)) {
+            coverage.expected += 1;
+            if valid
+                .iter()
+                .any(|backup| Path::new(backup).starts_with(&coverage.path))
+            {
+                coverage.covered += 1;
+            } else {
+                coverage.missing += 1;
+                coverage.missing_bytes += source.file_size;
+                missing_from.push(coverage.name.clone());
+            }
+        }
+
+        if !missing_from.is_empty() {
+            report.gaps.push(CoverageGap {
+                path: path.to_string_lossy().to_string(),
+                file_size: source.file_size,
+                missing_from,
+                no_valid_backup: valid.is_empty(),
+            });
+        }
+    }
+    report.destinations = destinations;
+    Ok(report)
+}
+
+/// Innermost configured source containing `path`
+fn configured_source<'a>(config: &'a Config, path: &Path) -> Option<&'a BackupSource> {
+    config
+        .backup_sources
+        .iter()
+        .filter(|source| path.starts_with(&source.parent_directory))
+        .max_by_key(|source| source.parent_directory.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::source_row::SourceRow;
+    use crate::repo::catalog::{insert_backup_row, insert_source_row};
+    use crate::repo::sqlite::{set_db_pool, setup_database};
+    use serial_test::serial;
+    use std::fs;
+    use std::time::{Duration, UNIX_EPOCH};
+    use tempfile::TempDir;
+
+    #[test]
+    #[serial]
+    fn test_coverage_reports_files_missing_from_destinations() {
+        let database = TempDir::new().unwrap();
+        set_db_pool(database.path().join("backup.db").to_str().unwrap()).unwrap();
+        setup_database().unwrap();
+        let source = TempDir::new().unwrap();
+        let one = TempDir::new().unwrap();
+        let two = TempDir::new().unwrap();
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "database_file": "",
+            "backup_sources": [{"parent_directory": source.path()}],
+            "backup_destinations": [{"name": "one", "path": one.path()}, two.path()]
+        }))
+        .unwrap();
+
+        let record = |name: &str, dir: &Path| {
+            insert_source_row(&SourceRow {
+                id: 0,
+                file_name: name.to_string(),
+                file_path: dir.to_string_lossy().to_string(),
+                hash: "hash".to_string(),
+                file_size: 4,
+                last_modified: Duration::from_secs(100),
+            })
+            .unwrap()
+        };
+        // Writes the copy unless `on_disk` is false, so the record points at nothing
+        let back_up = |source_id: i32, name: &str, destination: &Path, on_disk: bool| {
+            let path = destination.join(name);
+            let mut last_modified = Duration::from_secs(100);
+            if on_disk {
+                fs::write(&path, "data").unwrap();
+                last_modified = fs::metadata(&path)
+                    .unwrap()
+                    .modified()
+                    .unwrap()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap();
+            }
+            insert_backup_row(BackupRow {
+                source_id,
+                file_name: name.to_string(),
+                file_path: destination.to_string_lossy().to_string(),
+                last_modified: Duration::from_secs(last_modified.as_secs()),
+                original_path: None,
+                physical_size: None,
+            })
+            .unwrap();
+        };
+
+        let partial = record("a.txt", source.path());
+        back_up(partial, "a.txt", one.path(), true);
+        let lost = record("b.txt", source.path());
+        back_up(lost, "b.txt", one.path(), false);
+        back_up(lost, "b.txt", two.path(), false);
+        let complete = record("c.txt", source.path());
+        back_up(complete, "c.txt", one.path(), true);
+        back_up(complete, "c.txt", two.path(), true);
+        record("d.txt", Path::new("/no/longer/configured"));
+
+        let report = build_coverage_report(&config, None).unwrap();
+        assert_eq!(report.files_checked, 3);
+        let counts: Vec<_> = report
+            .destinations
+            .iter()
+            .map(|d| (d.name.as_str(), d.expected, d.covered, d.missing))
+            .collect();
+        let two_name = two.path().to_string_lossy();
+        assert_eq!(counts, [("one", 3, 2, 1), (two_name.as_ref(), 3, 1, 2)]);
+        assert_eq!(report.gaps.len(), 2);
+        assert!(report.gaps[0].path.ends_with("a.txt"));
+        assert_eq!(report.gaps[0].missing_from, [two_name.to_string()]);
+        assert!(!report.gaps[0].no_valid_backup);
+        assert!(report.gaps[1].no_valid_backup);
+        assert_eq!(report.gaps[1].missing_from.len(), 2);
+
+        let only_one = build_coverage_report(&config, Some("one")).unwrap();
+        assert_eq!(only_one.destinations.len(), 1);
+        assert_eq!(only_one.gaps.len(), 1);
+        assert!(only_one.gaps[0].path.ends_with("b.txt"));
+    }
+}
@@ -0,0 +1,287 @@
+use crate::models::backup_marker::DESTINATION_MARKER_FILE;
+use crate::models::config::{BackupDestination, Config};
+use crate::models::config_validator::check_writable;
+use crate::models::dry_run_mode::DryRunMode;
+use crate::models::error::{BackupError, Result};
+use crate::models::run_report::UnavailableDestination;
+use crate::repo::sqlite::{
+    clear_destination_mismatch, insert_destination_marker, select_destination_marker,
+    update_destination_mismatch,
+};
+use crate::service::audit::record_audit_event;
+use log::{error, info};
+use std::fs;
+use std::path::Path;
+use uuid::Uuid;
+
+/// Check each destination before a run copies to it; the ones it can't back up to, and why
+///
+/// A destination must be a writable directory. One marked `mounted` must also be on another
+/// filesystem than `/`, so an empty mount point isn't filled instead of the drive. With
+/// `destination_checks.markers` on, the ID file in each destination has to match the one recorded
+/// on an earlier run; a destination without a recorded ID gets one. Dry runs write nothing.
+pub fn check_destinations(
+    config: &Config,
+    dry_run_mode: DryRunMode,
+) -> Vec<UnavailableDestination> {
+    let write = !dry_run_mode.is_dry_run();
+    config
+        .backup_destinations
+        .iter()
+        .filter_map(|destination| {
+            check_destination(config, destination, write)
+                .err()
+                .map(|reason| UnavailableDestination {
+                    destination: destination.name().unwrap_or(destination.path()).to_string(),
+                    reason,
+                    offline: false,
+                })
+        })
+        .collect()
+}
+
+fn check_destination(
+    config: &Config,
+    destination: &BackupDestination,
+    write: bool,
+) -> std::result::Result<(), String> {
+    let path = Path::new(destination.path());
+    if !path.exists() {
+        if destination.mounted() {
+            return Err("not mounted: the path doesn't exist".to_string());
+        }
+        if !write {
+            return Ok(());
+        }
+        fs::create_dir_all(path).map_err(|e| format!("could not be created: {}", e))?;
+    }
+    if !path.is_dir() {
+        return Err("not a directory".to_string());
+    }
+    if destination.mounted() && on_root_filesystem(path) {
+        return Err("not mounted: the path is on the same filesystem as /".to_string());
+    }
+    if write {
+        check_writable(path).map_err(|e| format!("not writable: {}", e))?;
+    }
+    if config.destination_checks.markers {
+        check_marker(destination.path(), write)?;
+    }
+    Ok(())
+}
+
+/// Compare the destination's ID file with the recorded ID, recording one if there is none
+///
+/// A mismatch is logged and, outside dry runs, kept with the recorded ID for status reports.
+fn check_marker(destination: &str, write: bool) -> std::result::Result<(), String> {
+    let marker_path = Path::new(destination).join(DESTINATION_MARKER_FILE);
+    let found = read_marker(&marker_path);
+    let recorded = select_destination_marker(destination).map_err(|e| e.to_string())?;
+
+    let Some(recorded) = recorded else {
+        if write {
+            // A marker already there was kept from before the database was reset or moved
+            let id = match found {
+                Some(found) => found,
+                None => write_marker(&marker_path)?,
+            };
+            insert_destination_marker(destination, &id).map_err(|e| e.to_string())?;
+            info!("Destination {} has marker {}", destination, id);
+        }
+        return Ok(());
+    };
+    if found.as_deref() == Some(recorded.as_str()) {
+        if write {
+            clear_destination_mismatch(destination).map_err(|e| e.to_string())?;
+        }
+        return Ok(());
+    }
+
+    let reason = match &found {
+        Some(found) => format!(
+            "holds marker {} instead of {}; another drive may be mounted there",
+            found, recorded
+        ),
+        None => format!(
+            "has no {} file, though marker {} was recorded; it may be another drive or an \
+             empty mount point",
+            DESTINATION_MARKER_FILE, recorded
+        ),
+    };
+    error!("Destination {} {}", destination, reason);
+    if write {
+        update_destination_mismatch(destination, found.as_deref()).map_err(|e| e.to_string())?;
+    }
+    Err(reason)
+}
+
+/// Take the drive now at a destination as the destination, e.g. after replacing its disk
+///
+/// The drive's marker becomes the recorded one; a drive without a marker is given a new one.
+/// Returns the recorded ID.
+pub fn accept_destination(config: &Config, reference: &str, actor: &str) -> Result<String> {
+    let destination = config
+        .backup_destinations
+        .iter()
+        .find(|destination| destination.matches(reference))
+        .ok_or_else(|| {
+            BackupError::Destination(format!("No configured destination matches '{}'", reference))
+        })?;
+    let marker_path = Path::new(destination.path()).join(DESTINATION_MARKER_FILE);
+    let id = match read_marker(&marker_path) {
+        Some(found) => found,
+        None => write_marker(&marker_path).map_err(BackupError::Destination)?,
+    };
+    let previous = select_destination_marker(destination.path())?;
+    insert_destination_marker(destination.path(), &id)?;
+    record_audit_event(
+        "destination_accepted",
+        actor,
+        match previous {
+            Some(previous) if previous != id => format!(
+                "{} accepted with marker {} in place of {}",
+                destination.path(),
+                id,
+                previous
+            ),
+            _ => format!("{} accepted with marker {}", destination.path(), id),
+        },
+    )?;
+    Ok(id)
+}
+
+fn read_marker(marker_path: &Path) -> Option<String> {
+    fs::read_to_string(marker_path)
+        .ok()
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+}
+
+/// Write a new ID into the marker file
+fn write_marker(marker_path: &Path) -> std::result::Result<String, String> {
+    let id = Uuid::new_v4().to_string();
+    fs::write(marker_path, &id)
+        .map_err(|e| format!("could not write {}: {}", DESTINATION_MARKER_FILE, e))?;
+    Ok(id)
+}
+
+/// Whether `path` is on the filesystem of its root, i.e. nothing is mounted on the way to it
+#[cfg(unix)]
+pub(crate) fn on_root_filesystem(path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    let root = path.ancestors().last().unwrap_or(path);
+    match (fs::metadata(path), fs::metadata(root)) {
+        (Ok(path), Ok(root)) => path.dev() == root.dev(),
+        _ => false,
+    }
+}
+
+/// Drive letters and shares appear only while mounted, so existing is enough
+#[cfg(not(unix))]
+pub(crate) fn on_root_filesystem(_path: &Path) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::sqlite::{select_destination_mismatches, set_db_pool, setup_database};
+    use serial_test::serial;
+    use tempfile::TempDir;
+
+    fn config(database: &str, destinations: serde_json::Value) -> Config {
+        serde_json::from_value(serde_json::json!({
+            "database_file": database,
+            "backup_sources": [],
+            "backup_destinations": destinations
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    #[serial]
+    fn test_unmounted_destinations_are_unavailable() {
+        let dir = TempDir::new().unwrap();
+        let database = dir.path().join("backup.db").to_string_lossy().to_string();
+        set_db_pool(&database).unwrap();
+        setup_database().unwrap();
+        let missing = dir.path().join("usb");
+        let plain = dir.path().join("plain");
+
+        let config = config(
+            &database,
+            serde_json::json!([
+                { "name": "usb", "path": missing, "mounted": true },
+                plain
+            ]),
+        );
+        let unavailable = check_destinations(&config, DryRunMode::None);
+        assert_eq!(
+            unavailable,
+            vec![UnavailableDestination {
+                destination: "usb".to_string(),
+                reason: "not mounted: the path doesn't exist".to_string(),
+                offline: false,
+            }]
+        );
+        // An unmounted destination is never created; a plain one is, as before
+        assert!(!missing.exists());
+        assert!(plain.is_dir());
+
+        // A directory standing in for the drive on the root filesystem isn't taken for it
+        if on_root_filesystem(dir.path()) {
+            fs::create_dir_all(&missing).unwrap();
+            assert_eq!(check_destinations(&config, DryRunMode::None).len(), 1);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_marker_mismatch_makes_a_destination_unavailable() {
+        let dir = TempDir::new().unwrap();
+        let database = dir.path().join("backup.db").to_string_lossy().to_string();
+        set_db_pool(&database).unwrap();
+        setup_database().unwrap();
+        let destination = dir.path().join("backup");
+        let config = config(&database, serde_json::json!([destination]));
+
+        // A dry run leaves no marker behind
+        assert!(check_destinations(&config, DryRunMode::Full).is_empty());
+        assert!(!destination.join(DESTINATION_MARKER_FILE).exists());
+
+        assert!(check_destinations(&config, DryRunMode::None).is_empty());
+        let id = fs::read_to_string(destination.join(DESTINATION_MARKER_FILE)).unwrap();
+        assert_eq!(
+            select_destination_marker(&destination.to_string_lossy()).unwrap(),
+            Some(id.clone())
+        );
+        assert!(check_destinations(&config, DryRunMode::None).is_empty());
+
+        // Another drive in its place
+        fs::write(destination.join(DESTINATION_MARKER_FILE), "other-drive").unwrap();
+        let unavailable = check_destinations(&config, DryRunMode::None);
+        assert_eq!(unavailable.len(), 1);
+        assert!(unavailable[0].reason.contains(&id), "{:?}", unavailable);
+
+        let mismatches = select_destination_mismatches().unwrap();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(
+            (
+                mismatches[0].recorded_id.as_str(),
+                mismatches[0].found_id.as_deref()
+            ),
+            (id.as_str(), Some("other-drive"))
+        );
+
+        fs::remove_file(destination.join(DESTINATION_MARKER_FILE)).unwrap();
+        assert_eq!(check_destinations(&config, DryRunMode::None).len(), 1);
+        assert_eq!(select_destination_mismatches().unwrap()[0].found_id, None);
+
+        // Accepting the drive gives it a marker of its own and clears the mismatch
+        let accepted = accept_destination(&config, &destination.to_string_lossy(), "test").unwrap();
+        assert_ne!(accepted, id);
+        assert!(check_destinations(&config, DryRunMode::None).is_empty());
+        assert!(select_destination_mismatches().unwrap().is_empty());
+        assert!(accept_destination(&config, "elsewhere", "test").is_err());
+    }
+}
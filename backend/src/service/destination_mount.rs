@@ -0,0 +1,178 @@
+use crate::models::config::{BackupDestination, Config, DestinationCommand};
+use crate::models::run_report::UnavailableDestination;
+use crate::service::destination_check::on_root_filesystem;
+use crate::service::post_process::{join_output, read_in_background, wait_with_timeout};
+use log::{info, warn};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Longest udisks is given to mount or unmount a drive
+const UDISKS_TIMEOUT_SECONDS: u64 = 60;
+
+/// How a destination the run mounted is unmounted again
+enum Unmount {
+    Command(DestinationCommand),
+    Udisks(String),
+}
+
+/// Removable destinations mounted for one run, unmounted again when dropped
+#[derive(Default)]
+pub struct DestinationMounts {
+    mounted: Vec<(String, Option<Unmount>)>,
+}
+
+impl DestinationMounts {
+    /// Mount the destinations with a `mount_command` or `udisks_device` that aren't mounted yet
+    ///
+    /// Also returns the destinations that couldn't be mounted, which the run skips as offline.
+    /// Destinations that were mounted already are left mounted after the run.
+    pub fn mount(config: &Config) -> (Self, Vec<UnavailableDestination>) {
+        let mut mounts = DestinationMounts::default();
+        let mut offline = Vec::new();
+        for destination in &config.backup_destinations {
+            let path = destination.path();
+            if is_mounted(Path::new(path)) {
+                continue;
+            }
+            let mounted = match (destination.udisks_device(), destination.mount_command()) {
+                (Some(device), _) => {
+                    udisks("mount", device).map(|()| Some(Unmount::Udisks(device.to_string())))
+                }
+                (None, Some(command)) => run(command, path)
+                    .map(|()| destination.unmount_command().cloned().map(Unmount::Command)),
+                (None, None) => continue,
+            };
+            match mounted {
+                Ok(unmount) => {
+                    info!("Mounted destination {}", path);
+                    mounts.mounted.push((path.to_string(), unmount));
+                }
+                Err(e) => {
+                    warn!("Could not mount destination {}: {}", path, e);
+                    offline.push(offline_destination(destination, e));
+                }
+            }
+        }
+        (mounts, offline)
+    }
+}
+
+impl Drop for DestinationMounts {
+    fn drop(&mut self) {
+        for (path, unmount) in self.mounted.drain(..).rev() {
+            let unmounted = match unmount {
+                Some(Unmount::Command(command)) => run(&command, &path),
+                Some(Unmount::Udisks(device)) => udisks("unmount", &device),
+                None => continue,
+            };
+            match unmounted {
+                Ok(()) => info!("Unmounted destination {}", path),
+                Err(e) => warn!("Could not unmount destination {}: {}", path, e),
+            }
+        }
+    }
+}
+
+/// Whether something is mounted at `path`; on Windows, whether it exists
+fn is_mounted(path: &Path) -> bool {
+    path.exists() && !on_root_filesystem(path)
+}
+
+fn offline_destination(destination: &BackupDestination, error: String) -> UnavailableDestination {
+    UnavailableDestination {
+        destination: destination.name().unwrap_or(destination.path()).to_string(),
+        reason: format!("offline: {}", error),
+        offline: true,
+    }
+}
+
+/// Mount or unmount a block device through udisks
+fn udisks(action: &str, device: &str) -> std::result::Result<(), String> {
+    run(
+        &DestinationCommand {
+            command: "udisksctl".to_string(),
+            args: vec![
+                action.to_string(),
+                "--block-device".to_string(),
+                device.to_string(),
+                "--no-user-interaction".to_string(),
+            ],
+            timeout_seconds: Some(UDISKS_TIMEOUT_SECONDS),
+        },
+        device,
+    )
+}
+
+/// Run a destination command with `{path}` in its arguments replaced by `path`
+fn run(command: &DestinationCommand, path: &str) -> std::result::Result<(), String> {
+    let program = &command.command;
+    let mut child = Command::new(program)
+        .args(command.args.iter().map(|arg| arg.replace("{path}", path)))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start {}: {}", program, e))?;
+    let stderr = child.stderr.take().map(read_in_background);
+
+    let status = match command.timeout_seconds {
+        Some(seconds) => wait_with_timeout(&mut child, Duration::from_secs(seconds)),
+        None => child.wait().map(Some),
+    }
+    .map_err(|e| format!("Failed to wait for {}: {}", program, e))?;
+
+    match status {
+        Some(status) if status.success() => Ok(()),
+        Some(status) => {
+            Err(join_output(stderr)
+                .unwrap_or_else(|| format!("{} exited with {}", program, status)))
+        }
+        None => {
+            let _ = child.kill();
+            let _ = child.wait();
+            Err(format!(
+                "{} timed out after {} seconds",
+                program,
+                command.timeout_seconds.unwrap_or_default()
+            ))
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_mounts_for_the_run_and_skips_what_fails_to_mount() {
+        let dir = TempDir::new().unwrap();
+        let drive = dir.path().join("drive");
+        let missing = dir.path().join("missing");
+        let command = |program: &str| serde_json::json!({ "command": program, "args": ["{path}"] });
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "database_file": dir.path().join("backup.db"),
+            "backup_sources": [],
+            "backup_destinations": [
+                {
+                    "path": drive,
+                    "mount_command": command("mkdir"),
+                    "unmount_command": command("rmdir")
+                },
+                { "name": "away", "path": missing, "mount_command": command("false") }
+            ]
+        }))
+        .unwrap();
+
+        let (mounts, offline) = DestinationMounts::mount(&config);
+        assert!(drive.is_dir());
+        assert_eq!(offline.len(), 1);
+        assert_eq!(offline[0].destination, "away");
+        assert!(offline[0].offline);
+        assert!(offline[0].reason.starts_with("offline: "), "{:?}", offline);
+
+        drop(mounts);
+        assert!(!drive.exists());
+    }
+}
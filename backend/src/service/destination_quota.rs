@@ -0,0 +1,104 @@
+use crate::models::config::Config;
+use crate::models::error::Result;
+use crate::models::run_report::QuotaExceeded;
+use crate::repo::catalog::get_storage_overview;
+use crate::utils::directory::long_path;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// What one destination with a `max_total_bytes` holds during a run
+struct Quota {
+    name: String,
+    max_total_bytes: u64,
+    used_bytes: u64,
+    /// Set by the first copy that didn't fit; no more copies go to the destination this run
+    full: bool,
+    files_skipped: u64,
+    bytes_skipped: u64,
+}
+
+/// Keeps the copies of a run within each destination's `max_total_bytes`
+pub struct QuotaTracker {
+    /// By destination path
+    quotas: Mutex<HashMap<String, Quota>>,
+}
+
+impl QuotaTracker {
+    /// Start from the bytes the catalog records under each destination with a quota
+    pub fn new(config: &Config) -> Result<Self> {
+        let limited: Vec<_> = config
+            .backup_destinations
+            .iter()
+            .filter_map(|d| Some((d, d.max_total_bytes()?)))
+            .collect();
+        let mut quotas = HashMap::new();
+        if !limited.is_empty() {
+            let paths: Vec<String> = limited.iter().map(|(d, _)| d.path().to_string()).collect();
+            let overview = get_storage_overview(&paths)?;
+            for ((destination, max_total_bytes), stats) in
+                limited.into_iter().zip(overview.destination_stats)
+            {
+                quotas.insert(
+                    destination.path().to_string(),
+                    Quota {
+                        name: destination.name().unwrap_or(destination.path()).to_string(),
+                        max_total_bytes,
+                        used_bytes: stats.total_size,
+                        full: false,
+                        files_skipped: 0,
+                        bytes_skipped: 0,
+                    },
+                );
+            }
+        }
+        Ok(QuotaTracker {
+            quotas: Mutex::new(quotas),
+        })
+    }
+
+    /// Whether a copy of `file_size` bytes to `backup_path` fits its destination's quota,
+    /// counting it against the quota if it does
+    ///
+    /// A copy replacing a backup only adds the bytes it is larger by.
+    pub fn admit(&self, config: &Config, backup_path: &Path, file_size: u64) -> bool {
+        let Some(destination) = config.destination_of(backup_path) else {
+            return true;
+        };
+        let mut quotas = self.quotas.lock().unwrap();
+        let Some(quota) = quotas.get_mut(destination.path()) else {
+            return true;
+        };
+
+        let replaced = std::fs::metadata(long_path(backup_path)).map_or(0, |m| m.len());
+        let added = file_size.saturating_sub(replaced);
+        if !quota.full && quota.used_bytes + added <= quota.max_total_bytes {
+            quota.used_bytes += added;
+            return true;
+        }
+        quota.full = true;
+        quota.files_skipped += 1;
+        quota.bytes_skipped += file_size;
+        false
+    }
+
+    /// The destinations that turned copies away, by name
+    pub fn into_report(self) -> Vec<QuotaExceeded> {
+        let mut exceeded: Vec<QuotaExceeded> = self
+            .quotas
+            .into_inner()
+            .unwrap()
+            .into_values()
+            .filter(|quota| quota.full)
+            .map(|quota| QuotaExceeded {
+                destination: quota.name,
+                max_total_bytes: quota.max_total_bytes,
+                used_bytes: quota.used_bytes,
+                files_skipped: quota.files_skipped,
+                bytes_skipped: quota.bytes_skipped,
+            })
+            .collect();
+        exceeded.sort_by(|a, b| a.destination.cmp(&b.destination));
+        exceeded
+    }
+}
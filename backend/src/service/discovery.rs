@@ -0,0 +1,539 @@
+use crate::models::backup_marker::BACKUP_MARKER_FILE;
+use crate::models::config::{BackupSource, WalkErrorPolicy};
+use crate::models::dry_run_mode::DryRunMode;
+use crate::models::error::{BackupError, Result};
+use crate::models::run_report::{DiscoveredDirectory, SkippedEntry};
+use crate::models::run_selection::SelectedSource;
+use crate::repo::sqlite::{insert_known_directory, select_known_directories};
+use crate::service::plugin::PluginFilters;
+use crate::service::volume_snapshot::{SnapshotView, SourceSnapshots};
+use crate::utils::content_type::is_excluded_content_type;
+use crate::utils::directory::{get_top_level_dirs, has_file_attribute, walk_files_in_path};
+use crate::utils::file_filter::FileFilter;
+use indicatif::ProgressBar;
+use log::{info, warn};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tracing::info_span;
+
+/// Files buffered between discovery and preparation before the walk waits
+const DISCOVERY_BUFFER: usize = 4096;
+
+/// A source file found by discovery and the configured source root it belongs to
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveredFile {
+    pub shared_path: PathBuf,
+    pub file: PathBuf,
+    /// Where the file's content is read from instead, when its source was snapshotted
+    pub snapshot: Option<PathBuf>,
+}
+
+/// How many files discovery has found, and whether it has finished looking
+#[derive(Debug, Default)]
+pub struct DiscoveryCount {
+    found: AtomicU64,
+    complete: AtomicBool,
+}
+
+impl DiscoveryCount {
+    /// Files found so far; only grows, and is final once discovery is complete
+    pub fn found(&self) -> u64 {
+        self.found.load(Ordering::Relaxed)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.complete.load(Ordering::Relaxed)
+    }
+}
+
+/// Discovery's end of the stream of files
+pub struct FileSender {
+    sender: SyncSender<Result<DiscoveredFile>>,
+    count: Arc<DiscoveryCount>,
+}
+
+impl FileSender {
+    /// Hand a file to preparation; false once nobody is receiving
+    fn send(&self, file: DiscoveredFile) -> bool {
+        self.count.found.fetch_add(1, Ordering::Relaxed);
+        self.sender.send(Ok(file)).is_ok()
+    }
+
+    /// Tell preparation that discovery failed, so the run stops before copying
+    fn fail(&self, message: String) {
+        let _ = self.sender.send(Err(BackupError::DirectoryRead(message)));
+    }
+}
+
+impl Drop for FileSender {
+    fn drop(&mut self) {
+        self.count.complete.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Files streamed to the preparation phase while discovery is still walking the sources
+pub struct DiscoveredFiles {
+    receiver: Receiver<Result<DiscoveredFile>>,
+    count: Arc<DiscoveryCount>,
+}
+
+impl DiscoveredFiles {
+    /// Files found so far, kept up to date as discovery goes on
+    pub fn count(&self) -> Arc<DiscoveryCount> {
+        Arc::clone(&self.count)
+    }
+}
+
+impl IntoIterator for DiscoveredFiles {
+    type Item = Result<DiscoveredFile>;
+    type IntoIter = mpsc::IntoIter<Result<DiscoveredFile>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.receiver.into_iter()
+    }
+}
+
+/// A bounded channel from discovery to preparation
+pub fn discovery_channel() -> (FileSender, DiscoveredFiles) {
+    let (sender, receiver) = mpsc::sync_channel(DISCOVERY_BUFFER);
+    let count = Arc::new(DiscoveryCount::default());
+    (
+        FileSender {
+            sender,
+            count: Arc::clone(&count),
+        },
+        DiscoveredFiles { receiver, count },
+    )
+}
+
+/// What discovery found across the selected sources
+#[derive(Debug, Default)]
+pub struct Discovery {
+    /// Files sent to preparation
+    pub found: u64,
+    /// Entries passed over as unreadable, for sources with `on_error` set to `skip`
+    pub skipped_entries: Vec<SkippedEntry>,
+}
+
+/// Walk every selected source on `threads` threads, streaming the files that pass the source's
+/// filters and the filter plugins to `sender`
+///
+/// Files in `backlog` that are under a selected source are sent first, and passed over by the
+/// walk. Sources in `snapshots` are walked in their snapshot, but files are still named by their
+/// live path. A directory or file that can't be read fails discovery, unless its source's
+/// `on_error` is `skip`. The stream ends when `sender` is dropped on return.
+pub fn discover_source_files(
+    sources: &[SelectedSource],
+    snapshots: &SourceSnapshots,
+    plugin_filters: &PluginFilters,
+    backlog: &[PathBuf],
+    threads: usize,
+    progress: Option<&ProgressBar>,
+    sender: FileSender,
+) -> Result<Discovery> {
+    info!(
+        "Discovering files in {} source directories...",
+        sources.len()
+    );
+    let now = SystemTime::now();
+    let scans: Vec<SourceScan> = sources
+        .iter()
+        .map(|selected| SourceScan::new(selected, snapshots, now))
+        .collect();
+
+    let skipped_entries = Mutex::new(Vec::new());
+    let mut resumed = HashSet::new();
+    for file in backlog {
+        let Some(scan) = scans
+            .iter()
+            .find(|scan| file.starts_with(&scan.selected.scan_root))
+        else {
+            continue;
+        };
+        let walked = match &scan.snapshot {
+            Some((_, view)) => view.to_snapshot(file),
+            None => Some(file.clone()),
+        };
+        // Files deleted since are simply gone from the backlog
+        let Some(walked) = walked.filter(|walked| walked.is_file()) else {
+            continue;
+        };
+        if !scan.offer(walked, plugin_filters, &resumed, &sender) {
+            break;
+        }
+        resumed.insert(file.clone());
+    }
+
+    for scan in &scans {
+        let selected = scan.selected;
+        if let Some(pb) = progress {
+            pb.set_message(format!("Scanning: {}", selected.scan_root));
+        }
+
+        let source = &selected.source;
+        let _source_span = info_span!("source", source = %source.display_name()).entered();
+        let found_before = sender.count.found();
+        let scan_root = scan
+            .snapshot
+            .as_ref()
+            .map_or(&selected.scan_root, |(root, _)| root);
+
+        let skip_error = |path: Option<PathBuf>, error: String| {
+            let path = path.map(|path| match &scan.snapshot {
+                Some((_, view)) => view.to_live(&path),
+                None => path,
+            });
+            warn!(
+                "Skipped unreadable entry {:?} in {}: {}",
+                path, selected.scan_root, error
+            );
+            skipped_entries.lock().unwrap().push(SkippedEntry {
+                source: source.display_name().to_string(),
+                path: path.map(|path| path.to_string_lossy().to_string()),
+                error,
+            });
+        };
+        let walked = walk_files_in_path(
+            scan_root,
+            &source.skip_dirs,
+            &selected.max_depth,
+            &source.ignore_files,
+            &source.exclude_attributes,
+            threads,
+            match source.on_error {
+                WalkErrorPolicy::Skip => Some(&skip_error),
+                WalkErrorPolicy::Abort => None,
+            },
+            &|file| scan.offer(file, plugin_filters, &resumed, &sender),
+        );
+        if let Err(e) = walked {
+            sender.fail(format!("Discovery failed in {}", selected.scan_root));
+            return Err(e);
+        }
+
+        let excluded = scan.excluded_by_filter.load(Ordering::Relaxed);
+        if excluded > 0 {
+            info!(
+                "Excluded {} files in {} by size, age or extension",
+                excluded, selected.scan_root
+            );
+        }
+        let excluded = scan.excluded_by_type.load(Ordering::Relaxed);
+        if excluded > 0 {
+            info!(
+                "Excluded {} files in {} by content type",
+                excluded, selected.scan_root
+            );
+        }
+
+        let file_count = sender.count.found() - found_before;
+        if let Some(pb) = progress {
+            pb.set_message(format!(
+                "Found {} files in {}",
+                file_count, selected.scan_root
+            ));
+        }
+    }
+
+    let found = sender.count.found();
+    info!("Found {} files across {} directories", found, sources.len());
+    let skipped_entries = skipped_entries.into_inner().unwrap();
+    if !skipped_entries.is_empty() {
+        warn!(
+            "Skipped {} unreadable director(ies) or file(s)",
+            skipped_entries.len()
+        );
+    }
+    Ok(Discovery {
+        found,
+        skipped_entries,
+    })
+}
+
+/// What deciding on the files of one selected source needs
+struct SourceScan<'a> {
+    selected: &'a SelectedSource,
+    shared_path: PathBuf,
+    file_filter: Option<FileFilter>,
+    /// The scan root inside the source's snapshot, and how its paths map to live ones
+    snapshot: Option<(String, SnapshotView)>,
+    excluded_by_filter: AtomicU64,
+    excluded_by_type: AtomicU64,
+}
+
+impl<'a> SourceScan<'a> {
+    fn new(selected: &'a SelectedSource, snapshots: &SourceSnapshots, now: SystemTime) -> Self {
+        let source = &selected.source;
+        SourceScan {
+            selected,
+            shared_path: PathBuf::from(&source.parent_directory),
+            file_filter: FileFilter::for_source(source, now),
+            snapshot: snapshots.view(source).and_then(|view| {
+                let scan_root = view.to_snapshot(Path::new(&selected.scan_root))?;
+                Some((scan_root.to_string_lossy().to_string(), view))
+            }),
+            excluded_by_filter: AtomicU64::new(0),
+            excluded_by_type: AtomicU64::new(0),
+        }
+    }
+
+    /// Send `file`, as found where the source is walked, unless it is filtered out, in
+    /// `passed_over` or the source's backup marker; false once nobody is receiving
+    fn offer(
+        &self,
+        file: PathBuf,
+        plugin_filters: &PluginFilters,
+        passed_over: &HashSet<PathBuf>,
+        sender: &FileSender,
+    ) -> bool {
+        let source = &self.selected.source;
+        if self.file_filter.as_ref().is_some_and(|f| !f.accepts(&file)) {
+            self.excluded_by_filter.fetch_add(1, Ordering::Relaxed);
+            return true;
+        }
+        if is_excluded_content_type(&file, &source.exclude_content_types) {
+            self.excluded_by_type.fetch_add(1, Ordering::Relaxed);
+            return true;
+        }
+        let (file, snapshot) = match &self.snapshot {
+            Some((_, view)) => (view.to_live(&file), Some(file)),
+            None => (file, None),
+        };
+        if passed_over.contains(&file)
+            || file == self.shared_path.join(BACKUP_MARKER_FILE)
+            || !plugin_filters.keep(source.display_name(), &file)
+        {
+            return true;
+        }
+        sender.send(DiscoveredFile {
+            shared_path: self.shared_path.clone(),
+            file,
+            snapshot,
+        })
+    }
+}
+
+/// Detect top-level directories that appeared under sources marked with `auto_discover_children`
+///
+/// The first run against a source records its existing children as the baseline without
+/// reporting them, so only directories created afterwards are flagged as new.
+pub fn detect_new_directories(
+    backup_sources: &[BackupSource],
+    dry_run_mode: DryRunMode,
+) -> Result<Vec<DiscoveredDirectory>> {
+    let mut discovered = Vec::new();
+
+    for source in backup_sources.iter().filter(|s| s.auto_discover_children) {
+        let known = select_known_directories(&source.parent_directory)?;
+        let current: Vec<String> = get_top_level_dirs(&source.parent_directory, &source.skip_dirs)?
+            .into_iter()
+            .filter(|name| {
+                let path = Path::new(&source.parent_directory).join(name);
+                !source
+                    .exclude_attributes
+                    .iter()
+                    .any(|attribute| has_file_attribute(&path, *attribute))
+            })
+            .collect();
+        let is_baseline = known.is_empty();
+
+        for directory_name in current {
+            if known.contains(&directory_name) {
+                continue;
+            }
+
+            if is_baseline {
+                info!(
+                    "Recording existing directory for {}: {}",
+                    source.parent_directory, directory_name
+                );
+            } else {
+                warn!(
+                    "New directory discovered in {}: {} (will be backed up)",
+                    source.parent_directory, directory_name
+                );
+                discovered.push(DiscoveredDirectory {
+                    parent_directory: source.parent_directory.clone(),
+                    directory_name: directory_name.clone(),
+                });
+            }
+
+            if dry_run_mode.should_update_database() {
+                insert_known_directory(&source.parent_directory, &directory_name)?;
+            }
+        }
+    }
+
+    Ok(discovered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::config::{Config, VolumeSnapshot};
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_discovered_files_are_filtered_and_streamed() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("nested")).unwrap();
+        fs::write(dir.path().join("a.txt"), "a").unwrap();
+        fs::write(dir.path().join("nested").join("b.txt"), "b").unwrap();
+        fs::write(dir.path().join("c.log"), "c").unwrap();
+        let root = dir.path().to_str().unwrap().to_string();
+        let sources = vec![SelectedSource {
+            source: BackupSource {
+                parent_directory: root.clone(),
+                extensions: vec!["txt".to_string()],
+                ..Default::default()
+            },
+            scan_root: root.clone(),
+            max_depth: None,
+        }];
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "database_file": "",
+            "backup_sources": [{"parent_directory": root}],
+            "backup_destinations": ["/mnt/nas"]
+        }))
+        .unwrap();
+
+        let (sender, discovered) = discovery_channel();
+        let count = discovered.count();
+        let total = discover_source_files(
+            &sources,
+            &SourceSnapshots::default(),
+            &PluginFilters::start(&config),
+            &[],
+            2,
+            None,
+            sender,
+        )
+        .unwrap();
+
+        let mut files: Vec<PathBuf> = discovered
+            .into_iter()
+            .map(|file| file.unwrap().file)
+            .collect();
+        files.sort();
+        assert_eq!(total.found, 2);
+        assert_eq!(count.found(), 2);
+        assert!(count.is_complete());
+        assert_eq!(
+            files,
+            vec![
+                dir.path().join("a.txt"),
+                dir.path().join("nested").join("b.txt")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_snapshotted_sources_are_walked_in_their_snapshot() {
+        let live = TempDir::new().unwrap();
+        let snapshot = TempDir::new().unwrap();
+        fs::write(live.path().join("a.txt"), "a").unwrap();
+        fs::create_dir(snapshot.path().join("nested")).unwrap();
+        fs::write(snapshot.path().join("a.txt"), "a").unwrap();
+        fs::write(snapshot.path().join("nested").join("b.txt"), "b").unwrap();
+        let root = live.path().to_str().unwrap().to_string();
+        let sources = vec![SelectedSource {
+            source: BackupSource {
+                parent_directory: root.clone(),
+                snapshot: Some(VolumeSnapshot::Btrfs),
+                ..Default::default()
+            },
+            scan_root: live.path().join("nested").to_str().unwrap().to_string(),
+            max_depth: None,
+        }];
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "database_file": "",
+            "backup_sources": [{"parent_directory": root}],
+            "backup_destinations": ["/mnt/nas"]
+        }))
+        .unwrap();
+        let snapshots =
+            SourceSnapshots::with_view(VolumeSnapshot::Btrfs, live.path(), snapshot.path());
+
+        let (sender, discovered) = discovery_channel();
+        discover_source_files(
+            &sources,
+            &snapshots,
+            &PluginFilters::start(&config),
+            &[],
+            2,
+            None,
+            sender,
+        )
+        .unwrap();
+
+        let files: Vec<DiscoveredFile> = discovered.into_iter().map(Result::unwrap).collect();
+        assert_eq!(
+            files,
+            vec![DiscoveredFile {
+                shared_path: live.path().to_path_buf(),
+                file: live.path().join("nested").join("b.txt"),
+                snapshot: Some(snapshot.path().join("nested").join("b.txt")),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_backlog_files_are_sent_first_and_only_once() {
+        let dir = TempDir::new().unwrap();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(dir.path().join(name), name).unwrap();
+        }
+        let root = dir.path().to_str().unwrap().to_string();
+        let sources = vec![SelectedSource {
+            source: BackupSource {
+                parent_directory: root.clone(),
+                ..Default::default()
+            },
+            scan_root: root.clone(),
+            max_depth: None,
+        }];
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "database_file": "",
+            "backup_sources": [{"parent_directory": root}],
+            "backup_destinations": ["/mnt/nas"]
+        }))
+        .unwrap();
+        // Backlog files that are gone, or outside the selected sources, are passed over
+        let backlog = vec![
+            dir.path().join("c.txt"),
+            dir.path().join("deleted.txt"),
+            PathBuf::from("/elsewhere/d.txt"),
+        ];
+
+        let (sender, discovered) = discovery_channel();
+        let total = discover_source_files(
+            &sources,
+            &SourceSnapshots::default(),
+            &PluginFilters::start(&config),
+            &backlog,
+            1,
+            None,
+            sender,
+        )
+        .unwrap();
+
+        let files: Vec<PathBuf> = discovered
+            .into_iter()
+            .map(|file| file.unwrap().file)
+            .collect();
+        assert_eq!(total.found, 3);
+        assert_eq!(files[0], dir.path().join("c.txt"));
+        assert_eq!(
+            files
+                .iter()
+                .filter(|f| **f == dir.path().join("c.txt"))
+                .count(),
+            1
+        );
+    }
+}
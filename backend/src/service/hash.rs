@@ -1,47 +1,159 @@
+use crate::models::config::HashLimit;
 use crate::models::error::{BackupError, Result};
+use crate::utils::directory::long_path;
 use blake2::{Blake2b512, Digest};
 use std::fs;
-use std::io::{BufReader, Read};
-use std::path::PathBuf;
-
-pub fn hash_file(file: &PathBuf, max_mebibytes_bytes: &usize) -> Result<String> {
-    let max_bytes = max_mebibytes_bytes * 1048576;
-    let reader = BufReader::new(
-        fs::File::open(file).map_err(|cause| BackupError::HashError {
-            path: file.clone(),
-            cause,
-        })?,
-    );
-
-    hasher(reader, max_bytes).map_err(|cause| BackupError::HashError {
-        path: file.clone(),
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+/// Name of the algorithm behind every recorded hash, as written to catalog exports
+pub const HASH_ALGORITHM: &str = "blake2b-512";
+
+/// Bytes read by `hash_file` since the process started
+static HASHED_BYTES: AtomicU64 = AtomicU64::new(0);
+
+const MEBIBYTE: u64 = 1024 * 1024;
+
+/// Time `hash_file` spent reading and hashing, summed over all threads
+static HASHING_NANOS: AtomicU64 = AtomicU64::new(0);
+
+/// Work done by `hash_file`, for working out hash throughput
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HashTotals {
+    pub bytes: u64,
+    /// Reading and hashing time summed over threads, so rates are per hashing thread
+    pub nanos: u64,
+}
+
+impl HashTotals {
+    /// Totals since the process started
+    pub fn now() -> Self {
+        HashTotals {
+            bytes: HASHED_BYTES.load(Ordering::Relaxed),
+            nanos: HASHING_NANOS.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Work done between `earlier` and these totals
+    pub fn since(self, earlier: HashTotals) -> Self {
+        HashTotals {
+            bytes: self.bytes.saturating_sub(earlier.bytes),
+            nanos: self.nanos.saturating_sub(earlier.nanos),
+        }
+    }
+}
+
+/// Hash the part of `file` that `limit` covers
+pub fn hash_file(file: &Path, limit: HashLimit) -> Result<String> {
+    let hash_error = |cause| BackupError::HashError {
+        path: file.to_path_buf(),
         cause,
-    })
+    };
+    let opened = fs::File::open(long_path(file)).map_err(hash_error)?;
+    let file_size = opened.metadata().map_err(hash_error)?.len();
+
+    hasher(BufReader::new(opened), limit, file_size).map_err(hash_error)
+}
+
+/// Hashes the first `max_mebibytes` MiB of data fed to it in chunks, and with `tail_mebibytes`
+/// the last that many MiB of a longer file and its size, as `hash_file` does
+///
+/// Without a tail, or for a file within `max_mebibytes`, the hash is that of the start alone.
+pub struct SampledHasher {
+    hasher: Blake2b512,
+    /// Bytes from the start that are hashed
+    head_end: u64,
+    /// Where the hashed end of the file starts, if it is sampled
+    tail_start: Option<u64>,
+    file_size: u64,
+    position: u64,
+}
+
+impl SampledHasher {
+    /// Hasher of a file `file_size` bytes long
+    pub fn new(limit: HashLimit, file_size: u64) -> Self {
+        let head_end = limit.max_mebibytes as u64 * MEBIBYTE;
+        let tail_start = (limit.tail_mebibytes > 0 && file_size > head_end).then(|| {
+            file_size
+                .saturating_sub(limit.tail_mebibytes as u64 * MEBIBYTE)
+                .max(head_end)
+        });
+        SampledHasher {
+            hasher: Blake2b512::new(),
+            head_end,
+            tail_start,
+            file_size,
+            position: 0,
+        }
+    }
+
+    /// Hash whatever of `chunk`, the bytes following those fed so far, falls in the sample
+    pub fn update(&mut self, chunk: &[u8]) {
+        let start = self.position;
+        let end = start + chunk.len() as u64;
+        if start < self.head_end {
+            let take = (end.min(self.head_end) - start) as usize;
+            self.hasher.update(&chunk[..take]);
+        }
+        if let Some(tail_start) = self.tail_start.filter(|&tail_start| end > tail_start) {
+            let skip = (tail_start.max(start) - start) as usize;
+            self.hasher.update(&chunk[skip..]);
+        }
+        self.position = end;
+    }
+
+    /// Where the next bytes the sample needs start, or None once it is complete
+    fn next_needed(&self) -> Option<u64> {
+        if self.position < self.head_end {
+            return Some(self.position);
+        }
+        self.tail_start
+            .map(|tail_start| tail_start.max(self.position))
+    }
+
+    pub fn finish(mut self) -> String {
+        if self.tail_start.is_some() {
+            self.hasher.update(self.file_size.to_le_bytes());
+        }
+        hex::encode(self.hasher.finalize())
+    }
 }
 
-fn hasher<R: Read>(mut reader: BufReader<R>, max_bytes: usize) -> std::io::Result<String> {
-    let mut hasher = Blake2b512::new();
+fn hasher<R: Read + Seek>(
+    mut reader: BufReader<R>,
+    limit: HashLimit,
+    file_size: u64,
+) -> std::io::Result<String> {
+    let started = Instant::now();
+    let mut hasher = SampledHasher::new(limit, file_size);
     let mut buffer = [0; 8192];
-    let mut bytes_read = 0;
-    loop {
+    let mut read = 0u64;
+    while let Some(needed) = hasher.next_needed() {
+        // Past the start, reading jumps to the sampled end
+        if needed != hasher.position {
+            reader.seek(SeekFrom::Start(needed))?;
+            hasher.position = needed;
+        }
         let count = reader.read(&mut buffer)?;
         if count == 0 {
             break;
         }
-        bytes_read += count;
         hasher.update(&buffer[..count]);
-        if bytes_read >= max_bytes {
-            break;
-        }
+        read += count as u64;
     }
-    let output = hasher.finalize();
-    Ok(hex::encode(output))
+
+    HASHED_BYTES.fetch_add(read, Ordering::Relaxed);
+    HASHING_NANOS.fetch_add(started.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    Ok(hasher.finish())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::io::Write;
+    use std::path::PathBuf;
     use tempfile::NamedTempFile;
 
     #[test]
@@ -50,7 +162,7 @@ mod tests {
         temp_file.write_all(b"Hello, World!").unwrap();
         temp_file.flush().unwrap();
 
-        let hash = hash_file(&temp_file.path().to_path_buf(), &1).unwrap();
+        let hash = hash_file(temp_file.path(), HashLimit::new(1)).unwrap();
 
         // BLAKE2b512 produces 128 hex characters (64 bytes * 2)
         assert_eq!(hash.len(), 128);
@@ -58,6 +170,67 @@ mod tests {
         assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
     }
 
+    #[test]
+    fn test_hash_totals_count_bytes_read() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(&[7; 20_000]).unwrap();
+        temp_file.flush().unwrap();
+
+        let before = HashTotals::now();
+        hash_file(temp_file.path(), HashLimit::new(1)).unwrap();
+        let done = HashTotals::now().since(before);
+
+        // Other tests may hash at the same time
+        assert!(done.bytes >= 20_000);
+        assert!(done.nanos > 0);
+    }
+
+    #[test]
+    fn test_sampled_hasher_matches_hash_file() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        let data: Vec<u8> = (0..3_500_000u32).map(|i| (i % 241) as u8).collect();
+        temp_file.write_all(&data).unwrap();
+        temp_file.flush().unwrap();
+
+        for limit in [HashLimit::new(1), HashLimit::new(1).with_tail(1)] {
+            let mut sampled = SampledHasher::new(limit, data.len() as u64);
+            for chunk in data.chunks(100_000) {
+                sampled.update(chunk);
+            }
+            assert_eq!(
+                sampled.finish(),
+                hash_file(temp_file.path(), limit).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_tail_sample_finds_changes_near_the_end() {
+        let mut data = vec![0xAB; 3 * 1024 * 1024];
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(&data).unwrap();
+        temp_file.flush().unwrap();
+        let prefix = HashLimit::new(1);
+        let sampled = prefix.with_tail(1);
+        let before = (
+            hash_file(temp_file.path(), prefix).unwrap(),
+            hash_file(temp_file.path(), sampled).unwrap(),
+        );
+        assert_ne!(before.0, before.1);
+
+        *data.last_mut().unwrap() = 0;
+        std::fs::write(temp_file.path(), &data).unwrap();
+        assert_eq!(hash_file(temp_file.path(), prefix).unwrap(), before.0);
+        assert_ne!(hash_file(temp_file.path(), sampled).unwrap(), before.1);
+
+        // A file within the start is hashed whole either way
+        std::fs::write(temp_file.path(), b"small").unwrap();
+        assert_eq!(
+            hash_file(temp_file.path(), prefix).unwrap(),
+            hash_file(temp_file.path(), sampled).unwrap()
+        );
+    }
+
     #[test]
     fn test_hash_large_file_partial_hashing() {
         let mut temp_file = NamedTempFile::new().unwrap();
@@ -67,10 +240,10 @@ mod tests {
         temp_file.flush().unwrap();
 
         // Hash with max 1 MiB limit
-        let hash_1mb = hash_file(&temp_file.path().to_path_buf(), &1).unwrap();
+        let hash_1mb = hash_file(temp_file.path(), HashLimit::new(1)).unwrap();
 
         // Hash the same file with max 2 MiB limit
-        let hash_2mb = hash_file(&temp_file.path().to_path_buf(), &2).unwrap();
+        let hash_2mb = hash_file(temp_file.path(), HashLimit::new(2)).unwrap();
 
         // These should be different since we're hashing different amounts
         assert_ne!(hash_1mb, hash_2mb);
@@ -87,8 +260,8 @@ mod tests {
         temp_file2.write_all(content).unwrap();
         temp_file2.flush().unwrap();
 
-        let hash1 = hash_file(&temp_file1.path().to_path_buf(), &1).unwrap();
-        let hash2 = hash_file(&temp_file2.path().to_path_buf(), &1).unwrap();
+        let hash1 = hash_file(temp_file1.path(), HashLimit::new(1)).unwrap();
+        let hash2 = hash_file(temp_file2.path(), HashLimit::new(1)).unwrap();
 
         assert_eq!(hash1, hash2);
     }
@@ -103,8 +276,8 @@ mod tests {
         temp_file2.write_all(b"Content B").unwrap();
         temp_file2.flush().unwrap();
 
-        let hash1 = hash_file(&temp_file1.path().to_path_buf(), &1).unwrap();
-        let hash2 = hash_file(&temp_file2.path().to_path_buf(), &1).unwrap();
+        let hash1 = hash_file(temp_file1.path(), HashLimit::new(1)).unwrap();
+        let hash2 = hash_file(temp_file2.path(), HashLimit::new(1)).unwrap();
 
         assert_ne!(hash1, hash2);
     }
@@ -114,7 +287,7 @@ mod tests {
         let temp_file = NamedTempFile::new().unwrap();
         // Don't write anything - empty file
 
-        let hash = hash_file(&temp_file.path().to_path_buf(), &1).unwrap();
+        let hash = hash_file(temp_file.path(), HashLimit::new(1)).unwrap();
 
         // Should still produce a valid hash
         assert_eq!(hash.len(), 128);
@@ -129,7 +302,7 @@ mod tests {
     fn test_error_on_nonexistent_file() {
         let nonexistent_path = PathBuf::from("/this/path/does/not/exist/file.txt");
 
-        let result = hash_file(&nonexistent_path, &1);
+        let result = hash_file(&nonexistent_path, HashLimit::new(1));
 
         assert!(result.is_err());
         match result {
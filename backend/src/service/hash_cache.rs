@@ -0,0 +1,199 @@
+use crate::models::config::{Config, HashLimit};
+use crate::models::dry_run_mode::DryRunMode;
+use crate::models::error::{BackupError, Result};
+use crate::repo::sqlite::{insert_cached_hash, prune_hash_cache, select_cached_hash};
+use crate::service::hash::hash_file;
+use crate::utils::directory::long_path;
+use log::{debug, info, warn};
+use std::fs::{self, Metadata};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+/// Days a cached hash is kept after it was taken
+const HASH_CACHE_MAX_AGE_DAYS: u64 = 365;
+
+/// What identifies a file's content to the cache: who the file is, its size and when it changed
+#[derive(Debug, Clone, PartialEq)]
+struct CacheKey {
+    file: String,
+    size: u64,
+    modified_nanos: i64,
+}
+
+impl CacheKey {
+    fn of(path: &Path, metadata: &Metadata) -> Option<Self> {
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(CacheKey {
+            file: file_identity(path, metadata),
+            size: metadata.len(),
+            modified_nanos: i64::try_from(modified.as_nanos()).ok()?,
+        })
+    }
+}
+
+/// The device and inode of a file, which stay the same when it is renamed or its catalog record
+/// is lost
+#[cfg(unix)]
+fn file_identity(_path: &Path, metadata: &Metadata) -> String {
+    use std::os::unix::fs::MetadataExt;
+    format!("{}:{}", metadata.dev(), metadata.ino())
+}
+
+/// The file's absolute path, as file IDs aren't available from `std` on other platforms
+#[cfg(not(unix))]
+fn file_identity(path: &Path, _metadata: &Metadata) -> String {
+    std::path::absolute(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Hash `file` like `hash_file`, reusing the hash an earlier run took if the file is unchanged
+///
+/// Hashes taken here are cached for later runs unless the run leaves the database alone. The
+/// cache is only a shortcut, so failing to read or write it just means hashing the file.
+pub fn hash_file_cached(
+    file: &Path,
+    limit: HashLimit,
+    config: &Config,
+    dry_run_mode: DryRunMode,
+) -> Result<String> {
+    if !config.hash_cache {
+        return hash_file(file, limit);
+    }
+    let metadata = fs::metadata(long_path(file)).map_err(|cause| BackupError::MetadataError {
+        path: file.to_path_buf(),
+        cause,
+    })?;
+    let Some(key) = CacheKey::of(file, &metadata) else {
+        return hash_file(file, limit);
+    };
+
+    match select_cached_hash(&key.file, key.size, key.modified_nanos, limit) {
+        Ok(Some(hash)) => {
+            debug!("Reusing cached hash of {:?}", file);
+            return Ok(hash);
+        }
+        Ok(None) => {}
+        Err(e) => debug!("Hash cache lookup failed for {:?}: {}", file, e),
+    }
+
+    let hash = hash_file(file, limit)?;
+    if dry_run_mode.should_update_database() {
+        if let Err(e) = insert_cached_hash(&key.file, key.size, key.modified_nanos, limit, &hash) {
+            debug!("Failed to cache the hash of {:?}: {}", file, e);
+        }
+    }
+    Ok(hash)
+}
+
+/// Drop cached hashes too old to be worth keeping
+pub fn expire_cached_hashes() {
+    match prune_hash_cache(HASH_CACHE_MAX_AGE_DAYS) {
+        Ok(0) => {}
+        Ok(deleted) => info!("Expired {} cached hashes", deleted),
+        Err(e) => warn!("Failed to expire cached hashes: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::sqlite::{set_db_pool, setup_database};
+    use serial_test::serial;
+    use std::time::{Duration, SystemTime};
+    use tempfile::TempDir;
+
+    fn setup_test_db() -> TempDir {
+        let database = TempDir::new().unwrap();
+        set_db_pool(database.path().join("backup.db").to_str().unwrap()).unwrap();
+        setup_database().unwrap();
+        database
+    }
+
+    fn config(hash_cache: bool) -> Config {
+        let mut config: Config = serde_json::from_value(serde_json::json!({
+            "database_file": "",
+            "backup_sources": [],
+            "backup_destinations": []
+        }))
+        .unwrap();
+        config.hash_cache = hash_cache;
+        config
+    }
+
+    /// Swap a file's content for different content of the same size and modification time, which
+    /// the cache can't tell apart
+    fn rewrite_keeping_metadata(path: &Path, content: &str) {
+        let modified = fs::metadata(path).unwrap().modified().unwrap();
+        fs::write(path, content).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+    }
+
+    #[test]
+    #[serial]
+    fn test_unchanged_files_reuse_their_cached_hash() {
+        let _db = setup_test_db();
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("video.mkv");
+        fs::write(&file, "first").unwrap();
+
+        let hash =
+            hash_file_cached(&file, HashLimit::new(1), &config(true), DryRunMode::None).unwrap();
+        assert_eq!(hash, hash_file(&file, HashLimit::new(1)).unwrap());
+
+        rewrite_keeping_metadata(&file, "other");
+        assert_eq!(
+            hash_file_cached(&file, HashLimit::new(1), &config(true), DryRunMode::None).unwrap(),
+            hash
+        );
+        // A different hash limit or tail, or the cache turned off, hashes the file again
+        assert_ne!(
+            hash_file_cached(&file, HashLimit::new(2), &config(true), DryRunMode::None).unwrap(),
+            hash
+        );
+        let tail = HashLimit::new(1).with_tail(1);
+        assert_ne!(
+            hash_file_cached(&file, tail, &config(true), DryRunMode::None).unwrap(),
+            hash
+        );
+        assert_ne!(
+            hash_file_cached(&file, HashLimit::new(1), &config(false), DryRunMode::None).unwrap(),
+            hash
+        );
+
+        // A newer modification time is a different version of the file
+        fs::File::options()
+            .write(true)
+            .open(&file)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        assert_ne!(
+            hash_file_cached(&file, HashLimit::new(1), &config(true), DryRunMode::None).unwrap(),
+            hash
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_dry_runs_leave_the_cache_alone() {
+        let _db = setup_test_db();
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("video.mkv");
+        fs::write(&file, "first").unwrap();
+
+        let hash =
+            hash_file_cached(&file, HashLimit::new(1), &config(true), DryRunMode::Full).unwrap();
+        rewrite_keeping_metadata(&file, "other");
+        assert_ne!(
+            hash_file_cached(&file, HashLimit::new(1), &config(true), DryRunMode::Full).unwrap(),
+            hash
+        );
+    }
+}
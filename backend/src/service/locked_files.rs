@@ -0,0 +1,185 @@
+use crate::models::config::{LockedFileAction, LockedFiles};
+use crate::models::error::{BackupError, Result};
+use crate::models::run_report::LockedFile;
+use log::{debug, warn};
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// Retries reads of locked source files during a run and collects the ones that stayed locked
+pub struct LockedFileTracker {
+    policy: LockedFiles,
+    found: Mutex<Vec<LockedFile>>,
+}
+
+impl LockedFileTracker {
+    pub fn new(policy: &LockedFiles) -> Self {
+        LockedFileTracker {
+            policy: policy.clone(),
+            found: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Run `attempt` on `file`, trying again while it fails because the file is locked
+    ///
+    /// A file still locked after the configured retries is recorded and reported as
+    /// `BackupError::FileLocked`; other errors are returned as they are.
+    pub fn retry<T>(
+        &self,
+        source: &str,
+        file: &Path,
+        mut attempt: impl FnMut() -> Result<T>,
+    ) -> Result<T> {
+        let mut attempts = 1;
+        loop {
+            let e = match attempt() {
+                Err(e) if e.is_locked_file() => e,
+                result => return result,
+            };
+            if attempts <= self.policy.retries {
+                debug!(
+                    "{:?} is locked, trying again in {}ms (attempt {})",
+                    file, self.policy.retry_delay_ms, attempts
+                );
+                thread::sleep(Duration::from_millis(self.policy.retry_delay_ms));
+                attempts += 1;
+                continue;
+            }
+
+            let consequence = match self.policy.action {
+                LockedFileAction::Skip => "skipping it",
+                LockedFileAction::Fail => "failing the run",
+            };
+            warn!(
+                "{:?} is locked by another program after {} attempt(s), {}: {}",
+                file, attempts, consequence, e
+            );
+            self.found.lock().unwrap().push(LockedFile {
+                source: source.to_string(),
+                file: file.to_string_lossy().to_string(),
+                attempts,
+                error: e.to_string(),
+            });
+            return Err(BackupError::FileLocked {
+                path: file.to_path_buf(),
+                attempts,
+                reason: e.to_string(),
+            });
+        }
+    }
+
+    /// Whether a file that stays locked fails the run
+    pub fn fails_run(&self) -> bool {
+        self.policy.action == LockedFileAction::Fail
+    }
+
+    /// The first locked file, as the error failing the run, if the policy says it does
+    pub fn failure(&self) -> Option<BackupError> {
+        if !self.fails_run() {
+            return None;
+        }
+        let found = self.found.lock().unwrap();
+        found.first().map(|locked| BackupError::FileLocked {
+            path: locked.file.clone().into(),
+            attempts: locked.attempts,
+            reason: locked.error.clone(),
+        })
+    }
+
+    /// Every file that stayed locked, ordered by source and path
+    pub fn into_report(self) -> Vec<LockedFile> {
+        let mut found = self.found.into_inner().unwrap();
+        found.sort_by(|a, b| (&a.source, &a.file).cmp(&(&b.source, &b.file)));
+        found.dedup_by(|a, b| a.source == b.source && a.file == b.file);
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    fn locked() -> BackupError {
+        #[cfg(windows)]
+        let cause = io::Error::from_raw_os_error(32);
+        #[cfg(not(windows))]
+        let cause = io::Error::from(io::ErrorKind::ResourceBusy);
+        BackupError::HashError {
+            path: "/src/mail.pst".into(),
+            cause,
+        }
+    }
+
+    fn policy(retries: u32, action: LockedFileAction) -> LockedFiles {
+        LockedFiles {
+            retries,
+            retry_delay_ms: 0,
+            action,
+        }
+    }
+
+    #[test]
+    fn test_retry_until_the_file_is_released() {
+        let tracker = LockedFileTracker::new(&policy(2, LockedFileAction::Skip));
+        let mut calls = 0;
+        let result = tracker.retry("mail", Path::new("/src/mail.pst"), || {
+            calls += 1;
+            if calls < 3 {
+                Err(locked())
+            } else {
+                Ok("hash")
+            }
+        });
+        assert_eq!(result.unwrap(), "hash");
+        assert_eq!(calls, 3);
+        assert!(tracker.into_report().is_empty());
+    }
+
+    #[test]
+    fn test_files_locked_through_every_retry_are_reported() {
+        let tracker = LockedFileTracker::new(&policy(1, LockedFileAction::Skip));
+        let mut calls = 0;
+        let result: Result<()> = tracker.retry("mail", Path::new("/src/mail.pst"), || {
+            calls += 1;
+            Err(locked())
+        });
+        assert!(matches!(
+            result,
+            Err(BackupError::FileLocked { attempts: 2, .. })
+        ));
+        assert_eq!(calls, 2);
+        assert!(tracker.failure().is_none());
+
+        let report = tracker.into_report();
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].file, "/src/mail.pst");
+        assert_eq!(report[0].attempts, 2);
+    }
+
+    #[test]
+    fn test_fail_policy_fails_the_run() {
+        let tracker = LockedFileTracker::new(&policy(0, LockedFileAction::Fail));
+        let _ = tracker.retry("mail", Path::new("/src/mail.pst"), || -> Result<()> {
+            Err(locked())
+        });
+        assert!(matches!(
+            tracker.failure(),
+            Some(BackupError::FileLocked { attempts: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_other_errors_are_not_retried() {
+        let tracker = LockedFileTracker::new(&policy(3, LockedFileAction::Fail));
+        let mut calls = 0;
+        let result: Result<()> = tracker.retry("mail", Path::new("/src/mail.pst"), || {
+            calls += 1;
+            Err(BackupError::Io(io::Error::from(io::ErrorKind::NotFound)))
+        });
+        assert!(matches!(result, Err(BackupError::Io(_))));
+        assert_eq!(calls, 1);
+        assert!(tracker.failure().is_none());
+    }
+}
@@ -0,0 +1,46 @@
+use crate::models::config::Config;
+use crate::models::error::Result;
+use crate::models::manifest::{HashManifest, ManifestEntry};
+use crate::repo::catalog::select_sources;
+use crate::repo::sqlite::select_tombstones;
+use crate::service::scrub::hash_limit_of;
+use std::fs;
+use std::path::Path;
+
+/// Manifest of every catalogued source file that has at least one recorded backup, with the
+/// tombstones of those found deleted
+pub fn build_manifest(config: &Config) -> Result<HashManifest> {
+    let files = select_sources(None)?
+        .into_iter()
+        .filter(|(_, backups)| *backups > 0)
+        .map(|(source, _)| {
+            let path = Path::new(&source.file_path).join(&source.file_name);
+            let hash_limit = hash_limit_of(config, &source.file_path);
+            ManifestEntry {
+                hash_mebibytes: hash_limit.max_mebibytes,
+                hash_tail_mebibytes: hash_limit.tail_mebibytes,
+                path: path.to_string_lossy().to_string(),
+                hash: source.hash,
+                file_size: source.file_size,
+            }
+        })
+        .collect();
+
+    Ok(HashManifest {
+        host: host_name(),
+        generated_at: chrono::Utc::now().timestamp(),
+        files,
+        deleted: select_tombstones(false)?,
+    })
+}
+
+/// Name of this machine from the environment or `/etc/hostname`
+fn host_name() -> String {
+    ["HOSTNAME", "COMPUTERNAME"]
+        .iter()
+        .find_map(|var| std::env::var(var).ok())
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
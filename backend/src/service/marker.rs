@@ -0,0 +1,97 @@
+use crate::models::backup_marker::{BackupMarker, BACKUP_MARKER_FILE};
+use crate::models::error::{BackupError, Result};
+use chrono::{DateTime, Utc};
+use log::{debug, warn};
+use std::fs;
+use std::path::Path;
+
+/// Write the backup marker into a source root
+pub fn write_backup_marker(source_root: &str, run_id: &str, destinations: &[String]) -> Result<()> {
+    let marker = BackupMarker {
+        completed_at: Utc::now().to_rfc3339(),
+        run_id: run_id.to_string(),
+        destinations: destinations.to_vec(),
+    };
+
+    let marker_path = Path::new(source_root).join(BACKUP_MARKER_FILE);
+    let contents = serde_json::to_string_pretty(&marker).map_err(|e| {
+        BackupError::DirectoryRead(format!("Failed to serialize backup marker: {}", e))
+    })?;
+    fs::write(&marker_path, contents)?;
+
+    debug!("Wrote backup marker: {:?}", marker_path);
+    Ok(())
+}
+
+/// Read the backup marker from a source root, if one exists and can be parsed
+pub fn read_backup_marker(source_root: &str) -> Option<BackupMarker> {
+    let marker_path = Path::new(source_root).join(BACKUP_MARKER_FILE);
+    let contents = fs::read_to_string(&marker_path).ok()?;
+    match serde_json::from_str(&contents) {
+        Ok(marker) => Some(marker),
+        Err(e) => {
+            warn!("Ignoring unreadable backup marker {:?}: {}", marker_path, e);
+            None
+        }
+    }
+}
+
+/// Warn if a source's last recorded backup is older than the allowed age
+///
+/// Returns the age of the marker in hours when it exceeds the policy.
+pub fn check_marker_age(source_root: &str, max_age_hours: u64) -> Option<i64> {
+    let marker = read_backup_marker(source_root)?;
+    let completed_at = DateTime::parse_from_rfc3339(&marker.completed_at).ok()?;
+    let age_hours = Utc::now()
+        .signed_duration_since(completed_at.with_timezone(&Utc))
+        .num_hours();
+
+    if age_hours > max_age_hours as i64 {
+        warn!(
+            "Source {} was last backed up {} hours ago (policy: {} hours)",
+            source_root, age_hours, max_age_hours
+        );
+        Some(age_hours)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_write_and_read_backup_marker() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().to_str().unwrap();
+
+        write_backup_marker(root, "run-1", &["/mnt/nas".to_string()]).unwrap();
+
+        let marker = read_backup_marker(root).unwrap();
+        assert_eq!(marker.run_id, "run-1");
+        assert_eq!(marker.destinations, vec!["/mnt/nas"]);
+        assert!(check_marker_age(root, 1).is_none());
+    }
+
+    #[test]
+    fn test_check_marker_age_flags_stale_marker() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().to_str().unwrap();
+
+        let stale = BackupMarker {
+            completed_at: (Utc::now() - chrono::Duration::hours(48)).to_rfc3339(),
+            run_id: "old-run".to_string(),
+            destinations: vec![],
+        };
+        fs::write(
+            temp_dir.path().join(BACKUP_MARKER_FILE),
+            serde_json::to_string(&stale).unwrap(),
+        )
+        .unwrap();
+
+        let age = check_marker_age(root, 24).unwrap();
+        assert!(age >= 47);
+    }
+}
@@ -0,0 +1,151 @@
+use crate::api_state::AppState;
+use crate::models::config::{Config, MassChangeGuard};
+use crate::models::dry_run_mode::DryRunMode;
+use crate::models::error::{BackupError, Result};
+use crate::models::prepped_backup::PreppedBackup;
+use crate::models::run_diff::FileAction;
+use crate::models::run_report::MassChange;
+use crate::models::run_selection::{RunSelection, SelectedSource};
+use crate::repo::sqlite::{count_files_gone_since, select_last_file_action_run};
+use crate::service::audit::record_audit_event;
+use log::warn;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Compares a run's prepared files with the last run's before anything is copied, holding the
+/// copies back when more changed than `mass_change_guard` allows
+pub struct MassChangeCheck {
+    guard: Option<MassChangeGuard>,
+    /// Names of the sources the run backs up
+    sources: HashSet<String>,
+    /// Given `--confirm-mass-change`, so the run goes ahead whatever changed
+    confirmed: bool,
+    /// Prepared files changed since they were last backed up
+    changed: AtomicU64,
+}
+
+impl MassChangeCheck {
+    /// The check for a run of `selected`; off for dry runs, which read no records, and for runs
+    /// of only some paths of their sources, which would find most files gone
+    pub fn new(
+        config: &Config,
+        selection: &RunSelection,
+        selected: &[SelectedSource],
+        dry_run_mode: DryRunMode,
+        confirmed: bool,
+    ) -> Self {
+        let checked = dry_run_mode.should_update_database() && !selection.limits_paths();
+        MassChangeCheck {
+            guard: config.mass_change_guard.clone().filter(|_| checked),
+            sources: selected
+                .iter()
+                .map(|s| s.source.display_name().to_string())
+                .collect(),
+            confirmed,
+            changed: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether the run has to prepare every file before copying any
+    pub fn holds_files(&self) -> bool {
+        self.guard.is_some()
+    }
+
+    /// Count `prepped` in the changes since the last run
+    pub fn observe(&self, prepped: &PreppedBackup) {
+        if self.guard.is_some() && prepped.action() == FileAction::Updated {
+            self.changed.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// The changes since the last run, if they go past the guard's limits
+    ///
+    /// The files gone are counted in the database from the file actions of run `run_id`, so
+    /// every one of them has to have been `recorded`; if any failed to be written, the run isn't
+    /// checked.
+    pub fn check(&self, run_id: &str, recorded: bool) -> Result<Option<MassChange>> {
+        let Some(guard) = &self.guard else {
+            return Ok(None);
+        };
+        if !recorded {
+            warn!("The file actions of this run weren't all recorded, so mass_change_guard can't compare it with the last run");
+            return Ok(None);
+        }
+        let Some(since_run) = select_last_file_action_run(run_id)? else {
+            return Ok(None);
+        };
+
+        let (mut files_before, mut deleted_files) = (0, 0);
+        for source in &self.sources {
+            let (found, gone) = count_files_gone_since(&since_run, run_id, source)?;
+            files_before += found;
+            deleted_files += gone;
+        }
+        let changed_files = self.changed.load(Ordering::Relaxed);
+        let changed_percent = if files_before == 0 {
+            0.0
+        } else {
+            changed_files as f64 / files_before as f64 * 100.0
+        };
+
+        let exceeded = guard
+            .max_changed_percent
+            .is_some_and(|max| changed_percent > max)
+            || guard
+                .max_deleted_files
+                .is_some_and(|max| deleted_files > max);
+        Ok(exceeded.then_some(MassChange {
+            since_run,
+            files_before,
+            changed_files,
+            changed_percent,
+            deleted_files,
+            confirmed: false,
+        }))
+    }
+
+    /// Let the run go ahead with `change` if it was confirmed with `--confirm-mass-change` or,
+    /// for API runs, once an admin confirms it; fails if it isn't confirmed
+    ///
+    /// An API run is paused while it waits, and stopping it fails the run.
+    pub fn hold(&self, mut change: MassChange, state: Option<&AppState>) -> Result<MassChange> {
+        let description = describe_mass_change(&change);
+        warn!("{}", description);
+        if self.confirmed {
+            record_audit_event("mass_change_confirmed", "cli", description)?;
+            change.confirmed = true;
+            return Ok(change);
+        }
+        let Some(st) = state else {
+            return Err(BackupError::MassChange(format!(
+                "{}; nothing was copied. Run again with --confirm-mass-change to back it up",
+                description
+            )));
+        };
+
+        st.notify_message(format!(
+            "{}. The run is paused: confirm it with POST /api/confirm-mass-change or stop it",
+            description
+        ));
+        if !st.await_mass_change_confirmation(change.clone()) {
+            return Err(BackupError::MassChange(format!(
+                "{}; the run was stopped before it was confirmed and copied nothing",
+                description
+            )));
+        }
+        change.confirmed = true;
+        Ok(change)
+    }
+}
+
+/// One line on how much changed since the last run
+pub fn describe_mass_change(change: &MassChange) -> String {
+    format!(
+        "{} of {} file(s) ({:.1}%) changed and {} deleted since run {}",
+        change.changed_files,
+        change.files_before,
+        change.changed_percent,
+        change.deleted_files,
+        change.since_run
+    )
+}
@@ -0,0 +1,315 @@
+use crate::models::api::BackupStatus;
+use crate::models::job::Job;
+use crate::models::run_report::RunReport;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// Files, bytes and errors counted for one scope: everything, a destination or a job
+#[derive(Debug, Clone, Copy, Default)]
+struct CopyCounters {
+    files_copied: u64,
+    bytes_copied: u64,
+    errors: u64,
+}
+
+/// Counters exported at `GET /metrics`, kept from server start
+#[derive(Debug, Default)]
+pub struct Metrics {
+    runs: BTreeMap<&'static str, u64>,
+    copies: CopyCounters,
+    /// Unix timestamp of the last real run that completed without error
+    last_success: Option<i64>,
+    destinations: BTreeMap<String, CopyCounters>,
+    job_runs: BTreeMap<(String, &'static str), u64>,
+    job_copies: BTreeMap<String, CopyCounters>,
+}
+
+impl Metrics {
+    /// Count a finished run; dry runs count as runs but copy nothing
+    pub fn record_run(
+        &mut self,
+        job: Option<&str>,
+        status: &BackupStatus,
+        dry_run: bool,
+        report: Option<&RunReport>,
+        finished_at: i64,
+    ) {
+        *self.runs.entry(status.as_str()).or_default() += 1;
+        if *status == BackupStatus::Completed && !dry_run {
+            self.last_success = Some(finished_at);
+        }
+        if let Some(job) = job {
+            *self
+                .job_runs
+                .entry((job.to_string(), status.as_str()))
+                .or_default() += 1;
+        }
+
+        let Some(report) = report.filter(|_| !dry_run) else {
+            return;
+        };
+        for destination in &report.destinations {
+            let run = CopyCounters {
+                files_copied: destination.files_copied,
+                bytes_copied: destination.bytes_copied,
+                errors: destination.errors,
+            };
+            add(&mut self.copies, run);
+            add(
+                self.destinations
+                    .entry(destination.name.clone())
+                    .or_default(),
+                run,
+            );
+            if let Some(job) = job {
+                add(self.job_copies.entry(job.to_string()).or_default(), run);
+            }
+        }
+    }
+
+    /// Render the counters in the Prometheus text format
+    ///
+    /// Configured destinations and stored jobs are always listed, at zero until they copy
+    /// something, so alerting rules can match them before their first run.
+    pub fn render(&self, status: &BackupStatus, destinations: &[String], jobs: &[Job]) -> String {
+        let mut out = String::new();
+
+        family(
+            &mut out,
+            "backup_running",
+            "gauge",
+            "1 while a backup is running or paused",
+        );
+        let running = matches!(status, BackupStatus::Running | BackupStatus::Paused);
+        sample(&mut out, "backup_running", &[], u64::from(running));
+
+        family(
+            &mut out,
+            "runs_total",
+            "counter",
+            "Backup runs finished, by result",
+        );
+        for (status, count) in &self.runs {
+            sample(&mut out, "runs_total", &[("status", status)], *count);
+        }
+
+        if let Some(last_success) = self.last_success {
+            family(
+                &mut out,
+                "last_success_timestamp_seconds",
+                "gauge",
+                "When the last backup run, other than a dry run, completed without error",
+            );
+            sample(
+                &mut out,
+                "last_success_timestamp_seconds",
+                &[],
+                last_success,
+            );
+        }
+
+        copy_families(&mut out, "", &[(Vec::new(), self.copies)]);
+
+        let mut by_destination = self.destinations.clone();
+        for name in destinations {
+            by_destination.entry(name.clone()).or_default();
+        }
+        let by_destination: Vec<_> = by_destination
+            .into_iter()
+            .map(|(name, counters)| (vec![("dest", name)], counters))
+            .collect();
+        copy_families(&mut out, "destination_", &by_destination);
+
+        let mut by_job = self.job_copies.clone();
+        for job in jobs {
+            by_job.entry(job.definition.name.clone()).or_default();
+        }
+        let by_job: Vec<_> = by_job
+            .into_iter()
+            .map(|(name, counters)| (vec![("job_name", name)], counters))
+            .collect();
+        copy_families(&mut out, "job_", &by_job);
+
+        family(
+            &mut out,
+            "job_runs_total",
+            "counter",
+            "Runs started by each job, by result",
+        );
+        for ((job, status), count) in &self.job_runs {
+            sample(
+                &mut out,
+                "job_runs_total",
+                &[("job_name", job), ("status", status)],
+                *count,
+            );
+        }
+
+        family(
+            &mut out,
+            "job_enabled",
+            "gauge",
+            "1 if the job runs on its schedule",
+        );
+        for job in jobs {
+            let name = &job.definition.name;
+            sample(
+                &mut out,
+                "job_enabled",
+                &[("job_name", name)],
+                u64::from(job.definition.enabled),
+            );
+        }
+
+        family(
+            &mut out,
+            "job_last_success_timestamp_seconds",
+            "gauge",
+            "When a run started by the job last completed without error",
+        );
+        for job in jobs {
+            if let Some(at) = job.last_success_at {
+                let labels = [("job_name", job.definition.name.as_str())];
+                sample(&mut out, "job_last_success_timestamp_seconds", &labels, at);
+            }
+        }
+
+        family(
+            &mut out,
+            "job_next_run_timestamp_seconds",
+            "gauge",
+            "When the job is next scheduled to run",
+        );
+        for job in jobs {
+            if let Some(at) = job.next_run {
+                let labels = [("job_name", job.definition.name.as_str())];
+                sample(&mut out, "job_next_run_timestamp_seconds", &labels, at);
+            }
+        }
+
+        out
+    }
+}
+
+fn add(total: &mut CopyCounters, run: CopyCounters) {
+    total.files_copied += run.files_copied;
+    total.bytes_copied += run.bytes_copied;
+    total.errors += run.errors;
+}
+
+/// The files, bytes and errors counters, one sample per label set
+fn copy_families(out: &mut String, prefix: &str, scopes: &[(Vec<(&str, String)>, CopyCounters)]) {
+    let families = [
+        ("files_copied_total", "Copies written"),
+        ("bytes_copied_total", "Bytes written"),
+        ("copy_errors_total", "Copies that failed"),
+    ];
+
+    for (index, (name, help)) in families.into_iter().enumerate() {
+        let name = format!("{}{}", prefix, name);
+        family(out, &name, "counter", help);
+        for (labels, counters) in scopes {
+            let labels: Vec<(&str, &str)> = labels
+                .iter()
+                .map(|(key, value)| (*key, value.as_str()))
+                .collect();
+            let values = [
+                counters.files_copied,
+                counters.bytes_copied,
+                counters.errors,
+            ];
+            sample(out, &name, &labels, values[index]);
+        }
+    }
+}
+
+fn family(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP rustyhashbackup_{} {}", name, help);
+    let _ = writeln!(out, "# TYPE rustyhashbackup_{} {}", name, kind);
+}
+
+fn sample<V: std::fmt::Display>(out: &mut String, name: &str, labels: &[(&str, &str)], value: V) {
+    let _ = write!(out, "rustyhashbackup_{}", name);
+    if !labels.is_empty() {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(key, value)| format!("{}=\"{}\"", key, escape_label(value)))
+            .collect();
+        let _ = write!(out, "{{{}}}", labels.join(","));
+    }
+    let _ = writeln!(out, " {}", value);
+}
+
+/// Escape a label value as the text format requires
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::run_report::DestinationSummary;
+
+    fn report(destination: &str, bytes: u64) -> RunReport {
+        RunReport {
+            destinations: vec![DestinationSummary {
+                name: destination.to_string(),
+                files_copied: 2,
+                bytes_copied: bytes,
+                physical_bytes_copied: bytes,
+                errors: 1,
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_render_labels_destinations_and_jobs() {
+        let mut metrics = Metrics::default();
+        metrics.record_run(
+            Some("nightly"),
+            &BackupStatus::Completed,
+            false,
+            Some(&report("nas", 300)),
+            1000,
+        );
+        metrics.record_run(
+            None,
+            &BackupStatus::Failed,
+            false,
+            Some(&report("nas", 50)),
+            2000,
+        );
+        // Dry runs don't copy anything
+        metrics.record_run(
+            None,
+            &BackupStatus::Completed,
+            true,
+            Some(&report("nas", 999)),
+            3000,
+        );
+
+        let text = metrics.render(
+            &BackupStatus::Idle,
+            &["nas".to_string(), "usb \"2\"".to_string()],
+            &[],
+        );
+
+        assert!(text.contains("rustyhashbackup_backup_running 0\n"));
+        assert!(text.contains("rustyhashbackup_runs_total{status=\"completed\"} 2\n"));
+        assert!(text.contains("rustyhashbackup_last_success_timestamp_seconds 1000\n"));
+        assert!(text.contains("rustyhashbackup_bytes_copied_total 350\n"));
+        assert!(text.contains("rustyhashbackup_destination_bytes_copied_total{dest=\"nas\"} 350\n"));
+        assert!(text.contains(
+            "rustyhashbackup_destination_bytes_copied_total{dest=\"usb \\\"2\\\"\"} 0\n"
+        ));
+        assert!(text.contains("rustyhashbackup_job_bytes_copied_total{job_name=\"nightly\"} 300\n"));
+        assert!(text.contains(
+            "rustyhashbackup_job_runs_total{job_name=\"nightly\",status=\"completed\"} 1\n"
+        ));
+        assert!(text.contains("# TYPE rustyhashbackup_destination_copy_errors_total counter\n"));
+    }
+}
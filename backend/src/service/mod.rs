@@ -1,2 +1,39 @@
+pub mod audit;
+pub mod auth;
 pub mod backup;
+pub mod catalog_backup;
+pub mod catalog_journal;
+pub mod checksum_manifest;
+pub mod clock_skew;
+pub mod coverage;
+pub mod destination_check;
+pub mod destination_mount;
+pub mod destination_quota;
+pub mod discovery;
 pub mod hash;
+pub mod hash_cache;
+pub mod locked_files;
+pub mod manifest;
+pub mod marker;
+pub mod mass_change;
+pub mod metrics;
+pub mod operation_log;
+pub mod orphans;
+pub mod plugin;
+pub mod post_process;
+pub mod prune;
+pub mod regression;
+pub mod reload;
+pub mod repair;
+pub mod replicate;
+pub mod report_sink;
+pub mod restore;
+pub mod run_budget;
+pub mod run_diff;
+pub mod scrub;
+pub mod sqlite_snapshot;
+pub mod tombstones;
+pub mod trash;
+pub mod verify;
+pub mod volume_snapshot;
+pub mod workspace;
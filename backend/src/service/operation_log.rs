@@ -0,0 +1,114 @@
+use crate::models::config::Config;
+use crate::models::file_operation::FileOperationEntry;
+use log::warn;
+use once_cell::sync::Lazy;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// The `file_operation_log` open for appending, with whether a write to it has failed
+struct OpenLog {
+    path: PathBuf,
+    file: Option<File>,
+    write_failed: bool,
+}
+
+static LOG: Lazy<Mutex<Option<OpenLog>>> = Lazy::new(|| Mutex::new(None));
+
+/// Append `entry` to the configured `file_operation_log`, stamped with the current time
+///
+/// The file is only ever appended to, a line per entry, so it survives the database. Failures
+/// are logged, once for each file, and never fail the operation being recorded.
+pub fn record_file_operation(config: &Config, mut entry: FileOperationEntry) {
+    let Some(path) = config.file_operation_log.as_deref().map(Path::new) else {
+        return;
+    };
+    entry.at = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+    let line = match serde_json::to_string(&entry) {
+        Ok(json) => json + "\n",
+        Err(e) => {
+            warn!("Failed to serialize file operation: {}", e);
+            return;
+        }
+    };
+
+    let mut log = LOG.lock().unwrap();
+    if log.as_ref().is_none_or(|open| open.path != path) {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| warn!("Failed to open file operation log {:?}: {}", path, e))
+            .ok();
+        *log = Some(OpenLog {
+            path: path.to_path_buf(),
+            file,
+            write_failed: false,
+        });
+    }
+    let Some(open) = log.as_mut() else {
+        return;
+    };
+    let Some(file) = &mut open.file else {
+        return;
+    };
+    // One write per line, so lines from several threads never interleave
+    if let Err(e) = file.write_all(line.as_bytes()) {
+        if !open.write_failed {
+            open.write_failed = true;
+            warn!("Failed to write file operation log {:?}: {}", path, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::file_operation::FileOperation;
+    use serial_test::serial;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    #[serial]
+    fn test_operations_are_appended_as_json_lines() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("operations.jsonl");
+        fs::write(&path, "{\"earlier\":true}\n").unwrap();
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "database_file": "",
+            "backup_sources": [],
+            "backup_destinations": [],
+            "file_operation_log": path
+        }))
+        .unwrap();
+
+        record_file_operation(
+            &config,
+            FileOperationEntry {
+                source: Some("/photos/a.jpg".to_string()),
+                backup: Some("/mnt/nas/photos/a.jpg".to_string()),
+                hash: Some("ab12".to_string()),
+                bytes: Some(3),
+                ..FileOperationEntry::new(FileOperation::Copied)
+            },
+        );
+        record_file_operation(
+            &config,
+            FileOperationEntry::new(FileOperation::VerificationFailed),
+        );
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[1]["operation"], "copied");
+        assert_eq!(lines[1]["hash"], "ab12");
+        assert!(lines[1]["at"].as_str().unwrap().ends_with('Z'));
+        assert_eq!(lines[2]["operation"], "verification_failed");
+        assert!(lines[2].get("source").is_none());
+    }
+}
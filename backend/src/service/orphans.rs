@@ -0,0 +1,242 @@
+use crate::models::config::Config;
+use crate::models::error::Result;
+use crate::models::file_operation::{FileOperation, FileOperationEntry};
+use crate::models::orphan::{
+    DestinationOrphans, OrphanAction, OrphanCleanup, OrphanFile, OrphanReport,
+};
+use crate::repo::catalog::select_all_backups;
+use crate::repo::sqlite::format_bytes;
+use crate::service::audit::record_audit_event;
+use crate::service::operation_log::record_file_operation;
+use crate::service::prune::{available_path, destination_files, remove_empty_parents};
+use log::{info, warn};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Directory under each destination root that receives quarantined orphans
+pub const QUARANTINE_DIR: &str = "quarantine";
+
+/// Find destination files that no Backup_Files row points at
+///
+/// OS metadata and the `unclaimed/` and `quarantine/` areas are skipped. `destination` limits
+/// the scan to the destinations it names or points at.
+pub fn find_orphans(config: &Config, destination: Option<&str>) -> Result<OrphanReport> {
+    let recorded: HashSet<PathBuf> = select_all_backups()?
+        .into_iter()
+        .map(|backup| Path::new(&backup.file_path).join(&backup.file_name))
+        .collect();
+
+    let mut report = OrphanReport::default();
+    for configured in config
+        .backup_destinations
+        .iter()
+        .filter(|d| destination.is_none_or(|reference| d.matches(reference)))
+    {
+        let root = Path::new(configured.path());
+        if !root.is_dir() {
+            warn!(
+                "Skipping orphan scan, destination is not a directory: {}",
+                configured.path()
+            );
+            continue;
+        }
+
+        let mut orphans = DestinationOrphans {
+            name: configured.name().unwrap_or(configured.path()).to_string(),
+            path: configured.path().to_string(),
+            ..Default::default()
+        };
+        for path in destination_files(root) {
+            if recorded.contains(&path) {
+                continue;
+            }
+            let Ok(metadata) = fs::metadata(&path) else {
+                continue;
+            };
+            orphans.bytes += metadata.len();
+            orphans.files.push(OrphanFile {
+                path: path.to_string_lossy().to_string(),
+                file_size: metadata.len(),
+                modified_at: metadata
+                    .modified()
+                    .ok()
+                    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                    .map_or(0, |time| time.as_secs() as i64),
+            });
+        }
+        report.files += orphans.files.len() as u64;
+        report.bytes += orphans.bytes;
+        report.destinations.push(orphans);
+    }
+    Ok(report)
+}
+
+/// Quarantine or delete every orphan found now, recording the cleanup in the audit log
+pub fn clean_orphans(
+    config: &Config,
+    destination: Option<&str>,
+    action: OrphanAction,
+    actor: &str,
+) -> Result<OrphanCleanup> {
+    let report = find_orphans(config, destination)?;
+    let mut cleanup = OrphanCleanup {
+        action,
+        files: 0,
+        bytes: 0,
+        failed: 0,
+    };
+
+    for orphans in &report.destinations {
+        let root = Path::new(&orphans.path);
+        for file in &orphans.files {
+            let path = Path::new(&file.path);
+            let result = match action {
+                OrphanAction::Quarantine => quarantine(root, path).map(Some),
+                OrphanAction::Delete => fs::remove_file(path).map(|_| None),
+            };
+            match result {
+                Ok(moved_to) => {
+                    record_file_operation(
+                        config,
+                        FileOperationEntry {
+                            backup: Some(file.path.clone()),
+                            bytes: Some(file.file_size),
+                            detail: Some(moved_to.map_or("orphan".to_string(), |target| {
+                                target.to_string_lossy().to_string()
+                            })),
+                            ..FileOperationEntry::new(match action {
+                                OrphanAction::Quarantine => FileOperation::Quarantined,
+                                OrphanAction::Delete => FileOperation::Deleted,
+                            })
+                        },
+                    );
+                    cleanup.files += 1;
+                    cleanup.bytes += file.file_size;
+                    remove_empty_parents(path, root);
+                }
+                Err(e) => {
+                    warn!("Failed to {} orphan {:?}: {}", action.as_str(), path, e);
+                    cleanup.failed += 1;
+                }
+            }
+        }
+    }
+
+    if cleanup.files > 0 || cleanup.failed > 0 {
+        info!(
+            "Orphan cleanup ({}): {} file(s), {}, {} failed",
+            action.as_str(),
+            cleanup.files,
+            format_bytes(cleanup.bytes),
+            cleanup.failed
+        );
+        record_audit_event(
+            match action {
+                OrphanAction::Quarantine => "orphans_quarantined",
+                OrphanAction::Delete => "orphans_deleted",
+            },
+            actor,
+            format!(
+                "{} file(s), {} from {}; {} failed",
+                cleanup.files,
+                format_bytes(cleanup.bytes),
+                destination.unwrap_or("all destinations"),
+                cleanup.failed
+            ),
+        )?;
+    }
+    Ok(cleanup)
+}
+
+/// Move an orphan to the same relative path under the destination's quarantine folder
+fn quarantine(root: &Path, path: &Path) -> std::io::Result<PathBuf> {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    let target = available_path(root.join(QUARANTINE_DIR).join(relative));
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(path, &target)?;
+    info!("Quarantined orphan: {:?} → {:?}", path, target);
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::backup_row::BackupRow;
+    use crate::models::source_row::SourceRow;
+    use crate::repo::catalog::{insert_backup_row, insert_source_row};
+    use crate::repo::sqlite::{select_audit_events, set_db_pool, setup_database};
+    use crate::service::prune::UNCLAIMED_DIR;
+    use serial_test::serial;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    #[serial]
+    fn test_orphans_are_found_and_quarantined_or_deleted() {
+        let database = TempDir::new().unwrap();
+        set_db_pool(database.path().join("backup.db").to_str().unwrap()).unwrap();
+        setup_database().unwrap();
+        let destination = TempDir::new().unwrap();
+        let root = destination.path();
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "database_file": "",
+            "backup_sources": [{"parent_directory": "/photos"}],
+            "backup_destinations": [root]
+        }))
+        .unwrap();
+
+        fs::create_dir_all(root.join("photos/old")).unwrap();
+        fs::write(root.join("photos/kept.jpg"), "kept").unwrap();
+        fs::write(root.join("photos/old/left.jpg"), "leftover").unwrap();
+        fs::write(root.join("photos/.DS_Store"), "").unwrap();
+        fs::create_dir_all(root.join(UNCLAIMED_DIR)).unwrap();
+        fs::write(root.join(UNCLAIMED_DIR).join("moved.jpg"), "").unwrap();
+        let source_id = insert_source_row(&SourceRow {
+            id: 0,
+            file_name: "kept.jpg".to_string(),
+            file_path: "/photos".to_string(),
+            hash: "hash".to_string(),
+            file_size: 4,
+            last_modified: Duration::from_secs(100),
+        })
+        .unwrap();
+        insert_backup_row(BackupRow {
+            source_id,
+            file_name: "kept.jpg".to_string(),
+            file_path: root.join("photos").to_string_lossy().to_string(),
+            last_modified: Duration::from_secs(100),
+            original_path: None,
+            physical_size: None,
+        })
+        .unwrap();
+
+        let report = find_orphans(&config, None).unwrap();
+        assert_eq!((report.files, report.bytes), (1, 8));
+        assert!(report.destinations[0].files[0].path.ends_with("left.jpg"));
+
+        let cleanup = clean_orphans(&config, None, OrphanAction::Quarantine, "alice").unwrap();
+        assert_eq!((cleanup.files, cleanup.bytes, cleanup.failed), (1, 8, 0));
+        assert!(root
+            .join(QUARANTINE_DIR)
+            .join("photos/old/left.jpg")
+            .exists());
+        assert!(!root.join("photos/old").exists());
+        // Quarantined files are not orphans again
+        assert_eq!(find_orphans(&config, None).unwrap().files, 0);
+
+        fs::write(root.join("photos/stray.jpg"), "stray").unwrap();
+        let cleanup = clean_orphans(&config, None, OrphanAction::Delete, "bob").unwrap();
+        assert_eq!(cleanup.files, 1);
+        assert!(!root.join("photos/stray.jpg").exists());
+        assert!(root.join("photos/kept.jpg").exists());
+
+        let audit = select_audit_events(2).unwrap();
+        assert_eq!(audit[0].action, "orphans_deleted");
+        assert_eq!(audit[0].actor, "bob");
+        assert_eq!(audit[1].action, "orphans_quarantined");
+    }
+}
@@ -0,0 +1,428 @@
+use crate::models::config::{Config, PluginConfig, PluginKind};
+use crate::models::error::{BackupError, Result};
+use crate::models::prepped_backup::PreppedBackup;
+use crate::models::run_report::RunReport;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Sent with every request so plugins can refuse versions they don't understand
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// How long a plugin gets to exit after its stdin is closed before it is killed
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+/// One request line written to a plugin's stdin
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PluginRequest<'a> {
+    /// Should this source file be backed up?
+    Filter { source: &'a str, path: &'a str },
+    /// Store a new or changed source file
+    Store {
+        source: &'a str,
+        path: &'a str,
+        /// Path the file is mirrored to inside regular destinations
+        relative_path: &'a str,
+        hash: &'a str,
+        size: u64,
+    },
+    /// A run has finished, successfully or not
+    RunFinished {
+        dry_run: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        report: Option<&'a RunReport>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<&'a str>,
+    },
+}
+
+#[derive(Serialize)]
+struct Envelope<'a> {
+    protocol: u32,
+    #[serde(flatten)]
+    request: &'a PluginRequest<'a>,
+}
+
+/// One response line read from a plugin's stdout
+#[derive(Debug, Default, Deserialize)]
+pub struct PluginResponse {
+    #[serde(default = "default_ok")]
+    pub ok: bool,
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Answer to a filter request; files are kept unless this is false
+    #[serde(default)]
+    pub keep: Option<bool>,
+}
+
+const fn default_ok() -> bool {
+    true
+}
+
+/// A running plugin process that answers one JSON line for every request line
+pub struct Plugin {
+    name: String,
+    child: Child,
+    stdin: Option<ChildStdin>,
+    responses: Receiver<std::io::Result<String>>,
+    timeout: Option<Duration>,
+}
+
+impl Plugin {
+    pub fn start(config: &PluginConfig) -> Result<Self> {
+        let mut child = Command::new(&config.command)
+            .args(&config.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|e| plugin_error(&config.name, format!("failed to start: {}", e)))?;
+
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| plugin_error(&config.name, "stdout unavailable"))?;
+
+        // Read responses on a separate thread so a stuck plugin can be timed out
+        let (sender, responses) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        debug!("Started plugin {}: {}", config.name, config.command);
+        Ok(Plugin {
+            name: config.name.clone(),
+            stdin: child.stdin.take(),
+            child,
+            responses,
+            timeout: config.timeout_seconds.map(Duration::from_secs),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Send one request and wait for its response, failing if the plugin reports an error
+    pub fn call(&mut self, request: &PluginRequest) -> Result<PluginResponse> {
+        let line = serde_json::to_string(&Envelope {
+            protocol: PROTOCOL_VERSION,
+            request,
+        })
+        .map_err(|e| plugin_error(&self.name, e.to_string()))?;
+
+        let stdin = self
+            .stdin
+            .as_mut()
+            .ok_or_else(|| plugin_error(&self.name, "stdin closed"))?;
+        writeln!(stdin, "{}", line)
+            .and_then(|_| stdin.flush())
+            .map_err(|e| plugin_error(&self.name, format!("failed to send request: {}", e)))?;
+
+        let received = match self.timeout {
+            Some(timeout) => self.responses.recv_timeout(timeout),
+            None => self
+                .responses
+                .recv()
+                .map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        let line = match received {
+            Ok(Ok(line)) => line,
+            Ok(Err(e)) => {
+                return Err(plugin_error(
+                    &self.name,
+                    format!("failed to read response: {}", e),
+                ))
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                return Err(plugin_error(&self.name, "timed out waiting for a response"))
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(plugin_error(&self.name, "exited without responding"))
+            }
+        };
+
+        let response: PluginResponse = serde_json::from_str(&line)
+            .map_err(|e| plugin_error(&self.name, format!("invalid response '{}': {}", line, e)))?;
+        if !response.ok {
+            return Err(plugin_error(
+                &self.name,
+                response
+                    .error
+                    .unwrap_or_else(|| "request rejected".to_string()),
+            ));
+        }
+        Ok(response)
+    }
+}
+
+impl Drop for Plugin {
+    /// Closing stdin tells the plugin there are no more requests
+    fn drop(&mut self) {
+        self.stdin.take();
+
+        let started = Instant::now();
+        while started.elapsed() < SHUTDOWN_GRACE {
+            match self.child.try_wait() {
+                Ok(Some(_)) | Err(_) => return,
+                Ok(None) => thread::sleep(Duration::from_millis(50)),
+            }
+        }
+
+        warn!(
+            "Plugin {} did not exit after its input closed, killing it",
+            self.name
+        );
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn plugin_error(name: &str, message: impl Into<String>) -> BackupError {
+    BackupError::Plugin {
+        name: name.to_string(),
+        message: message.into(),
+    }
+}
+
+fn plugins_of_kind(config: &Config, kind: PluginKind) -> impl Iterator<Item = &PluginConfig> {
+    config.plugins.iter().filter(move |p| p.kind == kind)
+}
+
+/// A filter plugin and what it has decided so far
+struct FilterPlugin {
+    plugin: Mutex<Plugin>,
+    failed: AtomicBool,
+    excluded: AtomicU64,
+}
+
+/// Filter plugins started for one run, asked about each file as discovery finds it
+///
+/// Filters fail open: if a plugin can't be started or errors, the remaining files are kept.
+pub struct PluginFilters {
+    plugins: Vec<FilterPlugin>,
+}
+
+impl PluginFilters {
+    /// Start every filter plugin, skipping those that fail to launch
+    pub fn start(config: &Config) -> Self {
+        let plugins = plugins_of_kind(config, PluginKind::Filter)
+            .filter_map(|plugin_config| match Plugin::start(plugin_config) {
+                Ok(plugin) => Some(FilterPlugin {
+                    plugin: Mutex::new(plugin),
+                    failed: AtomicBool::new(false),
+                    excluded: AtomicU64::new(0),
+                }),
+                Err(e) => {
+                    warn!("{}; no files filtered", e);
+                    None
+                }
+            })
+            .collect();
+        PluginFilters { plugins }
+    }
+
+    /// Whether every plugin that is still answering keeps `file`
+    pub fn keep(&self, source: &str, file: &Path) -> bool {
+        let path = file.to_string_lossy();
+        let request = PluginRequest::Filter {
+            source,
+            path: &path,
+        };
+
+        for filter in &self.plugins {
+            if filter.failed.load(Ordering::Relaxed) {
+                continue;
+            }
+            let mut plugin = filter.plugin.lock().unwrap();
+            match plugin.call(&request) {
+                Ok(response) if response.keep == Some(false) => {
+                    filter.excluded.fetch_add(1, Ordering::Relaxed);
+                    return false;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    warn!("{}; keeping the remaining files", e);
+                    filter.failed.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+        true
+    }
+
+    /// Log how many files each plugin excluded
+    pub fn finish(self) {
+        for filter in self.plugins {
+            let excluded = filter.excluded.into_inner();
+            if excluded > 0 {
+                let plugin = filter.plugin.into_inner().unwrap();
+                info!("Plugin {} excluded {} files", plugin.name(), excluded);
+            }
+        }
+    }
+}
+
+/// Destination plugins started for one run
+pub struct PluginDestinations {
+    plugins: Vec<Mutex<Plugin>>,
+}
+
+impl PluginDestinations {
+    /// Start every destination plugin, skipping those that fail to launch
+    pub fn start(config: &Config) -> Self {
+        let plugins = plugins_of_kind(config, PluginKind::Destination)
+            .filter_map(|plugin_config| match Plugin::start(plugin_config) {
+                Ok(plugin) => Some(Mutex::new(plugin)),
+                Err(e) => {
+                    warn!("{}; skipping this destination", e);
+                    None
+                }
+            })
+            .collect();
+        PluginDestinations { plugins }
+    }
+
+    /// Hand a prepared file to each plugin, returning `(destination label, result)` per plugin
+    pub fn store(&self, prepped: &PreppedBackup) -> Vec<(String, Result<()>)> {
+        let path = prepped.read_from.to_string_lossy();
+        let relative_path = prepped.relative_path.to_string_lossy();
+        let request = PluginRequest::Store {
+            source: &prepped.source_name,
+            path: &path,
+            relative_path: &relative_path,
+            hash: &prepped.hash,
+            size: prepped.file_size,
+        };
+
+        self.plugins
+            .iter()
+            .map(|plugin| {
+                let mut plugin = plugin.lock().unwrap();
+                let result = plugin.call(&request).map(|_| ());
+                (destination_label(plugin.name()), result)
+            })
+            .collect()
+    }
+
+    /// Labels of every running destination plugin, as used in run reports
+    pub fn labels(&self) -> Vec<String> {
+        self.plugins
+            .iter()
+            .map(|plugin| destination_label(plugin.lock().unwrap().name()))
+            .collect()
+    }
+}
+
+/// How a plugin destination appears in run reports, e.g. `plugin:s3`
+pub fn destination_label(name: &str) -> String {
+    format!("plugin:{}", name)
+}
+
+/// Tell every notifier plugin how a run ended
+pub fn notify_run_finished(config: &Config, dry_run: bool, outcome: &anyhow::Result<RunReport>) {
+    let error = outcome.as_ref().err().map(|e| format!("{:#}", e));
+    let request = PluginRequest::RunFinished {
+        dry_run,
+        report: outcome.as_ref().ok(),
+        error: error.as_deref(),
+    };
+
+    for plugin_config in plugins_of_kind(config, PluginKind::Notifier) {
+        if let Err(e) = Plugin::start(plugin_config).and_then(|mut plugin| plugin.call(&request)) {
+            warn!("Failed to notify plugin: {}", e);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn shell_plugin(name: &str, kind: PluginKind, script: &str) -> PluginConfig {
+        PluginConfig {
+            name: name.to_string(),
+            kind,
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            timeout_seconds: Some(5),
+        }
+    }
+
+    #[test]
+    fn test_plugin_request_serialization() {
+        let request = PluginRequest::Filter {
+            source: "photos",
+            path: "/photos/a.jpg",
+        };
+        let line = serde_json::to_value(Envelope {
+            protocol: PROTOCOL_VERSION,
+            request: &request,
+        })
+        .unwrap();
+
+        assert_eq!(
+            line,
+            serde_json::json!({
+                "protocol": 1,
+                "type": "filter",
+                "source": "photos",
+                "path": "/photos/a.jpg"
+            })
+        );
+    }
+
+    #[test]
+    fn test_filter_plugin_excludes_files() {
+        let script = r#"while read -r line; do
+            case "$line" in
+                *.tmp*) echo '{"keep": false}' ;;
+                *) echo '{"keep": true}' ;;
+            esac
+        done"#;
+        let mut config: Config = serde_json::from_value(serde_json::json!({
+            "database_file": "",
+            "backup_sources": [{"parent_directory": "/photos"}],
+            "backup_destinations": ["/mnt/nas"]
+        }))
+        .unwrap();
+        config.plugins = vec![shell_plugin("no-temp", PluginKind::Filter, script)];
+
+        let filters = PluginFilters::start(&config);
+
+        assert!(filters.keep("photos", Path::new("/photos/a.jpg")));
+        assert!(!filters.keep("photos", Path::new("/photos/upload.tmp")));
+        filters.finish();
+    }
+
+    #[test]
+    fn test_plugin_reports_errors() {
+        let config = shell_plugin(
+            "broken",
+            PluginKind::Destination,
+            r#"read -r line; echo '{"ok": false, "error": "bucket missing"}'"#,
+        );
+        let mut plugin = Plugin::start(&config).unwrap();
+        let request = PluginRequest::Filter {
+            source: "photos",
+            path: "/photos/a.jpg",
+        };
+
+        let err = plugin.call(&request).unwrap_err();
+        assert!(err.to_string().contains("bucket missing"));
+
+        // The plugin exited after one line, so the next request fails instead of hanging
+        assert!(plugin.call(&request).is_err());
+    }
+}
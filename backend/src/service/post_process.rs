@@ -0,0 +1,320 @@
+use crate::models::config::{Config, PostProcessorConfig};
+use crate::models::post_process_result::{PostProcessResult, ProcessorStatus};
+use crate::repo::sqlite::insert_post_process_result;
+use crate::utils::content_type::content_type_matches;
+use log::{debug, warn};
+use std::io::Read;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Longest processor output kept with a stored result
+const MAX_DETAIL_LEN: usize = 1024;
+
+/// How often a command with a timeout is checked for completion
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A file that was just copied into a destination and verified
+pub struct CopiedFile<'a> {
+    pub source: &'a Path,
+    pub backup: &'a Path,
+    /// MIME type sniffed from the file's contents, when recognised
+    pub content_type: Option<&'a str>,
+}
+
+/// What a processor concluded about one file
+#[derive(Debug)]
+pub struct Verdict {
+    pub status: ProcessorStatus,
+    pub detail: Option<String>,
+}
+
+impl Verdict {
+    fn error(detail: String) -> Self {
+        Verdict {
+            status: ProcessorStatus::Error,
+            detail: Some(detail),
+        }
+    }
+}
+
+/// A step run against every successfully copied file, e.g. thumbnailing or a virus scan
+///
+/// In-process processors implement this trait and are added in [`PostProcessors::from_config`]
+/// alongside the configured external commands.
+pub trait PostProcessor: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// Whether the processor wants to see this file; defaults to every file
+    fn accepts(&self, _file: &CopiedFile) -> bool {
+        true
+    }
+
+    fn process(&self, file: &CopiedFile) -> Verdict;
+}
+
+/// Runs a configured external command; exit status 0 passes the file, anything else flags it
+pub struct CommandProcessor {
+    config: PostProcessorConfig,
+}
+
+impl CommandProcessor {
+    pub fn new(config: PostProcessorConfig) -> Self {
+        CommandProcessor { config }
+    }
+
+    fn args(&self, file: &CopiedFile) -> Vec<String> {
+        let backup = file.backup.to_string_lossy();
+        if self.config.args.is_empty() {
+            return vec![backup.to_string()];
+        }
+
+        let source = file.source.to_string_lossy();
+        self.config
+            .args
+            .iter()
+            .map(|arg| {
+                arg.replace("{backup}", &backup)
+                    .replace("{source}", &source)
+            })
+            .collect()
+    }
+}
+
+impl PostProcessor for CommandProcessor {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn accepts(&self, file: &CopiedFile) -> bool {
+        self.config.content_types.is_empty()
+            || file.content_type.is_some_and(|mime| {
+                self.config
+                    .content_types
+                    .iter()
+                    .any(|pattern| content_type_matches(pattern, mime))
+            })
+    }
+
+    fn process(&self, file: &CopiedFile) -> Verdict {
+        let mut child = match Command::new(&self.config.command)
+            .args(self.args(file))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(e) => {
+                return Verdict::error(format!("Failed to start {}: {}", self.config.command, e))
+            }
+        };
+
+        // Drain output on separate threads so a chatty command can't block on a full pipe
+        let stdout = child.stdout.take().map(read_in_background);
+        let stderr = child.stderr.take().map(read_in_background);
+
+        let status = match self.config.timeout_seconds {
+            Some(seconds) => wait_with_timeout(&mut child, Duration::from_secs(seconds)),
+            None => child.wait().map(Some),
+        };
+
+        match status {
+            Ok(Some(status)) => {
+                let detail = join_output(stdout).or_else(|| join_output(stderr));
+                if status.success() {
+                    Verdict {
+                        status: ProcessorStatus::Passed,
+                        detail,
+                    }
+                } else {
+                    Verdict {
+                        status: ProcessorStatus::Flagged,
+                        detail: detail.or_else(|| Some(format!("Command exited with {}", status))),
+                    }
+                }
+            }
+            Ok(None) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                Verdict::error(format!(
+                    "Timed out after {} seconds",
+                    self.config.timeout_seconds.unwrap_or_default()
+                ))
+            }
+            Err(e) => Verdict::error(format!("Failed to wait for {}: {}", self.config.command, e)),
+        }
+    }
+}
+
+/// The post-processors configured for a run
+pub struct PostProcessors {
+    processors: Vec<Box<dyn PostProcessor>>,
+    sniff_content_type: bool,
+}
+
+impl PostProcessors {
+    pub fn from_config(config: &Config) -> Self {
+        let processors: Vec<Box<dyn PostProcessor>> = config
+            .post_processors
+            .iter()
+            .map(|p| Box::new(CommandProcessor::new(p.clone())) as Box<dyn PostProcessor>)
+            .collect();
+
+        PostProcessors {
+            processors,
+            sniff_content_type: config
+                .post_processors
+                .iter()
+                .any(|p| !p.content_types.is_empty()),
+        }
+    }
+
+    /// Pass a copied file to every processor that accepts it and store each verdict
+    pub fn run(&self, source: &Path, backup: &Path) -> Vec<PostProcessResult> {
+        if self.processors.is_empty() {
+            return Vec::new();
+        }
+
+        let content_type = if self.sniff_content_type {
+            infer::get_from_path(backup).ok().flatten()
+        } else {
+            None
+        };
+        let file = CopiedFile {
+            source,
+            backup,
+            content_type: content_type.map(|t| t.mime_type()),
+        };
+
+        let mut results = Vec::new();
+        for processor in self.processors.iter().filter(|p| p.accepts(&file)) {
+            let verdict = processor.process(&file);
+            match verdict.status {
+                ProcessorStatus::Passed => debug!("{} passed {:?}", processor.name(), backup),
+                ProcessorStatus::Flagged => warn!(
+                    "{} flagged {:?}: {}",
+                    processor.name(),
+                    backup,
+                    verdict.detail.as_deref().unwrap_or_default()
+                ),
+                ProcessorStatus::Error => warn!(
+                    "{} failed for {:?}: {}",
+                    processor.name(),
+                    backup,
+                    verdict.detail.as_deref().unwrap_or_default()
+                ),
+            }
+
+            let result = PostProcessResult {
+                processor: processor.name().to_string(),
+                source_path: source.to_string_lossy().to_string(),
+                backup_path: backup.to_string_lossy().to_string(),
+                status: verdict.status,
+                detail: verdict.detail,
+                processed_at: chrono::Utc::now().timestamp(),
+            };
+            if let Err(e) = insert_post_process_result(&result) {
+                warn!(
+                    "Failed to store post-process result for {:?}: {}",
+                    backup, e
+                );
+            }
+            results.push(result);
+        }
+        results
+    }
+}
+
+pub(crate) fn read_in_background<R: Read + Send + 'static>(mut pipe: R) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = pipe.read_to_end(&mut output);
+        output
+    })
+}
+
+/// Collect a command's output, trimmed and truncated, or None if it printed nothing
+pub(crate) fn join_output(handle: Option<JoinHandle<Vec<u8>>>) -> Option<String> {
+    let output = handle?.join().ok()?;
+    let output = String::from_utf8_lossy(&output);
+    let trimmed = output.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+
+    let end = trimmed
+        .char_indices()
+        .map(|(idx, _)| idx)
+        .nth(MAX_DETAIL_LEN)
+        .unwrap_or(trimmed.len());
+    Some(trimmed[..end].to_string())
+}
+
+/// Wait for a child to exit, returning None if it is still running after `timeout`
+pub(crate) fn wait_with_timeout(
+    child: &mut Child,
+    timeout: Duration,
+) -> std::io::Result<Option<ExitStatus>> {
+    let started = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if started.elapsed() >= timeout {
+            return Ok(None);
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn shell_processor(script: &str, timeout_seconds: Option<u64>) -> CommandProcessor {
+        CommandProcessor::new(PostProcessorConfig {
+            name: "test".to_string(),
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string(), "{backup}".to_string()],
+            content_types: vec![],
+            timeout_seconds,
+        })
+    }
+
+    fn copied_file() -> CopiedFile<'static> {
+        CopiedFile {
+            source: Path::new("/photos/a.jpg"),
+            backup: Path::new("/mnt/nas/photos/a.jpg"),
+            content_type: Some("image/jpeg"),
+        }
+    }
+
+    #[test]
+    fn test_command_processor_verdicts() {
+        let passed = shell_processor("echo \"scanned $0\"", None).process(&copied_file());
+        assert_eq!(passed.status, ProcessorStatus::Passed);
+        assert_eq!(
+            passed.detail.as_deref(),
+            Some("scanned /mnt/nas/photos/a.jpg")
+        );
+
+        let flagged = shell_processor("echo 'Eicar FOUND'; exit 1", None).process(&copied_file());
+        assert_eq!(flagged.status, ProcessorStatus::Flagged);
+        assert_eq!(flagged.detail.as_deref(), Some("Eicar FOUND"));
+
+        let timed_out = shell_processor("sleep 5", Some(1)).process(&copied_file());
+        assert_eq!(timed_out.status, ProcessorStatus::Error);
+    }
+
+    #[test]
+    fn test_command_processor_content_type_filter() {
+        let mut processor = shell_processor("true", None);
+        processor.config.content_types = vec!["video/*".to_string()];
+        assert!(!processor.accepts(&copied_file()));
+
+        processor.config.content_types = vec!["image/*".to_string()];
+        assert!(processor.accepts(&copied_file()));
+    }
+}
@@ -0,0 +1,565 @@
+use crate::models::backup_marker::{BACKUP_MARKER_FILE, DESTINATION_MARKER_FILE};
+use crate::models::config::{Config, Filesystem};
+use crate::models::dry_run_mode::DryRunMode;
+use crate::models::error::Result;
+use crate::models::file_operation::{FileOperation, FileOperationEntry};
+use crate::models::prune_plan::{PlanStatus, PlannedFile, PrunePlan};
+use crate::models::unclaimed_file::UnclaimedFile;
+use crate::repo::sqlite::{
+    decide_prune_plan, format_bytes, insert_prune_plan, insert_unclaimed_file,
+    select_prune_plan_files, supersede_pending_prune_plans,
+};
+use crate::service::audit::record_audit_event;
+use crate::service::catalog_backup::CATALOG_BACKUP_DIR;
+use crate::service::checksum_manifest::is_checksum_manifest;
+use crate::service::hash::hash_file;
+use crate::service::operation_log::record_file_operation;
+use crate::service::orphans::QUARANTINE_DIR;
+use crate::service::trash::TRASH_DIR;
+use crate::service::workspace::WORKSPACE_DIR;
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use walkdir::WalkDir;
+
+/// Directory under each destination root that receives unclaimed files
+pub const UNCLAIMED_DIR: &str = "unclaimed";
+
+/// OS metadata files that are left in place rather than treated as unclaimed
+const GARBAGE_FILE_NAMES: &[&str] = &[
+    ".DS_Store",
+    "Thumbs.db",
+    "desktop.ini",
+    BACKUP_MARKER_FILE,
+    DESTINATION_MARKER_FILE,
+];
+
+/// Filesystem and OS housekeeping directories that are never scanned
+const SYSTEM_DIR_NAMES: &[&str] = &[
+    "lost+found",
+    "$RECYCLE.BIN",
+    "System Volume Information",
+    ".Trashes",
+    ".Spotlight-V100",
+    ".fseventsd",
+];
+
+/// Where one source's files are mirrored inside a destination
+struct MirroredSource {
+    backup_root: PathBuf,
+    source_root: PathBuf,
+    /// Filesystem of the destination, which may have encoded the names it holds
+    filesystem: Filesystem,
+}
+
+/// What a pruning run did with the unclaimed files it found
+#[derive(Debug, Default)]
+pub struct PruneOutcome {
+    /// Files moved, or that would be moved in a dry run
+    pub moved: Vec<UnclaimedFile>,
+    /// Plan left waiting for approval
+    pub pending_plan: Option<PrunePlan>,
+}
+
+/// Plan to move destination files that match no source, moving them once the plan is approved
+///
+/// Dry runs only report what would be moved. Otherwise the files are kept as a pending plan that
+/// replaces any older pending one, and are moved right away only with `auto_approve_pruning`.
+pub fn prune_unclaimed(
+    config: &Config,
+    dry_run_mode: DryRunMode,
+    run_id: &str,
+) -> Result<PruneOutcome> {
+    if dry_run_mode.is_dry_run() {
+        return Ok(PruneOutcome {
+            moved: move_unclaimed_files(config, dry_run_mode)?,
+            pending_plan: None,
+        });
+    }
+
+    let superseded = supersede_pending_prune_plans()?;
+    if !superseded.is_empty() {
+        record_audit_event(
+            "prune_plan_superseded",
+            "run",
+            format!("Plan(s) {:?} replaced by run {}", superseded, run_id),
+        )?;
+    }
+
+    let files = plan_unclaimed_files(config);
+    if files.is_empty() {
+        return Ok(PruneOutcome::default());
+    }
+
+    let mut plan = PrunePlan::pending(run_id, chrono::Utc::now().timestamp(), &files);
+    plan.id = insert_prune_plan(&plan, &files)?;
+    record_audit_event(
+        "prune_plan_created",
+        "run",
+        format!(
+            "Plan {} from run {}: {} file(s), {}",
+            plan.id,
+            run_id,
+            plan.files,
+            format_bytes(plan.bytes)
+        ),
+    )?;
+
+    if config.auto_approve_pruning {
+        let moved = approve_prune_plan(config, plan.id, "config")?.unwrap_or_default();
+        return Ok(PruneOutcome {
+            moved,
+            pending_plan: None,
+        });
+    }
+
+    warn!(
+        "Prune plan {} awaits approval: {} unclaimed file(s), {}",
+        plan.id,
+        plan.files,
+        format_bytes(plan.bytes)
+    );
+    Ok(PruneOutcome {
+        moved: Vec::new(),
+        pending_plan: Some(plan),
+    })
+}
+
+/// Approve a pending plan and move those of its files that are still unclaimed
+///
+/// Returns `None` if there is no pending plan with this ID.
+pub fn approve_prune_plan(
+    config: &Config,
+    id: i64,
+    actor: &str,
+) -> Result<Option<Vec<UnclaimedFile>>> {
+    if !decide_prune_plan(id, PlanStatus::Approved, actor)? {
+        return Ok(None);
+    }
+
+    let files = select_prune_plan_files(id)?;
+    let mut mirrored: HashMap<String, Vec<MirroredSource>> = HashMap::new();
+    let mut moved = Vec::new();
+    for file in &files {
+        let original = Path::new(&file.path);
+        let mirrored = mirrored
+            .entry(file.destination.clone())
+            .or_insert_with(|| mirrored_sources(config, &file.destination));
+        // The plan may be old, so files that went away or gained a source are left alone
+        if !original.is_file() || is_claimed(original, mirrored) {
+            continue;
+        }
+        match move_to_unclaimed(
+            config,
+            Path::new(&file.destination),
+            original,
+            DryRunMode::None,
+        ) {
+            Ok(file) => moved.push(file),
+            Err(e) => warn!("Failed to move unclaimed file {:?}: {}", original, e),
+        }
+    }
+
+    record_audit_event(
+        "prune_plan_approved",
+        actor,
+        format!(
+            "Plan {}: moved {} of {} file(s), {}",
+            id,
+            moved.len(),
+            files.len(),
+            format_bytes(moved.iter().map(|f| f.file_size).sum())
+        ),
+    )?;
+    Ok(Some(moved))
+}
+
+/// Reject a pending plan; returns false if there is no pending plan with this ID
+pub fn reject_prune_plan(id: i64, actor: &str) -> Result<bool> {
+    if !decide_prune_plan(id, PlanStatus::Rejected, actor)? {
+        return Ok(false);
+    }
+    record_audit_event("prune_plan_rejected", actor, format!("Plan {}", id))?;
+    Ok(true)
+}
+
+/// Every unclaimed file in each destination, with its size and age
+fn plan_unclaimed_files(config: &Config) -> Vec<PlannedFile> {
+    let mut planned = Vec::new();
+    for destination in config.destination_paths() {
+        let destination_root = Path::new(&destination);
+        if !destination_root.is_dir() {
+            warn!(
+                "Skipping unclaimed file scan, destination is not a directory: {}",
+                destination
+            );
+            continue;
+        }
+
+        let mirrored = mirrored_sources(config, &destination);
+        for path in find_unclaimed_files(destination_root, &mirrored) {
+            let Ok(metadata) = fs::metadata(&path) else {
+                continue;
+            };
+            let modified_at = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |time| time.as_secs() as i64);
+            planned.push(PlannedFile {
+                destination: destination.clone(),
+                path: path.to_string_lossy().to_string(),
+                file_size: metadata.len(),
+                modified_at,
+            });
+        }
+    }
+    planned
+}
+
+/// Move destination files that match no source into each destination's `unclaimed/` area
+///
+/// A file is claimed when it sits under a source's backup root and the corresponding source
+/// file still exists. Everything else, apart from OS metadata, is hashed, moved and
+/// catalogued so nothing is deleted. Dry runs only report what would be moved.
+fn move_unclaimed_files(config: &Config, dry_run_mode: DryRunMode) -> Result<Vec<UnclaimedFile>> {
+    let mut unclaimed = Vec::new();
+
+    for destination in config.destination_paths() {
+        let destination_root = Path::new(&destination);
+        if !destination_root.is_dir() {
+            warn!(
+                "Skipping unclaimed file scan, destination is not a directory: {}",
+                destination
+            );
+            continue;
+        }
+
+        let mirrored = mirrored_sources(config, &destination);
+        for original in find_unclaimed_files(destination_root, &mirrored) {
+            match move_to_unclaimed(config, destination_root, &original, dry_run_mode) {
+                Ok(file) => unclaimed.push(file),
+                Err(e) => warn!("Failed to move unclaimed file {:?}: {}", original, e),
+            }
+        }
+    }
+
+    if !unclaimed.is_empty() {
+        info!(
+            "{} {} unclaimed destination files",
+            if dry_run_mode.is_dry_run() {
+                "Would move"
+            } else {
+                "Moved"
+            },
+            unclaimed.len()
+        );
+    }
+
+    Ok(unclaimed)
+}
+
+/// Backup roots of every source routed to the given destination
+fn mirrored_sources(config: &Config, destination: &str) -> Vec<MirroredSource> {
+    config
+        .backup_sources
+        .iter()
+        .filter_map(|source| {
+            let source_root = PathBuf::from(&source.parent_directory);
+            let settings = config.source_settings(&source_root);
+            if !settings.destinations.iter().any(|d| d == destination) {
+                return None;
+            }
+
+            let filesystem = config.filesystem_of(destination);
+            let mut backup_root = Path::new(destination)
+                .join(settings.destination_subpath.as_deref().unwrap_or_default());
+            if let Some(name) = source_root.file_name() {
+                backup_root.push(&*filesystem.file_name(&name.to_string_lossy()));
+            }
+
+            Some(MirroredSource {
+                backup_root,
+                source_root,
+                filesystem,
+            })
+        })
+        .collect()
+}
+
+/// Walk a destination and return files that no source claims
+fn find_unclaimed_files(destination_root: &Path, mirrored: &[MirroredSource]) -> Vec<PathBuf> {
+    destination_files(destination_root)
+        .into_iter()
+        .filter(|path| !is_claimed(path, mirrored))
+        .collect()
+}
+
+/// Every file in a destination apart from OS metadata, checksum manifests and the unclaimed,
+/// quarantine, trash, database snapshot and run workspace areas
+pub(crate) fn destination_files(destination_root: &Path) -> Vec<PathBuf> {
+    let set_aside = [
+        destination_root.join(UNCLAIMED_DIR),
+        destination_root.join(QUARANTINE_DIR),
+        destination_root.join(TRASH_DIR),
+        destination_root.join(CATALOG_BACKUP_DIR),
+        destination_root.join(WORKSPACE_DIR),
+    ];
+
+    WalkDir::new(destination_root)
+        .into_iter()
+        .filter_entry(|entry| {
+            let system_dir = entry.file_type().is_dir()
+                && SYSTEM_DIR_NAMES
+                    .iter()
+                    .any(|name| entry.file_name() == *name);
+            !(system_dir || set_aside.iter().any(|dir| entry.path() == dir))
+        })
+        .filter_map(|entry| match entry {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                warn!("Failed to read destination entry: {}", e);
+                None
+            }
+        })
+        .filter(|entry| entry.file_type().is_file())
+        .filter(|entry| !is_garbage(entry.path()) && !is_checksum_manifest(entry.file_name()))
+        .map(|entry| entry.into_path())
+        .collect()
+}
+
+fn is_garbage(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+        return false;
+    };
+    GARBAGE_FILE_NAMES.contains(&name) || name.starts_with("._")
+}
+
+fn is_claimed(path: &Path, mirrored: &[MirroredSource]) -> bool {
+    mirrored.iter().any(|m| {
+        path.strip_prefix(&m.backup_root)
+            .map(|relative| {
+                m.source_root
+                    .join(m.filesystem.source_names(relative))
+                    .exists()
+            })
+            .unwrap_or(false)
+    })
+}
+
+fn move_to_unclaimed(
+    config: &Config,
+    destination_root: &Path,
+    original: &Path,
+    dry_run_mode: DryRunMode,
+) -> Result<UnclaimedFile> {
+    let relative = original.strip_prefix(destination_root).unwrap_or(original);
+    let target = available_path(destination_root.join(UNCLAIMED_DIR).join(relative));
+    let file_size = fs::metadata(original)?.len();
+
+    let hash = if dry_run_mode.should_hash() {
+        Some(hash_file(original, config.hash_limit())?)
+    } else {
+        None
+    };
+
+    let file = UnclaimedFile {
+        destination: destination_root.to_string_lossy().to_string(),
+        original_path: original.to_string_lossy().to_string(),
+        unclaimed_path: target.to_string_lossy().to_string(),
+        hash,
+        file_size,
+        moved_at: chrono::Utc::now().timestamp(),
+    };
+
+    if !dry_run_mode.should_copy_files() {
+        debug!(
+            "Dry-run mode: Would move unclaimed {:?} → {:?}",
+            original, target
+        );
+        return Ok(file);
+    }
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(original, &target)?;
+    info!("Moved unclaimed file: {:?} → {:?}", original, target);
+    record_file_operation(
+        config,
+        FileOperationEntry {
+            backup: Some(file.original_path.clone()),
+            hash: file.hash.clone(),
+            bytes: Some(file_size),
+            detail: Some(file.unclaimed_path.clone()),
+            ..FileOperationEntry::new(FileOperation::Unclaimed)
+        },
+    );
+
+    insert_unclaimed_file(&file)?;
+    remove_empty_parents(original, destination_root);
+    Ok(file)
+}
+
+/// Append a numeric suffix until the path doesn't collide with an earlier unclaimed file
+pub(crate) fn available_path(path: PathBuf) -> PathBuf {
+    if !path.exists() {
+        return path;
+    }
+
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    (1..)
+        .map(|n| path.with_file_name(format!("{}.{}", file_name, n)))
+        .find(|candidate| !candidate.exists())
+        .unwrap_or(path)
+}
+
+/// Remove directories left empty by a move, stopping at the destination root
+pub(crate) fn remove_empty_parents(moved: &Path, destination_root: &Path) {
+    let mut current = moved.parent();
+    while let Some(dir) = current {
+        if dir == destination_root || fs::remove_dir(dir).is_err() {
+            break;
+        }
+        current = dir.parent();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::sqlite::{
+        select_audit_events, select_prune_plan, select_unclaimed_files, set_db_pool, setup_database,
+    };
+    use serial_test::serial;
+    use tempfile::TempDir;
+
+    #[test]
+    #[serial]
+    fn test_unclaimed_files_wait_for_an_approved_plan() {
+        let database = TempDir::new().unwrap();
+        set_db_pool(database.path().join("backup.db").to_str().unwrap()).unwrap();
+        setup_database().unwrap();
+        let source = TempDir::new().unwrap();
+        let destination = TempDir::new().unwrap();
+        let backup_root = destination.path().join(source.path().file_name().unwrap());
+        fs::create_dir_all(&backup_root).unwrap();
+        fs::write(backup_root.join("deleted.txt"), "gone from source").unwrap();
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "database_file": "",
+            "backup_sources": [{"parent_directory": source.path()}],
+            "backup_destinations": [destination.path()],
+            "prune_unclaimed": true
+        }))
+        .unwrap();
+
+        let first = prune_unclaimed(&config, DryRunMode::None, "run-1").unwrap();
+        let first = first.pending_plan.unwrap();
+        assert_eq!((first.files, first.bytes), (1, 16));
+        let second = prune_unclaimed(&config, DryRunMode::None, "run-2").unwrap();
+        assert!(second.moved.is_empty());
+        let second = second.pending_plan.unwrap();
+        assert!(backup_root.join("deleted.txt").exists());
+        assert_eq!(
+            select_prune_plan(first.id).unwrap().unwrap().status,
+            PlanStatus::Superseded
+        );
+        assert!(approve_prune_plan(&config, first.id, "alice")
+            .unwrap()
+            .is_none());
+
+        let moved = approve_prune_plan(&config, second.id, "alice")
+            .unwrap()
+            .unwrap();
+        assert_eq!(moved.len(), 1);
+        assert!(!backup_root.join("deleted.txt").exists());
+        assert_eq!(select_unclaimed_files(None).unwrap().len(), 1);
+        let approved = select_prune_plan(second.id).unwrap().unwrap();
+        assert_eq!(approved.status, PlanStatus::Approved);
+        assert_eq!(approved.decided_by.as_deref(), Some("alice"));
+        assert!(!reject_prune_plan(second.id, "bob").unwrap());
+
+        let audit = select_audit_events(10).unwrap();
+        assert_eq!(audit[0].action, "prune_plan_approved");
+        assert_eq!(audit[0].actor, "alice");
+        assert!(audit.iter().any(|e| e.action == "prune_plan_superseded"));
+    }
+
+    #[test]
+    fn test_find_unclaimed_files_skips_claimed_and_garbage() {
+        let source = TempDir::new().unwrap();
+        let destination = TempDir::new().unwrap();
+        let source_name = source.path().file_name().unwrap();
+        let backup_root = destination.path().join(source_name);
+
+        fs::write(source.path().join("kept.txt"), "kept").unwrap();
+        fs::create_dir_all(&backup_root).unwrap();
+        fs::write(backup_root.join("kept.txt"), "kept").unwrap();
+        fs::write(backup_root.join("deleted.txt"), "gone from source").unwrap();
+        fs::write(backup_root.join(".DS_Store"), "").unwrap();
+        fs::create_dir_all(destination.path().join("stray")).unwrap();
+        fs::write(destination.path().join("stray").join("notes.txt"), "?").unwrap();
+        fs::create_dir_all(destination.path().join(UNCLAIMED_DIR)).unwrap();
+        fs::write(destination.path().join(UNCLAIMED_DIR).join("old.txt"), "").unwrap();
+
+        let mirrored = vec![MirroredSource {
+            backup_root,
+            source_root: source.path().to_path_buf(),
+            filesystem: Filesystem::Posix,
+        }];
+        let mut found = find_unclaimed_files(destination.path(), &mirrored);
+        found.sort();
+
+        let names: Vec<_> = found
+            .iter()
+            .map(|p| p.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(names, vec!["deleted.txt", "notes.txt"]);
+    }
+
+    #[test]
+    fn test_files_with_encoded_names_are_claimed_by_their_source() {
+        let source = TempDir::new().unwrap();
+        let destination = TempDir::new().unwrap();
+        fs::create_dir_all(source.path().join("docs")).unwrap();
+        fs::write(source.path().join("docs").join("a:b.txt"), "kept").unwrap();
+        let backup_root = destination
+            .path()
+            .join(source.path().file_name().unwrap())
+            .join("docs");
+        fs::create_dir_all(&backup_root).unwrap();
+        fs::write(backup_root.join("a%3Ab.txt"), "kept").unwrap();
+        fs::write(backup_root.join("c%3Ad.txt"), "gone from source").unwrap();
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "database_file": "",
+            "backup_sources": [{"parent_directory": source.path()}],
+            "backup_destinations": [{"path": destination.path(), "filesystem": "windows"}]
+        }))
+        .unwrap();
+
+        let destination_path = destination.path().to_str().unwrap();
+        let found = find_unclaimed_files(
+            destination.path(),
+            &mirrored_sources(&config, destination_path),
+        );
+        assert_eq!(found, vec![backup_root.join("c%3Ad.txt")]);
+    }
+
+    #[test]
+    fn test_available_path_adds_suffix_on_collision() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("report.pdf");
+        assert_eq!(available_path(path.clone()), path);
+
+        fs::write(&path, "first").unwrap();
+        assert_eq!(
+            available_path(path.clone()),
+            dir.path().join("report.pdf.1")
+        );
+    }
+}
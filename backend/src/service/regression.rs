@@ -0,0 +1,215 @@
+use crate::models::api::BackupStatus;
+use crate::models::config::RegressionDetection;
+use crate::models::run_record::RunRecord;
+use crate::models::run_report::{AnomalyMetric, RunAnomaly};
+use crate::repo::sqlite::format_bytes;
+
+const MIB: u64 = 1024 * 1024;
+
+/// Compare a finished run with the median of the comparable runs in `history`, newest first,
+/// and return the measurements that were far slower or larger
+///
+/// Dry runs, checkpoints and runs that didn't complete are neither checked nor used as a baseline.
+pub fn detect_anomalies(
+    settings: &RegressionDetection,
+    run: &RunRecord,
+    history: &[RunRecord],
+) -> Vec<RunAnomaly> {
+    if !settings.enabled || !comparable(run) {
+        return Vec::new();
+    }
+    let baseline: Vec<&RunRecord> = history
+        .iter()
+        .filter(|past| past.id != run.id && comparable(past))
+        .take(settings.baseline_runs)
+        .collect();
+    if baseline.len() < settings.min_baseline_runs {
+        return Vec::new();
+    }
+
+    let min_bytes = settings.min_mebibytes * MIB;
+    let mut anomalies: Vec<RunAnomaly> = [
+        slowdown(
+            settings,
+            AnomalyMetric::HashRate,
+            run,
+            &baseline,
+            |r| r.timing.map_or(0, |t| t.bytes_hashed) >= min_bytes,
+            RunRecord::hash_mib_per_sec,
+        ),
+        slowdown(
+            settings,
+            AnomalyMetric::CopyRate,
+            run,
+            &baseline,
+            |r| r.bytes_copied >= min_bytes,
+            RunRecord::mib_per_sec,
+        ),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+
+    // A quiet baseline is raised to the floor so that a run copying a little more isn't flagged
+    for (metric, value, past, floor) in [
+        (
+            AnomalyMetric::BytesCopied,
+            run.bytes_copied,
+            baseline.iter().map(|r| r.bytes_copied as f64).collect(),
+            min_bytes,
+        ),
+        (
+            AnomalyMetric::FilesCopied,
+            run.files_copied,
+            baseline.iter().map(|r| r.files_copied as f64).collect(),
+            settings.min_files,
+        ),
+    ] {
+        let usual = median(past);
+        let factor = value as f64 / usual.max(floor as f64).max(1.0);
+        if value >= floor && factor >= settings.growth_factor {
+            anomalies.push(RunAnomaly {
+                metric,
+                value: value as f64,
+                baseline: usual,
+                factor,
+            });
+        }
+    }
+
+    anomalies
+}
+
+/// One line describing an anomaly, for logs and notifications
+pub fn describe(anomaly: &RunAnomaly) -> String {
+    let (value, baseline, factor) = (anomaly.value, anomaly.baseline, anomaly.factor);
+    match anomaly.metric {
+        AnomalyMetric::HashRate => format!(
+            "Hashing ran at {:.1} MiB/s, {:.1}x slower than the usual {:.1} MiB/s",
+            value, factor, baseline
+        ),
+        AnomalyMetric::CopyRate => format!(
+            "Copying ran at {:.1} MiB/s, {:.1}x slower than the usual {:.1} MiB/s",
+            value, factor, baseline
+        ),
+        AnomalyMetric::BytesCopied => format!(
+            "Copied {}, {:.1}x the usual {}",
+            format_bytes(value as u64),
+            factor,
+            format_bytes(baseline as u64)
+        ),
+        AnomalyMetric::FilesCopied => format!(
+            "Copied {} files, {:.1}x the usual {:.0}",
+            value, factor, baseline
+        ),
+    }
+}
+
+/// Flag `run` if its rate is far below the median rate of the baseline runs
+///
+/// Only runs that moved enough data to measure a rate, per `measurable`, are compared.
+fn slowdown(
+    settings: &RegressionDetection,
+    metric: AnomalyMetric,
+    run: &RunRecord,
+    baseline: &[&RunRecord],
+    measurable: impl Fn(&RunRecord) -> bool,
+    rate: fn(&RunRecord) -> Option<f64>,
+) -> Option<RunAnomaly> {
+    let value = rate(run).filter(|_| measurable(run))?;
+    let rates: Vec<f64> = baseline
+        .iter()
+        .filter(|past| measurable(past))
+        .filter_map(|past| rate(past))
+        .collect();
+    if rates.len() < settings.min_baseline_runs {
+        return None;
+    }
+    let usual = median(rates);
+    let factor = usual / value;
+    (value > 0.0 && factor >= settings.slowdown_factor).then_some(RunAnomaly {
+        metric,
+        value,
+        baseline: usual,
+        factor,
+    })
+}
+
+fn comparable(run: &RunRecord) -> bool {
+    run.status == BackupStatus::Completed && !run.dry_run && !run.checkpoint && run.timing.is_some()
+}
+
+fn median(mut values: Vec<f64>) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_by(f64::total_cmp);
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::run_record::RunTiming;
+
+    /// A completed run that copied `mebibytes` MiB in `seconds` while hashing at 100 MiB/s
+    fn run(id: &str, mebibytes: u64, files: u64, seconds: u64) -> RunRecord {
+        RunRecord {
+            files_copied: files,
+            bytes_copied: mebibytes * MIB,
+            timing: Some(RunTiming {
+                duration_ms: seconds * 1000,
+                files_processed: files,
+                bytes_hashed: 1000 * MIB,
+                hash_micros: 10_000_000,
+            }),
+            ..RunRecord::finished(id, 0, seconds as i64, false, None, None)
+        }
+    }
+
+    #[test]
+    fn test_detect_anomalies() {
+        let settings = RegressionDetection::default();
+        let history: Vec<RunRecord> = (0..5)
+            .map(|i| run(&i.to_string(), 200 + i, 100, 10))
+            .collect();
+
+        assert!(detect_anomalies(&settings, &run("usual", 210, 120, 11), &history).is_empty());
+        assert!(
+            detect_anomalies(&settings, &run("early", 5000, 100, 10), &history[..2]).is_empty()
+        );
+
+        let mut slow = run("slow", 200, 100, 40);
+        slow.timing.as_mut().unwrap().hash_micros = 50_000_000;
+        let metrics: Vec<AnomalyMetric> = detect_anomalies(&settings, &slow, &history)
+            .iter()
+            .map(|a| a.metric)
+            .collect();
+        assert_eq!(
+            metrics,
+            vec![AnomalyMetric::HashRate, AnomalyMetric::CopyRate]
+        );
+
+        let anomalies = detect_anomalies(&settings, &run("big", 2000, 5000, 100), &history);
+        assert_eq!(anomalies.len(), 2);
+        assert_eq!(anomalies[0].metric, AnomalyMetric::BytesCopied);
+        assert_eq!(anomalies[0].baseline, 202.0 * MIB as f64);
+        assert_eq!(anomalies[1].metric, AnomalyMetric::FilesCopied);
+        assert_eq!(anomalies[1].factor, 5.0);
+        assert_eq!(
+            describe(&anomalies[1]),
+            "Copied 5000 files, 5.0x the usual 100"
+        );
+
+        let dry_run = RunRecord {
+            dry_run: true,
+            ..run("dry", 2000, 5000, 100)
+        };
+        assert!(detect_anomalies(&settings, &dry_run, &history).is_empty());
+    }
+}
@@ -0,0 +1,91 @@
+use crate::models::config::{setup_config, Config};
+use crate::models::error::Result;
+use crate::repo::catalog::open_catalog;
+use crate::repo::sqlite::{set_db_pool, setup_database, shard_catalog};
+use log::{info, warn};
+
+/// Re-read and validate a config file so it can replace the running configuration
+///
+/// The database pool is rebuilt when `database_file` changed, the catalog resharded when it or
+/// the shards changed, and reopened when `catalog_database_url` changed. `max_threads` is fixed
+/// when the thread pool is built, so a change to it only takes effect after a restart.
+pub fn reload_config(config_file: &str, current: &Config) -> Result<Config> {
+    let config = setup_config(config_file.to_string())?;
+
+    if config.database_file != current.database_file {
+        let db_file = if config.database_file.is_empty() {
+            ":memory:"
+        } else {
+            config.database_file.as_str()
+        };
+        set_db_pool(db_file)?;
+        setup_database()?;
+        info!("Switched database to {}", db_file);
+    }
+    if config.database_file != current.database_file
+        || config.catalog_sharding != current.catalog_sharding
+        || config.catalog_shard_roots() != current.catalog_shard_roots()
+    {
+        shard_catalog(config.catalog_sharding, &config.catalog_shard_roots())?;
+    }
+    if config.catalog_database_url != current.catalog_database_url {
+        open_catalog(config.catalog_database_url.as_deref())?;
+    }
+
+    if config.max_threads != current.max_threads {
+        warn!(
+            "max_threads changed from {} to {}; restart to apply",
+            current.max_threads, config.max_threads
+        );
+    }
+
+    info!("Configuration reloaded from {}", config_file);
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn write_config(dir: &TempDir, max_mebibytes_for_hash: usize) -> String {
+        let source = dir.path().join("source");
+        let destination = dir.path().join("destination");
+        fs::create_dir_all(&source).unwrap();
+        fs::create_dir_all(&destination).unwrap();
+
+        let config = serde_json::json!({
+            "database_file": "",
+            "max_mebibytes_for_hash": max_mebibytes_for_hash,
+            "backup_sources": [{ "parent_directory": source }],
+            "backup_destinations": [destination],
+        });
+        let path = dir.path().join("config.json");
+        fs::write(&path, config.to_string()).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_reload_config_picks_up_changes() {
+        let dir = TempDir::new().unwrap();
+        let path = write_config(&dir, 1);
+        let current = setup_config(path.clone()).unwrap();
+
+        let path = write_config(&dir, 8);
+        let reloaded = reload_config(&path, &current).unwrap();
+
+        assert_eq!(reloaded.max_mebibytes_for_hash, 8);
+    }
+
+    #[test]
+    fn test_reload_config_rejects_invalid_file() {
+        let dir = TempDir::new().unwrap();
+        let path = write_config(&dir, 1);
+        let current = setup_config(path.clone()).unwrap();
+
+        fs::write(&path, "{ not json").unwrap();
+
+        assert!(reload_config(&path, &current).is_err());
+    }
+}
@@ -0,0 +1,309 @@
+use crate::models::backup_row::BackupRow;
+use crate::models::config::{Config, HashLimit};
+use crate::models::error::{BackupError, Result};
+use crate::models::file_operation::{FileOperation, FileOperationEntry};
+use crate::models::scrub::{RepairReport, RepairedBackup, ScrubProblem, UnrepairedBackup};
+use crate::models::source_row::SourceRow;
+use crate::repo::catalog::{
+    insert_backup_row, select_backups_of_source, select_source, update_backup_verified,
+};
+use crate::repo::sqlite::{delete_scrub_problem, select_scrub_problems};
+use crate::service::audit::record_audit_event;
+use crate::service::hash::hash_file;
+use crate::service::operation_log::record_file_operation;
+use crate::service::scrub::hash_limit_of;
+use crate::utils::directory::{get_file_last_modified, get_file_physical_size, long_path};
+use crate::utils::file_copy::copy_file;
+use crate::utils::run_lock::RunLock;
+use log::{info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Copy every backup scrubs found damaged or missing again, from its source or a healthy backup
+///
+/// A source file still holding its recorded hash is copied from; otherwise each other backup of
+/// it under a configured destination is tried. The new copy is hashed before it is recorded.
+pub fn repair_backups(config: &Config, actor: &str, wait_for_lock: bool) -> Result<RepairReport> {
+    let _run_lock = RunLock::acquire(&config.database_file, wait_for_lock)?;
+    let mut report = RepairReport::default();
+
+    for problem in select_scrub_problems()? {
+        match repair_backup(config, &problem) {
+            Ok(Some(copied_from)) => {
+                info!("Repaired {} from {}", problem.backup, copied_from);
+                delete_scrub_problem(&problem.backup)?;
+                report.repaired.push(RepairedBackup {
+                    backup: problem.backup,
+                    copied_from,
+                });
+            }
+            Ok(None) => {
+                // The catalog no longer records the backup, so there is nothing to repair
+                delete_scrub_problem(&problem.backup)?;
+            }
+            Err(reason) => {
+                warn!("Could not repair {}: {}", problem.backup, reason);
+                report.unrepaired.push(UnrepairedBackup {
+                    backup: problem.backup,
+                    reason,
+                });
+            }
+        }
+    }
+
+    if !report.repaired.is_empty() || !report.unrepaired.is_empty() {
+        record_audit_event(
+            "backups_repaired",
+            actor,
+            format!(
+                "{} backup(s) copied again; {} could not be repaired",
+                report.repaired.len(),
+                report.unrepaired.len()
+            ),
+        )?;
+    }
+    Ok(report)
+}
+
+/// Copy one backup again; the path it was copied from, `None` if it is no longer recorded, or
+/// why it couldn't be repaired
+fn repair_backup(
+    config: &Config,
+    problem: &ScrubProblem,
+) -> std::result::Result<Option<String>, String> {
+    let backup = PathBuf::from(&problem.backup);
+    let source = Path::new(&problem.source);
+    let (Some(source_name), Some(source_dir)) = (source.file_name(), source.parent()) else {
+        return Err(format!("{} is not a file path", problem.source));
+    };
+    let Some(source_row) = select_source(
+        &source_name.to_string_lossy(),
+        &source_dir.to_string_lossy(),
+    )
+    .map_err(|e| e.to_string())?
+    else {
+        return Ok(None);
+    };
+    let recorded = select_backups_of_source(source_row.id).map_err(|e| e.to_string())?;
+    let Some(backup_row) = recorded
+        .iter()
+        .find(|row| Path::new(&row.file_path).join(&row.file_name) == backup)
+    else {
+        return Ok(None);
+    };
+
+    let hash_limit = hash_limit_of(config, &problem.source);
+    let holds_recorded_hash =
+        |path: &Path| hash_file(path, hash_limit).is_ok_and(|hash| hash == source_row.hash);
+
+    // The source first, then the other destinations' copies
+    let mut origins = vec![source.to_path_buf()];
+    origins.extend(
+        recorded
+            .iter()
+            .map(|row| Path::new(&row.file_path).join(&row.file_name))
+            .filter(|path| *path != backup && config.destination_of(path).is_some()),
+    );
+    let Some(origin) = origins.into_iter().find(|path| holds_recorded_hash(path)) else {
+        return Err(if source.exists() {
+            "the source changed since it was recorded and no other backup holds the recorded \
+             version; the next backup copies the new one"
+                .to_string()
+        } else {
+            "the source is gone and no other backup holds the recorded version".to_string()
+        });
+    };
+
+    copy_again(
+        config,
+        &origin,
+        &backup,
+        &source_row,
+        backup_row,
+        hash_limit,
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(Some(origin.to_string_lossy().to_string()))
+}
+
+/// Overwrite the backup with `origin`, check the copy and record it as verified now
+fn copy_again(
+    config: &Config,
+    origin: &Path,
+    backup: &Path,
+    source_row: &SourceRow,
+    backup_row: &BackupRow,
+    hash_limit: HashLimit,
+) -> Result<()> {
+    if let Some(parent) = backup.parent() {
+        fs::create_dir_all(long_path(parent))?;
+    }
+    copy_file(
+        &long_path(origin),
+        &long_path(backup),
+        config.copy_buffer_kib,
+    )
+    .map_err(|cause| BackupError::FileCopy {
+        from: origin.to_path_buf(),
+        to: backup.to_path_buf(),
+        cause,
+    })?;
+
+    let copy_hash = hash_file(backup, hash_limit)?;
+    let entry = |operation, detail: String| FileOperationEntry {
+        source: Some(
+            Path::new(&source_row.file_path)
+                .join(&source_row.file_name)
+                .to_string_lossy()
+                .to_string(),
+        ),
+        backup: Some(backup.to_string_lossy().to_string()),
+        hash: Some(source_row.hash.clone()),
+        bytes: Some(source_row.file_size),
+        detail: Some(detail),
+        ..FileOperationEntry::new(operation)
+    };
+    if copy_hash != source_row.hash {
+        let _ = fs::remove_file(long_path(backup));
+        let reason = format!(
+            "source hash {} != backup hash {}",
+            source_row.hash, copy_hash
+        );
+        record_file_operation(
+            config,
+            entry(
+                FileOperation::VerificationFailed,
+                format!("repair: {}", reason),
+            ),
+        );
+        return Err(BackupError::VerificationFailed {
+            path: backup.to_path_buf(),
+            reason,
+        });
+    }
+    record_file_operation(
+        config,
+        entry(
+            FileOperation::Copied,
+            format!("repaired from {}", origin.display()),
+        ),
+    );
+
+    insert_backup_row(BackupRow {
+        source_id: source_row.id,
+        file_name: backup_row.file_name.clone(),
+        file_path: backup_row.file_path.clone(),
+        last_modified: get_file_last_modified(backup)?,
+        original_path: None,
+        physical_size: get_file_physical_size(backup)?,
+    })?;
+    update_backup_verified(
+        source_row.id,
+        &backup_row.file_name,
+        &backup_row.file_path,
+        chrono::Utc::now().timestamp(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::config::ScrubConfig;
+    use crate::repo::catalog::insert_source_row;
+    use crate::repo::sqlite::{set_db_pool, setup_database};
+    use crate::service::scrub::scrub_backups;
+    use serial_test::serial;
+    use tempfile::TempDir;
+
+    #[test]
+    #[serial]
+    fn test_repair_copies_from_source_or_another_destination() {
+        let dir = TempDir::new().unwrap();
+        let database = dir.path().join("backup.db").to_string_lossy().to_string();
+        set_db_pool(&database).unwrap();
+        setup_database().unwrap();
+        let source_dir = dir.path().join("source");
+        let first_dir = dir.path().join("first");
+        let second_dir = dir.path().join("second");
+        for path in [&source_dir, &first_dir, &second_dir] {
+            fs::create_dir_all(path).unwrap();
+        }
+
+        for name in ["kept.txt", "gone.txt"] {
+            let contents = format!("contents of {}", name);
+            fs::write(source_dir.join(name), &contents).unwrap();
+            let source_id = insert_source_row(&SourceRow {
+                id: 0,
+                file_name: name.to_string(),
+                file_path: source_dir.to_string_lossy().to_string(),
+                hash: hash_file(&source_dir.join(name), HashLimit::new(1)).unwrap(),
+                file_size: contents.len() as u64,
+                last_modified: get_file_last_modified(&source_dir.join(name)).unwrap(),
+            })
+            .unwrap();
+            for backup_dir in [&first_dir, &second_dir] {
+                fs::write(backup_dir.join(name), &contents).unwrap();
+                insert_backup_row(BackupRow {
+                    source_id,
+                    file_name: name.to_string(),
+                    file_path: backup_dir.to_string_lossy().to_string(),
+                    last_modified: get_file_last_modified(&backup_dir.join(name)).unwrap(),
+                    original_path: None,
+                    physical_size: None,
+                })
+                .unwrap();
+            }
+        }
+
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "database_file": database,
+            "backup_sources": [{ "parent_directory": source_dir }],
+            "backup_destinations": [first_dir, second_dir]
+        }))
+        .unwrap();
+
+        // One backup lost while its source is still there, one whose source is gone too
+        fs::remove_file(first_dir.join("kept.txt")).unwrap();
+        fs::remove_file(first_dir.join("gone.txt")).unwrap();
+        fs::remove_file(source_dir.join("gone.txt")).unwrap();
+        let scrub = ScrubConfig {
+            schedule: String::new(),
+            percent: Some(100.0),
+            max_gibibytes: None,
+        };
+        assert_eq!(
+            scrub_backups(&config, &scrub, false)
+                .unwrap()
+                .problems
+                .len(),
+            2
+        );
+
+        let report = repair_backups(&config, "test", false).unwrap();
+        assert!(report.unrepaired.is_empty(), "{:?}", report.unrepaired);
+        let copied_from: Vec<_> = report
+            .repaired
+            .iter()
+            .map(|repaired| PathBuf::from(&repaired.copied_from))
+            .collect();
+        assert_eq!(
+            copied_from,
+            vec![second_dir.join("gone.txt"), source_dir.join("kept.txt")]
+        );
+        assert_eq!(
+            fs::read_to_string(first_dir.join("gone.txt")).unwrap(),
+            "contents of gone.txt"
+        );
+        assert!(select_scrub_problems().unwrap().is_empty());
+
+        // Repaired backups check out and leave nothing more to repair
+        assert!(scrub_backups(&config, &scrub, false)
+            .unwrap()
+            .problems
+            .is_empty());
+        assert_eq!(
+            repair_backups(&config, "test", false).unwrap(),
+            RepairReport::default()
+        );
+    }
+}
@@ -0,0 +1,321 @@
+use crate::models::backup_row::BackupRow;
+use crate::models::config::{BackupDestination, Config, HashLimit};
+use crate::models::error::{BackupError, Result};
+use crate::models::file_operation::{FileOperation, FileOperationEntry};
+use crate::models::replication::{FailedReplication, ReplicationReport};
+use crate::models::scrub::ScrubCandidate;
+use crate::repo::catalog::{insert_backup_row, select_scrub_candidates, update_backup_verified};
+use crate::service::audit::record_audit_event;
+use crate::service::hash::hash_file;
+use crate::service::operation_log::record_file_operation;
+use crate::service::scrub::hash_limit_of;
+use crate::utils::directory::{get_file_last_modified, get_file_physical_size, long_path};
+use crate::utils::file_copy::copy_file;
+use crate::utils::run_lock::RunLock;
+use log::{info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Copy every backup recorded under destination `from` to the same place under `to`
+///
+/// Names follow `to`'s filesystem. Each copy is hashed and recorded as a backup of its source
+/// only if it holds the source's recorded hash, so the source itself needn't be there. Backups
+/// `to` already holds intact are only recorded.
+pub fn replicate_backups(
+    config: &Config,
+    from: &BackupDestination,
+    to: &BackupDestination,
+    actor: &str,
+    wait_for_lock: bool,
+) -> Result<ReplicationReport> {
+    let _run_lock = RunLock::acquire(&config.database_file, wait_for_lock)?;
+    let mut report = ReplicationReport::default();
+
+    // A destination nested in `from` keeps its own backups
+    let mut candidates: Vec<ScrubCandidate> = select_scrub_candidates()?
+        .into_iter()
+        .filter(|candidate| {
+            config
+                .destination_of(Path::new(&candidate.backup.file_path))
+                .is_some_and(|destination| destination.path() == from.path())
+        })
+        .collect();
+    candidates.sort_by(|a, b| {
+        (&a.backup.file_path, &a.backup.file_name).cmp(&(&b.backup.file_path, &b.backup.file_name))
+    });
+    report.backups = candidates.len() as u64;
+    info!(
+        "Replicating {} backup(s) from {} to {}",
+        report.backups,
+        from.path(),
+        to.path()
+    );
+
+    for candidate in &candidates {
+        let backup = Path::new(&candidate.backup.file_path).join(&candidate.backup.file_name);
+        match replicate_backup(config, candidate, from, to) {
+            Ok(Some(bytes)) => {
+                report.copied += 1;
+                report.bytes_copied += bytes;
+            }
+            Ok(None) => report.already_present += 1,
+            Err(reason) => {
+                warn!("Could not replicate {}: {}", backup.display(), reason);
+                report.failed.push(FailedReplication {
+                    backup: backup.to_string_lossy().to_string(),
+                    reason,
+                });
+            }
+        }
+    }
+
+    if report.copied > 0 || !report.failed.is_empty() {
+        record_audit_event(
+            "backups_replicated",
+            actor,
+            format!(
+                "{} backup(s) copied from {} to {}; {} failed",
+                report.copied,
+                from.path(),
+                to.path(),
+                report.failed.len()
+            ),
+        )?;
+    }
+    info!(
+        "Replicated {} backup(s), {} already present, {} failed",
+        report.copied,
+        report.already_present,
+        report.failed.len()
+    );
+    Ok(report)
+}
+
+/// Copy one backup to `to`; the bytes copied, `None` if `to` already held it, or why it failed
+fn replicate_backup(
+    config: &Config,
+    candidate: &ScrubCandidate,
+    from: &BackupDestination,
+    to: &BackupDestination,
+) -> std::result::Result<Option<u64>, String> {
+    let backup = Path::new(&candidate.backup.file_path).join(&candidate.backup.file_name);
+    // The source changed after this copy was made, so its recorded hash can't vouch for it
+    if candidate.source_last_modified > candidate.backup.last_modified {
+        return Err(
+            "its source changed after it was copied; back up to the destination instead"
+                .to_string(),
+        );
+    }
+
+    // Where the backup would be under the source's own names, placed under `to` by its names
+    let original = candidate
+        .backup
+        .original_path
+        .as_ref()
+        .map_or_else(|| backup.clone(), PathBuf::from);
+    let relative = original
+        .strip_prefix(from.path())
+        .map_err(|_| format!("{} is not under {}", original.display(), from.path()))?;
+    let (Some(name), Some(relative_dir)) = (relative.file_name(), relative.parent()) else {
+        return Err(format!("{} is not a file path", relative.display()));
+    };
+    let filesystem = to.filesystem();
+    let target = Path::new(to.path())
+        .join(filesystem.dir_names(relative_dir))
+        .join(&*filesystem.file_name(&name.to_string_lossy()));
+    let target_original = Path::new(to.path()).join(relative);
+
+    let hash_limit = hash_limit_of(config, &candidate.source_path);
+    let intact = |path: &Path| hash_file(path, hash_limit).is_ok_and(|hash| hash == candidate.hash);
+    let copied = if intact(&target) {
+        None
+    } else {
+        let bytes = copy_verified(config, &backup, &target, candidate, hash_limit)
+            .map_err(|e| e.to_string())?;
+        Some(bytes)
+    };
+
+    record_copy(candidate, &target, &target_original).map_err(|e| e.to_string())?;
+    Ok(copied)
+}
+
+/// Copy `backup` to `target` and check the copy holds the source's recorded hash
+fn copy_verified(
+    config: &Config,
+    backup: &Path,
+    target: &Path,
+    candidate: &ScrubCandidate,
+    hash_limit: HashLimit,
+) -> Result<u64> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(long_path(parent))?;
+    }
+    let bytes = copy_file(
+        &long_path(backup),
+        &long_path(target),
+        config.copy_buffer_kib,
+    )
+    .map_err(|cause| BackupError::FileCopy {
+        from: backup.to_path_buf(),
+        to: target.to_path_buf(),
+        cause,
+    })?;
+
+    let copy_hash = hash_file(target, hash_limit)?;
+    let entry = |operation, detail: String| FileOperationEntry {
+        source: Some(candidate.source_path.clone()),
+        backup: Some(target.to_string_lossy().to_string()),
+        hash: Some(candidate.hash.clone()),
+        bytes: Some(bytes),
+        detail: Some(detail),
+        ..FileOperationEntry::new(operation)
+    };
+    if copy_hash != candidate.hash {
+        let _ = fs::remove_file(long_path(target));
+        let reason = format!(
+            "source hash {} != backup hash {}; the backup copied from is damaged",
+            candidate.hash, copy_hash
+        );
+        record_file_operation(
+            config,
+            entry(
+                FileOperation::VerificationFailed,
+                format!("replicate: {}", reason),
+            ),
+        );
+        return Err(BackupError::VerificationFailed {
+            path: target.to_path_buf(),
+            reason,
+        });
+    }
+    record_file_operation(
+        config,
+        entry(
+            FileOperation::Copied,
+            format!("replicated from {}", backup.display()),
+        ),
+    );
+    Ok(bytes)
+}
+
+/// Record `target` as a backup of the candidate's source, found intact now
+fn record_copy(candidate: &ScrubCandidate, target: &Path, target_original: &Path) -> Result<()> {
+    let file_name = target
+        .file_name()
+        .ok_or_else(|| BackupError::DirectoryRead(format!("No filename for {:?}", target)))?
+        .to_string_lossy()
+        .to_string();
+    let file_path = target
+        .parent()
+        .ok_or_else(|| BackupError::DirectoryRead(format!("No parent for {:?}", target)))?
+        .to_string_lossy()
+        .to_string();
+    insert_backup_row(BackupRow {
+        source_id: candidate.backup.source_id,
+        file_name: file_name.clone(),
+        file_path: file_path.clone(),
+        last_modified: get_file_last_modified(target)?,
+        original_path: (target != target_original)
+            .then(|| target_original.to_string_lossy().to_string()),
+        physical_size: get_file_physical_size(target)?,
+    })?;
+    update_backup_verified(
+        candidate.backup.source_id,
+        &file_name,
+        &file_path,
+        chrono::Utc::now().timestamp(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::source_row::SourceRow;
+    use crate::repo::catalog::{insert_source_row, select_backups_of_source};
+    use crate::repo::sqlite::{set_db_pool, setup_database};
+    use serial_test::serial;
+    use tempfile::TempDir;
+
+    #[test]
+    #[serial]
+    fn test_replicate_seeds_another_destination_without_the_source() {
+        let dir = TempDir::new().unwrap();
+        let database = dir.path().join("backup.db").to_string_lossy().to_string();
+        set_db_pool(&database).unwrap();
+        setup_database().unwrap();
+        let source_dir = dir.path().join("source");
+        let from_dir = dir.path().join("from");
+        let to_dir = dir.path().join("to");
+        fs::create_dir_all(&source_dir).unwrap();
+        fs::create_dir_all(from_dir.join("source")).unwrap();
+
+        let mut source_ids = Vec::new();
+        for name in ["notes: draft.txt", "plain.txt", "rotten.txt"] {
+            let contents = format!("contents of {}", name);
+            fs::write(source_dir.join(name), &contents).unwrap();
+            let backup = from_dir.join("source").join(name);
+            fs::write(&backup, &contents).unwrap();
+            let source_id = insert_source_row(&SourceRow {
+                id: 0,
+                file_name: name.to_string(),
+                file_path: source_dir.to_string_lossy().to_string(),
+                hash: hash_file(&source_dir.join(name), HashLimit::new(1)).unwrap(),
+                file_size: contents.len() as u64,
+                last_modified: get_file_last_modified(&source_dir.join(name)).unwrap(),
+            })
+            .unwrap();
+            insert_backup_row(BackupRow {
+                source_id,
+                file_name: name.to_string(),
+                file_path: from_dir.join("source").to_string_lossy().to_string(),
+                last_modified: get_file_last_modified(&backup).unwrap(),
+                original_path: None,
+                physical_size: None,
+            })
+            .unwrap();
+            source_ids.push(source_id);
+        }
+
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "database_file": database,
+            "backup_sources": [{ "parent_directory": source_dir }],
+            "backup_destinations": [from_dir, { "path": to_dir, "filesystem": "windows" }]
+        }))
+        .unwrap();
+        let (from, to) = (
+            &config.backup_destinations[0],
+            &config.backup_destinations[1],
+        );
+
+        // The source disk died and one backup rotted
+        fs::remove_dir_all(&source_dir).unwrap();
+        fs::write(from_dir.join("source").join("rotten.txt"), "rotten").unwrap();
+
+        let report = replicate_backups(&config, from, to, "test", false).unwrap();
+        assert_eq!((report.backups, report.copied), (3, 2));
+        assert_eq!(report.failed.len(), 1);
+        assert!(report.failed[0].backup.ends_with("rotten.txt"));
+        assert!(!to_dir.join("source").join("rotten.txt").exists());
+
+        // Named as the Windows destination can hold it, with the original name recorded
+        let renamed = to_dir.join("source").join("notes%3A draft.txt");
+        assert_eq!(
+            fs::read_to_string(&renamed).unwrap(),
+            "contents of notes: draft.txt"
+        );
+        let recorded = select_backups_of_source(source_ids[0]).unwrap();
+        let copy = recorded
+            .iter()
+            .find(|row| row.file_path.starts_with(&*to_dir.to_string_lossy()))
+            .unwrap();
+        assert_eq!(copy.file_name, "notes%3A draft.txt");
+        assert_eq!(
+            copy.original_path.as_deref().map(PathBuf::from),
+            Some(to_dir.join("source").join("notes: draft.txt"))
+        );
+
+        // A second pass finds both copies in place
+        let again = replicate_backups(&config, from, to, "test", false).unwrap();
+        assert_eq!((again.copied, again.already_present), (0, 2));
+    }
+}
@@ -0,0 +1,123 @@
+use crate::models::error::Result;
+use crate::models::run_report::{FileOutcome, RunReport};
+use crate::repo::sqlite::insert_run_file;
+use log::warn;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// One line of an NDJSON run report
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ReportLine<'a> {
+    File(&'a FileOutcome),
+    Summary(&'a RunReport),
+}
+
+/// Receives per-file outcomes as a run progresses so they are never all held in memory
+///
+/// Outcomes are appended to an NDJSON file (CLI `--report`) and, for runs started through
+/// the API, stored in the `Run_Files` table for the paginated and streaming endpoints.
+pub struct ReportSink {
+    ndjson: Option<Mutex<BufWriter<File>>>,
+    run_id: Option<String>,
+    write_failed: AtomicBool,
+}
+
+impl ReportSink {
+    /// Create a sink writing to `report_file` and/or the database under `run_id`
+    pub fn new(report_file: Option<&Path>, run_id: Option<String>) -> Result<Self> {
+        let ndjson = match report_file {
+            Some(path) => Some(Mutex::new(BufWriter::new(File::create(path)?))),
+            None => None,
+        };
+
+        Ok(ReportSink {
+            ndjson,
+            run_id,
+            write_failed: AtomicBool::new(false),
+        })
+    }
+
+    /// Record the outcome of one file copy
+    pub fn record(&self, outcome: FileOutcome) {
+        if let Some(run_id) = &self.run_id {
+            if let Err(e) = insert_run_file(run_id, &outcome) {
+                self.warn_once(&e.to_string());
+            }
+        }
+        self.write_line(&ReportLine::File(&outcome));
+    }
+
+    /// Append the run summary and flush the report file
+    pub fn finish(&self, report: &RunReport) {
+        self.write_line(&ReportLine::Summary(report));
+        if let Some(writer) = &self.ndjson {
+            if let Err(e) = writer.lock().unwrap().flush() {
+                self.warn_once(&e.to_string());
+            }
+        }
+    }
+
+    fn write_line(&self, line: &ReportLine) {
+        let Some(writer) = &self.ndjson else {
+            return;
+        };
+
+        let result = serde_json::to_string(line)
+            .map_err(std::io::Error::other)
+            .and_then(|json| writeln!(writer.lock().unwrap(), "{}", json));
+        if let Err(e) = result {
+            self.warn_once(&e.to_string());
+        }
+    }
+
+    /// Report failures once per run rather than once per file
+    fn warn_once(&self, message: &str) {
+        if !self.write_failed.swap(true, Ordering::Relaxed) {
+            warn!("Failed to record run report entry: {}", message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::run_report::FileStatus;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_report_sink_writes_ndjson_lines() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("report.ndjson");
+
+        let sink = ReportSink::new(Some(&path), None).unwrap();
+        sink.record(FileOutcome {
+            source: "photos".to_string(),
+            file: "/photos/a.jpg".to_string(),
+            destination: "/mnt/nas/photos/a.jpg".to_string(),
+            status: FileStatus::Copied,
+            bytes: 3,
+            physical_bytes: None,
+            error: None,
+        });
+        sink.finish(&RunReport::default());
+        drop(sink);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["type"], "file");
+        assert_eq!(lines[0]["status"], "copied");
+        assert_eq!(lines[1]["type"], "summary");
+        assert!(lines[1]["sources"].is_array());
+    }
+}
@@ -0,0 +1,366 @@
+use crate::models::config::Config;
+use crate::models::error::{BackupError, Result};
+use crate::models::restore_plan::{RestoreItem, RestoreOutcome, RestorePlan};
+use crate::models::tombstone::Tombstone;
+use crate::repo::catalog::{select_backups_of_source, select_sources};
+use crate::repo::sqlite::{select_backup_write_times, select_run, select_tombstones};
+use crate::service::trash::TRASH_DIR;
+use crate::utils::directory::long_path;
+use chrono::DateTime;
+use log::{info, warn};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use walkdir::WalkDir;
+
+/// The Unix timestamp `as_of` names: the end of the run with that ID, an RFC 3339 time or a Unix
+/// timestamp, along with the run if it was one
+pub fn resolve_as_of(as_of: &str) -> Result<(i64, Option<String>)> {
+    if let Some(run) = select_run(as_of)? {
+        return Ok((run.finished_at, Some(run.id)));
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(as_of) {
+        return Ok((time.timestamp(), None));
+    }
+    as_of.parse::<i64>().map(|time| (time, None)).map_err(|_| {
+        BackupError::Restore(format!(
+            "{} is neither a run ID, an RFC 3339 time nor a Unix timestamp",
+            as_of
+        ))
+    })
+}
+
+/// Pick, for each catalogued source file below `under`, the latest backup written at or before
+/// `as_of`, whether the current one or an earlier version kept in trash
+///
+/// A backup counts as written when the run that copied it finished, the current one by the
+/// latest run to write its path and each trashed version by the run before the one that replaced
+/// it. Current backups whose runs have left history fall back to the source's catalogued
+/// modification time, which the backup has matched since; trashed versions without a run are left
+/// out. Files whose tombstone says they were deleted by then are listed as deleted instead.
+pub fn build_restore_plan(
+    config: &Config,
+    as_of: i64,
+    run: Option<String>,
+    under: Option<&str>,
+) -> Result<RestorePlan> {
+    let tombstones = select_tombstones(false)?;
+    let deleted: HashSet<&str> = tombstones
+        .iter()
+        .filter(|tombstone| deleted_at(tombstone, as_of))
+        .map(|tombstone| tombstone.path.as_str())
+        .collect();
+    let written = select_backup_write_times()?;
+    let trashed = trashed_versions(config);
+    let mut plan = RestorePlan {
+        as_of,
+        run,
+        ..RestorePlan::default()
+    };
+    let mut seen = HashSet::new();
+    for (source, _) in select_sources(under)? {
+        let path = Path::new(&source.file_path)
+            .join(&source.file_name)
+            .to_string_lossy()
+            .to_string();
+        if !seen.insert(path.clone()) {
+            continue;
+        }
+        if deleted.contains(path.as_str()) {
+            plan.deleted.push(path);
+            continue;
+        }
+
+        let mut versions = Vec::new();
+        for backup in select_backups_of_source(source.id)? {
+            let backup_path = Path::new(&backup.file_path).join(&backup.file_name);
+            let runs = written
+                .get(backup_path.to_string_lossy().as_ref())
+                .map_or(&[][..], Vec::as_slice);
+            let current_at = runs
+                .first()
+                .copied()
+                .unwrap_or(backup.last_modified.as_secs() as i64);
+            if let Some(earlier) = trashed.get(&backup_path) {
+                versions.extend(
+                    earlier
+                        .iter()
+                        .zip(runs.iter().skip(1))
+                        .map(|(version, &at)| (at, false, version.clone())),
+                );
+            }
+            versions.push((current_at, true, backup_path));
+        }
+        let chosen = versions
+            .into_iter()
+            .filter(|(backed_up_at, _, _)| *backed_up_at <= as_of)
+            .max();
+        match chosen {
+            Some((backed_up_at, current, backup)) => plan.files.push(RestoreItem {
+                path,
+                backup: backup.to_string_lossy().to_string(),
+                backed_up_at,
+                from_trash: !current,
+            }),
+            None => plan.unavailable.push(path),
+        }
+    }
+    Ok(plan)
+}
+
+/// Whether the file was deleted at `as_of` and hadn't come back yet
+fn deleted_at(tombstone: &Tombstone, as_of: i64) -> bool {
+    tombstone.deleted_at <= as_of && tombstone.returned_at.is_none_or(|at| at > as_of)
+}
+
+/// The versions kept in each destination's trash, by the backup path they were replaced at, the
+/// latest replaced first
+///
+/// A version trashed twice the same day has `.1`, `.2` and so on added to its name, so it is
+/// counted for the path without them too, after the version trashed there earlier that day.
+fn trashed_versions(config: &Config) -> HashMap<PathBuf, Vec<PathBuf>> {
+    let mut versions: HashMap<PathBuf, Vec<(String, u64, PathBuf)>> = HashMap::new();
+    for destination in &config.backup_destinations {
+        let root = Path::new(destination.path());
+        let trash = root.join(TRASH_DIR);
+        if !long_path(&trash).is_dir() {
+            continue;
+        }
+        for entry in WalkDir::new(&trash)
+            .min_depth(2)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+        {
+            let Ok(relative) = entry.path().strip_prefix(&trash) else {
+                continue;
+            };
+            // The day folder comes first
+            let mut components = relative.components();
+            let day = components
+                .next()
+                .map(|day| day.as_os_str().to_string_lossy().to_string())
+                .unwrap_or_default();
+            let replaced_at = root.join(components.collect::<PathBuf>());
+            if let Some((unnumbered, number)) = without_number(&replaced_at) {
+                versions.entry(unnumbered).or_default().push((
+                    day.clone(),
+                    number,
+                    entry.path().to_path_buf(),
+                ));
+            }
+            versions
+                .entry(replaced_at)
+                .or_default()
+                .push((day, 0, entry.path().to_path_buf()));
+        }
+    }
+    versions
+        .into_iter()
+        .map(|(replaced_at, mut trashed)| {
+            trashed.sort_unstable_by(|a, b| b.cmp(a));
+            let trashed = trashed.into_iter().map(|(_, _, version)| version).collect();
+            (replaced_at, trashed)
+        })
+        .collect()
+}
+
+/// `path` without a `.<number>` ending added to keep a version from overwriting another, along
+/// with the number
+fn without_number(path: &Path) -> Option<(PathBuf, u64)> {
+    let name = path.file_name()?.to_str()?;
+    let (stem, number) = name.rsplit_once('.')?;
+    if stem.is_empty() || !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((path.with_file_name(stem), number.parse().ok()?))
+}
+
+/// Copy each file of the plan to its path under `target`, leaving files already there alone
+///
+/// A source's full path is kept below the target, so `/home/me/a.txt` goes to
+/// `<target>/home/me/a.txt`.
+pub fn carry_out_restore(plan: &RestorePlan, target: &Path) -> RestoreOutcome {
+    let mut outcome = RestoreOutcome {
+        target: target.to_string_lossy().to_string(),
+        ..RestoreOutcome::default()
+    };
+    for item in &plan.files {
+        let destination = restore_path(target, Path::new(&item.path));
+        if fs::exists(long_path(&destination)).unwrap_or(true) {
+            outcome.skipped += 1;
+            continue;
+        }
+        let copied = destination
+            .parent()
+            .map_or(Ok(()), |parent| fs::create_dir_all(long_path(parent)))
+            .and_then(|_| fs::copy(long_path(Path::new(&item.backup)), long_path(&destination)));
+        match copied {
+            Ok(_) => outcome.restored += 1,
+            Err(e) => {
+                warn!(
+                    "Failed to restore {} from {}: {}",
+                    item.path, item.backup, e
+                );
+                outcome.failed += 1;
+            }
+        }
+    }
+    info!(
+        "Restored {} file(s) to {:?}, {} already there, {} failed",
+        outcome.restored, target, outcome.skipped, outcome.failed
+    );
+    outcome
+}
+
+/// Where a source file is restored to under `target`, its drive or root left out
+fn restore_path(target: &Path, source: &Path) -> PathBuf {
+    source
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .fold(target.to_path_buf(), |path, component| path.join(component))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::backup_row::BackupRow;
+    use crate::models::run_record::RunRecord;
+    use crate::models::run_report::{FileOutcome, FileStatus};
+    use crate::models::source_row::SourceRow;
+    use crate::repo::catalog::{insert_backup_row, insert_source_row};
+    use crate::repo::sqlite::{
+        insert_run, insert_run_file, insert_tombstones, set_db_pool, setup_database,
+    };
+    use serial_test::serial;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    /// Record a run finished at `at` that copied a file to each of `backups`
+    fn copied_at(at: i64, backups: &[&Path]) {
+        let id = format!("run-{}", at);
+        insert_run(&RunRecord::finished(&id, at - 10, at, false, None, None)).unwrap();
+        for backup in backups {
+            insert_run_file(
+                &id,
+                &FileOutcome {
+                    source: "photos".to_string(),
+                    file: "/photos/a.jpg".to_string(),
+                    destination: backup.to_string_lossy().to_string(),
+                    status: FileStatus::Copied,
+                    bytes: 5,
+                    physical_bytes: None,
+                    error: None,
+                },
+            )
+            .unwrap();
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_restore_plan_picks_the_version_backed_up_by_the_time_asked_for() {
+        let database = TempDir::new().unwrap();
+        set_db_pool(database.path().join("backup.db").to_str().unwrap()).unwrap();
+        setup_database().unwrap();
+        let destination = TempDir::new().unwrap();
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "database_file": database.path().join("backup.db"),
+            "backup_sources": [{"parent_directory": "/photos"}],
+            "backup_destinations": [destination.path()]
+        }))
+        .unwrap();
+        let backup = |name: &str, content: &str, at: u64| {
+            let id = insert_source_row(&SourceRow {
+                id: 0,
+                file_name: name.to_string(),
+                file_path: "/photos".to_string(),
+                hash: content.to_string(),
+                file_size: content.len() as u64,
+                last_modified: Duration::from_secs(at),
+            })
+            .unwrap();
+            let path = destination.path().join("photos").join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, content).unwrap();
+            insert_backup_row(BackupRow {
+                source_id: id,
+                file_name: name.to_string(),
+                file_path: path.parent().unwrap().to_string_lossy().to_string(),
+                last_modified: Duration::from_secs(at),
+                original_path: None,
+                physical_size: None,
+            })
+            .unwrap();
+        };
+        // Only the catalog and the runs say when each was written, not the files' own times
+        backup("a.jpg", "third", 2900);
+        backup("b.jpg", "kept", 1000);
+        backup("c.jpg", "later", 0);
+        let photos = destination.path().join("photos");
+        copied_at(1000, &[&photos.join("a.jpg")]);
+        copied_at(2000, &[&photos.join("a.jpg")]);
+        copied_at(3000, &[&photos.join("a.jpg")]);
+        copied_at(5000, &[&photos.join("c.jpg")]);
+        // Two earlier versions of a.jpg, replaced the same day
+        let day = destination.path().join(TRASH_DIR).join("2025-01-01/photos");
+        fs::create_dir_all(&day).unwrap();
+        for (name, content) in [("a.jpg", "first"), ("a.jpg.1", "second")] {
+            fs::write(day.join(name), content).unwrap();
+        }
+        insert_tombstones(&[Tombstone {
+            path: "/photos/b.jpg".to_string(),
+            hash: "kept".to_string(),
+            file_size: 4,
+            deleted_at: 2500,
+            run_id: "run-1".to_string(),
+            returned_at: None,
+        }])
+        .unwrap();
+
+        let plan = build_restore_plan(&config, 2200, None, None).unwrap();
+        assert_eq!(plan.files.len(), 2);
+        assert_eq!(
+            (plan.files[0].path.as_str(), plan.files[0].backed_up_at),
+            ("/photos/a.jpg", 2000)
+        );
+        assert!(plan.files[0].from_trash);
+        assert_eq!(plan.files[1].path, "/photos/b.jpg");
+        assert_eq!(plan.unavailable, vec!["/photos/c.jpg".to_string()]);
+
+        let plan = build_restore_plan(&config, 4000, None, None).unwrap();
+        assert_eq!(plan.files.len(), 1);
+        assert_eq!(plan.files[0].backed_up_at, 3000);
+        assert!(!plan.files[0].from_trash);
+        assert_eq!(plan.deleted, vec!["/photos/b.jpg".to_string()]);
+
+        let target = TempDir::new().unwrap();
+        let plan = build_restore_plan(&config, 1500, None, None).unwrap();
+        let outcome = carry_out_restore(&plan, target.path());
+        assert_eq!((outcome.restored, outcome.skipped), (2, 0));
+        let restored = target.path().join("photos");
+        assert_eq!(fs::read_to_string(restored.join("a.jpg")).unwrap(), "first");
+        assert_eq!(fs::read_to_string(restored.join("b.jpg")).unwrap(), "kept");
+        assert_eq!(carry_out_restore(&plan, target.path()).skipped, 2);
+
+        assert_eq!(resolve_as_of("1500").unwrap(), (1500, None));
+        assert_eq!(
+            resolve_as_of("1970-01-01T01:00:00+01:00").unwrap(),
+            (0, None)
+        );
+        assert!(resolve_as_of("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_numbered_trash_versions_and_restore_paths() {
+        assert_eq!(
+            without_number(Path::new("/d/a.jpg.2")),
+            Some(("/d/a.jpg".into(), 2))
+        );
+        assert_eq!(without_number(Path::new("/d/a.jpg")), None);
+        assert_eq!(
+            restore_path(Path::new("/restore"), Path::new("/home/me/a.txt")),
+            PathBuf::from("/restore/home/me/a.txt")
+        );
+    }
+}
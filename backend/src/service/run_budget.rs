@@ -0,0 +1,252 @@
+use crate::models::config::Config;
+use crate::models::run_report::{BudgetExhausted, BudgetLimit};
+use crate::models::run_selection::SelectedSource;
+use crate::repo::sqlite::{replace_run_backlog, select_run_backlog};
+use log::{info, warn};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// How much of its time and byte budget a run has used, and the files it left once it ran out
+pub struct RunBudget {
+    started: Instant,
+    max_duration: Option<Duration>,
+    max_bytes: Option<u64>,
+    bytes_spent: AtomicU64,
+    exhausted: OnceLock<BudgetLimit>,
+    /// Files earlier runs left, offered to this run before any others
+    backlog: Vec<PathBuf>,
+    /// The run the files it leaves are saved for, unless they aren't saved
+    saved_for: Option<String>,
+    batch_size: usize,
+    /// Files left and not yet saved
+    deferred: Mutex<Vec<String>>,
+    files_deferred: AtomicU64,
+}
+
+impl RunBudget {
+    /// Start the budget of run `run_id` over `sources`, with the files earlier runs left under
+    /// them; the files it leaves are saved for the next run if `save`
+    pub fn start(config: &Config, sources: &[SelectedSource], run_id: &str, save: bool) -> Self {
+        let backlog = match select_run_backlog() {
+            Ok(backlog) => backlog
+                .into_iter()
+                .map(PathBuf::from)
+                .filter(|file| sources.iter().any(|s| file.starts_with(&s.scan_root)))
+                .collect(),
+            Err(e) => {
+                warn!("Failed to load the files earlier runs left: {}", e);
+                Vec::new()
+            }
+        };
+        if !backlog.is_empty() {
+            info!(
+                "Resuming with {} file(s) earlier runs ran out of budget for",
+                backlog.len()
+            );
+        }
+        RunBudget {
+            backlog,
+            saved_for: save.then(|| run_id.to_string()),
+            batch_size: config.candidate_batch_size.max(1),
+            ..RunBudget::new(
+                config
+                    .max_run_duration_minutes
+                    .map(|m| Duration::from_secs(m * 60)),
+                config.max_bytes_per_run,
+            )
+        }
+    }
+
+    fn new(max_duration: Option<Duration>, max_bytes: Option<u64>) -> Self {
+        RunBudget {
+            started: Instant::now(),
+            max_duration,
+            max_bytes,
+            bytes_spent: AtomicU64::new(0),
+            exhausted: OnceLock::new(),
+            backlog: Vec::new(),
+            saved_for: None,
+            batch_size: usize::MAX,
+            deferred: Mutex::new(Vec::new()),
+            files_deferred: AtomicU64::new(0),
+        }
+    }
+
+    /// A budget that never runs out and has no backlog
+    pub fn unlimited() -> Self {
+        RunBudget::new(None, None)
+    }
+
+    /// Files to offer before walking the sources
+    pub fn backlog(&self) -> &[PathBuf] {
+        &self.backlog
+    }
+
+    /// Whether the run is out of time or has already run out of bytes
+    pub fn is_exhausted(&self) -> bool {
+        if self.exhausted.get().is_some() {
+            return true;
+        }
+        if self
+            .max_duration
+            .is_some_and(|max| self.started.elapsed() >= max)
+        {
+            self.exhaust(BudgetLimit::Duration);
+            return true;
+        }
+        false
+    }
+
+    /// Take `bytes` from the byte budget, or run out if they don't fit
+    ///
+    /// The first copy of a run always fits, so a file larger than the whole budget isn't left
+    /// behind forever.
+    pub fn spend(&self, bytes: u64) -> bool {
+        if self.is_exhausted() {
+            return false;
+        }
+        let Some(max) = self.max_bytes else {
+            return true;
+        };
+        let spent = self
+            .bytes_spent
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |spent| {
+                (spent == 0 || spent.saturating_add(bytes) <= max).then(|| spent + bytes)
+            });
+        if spent.is_err() {
+            self.exhaust(BudgetLimit::Bytes);
+        }
+        spent.is_ok()
+    }
+
+    fn exhaust(&self, limit: BudgetLimit) {
+        if self.exhausted.set(limit).is_ok() {
+            warn!(
+                "Run is out of its {} budget, leaving the remaining files for the next run",
+                limit.as_str()
+            );
+        }
+    }
+
+    /// Leave `file` for the next run
+    ///
+    /// Every `candidate_batch_size` files left are saved as they come, so a run that runs out
+    /// early doesn't hold the rest of its sources in memory.
+    pub fn defer(&self, file: &Path) {
+        self.files_deferred.fetch_add(1, Ordering::Relaxed);
+        if self.saved_for.is_none() {
+            return;
+        }
+        let mut deferred = self.deferred.lock().unwrap();
+        deferred.push(file.to_string_lossy().to_string());
+        if deferred.len() >= self.batch_size {
+            let batch = std::mem::take(&mut *deferred);
+            drop(deferred);
+            self.save(&[], &batch);
+        }
+    }
+
+    /// Save the files the run left in place of the backlog it was offered
+    pub fn finish(self) -> Option<BudgetExhausted> {
+        let deferred = std::mem::take(&mut *self.deferred.lock().unwrap());
+        let offered: Vec<String> = self
+            .backlog
+            .iter()
+            .map(|file| file.to_string_lossy().to_string())
+            .collect();
+        self.save(&offered, &deferred);
+        let limit = *self.exhausted.get()?;
+        let files_deferred = self.files_deferred.load(Ordering::Relaxed);
+        info!("{} file(s) left for the next run", files_deferred);
+        Some(BudgetExhausted {
+            limit,
+            files_deferred,
+        })
+    }
+
+    fn save(&self, offered: &[String], deferred: &[String]) {
+        let Some(run_id) = &self.saved_for else {
+            return;
+        };
+        if let Err(e) = replace_run_backlog(run_id, offered, deferred) {
+            warn!("Failed to record the files left for the next run: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::sqlite::{set_db_pool, setup_database};
+    use serial_test::serial;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_byte_budget_runs_out_at_the_first_copy_that_does_not_fit() {
+        let budget = RunBudget::new(None, Some(100));
+        // The first copy fits even when it is larger than the whole budget
+        assert!(budget.spend(150));
+        assert!(!budget.spend(1));
+        assert!(budget.is_exhausted());
+
+        let budget = RunBudget::new(None, Some(100));
+        assert!(budget.spend(60));
+        assert!(budget.spend(40));
+        assert!(!budget.spend(1));
+        // Smaller files that would still fit are left too, so the run stops where it ran out
+        assert!(!budget.spend(0));
+    }
+
+    #[test]
+    fn test_time_budget_runs_out() {
+        let budget = RunBudget::new(Some(Duration::ZERO), None);
+        assert!(budget.is_exhausted());
+        assert!(!budget.spend(1));
+        assert!(!RunBudget::unlimited().is_exhausted());
+    }
+
+    #[test]
+    #[serial]
+    fn test_deferred_files_replace_the_backlog_offered() {
+        let database = TempDir::new().unwrap();
+        set_db_pool(database.path().join("backup.db").to_str().unwrap()).unwrap();
+        setup_database().unwrap();
+        replace_run_backlog(
+            "earlier",
+            &[],
+            &["/photos/a.jpg".to_string(), "/music/b.mp3".to_string()],
+        )
+        .unwrap();
+
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "database_file": "",
+            "backup_sources": [],
+            "backup_destinations": [],
+            "max_bytes_per_run": 10
+        }))
+        .unwrap();
+        let photos = SelectedSource {
+            source: Default::default(),
+            scan_root: "/photos".to_string(),
+            max_depth: None,
+        };
+        let budget = RunBudget::start(&config, &[photos], "run", true);
+        assert_eq!(budget.backlog(), [PathBuf::from("/photos/a.jpg")]);
+
+        assert!(budget.spend(10));
+        assert!(!budget.spend(10));
+        budget.defer(Path::new("/photos/c.jpg"));
+        // Left again, so it stays in the backlog
+        budget.defer(Path::new("/photos/a.jpg"));
+        let exhausted = budget.finish().unwrap();
+        assert_eq!(exhausted.limit, BudgetLimit::Bytes);
+        assert_eq!(exhausted.files_deferred, 2);
+
+        assert_eq!(
+            select_run_backlog().unwrap(),
+            ["/music/b.mp3", "/photos/c.jpg", "/photos/a.jpg"]
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use crate::models::config::Config;
+use crate::models::dry_run_mode::DryRunMode;
+use crate::models::error::{BackupError, Result};
+use crate::models::run_diff::{RunDiff, RunFileAction};
+use crate::repo::sqlite::{
+    count_run_file_actions, insert_run_file_actions, select_run, select_run_diff,
+};
+use log::warn;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// File actions held before they are written in one transaction
+const ACTION_BATCH_SIZE: usize = 1000;
+
+/// Collects what a run found of each source file and writes it to `Run_File_Actions` in batches
+///
+/// Dry runs, and every run with `file_action_runs` at 0, record nothing.
+pub struct FileActionLog {
+    run_id: Option<String>,
+    pending: Mutex<Vec<RunFileAction>>,
+    write_failed: AtomicBool,
+}
+
+impl FileActionLog {
+    pub fn new(run_id: &str, config: &Config, dry_run_mode: DryRunMode) -> Self {
+        let records = config.file_action_runs > 0 && dry_run_mode.should_update_database();
+        FileActionLog {
+            run_id: records.then(|| run_id.to_string()),
+            pending: Mutex::new(Vec::new()),
+            write_failed: AtomicBool::new(false),
+        }
+    }
+
+    pub fn record(&self, action: RunFileAction) {
+        if self.run_id.is_none() {
+            return;
+        }
+        let mut pending = self.pending.lock().unwrap();
+        pending.push(action);
+        if pending.len() >= ACTION_BATCH_SIZE {
+            let batch = std::mem::take(&mut *pending);
+            drop(pending);
+            self.write(&batch);
+        }
+    }
+
+    /// Whether every action so far was written, so the run's records can be compared
+    pub fn records(&self) -> bool {
+        self.run_id.is_some() && !self.write_failed.load(Ordering::Relaxed)
+    }
+
+    /// Write the actions still held
+    pub fn finish(&self) {
+        let batch = std::mem::take(&mut *self.pending.lock().unwrap());
+        self.write(&batch);
+    }
+
+    fn write(&self, batch: &[RunFileAction]) {
+        let Some(run_id) = &self.run_id else {
+            return;
+        };
+        if batch.is_empty() {
+            return;
+        }
+        if let Err(e) = insert_run_file_actions(run_id, batch) {
+            // Once per run rather than once per batch
+            if !self.write_failed.swap(true, Ordering::Relaxed) {
+                warn!("Failed to record file actions of run {}: {}", run_id, e);
+            }
+        }
+    }
+}
+
+/// Compare what two runs found: the files new in the later one, changed since the earlier one,
+/// and gone from the sources the later one backed up
+///
+/// The runs may be given in either order. Each group lists at most `limit` paths.
+pub fn diff_runs(first: &str, second: &str, limit: usize) -> Result<RunDiff> {
+    if first == second {
+        return Err(BackupError::RunDiff(
+            "a run can only be compared with another run".to_string(),
+        ));
+    }
+    let mut runs = Vec::new();
+    for id in [first, second] {
+        let run = select_run(id)?
+            .ok_or_else(|| BackupError::RunDiff(format!("no run {} is recorded", id)))?;
+        if count_run_file_actions(id)? == 0 {
+            return Err(BackupError::RunDiff(format!(
+                "run {} has no file actions recorded; dry runs, failed runs and runs older than \
+                 the last file_action_runs have none",
+                id
+            )));
+        }
+        runs.push(run);
+    }
+    runs.sort_by_key(|run| run.started_at);
+    select_run_diff(&runs[0], &runs[1], limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::run_diff::FileAction;
+    use crate::models::run_record::RunRecord;
+    use crate::repo::sqlite::{insert_run, prune_run_file_actions, set_db_pool, setup_database};
+    use serial_test::serial;
+    use tempfile::TempDir;
+
+    fn record_run(config: &Config, id: &str, started_at: i64, files: &[(&str, FileAction)]) {
+        insert_run(&RunRecord::finished(
+            id,
+            started_at,
+            started_at + 1,
+            false,
+            None,
+            None,
+        ))
+        .unwrap();
+        let log = FileActionLog::new(id, config, DryRunMode::None);
+        for (file, action) in files {
+            let source = if file.starts_with("/docs") {
+                "docs"
+            } else {
+                "photos"
+            };
+            log.record(RunFileAction {
+                source: source.to_string(),
+                file: file.to_string(),
+                action: *action,
+            });
+        }
+        log.finish();
+    }
+
+    #[test]
+    #[serial]
+    fn test_diff_runs_finds_new_changed_and_deleted_files() {
+        let dir = TempDir::new().unwrap();
+        let database = dir.path().join("backup.db").to_string_lossy().to_string();
+        set_db_pool(&database).unwrap();
+        setup_database().unwrap();
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "database_file": database,
+            "backup_sources": [],
+            "backup_destinations": []
+        }))
+        .unwrap();
+
+        use FileAction::*;
+        record_run(
+            &config,
+            "first",
+            1_000,
+            &[
+                ("/photos/a.jpg", Copied),
+                ("/photos/b.jpg", Copied),
+                ("/photos/c.jpg", Copied),
+                ("/docs/d.txt", Copied),
+            ],
+        );
+        // b.jpg changed here and was backed up already, so the last run skips it
+        record_run(
+            &config,
+            "between",
+            2_000,
+            &[
+                ("/photos/a.jpg", Skipped),
+                ("/photos/b.jpg", Updated),
+                ("/photos/c.jpg", Skipped),
+                ("/docs/d.txt", Skipped),
+            ],
+        );
+        // A run of the photos alone says nothing about the documents
+        record_run(
+            &config,
+            "last",
+            3_000,
+            &[
+                ("/photos/a.jpg", Updated),
+                ("/photos/b.jpg", Skipped),
+                ("/photos/e.jpg", Copied),
+            ],
+        );
+
+        let diff = diff_runs("last", "first", 100).unwrap();
+        assert_eq!(
+            (diff.from_run.as_str(), diff.to_run.as_str()),
+            ("first", "last")
+        );
+        assert_eq!((diff.files_before, diff.files_after), (4, 3));
+        assert_eq!(diff.new, vec!["/photos/e.jpg"]);
+        assert_eq!(diff.changed, vec!["/photos/a.jpg", "/photos/b.jpg"]);
+        assert_eq!(diff.deleted, vec!["/photos/c.jpg"]);
+        assert_eq!(diff.changed_percent, 75.0);
+
+        let limited = diff_runs("first", "last", 1).unwrap();
+        assert_eq!(limited.changed_files, 2);
+        assert_eq!(limited.changed, vec!["/photos/a.jpg"]);
+
+        assert!(diff_runs("first", "first", 10).is_err());
+        assert!(diff_runs("first", "unknown", 10).is_err());
+
+        // Only the most recent runs keep their actions
+        prune_run_file_actions(2).unwrap();
+        assert_eq!(count_run_file_actions("first").unwrap(), 0);
+        assert!(matches!(
+            diff_runs("first", "last", 10),
+            Err(BackupError::RunDiff(_))
+        ));
+        assert_eq!(diff_runs("between", "last", 10).unwrap().changed_files, 1);
+    }
+}
@@ -0,0 +1,378 @@
+use crate::models::config::{Config, HashLimit, ScrubConfig};
+use crate::models::error::{BackupError, Result};
+use crate::models::file_operation::{FileOperation, FileOperationEntry};
+use crate::models::scrub::{ScrubCandidate, ScrubProblem, ScrubProblemKind, ScrubReport};
+use crate::repo::catalog::{select_scrub_candidates, update_backup_verified};
+use crate::repo::sqlite::{delete_scrub_problem, format_bytes, upsert_scrub_problem};
+use crate::service::hash::hash_file;
+use crate::service::operation_log::record_file_operation;
+use crate::utils::directory::get_file_last_modified;
+use crate::utils::run_lock::RunLock;
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use log::{error, info, warn};
+use std::hash::{BuildHasher, RandomState};
+use std::io::ErrorKind;
+use std::path::Path;
+use std::str::FromStr;
+
+const GIBIBYTE: f64 = (1024 * 1024 * 1024) as f64;
+
+/// Read back the backups verified longest ago and check them against their source's hash
+///
+/// Only backups under the configured destinations are considered. Stops after `percent` of
+/// them or once `max_gibibytes` have been read, whichever comes first. Backups found intact are
+/// stamped with the time, so the next scrub carries on with the others; problems are kept in
+/// `Scrub_Problems` for `repair`.
+pub fn scrub_backups(
+    config: &Config,
+    scrub: &ScrubConfig,
+    wait_for_lock: bool,
+) -> Result<ScrubReport> {
+    // A backup rewriting a file while it is read would look like a changed backup
+    let _run_lock = RunLock::acquire(&config.database_file, wait_for_lock)?;
+    let mut candidates = recorded_candidates(config)?;
+    candidates.sort_by(|a, b| {
+        (a.last_verified, &a.backup.file_path, &a.backup.file_name).cmp(&(
+            b.last_verified,
+            &b.backup.file_path,
+            &b.backup.file_name,
+        ))
+    });
+    let max_checked = scrub.percent.map(|percent| share_of(&candidates, percent));
+    let max_bytes = scrub.max_gibibytes.map(|gibibytes| gibibytes * GIBIBYTE);
+    info!(
+        "Scrubbing up to {} of {} recorded backup(s)",
+        describe_limit(max_checked, max_bytes),
+        candidates.len()
+    );
+
+    let report = check_backups(config, &candidates, max_checked, max_bytes, "scrub")?;
+    info!(
+        "Scrub checked {} backup(s), {}, skipped {} with newer sources, found {} problem(s)",
+        report.checked,
+        format_bytes(report.bytes_read),
+        report.skipped,
+        report.problems.len()
+    );
+    Ok(report)
+}
+
+/// Read back `percent` of the recorded backups, picked at random, as a run ends with
+/// `verify_sample_percent` set
+///
+/// Backups are stamped and problems kept as a scrub does, so each run adds a little assurance
+/// between scrubs. The run calling it holds the run lock already.
+pub fn verify_sample(config: &Config, percent: f64) -> Result<ScrubReport> {
+    let mut candidates = recorded_candidates(config)?;
+    let random = RandomState::new();
+    candidates.sort_by_cached_key(|candidate| {
+        random.hash_one((&candidate.backup.file_path, &candidate.backup.file_name))
+    });
+    let max_checked = share_of(&candidates, percent);
+    info!(
+        "Verifying a random sample of {} of {} recorded backup(s)",
+        max_checked.min(candidates.len() as u64),
+        candidates.len()
+    );
+
+    let report = check_backups(config, &candidates, Some(max_checked), None, "sample")?;
+    info!(
+        "Sample verification checked {} backup(s), {}, found {} problem(s)",
+        report.checked,
+        format_bytes(report.bytes_read),
+        report.problems.len()
+    );
+    Ok(report)
+}
+
+/// Recorded backups under the configured destinations
+fn recorded_candidates(config: &Config) -> Result<Vec<ScrubCandidate>> {
+    // A shared catalog also records other machines' backups, which aren't there to read
+    Ok(select_scrub_candidates()?
+        .into_iter()
+        .filter(|candidate| {
+            config
+                .destination_of(Path::new(&candidate.backup.file_path))
+                .is_some()
+        })
+        .collect())
+}
+
+/// `percent` of `candidates`, at least one
+fn share_of(candidates: &[ScrubCandidate], percent: f64) -> u64 {
+    ((candidates.len() as f64 * percent / 100.0).ceil() as u64).max(1)
+}
+
+/// Check `candidates` in order until `max_checked` are checked or `max_bytes` read, recording
+/// each check as `kind` in the file operation log
+fn check_backups(
+    config: &Config,
+    candidates: &[ScrubCandidate],
+    max_checked: Option<u64>,
+    max_bytes: Option<f64>,
+    kind: &str,
+) -> Result<ScrubReport> {
+    let mut report = ScrubReport {
+        started_at: chrono::Utc::now().timestamp(),
+        backups: candidates.len() as u64,
+        ..Default::default()
+    };
+
+    for candidate in candidates {
+        if max_checked.is_some_and(|max| report.checked >= max)
+            || max_bytes.is_some_and(|max| report.bytes_read as f64 >= max)
+        {
+            break;
+        }
+        // The source changed after this copy was made, so it no longer has the recorded hash
+        if candidate.source_last_modified > candidate.backup.last_modified {
+            report.skipped += 1;
+            continue;
+        }
+
+        let hash_limit = hash_limit_of(config, &candidate.source_path);
+        report.checked += 1;
+        report.bytes_read += hash_limit.bytes_hashed(candidate.file_size);
+        let checked = check_backup(candidate, hash_limit);
+        let backup = Path::new(&candidate.backup.file_path).join(&candidate.backup.file_name);
+        record_file_operation(
+            config,
+            FileOperationEntry {
+                source: Some(candidate.source_path.clone()),
+                backup: Some(backup.to_string_lossy().to_string()),
+                hash: Some(candidate.hash.clone()),
+                bytes: Some(candidate.file_size),
+                detail: Some(match &checked {
+                    Ok(()) => kind.to_string(),
+                    Err(problem) => {
+                        format!("{}: {}, {}", kind, problem.kind.as_str(), problem.detail)
+                    }
+                }),
+                ..FileOperationEntry::new(if checked.is_ok() {
+                    FileOperation::Verified
+                } else {
+                    FileOperation::VerificationFailed
+                })
+            },
+        );
+        match checked {
+            Ok(()) => {
+                update_backup_verified(
+                    candidate.backup.source_id,
+                    &candidate.backup.file_name,
+                    &candidate.backup.file_path,
+                    chrono::Utc::now().timestamp(),
+                )?;
+                delete_scrub_problem(&backup.to_string_lossy())?;
+            }
+            Err(problem) => {
+                error!(
+                    "Backup {} is {}, found by {}: {}",
+                    problem.backup,
+                    problem.kind.as_str(),
+                    kind,
+                    problem.detail
+                );
+                upsert_scrub_problem(&problem)?;
+                report.problems.push(problem);
+            }
+        }
+    }
+
+    report.finished_at = chrono::Utc::now().timestamp();
+    Ok(report)
+}
+
+/// When the configured scrub next runs after `after`, if one is configured
+pub fn next_scrub_after(config: &Config, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let schedule = Schedule::from_str(&config.scrub.as_ref()?.schedule).ok()?;
+    schedule.after(&after).next()
+}
+
+/// Scrub for a scheduler, waiting for a running backup and logging what goes wrong
+pub fn run_scheduled_scrub(config: &Config) -> Option<ScrubReport> {
+    let scrub = config.scrub.as_ref()?;
+    match scrub_backups(config, scrub, true) {
+        Ok(report) => Some(report),
+        Err(e) => {
+            warn!("Scheduled scrub failed: {}", e);
+            None
+        }
+    }
+}
+
+/// Hash a backup and compare it with its record
+fn check_backup(
+    candidate: &ScrubCandidate,
+    hash_limit: HashLimit,
+) -> std::result::Result<(), ScrubProblem> {
+    let path = Path::new(&candidate.backup.file_path).join(&candidate.backup.file_name);
+    let problem = |kind, detail: String| ScrubProblem {
+        backup: path.to_string_lossy().to_string(),
+        source: candidate.source_path.clone(),
+        kind,
+        detail,
+        found_at: chrono::Utc::now().timestamp(),
+    };
+    let unreadable = |e: BackupError| match &e {
+        BackupError::MetadataError { cause, .. } | BackupError::HashError { cause, .. }
+            if cause.kind() == ErrorKind::NotFound =>
+        {
+            problem(
+                ScrubProblemKind::Missing,
+                "no file at this path".to_string(),
+            )
+        }
+        _ => problem(ScrubProblemKind::Unreadable, e.to_string()),
+    };
+
+    let modified = get_file_last_modified(&path).map_err(unreadable)?;
+    if modified.as_secs() != candidate.backup.last_modified.as_secs() {
+        return Err(problem(
+            ScrubProblemKind::Changed,
+            format!(
+                "modified at {}, recorded at {}",
+                modified.as_secs(),
+                candidate.backup.last_modified.as_secs()
+            ),
+        ));
+    }
+    let hash = hash_file(&path, hash_limit).map_err(unreadable)?;
+    if hash != candidate.hash {
+        return Err(problem(
+            ScrubProblemKind::Corrupt,
+            format!("source hash {} != backup hash {}", candidate.hash, hash),
+        ));
+    }
+    Ok(())
+}
+
+/// Hash limit of the configured source a source file is under
+pub fn hash_limit_of(config: &Config, source_path: &str) -> HashLimit {
+    config
+        .backup_sources
+        .iter()
+        .filter(|source| Path::new(source_path).starts_with(&source.parent_directory))
+        .max_by_key(|source| source.parent_directory.len())
+        .map_or(config.hash_limit(), |source| {
+            config
+                .source_settings(Path::new(&source.parent_directory))
+                .hash_limit()
+        })
+}
+
+fn describe_limit(max_checked: Option<u64>, max_bytes: Option<f64>) -> String {
+    match (max_checked, max_bytes) {
+        (Some(count), Some(bytes)) => {
+            format!("{} backup(s) or {}", count, format_bytes(bytes as u64))
+        }
+        (Some(count), None) => format!("{} backup(s)", count),
+        (None, Some(bytes)) => format_bytes(bytes as u64),
+        (None, None) => "all".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::backup_row::BackupRow;
+    use crate::models::source_row::SourceRow;
+    use crate::repo::catalog::{insert_backup_row, insert_source_row};
+    use crate::repo::sqlite::{set_db_pool, setup_database};
+    use serial_test::serial;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    #[serial]
+    fn test_scrub_rotates_through_backups_and_finds_corruption() {
+        let dir = TempDir::new().unwrap();
+        let database = dir.path().join("backup.db").to_string_lossy().to_string();
+        set_db_pool(&database).unwrap();
+        setup_database().unwrap();
+        let source_dir = dir.path().join("source");
+        let backup_dir = dir.path().join("backup");
+        fs::create_dir_all(&source_dir).unwrap();
+        fs::create_dir_all(&backup_dir).unwrap();
+
+        for name in ["a.txt", "b.txt", "c.txt", "d.txt"] {
+            let contents = format!("contents of {}", name);
+            fs::write(source_dir.join(name), &contents).unwrap();
+            fs::write(backup_dir.join(name), &contents).unwrap();
+            let source_id = insert_source_row(&SourceRow {
+                id: 0,
+                file_name: name.to_string(),
+                file_path: source_dir.to_string_lossy().to_string(),
+                hash: hash_file(&source_dir.join(name), HashLimit::new(1)).unwrap(),
+                file_size: contents.len() as u64,
+                last_modified: get_file_last_modified(&source_dir.join(name)).unwrap(),
+            })
+            .unwrap();
+            insert_backup_row(BackupRow {
+                source_id,
+                file_name: name.to_string(),
+                file_path: backup_dir.to_string_lossy().to_string(),
+                last_modified: get_file_last_modified(&backup_dir.join(name)).unwrap(),
+                original_path: None,
+                physical_size: None,
+            })
+            .unwrap();
+        }
+
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "database_file": database,
+            "backup_sources": [{ "parent_directory": source_dir }],
+            "backup_destinations": [backup_dir]
+        }))
+        .unwrap();
+        let half = ScrubConfig {
+            schedule: "0 0 3 1 * *".to_string(),
+            percent: Some(50.0),
+            max_gibibytes: None,
+        };
+
+        // Rot a byte of a backup without touching its modification time
+        let rotten = backup_dir.join("c.txt");
+        let modified = fs::metadata(&rotten).unwrap().modified().unwrap();
+        fs::write(&rotten, "contents of c.tx!").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&rotten)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        fs::remove_file(backup_dir.join("d.txt")).unwrap();
+
+        let first = scrub_backups(&config, &half, false).unwrap();
+        assert_eq!((first.backups, first.checked), (4, 2));
+        assert!(first.problems.is_empty(), "{:?}", first.problems);
+
+        // The next scrub takes the two that weren't verified yet
+        let second = scrub_backups(&config, &half, false).unwrap();
+        assert_eq!(second.checked, 2);
+        let kinds: Vec<_> = second
+            .problems
+            .iter()
+            .map(|problem| (problem.kind, problem.backup.ends_with("c.txt")))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (ScrubProblemKind::Corrupt, true),
+                (ScrubProblemKind::Missing, false)
+            ]
+        );
+
+        // Problems aren't stamped, so they come up first again
+        let third = scrub_backups(&config, &half, false).unwrap();
+        assert_eq!(third.problems.len(), 2);
+
+        // A sample picks at random, so only how many it reads is known
+        let sample = verify_sample(&config, 25.0).unwrap();
+        assert_eq!((sample.backups, sample.checked), (4, 1));
+        let everything = verify_sample(&config, 100.0).unwrap();
+        assert_eq!(everything.checked, 4);
+        assert_eq!(everything.problems.len(), 2);
+    }
+}
@@ -0,0 +1,209 @@
+use crate::models::config::SqliteSnapshot;
+use crate::models::error::{BackupError, Result};
+use crate::service::post_process::{join_output, read_in_background, wait_with_timeout};
+use log::debug;
+use rusqlite::{Connection, OpenFlags, MAIN_DB};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// First bytes of every SQLite 3 database file
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// How long the backup API waits for a writer to release its lock
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Whether `path` is a SQLite database that another program has open
+///
+/// SQLite keeps a `-wal` file while a WAL database is open and a `-journal` file while a
+/// rollback-journal database is being written, so copying the file alone could tear it.
+pub fn is_live_database(path: &Path) -> bool {
+    has_sqlite_header(path)
+        && ["-wal", "-journal"]
+            .iter()
+            .any(|suffix| with_suffix(path, suffix).exists())
+}
+
+/// Whether `path` is the `-wal`, `-shm` or `-journal` file of a SQLite database
+pub fn is_database_sidecar(path: &Path) -> bool {
+    let name = path.as_os_str().to_string_lossy();
+    ["-wal", "-shm", "-journal"].iter().any(|suffix| {
+        name.strip_suffix(suffix)
+            .is_some_and(|database| has_sqlite_header(Path::new(database)))
+    })
+}
+
+/// Write a consistent, self-contained snapshot of the database at `source` to `target`
+///
+/// The snapshot is taken next to `target` and only replaces it once it passes SQLite's
+/// `quick_check`, so a failed snapshot leaves any earlier backup in place.
+pub fn snapshot_database(method: &SqliteSnapshot, source: &Path, target: &Path) -> Result<()> {
+    let file_name = target.file_name().unwrap_or_default().to_string_lossy();
+    let partial = target.with_file_name(format!(".{}.snapshot", file_name));
+    let failed = |reason: String| BackupError::SqliteSnapshot {
+        path: source.to_path_buf(),
+        reason,
+    };
+
+    let _ = fs::remove_file(&partial);
+    let taken = match method {
+        SqliteSnapshot::BackupApi => backup_api(source, &partial).map_err(|e| e.to_string()),
+        SqliteSnapshot::Command {
+            command,
+            args,
+            timeout_seconds,
+        } => run_command(command, args, *timeout_seconds, source, &partial),
+    }
+    .and_then(|_| check_snapshot(&partial).map_err(|e| e.to_string()));
+
+    if let Err(reason) = taken {
+        let _ = fs::remove_file(&partial);
+        return Err(failed(reason));
+    }
+    fs::rename(&partial, target)?;
+    debug!("Snapshot of {:?} written to {:?}", source, target);
+    Ok(())
+}
+
+fn backup_api(source: &Path, target: &Path) -> rusqlite::Result<()> {
+    let connection = Connection::open_with_flags(
+        source,
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )?;
+    connection.busy_timeout(BUSY_TIMEOUT)?;
+    connection.backup(MAIN_DB, target, None)
+}
+
+fn run_command(
+    command: &str,
+    args: &[String],
+    timeout_seconds: Option<u64>,
+    source: &Path,
+    target: &Path,
+) -> std::result::Result<(), String> {
+    let source = source.to_string_lossy();
+    let target = target.to_string_lossy();
+    let args = args.iter().map(|arg| {
+        arg.replace("{source}", &source)
+            .replace("{target}", &target)
+    });
+
+    let mut child = Command::new(command)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start {}: {}", command, e))?;
+    let stderr = child.stderr.take().map(read_in_background);
+
+    let status = match timeout_seconds {
+        Some(seconds) => wait_with_timeout(&mut child, Duration::from_secs(seconds)),
+        None => child.wait().map(Some),
+    }
+    .map_err(|e| format!("Failed to wait for {}: {}", command, e))?;
+
+    match status {
+        Some(status) if status.success() => Ok(()),
+        Some(status) => {
+            Err(join_output(stderr)
+                .unwrap_or_else(|| format!("{} exited with {}", command, status)))
+        }
+        None => {
+            let _ = child.kill();
+            let _ = child.wait();
+            Err(format!(
+                "{} timed out after {} seconds",
+                command,
+                timeout_seconds.unwrap_or_default()
+            ))
+        }
+    }
+}
+
+/// Switch the snapshot to a rollback journal so it is a single file, then check its integrity
+fn check_snapshot(path: &Path) -> rusqlite::Result<()> {
+    let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+    let _: String = connection.query_row("PRAGMA journal_mode = DELETE", [], |row| row.get(0))?;
+    let check: String = connection.query_row("PRAGMA quick_check", [], |row| row.get(0))?;
+    if check != "ok" {
+        return Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CORRUPT),
+            Some(check),
+        ));
+    }
+    Ok(())
+}
+
+fn has_sqlite_header(path: &Path) -> bool {
+    let mut header = [0u8; 16];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok_and(|_| &header == SQLITE_HEADER)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_live_database_is_snapshotted_with_its_wal() {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("app.db");
+        let live = Connection::open(&source).unwrap();
+        live.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE notes(body TEXT);
+             INSERT INTO notes VALUES ('only in the WAL so far');",
+        )
+        .unwrap();
+        fs::write(dir.path().join("plain.txt"), "SQLite format 3").unwrap();
+        assert!(is_live_database(&source));
+        assert!(!is_live_database(&dir.path().join("plain.txt")));
+        assert!(is_database_sidecar(&dir.path().join("app.db-wal")));
+        assert!(!is_database_sidecar(&dir.path().join("plain.txt-wal")));
+
+        let target = dir.path().join("backup.db");
+        snapshot_database(&SqliteSnapshot::BackupApi, &source, &target).unwrap();
+        drop(live);
+
+        assert!(!is_live_database(&target));
+        let snapshot = Connection::open(&target).unwrap();
+        let body: String = snapshot
+            .query_row("SELECT body FROM notes", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(body, "only in the WAL so far");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_failed_snapshot_command_keeps_the_earlier_backup() {
+        let dir = TempDir::new().unwrap();
+        let target = dir.path().join("backup.db");
+        fs::write(&target, "earlier backup").unwrap();
+        let failing = SqliteSnapshot::Command {
+            command: "sh".to_string(),
+            args: vec![
+                "-c".to_string(),
+                "echo database is locked >&2; exit 3".to_string(),
+                "{target}".to_string(),
+            ],
+            timeout_seconds: Some(5),
+        };
+
+        let err = snapshot_database(&failing, &dir.path().join("app.db"), &target).unwrap_err();
+        assert!(err.to_string().contains("database is locked"));
+        assert_eq!(fs::read_to_string(&target).unwrap(), "earlier backup");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,166 @@
+use crate::models::config::Config;
+use crate::models::error::Result;
+use crate::models::source_row::SourceRow;
+use crate::models::tombstone::Tombstone;
+use crate::repo::catalog::select_sources;
+use crate::repo::sqlite::{
+    close_tombstones, insert_tombstones, select_files_with_actions, select_tombstones,
+};
+use crate::utils::directory::long_path;
+use log::info;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// Catalogued files looked up in the run's file actions at once
+const LOOKUP_BATCH_SIZE: usize = 1000;
+
+/// Leave a tombstone for every catalogued file under the sources that is gone from its path,
+/// and close the tombstones of paths with a file at them again, returning how many were left
+///
+/// With `actions_recorded`, a file the run recorded an action for was found by its discovery, so
+/// only the catalogued files it didn't find, such as ones a filter now leaves out, are looked for
+/// on disk. A source whose directory is missing, such as an unplugged drive, is passed over
+/// rather than taken as every file in it deleted.
+pub fn record_tombstones(config: &Config, run_id: &str, actions_recorded: bool) -> Result<u64> {
+    let now = chrono::Utc::now().timestamp();
+    let open: HashSet<String> = select_tombstones(true)?
+        .into_iter()
+        .map(|tombstone| tombstone.path)
+        .collect();
+    let mut seen = HashSet::new();
+    let (mut deleted, mut returned) = (Vec::new(), Vec::new());
+    for source in &config.backup_sources {
+        if !long_path(Path::new(&source.parent_directory)).is_dir() {
+            continue;
+        }
+        let rows: Vec<(String, SourceRow)> = select_sources(Some(&source.parent_directory))?
+            .into_iter()
+            .map(|(row, _)| {
+                let path = Path::new(&row.file_path).join(&row.file_name);
+                (path.to_string_lossy().to_string(), row)
+            })
+            .filter(|(key, _)| seen.insert(key.clone()))
+            .collect();
+        for batch in rows.chunks(LOOKUP_BATCH_SIZE) {
+            let found = if actions_recorded {
+                let keys: Vec<String> = batch.iter().map(|(key, _)| key.clone()).collect();
+                select_files_with_actions(run_id, &keys)?
+            } else {
+                HashSet::new()
+            };
+            for (key, row) in batch {
+                // A file that can't be looked at is taken to be there
+                let exists =
+                    found.contains(key) || fs::exists(long_path(Path::new(key))).unwrap_or(true);
+                match (exists, open.contains(key)) {
+                    (true, true) => returned.push(key.clone()),
+                    (false, false) => deleted.push(Tombstone {
+                        path: key.clone(),
+                        hash: row.hash.clone(),
+                        file_size: row.file_size,
+                        deleted_at: now,
+                        run_id: run_id.to_string(),
+                        returned_at: None,
+                    }),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    close_tombstones(&returned, now)?;
+    insert_tombstones(&deleted)?;
+    if !deleted.is_empty() {
+        info!(
+            "{} source file(s) were deleted since the last run",
+            deleted.len()
+        );
+    }
+    Ok(deleted.len() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::run_diff::{FileAction, RunFileAction};
+    use crate::models::source_row::SourceRow;
+    use crate::repo::catalog::insert_source_row;
+    use crate::repo::sqlite::{insert_run_file_actions, set_db_pool, setup_database};
+    use serial_test::serial;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    #[serial]
+    fn test_tombstones_are_left_for_deleted_files_and_closed_when_they_return() {
+        let database = TempDir::new().unwrap();
+        set_db_pool(database.path().join("backup.db").to_str().unwrap()).unwrap();
+        setup_database().unwrap();
+        let source = TempDir::new().unwrap();
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "database_file": database.path().join("backup.db"),
+            "backup_sources": [{"parent_directory": source.path()}, {"parent_directory": "/gone"}],
+            "backup_destinations": []
+        }))
+        .unwrap();
+        let record = |name: &str, dir: &Path| {
+            insert_source_row(&SourceRow {
+                id: 0,
+                file_name: name.to_string(),
+                file_path: dir.to_string_lossy().to_string(),
+                hash: format!("hash-{}", name),
+                file_size: 3,
+                last_modified: Duration::from_secs(100),
+            })
+            .unwrap();
+        };
+        fs::write(source.path().join("kept.txt"), "abc").unwrap();
+        record("kept.txt", source.path());
+        record("deleted.txt", source.path());
+        // Under a source that isn't there, so not taken as deleted
+        record("offline.txt", Path::new("/gone"));
+
+        // A file the run recorded an action for is there, without looking
+        let prepared = |run_id: &str, name: &str| {
+            insert_run_file_actions(
+                run_id,
+                &[RunFileAction {
+                    source: "docs".to_string(),
+                    file: source.path().join(name).to_string_lossy().to_string(),
+                    action: FileAction::Skipped,
+                }],
+            )
+            .unwrap();
+        };
+        prepared("run-0", "deleted.txt");
+        assert_eq!(record_tombstones(&config, "run-0", true).unwrap(), 0);
+        assert!(select_tombstones(false).unwrap().is_empty());
+
+        prepared("run-1", "kept.txt");
+        assert_eq!(record_tombstones(&config, "run-1", true).unwrap(), 1);
+        // Still gone, so the open tombstone stands
+        assert_eq!(record_tombstones(&config, "run-2", false).unwrap(), 0);
+        let tombstones = select_tombstones(false).unwrap();
+        assert_eq!(tombstones.len(), 1);
+        assert!(tombstones[0].path.ends_with("deleted.txt"));
+        assert_eq!(
+            (tombstones[0].hash.as_str(), tombstones[0].run_id.as_str()),
+            ("hash-deleted.txt", "run-1")
+        );
+
+        fs::write(source.path().join("deleted.txt"), "abc").unwrap();
+        assert_eq!(record_tombstones(&config, "run-3", false).unwrap(), 0);
+        assert!(select_tombstones(true).unwrap().is_empty());
+
+        fs::remove_file(source.path().join("deleted.txt")).unwrap();
+        assert_eq!(record_tombstones(&config, "run-4", false).unwrap(), 1);
+        let tombstones = select_tombstones(false).unwrap();
+        assert_eq!(tombstones.len(), 2);
+        assert!(tombstones[0].returned_at.is_some());
+        assert_eq!(
+            (tombstones[1].run_id.as_str(), tombstones[1].returned_at),
+            ("run-4", None)
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use crate::models::config::Config;
+use crate::models::error::Result;
+use crate::models::file_operation::{FileOperation, FileOperationEntry};
+use crate::service::operation_log::record_file_operation;
+use crate::service::prune::{available_path, remove_empty_parents};
+use crate::utils::directory::long_path;
+use chrono::NaiveDate;
+use log::{info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Directory under each destination root that receives backups replaced by changed source files
+pub const TRASH_DIR: &str = ".trash";
+
+/// Name of the folder under `.trash/` a day's replaced backups go to
+const DAY_FORMAT: &str = "%Y-%m-%d";
+
+/// Move the backup at `backup_path` to the same relative path under its destination's
+/// `.trash/<date>/` before a changed source file replaces it, returning where it went
+///
+/// Nothing is moved without `trash_retention_days`, or when there is no backup there yet.
+pub fn trash_replaced_backup(config: &Config, backup_path: &Path) -> Result<Option<PathBuf>> {
+    if config.trash_retention_days.is_none() || !long_path(backup_path).is_file() {
+        return Ok(None);
+    }
+    let Some(destination) = config.destination_of(backup_path) else {
+        return Ok(None);
+    };
+    let root = Path::new(destination.path());
+    let relative = backup_path.strip_prefix(root).unwrap_or(backup_path);
+    let day = chrono::Utc::now().format(DAY_FORMAT).to_string();
+    let target = available_path(root.join(TRASH_DIR).join(day).join(relative));
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(long_path(parent))?;
+    }
+    fs::rename(long_path(backup_path), long_path(&target))?;
+    info!(
+        "Moved replaced backup to trash: {:?} → {:?}",
+        backup_path, target
+    );
+    record_file_operation(
+        config,
+        FileOperationEntry {
+            backup: Some(backup_path.to_string_lossy().to_string()),
+            detail: Some(target.to_string_lossy().to_string()),
+            ..FileOperationEntry::new(FileOperation::Trashed)
+        },
+    );
+    Ok(Some(target))
+}
+
+/// Put a trashed backup back where it was after the copy replacing it failed
+pub fn restore_trashed_backup(config: &Config, trashed: &Path, backup_path: &Path) {
+    match fs::rename(long_path(trashed), long_path(backup_path)) {
+        Ok(()) => {
+            if let Some(destination) = config.destination_of(backup_path) {
+                remove_empty_parents(trashed, Path::new(destination.path()));
+            }
+        }
+        Err(e) => warn!(
+            "Failed to restore {:?} from trash after its replacement failed: {}",
+            backup_path, e
+        ),
+    }
+}
+
+/// Delete the days of trash older than `trash_retention_days` from every destination, returning
+/// how many files went
+///
+/// Folders under `.trash/` that aren't named for a day are left alone.
+pub fn empty_expired_trash(config: &Config, today: NaiveDate) -> Result<u64> {
+    let Some(retention_days) = config.trash_retention_days else {
+        return Ok(0);
+    };
+    let mut deleted = 0;
+    for destination in &config.backup_destinations {
+        let trash = Path::new(destination.path()).join(TRASH_DIR);
+        let Ok(days) = fs::read_dir(&trash) else {
+            continue;
+        };
+        for day in days {
+            let day = day?;
+            let expired = day
+                .file_name()
+                .to_str()
+                .and_then(|name| NaiveDate::parse_from_str(name, DAY_FORMAT).ok())
+                .is_some_and(|date| (today - date).num_days() > retention_days as i64);
+            if !expired {
+                continue;
+            }
+            let files = WalkDir::new(day.path())
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file())
+                .count() as u64;
+            fs::remove_dir_all(day.path())?;
+            deleted += files;
+        }
+    }
+    if deleted > 0 {
+        info!("Deleted {} expired file(s) from trash", deleted);
+    }
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn trash_config(destination: &TempDir) -> Config {
+        serde_json::from_value(serde_json::json!({
+            "database_file": "",
+            "backup_sources": [],
+            "backup_destinations": [destination.path()],
+            "trash_retention_days": 7
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_replaced_backups_go_to_trash_until_they_expire() {
+        let destination = TempDir::new().unwrap();
+        let config = trash_config(&destination);
+        let backup = destination.path().join("photos").join("a.jpg");
+        fs::create_dir_all(backup.parent().unwrap()).unwrap();
+        fs::write(&backup, "first").unwrap();
+
+        let trashed = trash_replaced_backup(&config, &backup).unwrap().unwrap();
+        let today = chrono::Utc::now().date_naive();
+        assert_eq!(
+            trashed,
+            destination
+                .path()
+                .join(TRASH_DIR)
+                .join(today.format(DAY_FORMAT).to_string())
+                .join("photos")
+                .join("a.jpg")
+        );
+        assert!(!backup.exists());
+        assert_eq!(trash_replaced_backup(&config, &backup).unwrap(), None);
+
+        // A second replacement the same day keeps both
+        fs::write(&backup, "second").unwrap();
+        let again = trash_replaced_backup(&config, &backup).unwrap().unwrap();
+        assert_eq!(again.file_name().unwrap(), "a.jpg.1");
+        restore_trashed_backup(&config, &again, &backup);
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "second");
+
+        let other = destination.path().join(TRASH_DIR).join("keep");
+        fs::create_dir_all(&other).unwrap();
+        assert_eq!(
+            empty_expired_trash(&config, today + chrono::Days::new(7)).unwrap(),
+            0
+        );
+        assert_eq!(
+            empty_expired_trash(&config, today + chrono::Days::new(8)).unwrap(),
+            1
+        );
+        assert!(!trashed.exists());
+        assert!(other.exists());
+
+        let without_trash = Config {
+            trash_retention_days: None,
+            ..config
+        };
+        assert_eq!(
+            trash_replaced_backup(&without_trash, &backup).unwrap(),
+            None
+        );
+        assert!(backup.exists());
+    }
+}
@@ -0,0 +1,282 @@
+use crate::models::config::{HashLimit, VerificationMode, VerificationTier};
+use crate::models::error::{BackupError, Result};
+use crate::service::hash::hash_file;
+use crate::utils::directory::long_path;
+use log::debug;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+const MEBIBYTE: u64 = 1024 * 1024;
+
+/// The first tier whose size limit covers `file_size`
+pub fn tier_for(tiers: &[VerificationTier], file_size: u64) -> Option<&VerificationTier> {
+    tiers.iter().find(|tier| {
+        tier.max_size_mebibytes
+            .is_none_or(|max| file_size <= max.saturating_mul(MEBIBYTE))
+    })
+}
+
+/// Whether files of this size are verified while they are copied
+pub fn streams(tiers: &[VerificationTier], file_size: u64) -> bool {
+    tier_for(tiers, file_size).is_some_and(|tier| tier.mode == VerificationMode::Stream)
+}
+
+/// Check a copy made with `copy_file_through` from the hash taken during the copy
+///
+/// The bytes hashed are the bytes written, so a match shows the copy holds the data that was
+/// hashed when the file was prepared. Only the copy's size is read back.
+pub fn verify_streamed(
+    copy: &Path,
+    source_hash: &str,
+    copied_hash: &str,
+    bytes_copied: u64,
+) -> Result<()> {
+    let copy_size = File::open(long_path(copy))
+        .and_then(|file| file.metadata())
+        .map_err(|cause| BackupError::MetadataError {
+            path: copy.to_path_buf(),
+            cause,
+        })?
+        .len();
+    debug!("Verifying {:?} using the hash taken while copying", copy);
+
+    let reason = if copy_size != bytes_copied {
+        format!(
+            "{} bytes were copied, backup is {}",
+            bytes_copied, copy_size
+        )
+    } else if copied_hash != source_hash {
+        format!("source hash {} != copied hash {}", source_hash, copied_hash)
+    } else {
+        return Ok(());
+    };
+    Err(BackupError::VerificationFailed {
+        path: copy.to_path_buf(),
+        reason,
+    })
+}
+
+/// Check a fresh copy against its source using the tier configured for its size
+///
+/// Without a matching tier the part of the copy `hash_limit` covers is hashed and compared with
+/// `source_hash`, as before tiers existed.
+pub fn verify_copy(
+    source: &Path,
+    copy: &Path,
+    source_hash: &str,
+    hash_limit: HashLimit,
+    tiers: &[VerificationTier],
+) -> Result<()> {
+    let file_size = File::open(long_path(source))
+        .and_then(|file| file.metadata())
+        .map_err(|cause| BackupError::MetadataError {
+            path: source.to_path_buf(),
+            cause,
+        })?
+        .len();
+    let tier = tier_for(tiers, file_size);
+    let mode = tier.map_or(VerificationMode::Hash, |tier| tier.mode);
+    debug!("Verifying {:?} using {:?} verification", copy, mode);
+
+    let mismatch = match (mode, tier) {
+        (VerificationMode::Full, _) => compare_full(source, copy),
+        (VerificationMode::Sampled, Some(tier)) => compare_sampled(source, copy, tier),
+        _ => {
+            let copy_hash = hash_file(copy, hash_limit)?;
+            Ok((copy_hash != source_hash)
+                .then(|| format!("source hash {} != backup hash {}", source_hash, copy_hash)))
+        }
+    }
+    .map_err(|cause| BackupError::HashError {
+        path: copy.to_path_buf(),
+        cause,
+    })?;
+
+    match mismatch {
+        Some(reason) => Err(BackupError::VerificationFailed {
+            path: copy.to_path_buf(),
+            reason,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Compare every byte; returns a description of the first difference
+fn compare_full(source: &Path, copy: &Path) -> io::Result<Option<String>> {
+    let mut source_reader = BufReader::new(File::open(long_path(source))?);
+    let mut copy_reader = BufReader::new(File::open(long_path(copy))?);
+    let mut source_buffer = [0; 8192];
+    let mut copy_buffer = [0; 8192];
+    let mut offset = 0u64;
+
+    loop {
+        let count = read_full(&mut source_reader, &mut source_buffer)?;
+        let copy_count = read_full(&mut copy_reader, &mut copy_buffer)?;
+        if count != copy_count {
+            return Ok(Some(format!(
+                "sizes differ after {} bytes",
+                offset + count.min(copy_count) as u64
+            )));
+        }
+        if source_buffer[..count] != copy_buffer[..count] {
+            return Ok(Some(format!("contents differ near byte {}", offset)));
+        }
+        if count == 0 {
+            return Ok(None);
+        }
+        offset += count as u64;
+    }
+}
+
+/// Compare sizes, then `samples` blocks spread evenly from the start to the end of the file
+fn compare_sampled(
+    source: &Path,
+    copy: &Path,
+    tier: &VerificationTier,
+) -> io::Result<Option<String>> {
+    let mut source_file = File::open(long_path(source))?;
+    let mut copy_file = File::open(long_path(copy))?;
+    let size = source_file.metadata()?.len();
+    let copy_size = copy_file.metadata()?.len();
+    if size != copy_size {
+        return Ok(Some(format!(
+            "source is {} bytes, backup is {}",
+            size, copy_size
+        )));
+    }
+
+    let block = u64::from(tier.sample_kibibytes.max(1)) * 1024;
+    let mut source_buffer = vec![0; block as usize];
+    let mut copy_buffer = vec![0; block as usize];
+    for offset in sample_offsets(size, block, tier.samples) {
+        let len = block.min(size - offset) as usize;
+        source_file.seek(SeekFrom::Start(offset))?;
+        copy_file.seek(SeekFrom::Start(offset))?;
+        source_file.read_exact(&mut source_buffer[..len])?;
+        copy_file.read_exact(&mut copy_buffer[..len])?;
+        if source_buffer[..len] != copy_buffer[..len] {
+            return Ok(Some(format!("sampled block at byte {} differs", offset)));
+        }
+    }
+    Ok(None)
+}
+
+/// Start offsets of evenly spaced blocks, always including the first and last block
+fn sample_offsets(size: u64, block: u64, samples: u32) -> Vec<u64> {
+    let last = size.saturating_sub(block);
+    let samples = u64::from(samples.max(1));
+    if last == 0 || samples == 1 {
+        return vec![0];
+    }
+
+    let mut offsets: Vec<u64> = (0..samples).map(|i| last * i / (samples - 1)).collect();
+    offsets.dedup();
+    offsets
+}
+
+/// Fill `buffer` unless the reader ends first
+fn read_full<R: Read>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..])? {
+            0 => break,
+            count => filled += count,
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn tier(max_size_mebibytes: Option<u64>, mode: VerificationMode) -> VerificationTier {
+        VerificationTier {
+            max_size_mebibytes,
+            mode,
+            samples: 4,
+            sample_kibibytes: 1,
+        }
+    }
+
+    #[test]
+    fn test_tier_for_picks_first_covering_tier() {
+        let tiers = vec![
+            tier(Some(100), VerificationMode::Full),
+            tier(None, VerificationMode::Sampled),
+        ];
+
+        assert_eq!(
+            tier_for(&tiers, 100 * MEBIBYTE).unwrap().mode,
+            VerificationMode::Full
+        );
+        assert_eq!(
+            tier_for(&tiers, 100 * MEBIBYTE + 1).unwrap().mode,
+            VerificationMode::Sampled
+        );
+        assert!(tier_for(&tiers[..1], 200 * MEBIBYTE).is_none());
+    }
+
+    #[test]
+    fn test_sample_offsets_cover_both_ends() {
+        assert_eq!(sample_offsets(10_000, 1024, 3), vec![0, 4488, 8976]);
+        assert_eq!(sample_offsets(500, 1024, 8), vec![0]);
+        assert_eq!(
+            sample_offsets(2048, 1024, 8),
+            vec![0, 146, 292, 438, 585, 731, 877, 1024]
+        );
+    }
+
+    #[test]
+    fn test_verify_streamed_compares_hash_and_size() {
+        let dir = TempDir::new().unwrap();
+        let copy = dir.path().join("copy.bin");
+        fs::write(&copy, b"0123456789").unwrap();
+
+        assert!(streams(&[tier(None, VerificationMode::Stream)], 10));
+        assert!(!streams(
+            &[tier(Some(1), VerificationMode::Stream)],
+            2 * MEBIBYTE
+        ));
+        assert!(verify_streamed(&copy, "abc", "abc", 10).is_ok());
+        assert!(matches!(
+            verify_streamed(&copy, "abc", "abd", 10),
+            Err(BackupError::VerificationFailed { .. })
+        ));
+        assert!(verify_streamed(&copy, "abc", "abc", 11).is_err());
+    }
+
+    #[test]
+    fn test_full_and_sampled_detect_changes() {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("source.bin");
+        let copy = dir.path().join("copy.bin");
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(&source, &data).unwrap();
+        fs::write(&copy, &data).unwrap();
+
+        let full = [tier(None, VerificationMode::Full)];
+        let sampled = [tier(None, VerificationMode::Sampled)];
+        assert!(verify_copy(&source, &copy, "", HashLimit::new(1), &full).is_ok());
+        assert!(verify_copy(&source, &copy, "", HashLimit::new(1), &sampled).is_ok());
+
+        // The last byte is inside the final sampled block
+        let mut changed = data.clone();
+        *changed.last_mut().unwrap() ^= 0xff;
+        fs::write(&copy, &changed).unwrap();
+        assert!(matches!(
+            verify_copy(&source, &copy, "", HashLimit::new(1), &full),
+            Err(BackupError::VerificationFailed { .. })
+        ));
+        assert!(matches!(
+            verify_copy(&source, &copy, "", HashLimit::new(1), &sampled),
+            Err(BackupError::VerificationFailed { .. })
+        ));
+
+        fs::write(&copy, &data[..5000]).unwrap();
+        assert!(verify_copy(&source, &copy, "", HashLimit::new(1), &sampled).is_err());
+    }
+}
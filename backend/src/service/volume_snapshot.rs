@@ -0,0 +1,566 @@
+use crate::models::config::{BackupSource, VolumeSnapshot};
+use crate::models::run_selection::SelectedSource;
+use crate::utils::directory::strip_long_path_prefix;
+use log::{info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A snapshot of the volume under one or more sources
+struct Snapshot {
+    method: VolumeSnapshot,
+    /// Live directory the snapshot covers, such as the volume's mount point
+    root: PathBuf,
+    /// The same directory inside the snapshot
+    view: PathBuf,
+    /// Undoes the snapshot; none for snapshots the run didn't take itself
+    release: Option<platform::Release>,
+}
+
+/// Where a snapshotted source's files are found for the length of a run
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotView {
+    /// The source's parent directory, as configured
+    live: PathBuf,
+    /// The source's parent directory inside the snapshot
+    snapshot: PathBuf,
+}
+
+impl SnapshotView {
+    /// Where a live path inside the source is found in the snapshot
+    pub fn to_snapshot(&self, path: &Path) -> Option<PathBuf> {
+        let relative = path
+            .strip_prefix(&self.live)
+            .ok()
+            .map(Path::to_path_buf)
+            .or_else(|| {
+                let live = canonical(&self.live).ok()?;
+                Some(canonical(path).ok()?.strip_prefix(live).ok()?.to_path_buf())
+            })?;
+        Some(join(&self.snapshot, &relative))
+    }
+
+    /// The live path of a file found in the snapshot
+    pub fn to_live(&self, path: &Path) -> PathBuf {
+        match path.strip_prefix(&self.snapshot) {
+            Ok(relative) => join(&self.live, relative),
+            Err(_) => path.to_path_buf(),
+        }
+    }
+}
+
+/// Volume snapshots of the sources of one run, released when dropped
+///
+/// Sources on the same volume share a snapshot. A source whose snapshot can't be taken is read
+/// live, as it would be without one.
+#[derive(Default)]
+pub struct SourceSnapshots {
+    taken: Vec<Snapshot>,
+}
+
+impl SourceSnapshots {
+    /// Snapshot the volumes of the selected sources that ask for it
+    pub fn take(sources: &[SelectedSource], run_id: &str) -> Self {
+        let mut snapshots = SourceSnapshots::default();
+        for selected in sources {
+            let source = &selected.source;
+            let Some(method) = source.snapshot else {
+                continue;
+            };
+            if snapshots.view(source).is_some() {
+                continue;
+            }
+
+            let name = format!("rustyhashbackup-{}-{}", run_id, snapshots.taken.len());
+            let taken = canonical(Path::new(&source.parent_directory))
+                .map_err(|e| e.to_string())
+                .and_then(|path| platform::take(method, &path, &name));
+            match taken {
+                Ok((root, view, release)) => {
+                    info!(
+                        "Reading {} from the {} snapshot of {}",
+                        source.parent_directory,
+                        method,
+                        root.display()
+                    );
+                    snapshots.taken.push(Snapshot {
+                        method,
+                        root,
+                        view,
+                        release: Some(release),
+                    });
+                }
+                Err(e) => warn!(
+                    "Could not snapshot {} with {}, reading its live files: {}",
+                    source.parent_directory, method, e
+                ),
+            }
+        }
+        snapshots
+    }
+
+    /// Where a source is read from, if it was snapshotted
+    pub fn view(&self, source: &BackupSource) -> Option<SnapshotView> {
+        let method = source.snapshot?;
+        let live = PathBuf::from(&source.parent_directory);
+        let path = canonical(&live).ok()?;
+        self.taken
+            .iter()
+            .filter(|snapshot| snapshot.method == method)
+            .find_map(|snapshot| {
+                let relative = path.strip_prefix(&snapshot.root).ok()?;
+                Some(SnapshotView {
+                    live: live.clone(),
+                    snapshot: join(&snapshot.view, relative),
+                })
+            })
+    }
+
+    /// Stand in `view` for a snapshot of `root`, without taking or releasing anything
+    #[cfg(test)]
+    pub fn with_view(method: VolumeSnapshot, root: &Path, view: &Path) -> Self {
+        SourceSnapshots {
+            taken: vec![Snapshot {
+                method,
+                root: canonical(root).unwrap(),
+                view: view.to_path_buf(),
+                release: None,
+            }],
+        }
+    }
+}
+
+impl Drop for SourceSnapshots {
+    fn drop(&mut self) {
+        for snapshot in self.taken.drain(..) {
+            let Some(release) = snapshot.release else {
+                continue;
+            };
+            if let Err(e) = release.release() {
+                warn!(
+                    "Failed to release the {} snapshot of {}: {}",
+                    snapshot.method,
+                    snapshot.root.display(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+fn canonical(path: &Path) -> std::io::Result<PathBuf> {
+    Ok(strip_long_path_prefix(&fs::canonicalize(path)?).into_owned())
+}
+
+/// `base` joined with `relative`, leaving `base` as it is when there is nothing to join
+fn join(base: &Path, relative: &Path) -> PathBuf {
+    if relative.as_os_str().is_empty() {
+        base.to_path_buf()
+    } else {
+        base.join(relative)
+    }
+}
+
+/// Runs a snapshot tool, returning its output or what it printed on failure
+#[cfg(any(target_os = "linux", windows))]
+fn run(command: &mut std::process::Command) -> Result<String, String> {
+    let program = command.get_program().to_string_lossy().to_string();
+    let output = command
+        .output()
+        .map_err(|e| format!("failed to run {}: {}", program, e))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(format!(
+            "{} exited with {}: {}",
+            program,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+/// The fields of one line of `findmnt --pairs` output, such as `SOURCE="/dev/sda1"`
+#[cfg(any(target_os = "linux", test))]
+fn parse_findmnt_pairs(line: &str) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    let mut rest = line.trim();
+    while let Some((key, value)) = rest.split_once("=\"") {
+        let Some(end) = value.find('"') else {
+            break;
+        };
+        pairs.push((key.trim().to_string(), unescape_findmnt(&value[..end])));
+        rest = &value[end + 1..];
+    }
+    pairs
+}
+
+/// Undo the `\x20` escapes findmnt writes for spaces and other unsafe bytes
+#[cfg(any(target_os = "linux", test))]
+fn unescape_findmnt(value: &str) -> String {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = (byte == b'\\' && tail.first() == Some(&b'x'))
+            .then(|| tail.get(1..3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(decoded) => {
+                bytes.push(decoded);
+                rest = &tail[3..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// LVM and btrfs snapshots on Linux
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{parse_findmnt_pairs, run};
+    use crate::models::config::VolumeSnapshot;
+    use std::fs;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::{Path, PathBuf};
+    use std::process::Command;
+
+    /// Space an LVM snapshot is given for the changes made to its origin during the run
+    const LVM_SNAPSHOT_EXTENTS: &str = "10%ORIGIN";
+
+    /// Inode number btrfs gives the root directory of every subvolume
+    const BTRFS_SUBVOLUME_ROOT_INODE: u64 = 256;
+
+    pub enum Release {
+        Lvm {
+            /// `vg/lv` name of the snapshot volume
+            volume: String,
+            mount_point: PathBuf,
+        },
+        Btrfs {
+            subvolume: PathBuf,
+        },
+    }
+
+    impl Release {
+        pub fn release(self) -> Result<(), String> {
+            match self {
+                Release::Lvm {
+                    volume,
+                    mount_point,
+                } => {
+                    run(Command::new("umount").arg(&mount_point))?;
+                    let _ = fs::remove_dir(&mount_point);
+                    run(Command::new("lvremove").args(["--force", &volume])).map(|_| ())
+                }
+                Release::Btrfs { subvolume } => run(Command::new("btrfs")
+                    .args(["subvolume", "delete"])
+                    .arg(&subvolume))
+                .map(|_| ()),
+            }
+        }
+    }
+
+    /// Snapshot the volume holding `path`, returning the directory covered, where it is found
+    /// in the snapshot and how to release it
+    pub fn take(
+        method: VolumeSnapshot,
+        path: &Path,
+        name: &str,
+    ) -> Result<(PathBuf, PathBuf, Release), String> {
+        match method {
+            VolumeSnapshot::Lvm => lvm(path, name),
+            VolumeSnapshot::Btrfs => btrfs(path, name),
+            VolumeSnapshot::Vss => Err("Volume Shadow Copy is only available on Windows".into()),
+        }
+    }
+
+    struct Mount {
+        source: String,
+        target: PathBuf,
+        fstype: String,
+    }
+
+    fn mount_of(path: &Path) -> Result<Mount, String> {
+        let output = run(Command::new("findmnt")
+            .args([
+                "--noheadings",
+                "--pairs",
+                "--output",
+                "SOURCE,TARGET,FSTYPE",
+            ])
+            .arg("--target")
+            .arg(path))?;
+        let pairs = parse_findmnt_pairs(output.lines().next().unwrap_or_default());
+        let field = |name: &str| {
+            pairs
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+                .ok_or_else(|| format!("findmnt gave no {} for {}", name, path.display()))
+        };
+        Ok(Mount {
+            source: field("SOURCE")?,
+            target: PathBuf::from(field("TARGET")?),
+            fstype: field("FSTYPE")?,
+        })
+    }
+
+    /// Snapshot the logical volume `path` is mounted from and mount the snapshot read-only
+    fn lvm(path: &Path, name: &str) -> Result<(PathBuf, PathBuf, Release), String> {
+        let mount = mount_of(path)?;
+        let origin = run(Command::new("lvs").args([
+            "--noheadings",
+            "--options",
+            "vg_name,lv_name",
+            &mount.source,
+        ]))?;
+        let (vg, lv) = match origin.split_whitespace().collect::<Vec<_>>()[..] {
+            [vg, lv] => (vg.to_string(), lv.to_string()),
+            _ => return Err(format!("{} is not an LVM logical volume", mount.source)),
+        };
+
+        run(Command::new("lvcreate").args([
+            "--snapshot",
+            "--name",
+            name,
+            "--extents",
+            LVM_SNAPSHOT_EXTENTS,
+            &format!("{}/{}", vg, lv),
+        ]))?;
+        let volume = format!("{}/{}", vg, name);
+        let mount_point = std::env::temp_dir().join(name);
+        // XFS refuses to mount a second filesystem with the same UUID
+        let options = if mount.fstype == "xfs" {
+            "ro,nouuid"
+        } else {
+            "ro"
+        };
+        let mounted = fs::create_dir_all(&mount_point)
+            .map_err(|e| e.to_string())
+            .and_then(|_| {
+                run(Command::new("mount")
+                    .args(["-o", options, &format!("/dev/{}", volume)])
+                    .arg(&mount_point))
+            });
+        if let Err(e) = mounted {
+            let _ = fs::remove_dir(&mount_point);
+            let _ = run(Command::new("lvremove").args(["--force", &volume]));
+            return Err(e);
+        }
+
+        Ok((
+            mount.target,
+            mount_point.clone(),
+            Release::Lvm {
+                volume,
+                mount_point,
+            },
+        ))
+    }
+
+    /// Take a read-only snapshot of the subvolume holding `path`, inside that subvolume
+    fn btrfs(path: &Path, name: &str) -> Result<(PathBuf, PathBuf, Release), String> {
+        let mount = mount_of(path)?;
+        if mount.fstype != "btrfs" {
+            return Err(format!("{} is not on btrfs", path.display()));
+        }
+        let root = path
+            .ancestors()
+            .take_while(|dir| dir.starts_with(&mount.target))
+            .find(|dir| {
+                fs::metadata(dir).is_ok_and(|metadata| metadata.ino() == BTRFS_SUBVOLUME_ROOT_INODE)
+            })
+            .ok_or_else(|| format!("no btrfs subvolume holds {}", path.display()))?
+            .to_path_buf();
+
+        let subvolume = root.join(format!(".{}", name));
+        run(Command::new("btrfs")
+            .args(["subvolume", "snapshot", "-r"])
+            .arg(&root)
+            .arg(&subvolume))?;
+        Ok((root, subvolume.clone(), Release::Btrfs { subvolume }))
+    }
+}
+
+/// Volume Shadow Copy on Windows
+#[cfg(windows)]
+mod platform {
+    use super::run;
+    use crate::models::config::VolumeSnapshot;
+    use std::os::windows::process::CommandExt;
+    use std::path::{Component, Path, PathBuf, Prefix};
+    use std::process::Command;
+
+    /// Keeps PowerShell from opening a console window
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    pub struct Release {
+        shadow_id: String,
+    }
+
+    impl Release {
+        pub fn release(self) -> Result<(), String> {
+            powershell(&format!(
+                "Get-CimInstance Win32_ShadowCopy -Filter \"ID='{}'\" | Remove-CimInstance",
+                self.shadow_id
+            ))
+            .map(|_| ())
+        }
+    }
+
+    /// Shadow-copy the volume holding `path`, returning the volume's root, where it is found in
+    /// the shadow copy and how to release it
+    pub fn take(
+        method: VolumeSnapshot,
+        path: &Path,
+        _name: &str,
+    ) -> Result<(PathBuf, PathBuf, Release), String> {
+        if method != VolumeSnapshot::Vss {
+            return Err("LVM and btrfs snapshots are only available on Linux".into());
+        }
+        let volume = match path.components().next() {
+            Some(Component::Prefix(prefix)) => match prefix.kind() {
+                Prefix::Disk(letter) | Prefix::VerbatimDisk(letter) => {
+                    format!("{}:\\", letter as char)
+                }
+                _ => return Err(format!("{} is not on a local drive", path.display())),
+            },
+            _ => return Err(format!("{} has no drive letter", path.display())),
+        };
+
+        let created = powershell(&format!(
+            "$r = ([WMICLASS]'root\\cimv2:Win32_ShadowCopy').Create('{}', 'ClientAccessible'); \
+             if ($r.ReturnValue -ne 0) {{ Write-Error \"Win32_ShadowCopy.Create returned $($r.ReturnValue)\"; exit 1 }}; \
+             $s = Get-CimInstance Win32_ShadowCopy -Filter \"ID='$($r.ShadowID)'\"; \
+             Write-Output \"$($s.ID)|$($s.DeviceObject)\"",
+            volume
+        ))?;
+        let (shadow_id, device) = created
+            .split_once('|')
+            .ok_or_else(|| format!("unexpected output from PowerShell: {}", created))?;
+
+        Ok((
+            PathBuf::from(volume),
+            PathBuf::from(format!("{}\\", device)),
+            Release {
+                shadow_id: shadow_id.to_string(),
+            },
+        ))
+    }
+
+    fn powershell(script: &str) -> Result<String, String> {
+        run(Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", script])
+            .creation_flags(CREATE_NO_WINDOW))
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod platform {
+    use crate::models::config::VolumeSnapshot;
+    use std::path::{Path, PathBuf};
+
+    pub enum Release {}
+
+    impl Release {
+        pub fn release(self) -> Result<(), String> {
+            match self {}
+        }
+    }
+
+    pub fn take(
+        _method: VolumeSnapshot,
+        _path: &Path,
+        _name: &str,
+    ) -> Result<(PathBuf, PathBuf, Release), String> {
+        Err("volume snapshots are not supported on this platform".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn source(parent_directory: &Path, snapshot: Option<VolumeSnapshot>) -> BackupSource {
+        BackupSource {
+            parent_directory: parent_directory.to_str().unwrap().to_string(),
+            snapshot,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_views_translate_paths_between_snapshot_and_live_source() {
+        let volume = TempDir::new().unwrap();
+        let view = TempDir::new().unwrap();
+        let documents = volume.path().join("home").join("documents");
+        fs::create_dir_all(&documents).unwrap();
+        let snapshots = SourceSnapshots::with_view(VolumeSnapshot::Lvm, volume.path(), view.path());
+
+        let snapshot_view = snapshots
+            .view(&source(&documents, Some(VolumeSnapshot::Lvm)))
+            .unwrap();
+        let in_snapshot = view.path().join("home").join("documents");
+        assert_eq!(
+            snapshot_view.to_snapshot(&documents),
+            Some(in_snapshot.clone())
+        );
+        assert_eq!(
+            snapshot_view.to_snapshot(&documents.join("taxes")),
+            Some(in_snapshot.join("taxes"))
+        );
+        assert_eq!(
+            snapshot_view.to_live(&in_snapshot.join("taxes").join("2025.pdf")),
+            documents.join("taxes").join("2025.pdf")
+        );
+        assert_eq!(snapshot_view.to_snapshot(Path::new("/elsewhere")), None);
+
+        // Only sources asking for this kind of snapshot are read from it
+        assert!(snapshots.view(&source(&documents, None)).is_none());
+        assert!(snapshots
+            .view(&source(&documents, Some(VolumeSnapshot::Btrfs)))
+            .is_none());
+        let outside = TempDir::new().unwrap();
+        assert!(snapshots
+            .view(&source(outside.path(), Some(VolumeSnapshot::Lvm)))
+            .is_none());
+    }
+
+    #[test]
+    fn test_failed_snapshots_fall_back_to_live_files() {
+        // Volume Shadow Copy is the one kind this test can count on failing to take
+        if cfg!(windows) {
+            return;
+        }
+        let dir = TempDir::new().unwrap();
+        let selected = SelectedSource {
+            source: source(dir.path(), Some(VolumeSnapshot::Vss)),
+            scan_root: dir.path().to_str().unwrap().to_string(),
+            max_depth: None,
+        };
+
+        let snapshots = SourceSnapshots::take(std::slice::from_ref(&selected), "run-1");
+        assert!(snapshots.view(&selected.source).is_none());
+    }
+
+    #[test]
+    fn test_parse_findmnt_pairs() {
+        let pairs = parse_findmnt_pairs(
+            r#"SOURCE="/dev/mapper/vg-home" TARGET="/mnt/my\x20files" FSTYPE="ext4""#,
+        );
+        assert_eq!(
+            pairs,
+            vec![
+                ("SOURCE".to_string(), "/dev/mapper/vg-home".to_string()),
+                ("TARGET".to_string(), "/mnt/my files".to_string()),
+                ("FSTYPE".to_string(), "ext4".to_string()),
+            ]
+        );
+        assert_eq!(unescape_findmnt(r"C:\xZZ"), r"C:\xZZ");
+    }
+}
@@ -0,0 +1,281 @@
+use crate::models::config::Config;
+use crate::utils::directory::long_path;
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::fs::{self, File, TryLockError};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use walkdir::WalkDir;
+
+/// Directory under each destination root, and beside the database unless `workspace_dir` is set,
+/// holding the workspaces of the runs in progress
+pub const WORKSPACE_DIR: &str = ".work";
+
+/// File in each workspace its run holds locked for as long as it works
+const WORKSPACE_LOCK: &str = ".lock";
+
+/// Names tried for a run's workspace before giving up
+const MAX_WORKSPACE_ATTEMPTS: u32 = 100;
+
+/// Scratch space of one run: its held files beside the database, and at each destination the
+/// copies being written before they are renamed into place
+///
+/// Workspaces are made the first time they are needed and removed when the run finishes. Each
+/// holds an OS file lock while its run lives, so the workspace of a run that crashed can be told
+/// from one in use and cleaned up by `clean_abandoned_workspaces`.
+pub struct RunWorkspace {
+    run_id: String,
+    local_root: PathBuf,
+    local: Mutex<Option<Workspace>>,
+    destinations: Mutex<HashMap<String, Workspace>>,
+    staged: AtomicU64,
+}
+
+impl RunWorkspace {
+    pub fn new(config: &Config, run_id: &str) -> Self {
+        RunWorkspace {
+            run_id: run_id.to_string(),
+            local_root: local_root(config),
+            local: Mutex::new(None),
+            destinations: Mutex::new(HashMap::new()),
+            staged: AtomicU64::new(0),
+        }
+    }
+
+    /// The run's workspace beside the database, made if it isn't there yet
+    pub fn local_dir(&self) -> io::Result<PathBuf> {
+        let mut local = self.local.lock().unwrap();
+        if local.is_none() {
+            *local = Some(Workspace::open(&self.local_root, &self.run_id)?);
+        }
+        Ok(local.as_ref().unwrap().dir.clone())
+    }
+
+    /// Where to write the copy that becomes `backup_path`, in the run's workspace at its
+    /// destination, so it can be renamed into place; `None` outside the configured destinations
+    pub fn stage(&self, config: &Config, backup_path: &Path) -> io::Result<Option<PathBuf>> {
+        let Some(destination) = config.destination_of(backup_path) else {
+            return Ok(None);
+        };
+        let mut destinations = self.destinations.lock().unwrap();
+        if !destinations.contains_key(destination.path()) {
+            let root = Path::new(destination.path()).join(WORKSPACE_DIR);
+            let workspace = Workspace::open(&root, &self.run_id)?;
+            destinations.insert(destination.path().to_string(), workspace);
+        }
+        let staged = self.staged.fetch_add(1, Ordering::Relaxed);
+        Ok(Some(
+            destinations[destination.path()]
+                .dir
+                .join(format!("{}.part", staged)),
+        ))
+    }
+}
+
+struct Workspace {
+    dir: PathBuf,
+    lock: Option<File>,
+}
+
+impl Workspace {
+    /// Make and lock the workspace of `run_id` under `root`; a run ID that is in use already,
+    /// such as a resumed run's, gets a numbered workspace of its own
+    fn open(root: &Path, run_id: &str) -> io::Result<Workspace> {
+        for attempt in 0..MAX_WORKSPACE_ATTEMPTS {
+            let name = match attempt {
+                0 => run_id.to_string(),
+                n => format!("{}-{}", run_id, n),
+            };
+            let dir = root.join(name);
+            let lock_path = dir.join(WORKSPACE_LOCK);
+            let lock = fs::create_dir_all(long_path(&dir)).and_then(|_| {
+                File::options()
+                    .write(true)
+                    .create(true)
+                    .truncate(false)
+                    .open(long_path(&lock_path))
+            });
+            // Removed by another run's cleanup between the steps, so tried again under a new name
+            let lock = match lock {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                lock => lock?,
+            };
+            match lock.try_lock() {
+                Ok(()) if lock_path.exists() => {
+                    debug!("Run workspace at {:?}", dir);
+                    return Ok(Workspace {
+                        dir,
+                        lock: Some(lock),
+                    });
+                }
+                Ok(()) | Err(TryLockError::WouldBlock) => continue,
+                Err(TryLockError::Error(e)) => return Err(e),
+            }
+        }
+        Err(io::Error::other(format!(
+            "No free workspace for run {} under {:?}",
+            run_id, root
+        )))
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        // Unlocked first, as Windows won't remove a file that is open
+        drop(self.lock.take());
+        if let Err(e) = fs::remove_dir_all(long_path(&self.dir)) {
+            warn!("Failed to remove run workspace {:?}: {}", self.dir, e);
+        }
+        if let Some(root) = self.dir.parent() {
+            let _ = fs::remove_dir(long_path(root));
+        }
+    }
+}
+
+/// Remove the workspaces runs that crashed left beside the database and at every destination,
+/// returning how many went and the bytes they held
+///
+/// Only directories with a workspace lock nobody holds are removed.
+pub fn clean_abandoned_workspaces(config: &Config) -> (u64, u64) {
+    let roots = std::iter::once(local_root(config)).chain(
+        config
+            .destination_paths()
+            .into_iter()
+            .map(|destination| Path::new(&destination).join(WORKSPACE_DIR)),
+    );
+    let (mut removed, mut bytes) = (0, 0);
+    for root in roots {
+        let Ok(entries) = fs::read_dir(long_path(&root)) else {
+            continue;
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let dir = entry.path();
+            if !is_abandoned(&dir) {
+                continue;
+            }
+            let size = directory_size(&dir);
+            match fs::remove_dir_all(long_path(&dir)) {
+                Ok(()) => {
+                    removed += 1;
+                    bytes += size;
+                }
+                Err(e) => warn!("Failed to remove abandoned workspace {:?}: {}", dir, e),
+            }
+        }
+        let _ = fs::remove_dir(long_path(&root));
+    }
+    if removed > 0 {
+        info!(
+            "Removed {} workspace(s) abandoned by runs that didn't finish",
+            removed
+        );
+    }
+    (removed, bytes)
+}
+
+/// Bytes held by the workspaces under a destination
+pub fn workspace_size(destination_root: &Path) -> u64 {
+    directory_size(&destination_root.join(WORKSPACE_DIR))
+}
+
+/// Where the runs' workspaces beside the database go: `workspace_dir`, or `.work` beside the
+/// database, or in the temporary directory for an in-memory database
+fn local_root(config: &Config) -> PathBuf {
+    if let Some(dir) = &config.workspace_dir {
+        return PathBuf::from(dir);
+    }
+    let in_memory = ["", ":memory:"].contains(&config.database_file.as_str())
+        || config.database_file.starts_with("file:");
+    match Path::new(&config.database_file).parent() {
+        _ if in_memory => std::env::temp_dir().join("rustyhashbackup-work"),
+        Some(dir) if !dir.as_os_str().is_empty() => dir.join(WORKSPACE_DIR),
+        _ => PathBuf::from(WORKSPACE_DIR),
+    }
+}
+
+/// Whether `dir` is a workspace whose run is gone
+fn is_abandoned(dir: &Path) -> bool {
+    let Ok(lock) = File::options()
+        .write(true)
+        .open(long_path(&dir.join(WORKSPACE_LOCK)))
+    else {
+        return false;
+    };
+    lock.try_lock().is_ok()
+}
+
+fn directory_size(dir: &Path) -> u64 {
+    WalkDir::new(long_path(dir))
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn workspace_config(database: &TempDir, destination: &TempDir) -> Config {
+        serde_json::from_value(serde_json::json!({
+            "database_file": database.path().join("backup.db"),
+            "backup_sources": [],
+            "backup_destinations": [destination.path()]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_workspaces_are_removed_with_their_run_or_once_abandoned() {
+        let database = TempDir::new().unwrap();
+        let destination = TempDir::new().unwrap();
+        let config = workspace_config(&database, &destination);
+
+        let run = RunWorkspace::new(&config, "run");
+        let local = run.local_dir().unwrap();
+        assert_eq!(local, database.path().join(WORKSPACE_DIR).join("run"));
+        let backup = destination.path().join("photos").join("a.jpg");
+        let staged = run.stage(&config, &backup).unwrap().unwrap();
+        assert_eq!(
+            staged,
+            destination
+                .path()
+                .join(WORKSPACE_DIR)
+                .join("run")
+                .join("0.part")
+        );
+        assert_eq!(
+            run.stage(&config, Path::new("/elsewhere/a.jpg")).unwrap(),
+            None
+        );
+        fs::write(&staged, "copy").unwrap();
+        assert_eq!(workspace_size(destination.path()), 4);
+
+        // In use, so left alone, as is a directory that isn't a workspace
+        let stray = database.path().join(WORKSPACE_DIR).join("notes");
+        fs::create_dir_all(&stray).unwrap();
+        assert_eq!(clean_abandoned_workspaces(&config), (0, 0));
+        // A second run with the same ID gets a workspace of its own
+        let resumed = RunWorkspace::new(&config, "run");
+        assert!(resumed.local_dir().unwrap().ends_with("run-1"));
+        drop(resumed);
+
+        drop(run);
+        assert!(!destination.path().join(WORKSPACE_DIR).exists());
+        assert!(!local.exists());
+        assert!(stray.exists());
+
+        // A crashed run leaves its workspace with a lock nobody holds
+        let crashed = destination.path().join(WORKSPACE_DIR).join("crashed");
+        fs::create_dir_all(&crashed).unwrap();
+        fs::write(crashed.join(WORKSPACE_LOCK), "").unwrap();
+        fs::write(crashed.join("7.part"), "half a copy").unwrap();
+        assert_eq!(clean_abandoned_workspaces(&config), (1, 11));
+        assert!(!destination.path().join(WORKSPACE_DIR).exists());
+    }
+}
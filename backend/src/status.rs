@@ -0,0 +1,344 @@
+use crate::models::api::{BackupHistoryResponse, BackupStatus, StatusResponse};
+use crate::output::OutputFormat;
+use crate::repo::sqlite::format_bytes;
+use crate::service::mass_change::describe_mass_change;
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use serde::de::DeserializeOwned;
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+/// How long to wait for the API instance to answer
+const STATUS_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Args)]
+pub struct StatusArgs {
+    /// Address of the `--api` instance, e.g. http://nas.local:8000; defaults to the address the
+    /// web server is configured with in Rocket.toml or ROCKET_ADDRESS and ROCKET_PORT
+    #[arg(long = "url")]
+    url: Option<String>,
+}
+
+/// Print the state of the backup a running API instance is working on, and how its last run went
+pub fn run(args: &StatusArgs, default_url: String, output: OutputFormat) -> Result<()> {
+    let url = args.url.clone().unwrap_or(default_url);
+    let status: StatusResponse = get_json(&url, "/api/status")?;
+    let history: BackupHistoryResponse = get_json(&url, "/api/history")?;
+    let answers = serde_json::json!({"url": url, "status": status, "history": history});
+    output.print(&answers, || describe_status(&url, &status, &history))
+}
+
+/// GET `path` from the server at `url` and parse its JSON body
+///
+/// The request is sent as HTTP/1.0, so the body ends with the connection rather than in chunks.
+fn get_json<T: DeserializeOwned>(url: &str, path: &str) -> Result<T> {
+    let Some(rest) = url.strip_prefix("http://") else {
+        bail!("{} is not an http:// address", url);
+    };
+    let (authority, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+    let prefix = prefix.trim_end_matches('/');
+    let address = if authority
+        .rsplit_once(':')
+        .is_some_and(|(_, port)| port.parse::<u16>().is_ok())
+    {
+        authority.to_string()
+    } else {
+        format!("{}:80", authority)
+    };
+
+    let unreachable = || format!("Could not reach the API at {}; is `--api` running?", url);
+    let mut stream = TcpStream::connect(&address).with_context(unreachable)?;
+    stream.set_read_timeout(Some(STATUS_TIMEOUT))?;
+    let target = if prefix.is_empty() {
+        path.to_string()
+    } else {
+        format!("/{}{}", prefix, path)
+    };
+    write!(
+        stream,
+        "GET {} HTTP/1.0\r\nHost: {}\r\nAccept: application/json\r\n\r\n",
+        target, authority
+    )
+    .with_context(unreachable)?;
+    let mut response = Vec::new();
+    stream
+        .read_to_end(&mut response)
+        .with_context(|| format!("Failed to read the answer of {}{}", url, target))?;
+
+    let response = String::from_utf8_lossy(&response);
+    let (head, body) = response
+        .split_once("\r\n\r\n")
+        .with_context(|| format!("{} sent an incomplete answer", url))?;
+    let status_line = head.lines().next().unwrap_or_default();
+    if status_line.split_whitespace().nth(1) != Some("200") {
+        bail!("{} answered {} to GET {}", url, status_line, target);
+    }
+    serde_json::from_str(body).with_context(|| format!("{} sent an unexpected answer", url))
+}
+
+fn describe_status(url: &str, status: &StatusResponse, history: &BackupHistoryResponse) -> String {
+    let mut out = String::new();
+    let _ = write!(out, "Status:   {}", status.status.as_str());
+    // The API names the dry run mode of every run, "None" for real ones
+    if let Some(mode) = status
+        .dry_run_mode
+        .as_deref()
+        .filter(|mode| *mode != "None")
+    {
+        let _ = write!(out, " (dry run: {})", mode.to_lowercase());
+    }
+    let _ = writeln!(out, "  [{}]", url);
+
+    let active = matches!(
+        status.status,
+        BackupStatus::Running | BackupStatus::Paused | BackupStatus::Stopping
+    );
+    if active {
+        if let Some(started_at) = &status.started_at {
+            let _ = writeln!(out, "Started:  {}", started_at);
+        }
+        if let Some(progress) = &status.progress {
+            let _ = writeln!(
+                out,
+                "Phase:    {}/3 {}",
+                progress.phase, progress.phase_description
+            );
+            // Still growing while discovery walks the sources
+            let counting = if progress.discovery_complete { "" } else { "+" };
+            let _ = write!(
+                out,
+                "Progress: {}/{}{} file(s), {:.1}%",
+                progress.files_processed, progress.total_files, counting, progress.percentage
+            );
+            if let Some(bytes) = progress.bytes_processed {
+                let _ = write!(out, ", {} copied", format_bytes(bytes));
+            }
+            out.push('\n');
+            let _ = write!(
+                out,
+                "Elapsed:  {}",
+                format_seconds(progress.elapsed_seconds)
+            );
+            if let Some(speed) = progress.bytes_per_second {
+                let _ = write!(out, ", {}/s", format_bytes(speed as u64));
+            }
+            if let Some(eta) = progress.eta_seconds {
+                let _ = write!(out, ", about {} left", format_seconds(eta));
+            }
+            out.push('\n');
+            if let Some(file) = &progress.current_file {
+                let _ = write!(out, "Current:  {}", file);
+                if let Some(source) = &progress.current_source {
+                    let _ = write!(out, " ({})", source);
+                }
+                out.push('\n');
+            }
+        }
+    }
+
+    for mismatch in &status.destination_mismatches {
+        let found = mismatch
+            .found_id
+            .as_deref()
+            .map_or("no marker".to_string(), |id| format!("marker {}", id));
+        let _ = writeln!(
+            out,
+            "Wrong drive: {} has {} instead of {}",
+            mismatch.path, found, mismatch.recorded_id
+        );
+    }
+
+    if let Some(change) = &status.mass_change {
+        let _ = writeln!(
+            out,
+            "Held:     {}; confirm with POST /api/confirm-mass-change",
+            describe_mass_change(change)
+        );
+    }
+
+    let last = history
+        .entries
+        .iter()
+        .find(|entry| !matches!(entry.status, BackupStatus::Running | BackupStatus::Paused));
+    let Some(last) = last else {
+        let _ = writeln!(out, "Last run: none since the server started");
+        return out;
+    };
+    let _ = write!(
+        out,
+        "Last run: {} {}{}, started {}",
+        last.id,
+        last.status.as_str(),
+        if last.dry_run { " (dry run)" } else { "" },
+        last.started_at
+    );
+    if let Some(completed_at) = &last.completed_at {
+        let _ = write!(out, ", finished {}", completed_at);
+    }
+    out.push('\n');
+    let _ = write!(out, "          {} file(s)", last.files_processed);
+    if let Some(bytes) = last.bytes_processed {
+        let _ = write!(out, ", {}", format_bytes(bytes));
+    }
+    if let Some(report) = &last.report {
+        let copied: u64 = report.sources.iter().map(|s| s.files_copied).sum();
+        let errors: u64 = report.sources.iter().map(|s| s.errors).sum();
+        let _ = write!(out, ", {} copied, {} error(s)", copied, errors);
+        if !report.locked_files.is_empty() {
+            let _ = write!(out, ", {} locked", report.locked_files.len());
+        }
+        if !report.skipped_entries.is_empty() {
+            let _ = write!(out, ", {} unreadable", report.skipped_entries.len());
+        }
+        if !report.changed_during_run.is_empty() {
+            let _ = write!(out, ", {} changed mid-run", report.changed_during_run.len());
+        }
+        if let Some(exhausted) = &report.budget_exhausted {
+            let _ = write!(out, ", {} left for next run", exhausted.files_deferred);
+        }
+        if !report.quota_exceeded.is_empty() {
+            let _ = write!(
+                out,
+                ", {} destination(s) over quota",
+                report.quota_exceeded.len()
+            );
+        }
+        let offline = report
+            .skipped_destinations
+            .iter()
+            .filter(|skipped| skipped.offline)
+            .count();
+        if offline > 0 {
+            let _ = write!(out, ", {} destination(s) offline, skipped", offline);
+        }
+        if report.skipped_destinations.len() > offline {
+            let _ = write!(
+                out,
+                ", {} destination(s) unavailable, skipped",
+                report.skipped_destinations.len() - offline
+            );
+        }
+    }
+    out.push('\n');
+    if let Some(error) = &last.error {
+        let _ = writeln!(out, "Error:    {}", error);
+    }
+    out
+}
+
+/// `seconds` as hours and minutes, or minutes and seconds
+fn format_seconds(seconds: u64) -> String {
+    match (seconds / 3600, seconds / 60 % 60, seconds % 60) {
+        (0, 0, secs) => format!("{}s", secs),
+        (0, mins, secs) => format!("{}m {:02}s", mins, secs),
+        (hours, mins, _) => format!("{}h {:02}m", hours, mins),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::api::{BackupHistoryEntry, BackupProgress};
+    use crate::models::backup_marker::DestinationMismatch;
+    use crate::models::run_report::MassChange;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_describes_the_running_backup_and_the_last_run() {
+        let status = StatusResponse {
+            status: BackupStatus::Running,
+            progress: Some(BackupProgress {
+                phase: 3,
+                phase_description: "Copying files".to_string(),
+                files_processed: 40,
+                total_files: 160,
+                discovery_complete: true,
+                percentage: 25.0,
+                current_file: Some("IMG_0001.jpg".to_string()),
+                current_source: Some("photos".to_string()),
+                elapsed_seconds: 300,
+                bytes_per_second: Some(12.5 * 1024.0 * 1024.0),
+                eta_seconds: Some(3900),
+                ..Default::default()
+            }),
+            started_at: Some("2026-10-15T02:00:00Z".to_string()),
+            completed_at: None,
+            error: None,
+            dry_run_mode: None,
+            destination_mismatches: vec![DestinationMismatch {
+                path: "/mnt/usb".to_string(),
+                recorded_id: "1f0c".to_string(),
+                found_id: None,
+                found_at: 0,
+            }],
+            mass_change: Some(MassChange {
+                since_run: "run-1".to_string(),
+                files_before: 200,
+                changed_files: 150,
+                changed_percent: 75.0,
+                deleted_files: 0,
+                confirmed: false,
+            }),
+        };
+        let history = BackupHistoryResponse {
+            entries: vec![BackupHistoryEntry {
+                id: "run-1".to_string(),
+                started_at: "2026-10-14T02:00:00Z".to_string(),
+                completed_at: Some("2026-10-14T02:10:00Z".to_string()),
+                status: BackupStatus::Failed,
+                files_processed: 12,
+                bytes_processed: None,
+                error: Some("Destination is full".to_string()),
+                dry_run: false,
+                note: None,
+                report: None,
+            }],
+            total: 1,
+        };
+
+        let described = describe_status("http://127.0.0.1:8000", &status, &history);
+        assert_eq!(
+            described,
+            "Status:   running  [http://127.0.0.1:8000]\n\
+             Started:  2026-10-15T02:00:00Z\n\
+             Phase:    3/3 Copying files\n\
+             Progress: 40/160 file(s), 25.0%\n\
+             Elapsed:  5m 00s, 12.5 MB/s, about 1h 05m left\n\
+             Current:  IMG_0001.jpg (photos)\n\
+             Wrong drive: /mnt/usb has no marker instead of 1f0c\n\
+             Held:     150 of 200 file(s) (75.0%) changed and 0 deleted since run run-1; \
+             confirm with POST /api/confirm-mass-change\n\
+             Last run: run-1 failed, started 2026-10-14T02:00:00Z, finished 2026-10-14T02:10:00Z\n          \
+             12 file(s)\n\
+             Error:    Destination is full\n"
+        );
+    }
+
+    #[test]
+    fn test_reads_json_from_the_api() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/backup/", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0u8; 1024];
+            let read = stream.read(&mut request).unwrap();
+            let request = String::from_utf8_lossy(&request[..read]).to_string();
+            stream
+                .write_all(
+                    b"HTTP/1.0 200 OK\r\nContent-Type: application/json\r\n\r\n{\"total\":3}",
+                )
+                .unwrap();
+            request
+        });
+
+        let body: serde_json::Value = get_json(&url, "/api/history").unwrap();
+        assert_eq!(body["total"], 3);
+        assert!(server
+            .join()
+            .unwrap()
+            .starts_with("GET /backup/api/history HTTP/1.0\r\n"));
+    }
+}
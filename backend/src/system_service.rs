@@ -0,0 +1,543 @@
+use crate::models::config::Config;
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Service name used when `--name` isn't given
+const DEFAULT_SERVICE_NAME: &str = "rustyhashbackup";
+
+/// How `install-service` registers the backup to run at boot
+#[derive(Args)]
+pub struct InstallArgs {
+    /// Run the web server and API instead of the CLI scheduler
+    #[arg(long = "api")]
+    api: bool,
+    /// Name of the service or systemd unit
+    #[arg(long = "name", default_value = DEFAULT_SERVICE_NAME)]
+    name: String,
+    /// Account to run as; for systemd this defaults to the user who ran sudo
+    #[arg(long = "user")]
+    user: Option<String>,
+    /// Password of the `--user` account, which Windows needs to start the service as it
+    #[cfg(windows)]
+    #[arg(
+        long = "password",
+        requires = "user",
+        env = "RUSTYHASHBACKUP_SERVICE_PASSWORD"
+    )]
+    password: Option<String>,
+    /// Print the systemd unit instead of installing it
+    #[arg(long = "print")]
+    print: bool,
+}
+
+/// Which service `uninstall-service` removes
+#[derive(Args)]
+pub struct UninstallArgs {
+    /// Name the service or systemd unit was installed with
+    #[arg(long = "name", default_value = DEFAULT_SERVICE_NAME)]
+    name: String,
+}
+
+/// What the installed service runs
+#[derive(Debug, Clone, PartialEq)]
+struct ServiceSpec {
+    name: String,
+    executable: PathBuf,
+    config_file: PathBuf,
+    profile: Option<String>,
+    working_dir: PathBuf,
+    log_level: String,
+    api: bool,
+    user: Option<String>,
+}
+
+impl ServiceSpec {
+    fn new(
+        args: &InstallArgs,
+        config_file: &str,
+        profile: Option<&str>,
+        log_level: &str,
+    ) -> Result<Self> {
+        Ok(ServiceSpec {
+            name: args.name.clone(),
+            executable: std::env::current_exe().context("Failed to locate this executable")?,
+            config_file: std::path::absolute(config_file)
+                .with_context(|| format!("Failed to resolve {}", config_file))?,
+            profile: profile.map(str::to_string),
+            // Templates and static files are found relative to it in API mode
+            working_dir: std::env::current_dir().context("Failed to read the current directory")?,
+            log_level: log_level.to_string(),
+            api: args.api,
+            user: args
+                .user
+                .clone()
+                .or_else(|| std::env::var("SUDO_USER").ok()),
+        })
+    }
+
+    fn description(&self) -> &'static str {
+        if self.api {
+            "RustyHashBackup web server"
+        } else {
+            "RustyHashBackup scheduled backups"
+        }
+    }
+
+    /// Arguments the service starts this executable with
+    fn arguments(&self) -> Vec<String> {
+        let mut arguments = vec![
+            "--config".to_string(),
+            self.config_file.display().to_string(),
+            "--log-level".to_string(),
+            self.log_level.clone(),
+        ];
+        if let Some(profile) = &self.profile {
+            arguments.push("--profile".to_string());
+            arguments.push(profile.clone());
+        }
+        if self.api {
+            arguments.push("--api".to_string());
+        }
+        arguments
+    }
+}
+
+/// Register the scheduler, or the web server with `--api`, to start at boot
+pub fn install(
+    args: &InstallArgs,
+    config_file: &str,
+    profile: Option<&str>,
+    config: &Config,
+    log_level: &str,
+) -> Result<()> {
+    if !args.api && config.schedule.is_none() {
+        bail!(
+            "The configuration has no schedule, so the service would back up once and exit; \
+             add a schedule or install with --api"
+        );
+    }
+    let spec = ServiceSpec::new(args, config_file, profile, log_level)?;
+    platform::install(args, &spec)
+}
+
+/// Stop and remove a service installed by `install-service`
+pub fn uninstall(args: &UninstallArgs) -> Result<()> {
+    platform::uninstall(&args.name)
+}
+
+fn run_command(program: &str, args: &[&str]) -> Result<()> {
+    let status = Command::new(program)
+        .args(args)
+        .status()
+        .with_context(|| format!("Failed to run {}", program))?;
+    if !status.success() {
+        bail!("{} {} exited with {}", program, args.join(" "), status);
+    }
+    Ok(())
+}
+
+/// A systemd unit that starts the service at boot and restarts it if it fails
+fn systemd_unit(spec: &ServiceSpec) -> String {
+    let mut exec_start = vec![systemd_quote(&spec.executable.display().to_string())];
+    exec_start.extend(spec.arguments().iter().map(|arg| systemd_quote(arg)));
+
+    let mut unit = format!(
+        "[Unit]\n\
+         Description={}\n\
+         Wants=network-online.target\n\
+         After=network-online.target\n\
+         \n\
+         [Service]\n\
+         Type=simple\n",
+        spec.description()
+    );
+    if let Some(user) = &spec.user {
+        unit.push_str(&format!("User={}\n", user));
+    }
+    unit.push_str(&format!(
+        "WorkingDirectory={}\n\
+         ExecStart={}\n\
+         Restart=on-failure\n\
+         RestartSec=30\n\
+         \n\
+         [Install]\n\
+         WantedBy=multi-user.target\n",
+        systemd_quote(&spec.working_dir.display().to_string()),
+        exec_start.join(" ")
+    ));
+    unit
+}
+
+/// Quote an argument for a unit file, where `%` starts a specifier
+fn systemd_quote(arg: &str) -> String {
+    let escaped = arg.replace('%', "%%");
+    if !escaped.is_empty()
+        && !escaped
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '\\' | ';'))
+    {
+        return escaped;
+    }
+    format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The command line the Windows service control manager starts the service with
+#[cfg_attr(not(windows), allow(dead_code))]
+fn windows_command_line(spec: &ServiceSpec) -> String {
+    let mut arguments = vec![spec.executable.display().to_string()];
+    arguments.extend(spec.arguments());
+    arguments.push("--windows-service".to_string());
+    arguments.push(spec.working_dir.display().to_string());
+    arguments
+        .iter()
+        .map(|arg| windows_quote(arg))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Quote an argument so the C runtime splits it back out as it was
+#[cfg_attr(not(windows), allow(dead_code))]
+fn windows_quote(arg: &str) -> String {
+    if !arg.is_empty() && !arg.chars().any(|c| c.is_whitespace() || c == '"') {
+        return arg.to_string();
+    }
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                // Backslashes before a quote are escapes, so they and the quote are doubled up
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            _ => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                quoted.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{run_command, systemd_unit, InstallArgs, ServiceSpec};
+    use anyhow::{bail, Context, Result};
+    use log::warn;
+    use std::path::PathBuf;
+
+    fn unit_path(name: &str) -> PathBuf {
+        PathBuf::from(format!("/etc/systemd/system/{}.service", name))
+    }
+
+    pub fn install(args: &InstallArgs, spec: &ServiceSpec) -> Result<()> {
+        let unit = systemd_unit(spec);
+        if args.print {
+            print!("{}", unit);
+            return Ok(());
+        }
+
+        let path = unit_path(&spec.name);
+        std::fs::write(&path, unit).with_context(|| {
+            format!(
+                "Failed to write {} (run with sudo, or use --print)",
+                path.display()
+            )
+        })?;
+        run_command("systemctl", &["daemon-reload"])?;
+        run_command("systemctl", &["enable", "--now", &spec.name])?;
+
+        println!("Installed and started {}", path.display());
+        if spec.user.is_none() {
+            println!(
+                "The service runs as root; reinstall with --user to run it as another account"
+            );
+        }
+        println!("Follow its output with: journalctl -u {} -f", spec.name);
+        Ok(())
+    }
+
+    pub fn uninstall(name: &str) -> Result<()> {
+        let path = unit_path(name);
+        if !path.exists() {
+            bail!("No service is installed at {}", path.display());
+        }
+        if let Err(e) = run_command("systemctl", &["disable", "--now", name]) {
+            warn!("Failed to stop {}: {}", name, e);
+        }
+        std::fs::remove_file(&path)
+            .with_context(|| format!("Failed to remove {} (run with sudo)", path.display()))?;
+        run_command("systemctl", &["daemon-reload"])?;
+
+        println!("Removed {}", path.display());
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::{run_command, systemd_unit, windows_command_line, InstallArgs, ServiceSpec};
+    use anyhow::Result;
+    use log::warn;
+
+    pub fn install(args: &InstallArgs, spec: &ServiceSpec) -> Result<()> {
+        if args.print {
+            // A unit for running the same setup under WSL or on a Linux machine
+            print!("{}", systemd_unit(spec));
+            return Ok(());
+        }
+
+        let command_line = windows_command_line(spec);
+        let mut create = vec![
+            "create",
+            spec.name.as_str(),
+            "binPath=",
+            command_line.as_str(),
+            "start=",
+            "auto",
+            "DisplayName=",
+            spec.description(),
+        ];
+        if let Some(user) = &spec.user {
+            create.extend(["obj=", user.as_str()]);
+            if let Some(password) = &args.password {
+                create.extend(["password=", password.as_str()]);
+            }
+        }
+        run_command("sc.exe", &create)?;
+        run_command("sc.exe", &["description", &spec.name, spec.description()])?;
+        // Restart after a failure, waiting 30 seconds, and forget failures after a day
+        run_command(
+            "sc.exe",
+            &[
+                "failure",
+                &spec.name,
+                "reset=",
+                "86400",
+                "actions=",
+                "restart/30000",
+            ],
+        )?;
+        run_command("sc.exe", &["start", &spec.name])?;
+
+        println!("Installed and started the {} service", spec.name);
+        Ok(())
+    }
+
+    pub fn uninstall(name: &str) -> Result<()> {
+        if let Err(e) = run_command("sc.exe", &["stop", name]) {
+            warn!("Failed to stop {}: {}", name, e);
+        }
+        run_command("sc.exe", &["delete", name])?;
+
+        println!("Removed the {} service", name);
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod platform {
+    use super::{systemd_unit, InstallArgs, ServiceSpec};
+    use anyhow::{bail, Result};
+
+    pub fn install(args: &InstallArgs, spec: &ServiceSpec) -> Result<()> {
+        if args.print {
+            print!("{}", systemd_unit(spec));
+            return Ok(());
+        }
+        bail!("install-service supports systemd on Linux and Windows services; use --print for a systemd unit")
+    }
+
+    pub fn uninstall(_name: &str) -> Result<()> {
+        bail!("uninstall-service supports systemd on Linux and Windows services")
+    }
+}
+
+/// Runs the process as a Windows service once `--windows-service` is given
+///
+/// The service control manager is answered from a thread of its own while the scheduler or web
+/// server runs as usual, and stopping the service ends the process.
+#[cfg(windows)]
+pub mod windows {
+    use std::ffi::c_void;
+    use std::sync::atomic::{AtomicPtr, Ordering};
+    use std::{ptr, thread};
+    use windows_sys::core::PWSTR;
+    use windows_sys::Win32::System::Services::{
+        RegisterServiceCtrlHandlerExW, SetServiceStatus, StartServiceCtrlDispatcherW,
+        SERVICE_ACCEPT_SHUTDOWN, SERVICE_ACCEPT_STOP, SERVICE_CONTROL_INTERROGATE,
+        SERVICE_CONTROL_SHUTDOWN, SERVICE_CONTROL_STOP, SERVICE_RUNNING, SERVICE_STATUS,
+        SERVICE_STATUS_CURRENT_STATE, SERVICE_STOPPED, SERVICE_TABLE_ENTRYW,
+        SERVICE_WIN32_OWN_PROCESS,
+    };
+
+    const NO_ERROR: u32 = 0;
+    const ERROR_CALL_NOT_IMPLEMENTED: u32 = 120;
+
+    static STATUS_HANDLE: AtomicPtr<c_void> = AtomicPtr::new(ptr::null_mut());
+
+    fn wide(text: &str) -> Vec<u16> {
+        text.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    /// Connect to the service control manager, ending the process if that fails
+    pub fn spawn_dispatcher() {
+        let spawned = thread::Builder::new()
+            .name("service-dispatcher".to_string())
+            .spawn(|| {
+                // Own-process services are looked up by process, so the name isn't checked
+                let mut name = wide("RustyHashBackup");
+                let table = [
+                    SERVICE_TABLE_ENTRYW {
+                        lpServiceName: name.as_mut_ptr(),
+                        lpServiceProc: Some(service_main),
+                    },
+                    SERVICE_TABLE_ENTRYW {
+                        lpServiceName: ptr::null_mut(),
+                        lpServiceProc: None,
+                    },
+                ];
+                // SAFETY: the table ends with a null entry and outlives the call, which returns
+                // once the service has stopped
+                if unsafe { StartServiceCtrlDispatcherW(table.as_ptr()) } == 0 {
+                    log::error!(
+                        "Failed to connect to the service control manager: {}",
+                        std::io::Error::last_os_error()
+                    );
+                    std::process::exit(1);
+                }
+            });
+        if let Err(e) = spawned {
+            log::error!("Failed to start the service dispatcher thread: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    unsafe extern "system" fn service_main(_argc: u32, _argv: *mut PWSTR) {
+        let name = wide("RustyHashBackup");
+        // SAFETY: the name is null-terminated and the handler takes no context
+        let handle = unsafe {
+            RegisterServiceCtrlHandlerExW(name.as_ptr(), Some(control_handler), ptr::null())
+        };
+        if handle.is_null() {
+            log::error!(
+                "Failed to register the service control handler: {}",
+                std::io::Error::last_os_error()
+            );
+            std::process::exit(1);
+        }
+        STATUS_HANDLE.store(handle, Ordering::SeqCst);
+        set_state(SERVICE_RUNNING);
+
+        // The work runs on the main thread; this one only has to outlive it
+        loop {
+            thread::park();
+        }
+    }
+
+    unsafe extern "system" fn control_handler(
+        control: u32,
+        _event_type: u32,
+        _event_data: *mut c_void,
+        _context: *mut c_void,
+    ) -> u32 {
+        match control {
+            SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN => {
+                log::info!("Service stop requested");
+                set_state(SERVICE_STOPPED);
+                std::process::exit(0);
+            }
+            SERVICE_CONTROL_INTERROGATE => NO_ERROR,
+            _ => ERROR_CALL_NOT_IMPLEMENTED,
+        }
+    }
+
+    fn set_state(state: SERVICE_STATUS_CURRENT_STATE) {
+        let status = SERVICE_STATUS {
+            dwServiceType: SERVICE_WIN32_OWN_PROCESS,
+            dwCurrentState: state,
+            dwControlsAccepted: if state == SERVICE_RUNNING {
+                SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN
+            } else {
+                0
+            },
+            dwWin32ExitCode: NO_ERROR,
+            dwServiceSpecificExitCode: 0,
+            dwCheckPoint: 0,
+            dwWaitHint: 0,
+        };
+        // SAFETY: the handle came from RegisterServiceCtrlHandlerExW and the status is plain data
+        unsafe { SetServiceStatus(STATUS_HANDLE.load(Ordering::SeqCst), &status) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(api: bool, user: Option<&str>) -> ServiceSpec {
+        ServiceSpec {
+            name: "rustyhashbackup".to_string(),
+            executable: PathBuf::from("/opt/rhb/RustyHashBackUp"),
+            config_file: PathBuf::from("/etc/rhb/my config.json"),
+            profile: None,
+            working_dir: PathBuf::from("/opt/rhb"),
+            log_level: "info".to_string(),
+            api,
+            user: user.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_systemd_unit() {
+        let unit = systemd_unit(&spec(false, Some("alice")));
+        assert!(unit.contains("Description=RustyHashBackup scheduled backups\n"));
+        assert!(unit.contains("User=alice\n"));
+        assert!(unit.contains("WorkingDirectory=/opt/rhb\n"));
+        assert!(unit.contains(
+            "ExecStart=/opt/rhb/RustyHashBackUp --config \"/etc/rhb/my config.json\" --log-level info\n"
+        ));
+        assert!(unit.contains("WantedBy=multi-user.target\n"));
+
+        let unit = systemd_unit(&spec(true, None));
+        assert!(!unit.contains("User="));
+        assert!(unit.contains("--log-level info --api\n"));
+
+        let mut nightly = spec(false, None);
+        nightly.profile = Some("nightly full".to_string());
+        assert!(systemd_unit(&nightly).contains("--log-level info --profile \"nightly full\"\n"));
+    }
+
+    #[test]
+    fn test_systemd_quote() {
+        assert_eq!(systemd_quote("/opt/rhb"), "/opt/rhb");
+        assert_eq!(systemd_quote("100%"), "100%%");
+        assert_eq!(systemd_quote(r#"a "b"\c"#), r#""a \"b\"\\c""#);
+    }
+
+    #[test]
+    fn test_windows_command_line() {
+        let mut spec = spec(true, None);
+        spec.executable = PathBuf::from(r"C:\Program Files\RustyHashBackup\rhb.exe");
+        spec.working_dir = PathBuf::from(r"C:\Program Files\RustyHashBackup\");
+        assert_eq!(
+            windows_command_line(&spec),
+            r#""C:\Program Files\RustyHashBackup\rhb.exe" --config "/etc/rhb/my config.json" --log-level info --api --windows-service "C:\Program Files\RustyHashBackup\\""#
+        );
+    }
+
+    #[test]
+    fn test_windows_quote() {
+        assert_eq!(windows_quote("plain"), "plain");
+        assert_eq!(windows_quote(""), r#""""#);
+        assert_eq!(windows_quote(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(windows_quote(r#"a\"b c"#), r#""a\\\"b c""#);
+    }
+}
@@ -0,0 +1,518 @@
+use crate::api_state::{AppState, LaunchOutcome};
+use crate::desktop::SchedulerStatus;
+use crate::models::api::{BackupHistoryEntry, BackupProgress, BackupStatus};
+use crate::models::dry_run_mode::DryRunMode;
+use crate::models::run_selection::RunSelection;
+use log::{info, warn};
+use rocket::tokio::runtime::Handle;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// What the tray icon shows, derived from the shared application state
+#[derive(Debug, Clone, PartialEq)]
+struct TrayView {
+    status: BackupStatus,
+    status_line: String,
+    last_run_line: String,
+}
+
+impl TrayView {
+    fn from_state(state: &AppState) -> Self {
+        let status = state.get_status();
+        TrayView {
+            status_line: status_line(&status, state.get_progress().as_ref()),
+            last_run_line: last_run_line(state.get_history().first()),
+            status,
+        }
+    }
+
+    fn can_run_now(&self) -> bool {
+        !matches!(
+            self.status,
+            BackupStatus::Running | BackupStatus::Paused | BackupStatus::Stopping
+        )
+    }
+
+    /// Label and enabled state of the pause/resume item
+    fn pause_item(&self) -> (&'static str, bool) {
+        match self.status {
+            BackupStatus::Running => ("Pause", true),
+            BackupStatus::Paused => ("Resume", true),
+            _ => ("Pause", false),
+        }
+    }
+}
+
+fn status_line(status: &BackupStatus, progress: Option<&BackupProgress>) -> String {
+    match (status, progress) {
+        (BackupStatus::Running | BackupStatus::Paused, Some(progress)) if progress.phase > 0 => {
+            format!(
+                "{:?}: {} ({:.0}%)",
+                status, progress.phase_description, progress.percentage
+            )
+        }
+        _ => format!("{:?}", status),
+    }
+}
+
+fn last_run_line(last_run: Option<&BackupHistoryEntry>) -> String {
+    let Some(entry) = last_run else {
+        return "Last run: none yet".to_string();
+    };
+
+    let finished = entry
+        .completed_at
+        .as_deref()
+        .and_then(|at| chrono::DateTime::parse_from_rfc3339(at).ok())
+        .map(|at| {
+            at.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        });
+    match (&entry.error, finished) {
+        (Some(_), Some(at)) => format!("Last run: failed at {}", at),
+        (None, Some(at)) => format!("Last run: {} files at {}", entry.files_processed, at),
+        _ => format!("Last run: {:?}", entry.status),
+    }
+}
+
+/// What the CLI scheduler's tray icon shows
+#[derive(Debug, Clone, PartialEq)]
+struct SchedulerView {
+    status: BackupStatus,
+    next_run_line: String,
+    last_run_line: String,
+}
+
+impl SchedulerView {
+    fn from_status(scheduler: &SchedulerStatus) -> Self {
+        let last_run = scheduler.last_run.as_ref();
+        let status = match last_run {
+            _ if scheduler.running => BackupStatus::Running,
+            Some(result) if result.failed => BackupStatus::Failed,
+            Some(_) => BackupStatus::Completed,
+            None => BackupStatus::Idle,
+        };
+        let next_run_line = match scheduler.next_run {
+            _ if scheduler.running => "Running a backup now".to_string(),
+            Some(next) => format!("Next run: {}", local_time(next)),
+            None => "Next run: not scheduled".to_string(),
+        };
+        let last_run_line = match last_run {
+            Some(result) => format!(
+                "{} at {}: {}",
+                result.title,
+                local_time(result.finished_at),
+                result.summary
+            ),
+            None => "Last run: none yet".to_string(),
+        };
+        SchedulerView {
+            status,
+            next_run_line,
+            last_run_line,
+        }
+    }
+}
+
+fn local_time(at: chrono::DateTime<chrono::Utc>) -> String {
+    at.with_timezone(&chrono::Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+/// Quick actions offered in the tray menu
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TrayAction {
+    RunNow,
+    TogglePause,
+    OpenDashboard,
+    Quit,
+}
+
+fn perform(action: TrayAction, state: &AppState, dashboard_url: &str, runtime: &Handle) {
+    match action {
+        TrayAction::RunNow => {
+            // The backup task is spawned onto the server's runtime
+            let _guard = runtime.enter();
+            match state.launch_backup(DryRunMode::None, true, RunSelection::default(), None, None) {
+                Ok(LaunchOutcome::Started(id) | LaunchOutcome::Existing { backup_id: id, .. }) => {
+                    info!("Backup {} started from the system tray", id)
+                }
+                Err(e) => warn!("Couldn't start backup from the system tray: {}", e),
+            }
+        }
+        TrayAction::TogglePause => {
+            let result = if state.get_status() == BackupStatus::Paused {
+                state.resume_backup()
+            } else {
+                state.pause_backup()
+            };
+            if let Err(e) = result {
+                warn!("{}", e);
+            }
+        }
+        TrayAction::OpenDashboard => {
+            if let Err(e) = open_in_browser(dashboard_url) {
+                warn!("Failed to open {} in a browser: {}", dashboard_url, e);
+            }
+        }
+        TrayAction::Quit => {
+            info!("Quit requested from the system tray");
+            std::process::exit(0);
+        }
+    }
+}
+
+fn open_in_browser(url: &str) -> std::io::Result<()> {
+    #[cfg(windows)]
+    let mut command = {
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    };
+    #[cfg(target_os = "macos")]
+    let mut command = std::process::Command::new("open");
+    #[cfg(not(any(windows, target_os = "macos")))]
+    let mut command = std::process::Command::new("xdg-open");
+
+    command.arg(url).spawn().map(|_| ())
+}
+
+/// Show the tray icon on its own thread; the web server keeps running if it can't be created
+pub fn spawn(state: AppState, dashboard_url: String, runtime: Handle) {
+    let spawned = thread::Builder::new()
+        .name("system-tray".to_string())
+        .spawn(move || {
+            if let Err(e) = platform::run(state, &dashboard_url, &runtime) {
+                warn!(
+                    "System tray unavailable, continuing without it: {}. The dashboard is at {}",
+                    e, dashboard_url
+                );
+            }
+        });
+    if let Err(e) = spawned {
+        warn!("Failed to start the system tray thread: {}", e);
+    }
+}
+
+/// Show the CLI scheduler's tray icon on its own thread; the scheduler runs on without it
+pub fn spawn_scheduler(status: Arc<Mutex<SchedulerStatus>>) {
+    let spawned = thread::Builder::new()
+        .name("system-tray".to_string())
+        .spawn(move || {
+            if let Err(e) = platform::run_scheduler(status) {
+                warn!("System tray unavailable, continuing without it: {}", e);
+            }
+        });
+    if let Err(e) = spawned {
+        warn!("Failed to start the system tray thread: {}", e);
+    }
+}
+
+#[cfg(any(target_os = "linux", windows))]
+mod platform {
+    use super::{perform, SchedulerView, TrayAction, TrayView};
+    use crate::api_state::AppState;
+    use crate::desktop::SchedulerStatus;
+    use crate::models::api::BackupStatus;
+    use rocket::tokio::runtime::Handle;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tray_icon::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+    use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
+
+    /// How often menu clicks are handled and the icon is refreshed
+    const REFRESH_INTERVAL: Duration = Duration::from_millis(200);
+
+    const ICON_SIZE: u32 = 32;
+
+    struct TrayMenu {
+        status: MenuItem,
+        last_run: MenuItem,
+        run_now: MenuItem,
+        pause: MenuItem,
+        open_dashboard: MenuItem,
+        quit: MenuItem,
+    }
+
+    impl TrayMenu {
+        fn build() -> Result<(Menu, Self), String> {
+            let items = TrayMenu {
+                status: MenuItem::new("", false, None),
+                last_run: MenuItem::new("", false, None),
+                run_now: MenuItem::new("Run now", true, None),
+                pause: MenuItem::new("Pause", false, None),
+                open_dashboard: MenuItem::new("Open dashboard", true, None),
+                quit: MenuItem::new("Quit", true, None),
+            };
+            let menu = Menu::new();
+            menu.append_items(&[
+                &items.status,
+                &items.last_run,
+                &PredefinedMenuItem::separator(),
+                &items.run_now,
+                &items.pause,
+                &items.open_dashboard,
+                &PredefinedMenuItem::separator(),
+                &items.quit,
+            ])
+            .map_err(|e| e.to_string())?;
+            Ok((menu, items))
+        }
+
+        fn action_for(&self, event: &MenuEvent) -> Option<TrayAction> {
+            let id = event.id();
+            if id == self.run_now.id() {
+                Some(TrayAction::RunNow)
+            } else if id == self.pause.id() {
+                Some(TrayAction::TogglePause)
+            } else if id == self.open_dashboard.id() {
+                Some(TrayAction::OpenDashboard)
+            } else if id == self.quit.id() {
+                Some(TrayAction::Quit)
+            } else {
+                None
+            }
+        }
+
+        fn show(&self, tray: &TrayIcon, view: &TrayView) {
+            self.status.set_text(&view.status_line);
+            self.last_run.set_text(&view.last_run_line);
+            self.run_now.set_enabled(view.can_run_now());
+            let (label, enabled) = view.pause_item();
+            self.pause.set_text(label);
+            self.pause.set_enabled(enabled);
+
+            let tooltip = format!(
+                "RustyHashBackup\n{}\n{}",
+                view.status_line, view.last_run_line
+            );
+            let _ = tray.set_tooltip(Some(tooltip));
+            let _ = tray.set_icon(status_icon(&view.status));
+        }
+    }
+
+    pub fn run(state: AppState, dashboard_url: &str, runtime: &Handle) -> Result<(), String> {
+        let (menu, items) = TrayMenu::build()?;
+        let tray = TrayIconBuilder::new()
+            .with_menu(Box::new(menu))
+            .with_tooltip("RustyHashBackup")
+            .with_icon(status_icon(&BackupStatus::Idle).ok_or("failed to draw icon")?)
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        let mut shown: Option<TrayView> = None;
+        loop {
+            pump_messages();
+
+            while let Ok(event) = MenuEvent::receiver().try_recv() {
+                if let Some(action) = items.action_for(&event) {
+                    perform(action, &state, dashboard_url, runtime);
+                }
+            }
+
+            let view = TrayView::from_state(&state);
+            if shown.as_ref() != Some(&view) {
+                items.show(&tray, &view);
+                shown = Some(view);
+            }
+
+            std::thread::sleep(REFRESH_INTERVAL);
+        }
+    }
+
+    pub fn run_scheduler(status: Arc<Mutex<SchedulerStatus>>) -> Result<(), String> {
+        let next_run = MenuItem::new("", false, None);
+        let last_run = MenuItem::new("", false, None);
+        let quit = MenuItem::new("Quit", true, None);
+        let menu = Menu::new();
+        menu.append_items(&[
+            &next_run,
+            &last_run,
+            &PredefinedMenuItem::separator(),
+            &quit,
+        ])
+        .map_err(|e| e.to_string())?;
+        let tray = TrayIconBuilder::new()
+            .with_menu(Box::new(menu))
+            .with_tooltip("RustyHashBackup")
+            .with_icon(status_icon(&BackupStatus::Idle).ok_or("failed to draw icon")?)
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        let mut shown: Option<SchedulerView> = None;
+        loop {
+            pump_messages();
+
+            while let Ok(event) = MenuEvent::receiver().try_recv() {
+                if event.id() == quit.id() {
+                    log::info!("Quit requested from the system tray");
+                    std::process::exit(0);
+                }
+            }
+
+            let view = SchedulerView::from_status(&status.lock().unwrap());
+            if shown.as_ref() != Some(&view) {
+                next_run.set_text(&view.next_run_line);
+                last_run.set_text(&view.last_run_line);
+                let tooltip = format!(
+                    "RustyHashBackup\n{}\n{}",
+                    view.next_run_line, view.last_run_line
+                );
+                let _ = tray.set_tooltip(Some(tooltip));
+                let _ = tray.set_icon(status_icon(&view.status));
+                shown = Some(view);
+            }
+
+            std::thread::sleep(REFRESH_INTERVAL);
+        }
+    }
+
+    /// A filled circle in the status colour
+    fn status_icon(status: &BackupStatus) -> Option<Icon> {
+        let [r, g, b] = match status {
+            BackupStatus::Idle => [0x6b, 0x72, 0x80],
+            BackupStatus::Running => [0x25, 0x63, 0xeb],
+            BackupStatus::Paused | BackupStatus::Stopping => [0xf5, 0x9e, 0x0b],
+            BackupStatus::Failed | BackupStatus::Interrupted => [0xdc, 0x26, 0x26],
+            BackupStatus::Completed => [0x16, 0xa3, 0x4a],
+        };
+
+        let center = (ICON_SIZE as f32 - 1.0) / 2.0;
+        let radius = ICON_SIZE as f32 / 2.0 - 1.0;
+        let mut rgba = Vec::with_capacity((ICON_SIZE * ICON_SIZE * 4) as usize);
+        for y in 0..ICON_SIZE {
+            for x in 0..ICON_SIZE {
+                let distance = (x as f32 - center).hypot(y as f32 - center);
+                let alpha = if distance <= radius { 0xff } else { 0 };
+                rgba.extend_from_slice(&[r, g, b, alpha]);
+            }
+        }
+        Icon::from_rgba(rgba, ICON_SIZE, ICON_SIZE).ok()
+    }
+
+    /// Dispatch pending window messages; the tray's hidden window lives on this thread
+    #[cfg(windows)]
+    fn pump_messages() {
+        use windows_sys::Win32::UI::WindowsAndMessaging::{
+            DispatchMessageW, PeekMessageW, TranslateMessage, MSG, PM_REMOVE,
+        };
+
+        // SAFETY: MSG is plain data and is only read after PeekMessageW fills it
+        unsafe {
+            let mut msg: MSG = std::mem::zeroed();
+            while PeekMessageW(&mut msg, std::ptr::null_mut(), 0, 0, PM_REMOVE) != 0 {
+                TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+        }
+    }
+
+    /// The StatusNotifierItem backend talks D-Bus from its own thread
+    #[cfg(not(windows))]
+    fn pump_messages() {}
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod platform {
+    use crate::api_state::AppState;
+    use crate::desktop::SchedulerStatus;
+    use rocket::tokio::runtime::Handle;
+    use std::sync::{Arc, Mutex};
+
+    pub fn run(_state: AppState, _dashboard_url: &str, _runtime: &Handle) -> Result<(), String> {
+        Err("tray mode is only supported on Linux and Windows".to_string())
+    }
+
+    pub fn run_scheduler(_status: Arc<Mutex<SchedulerStatus>>) -> Result<(), String> {
+        Err("tray mode is only supported on Linux and Windows".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::desktop::RunResult;
+
+    fn history_entry(error: Option<&str>) -> BackupHistoryEntry {
+        BackupHistoryEntry {
+            id: "run".to_string(),
+            started_at: "2026-01-02T03:00:00+00:00".to_string(),
+            completed_at: Some("2026-01-02T03:04:05+00:00".to_string()),
+            status: if error.is_some() {
+                BackupStatus::Failed
+            } else {
+                BackupStatus::Completed
+            },
+            files_processed: 42,
+            bytes_processed: None,
+            error: error.map(str::to_string),
+            dry_run: false,
+            note: None,
+            report: None,
+        }
+    }
+
+    #[test]
+    fn test_status_line_includes_phase_while_running() {
+        let progress = BackupProgress {
+            phase: 3,
+            phase_description: "Copying files".to_string(),
+            percentage: 41.6,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            status_line(&BackupStatus::Running, Some(&progress)),
+            "Running: Copying files (42%)"
+        );
+        assert_eq!(status_line(&BackupStatus::Idle, Some(&progress)), "Idle");
+    }
+
+    #[test]
+    fn test_last_run_line() {
+        assert_eq!(last_run_line(None), "Last run: none yet");
+        assert!(last_run_line(Some(&history_entry(None))).starts_with("Last run: 42 files at "));
+        assert!(last_run_line(Some(&history_entry(Some("disk full"))))
+            .starts_with("Last run: failed at "));
+    }
+
+    #[test]
+    fn test_scheduler_view() {
+        let mut scheduler = SchedulerStatus::default();
+        let view = SchedulerView::from_status(&scheduler);
+        assert_eq!(view.status, BackupStatus::Idle);
+        assert_eq!(view.next_run_line, "Next run: not scheduled");
+        assert_eq!(view.last_run_line, "Last run: none yet");
+
+        scheduler.next_run = Some(chrono::Utc::now());
+        scheduler.last_run = Some(RunResult {
+            finished_at: chrono::Utc::now(),
+            failed: true,
+            title: "Backup failed".to_string(),
+            summary: "destination is full".to_string(),
+        });
+        let view = SchedulerView::from_status(&scheduler);
+        assert_eq!(view.status, BackupStatus::Failed);
+        assert!(view.next_run_line.starts_with("Next run: "));
+        assert!(view.last_run_line.starts_with("Backup failed at "));
+        assert!(view.last_run_line.ends_with(": destination is full"));
+
+        scheduler.running = true;
+        let view = SchedulerView::from_status(&scheduler);
+        assert_eq!(view.status, BackupStatus::Running);
+        assert_eq!(view.next_run_line, "Running a backup now");
+    }
+
+    #[test]
+    fn test_menu_state_follows_status() {
+        let mut view = TrayView::from_state(&AppState::new());
+        assert!(view.can_run_now());
+        assert_eq!(view.pause_item(), ("Pause", false));
+
+        view.status = BackupStatus::Paused;
+        assert!(!view.can_run_now());
+        assert_eq!(view.pause_item(), ("Resume", true));
+    }
+}
@@ -0,0 +1,117 @@
+use crate::models::user::{Role, User};
+use crate::output::OutputFormat;
+use crate::repo::sqlite::{delete_user, insert_user, select_users, update_user_password};
+use crate::service::audit::record_audit_event;
+use crate::service::auth::hash_password;
+use anyhow::{bail, Context, Result};
+use clap::Subcommand;
+use std::io::{BufRead, IsTerminal, Write};
+
+/// Shortest password `user add` and `user set-password` accept
+const MIN_PASSWORD_LEN: usize = 8;
+
+/// Who may sign in to the web UI and API; nobody has to until the first user is added
+#[derive(Subcommand)]
+pub enum UserCommand {
+    /// Add a user, reading their password from standard input
+    Add {
+        name: String,
+        #[arg(long = "role", value_enum, default_value = "viewer")]
+        role: Role,
+    },
+    /// Replace a user's password, read from standard input, and end their sessions
+    SetPassword { name: String },
+    /// Remove a user and end their sessions
+    Remove { name: String },
+    /// List the users and their roles
+    List,
+}
+
+/// Add, change, remove or list users
+pub fn run(command: &UserCommand, output: OutputFormat) -> Result<()> {
+    match command {
+        UserCommand::Add { name, role } => {
+            if name.trim().is_empty() || name.contains(char::is_whitespace) {
+                bail!("User name must be non-empty and contain no whitespace");
+            }
+            let user = User {
+                name: name.clone(),
+                role: *role,
+                created_at: chrono::Utc::now().timestamp(),
+            };
+            if !insert_user(&user, &hash_password(&read_password()?))? {
+                bail!("User {} already exists", name);
+            }
+            record_audit_event(
+                "user_added",
+                "cli",
+                format!("{} as {}", name, role.as_str()),
+            )?;
+            output.print(&user, || format!("Added {} as {}\n", name, role.as_str()))?;
+        }
+        UserCommand::SetPassword { name } => {
+            if !update_user_password(name, &hash_password(&read_password()?))? {
+                bail!("No user named {}", name);
+            }
+            record_audit_event("password_changed", "cli", name.clone())?;
+            output.print(&serde_json::json!({ "name": name }), || {
+                format!("Changed the password of {}\n", name)
+            })?;
+        }
+        UserCommand::Remove { name } => {
+            if !delete_user(name)? {
+                bail!("No user named {}", name);
+            }
+            record_audit_event("user_removed", "cli", name.clone())?;
+            output.print(&serde_json::json!({ "name": name }), || {
+                format!("Removed {}\n", name)
+            })?;
+        }
+        UserCommand::List => {
+            let users = select_users().context("Failed to read the users")?;
+            output.print(&users, || {
+                let mut out = String::new();
+                if users.is_empty() {
+                    out.push_str(
+                        "No users; the web UI and API are open to anyone who can reach them\n",
+                    );
+                }
+                for user in &users {
+                    let added = chrono::DateTime::from_timestamp(user.created_at, 0)
+                        .map(|at| at.format("%Y-%m-%d").to_string())
+                        .unwrap_or_default();
+                    out.push_str(&format!(
+                        "{:<24} {:<8} added {}\n",
+                        user.name,
+                        user.role.as_str(),
+                        added
+                    ));
+                }
+                out
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// First line of standard input, prompting for it when run from a terminal
+fn read_password() -> Result<String> {
+    let stdin = std::io::stdin();
+    if stdin.is_terminal() {
+        eprint!("Password: ");
+        std::io::stderr().flush()?;
+    }
+    let mut password = String::new();
+    stdin
+        .lock()
+        .read_line(&mut password)
+        .context("Failed to read the password from standard input")?;
+    let password = password.trim_end_matches(['\r', '\n']).to_string();
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        bail!(
+            "Password must be at least {} characters long",
+            MIN_PASSWORD_LEN
+        );
+    }
+    Ok(password)
+}
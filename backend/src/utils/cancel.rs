@@ -0,0 +1,13 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static CANCEL_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Ask the CLI run in progress to stop taking on files, as Ctrl+C does
+pub fn request_cancel() {
+    CANCEL_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Whether the run was asked to stop with `request_cancel`
+pub fn is_cancel_requested() -> bool {
+    CANCEL_REQUESTED.load(Ordering::SeqCst)
+}
@@ -0,0 +1,183 @@
+use once_cell::sync::Lazy;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Mutex, RwLock};
+use std::thread;
+use std::time::Duration;
+
+/// Faults injected into the copies of a run by the hidden `--chaos` option, to check that
+/// retries, error reports and notifications work before trusting a setup with real data
+///
+/// Which files are hit depends only on the seed and each file's path, so a run can be repeated.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Chaos {
+    /// Share of copies that fail, from 0 to 1
+    pub copy_failures: f64,
+    /// Share of files whose first copy attempt finds them locked, so the copy is retried
+    pub locked: f64,
+    /// Share of copies that fail verification as if they had been damaged on the way
+    pub hash_mismatches: f64,
+    /// Delay before every copy
+    pub slow_io: Duration,
+    pub seed: u64,
+}
+
+impl FromStr for Chaos {
+    type Err = String;
+
+    /// Parse a spec like `copy=0.1,locked=0.05,hash=0.02,slow-ms=200,seed=7`
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut chaos = Chaos {
+            seed: uuid::Uuid::new_v4().as_u64_pair().0,
+            ..Default::default()
+        };
+        for part in spec
+            .split(',')
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+        {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("'{}' is not key=value", part))?;
+            let share = || match value.parse::<f64>() {
+                Ok(share) if (0.0..=1.0).contains(&share) => Ok(share),
+                _ => Err(format!(
+                    "{} must be a share from 0 to 1, not '{}'",
+                    key, value
+                )),
+            };
+            let number = || {
+                value
+                    .parse::<u64>()
+                    .map_err(|_| format!("{} must be a whole number, not '{}'", key, value))
+            };
+            match key {
+                "copy" => chaos.copy_failures = share()?,
+                "locked" => chaos.locked = share()?,
+                "hash" => chaos.hash_mismatches = share()?,
+                "slow-ms" => chaos.slow_io = Duration::from_millis(number()?),
+                "seed" => chaos.seed = number()?,
+                _ => {
+                    return Err(format!(
+                        "unknown fault '{}'; use copy, locked, hash, slow-ms or seed",
+                        key
+                    ))
+                }
+            }
+        }
+        Ok(chaos)
+    }
+}
+
+impl Chaos {
+    /// Whether the fault `kind` hits `path`, for a share `chance` of all paths
+    fn hits(&self, kind: &str, path: &Path, chance: f64) -> bool {
+        if chance <= 0.0 {
+            return false;
+        }
+        let mut hasher = DefaultHasher::new();
+        (self.seed, kind, path).hash(&mut hasher);
+        (hasher.finish() as f64 / u64::MAX as f64) < chance
+    }
+}
+
+static CHAOS: Lazy<RwLock<Option<Chaos>>> = Lazy::new(|| RwLock::new(None));
+
+/// Files already found locked once, so their retries go through
+static LOCKED_ONCE: Lazy<Mutex<HashSet<PathBuf>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// Inject `chaos` into every later copy of this process
+pub fn enable(chaos: Chaos) {
+    *CHAOS.write().unwrap() = Some(chaos);
+}
+
+/// Stop injecting faults, so later tests copy normally
+#[cfg(test)]
+pub fn disable() {
+    *CHAOS.write().unwrap() = None;
+}
+
+/// Wait and fail as the faults for copying `source` say, before it is copied
+pub fn before_copy(source: &Path) -> io::Result<()> {
+    let Some(chaos) = CHAOS.read().unwrap().clone() else {
+        return Ok(());
+    };
+    if !chaos.slow_io.is_zero() {
+        thread::sleep(chaos.slow_io);
+    }
+    if chaos.hits("copy", source, chaos.copy_failures) {
+        return Err(io::Error::other("copy failure injected by --chaos"));
+    }
+    if chaos.hits("locked", source, chaos.locked)
+        && LOCKED_ONCE.lock().unwrap().insert(source.to_path_buf())
+    {
+        return Err(locked_error());
+    }
+    Ok(())
+}
+
+/// Whether the copy at `backup` is to fail verification
+pub fn damages(backup: &Path) -> bool {
+    CHAOS
+        .read()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|chaos| chaos.hits("hash", backup, chaos.hash_mismatches))
+}
+
+#[cfg(windows)]
+fn locked_error() -> io::Error {
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    io::Error::from_raw_os_error(ERROR_SHARING_VIOLATION)
+}
+
+#[cfg(not(windows))]
+fn locked_error() -> io::Error {
+    io::Error::new(io::ErrorKind::ResourceBusy, "file lock injected by --chaos")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_chaos_spec() {
+        let chaos: Chaos = "copy=0.1, hash=1,slow-ms=250,seed=7".parse().unwrap();
+        assert_eq!(
+            chaos,
+            Chaos {
+                copy_failures: 0.1,
+                locked: 0.0,
+                hash_mismatches: 1.0,
+                slow_io: Duration::from_millis(250),
+                seed: 7,
+            }
+        );
+        assert!("copy=2".parse::<Chaos>().is_err());
+        assert!("explode=0.5".parse::<Chaos>().is_err());
+        assert!("copy".parse::<Chaos>().is_err());
+    }
+
+    #[test]
+    fn test_hits_depend_on_seed_and_path_only() {
+        let chaos: Chaos = "seed=7".parse().unwrap();
+        let paths: Vec<PathBuf> = (0..1000)
+            .map(|i| PathBuf::from(format!("/src/file{}.txt", i)))
+            .collect();
+        let hit = |chaos: &Chaos| {
+            paths
+                .iter()
+                .filter(|path| chaos.hits("copy", path, 0.2))
+                .count()
+        };
+
+        assert_eq!(hit(&chaos), hit(&chaos));
+        assert!((150..250).contains(&hit(&chaos)), "{}", hit(&chaos));
+        assert!(!chaos.hits("copy", &paths[0], 0.0));
+        assert!(chaos.hits("copy", &paths[0], 1.0));
+    }
+}
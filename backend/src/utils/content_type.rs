@@ -0,0 +1,70 @@
+use log::{debug, warn};
+use std::path::Path;
+
+/// Returns true if a MIME type matches a `type/subtype` or `type/*` pattern
+pub fn content_type_matches(pattern: &str, mime_type: &str) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(kind) => mime_type
+            .split_once('/')
+            .is_some_and(|(mime_kind, _)| mime_kind.eq_ignore_ascii_case(kind)),
+        None => pattern.eq_ignore_ascii_case(mime_type),
+    }
+}
+
+/// Returns true if the file's sniffed content type matches any exclusion pattern
+///
+/// Only the first bytes of the file are read. Files with an unrecognised type, or that
+/// can't be read, are kept so the later phases can back them up or report the error.
+pub fn is_excluded_content_type(file: &Path, patterns: &[String]) -> bool {
+    if patterns.is_empty() {
+        return false;
+    }
+
+    match infer::get_from_path(file) {
+        Ok(Some(kind)) => {
+            let excluded = patterns
+                .iter()
+                .any(|p| content_type_matches(p, kind.mime_type()));
+            if excluded {
+                debug!("Excluding {:?} by content type {}", file, kind.mime_type());
+            }
+            excluded
+        }
+        Ok(None) => false,
+        Err(e) => {
+            warn!("Failed to sniff content type of {:?}: {}", file, e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_content_type_matches_wildcard_and_exact() {
+        assert!(content_type_matches("video/*", "video/mp4"));
+        assert!(content_type_matches("image/png", "image/png"));
+        assert!(!content_type_matches("video/*", "image/png"));
+        assert!(!content_type_matches("image/jpeg", "image/png"));
+    }
+
+    #[test]
+    fn test_excluded_content_type_sniffs_magic_bytes() {
+        let dir = TempDir::new().unwrap();
+        // A PNG signature with a misleading extension, plus a plain text file
+        let image = dir.path().join("holiday.txt");
+        fs::write(&image, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        let notes = dir.path().join("notes.txt");
+        fs::write(&notes, "just text").unwrap();
+
+        let patterns = ["image/*".to_string()];
+
+        assert!(is_excluded_content_type(&image, &patterns));
+        assert!(!is_excluded_content_type(&notes, &patterns));
+        assert!(!is_excluded_content_type(&image, &[]));
+    }
+}
@@ -1,97 +1,278 @@
-use log::{Level, LevelFilter, Log, Metadata, Record};
+use crate::models::config::Config;
+use crate::models::error::Result;
+use crate::models::log_row::LogRow;
+use crate::repo::sqlite::{insert_log_entries, prune_logs};
+use log::info;
+use once_cell::sync::Lazy;
+use serde_json::{Map, Value};
+use std::cell::Cell;
+use std::fmt;
+use std::io::IsTerminal;
 use std::sync::mpsc::{self, Sender};
 use std::thread;
+use tokio::sync::broadcast;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Subscriber};
+use tracing_log::NormalizeEvent;
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
+
+thread_local! {
+    /// Set on the writer thread, so the database's own events aren't written back to it
+    static WRITING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Most log entries the background worker writes in one transaction
+const WRITE_BATCH: usize = 500;
+
+/// Entries a stream subscriber can fall behind by before it misses some
+const FEED_CAPACITY: usize = 1024;
+
+/// Entries written to the Logs table, for streaming them as they arrive
+static LOG_FEED: Lazy<broadcast::Sender<LogRow>> =
+    Lazy::new(|| broadcast::channel(FEED_CAPACITY).0);
 
 /// Message sent from logger to background worker
 struct LogMessage {
-    level: String,
+    timestamp: i64,
+    level: &'static str,
     message: String,
-    source: Option<String>,
+    context: Option<String>,
+    source: String,
 }
 
-/// Custom logger that writes to the database asynchronously
-pub struct DatabaseLogger {
-    level: LevelFilter,
+/// Writes this crate's events to the Logs table from a background thread
+///
+/// The fields of the spans an event happened in, such as the run ID and source name, are stored
+/// as a JSON object in the Context column, together with the event's own fields.
+struct DatabaseLayer {
     tx: Sender<LogMessage>,
 }
 
-impl DatabaseLogger {
-    /// Create a new DatabaseLogger and start the background worker thread
-    pub fn new(level: LevelFilter) -> Self {
+/// Fields a span was created or updated with
+struct SpanFields(Map<String, Value>);
+
+impl DatabaseLayer {
+    /// Create a new DatabaseLayer and start the background worker thread
+    fn new() -> Self {
         let (tx, rx) = mpsc::channel::<LogMessage>();
 
         // Spawn background worker thread
         thread::spawn(move || {
+            WRITING.with(|writing| writing.set(true));
             log_writer_worker(rx);
         });
 
-        DatabaseLogger { level, tx }
-    }
-
-    /// Get the log level filter
-    pub fn level(&self) -> LevelFilter {
-        self.level
+        DatabaseLayer { tx }
     }
 }
 
-impl Log for DatabaseLogger {
-    fn enabled(&self, metadata: &Metadata) -> bool {
-        metadata.level() <= self.level
+impl<S> Layer<S> for DatabaseLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = Map::new();
+        attrs.record(&mut FieldVisitor::new(&mut fields));
+        span.extensions_mut().insert(SpanFields(fields));
     }
 
-    fn log(&self, record: &Record) {
-        if !self.enabled(record.metadata()) {
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
             return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(SpanFields(fields)) = extensions.get_mut::<SpanFields>() {
+            values.record(&mut FieldVisitor::new(fields));
         }
+    }
 
-        let level = match record.level() {
-            Level::Error => "ERROR",
-            Level::Warn => "WARN",
-            Level::Info => "INFO",
-            Level::Debug => "DEBUG",
-            Level::Trace => "TRACE",
-        };
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        // Records from the `log` macros name their real target in a field
+        let normalized = event.normalized_metadata();
+        let metadata = normalized.as_ref().unwrap_or_else(|| event.metadata());
+        // Requests served by Rocket and the like would flood the table
+        if WRITING.with(Cell::get) || !metadata.target().starts_with(env!("CARGO_CRATE_NAME")) {
+            return;
+        }
 
-        // Also log to stderr for console output
-        eprintln!(
-            "[{}] {}: {}",
-            chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
-            level,
-            record.args()
-        );
+        let mut context = Map::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if let Some(SpanFields(fields)) = span.extensions().get::<SpanFields>() {
+                    context.extend(fields.clone());
+                }
+            }
+        }
+        let mut visitor = FieldVisitor::new(&mut context);
+        event.record(&mut visitor);
+        let message = visitor.message.take().unwrap_or_default();
 
         let message = LogMessage {
-            level: level.to_string(),
-            message: format!("{}", record.args()),
-            source: record.module_path().map(|s| s.to_string()),
+            timestamp: chrono::Utc::now().timestamp(),
+            level: level_name(metadata.level()),
+            message,
+            context: (!context.is_empty()).then(|| Value::Object(context).to_string()),
+            source: metadata.target().to_string(),
         };
 
         // Send to background worker thread (non-blocking)
         // Ignore errors if channel is disconnected
         let _ = self.tx.send(message);
     }
+}
+
+fn level_name(level: &Level) -> &'static str {
+    match *level {
+        Level::ERROR => "ERROR",
+        Level::WARN => "WARN",
+        Level::INFO => "INFO",
+        Level::DEBUG => "DEBUG",
+        Level::TRACE => "TRACE",
+    }
+}
+
+/// Collects fields into a JSON object, keeping an event's message apart
+struct FieldVisitor<'a> {
+    fields: &'a mut Map<String, Value>,
+    message: Option<String>,
+}
+
+impl<'a> FieldVisitor<'a> {
+    fn new(fields: &'a mut Map<String, Value>) -> Self {
+        FieldVisitor {
+            fields,
+            message: None,
+        }
+    }
+
+    fn insert(&mut self, field: &Field, value: Value) {
+        match field.name() {
+            "message" => self.message = Some(value.as_str().unwrap_or_default().to_string()),
+            // Where a `log` record came from, which the source column already names
+            name if name.starts_with("log.") => {}
+            name => {
+                self.fields.insert(name.to_string(), value);
+            }
+        }
+    }
+}
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, Value::from(value));
+    }
 
-    fn flush(&self) {
-        // Channel-based logging doesn't need explicit flushing
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.insert(field, Value::from(format!("{:?}", value)));
     }
 }
 
 /// Background worker that writes log messages to the database
+///
+/// Messages that queued up while a batch was being written go into the next one, so a burst of
+/// debug logging costs one transaction per batch rather than one per message.
 fn log_writer_worker(rx: mpsc::Receiver<LogMessage>) {
-    while let Ok(log_msg) = rx.recv() {
-        // Write to database
+    while let Ok(first) = rx.recv() {
+        let mut batch: Vec<LogRow> = std::iter::once(first)
+            .chain(rx.try_iter().take(WRITE_BATCH - 1))
+            .map(|log_msg| LogRow {
+                id: 0,
+                timestamp: log_msg.timestamp,
+                level: log_msg.level.to_string(),
+                message: log_msg.message,
+                context: log_msg.context,
+                source: Some(log_msg.source),
+            })
+            .collect();
         // Ignore errors to prevent infinite recursion if logging fails
-        let _ = crate::repo::sqlite::insert_log_entry(
-            &log_msg.level,
-            &log_msg.message,
-            log_msg.source.as_deref(),
-        );
+        if insert_log_entries(&mut batch).is_ok() && LOG_FEED.receiver_count() > 0 {
+            for log in batch {
+                let _ = LOG_FEED.send(log);
+            }
+        }
     }
 }
 
-/// Initialize the database logger
-pub fn init_database_logger(level: LevelFilter) -> Result<(), log::SetLoggerError> {
-    let logger = DatabaseLogger::new(level);
-    log::set_max_level(level);
-    log::set_boxed_logger(Box::new(logger))
+/// Receive the entries written to the Logs table from now on
+pub fn subscribe_logs() -> broadcast::Receiver<LogRow> {
+    LOG_FEED.subscribe()
+}
+
+/// Prune the Logs table down to the configured retention, returning how many entries went
+pub fn apply_log_retention(config: &Config) -> Result<usize> {
+    let deleted = prune_logs(config.log_retention_days, config.max_log_rows)?;
+    if deleted > 0 {
+        info!("Pruned {} log entries past the retention limits", deleted);
+    }
+    Ok(deleted)
+}
+
+/// Log to stderr and to the Logs table at `level`, unless `RUST_LOG` says otherwise
+///
+/// Records made through the `log` macros are turned into `tracing` events, so they carry the
+/// fields of the spans they happen in.
+pub fn init_logging(level: LevelFilter) {
+    let filter = EnvFilter::builder()
+        .with_default_directive(level.into())
+        .from_env_lossy();
+    let console = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal());
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(console)
+        .with(DatabaseLayer::new())
+        .init();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing::info_span;
+
+    fn capture(emit: impl FnOnce()) -> Vec<LogMessage> {
+        let (tx, rx) = mpsc::channel();
+        let subscriber = tracing_subscriber::registry().with(DatabaseLayer { tx });
+        tracing::subscriber::with_default(subscriber, emit);
+        rx.try_iter().collect()
+    }
+
+    #[test]
+    fn test_events_carry_the_fields_of_their_spans() {
+        let messages = capture(|| {
+            let _run = info_span!("run", run_id = "abc").entered();
+            let _source = info_span!("source", source = "photos").entered();
+            tracing::warn!(file = "a.jpg", "Copy failed");
+            tracing::info!(target: "rocket::server", "GET /api/logs");
+        });
+
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].level, "WARN");
+        assert_eq!(messages[0].message, "Copy failed");
+        let context: Value = serde_json::from_str(messages[0].context.as_deref().unwrap()).unwrap();
+        assert_eq!(
+            context,
+            serde_json::json!({"run_id": "abc", "source": "photos", "file": "a.jpg"})
+        );
+    }
 }
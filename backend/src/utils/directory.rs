@@ -1,39 +1,225 @@
+use crate::models::config::FileAttribute;
 use crate::models::error::{BackupError, Result};
-use std::path::{Path, PathBuf};
+use ignore::{WalkBuilder, WalkState};
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
 use std::time::{Duration, UNIX_EPOCH};
-use walkdir::WalkDir;
-
-pub fn get_files_in_path(
+use unicode_normalization::UnicodeNormalization;
+
+/// Call `visit` with every file below `dir`, skipping `skip_dirs`, anything excluded by the
+/// named ignore files and files and directories with any of `exclude_attributes`
+///
+/// Subdirectories are read on up to `threads` threads (0 picks a default), so `visit` is called
+/// concurrently and in no particular order. The walk ends early once `visit` returns false.
+/// Ignore files such as `.gitignore` or `.backupignore` use gitignore syntax and apply to the
+/// directory they are in and everything below it, wherever they appear in the walk.
+///
+/// An entry that can't be read fails the walk, unless `skip_error` is given: it is then called
+/// with the entry's path, if the error names one, and the error, and the walk carries on.
+#[allow(clippy::too_many_arguments)]
+pub fn walk_files_in_path(
     dir: &str,
     skip_dirs: &[String],
     max_depth: &Option<usize>,
-) -> Result<Vec<PathBuf>> {
-    let mut files = Vec::new();
-    let mut dir_walk = WalkDir::new(dir)
-        .max_depth(max_depth.unwrap_or(usize::MAX))
+    ignore_files: &[String],
+    exclude_attributes: &[FileAttribute],
+    threads: usize,
+    skip_error: Option<&(dyn Fn(Option<PathBuf>, String) + Sync)>,
+    visit: &(dyn Fn(PathBuf) -> bool + Sync),
+) -> Result<()> {
+    let mut walk = WalkBuilder::new(long_path(Path::new(dir)));
+    // Only the configured ignore files apply; hidden files and global git excludes are kept
+    walk.standard_filters(false)
         .follow_links(true)
-        .into_iter();
+        .max_depth(*max_depth)
+        .threads(threads);
+    for name in ignore_files {
+        walk.add_custom_ignore_filename(name);
+    }
+    let skip_dirs = skip_dirs.to_vec();
+    let exclude_attributes = exclude_attributes.to_vec();
+    walk.filter_entry(move |entry| {
+        let skipped_dir = entry.file_type().is_some_and(|t| t.is_dir())
+            && skip_dirs
+                .iter()
+                .any(|skip| entry.file_name() == skip.as_str());
+        // The source itself is backed up even if it is hidden
+        let excluded = entry.depth() > 0
+            && exclude_attributes
+                .iter()
+                .any(|attribute| has_file_attribute(entry.path(), *attribute));
+        !(skipped_dir || excluded)
+    });
+
+    let first_error = Mutex::new(None);
+    walk.build_parallel().run(|| {
+        Box::new(|entry| {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    let Some(skip) = skip_error else {
+                        first_error.lock().unwrap().get_or_insert(e);
+                        return WalkState::Quit;
+                    };
+                    let path = error_path(&e).map(|path| strip_long_path_prefix(path).into_owned());
+                    skip(path, e.to_string());
+                    return WalkState::Continue;
+                }
+            };
+
+            if entry.file_type().is_some_and(|t| t.is_dir()) {
+                return WalkState::Continue;
+            }
+            if visit(strip_long_path_prefix(entry.path()).into_owned()) {
+                WalkState::Continue
+            } else {
+                WalkState::Quit
+            }
+        })
+    });
+
+    match first_error.into_inner().unwrap() {
+        Some(e) => Err(BackupError::DirectoryRead(format!(
+            "Failed to read directory entry: {}",
+            e
+        ))),
+        None => Ok(()),
+    }
+}
 
-    while let Some(entry) = dir_walk.next() {
+/// The path a walk error is about, if it names one
+fn error_path(error: &ignore::Error) -> Option<&Path> {
+    match error {
+        ignore::Error::WithPath { path, .. } => Some(path),
+        ignore::Error::Loop { child, .. } => Some(child),
+        ignore::Error::WithDepth { err, .. } | ignore::Error::WithLineNumber { err, .. } => {
+            error_path(err)
+        }
+        _ => None,
+    }
+}
+
+/// Whether the file or directory at `path` has `attribute`
+///
+/// Names starting with a dot count as hidden everywhere. Attributes the platform doesn't have,
+/// or can't be read for `path`, never match.
+pub fn has_file_attribute(path: &Path, attribute: FileAttribute) -> bool {
+    let dot_file = attribute == FileAttribute::Hidden
+        && path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+    dot_file || has_platform_attribute(path, attribute)
+}
+
+#[cfg(windows)]
+fn has_platform_attribute(path: &Path, attribute: FileAttribute) -> bool {
+    use std::os::windows::fs::MetadataExt;
+    /// `FILE_ATTRIBUTE_HIDDEN` and `FILE_ATTRIBUTE_SYSTEM`
+    const HIDDEN: u32 = 0x2;
+    const SYSTEM: u32 = 0x4;
+
+    let mask = match attribute {
+        FileAttribute::Hidden => HIDDEN,
+        FileAttribute::System => SYSTEM,
+        FileAttribute::Nodump => return false,
+    };
+    fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_attributes() & mask != 0)
+}
+
+#[cfg(target_os = "linux")]
+fn has_platform_attribute(path: &Path, attribute: FileAttribute) -> bool {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::fs::OpenOptionsExt;
+    /// `FS_NODUMP_FL`, set by `chattr +d`
+    const NODUMP: libc::c_int = 0x40;
+
+    if attribute != FileAttribute::Nodump {
+        return false;
+    }
+    // Non-blocking so opening a FIFO doesn't wait for a writer
+    let Ok(file) = fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(path)
+    else {
+        return false;
+    };
+    let mut flags: libc::c_int = 0;
+    // SAFETY: the descriptor is open for the duration of the call, and the kernel writes an int
+    let result = unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_GETFLAGS, &mut flags) };
+    result == 0 && flags & NODUMP != 0
+}
+
+#[cfg(any(
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "dragonfly"
+))]
+fn has_platform_attribute(path: &Path, attribute: FileAttribute) -> bool {
+    use std::os::unix::ffi::OsStrExt;
+    /// The `nodump` and `hidden` flags of `chflags`
+    const UF_NODUMP: u32 = 0x1;
+    const UF_HIDDEN: u32 = 0x8000;
+
+    let mask = match attribute {
+        FileAttribute::Hidden => UF_HIDDEN,
+        FileAttribute::Nodump => UF_NODUMP,
+        FileAttribute::System => return false,
+    };
+    let Ok(path) = std::ffi::CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    let mut stat = std::mem::MaybeUninit::<libc::stat>::uninit();
+    // SAFETY: `path` is a valid C string and `stat` is only read once the call filled it in
+    if unsafe { libc::stat(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return false;
+    }
+    let flags = unsafe { stat.assume_init() }.st_flags as u32;
+    flags & mask != 0
+}
+
+#[cfg(not(any(
+    windows,
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+    target_os = "dragonfly"
+)))]
+fn has_platform_attribute(_path: &Path, _attribute: FileAttribute) -> bool {
+    false
+}
+
+pub fn get_top_level_dirs(dir: &str, skip_dirs: &[String]) -> Result<Vec<String>> {
+    let entries = fs::read_dir(long_path(Path::new(dir))).map_err(|e| {
+        BackupError::DirectoryRead(format!("Failed to read directory {}: {}", dir, e))
+    })?;
+
+    let mut dirs = Vec::new();
+    for entry in entries {
         let entry = entry.map_err(|e| {
             BackupError::DirectoryRead(format!("Failed to read directory entry: {}", e))
         })?;
 
-        if entry.file_type().is_dir()
-            && skip_dirs.contains(&entry.file_name().to_string_lossy().to_string())
-        {
-            dir_walk.skip_current_dir();
-            continue;
-        } else if entry.file_type().is_dir() {
+        if !entry.path().is_dir() {
             continue;
         }
-        files.push(entry.path().to_path_buf());
+
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !skip_dirs.contains(&name) {
+            dirs.push(name);
+        }
     }
-    Ok(files)
+    Ok(dirs)
 }
 
 pub fn get_file_size(file: &Path) -> Result<u64> {
-    let metadata = file
+    let metadata = long_path(file)
         .metadata()
         .map_err(|cause| BackupError::MetadataError {
             path: file.to_path_buf(),
@@ -43,7 +229,7 @@ pub fn get_file_size(file: &Path) -> Result<u64> {
 }
 
 pub fn get_file_last_modified(file: &Path) -> Result<Duration> {
-    let metadata = file
+    let metadata = long_path(file)
         .metadata()
         .map_err(|cause| BackupError::MetadataError {
             path: file.to_path_buf(),
@@ -65,6 +251,248 @@ pub fn get_file_last_modified(file: &Path) -> Result<Duration> {
         })
 }
 
+/// Bytes of disk space `file` takes up, which is less than its size if it is sparse
+///
+/// None where the platform doesn't report allocated blocks.
+pub fn get_file_physical_size(file: &Path) -> Result<Option<u64>> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        let metadata = long_path(file)
+            .metadata()
+            .map_err(|cause| BackupError::MetadataError {
+                path: file.to_path_buf(),
+                cause,
+            })?;
+        Ok(Some(metadata.blocks() * 512))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = file;
+        Ok(None)
+    }
+}
+
+#[cfg(any(windows, test))]
+/// Prefix used by Windows for paths that bypass the 260 character `MAX_PATH` limit
+const VERBATIM_PREFIX: &str = r"\\?\";
+#[cfg(any(windows, test))]
+/// Verbatim form of a UNC path, e.g. `\\?\UNC\nas\share` for `\\nas\share`
+const VERBATIM_UNC_PREFIX: &str = r"\\?\UNC\";
+
+/// Path to hand to filesystem calls so deeply nested files work on Windows
+///
+/// On Windows absolute paths are cleaned and given the `\\?\` (or `\\?\UNC\`) prefix, which
+/// lifts the `MAX_PATH` limit. Elsewhere the path is returned unchanged.
+pub fn long_path(path: &Path) -> Cow<'_, Path> {
+    #[cfg(windows)]
+    {
+        let absolute = match std::path::absolute(path) {
+            Ok(absolute) => lexically_normalize(&absolute),
+            Err(_) => return Cow::Borrowed(path),
+        };
+        match absolute.to_str() {
+            Some(s) => Cow::Owned(PathBuf::from(add_verbatim_prefix(s))),
+            None => Cow::Borrowed(path),
+        }
+    }
+    #[cfg(not(windows))]
+    {
+        Cow::Borrowed(path)
+    }
+}
+
+/// Undo [`long_path`], turning `\\?\C:\x` into `C:\x` and `\\?\UNC\nas\share` into `\\nas\share`
+pub fn strip_long_path_prefix(path: &Path) -> Cow<'_, Path> {
+    #[cfg(windows)]
+    {
+        match path.to_str() {
+            Some(s) if s.starts_with(VERBATIM_PREFIX) => {
+                Cow::Owned(PathBuf::from(remove_verbatim_prefix(s)))
+            }
+            _ => Cow::Borrowed(path),
+        }
+    }
+    #[cfg(not(windows))]
+    {
+        Cow::Borrowed(path)
+    }
+}
+
+/// Form of a path used for comparisons: no verbatim prefix and no `.` or `..` components
+///
+/// `canonicalize` returns verbatim paths on Windows while configured paths don't have the
+/// prefix, so both sides of a containment check need normalizing first.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    lexically_normalize(&strip_long_path_prefix(path))
+}
+
+/// Whether the platform's default filesystems treat `Photos` and `photos` as the same name
+const CASE_INSENSITIVE_FS: bool = cfg!(any(windows, target_os = "macos"));
+
+/// Part of `path` below `base`, compared component by component, or None if `path` isn't inside it
+///
+/// Components match when they are equal after Unicode NFC normalization, so a source configured
+/// as `Café` still matches files walked as `Cafe\u{301}`. On Windows and macOS the comparison
+/// also ignores case.
+pub fn path_relative_to(path: &Path, base: &Path) -> Option<PathBuf> {
+    strip_prefix_loosely(
+        &normalize_path(path),
+        &normalize_path(base),
+        CASE_INSENSITIVE_FS,
+    )
+}
+
+fn strip_prefix_loosely(path: &Path, base: &Path, ignore_case: bool) -> Option<PathBuf> {
+    if let Ok(relative) = path.strip_prefix(base) {
+        return Some(relative.to_path_buf());
+    }
+
+    let mut remaining = path.components();
+    for base_component in base.components() {
+        let component = remaining.next()?;
+        if !components_match(base_component, component, ignore_case) {
+            return None;
+        }
+    }
+    Some(remaining.as_path().to_path_buf())
+}
+
+fn components_match(a: Component, b: Component, ignore_case: bool) -> bool {
+    if a == b {
+        return true;
+    }
+    let (Some(a), Some(b)) = (a.as_os_str().to_str(), b.as_os_str().to_str()) else {
+        return false;
+    };
+    let a: String = a.nfc().collect();
+    let b: String = b.nfc().collect();
+    if ignore_case {
+        a.to_lowercase() == b.to_lowercase()
+    } else {
+        a == b
+    }
+}
+
+/// Resolve `.` and `..` components without touching the filesystem
+fn lexically_normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                // Nothing lies above a root
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => normalized.push(component),
+            },
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Characters NTFS, FAT32 and exFAT refuse in a name, besides control characters
+const WINDOWS_RESERVED_CHARS: &str = r#"<>:"/\|?*"#;
+
+/// Names Windows keeps for devices, whatever extension follows them
+const WINDOWS_RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// `name` as a file or folder on a Windows filesystem, with what it can't hold percent-encoded
+///
+/// Reserved and control characters become `%XX`, as do a trailing dot or space and the first
+/// letter of a device name such as `CON.txt`. `%` itself is always encoded, so two different
+/// names never end up the same and the original can be decoded again.
+pub fn windows_file_name(name: &str) -> Cow<'_, str> {
+    let stem = name.split('.').next().unwrap_or(name);
+    let device = WINDOWS_RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem));
+    let last = name.char_indices().next_back().map(|(idx, _)| idx);
+
+    let encode = |idx: usize, c: char| {
+        c == '%'
+            || c.is_ascii_control()
+            || WINDOWS_RESERVED_CHARS.contains(c)
+            || (device && idx == 0)
+            || (Some(idx) == last && (c == '.' || c == ' '))
+    };
+    if !name.char_indices().any(|(idx, c)| encode(idx, c)) {
+        return Cow::Borrowed(name);
+    }
+
+    let mut encoded = String::with_capacity(name.len() + 6);
+    for (idx, c) in name.char_indices() {
+        if encode(idx, c) {
+            encoded.push_str(&format!("%{:02X}", c as u32));
+        } else {
+            encoded.push(c);
+        }
+    }
+    Cow::Owned(encoded)
+}
+
+/// The name `windows_file_name` gave `name`, with each `%XX` decoded again
+///
+/// Anything that isn't a `%XX` escape is kept as it is, so names written before encoding was
+/// turned on come back unchanged unless they held a `%` themselves.
+pub fn decode_windows_file_name(name: &str) -> Cow<'_, str> {
+    if !name.contains('%') {
+        return Cow::Borrowed(name);
+    }
+
+    let mut decoded = String::with_capacity(name.len());
+    let mut rest = name;
+    while let Some(idx) = rest.find('%') {
+        decoded.push_str(&rest[..idx]);
+        let escaped = rest
+            .get(idx + 1..idx + 3)
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .filter(u8::is_ascii);
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte as char);
+                rest = &rest[idx + 3..];
+            }
+            None => {
+                decoded.push('%');
+                rest = &rest[idx + 1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    Cow::Owned(decoded)
+}
+
+#[cfg(any(windows, test))]
+fn add_verbatim_prefix(path: &str) -> String {
+    if path.starts_with(VERBATIM_PREFIX) {
+        return path.to_string();
+    }
+
+    let path = path.replace('/', r"\");
+    match path.strip_prefix(r"\\") {
+        Some(unc) => format!("{}{}", VERBATIM_UNC_PREFIX, unc),
+        None => format!("{}{}", VERBATIM_PREFIX, path),
+    }
+}
+
+#[cfg(any(windows, test))]
+fn remove_verbatim_prefix(path: &str) -> String {
+    if let Some(unc) = path.strip_prefix(VERBATIM_UNC_PREFIX) {
+        format!(r"\\{}", unc)
+    } else if let Some(local) = path.strip_prefix(VERBATIM_PREFIX) {
+        local.to_string()
+    } else {
+        path.to_string()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -72,6 +500,29 @@ mod tests {
     use std::io::Write;
     use tempfile::{NamedTempFile, TempDir};
 
+    fn get_files_in_path(
+        dir: &str,
+        skip_dirs: &[String],
+        max_depth: &Option<usize>,
+        ignore_files: &[String],
+    ) -> Result<Vec<PathBuf>> {
+        let files = Mutex::new(Vec::new());
+        walk_files_in_path(
+            dir,
+            skip_dirs,
+            max_depth,
+            ignore_files,
+            &[],
+            2,
+            None,
+            &|file| {
+                files.lock().unwrap().push(file);
+                true
+            },
+        )?;
+        Ok(files.into_inner().unwrap())
+    }
+
     #[test]
     fn test_get_files_in_flat_directory() {
         let temp_dir = TempDir::new().unwrap();
@@ -82,11 +533,65 @@ mod tests {
         fs::File::create(temp_dir.path().join("file2.txt")).unwrap();
         fs::File::create(temp_dir.path().join("file3.log")).unwrap();
 
-        let files = get_files_in_path(&dir_path, &vec![], &None).unwrap();
+        let files = get_files_in_path(&dir_path, &[], &None, &[]).unwrap();
 
         assert_eq!(files.len(), 3);
     }
 
+    #[test]
+    fn test_walk_skips_files_and_directories_with_excluded_attributes() {
+        // TempDir names start with a dot, so the source itself is hidden too
+        let temp_dir = TempDir::new().unwrap();
+        fs::write(temp_dir.path().join("notes.txt"), "kept").unwrap();
+        fs::write(temp_dir.path().join(".DS_Store"), "cruft").unwrap();
+        fs::create_dir(temp_dir.path().join(".cache")).unwrap();
+        fs::write(temp_dir.path().join(".cache").join("blob"), "cruft").unwrap();
+        let scratch = temp_dir.path().join("scratch.iso");
+        fs::write(&scratch, "large").unwrap();
+        let nodump = std::process::Command::new("chattr")
+            .arg("+d")
+            .arg(&scratch)
+            .output()
+            .is_ok_and(|output| output.status.success());
+
+        let walk = |attributes: &[FileAttribute]| {
+            let files = Mutex::new(Vec::new());
+            walk_files_in_path(
+                temp_dir.path().to_str().unwrap(),
+                &[],
+                &None,
+                &[],
+                attributes,
+                2,
+                None,
+                &|file| {
+                    files
+                        .lock()
+                        .unwrap()
+                        .push(file.file_name().unwrap().to_string_lossy().to_string());
+                    true
+                },
+            )
+            .unwrap();
+            let mut files = files.into_inner().unwrap();
+            files.sort();
+            files
+        };
+
+        assert_eq!(walk(&[]).len(), 4);
+        assert_eq!(
+            walk(&[FileAttribute::Hidden]),
+            vec!["notes.txt", "scratch.iso"]
+        );
+        // Only where the filesystem holds the flag, which tmpfs doesn't
+        if cfg!(target_os = "linux") && nodump {
+            assert_eq!(
+                walk(&[FileAttribute::Hidden, FileAttribute::Nodump]),
+                vec!["notes.txt"]
+            );
+        }
+    }
+
     #[test]
     fn test_get_files_respects_max_depth() {
         let temp_dir = TempDir::new().unwrap();
@@ -104,15 +609,15 @@ mod tests {
         fs::File::create(sub_dir2.join("level2.txt")).unwrap();
 
         // max_depth = 1 should only find root.txt
-        let files_depth1 = get_files_in_path(&dir_path, &vec![], &Some(1)).unwrap();
+        let files_depth1 = get_files_in_path(&dir_path, &[], &Some(1), &[]).unwrap();
         assert_eq!(files_depth1.len(), 1);
 
         // max_depth = 2 should find root.txt and level1.txt
-        let files_depth2 = get_files_in_path(&dir_path, &vec![], &Some(2)).unwrap();
+        let files_depth2 = get_files_in_path(&dir_path, &[], &Some(2), &[]).unwrap();
         assert_eq!(files_depth2.len(), 2);
 
         // max_depth = 3 should find all three files
-        let files_depth3 = get_files_in_path(&dir_path, &vec![], &Some(3)).unwrap();
+        let files_depth3 = get_files_in_path(&dir_path, &[], &Some(3), &[]).unwrap();
         assert_eq!(files_depth3.len(), 3);
     }
 
@@ -132,7 +637,7 @@ mod tests {
         fs::create_dir(&keep_dir).unwrap();
         fs::File::create(keep_dir.join("kept.txt")).unwrap();
 
-        let files = get_files_in_path(&dir_path, &vec!["skip_me".to_string()], &None).unwrap();
+        let files = get_files_in_path(&dir_path, &["skip_me".to_string()], &None, &[]).unwrap();
 
         // Should find file.txt and keep_me/kept.txt, but not skip_me/skipped.txt
         assert_eq!(files.len(), 2);
@@ -143,13 +648,141 @@ mod tests {
             .any(|f| f.file_name().unwrap() == "skipped.txt"));
     }
 
+    #[test]
+    fn test_get_files_honors_ignore_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir_path = temp_dir.path().to_str().unwrap().to_string();
+
+        let repo = temp_dir.path().join("project");
+        fs::create_dir_all(repo.join("target/debug")).unwrap();
+        fs::write(repo.join(".gitignore"), "target/\n*.log\n").unwrap();
+        fs::write(repo.join("main.rs"), "fn main() {}").unwrap();
+        fs::write(repo.join("build.log"), "noise").unwrap();
+        fs::write(repo.join("target/debug/app"), "binary").unwrap();
+        fs::write(temp_dir.path().join(".backupignore"), "*.tmp\n").unwrap();
+        fs::write(temp_dir.path().join("scratch.tmp"), "").unwrap();
+        fs::write(temp_dir.path().join("root.log"), "kept").unwrap();
+
+        let names = |files: Vec<PathBuf>| {
+            let mut names: Vec<String> = files
+                .iter()
+                .map(|f| f.file_name().unwrap().to_string_lossy().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+
+        let ignore_files = vec![".gitignore".to_string(), ".backupignore".to_string()];
+        let files = get_files_in_path(&dir_path, &[], &None, &ignore_files).unwrap();
+        assert_eq!(
+            names(files),
+            vec![".backupignore", ".gitignore", "main.rs", "root.log"]
+        );
+
+        // Without ignore files configured everything is found
+        let files = get_files_in_path(&dir_path, &[], &None, &[]).unwrap();
+        assert_eq!(files.len(), 7);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_walk_errors_fail_the_walk_unless_skipped() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().to_str().unwrap();
+        fs::write(temp_dir.path().join("a.txt"), "kept").unwrap();
+        fs::create_dir(temp_dir.path().join("sub")).unwrap();
+        fs::write(temp_dir.path().join("sub").join("b.txt"), "kept").unwrap();
+        // Links are followed, so this one leads back into the walk
+        std::os::unix::fs::symlink(temp_dir.path(), temp_dir.path().join("sub").join("loop"))
+            .unwrap();
+
+        assert!(matches!(
+            get_files_in_path(dir, &[], &None, &[]),
+            Err(BackupError::DirectoryRead(_))
+        ));
+
+        let files = Mutex::new(Vec::new());
+        let skipped = Mutex::new(Vec::new());
+        walk_files_in_path(
+            dir,
+            &[],
+            &None,
+            &[],
+            &[],
+            2,
+            Some(&|path, _| skipped.lock().unwrap().push(path)),
+            &|file| {
+                files.lock().unwrap().push(file);
+                true
+            },
+        )
+        .unwrap();
+        let mut files = files.into_inner().unwrap();
+        files.sort();
+        assert_eq!(
+            files,
+            [
+                temp_dir.path().join("a.txt"),
+                temp_dir.path().join("sub").join("b.txt")
+            ]
+        );
+        assert_eq!(
+            skipped.into_inner().unwrap(),
+            [Some(temp_dir.path().join("sub").join("loop"))]
+        );
+    }
+
+    #[test]
+    fn test_walk_stops_when_visitor_returns_false() {
+        let temp_dir = TempDir::new().unwrap();
+        for i in 0..20 {
+            let dir = temp_dir.path().join(format!("dir{}", i));
+            fs::create_dir(&dir).unwrap();
+            fs::File::create(dir.join("file.txt")).unwrap();
+        }
+
+        let visited = Mutex::new(0);
+        walk_files_in_path(
+            temp_dir.path().to_str().unwrap(),
+            &[],
+            &None,
+            &[],
+            &[],
+            1,
+            None,
+            &|_| {
+                *visited.lock().unwrap() += 1;
+                false
+            },
+        )
+        .unwrap();
+
+        assert_eq!(visited.into_inner().unwrap(), 1);
+    }
+
     #[test]
     fn test_get_files_error_on_nonexistent_directory() {
-        let result = get_files_in_path(&"/this/does/not/exist".to_string(), &vec![], &None);
+        let result = get_files_in_path("/this/does/not/exist", &[], &None, &[]);
 
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_get_top_level_dirs_ignores_files_and_skip_dirs() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir_path = temp_dir.path().to_str().unwrap().to_string();
+
+        fs::File::create(temp_dir.path().join("loose.txt")).unwrap();
+        fs::create_dir(temp_dir.path().join("camera-dump")).unwrap();
+        fs::create_dir(temp_dir.path().join("skip_me")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("projects").join("nested")).unwrap();
+
+        let mut dirs = get_top_level_dirs(&dir_path, &["skip_me".to_string()]).unwrap();
+        dirs.sort();
+
+        assert_eq!(dirs, vec!["camera-dump", "projects"]);
+    }
+
     #[test]
     fn test_get_file_size() {
         let mut temp_file = NamedTempFile::new().unwrap();
@@ -157,7 +790,7 @@ mod tests {
         temp_file.write_all(content).unwrap();
         temp_file.flush().unwrap();
 
-        let size = get_file_size(&temp_file.path().to_path_buf()).unwrap();
+        let size = get_file_size(temp_file.path()).unwrap();
 
         assert_eq!(size, content.len() as u64);
     }
@@ -166,12 +799,109 @@ mod tests {
     fn test_get_file_last_modified() {
         let temp_file = NamedTempFile::new().unwrap();
 
-        let last_modified = get_file_last_modified(&temp_file.path().to_path_buf()).unwrap();
+        let last_modified = get_file_last_modified(temp_file.path()).unwrap();
 
         // Should return a valid duration
         assert!(last_modified.as_secs() > 0);
     }
 
+    #[test]
+    fn test_verbatim_prefix_round_trip() {
+        assert_eq!(
+            add_verbatim_prefix(r"C:\Users\me\Documents"),
+            r"\\?\C:\Users\me\Documents"
+        );
+        assert_eq!(
+            add_verbatim_prefix(r"\\nas\share\photos"),
+            r"\\?\UNC\nas\share\photos"
+        );
+        assert_eq!(
+            add_verbatim_prefix("C:/Users/me/Documents"),
+            r"\\?\C:\Users\me\Documents"
+        );
+        assert_eq!(add_verbatim_prefix(r"\\?\D:\backup"), r"\\?\D:\backup");
+
+        assert_eq!(
+            remove_verbatim_prefix(r"\\?\UNC\nas\share\photos"),
+            r"\\nas\share\photos"
+        );
+        assert_eq!(remove_verbatim_prefix(r"\\?\C:\Users"), r"C:\Users");
+        assert_eq!(remove_verbatim_prefix(r"C:\Users"), r"C:\Users");
+    }
+
+    #[test]
+    fn test_normalize_path_resolves_dot_components() {
+        let path = Path::new("/mnt/backup/./photos/../docs/report.pdf");
+        assert_eq!(
+            normalize_path(path),
+            PathBuf::from("/mnt/backup/docs/report.pdf")
+        );
+    }
+
+    #[test]
+    fn test_windows_file_name_encodes_what_windows_refuses() {
+        assert!(matches!(
+            windows_file_name("report v2.pdf"),
+            Cow::Borrowed("report v2.pdf")
+        ));
+        assert_eq!(windows_file_name("12:30 what?.txt"), "12%3A30 what%3F.txt");
+        assert_eq!(
+            windows_file_name("a*b|c\\d\"e<f>"),
+            "a%2Ab%7Cc%5Cd%22e%3Cf%3E"
+        );
+        assert_eq!(windows_file_name("tab\there"), "tab%09here");
+        assert_eq!(windows_file_name("notes."), "notes%2E");
+        assert_eq!(windows_file_name("draft "), "draft%20");
+        assert_eq!(windows_file_name("con.txt"), "%63on.txt");
+        assert_eq!(windows_file_name("LPT1"), "%4CPT1");
+        assert_eq!(windows_file_name("console.txt"), "console.txt");
+        // Already encoded-looking names stay apart from the names they look like
+        assert_eq!(windows_file_name("a%3Ab"), "a%253Ab");
+        assert_eq!(windows_file_name("日本:語"), "日本%3A語");
+        for name in [
+            "12:30 what?.txt",
+            "con.txt",
+            "notes.",
+            "a%3Ab",
+            "日本:語",
+            "100%",
+        ] {
+            assert_eq!(decode_windows_file_name(&windows_file_name(name)), name);
+        }
+    }
+
+    #[test]
+    fn test_path_relative_to_compares_components() {
+        assert_eq!(
+            path_relative_to(Path::new("/data/photos/2024"), Path::new("/data")),
+            Some(PathBuf::from("photos/2024"))
+        );
+        // A shared string prefix isn't a shared directory
+        assert_eq!(
+            path_relative_to(Path::new("/data-old/photos"), Path::new("/data")),
+            None
+        );
+        assert_eq!(
+            path_relative_to(
+                Path::new("/data/Cafe\u{301}/x"),
+                Path::new("/data/Caf\u{e9}")
+            ),
+            Some(PathBuf::from("x"))
+        );
+    }
+
+    #[test]
+    fn test_strip_prefix_loosely_ignores_case_when_asked() {
+        let path = Path::new("/Users/Me/Pictures/2024");
+        let base = Path::new("/users/me");
+
+        assert_eq!(strip_prefix_loosely(path, base, false), None);
+        assert_eq!(
+            strip_prefix_loosely(path, base, true),
+            Some(PathBuf::from("Pictures/2024"))
+        );
+    }
+
     #[test]
     fn test_get_file_size_error_on_missing_file() {
         let nonexistent = PathBuf::from("/this/file/does/not/exist.txt");
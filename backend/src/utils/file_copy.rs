@@ -0,0 +1,348 @@
+use log::debug;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// Buffer size for copies that go through user space, unless `copy_buffer_kib` is set
+pub const DEFAULT_COPY_BUFFER_KIB: usize = 1024;
+
+/// Copy `from` to `to`, replacing it, and return the number of bytes copied
+///
+/// On Linux the copy is first attempted as a reflink (`FICLONE`, btrfs and XFS), which shares
+/// the data blocks instantly. A sparse source is then copied range by range, skipping its holes
+/// so the copy stays sparse; anything else goes through `copy_file_range`, which stays in the
+/// kernel and lets NFS and SMB copy server-side. macOS and Windows use the OS copy call, which
+/// already clones on APFS and copies server-side on SMB. Everything else, and any Linux copy the
+/// kernel can't speed up, goes through a `buffer_kib` buffer. Permissions are copied like
+/// `fs::copy` does.
+pub fn copy_file(from: &Path, to: &Path, buffer_kib: usize) -> io::Result<u64> {
+    platform::copy_file(from, to, buffer_kib)
+}
+
+/// Copy `from` to `to` through a `buffer_kib` buffer, showing each chunk to `inspect` as it is
+/// written, and return the number of bytes copied
+///
+/// The kernel fast paths are skipped so the data can be hashed in the same pass. On Linux the
+/// holes of a sparse source are still skipped, and shown to `inspect` as the zeros they read as.
+pub fn copy_file_through(
+    from: &Path,
+    to: &Path,
+    buffer_kib: usize,
+    inspect: &mut dyn FnMut(&[u8]),
+) -> io::Result<u64> {
+    let (mut source, mut target, len) = open_pair(from, to)?;
+    #[cfg(target_os = "linux")]
+    if let Some(copied) = platform::copy_sparse(&mut source, &mut target, len, buffer_kib, inspect)?
+    {
+        return Ok(copied);
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = len;
+    buffered_copy(&mut source, &mut target, buffer_kib, inspect)
+}
+
+#[cfg(any(target_os = "macos", windows))]
+mod platform {
+    use std::fs;
+    use std::io;
+    use std::path::Path;
+
+    pub fn copy_file(from: &Path, to: &Path, _buffer_kib: usize) -> io::Result<u64> {
+        fs::copy(from, to)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{buffered_copy, open_pair};
+    use log::debug;
+    use std::fs::File;
+    use std::io::{self, Read, Seek, SeekFrom};
+    use std::os::fd::AsRawFd;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::Path;
+
+    pub fn copy_file(from: &Path, to: &Path, buffer_kib: usize) -> io::Result<u64> {
+        let (mut source, mut target, len) = open_pair(from, to)?;
+
+        if reflink(&source, &target).is_ok() {
+            debug!("Reflinked {:?} → {:?}", from, to);
+            return Ok(len);
+        }
+
+        if let Some(copied) = copy_sparse(&mut source, &mut target, len, buffer_kib, &mut |_| {})? {
+            debug!("Copied the data of sparse {:?} → {:?}", from, to);
+            return Ok(copied);
+        }
+
+        let copied = copy_in_kernel(&source, &target, len)?;
+        if copied == len {
+            debug!("Copied {:?} → {:?} with copy_file_range", from, to);
+            return Ok(len);
+        }
+
+        // Finish whatever the kernel couldn't do from where it stopped
+        source.seek(SeekFrom::Start(copied))?;
+        target.seek(SeekFrom::Start(copied))?;
+        Ok(copied + buffered_copy(&mut source, &mut target, buffer_kib, &mut |_| {})?)
+    }
+
+    /// Share the source's blocks with the target; fails unless both are on the same
+    /// copy-on-write filesystem
+    fn reflink(source: &File, target: &File) -> io::Result<()> {
+        // SAFETY: both descriptors are open for the duration of the call
+        let result = unsafe { libc::ioctl(target.as_raw_fd(), libc::FICLONE, source.as_raw_fd()) };
+        if result == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    /// Copy only the data of a sparse `source`, leaving its holes as holes in `target`
+    ///
+    /// Returns None, having written nothing, if the source has no holes or its filesystem can't
+    /// tell where they are.
+    pub fn copy_sparse(
+        source: &mut File,
+        target: &mut File,
+        len: u64,
+        buffer_kib: usize,
+        inspect: &mut dyn FnMut(&[u8]),
+    ) -> io::Result<Option<u64>> {
+        if source.metadata()?.blocks() * 512 >= len {
+            return Ok(None);
+        }
+        let zeros = vec![0; buffer_kib.max(1) * 1024];
+        let mut offset = 0u64;
+        let mut copy_len = len;
+
+        while offset < len {
+            let data = match seek(source, offset, libc::SEEK_DATA) {
+                Ok(data) => data.min(len),
+                // Only holes are left
+                Err(e) if e.raw_os_error() == Some(libc::ENXIO) => len,
+                Err(e) if offset == 0 && e.raw_os_error() == Some(libc::EINVAL) => return Ok(None),
+                Err(e) => return Err(e),
+            };
+            // Holes read as zeros, which the hash has to see too
+            let mut hole = data - offset;
+            while hole > 0 {
+                let chunk = hole.min(zeros.len() as u64) as usize;
+                inspect(&zeros[..chunk]);
+                hole -= chunk as u64;
+            }
+            if data == len {
+                break;
+            }
+
+            let end = seek(source, data, libc::SEEK_HOLE)?.min(len);
+            source.seek(SeekFrom::Start(data))?;
+            target.seek(SeekFrom::Start(data))?;
+            let copied = buffered_copy(
+                &mut Read::by_ref(source).take(end - data),
+                target,
+                buffer_kib,
+                inspect,
+            )?;
+            offset = data + copied;
+            // The source shrank while copying
+            if copied < end - data {
+                copy_len = offset;
+                break;
+            }
+        }
+
+        // Also makes the trailing hole, which has no data to write
+        target.set_len(copy_len)?;
+        Ok(Some(copy_len))
+    }
+
+    fn seek(file: &File, offset: u64, whence: libc::c_int) -> io::Result<u64> {
+        // SAFETY: the descriptor is open for the duration of the call
+        let result = unsafe { libc::lseek(file.as_raw_fd(), offset as libc::off_t, whence) };
+        if result < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(result as u64)
+        }
+    }
+
+    /// Copy with `copy_file_range` until done or the kernel declines, returning the bytes copied
+    fn copy_in_kernel(source: &File, target: &File, len: u64) -> io::Result<u64> {
+        // Large requests are split by the kernel anyway; this keeps each call bounded
+        const CHUNK: u64 = 1 << 30;
+        let mut copied = 0u64;
+
+        while copied < len {
+            let chunk = (len - copied).min(CHUNK) as usize;
+            // SAFETY: null offsets make the kernel use and advance each file's own position
+            let result = unsafe {
+                libc::copy_file_range(
+                    source.as_raw_fd(),
+                    std::ptr::null_mut(),
+                    target.as_raw_fd(),
+                    std::ptr::null_mut(),
+                    chunk,
+                    0,
+                )
+            };
+            match result {
+                // The source shrank while copying; the buffered copy reads to the real end
+                0 => break,
+                n if n > 0 => copied += n as u64,
+                _ => {
+                    let error = io::Error::last_os_error();
+                    match error.raw_os_error() {
+                        // Not supported for these files or filesystems
+                        Some(
+                            libc::EXDEV
+                            | libc::ENOSYS
+                            | libc::EOPNOTSUPP
+                            | libc::EINVAL
+                            | libc::EPERM
+                            | libc::EBADF,
+                        ) => break,
+                        Some(libc::EINTR) => continue,
+                        _ => return Err(error),
+                    }
+                }
+            }
+        }
+        Ok(copied)
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+mod platform {
+    use super::{buffered_copy, open_pair};
+    use std::io;
+    use std::path::Path;
+
+    pub fn copy_file(from: &Path, to: &Path, buffer_kib: usize) -> io::Result<u64> {
+        let (mut source, mut target, _) = open_pair(from, to)?;
+        buffered_copy(&mut source, &mut target, buffer_kib, &mut |_| {})
+    }
+}
+
+/// Open the source, create or truncate the target with the source's permissions, and return
+/// both with the source's length
+fn open_pair(from: &Path, to: &Path) -> io::Result<(File, File, u64)> {
+    let source = File::open(from)?;
+    let metadata = source.metadata()?;
+    if !metadata.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the source path is not a regular file",
+        ));
+    }
+
+    let target = File::create(to)?;
+    target.set_permissions(metadata.permissions())?;
+    Ok((source, target, metadata.len()))
+}
+
+/// Copy the rest of `source` into `target` through a `buffer_kib` buffer
+fn buffered_copy<R: Read, W: Write>(
+    source: &mut R,
+    target: &mut W,
+    buffer_kib: usize,
+    inspect: &mut dyn FnMut(&[u8]),
+) -> io::Result<u64> {
+    let mut buffer = vec![0; buffer_kib.max(1) * 1024];
+    let mut copied = 0u64;
+
+    loop {
+        let count = match source.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(count) => count,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        inspect(&buffer[..count]);
+        target.write_all(&buffer[..count])?;
+        copied += count as u64;
+    }
+    target.flush()?;
+    debug!(
+        "Copied {} bytes through a {} KiB buffer",
+        copied, buffer_kib
+    );
+    Ok(copied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_copy_file_replaces_target() {
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("source.bin");
+        let target = dir.path().join("target.bin");
+        let data: Vec<u8> = (0..300_000u32).map(|i| (i % 253) as u8).collect();
+        fs::write(&source, &data).unwrap();
+        fs::write(&target, "an older, longer backup that has to be truncated").unwrap();
+
+        assert_eq!(copy_file(&source, &target, 4).unwrap(), data.len() as u64);
+        assert_eq!(fs::read(&target).unwrap(), data);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_sparse_copies_keep_their_holes() {
+        use std::io::{Seek, SeekFrom};
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = TempDir::new().unwrap();
+        let source = dir.path().join("disk.img");
+        let mut file = File::create(&source).unwrap();
+        file.seek(SeekFrom::Start(4 << 20)).unwrap();
+        file.write_all(b"boot sector").unwrap();
+        file.set_len(16 << 20).unwrap();
+        drop(file);
+        let expected = fs::read(&source).unwrap();
+        if fs::metadata(&source).unwrap().blocks() * 512 >= expected.len() as u64 {
+            // The filesystem holding the temporary directory has no holes
+            return;
+        }
+
+        let target = dir.path().join("copy.img");
+        assert_eq!(copy_file(&source, &target, 64).unwrap(), 16 << 20);
+        assert_eq!(fs::read(&target).unwrap(), expected);
+        assert!(fs::metadata(&target).unwrap().blocks() * 512 < 1 << 20);
+
+        let mut seen = Vec::new();
+        let copied = copy_file_through(&source, &target, 64, &mut |chunk| {
+            seen.extend_from_slice(chunk)
+        })
+        .unwrap();
+        assert_eq!(copied, 16 << 20);
+        assert_eq!(seen, expected);
+        assert_eq!(fs::read(&target).unwrap(), expected);
+        assert!(fs::metadata(&target).unwrap().blocks() * 512 < 1 << 20);
+    }
+
+    #[test]
+    fn test_buffered_copy_uses_small_buffers() {
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 7) as u8).collect();
+        let mut copy = Vec::new();
+
+        assert_eq!(
+            buffered_copy(&mut data.as_slice(), &mut copy, 1, &mut |_| {}).unwrap(),
+            10_000
+        );
+        assert_eq!(copy, data);
+
+        let mut seen = 0;
+        copy.clear();
+        buffered_copy(&mut data.as_slice(), &mut copy, 1, &mut |chunk| {
+            assert!(chunk.len() <= 1024);
+            seen += chunk.len();
+        })
+        .unwrap();
+        assert_eq!(seen, data.len());
+    }
+}
@@ -0,0 +1,151 @@
+use crate::models::config::BackupSource;
+use crate::utils::directory::{get_file_last_modified, get_file_size};
+use log::{debug, warn};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Size, age and extension limits a source's files must satisfy to be backed up
+#[derive(Debug)]
+pub struct FileFilter {
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    /// Oldest accepted modification time, as time since the Unix epoch
+    modified_after: Option<Duration>,
+    /// Lowercase extensions without the leading dot
+    extensions: Vec<String>,
+}
+
+impl FileFilter {
+    /// Build the filter for a source, or None if it doesn't configure any limits
+    pub fn for_source(source: &BackupSource, now: SystemTime) -> Option<Self> {
+        let modified_after = source.modified_within_days.map(|days| {
+            now.duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .saturating_sub(Duration::from_secs(days.saturating_mul(SECONDS_PER_DAY)))
+        });
+        let filter = FileFilter {
+            min_size: source.min_size,
+            max_size: source.max_size,
+            modified_after,
+            extensions: source
+                .extensions
+                .iter()
+                .map(|ext| normalize_extension(ext))
+                .collect(),
+        };
+
+        let configured = filter.min_size.is_some()
+            || filter.max_size.is_some()
+            || filter.modified_after.is_some()
+            || !filter.extensions.is_empty();
+        configured.then_some(filter)
+    }
+
+    /// Whether a file passes every configured limit
+    ///
+    /// Files whose metadata can't be read are kept so the later phases report the error.
+    pub fn accepts(&self, file: &Path) -> bool {
+        if !self.extensions.is_empty() {
+            let extension = file
+                .extension()
+                .map(|ext| ext.to_string_lossy().to_lowercase());
+            if !extension.is_some_and(|ext| self.extensions.contains(&ext)) {
+                debug!("Excluding {:?} by extension", file);
+                return false;
+            }
+        }
+
+        if self.min_size.is_some() || self.max_size.is_some() {
+            match get_file_size(file) {
+                Ok(size) => {
+                    if self.min_size.is_some_and(|min| size < min)
+                        || self.max_size.is_some_and(|max| size > max)
+                    {
+                        debug!("Excluding {:?} by size ({} bytes)", file, size);
+                        return false;
+                    }
+                }
+                Err(e) => warn!("Failed to read size of {:?}: {}", file, e),
+            }
+        }
+
+        if let Some(modified_after) = self.modified_after {
+            match get_file_last_modified(file) {
+                Ok(modified) if modified < modified_after => {
+                    debug!("Excluding {:?} by modification time", file);
+                    return false;
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Failed to read modification time of {:?}: {}", file, e),
+            }
+        }
+
+        true
+    }
+}
+
+/// `.PDF` and `pdf` both become `pdf`
+pub fn normalize_extension(extension: &str) -> String {
+    extension.trim().trim_start_matches('.').to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_filters_by_size_and_extension() {
+        let dir = TempDir::new().unwrap();
+        let small = dir.path().join("notes.TXT");
+        fs::write(&small, "hi").unwrap();
+        let large = dir.path().join("disk.iso");
+        fs::write(&large, vec![0u8; 4096]).unwrap();
+        let report = dir.path().join("report.pdf");
+        fs::write(&report, vec![0u8; 512]).unwrap();
+        let files = [small, large, report];
+        let accepted = |source: &BackupSource| {
+            let filter = FileFilter::for_source(source, SystemTime::now()).unwrap();
+            files.iter().map(|f| filter.accepts(f)).collect::<Vec<_>>()
+        };
+
+        let source = BackupSource {
+            max_size: Some(1024),
+            ..Default::default()
+        };
+        assert_eq!(accepted(&source), vec![true, false, true]);
+
+        let source = BackupSource {
+            min_size: Some(100),
+            extensions: vec![".txt".to_string(), "PDF".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(accepted(&source), vec![false, false, true]);
+    }
+
+    #[test]
+    fn test_filters_by_modification_time() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("recent.txt");
+        fs::write(&file, "fresh").unwrap();
+        let source = BackupSource {
+            modified_within_days: Some(7),
+            ..Default::default()
+        };
+
+        let filter = FileFilter::for_source(&source, SystemTime::now()).unwrap();
+        assert!(filter.accepts(&file));
+
+        let a_month_later = SystemTime::now() + Duration::from_secs(30 * SECONDS_PER_DAY);
+        let filter = FileFilter::for_source(&source, a_month_later).unwrap();
+        assert!(!filter.accepts(&file));
+    }
+
+    #[test]
+    fn test_no_filter_without_limits() {
+        assert!(FileFilter::for_source(&BackupSource::default(), SystemTime::now()).is_none());
+    }
+}
@@ -1,3 +1,11 @@
+pub mod cancel;
+pub mod chaos;
+pub mod content_type;
 pub mod db_logger;
 pub mod directory;
+pub mod file_copy;
+pub mod file_filter;
 pub mod progress;
+pub mod resource_group;
+pub mod run_lock;
+pub mod semaphore;
@@ -0,0 +1,132 @@
+use crate::models::config::LowPriorityGroup;
+use crate::models::error::Result;
+
+/// Move the current process into a low-priority resource group
+///
+/// All worker threads belong to the process, so hashing and copying run under the
+/// configured weights. On Linux this is a cgroup v2 directory; on Windows a Job Object.
+pub fn enter_low_priority_group(group: &LowPriorityGroup) -> Result<()> {
+    platform::enter(group)
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use crate::models::config::LowPriorityGroup;
+    use crate::models::error::Result;
+    use log::{info, warn};
+    use std::fs;
+    use std::path::Path;
+
+    pub fn enter(group: &LowPriorityGroup) -> Result<()> {
+        let cgroup = Path::new(&group.cgroup_path);
+
+        // Delegate the cpu and io controllers to children of the parent cgroup
+        if let Some(parent) = cgroup.parent() {
+            let subtree_control = parent.join("cgroup.subtree_control");
+            if let Err(e) = fs::write(&subtree_control, "+cpu +io") {
+                warn!(
+                    "Could not enable cpu/io controllers in {:?}: {}",
+                    subtree_control, e
+                );
+            }
+        }
+
+        fs::create_dir_all(cgroup)?;
+
+        if let Some(weight) = group.cpu_weight {
+            fs::write(cgroup.join("cpu.weight"), weight.to_string())?;
+        }
+        if let Some(weight) = group.io_weight {
+            fs::write(cgroup.join("io.weight"), format!("default {}", weight))?;
+        }
+        if group.cpu_rate_percent.is_some() {
+            warn!("cpu_rate_percent is only applied on Windows; use cpu_weight on Linux");
+        }
+
+        fs::write(cgroup.join("cgroup.procs"), std::process::id().to_string())?;
+
+        info!("Running inside cgroup {}", group.cgroup_path);
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use crate::models::config::LowPriorityGroup;
+    use crate::models::error::Result;
+    use log::{info, warn};
+    use std::io;
+    use std::mem::{size_of, zeroed};
+    use std::ptr::null;
+    use windows_sys::Win32::System::JobObjects::{
+        AssignProcessToJobObject, CreateJobObjectW, JobObjectBasicLimitInformation,
+        JobObjectCpuRateControlInformation, SetInformationJobObject,
+        JOBOBJECT_BASIC_LIMIT_INFORMATION, JOBOBJECT_CPU_RATE_CONTROL_INFORMATION,
+        JOB_OBJECT_CPU_RATE_CONTROL_ENABLE, JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP,
+        JOB_OBJECT_LIMIT_PRIORITY_CLASS,
+    };
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, BELOW_NORMAL_PRIORITY_CLASS};
+
+    pub fn enter(group: &LowPriorityGroup) -> Result<()> {
+        if group.cpu_weight.is_some() || group.io_weight.is_some() {
+            warn!("cpu_weight and io_weight are only applied on Linux; use cpu_rate_percent on Windows");
+        }
+
+        // The job handle is intentionally never closed so the limits last for the process lifetime
+        unsafe {
+            let job = CreateJobObjectW(null(), null());
+            if job.is_null() {
+                return Err(io::Error::last_os_error().into());
+            }
+
+            let mut basic: JOBOBJECT_BASIC_LIMIT_INFORMATION = zeroed();
+            basic.LimitFlags = JOB_OBJECT_LIMIT_PRIORITY_CLASS;
+            basic.PriorityClass = BELOW_NORMAL_PRIORITY_CLASS;
+            if SetInformationJobObject(
+                job,
+                JobObjectBasicLimitInformation,
+                &basic as *const _ as *const _,
+                size_of::<JOBOBJECT_BASIC_LIMIT_INFORMATION>() as u32,
+            ) == 0
+            {
+                return Err(io::Error::last_os_error().into());
+            }
+
+            if let Some(percent) = group.cpu_rate_percent {
+                let mut rate: JOBOBJECT_CPU_RATE_CONTROL_INFORMATION = zeroed();
+                rate.ControlFlags =
+                    JOB_OBJECT_CPU_RATE_CONTROL_ENABLE | JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP;
+                // CpuRate is expressed in hundredths of a percent
+                rate.Anonymous.CpuRate = percent * 100;
+                if SetInformationJobObject(
+                    job,
+                    JobObjectCpuRateControlInformation,
+                    &rate as *const _ as *const _,
+                    size_of::<JOBOBJECT_CPU_RATE_CONTROL_INFORMATION>() as u32,
+                ) == 0
+                {
+                    return Err(io::Error::last_os_error().into());
+                }
+            }
+
+            if AssignProcessToJobObject(job, GetCurrentProcess()) == 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+        }
+
+        info!("Running inside a below-normal priority Job Object");
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+mod platform {
+    use crate::models::config::LowPriorityGroup;
+    use crate::models::error::Result;
+    use log::warn;
+
+    pub fn enter(_group: &LowPriorityGroup) -> Result<()> {
+        warn!("low_priority_group is not supported on this platform; running with normal priority");
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+use crate::models::error::{BackupError, Result};
+use log::info;
+use std::fs::{self, File, TryLockError};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Keeps other runs against the same database from starting until dropped
+///
+/// The lock is an OS file lock on a file beside the database, so it goes away with the process
+/// even if it crashes.
+pub struct RunLock {
+    _file: Option<File>,
+}
+
+impl RunLock {
+    /// Lock the database for a run, waiting for a run already holding it if `wait`
+    pub fn acquire(database_file: &str, wait: bool) -> Result<RunLock> {
+        // Nothing else can open an in-memory database
+        if database_file == ":memory:" {
+            return Ok(RunLock { _file: None });
+        }
+        let path = lock_path(database_file);
+        let lock_error = |cause| BackupError::RunLock {
+            path: path.clone(),
+            cause,
+        };
+        let mut file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(lock_error)?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let holder = describe_holder(&path);
+                if !wait {
+                    return Err(BackupError::AlreadyRunning {
+                        lock_file: path,
+                        holder,
+                    });
+                }
+                info!(
+                    "Waiting for the backup already running ({}) to finish",
+                    holder
+                );
+                file.lock().map_err(lock_error)?;
+            }
+            Err(TryLockError::Error(cause)) => return Err(lock_error(cause)),
+        }
+
+        // Only read while locked, to tell whoever is kept waiting who holds the lock
+        file.set_len(0)
+            .and_then(|_| {
+                write!(
+                    file,
+                    "process {}, started {}",
+                    std::process::id(),
+                    chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
+                )
+            })
+            .map_err(lock_error)?;
+        Ok(RunLock { _file: Some(file) })
+    }
+}
+
+/// The lock file of a database
+fn lock_path(database_file: &str) -> PathBuf {
+    PathBuf::from(format!("{}.lock", database_file))
+}
+
+/// Who the lock file says holds it; Windows doesn't let the locked file be read
+fn describe_holder(path: &Path) -> String {
+    match fs::read_to_string(path) {
+        Ok(holder) if !holder.trim().is_empty() => holder.trim().to_string(),
+        _ => format!("see {}", path.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_second_run_fails_while_the_first_holds_the_lock() {
+        let dir = TempDir::new().unwrap();
+        let database = dir.path().join("backup.db");
+        let database = database.to_str().unwrap();
+
+        let first = RunLock::acquire(database, false).unwrap();
+        match RunLock::acquire(database, false) {
+            Err(BackupError::AlreadyRunning { holder, .. }) => {
+                assert!(holder.contains(&std::process::id().to_string()) || cfg!(windows))
+            }
+            _ => panic!("expected the second run to be refused"),
+        }
+        drop(first);
+        assert!(RunLock::acquire(database, false).is_ok());
+        assert!(RunLock::acquire(":memory:", false).is_ok());
+    }
+
+    #[test]
+    fn test_waiting_run_starts_once_the_first_finishes() {
+        let dir = TempDir::new().unwrap();
+        let database = dir.path().join("backup.db");
+        let database = database.to_str().unwrap().to_string();
+
+        let first = RunLock::acquire(&database, false).unwrap();
+        let (locked, acquired) = mpsc::channel();
+        let waiting = thread::spawn(move || {
+            let lock = RunLock::acquire(&database, true).unwrap();
+            locked.send(()).unwrap();
+            drop(lock);
+        });
+        assert!(acquired.recv_timeout(Duration::from_millis(200)).is_err());
+        drop(first);
+        acquired.recv_timeout(Duration::from_secs(5)).unwrap();
+        waiting.join().unwrap();
+    }
+}
@@ -0,0 +1,68 @@
+use std::sync::{Condvar, Mutex};
+
+/// Lets at most a fixed number of threads past at once, blocking the others until a permit frees
+pub struct Semaphore {
+    available: Mutex<usize>,
+    released: Condvar,
+}
+
+/// A permit taken from a `Semaphore`, given back when dropped
+pub struct Permit<'a> {
+    semaphore: &'a Semaphore,
+}
+
+impl Semaphore {
+    pub fn new(permits: usize) -> Self {
+        Semaphore {
+            available: Mutex::new(permits),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Wait until a permit is free and take it
+    pub fn acquire(&self) -> Permit<'_> {
+        let mut available = self
+            .released
+            .wait_while(self.available.lock().unwrap(), |available| *available == 0)
+            .unwrap();
+        *available -= 1;
+        Permit { semaphore: self }
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        *self.semaphore.available.lock().unwrap() += 1;
+        self.semaphore.released.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_no_more_than_the_permits_hold_at_once() {
+        let semaphore = Semaphore::new(2);
+        let holding = AtomicUsize::new(0);
+        let most_holding = AtomicUsize::new(0);
+
+        thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    let _permit = semaphore.acquire();
+                    let now = holding.fetch_add(1, Ordering::SeqCst) + 1;
+                    most_holding.fetch_max(now, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(10));
+                    holding.fetch_sub(1, Ordering::SeqCst);
+                });
+            }
+        });
+
+        assert_eq!(most_holding.into_inner(), 2);
+        assert_eq!(*semaphore.available.lock().unwrap(), 2);
+    }
+}
@@ -0,0 +1,118 @@
+use crate::models::config::load_config;
+use crate::models::config_validator::check_config;
+use crate::models::validation_issue::{Severity, ValidationIssue};
+use crate::output::OutputFormat;
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::fmt::Write;
+
+/// What `--validate-only` found in the configuration
+#[derive(Debug, Serialize)]
+pub struct ValidationResult {
+    pub config_file: String,
+    /// Whether the configuration has no errors; warnings don't count
+    pub valid: bool,
+    pub issues: Vec<ValidationIssue>,
+}
+
+/// Check the configuration, and the profile if one is given, printing every error and warning;
+/// fails if there are errors
+pub fn run(config_file: &str, profile: Option<&str>, output: OutputFormat) -> Result<()> {
+    let result = validate(config_file, profile)?;
+    output.print(&result, || describe_result(&result))?;
+    if !result.valid {
+        bail!("The configuration in {} is invalid", config_file);
+    }
+    Ok(())
+}
+
+fn validate(config_file: &str, profile: Option<&str>) -> Result<ValidationResult> {
+    let config = load_config(config_file.to_string()).context("Failed to load configuration")?;
+    if let Some(profile) = profile {
+        config.with_profile(profile)?;
+    }
+    let issues = check_config(&config);
+    Ok(ValidationResult {
+        config_file: config_file.to_string(),
+        valid: !issues.iter().any(ValidationIssue::is_error),
+        issues,
+    })
+}
+
+fn describe_result(result: &ValidationResult) -> String {
+    let mut out = String::new();
+    for issue in &result.issues {
+        let severity = match issue.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let _ = writeln!(out, "{}: {}", severity, issue);
+    }
+    let errors = result
+        .issues
+        .iter()
+        .filter(|issue| issue.is_error())
+        .count();
+    let _ = match errors {
+        0 => writeln!(out, "Configuration is valid"),
+        errors => writeln!(out, "Configuration has {} error(s)", errors),
+    };
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_validation_result_lists_every_issue() {
+        let dir = TempDir::new().unwrap();
+        let config_file = dir.path().join("config.json");
+        fs::write(
+            &config_file,
+            serde_json::json!({
+                "database_file": dir.path().join("backup.db"),
+                "max_threads": 0,
+                "force_overwrite_backup": true,
+                "backup_sources": [{"parent_directory": dir.path()}],
+                "backup_destinations": [dir.path().join("backup")]
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let config_file = config_file.to_str().unwrap();
+
+        let result = validate(config_file, None).unwrap();
+        assert!(!result.valid);
+        let json: Value = serde_json::to_value(&result).unwrap();
+        assert_eq!(json["valid"], false);
+        let issues = json["issues"].as_array().unwrap();
+        assert!(issues
+            .iter()
+            .any(|issue| issue["field"] == "max_threads" && issue["severity"] == "error"));
+        assert!(issues.iter().any(|issue| issue["severity"] == "warning"));
+        assert!(describe_result(&result).ends_with(" error(s)\n"));
+        assert!(run(config_file, None, OutputFormat::Json).is_err());
+
+        let valid = dir.path().join("valid.json");
+        fs::write(
+            &valid,
+            serde_json::json!({
+                "database_file": dir.path().join("backup.db"),
+                "backup_sources": [{"parent_directory": dir.path()}],
+                "backup_destinations": [dir.path().join("backup")]
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let result = validate(valid.to_str().unwrap(), None).unwrap();
+        assert!(result.valid);
+        assert!(describe_result(&result).ends_with("Configuration is valid\n"));
+
+        assert!(validate(config_file, Some("missing")).is_err());
+        assert!(validate(&format!("{}.gone", config_file), None).is_err());
+    }
+}
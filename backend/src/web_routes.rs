@@ -1,8 +1,28 @@
-use crate::api_state::AppState;
+use crate::api_auth::{can_change, end_session, start_session, Admin, Viewer};
+use crate::models::api::ErrorResponse;
+use crate::models::user::User;
+use crate::service::auth::sign_in_required;
+use rocket::form::Form;
+use rocket::http::{CookieJar, Status};
 use rocket::response::Redirect;
-use rocket::State;
+use rocket::serde::json::Json;
+use rocket::{Either, Request};
 use rocket_dyn_templates::{context, Template};
 
+/// Render a page of the web UI for the signed-in user, if there is one
+fn page(name: &'static str, title: &str, active_tab: &str, user: Option<User>) -> Template {
+    let can_change = can_change(user.as_ref());
+    Template::render(
+        name,
+        context! {
+            title,
+            active_tab,
+            user,
+            can_change,
+        },
+    )
+}
+
 /// GET / - Redirect to dashboard
 #[get("/")]
 pub fn index() -> Redirect {
@@ -11,48 +31,84 @@ pub fn index() -> Redirect {
 
 /// GET /dashboard - Dashboard page
 #[get("/dashboard")]
-pub fn dashboard(_state: &State<AppState>) -> Template {
-    Template::render(
-        "dashboard",
-        context! {
-            title: "Dashboard",
-            active_tab: "dashboard",
-        },
-    )
+pub fn dashboard(viewer: Viewer) -> Template {
+    page("dashboard", "Dashboard", "dashboard", viewer.0)
 }
 
 /// GET /configuration - Configuration page
 #[get("/configuration")]
-pub fn configuration(_state: &State<AppState>) -> Template {
-    Template::render(
-        "configuration",
-        context! {
-            title: "Configuration",
-            active_tab: "configuration",
-        },
-    )
+pub fn configuration(admin: Admin) -> Template {
+    page("configuration", "Configuration", "configuration", admin.0)
 }
 
 /// GET /logs - Logs page
 #[get("/logs")]
-pub fn logs(_state: &State<AppState>) -> Template {
-    Template::render(
-        "logs",
-        context! {
-            title: "Logs",
-            active_tab: "logs",
-        },
-    )
+pub fn logs(viewer: Viewer) -> Template {
+    page("logs", "Logs", "logs", viewer.0)
+}
+
+/// GET /files - Search catalogued source files and check their backups
+#[get("/files")]
+pub fn files(viewer: Viewer) -> Template {
+    page("files", "Files", "files", viewer.0)
 }
 
 /// GET /help - Help page
 #[get("/help")]
-pub fn help() -> Template {
-    Template::render(
-        "help",
-        context! {
-            title: "Help",
-            active_tab: "help",
-        },
-    )
+pub fn help(viewer: Viewer) -> Template {
+    page("help", "Help", "help", viewer.0)
+}
+
+#[derive(FromForm)]
+pub struct LoginForm {
+    name: String,
+    password: String,
+}
+
+/// GET /login - Sign-in page, skipped while no users are set up
+#[get("/login")]
+pub fn login_page() -> Either<Template, Redirect> {
+    if !sign_in_required() {
+        return Either::Right(Redirect::to("/dashboard"));
+    }
+    Either::Left(Template::render(
+        "login",
+        context! { title: "Sign In", name: "", error: "" },
+    ))
+}
+
+/// POST /login - Sign in from the login form
+#[post("/login", data = "<form>")]
+pub fn login(form: Form<LoginForm>, cookies: &CookieJar<'_>) -> Either<Template, Redirect> {
+    let error = match start_session(cookies, &form.name, &form.password) {
+        Ok(Some(_)) => return Either::Right(Redirect::to("/dashboard")),
+        Ok(None) => "Wrong user name or password".to_string(),
+        Err(e) => format!("Failed to sign in: {}", e),
+    };
+    Either::Left(Template::render(
+        "login",
+        context! { title: "Sign In", name: &form.name, error },
+    ))
+}
+
+/// POST /logout - Sign out and go back to the sign-in page
+#[post("/logout")]
+pub fn logout(cookies: &CookieJar<'_>) -> Redirect {
+    end_session(cookies);
+    Redirect::to("/login")
+}
+
+/// Send people who aren't signed in to the sign-in page, and API clients a JSON error
+#[catch(401)]
+pub fn unauthorized(request: &Request<'_>) -> Either<Redirect, (Status, Json<ErrorResponse>)> {
+    if !request.uri().path().starts_with("/api/") {
+        return Either::Left(Redirect::to("/login"));
+    }
+    Either::Right((
+        Status::Unauthorized,
+        Json(ErrorResponse {
+            error: "Sign in first, with POST /api/login".to_string(),
+            details: None,
+        }),
+    ))
 }